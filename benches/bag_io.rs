@@ -0,0 +1,204 @@
+//! Throughput of the read, write and copy paths
+//!
+//! Every benchmark moves the same bag of [`MESSAGE_COUNT`] IMU-sized messages and
+//! is reported twice: under `.../messages` in messages per second and under
+//! `.../bytes` in bytes per second of payload.
+//!
+//! Usage:
+//! ```bash
+//! cargo bench --bench bag_io
+//! # Compare the results with the Python rosbags package
+//! python3 scripts/compare_python_rosbags.py
+//! ```
+
+use criterion::measurement::WallTime;
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkGroup, Criterion, Throughput,
+};
+use rosbags_rs::{CompressionFormat, CompressionMode, Reader, StoragePlugin, Writer};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Messages in every benchmarked bag
+const MESSAGE_COUNT: u64 = 10_000;
+/// Payload size of a `sensor_msgs/msg/Imu` with a short frame id
+const MESSAGE_SIZE: usize = 332;
+/// Messages buffered per flush in the batched write benchmarks
+const BATCH_SIZE: usize = 1_000;
+
+const STORAGES: [(&str, StoragePlugin); 2] = [
+    ("sqlite3", StoragePlugin::Sqlite3),
+    ("mcap", StoragePlugin::Mcap),
+];
+
+/// Payload of message `index`, a valid little-endian CDR header and varying contents
+fn payload(index: u64) -> Vec<u8> {
+    let mut data = vec![0u8; MESSAGE_SIZE];
+    data[1] = 1;
+    data[4..12].copy_from_slice(&index.to_le_bytes());
+    data
+}
+
+/// How a benchmarked writer is configured
+#[derive(Clone, Copy)]
+enum WriteMode {
+    /// Every message is flushed to storage on its own
+    Unbatched,
+    /// Messages are flushed in batches of [`BATCH_SIZE`]
+    Batched,
+    /// Batched, with every message compressed with zstd
+    Compressed,
+}
+
+/// Write the benchmark bag to `path`
+fn write_bag(path: &Path, storage: StoragePlugin, mode: WriteMode, payloads: &[Vec<u8>]) {
+    let mut builder = Writer::builder(path).storage(storage);
+    builder = match mode {
+        WriteMode::Unbatched => builder.buffer(10, 1),
+        WriteMode::Batched => builder.buffer(10, BATCH_SIZE),
+        WriteMode::Compressed => builder
+            .buffer(10, BATCH_SIZE)
+            .compression(CompressionMode::Message, CompressionFormat::Zstd),
+    };
+    let mut writer = builder.open().unwrap();
+    let connection = writer
+        .add_connection(
+            "/imu".to_string(),
+            "sensor_msgs/msg/Imu".to_string(),
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    for (index, data) in payloads.iter().enumerate() {
+        writer
+            .write(&connection, index as u64 * 1_000_000, data)
+            .unwrap();
+    }
+    writer.close().unwrap();
+}
+
+/// Copy every message of `source` to a new bag at `path` without decoding
+fn copy_bag(source: &Path, path: &Path, storage: StoragePlugin) {
+    let mut reader = Reader::new(source).unwrap();
+    reader.open().unwrap();
+    let mut writer = Writer::builder(path)
+        .storage(storage)
+        .buffer(10, BATCH_SIZE)
+        .open()
+        .unwrap();
+    let mut connections = Vec::new();
+    for connection in reader.connections() {
+        connections.push((
+            connection.id,
+            writer.add_connection_from(connection).unwrap(),
+        ));
+    }
+    for message in reader.raw_messages().unwrap() {
+        let message = message.unwrap();
+        let (_, connection) = connections
+            .iter()
+            .find(|(id, _)| *id == message.connection.id)
+            .unwrap();
+        writer
+            .copy_raw_message_from_reader(connection, message.timestamp, &message.raw_data)
+            .unwrap();
+    }
+    writer.close().unwrap();
+}
+
+/// Count the messages of `path`, reading every payload
+fn read_bag(path: &Path) -> u64 {
+    let mut reader = Reader::new(path).unwrap();
+    reader.open().unwrap();
+    let mut bytes = 0;
+    let mut count = 0;
+    for message in reader.raw_messages().unwrap() {
+        bytes += message.unwrap().raw_data.len();
+        count += 1;
+    }
+    assert_eq!(bytes, MESSAGE_COUNT as usize * MESSAGE_SIZE);
+    count
+}
+
+/// Register `routine` under `name/messages` and `name/bytes`
+fn bench_throughput(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    mut routine: impl FnMut(&mut criterion::Bencher),
+) {
+    group.throughput(Throughput::Elements(MESSAGE_COUNT));
+    group.bench_function(format!("{name}/messages"), &mut routine);
+    group.throughput(Throughput::Bytes(MESSAGE_COUNT * MESSAGE_SIZE as u64));
+    group.bench_function(format!("{name}/bytes"), &mut routine);
+}
+
+/// A path for a fresh output bag, removing what the previous iteration wrote
+fn fresh_output(dir: &Path) -> PathBuf {
+    let path = dir.join("output");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    path
+}
+
+fn bag_io(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let payloads: Vec<_> = (0..MESSAGE_COUNT).map(payload).collect();
+    let sources: Vec<_> = STORAGES
+        .iter()
+        .map(|&(name, storage)| {
+            let path = dir.path().join(format!("source_{name}"));
+            write_bag(&path, storage, WriteMode::Batched, &payloads);
+            (name, storage, path)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("read");
+    for (name, _, path) in &sources {
+        bench_throughput(&mut group, name, |b| b.iter(|| read_bag(path)));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("write");
+    for (name, storage) in STORAGES {
+        for (mode_name, mode) in [
+            ("unbatched", WriteMode::Unbatched),
+            ("batched", WriteMode::Batched),
+            ("zstd", WriteMode::Compressed),
+        ] {
+            bench_throughput(&mut group, &format!("{name}/{mode_name}"), |b| {
+                b.iter_batched(
+                    || fresh_output(dir.path()),
+                    |path| write_bag(&path, storage, mode, &payloads),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("copy");
+    for (source_name, _, source) in &sources {
+        for (name, storage) in STORAGES {
+            bench_throughput(&mut group, &format!("{source_name}_to_{name}"), |b| {
+                b.iter_batched(
+                    || fresh_output(dir.path()),
+                    |path| copy_bag(source, &path, storage),
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(5));
+    targets = bag_io
+}
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+//! Measure the sustained rate of writing small messages to SQLite3 bags
+//!
+//! Writes IMU-sized (~330 byte) messages as fast as possible with several
+//! [`SqliteWriteOptions`] and prints the achieved message rate of each.
+//!
+//! Usage:
+//! ```bash
+//! cargo run --release --example sqlite_write_rate -- [message_count]
+//! ```
+
+use rosbags_rs::storage::sqlite::SqliteWriteOptions;
+use rosbags_rs::Writer;
+use std::time::Instant;
+
+/// Payload size of a `sensor_msgs/msg/Imu` with a short frame id
+const MESSAGE_SIZE: usize = 332;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: u64 = match std::env::args().nth(1) {
+        Some(count) => count.parse()?,
+        None => 100_000,
+    };
+
+    let configurations = [
+        (
+            "default (commit every batch)",
+            SqliteWriteOptions::default(),
+        ),
+        ("resilient()", SqliteWriteOptions::resilient()),
+        ("high_rate()", SqliteWriteOptions::high_rate()),
+    ];
+
+    let temp_dir = tempfile::tempdir()?;
+    let mut payload = vec![0u8; MESSAGE_SIZE];
+    payload[1] = 1;
+
+    println!("Writing {count} messages of {MESSAGE_SIZE} bytes");
+    for (index, (name, options)) in configurations.into_iter().enumerate() {
+        let mut writer = Writer::new(temp_dir.path().join(format!("bag_{index}")), None, None)?;
+        writer.set_sqlite_options(options)?;
+        writer.open()?;
+        let connection = writer.add_connection(
+            "/imu".to_string(),
+            "sensor_msgs/msg/Imu".to_string(),
+            None,
+            None,
+            None,
+            None,
+        )?;
+
+        let start = Instant::now();
+        for i in 0..count {
+            writer.write(&connection, i * 1_000_000, &payload)?;
+        }
+        writer.close()?;
+        let elapsed = start.elapsed().as_secs_f64();
+
+        println!(
+            "{name:<42} {:>10.0} msg/s ({elapsed:.2} s)",
+            count as f64 / elapsed
+        );
+    }
+
+    Ok(())
+}
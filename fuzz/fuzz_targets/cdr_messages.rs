@@ -0,0 +1,38 @@
+//! Feed arbitrary bytes to every built-in `FromCdr` message parser
+//!
+//! The first input byte picks the message type, the rest is the CDR payload.
+//! Parsers may reject the input but must never panic or allocate unbounded memory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rosbags_rs::messages::{deserialize_message, ImageView, PointCloud2View};
+
+const MESSAGE_TYPES: &[&str] = &[
+    "sensor_msgs/msg/Imu",
+    "geometry_msgs/msg/TransformStamped",
+    "tf2_msgs/msg/TFMessage",
+    "geometry_msgs/msg/PoseWithCovarianceStamped",
+    "geometry_msgs/msg/PointStamped",
+    "sensor_msgs/msg/NavSatFix",
+    "nav_msgs/msg/Odometry",
+    "action_msgs/msg/GoalStatusArray",
+    "rosgraph_msgs/msg/Clock",
+];
+
+fuzz_target!(|input: &[u8]| {
+    let Some((&selector, data)) = input.split_first() else {
+        return;
+    };
+    match selector as usize % (MESSAGE_TYPES.len() + 2) {
+        index if index < MESSAGE_TYPES.len() => {
+            let _ = deserialize_message(data, MESSAGE_TYPES[index]);
+        }
+        index if index == MESSAGE_TYPES.len() => {
+            let _ = ImageView::from_cdr_bytes(data);
+        }
+        _ => {
+            let _ = PointCloud2View::from_cdr_bytes(data);
+        }
+    }
+});
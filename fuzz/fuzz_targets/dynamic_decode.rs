@@ -0,0 +1,35 @@
+//! Parse an arbitrary `.msg` definition and decode an arbitrary payload with it
+//!
+//! The input is split at the first NUL byte: the text before it is the message
+//! definition of `fuzz/msg/Root`, the bytes after it are the CDR payload. Both the
+//! definition parser and the dynamic decoder must fail cleanly on bad input,
+//! including self-referencing definitions and huge sequence lengths.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rosbags_rs::cdr::CdrDeserializer;
+use rosbags_rs::dynamic::{decode_message, skip_message};
+use rosbags_rs::schema::MessageSchemas;
+
+const ROOT: &str = "fuzz/msg/Root";
+
+fuzz_target!(|input: &[u8]| {
+    let Some(split) = input.iter().position(|&byte| byte == 0) else {
+        return;
+    };
+    let Ok(definition) = std::str::from_utf8(&input[..split]) else {
+        return;
+    };
+    let data = &input[split + 1..];
+    let Ok(schemas) = MessageSchemas::parse_msg(ROOT, definition) else {
+        return;
+    };
+
+    if let Ok(mut deserializer) = CdrDeserializer::new(data) {
+        let _ = decode_message(&mut deserializer, &schemas, ROOT);
+    }
+    if let Ok(mut deserializer) = CdrDeserializer::new(data) {
+        let _ = skip_message(&mut deserializer, &schemas, ROOT);
+    }
+});
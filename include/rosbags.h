@@ -0,0 +1,149 @@
+#ifndef ROSBAGS_H
+#define ROSBAGS_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Outcome of a C API call
+typedef enum RosbagsStatus {
+  // The call succeeded
+  ROSBAGS_STATUS_OK = 0,
+  // Iteration has reached the end of the bag
+  ROSBAGS_STATUS_END = 1,
+  // The call failed; see `rosbags_last_error`
+  ROSBAGS_STATUS_ERROR = -1,
+} RosbagsStatus;
+
+// Open bag, opaque to C
+typedef struct RosbagsReader RosbagsReader;
+
+// Connection (topic and type) of a bag
+typedef struct RosbagsConnection {
+  // Connection id, as in `RosbagsMessage::connection_id`
+  uint32_t id;
+  // Topic name
+  const char *topic;
+  // Message type, e.g. `sensor_msgs/msg/Image`
+  const char *message_type;
+  // Serialization format, usually `cdr`
+  const char *serialization_format;
+  // Stored message definition, empty if the bag has none
+  const char *message_definition;
+  // Number of messages
+  uint64_t message_count;
+} RosbagsConnection;
+
+// Message read by `rosbags_reader_next_message`
+typedef struct RosbagsMessage {
+  // Id of the connection of the message
+  uint32_t connection_id;
+  // Topic name
+  const char *topic;
+  // Message type
+  const char *message_type;
+  // Log (receive) time in nanoseconds since the epoch
+  uint64_t timestamp;
+  // Publish time in nanoseconds since the epoch, `timestamp` if not recorded
+  uint64_t publish_time;
+  // Sequence number of the message on its channel (0 if not recorded)
+  uint32_t sequence;
+  // Serialized (CDR) payload
+  const uint8_t *data;
+  // Length of the payload in bytes
+  size_t data_len;
+} RosbagsMessage;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Version of the library, e.g. `0.3.4`
+const char *rosbags_version(void);
+
+// Description of the last failed call on this thread, `NULL` if none failed
+//
+// The string stays valid until the next failing call on the thread.
+const char *rosbags_last_error(void);
+
+// Open the bag directory at `path`, `NULL` on failure
+//
+// # Safety
+// `path` must be `NULL` or a NUL-terminated UTF-8 string.
+RosbagsReader *rosbags_reader_open(const char *path);
+
+// Close a reader and release everything handed out for it; `NULL` is ignored
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, which is
+// invalid afterwards.
+void rosbags_reader_close(RosbagsReader *reader);
+
+// Number of messages in the bag, 0 for `NULL`
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+uint64_t rosbags_reader_message_count(RosbagsReader *reader);
+
+// Time of the first message in nanoseconds since the epoch, 0 for `NULL`
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+uint64_t rosbags_reader_start_time(RosbagsReader *reader);
+
+// Time of the last message in nanoseconds since the epoch, 0 for `NULL`
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+uint64_t rosbags_reader_end_time(RosbagsReader *reader);
+
+// Number of connections, 0 for `NULL`
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+size_t rosbags_reader_connection_count(RosbagsReader *reader);
+
+// Fill `connection` with connection `index`, from 0 to
+// `rosbags_reader_connection_count() - 1`
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and
+// `connection` `NULL` or valid for writes.
+RosbagsStatus rosbags_reader_connection(RosbagsReader *reader,
+                                        size_t index,
+                                        RosbagsConnection *connection);
+
+// Only iterate over the `count` topics in `topics`, or over all topics if `count`
+// is 0, and restart iteration
+//
+// Fails if a topic is not in the bag.
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and `topics`
+// point to `count` NUL-terminated UTF-8 strings.
+RosbagsStatus rosbags_reader_set_topics(RosbagsReader *reader,
+                                        const char *const *topics,
+                                        size_t count);
+
+// Restart iteration from the first message
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+RosbagsStatus rosbags_reader_rewind(RosbagsReader *reader);
+
+// Fill `message` with the next message in timestamp order
+//
+// Returns `ROSBAGS_STATUS_END` after the last message. The first call reads the
+// messages of the bag.
+//
+// # Safety
+// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and `message`
+// `NULL` or valid for writes.
+RosbagsStatus rosbags_reader_next_message(RosbagsReader *reader, RosbagsMessage *message);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ROSBAGS_H */
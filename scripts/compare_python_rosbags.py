@@ -0,0 +1,183 @@
+#!/usr/bin/env python3
+"""
+Compare the rosbags-rs benchmark results with the Python rosbags package.
+
+Runs the read, write and copy workloads of `benches/bag_io.rs` with the Python
+`rosbags` package on bags of the same shape and prints both sets of throughputs
+side by side. The Rust numbers are taken from criterion's results, so run the
+benchmarks first.
+
+Requirements:
+- rosbags package: pip install rosbags
+
+Usage:
+    cargo bench --bench bag_io
+    python scripts/compare_python_rosbags.py [--criterion-dir target/criterion] [--repeat 3]
+"""
+
+import argparse
+import json
+import shutil
+import sys
+import tempfile
+import time
+from pathlib import Path
+from typing import Callable, Dict, List, Optional, Tuple
+
+try:
+    from rosbags.rosbag2 import Reader, Writer
+    from rosbags.rosbag2.enums import StoragePlugin
+    from rosbags.typesys import Stores, get_typestore
+except ImportError:
+    print("Error: rosbags package not found. Install with: pip install rosbags")
+    sys.exit(1)
+
+# Must match the constants of benches/bag_io.rs
+MESSAGE_COUNT = 10_000
+MESSAGE_SIZE = 332
+TOPIC = "/imu"
+MESSAGE_TYPE = "sensor_msgs/msg/Imu"
+
+STORAGES = {"sqlite3": StoragePlugin.SQLITE3, "mcap": StoragePlugin.MCAP}
+TYPESTORE = get_typestore(Stores.ROS2_HUMBLE)
+
+
+def payload(index: int) -> bytes:
+    """Payload of message `index`, identical to the Rust benchmark."""
+    data = bytearray(MESSAGE_SIZE)
+    data[1] = 1
+    data[4:12] = index.to_bytes(8, "little")
+    return bytes(data)
+
+
+PAYLOADS = [payload(index) for index in range(MESSAGE_COUNT)]
+
+
+def write_bag(path: Path, storage: str, compressed: bool = False) -> None:
+    """Write the benchmark bag to `path`."""
+    writer = Writer(path, version=8, storage_plugin=STORAGES[storage])
+    if compressed:
+        writer.set_compression(Writer.CompressionMode.MESSAGE, Writer.CompressionFormat.ZSTD)
+    with writer:
+        connection = writer.add_connection(TOPIC, MESSAGE_TYPE, typestore=TYPESTORE)
+        for index, data in enumerate(PAYLOADS):
+            writer.write(connection, index * 1_000_000, data)
+
+
+def read_bag(path: Path) -> None:
+    """Read every payload of the bag at `path`."""
+    total = 0
+    with Reader(path) as reader:
+        for _, _, data in reader.messages():
+            total += len(data)
+    assert total == MESSAGE_COUNT * MESSAGE_SIZE
+
+
+def copy_bag(source: Path, path: Path, storage: str) -> None:
+    """Copy every message of `source` to a new bag at `path` without decoding."""
+    with Reader(source) as reader:
+        writer = Writer(path, version=8, storage_plugin=STORAGES[storage])
+        with writer:
+            connections = {
+                connection.id: writer.add_connection(
+                    connection.topic, connection.msgtype, typestore=TYPESTORE
+                )
+                for connection in reader.connections
+            }
+            for connection, timestamp, data in reader.messages():
+                writer.write(connections[connection.id], timestamp, data)
+
+
+def best_time(run: Callable[[Path], None], workdir: Path, repeat: int) -> float:
+    """Fastest of `repeat` runs in seconds, each writing to a fresh output path."""
+    best = float("inf")
+    for _ in range(repeat):
+        output = workdir / "output"
+        if output.exists():
+            shutil.rmtree(output)
+        start = time.perf_counter()
+        run(output)
+        best = min(best, time.perf_counter() - start)
+    return best
+
+
+def rust_time(criterion_dir: Path, group: str, name: str) -> Optional[float]:
+    """Mean time in seconds criterion measured for `group/name/messages`."""
+    # criterion turns the slashes of benchmark ids into underscores
+    directory = f"{name}/messages".replace("/", "_")
+    estimates = criterion_dir / group / directory / "new" / "estimates.json"
+    if not estimates.exists():
+        return None
+    with open(estimates) as f:
+        return json.load(f)["mean"]["point_estimate"] / 1e9
+
+
+def python_workloads(workdir: Path) -> List[Tuple[str, str, Callable[[Path], None]]]:
+    """Workloads as (criterion group, benchmark name, run) in benchmark order."""
+    sources: Dict[str, Path] = {}
+    for storage in STORAGES:
+        sources[storage] = workdir / f"source_{storage}"
+        write_bag(sources[storage], storage)
+
+    workloads = []
+    for storage, source in sources.items():
+        workloads.append(("read", storage, lambda _, source=source: read_bag(source)))
+    for storage in STORAGES:
+        # Python rosbags writes every message straight to storage, with no batching
+        workloads.append(
+            ("write", f"{storage}/unbatched", lambda out, s=storage: write_bag(out, s))
+        )
+        workloads.append(
+            ("write", f"{storage}/zstd", lambda out, s=storage: write_bag(out, s, True))
+        )
+    for source_name, source in sources.items():
+        for storage in STORAGES:
+            workloads.append(
+                (
+                    "copy",
+                    f"{source_name}_to_{storage}",
+                    lambda out, src=source, s=storage: copy_bag(src, out, s),
+                )
+            )
+    return workloads
+
+
+def rate(seconds: Optional[float]) -> str:
+    """Format a run time as messages per second and MB/s."""
+    if seconds is None:
+        return f"{'-':>24}"
+    messages = MESSAGE_COUNT / seconds
+    megabytes = MESSAGE_COUNT * MESSAGE_SIZE / seconds / 1e6
+    return f"{messages:>12,.0f} msg/s {megabytes:>6.1f} MB/s"
+
+
+def main() -> None:
+    parser = argparse.ArgumentParser(description=__doc__.strip().splitlines()[0])
+    parser.add_argument(
+        "--criterion-dir",
+        type=Path,
+        default=Path(__file__).resolve().parent.parent / "target" / "criterion",
+        help="Directory with criterion results (default: target/criterion)",
+    )
+    parser.add_argument(
+        "--repeat", type=int, default=3, help="Runs per Python workload, the fastest counts"
+    )
+    args = parser.parse_args()
+
+    if not args.criterion_dir.exists():
+        print(f"No criterion results in {args.criterion_dir}, run `cargo bench --bench bag_io`")
+        sys.exit(1)
+
+    print(f"{MESSAGE_COUNT} messages of {MESSAGE_SIZE} bytes\n")
+    print(f"{'Benchmark':<28} {'rosbags-rs':>24} {'Python rosbags':>24} {'Speedup':>8}")
+    with tempfile.TemporaryDirectory() as tmp:
+        workdir = Path(tmp)
+        for group, name, run in python_workloads(workdir):
+            python = best_time(run, workdir, args.repeat)
+            rust = rust_time(args.criterion_dir, group, name)
+            speedup = f"{python / rust:>7.1f}x" if rust else f"{'-':>8}"
+            print(f"{group + '/' + name:<28} {rate(rust)} {rate(python)} {speedup}")
+
+
+if __name__ == "__main__":
+    main()
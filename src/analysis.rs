@@ -0,0 +1,455 @@
+//! Message rate and timing analysis
+//!
+//! [`Reader::histogram`] counts the messages of every topic in consecutive time
+//! windows. The resulting [`Histogram`] is a time series per topic from which rates,
+//! dropouts and rate anomalies are read without exporting the bag first.
+//! [`Reader::stamp_skew`] compares the `header.stamp` of messages with the time they
+//! were recorded, to debug the time synchronization of sensors.
+
+use crate::error::{ReaderError, Result};
+use crate::export::{has_header, header_stamp};
+use crate::reader::Reader;
+use crate::types::Connection;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Message counts per topic in consecutive time windows of equal length
+///
+/// Windows are half-open and start at the first message read, so window `i` covers
+/// `start + i * window <= timestamp < start + (i + 1) * window`. Every topic has a
+/// count for every window, including topics without messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// First timestamp of the first window in nanoseconds
+    start: u64,
+    /// Length of a window in nanoseconds
+    window: u64,
+    /// Number of windows
+    len: usize,
+    /// Message counts per window, by topic
+    counts: BTreeMap<String, Vec<u64>>,
+}
+
+impl Histogram {
+    /// Count the messages `reader` yields per topic in windows of `window`
+    ///
+    /// The topic allowlist and time range of the reader's options apply. Bags
+    /// without messages have no windows.
+    pub(crate) fn compute(reader: &Reader, window: Duration) -> Result<Self> {
+        let window = u64::try_from(window.as_nanos()).unwrap_or(u64::MAX);
+        if window == 0 {
+            return Err(ReaderError::generic(
+                "Histogram window must be longer than zero",
+            ));
+        }
+
+        let mut counts: BTreeMap<String, Vec<u64>> = reader
+            .connections()
+            .iter()
+            .map(|connection| (connection.topic.clone(), Vec::new()))
+            .collect();
+        // Messages come in timestamp order, so the first one starts the first window
+        let mut start = None;
+        let mut len = 0;
+        for message in reader.raw_messages_borrowed()? {
+            let message = message?;
+            let start = *start.get_or_insert(message.timestamp);
+            let index = usize::try_from((message.timestamp - start) / window)
+                .map_err(|_| ReaderError::generic("Too many histogram windows"))?;
+            len = len.max(index + 1);
+            let topic_counts = counts.entry(message.connection.topic.clone()).or_default();
+            if topic_counts.len() <= index {
+                topic_counts.resize(index + 1, 0);
+            }
+            topic_counts[index] += 1;
+        }
+        for topic_counts in counts.values_mut() {
+            topic_counts.resize(len, 0);
+        }
+
+        Ok(Self {
+            start: start.unwrap_or_default(),
+            window,
+            len,
+            counts,
+        })
+    }
+
+    /// Get the first timestamp of the first window in nanoseconds
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Get the length of a window in nanoseconds
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Get the number of windows
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no windows, i.e. no messages were read
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the time span of window `index` in nanoseconds (end exclusive)
+    pub fn window_range(&self, index: usize) -> Range<u64> {
+        let start = self
+            .start
+            .saturating_add(self.window.saturating_mul(index as u64));
+        start..start.saturating_add(self.window)
+    }
+
+    /// Iterate over the topics in name order
+    pub fn topics(&self) -> impl Iterator<Item = &str> {
+        self.counts.keys().map(String::as_str)
+    }
+
+    /// Get the message count of `topic` in every window, `None` for unknown topics
+    pub fn counts(&self, topic: &str) -> Option<&[u64]> {
+        self.counts.get(topic).map(Vec::as_slice)
+    }
+
+    /// Get the message rate of `topic` in every window in Hz, `None` for unknown topics
+    pub fn rates(&self, topic: &str) -> Option<Vec<f64>> {
+        let seconds = self.window as f64 / 1e9;
+        let counts = self.counts(topic)?;
+        Some(counts.iter().map(|&count| count as f64 / seconds).collect())
+    }
+
+    /// Get the runs of consecutive windows without messages of `topic` between its
+    /// first and last message, as ranges of window indexes
+    ///
+    /// Windows before the topic's first and after its last message are not dropouts.
+    pub fn dropouts(&self, topic: &str) -> Vec<Range<usize>> {
+        let Some(counts) = self.counts(topic) else {
+            return Vec::new();
+        };
+        let Some(first) = counts.iter().position(|&count| count > 0) else {
+            return Vec::new();
+        };
+        let last = counts.iter().rposition(|&count| count > 0).unwrap_or(first);
+
+        let mut dropouts = Vec::new();
+        let mut run_start = None;
+        for (index, &count) in counts.iter().enumerate().take(last + 1).skip(first) {
+            match (count, run_start) {
+                (0, None) => run_start = Some(index),
+                (0, Some(_)) => {}
+                (_, Some(run)) => {
+                    dropouts.push(run..index);
+                    run_start = None;
+                }
+                (_, None) => {}
+            }
+        }
+        dropouts
+    }
+
+    /// Get the windows in which the count of `topic` differs from its median count
+    /// by more than `tolerance` (a fraction, e.g. `0.2` for 20%)
+    ///
+    /// Only windows between the topic's first and last message are checked; the
+    /// first and last of them are skipped as they usually cover part of the window.
+    pub fn anomalies(&self, topic: &str, tolerance: f64) -> Vec<usize> {
+        let Some(counts) = self.counts(topic) else {
+            return Vec::new();
+        };
+        let (Some(first), Some(last)) = (
+            counts.iter().position(|&count| count > 0),
+            counts.iter().rposition(|&count| count > 0),
+        ) else {
+            return Vec::new();
+        };
+        if last < first + 2 {
+            return Vec::new();
+        }
+
+        let inner = first + 1..last;
+        let mut sorted = counts[inner.clone()].to_vec();
+        sorted.sort_unstable();
+        let median = sorted[sorted.len() / 2] as f64;
+        inner
+            .filter(|&index| (counts[index] as f64 - median).abs() > median * tolerance)
+            .collect()
+    }
+}
+
+/// Offset of the bag timestamps of a topic from the `header.stamp` of its messages
+///
+/// The offset of a message is its bag timestamp minus its header stamp, in
+/// nanoseconds: the latency from stamping to recording, negative if the stamp lies
+/// after the bag time. Drift is the rate at which the offset changes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StampSkew {
+    /// Topic name
+    pub topic: String,
+    /// Number of messages with a header stamp
+    pub count: u64,
+    /// Number of messages with a zero (unset) or unreadable header stamp, left out
+    pub unstamped: u64,
+    /// Smallest offset in nanoseconds
+    pub min_offset: i64,
+    /// Largest offset in nanoseconds
+    pub max_offset: i64,
+    /// Mean offset in nanoseconds
+    pub mean_offset: f64,
+    /// Standard deviation of the offset in nanoseconds
+    pub std_dev: f64,
+    /// Change of the offset per nanosecond of bag time, from a least squares line
+    /// through the offsets (multiply by 1e6 for parts per million)
+    pub drift: f64,
+}
+
+impl StampSkew {
+    /// Compare bag time and header stamps of the messages `reader` yields, by topic
+    ///
+    /// Only connections whose messages start with a `std_msgs/msg/Header` are read.
+    pub(crate) fn compute(reader: &Reader) -> Result<Vec<Self>> {
+        let connections: Vec<Connection> = reader
+            .connections()
+            .iter()
+            .filter(|connection| has_header(connection))
+            .cloned()
+            .collect();
+        if connections.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut fits: BTreeMap<String, OffsetFit> = BTreeMap::new();
+        for message in reader.raw_messages_filtered(Some(&connections), None, None)? {
+            let message = message?;
+            let fit = fits.entry(message.connection.topic.clone()).or_default();
+            match header_stamp(&message.raw_data).filter(|&stamp| stamp != 0) {
+                Some(stamp) => fit.push(message.timestamp, stamp),
+                None => fit.unstamped += 1,
+            }
+        }
+        Ok(fits
+            .into_iter()
+            .map(|(topic, fit)| fit.finish(topic))
+            .collect())
+    }
+}
+
+/// Running sums of the offsets of one topic for their statistics and line fit
+///
+/// Times and offsets are taken relative to those of the first message so that the
+/// sums keep their precision in `f64`.
+#[derive(Debug, Default)]
+struct OffsetFit {
+    /// Bag time and offset of the first message
+    origin: Option<(u64, i64)>,
+    count: u64,
+    unstamped: u64,
+    min: i64,
+    max: i64,
+    /// Sums of the relative time `x`, the relative offset `y` and their products
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_xy: f64,
+    sum_yy: f64,
+}
+
+impl OffsetFit {
+    fn push(&mut self, timestamp: u64, stamp: u64) {
+        let offset = i64::try_from(i128::from(timestamp) - i128::from(stamp)).unwrap_or(
+            if timestamp > stamp {
+                i64::MAX
+            } else {
+                i64::MIN
+            },
+        );
+        let (time0, offset0) = *self.origin.get_or_insert((timestamp, offset));
+        if self.count == 0 {
+            (self.min, self.max) = (offset, offset);
+        }
+        self.count += 1;
+        self.min = self.min.min(offset);
+        self.max = self.max.max(offset);
+
+        let x = (i128::from(timestamp) - i128::from(time0)) as f64;
+        let y = (i128::from(offset) - i128::from(offset0)) as f64;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_xy += x * y;
+        self.sum_yy += y * y;
+    }
+
+    fn finish(self, topic: String) -> StampSkew {
+        let mut skew = StampSkew {
+            topic,
+            count: self.count,
+            unstamped: self.unstamped,
+            min_offset: self.min,
+            max_offset: self.max,
+            ..StampSkew::default()
+        };
+        let Some((_, offset0)) = self.origin else {
+            return skew;
+        };
+        let n = self.count as f64;
+        let mean_y = self.sum_y / n;
+        skew.mean_offset = offset0 as f64 + mean_y;
+        skew.std_dev = (self.sum_yy / n - mean_y * mean_y).max(0.0).sqrt();
+        let variance_x = self.sum_xx / n - (self.sum_x / n).powi(2);
+        if variance_x > 0.0 {
+            let covariance = self.sum_xy / n - self.sum_x / n * mean_y;
+            skew.drift = covariance / variance_x;
+        }
+        skew
+    }
+}
+
+#[cfg(all(test, feature = "default"))]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_histogram_counts_every_message() {
+        for bag in [
+            "tests/test_bags/test_bag_sqlite3",
+            "tests/test_bags/test_bag_mcap",
+        ] {
+            let mut reader = Reader::new(bag).unwrap();
+            reader.open().unwrap();
+            let histogram = reader.histogram(Duration::from_secs(5)).unwrap();
+            // 18.7 s of messages in 5 s windows
+            assert_eq!(histogram.len(), 4);
+            assert_eq!(histogram.start(), reader.start_time());
+            assert_eq!(
+                histogram.window_range(1).start,
+                reader.start_time() + 5_000_000_000
+            );
+
+            for topic in reader.topics() {
+                let counts = histogram.counts(&topic.name).unwrap();
+                assert_eq!(counts.len(), 4);
+                assert_eq!(counts.iter().sum::<u64>(), topic.message_count);
+            }
+            assert_eq!(histogram.topics().count(), reader.topics().len());
+            assert!(histogram.counts("/missing").is_none());
+            assert!(reader.histogram(Duration::ZERO).is_err());
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_histogram_dropouts_and_anomalies() {
+        use crate::Writer;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bag_path = dir.path().join("rates");
+        let mut writer = Writer::builder(&bag_path).open().unwrap();
+        let connection = writer
+            .add_connection(
+                "/imu".to_string(),
+                "std_msgs/msg/UInt8".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        // 10 Hz for 10 s with a 2 s gap and a second at 5 Hz
+        for tick in 0..100u64 {
+            let second = tick / 10;
+            if (4..6).contains(&second) || (second == 8 && tick % 2 == 1) {
+                continue;
+            }
+            writer
+                .write(&connection, tick * 100_000_000, &[0, 1, 0, 0, 0])
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut reader = Reader::new(&bag_path).unwrap();
+        reader.open().unwrap();
+        let histogram = reader.histogram(Duration::from_secs(1)).unwrap();
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(
+            histogram.counts("/imu").unwrap(),
+            [10, 10, 10, 10, 0, 0, 10, 10, 5, 10]
+        );
+        assert_eq!(histogram.rates("/imu").unwrap()[8], 5.0);
+        assert_eq!(histogram.dropouts("/imu"), vec![4..6]);
+        assert_eq!(histogram.anomalies("/imu", 0.2), [4, 5, 8]);
+        assert!(histogram.dropouts("/missing").is_empty());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_stamp_skew_offsets_and_drift() {
+        use crate::messages::{Header, Time, ToCdr, Vector3, Vector3Stamped};
+        use crate::Writer;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bag_path = dir.path().join("skew");
+        let mut writer = Writer::builder(&bag_path).open().unwrap();
+        let mut connections = Vec::new();
+        for (topic, message_type) in [
+            ("/gps", "geometry_msgs/msg/Vector3Stamped"),
+            ("/count", "std_msgs/msg/UInt8"),
+        ] {
+            connections.push(
+                writer
+                    .add_connection(
+                        topic.to_string(),
+                        message_type.to_string(),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .unwrap(),
+            );
+        }
+        // The sensor clock runs 100 ppm slow and is recorded 5 ms late
+        let start = 1_000_000_000_000;
+        let message = |stamp| Vector3Stamped {
+            header: Header {
+                stamp: Time::from_nanos(stamp),
+                frame_id: "gps".to_string(),
+            },
+            vector: Vector3::default(),
+        };
+        for i in 0..=10u64 {
+            let message = message(start + i * 999_900_000);
+            writer
+                .write(
+                    &connections[0],
+                    start + i * 1_000_000_000 + 5_000_000,
+                    &message.to_cdr_bytes(),
+                )
+                .unwrap();
+            writer
+                .write(&connections[1], start + i, &[0, 1, 0, 0, 0])
+                .unwrap();
+        }
+        let unset = message(0).to_cdr_bytes();
+        writer
+            .write(&connections[0], start + 20_000_000_000, &unset)
+            .unwrap();
+        writer.close().unwrap();
+
+        let mut reader = Reader::new(&bag_path).unwrap();
+        reader.open().unwrap();
+        let skews = reader.stamp_skew().unwrap();
+        assert_eq!(skews.len(), 1);
+        let skew = &skews[0];
+        assert_eq!(skew.topic, "/gps");
+        assert_eq!((skew.count, skew.unstamped), (11, 1));
+        assert_eq!(skew.min_offset, 5_000_000);
+        assert_eq!(skew.max_offset, 6_000_000);
+        assert!((skew.mean_offset - 5_500_000.0).abs() < 1e-3);
+        assert!((skew.drift * 1e6 - 100.0).abs() < 1e-6);
+        assert!(skew.std_dev > 0.0);
+    }
+}
@@ -0,0 +1,757 @@
+//! Reading bags packed in tar and zip archives
+//!
+//! Datasets often ship a bag directory as `scenario.tar.gz` or `scenario.zip`.
+//! [`open`] reads the storage files and `metadata.yaml` of a bag from such an
+//! archive into memory and returns a [`Reader`] over them, without extracting the
+//! archive to disk. Supported are `.tar`, `.tar.gz`/`.tgz`, `.tar.zst`/`.tzst`
+//! (with the `compression` feature) and `.zip` with stored or deflated entries.
+//!
+//! Both formats are parsed here instead of with the `tar` and `zip` crates: only the
+//! storage files and `metadata.yaml` of the selected bag are read, straight into the
+//! buffers the reader keeps, and the dependencies stay at `flate2` and `crc32fast`.
+//! Tar header checksums and zip CRC-32s are verified, and sizes from the headers
+//! are never trusted for allocations.
+//!
+//! # Example
+//! ```no_run
+//! use rosbags_rs::{archive, ReaderOptions};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! println!("{:?}", archive::bags("scenario.tar.gz")?);
+//! let mut reader = archive::open("scenario.tar.gz", None, ReaderOptions::default())?;
+//! reader.open()?;
+//! println!("{} messages", reader.message_count());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{ReaderError, Result};
+use crate::metadata::BagMetadata;
+use crate::reader::{Reader, ReaderOptions};
+use crate::storage::StorageInput;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Archive formats, by file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    TarGz,
+    #[cfg(feature = "compression")]
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        #[cfg(feature = "compression")]
+        if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            return Some(Self::TarZst);
+        }
+        if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `path` names an archive format that can be read
+pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+    ArchiveFormat::of(path.as_ref()).is_some()
+}
+
+/// Directories of the bags in the archive at `path`, relative to the archive root
+///
+/// A bag is a directory holding `.db3` or `.mcap` files. Only entry names are read,
+/// but compressed tar archives are still decompressed to find them.
+pub fn bags<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+    let files = read_entries(path.as_ref(), |_| false)?;
+    Ok(bag_dirs(files.keys()))
+}
+
+/// Create a reader for the bag in directory `bag` of the archive at `path`, or for
+/// its only bag if `bag` is `None`
+///
+/// The storage files are read into memory; the reader takes the bag's
+/// `metadata.yaml` if the archive has one, and otherwise reads topics and times from
+/// the storage as with [`ReaderOptions::skip_metadata`].
+pub fn open<P: AsRef<Path>>(path: P, bag: Option<&Path>, options: ReaderOptions) -> Result<Reader> {
+    let path = path.as_ref();
+    // Without a selected bag, keep every bag's files: there must be only one
+    let selected = bag.map(normalize);
+    let mut files = read_entries(path, |name| {
+        selected
+            .as_deref()
+            .map_or(true, |bag| name.parent() == Some(bag))
+    })?;
+    let bag = match selected {
+        Some(bag) => bag,
+        None => match bag_dirs(files.keys()).as_slice() {
+            [bag] => bag.clone(),
+            [] => {
+                return Err(ReaderError::BagNotFound {
+                    path: path.to_path_buf(),
+                })
+            }
+            several => {
+                let names: Vec<_> = several
+                    .iter()
+                    .map(|bag| bag.display().to_string())
+                    .collect();
+                return Err(ReaderError::generic(format!(
+                    "Archive {} holds several bags ({}), select one",
+                    path.display(),
+                    names.join(", ")
+                )));
+            }
+        },
+    };
+
+    let metadata = files
+        .remove(&bag.join("metadata.yaml"))
+        .flatten()
+        .map(|yaml| {
+            let yaml = String::from_utf8(yaml)
+                .map_err(|_| ReaderError::generic("metadata.yaml is not valid UTF-8"))?;
+            BagMetadata::from_yaml(&yaml)
+        })
+        .transpose()?;
+
+    // Storage files in the order of the metadata, or in name order
+    let names: Vec<PathBuf> = match &metadata {
+        Some(metadata) => metadata
+            .info()
+            .relative_file_paths
+            .iter()
+            .map(|file| bag.join(file))
+            .collect(),
+        None => files
+            .keys()
+            .filter(|name| is_storage_file(name))
+            .cloned()
+            .collect(),
+    };
+    if names.is_empty() {
+        return Err(ReaderError::BagNotFound {
+            path: path.join(&bag),
+        });
+    }
+
+    let mut inputs = Vec::with_capacity(names.len());
+    for name in names {
+        let Some(data) = files.remove(&name).flatten() else {
+            return Err(ReaderError::StorageFileNotFound {
+                path: path.join(name),
+            });
+        };
+        inputs.push(StorageInput::from_bytes(path.join(name), data));
+    }
+    Reader::from_inputs(inputs, metadata, options)
+}
+
+/// Whether `name` is a `.db3` or `.mcap` file
+fn is_storage_file(name: &Path) -> bool {
+    name.extension()
+        .is_some_and(|ext| ext == "db3" || ext == "mcap")
+}
+
+/// Directories holding storage files, in name order
+fn bag_dirs<'a>(names: impl Iterator<Item = &'a PathBuf>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = names
+        .filter(|name| is_storage_file(name))
+        .map(|name| name.parent().map(Path::to_path_buf).unwrap_or_default())
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Entry name without `./` and trailing `/`
+fn normalize(name: &Path) -> PathBuf {
+    name.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Regular files of the archive by name, with the contents of those `keep` selects
+/// (among storage files and `metadata.yaml`)
+type Entries = BTreeMap<PathBuf, Option<Vec<u8>>>;
+
+fn read_entries(path: &Path, keep: impl Fn(&Path) -> bool) -> Result<Entries> {
+    let format = ArchiveFormat::of(path).ok_or_else(|| {
+        ReaderError::generic(format!("{} is not a tar or zip archive", path.display()))
+    })?;
+    let file = File::open(path).map_err(|_| ReaderError::BagNotFound {
+        path: path.to_path_buf(),
+    })?;
+    let keep = |name: &Path| {
+        (is_storage_file(name) || name.file_name().is_some_and(|n| n == "metadata.yaml"))
+            && keep(name)
+    };
+
+    match format {
+        ArchiveFormat::Tar => read_tar(BufReader::new(file), keep),
+        ArchiveFormat::TarGz => read_tar(
+            flate2::read::MultiGzDecoder::new(BufReader::new(file)),
+            keep,
+        ),
+        #[cfg(feature = "compression")]
+        ArchiveFormat::TarZst => read_tar(zstd::stream::read::Decoder::new(file)?, keep),
+        ArchiveFormat::Zip => read_zip(BufReader::new(file), keep),
+    }
+    .map_err(|e| match e {
+        crate::error::BagError::Io(e) => {
+            ReaderError::generic(format!("Failed to read archive {}: {e}", path.display()))
+        }
+        e => e,
+    })
+}
+
+/// Tar block size
+const BLOCK: usize = 512;
+
+/// Read the entries of a tar stream (ustar, with GNU and pax long names)
+fn read_tar(mut reader: impl Read, keep: impl Fn(&Path) -> bool) -> Result<Entries> {
+    let mut entries = Entries::new();
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; BLOCK];
+
+    loop {
+        if !read_block(&mut reader, &mut header)? || header.iter().all(|&b| b == 0) {
+            break;
+        }
+        check_tar_checksum(&header)?;
+        let size = tar_size(&header[124..136])?;
+        let name = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let name = tar_str(&header[0..100]);
+                let prefix = tar_str(&header[345..500]);
+                if header[257..262] == *b"ustar" && !prefix.is_empty() {
+                    format!("{prefix}/{name}")
+                } else {
+                    name
+                }
+            }
+        };
+
+        match header[156] {
+            // Regular file
+            b'0' | 0 => {
+                let name = normalize(Path::new(&name));
+                if keep(&name) {
+                    entries.insert(name, Some(read_exact_vec(&mut reader, size)?));
+                    skip(&mut reader, padding(size))?;
+                } else {
+                    entries.insert(name, None);
+                    skip(&mut reader, size + padding(size))?;
+                }
+            }
+            // GNU long name of the next entry
+            b'L' => {
+                let data = read_exact_vec(&mut reader, size)?;
+                skip(&mut reader, padding(size))?;
+                long_name = Some(tar_str(&data));
+            }
+            // pax extended header, possibly with the path of the next entry
+            b'x' => {
+                let data = read_exact_vec(&mut reader, size)?;
+                skip(&mut reader, padding(size))?;
+                long_name = pax_path(&data);
+            }
+            _ => skip(&mut reader, size + padding(size))?,
+        }
+    }
+    Ok(entries)
+}
+
+/// Read a full block, `false` at the end of the stream
+fn read_block(reader: &mut impl Read, block: &mut [u8; BLOCK]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < BLOCK {
+        match reader.read(&mut block[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => return Err(ReaderError::generic("Truncated tar header")),
+            n => filled += n,
+        }
+    }
+    Ok(true)
+}
+
+/// Padding of `size` bytes of entry data to a full block
+fn padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+/// NUL-terminated string of a header field
+fn tar_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Check the checksum of a header, the sum of its bytes with the checksum field as
+/// spaces
+fn check_tar_checksum(header: &[u8; BLOCK]) -> Result<()> {
+    let digits = tar_str(&header[148..156]);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    let stored = u32::from_str_radix(digits, 8)
+        .map_err(|_| ReaderError::generic(format!("Invalid tar header checksum '{digits}'")))?;
+    let sum: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u32::from(b)
+            }
+        })
+        .sum();
+    if sum != stored {
+        return Err(ReaderError::generic("Tar header checksum mismatch"));
+    }
+    Ok(())
+}
+
+/// Entry size, octal or base-256 for entries of 8 GiB and more
+fn tar_size(field: &[u8]) -> Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let (high, low) = field.split_at(field.len() - 8);
+        if (high[0] & 0x7f) != 0 || high[1..].iter().any(|&b| b != 0) {
+            return Err(ReaderError::generic("Tar entry size exceeds 64 bits"));
+        }
+        return Ok(low.iter().fold(0, |size, &b| size << 8 | u64::from(b)));
+    }
+    let digits = tar_str(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| ReaderError::generic(format!("Invalid tar entry size '{digits}'")))
+}
+
+/// `path` record of a pax extended header (`<length> path=<value>\n` records)
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines()
+        .filter_map(|record| record.split_once(' ').map(|(_, record)| record))
+        .find_map(|record| record.strip_prefix("path="))
+        .map(str::to_string)
+}
+
+/// Read `size` bytes, growing the buffer with the data read instead of trusting the
+/// size of the header
+fn read_exact_vec(reader: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(size).read_to_end(&mut data)?;
+    if data.len() as u64 != size {
+        return Err(ReaderError::generic("Truncated tar entry"));
+    }
+    Ok(data)
+}
+
+fn skip(reader: &mut impl Read, size: u64) -> Result<()> {
+    let skipped = std::io::copy(&mut reader.take(size), &mut std::io::sink())?;
+    if skipped != size {
+        return Err(ReaderError::generic("Truncated tar entry"));
+    }
+    Ok(())
+}
+
+/// End of central directory record
+const ZIP_END: u32 = 0x0605_4b50;
+/// ZIP64 end of central directory locator
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+/// ZIP64 end of central directory record
+const ZIP64_END: u32 = 0x0606_4b50;
+/// Central directory file header
+const ZIP_CENTRAL: u32 = 0x0201_4b50;
+/// Local file header
+const ZIP_LOCAL: u32 = 0x0403_4b50;
+
+/// Read the entries of a zip archive (stored or deflated, with ZIP64)
+fn read_zip<R: Read + Seek>(mut reader: R, keep: impl Fn(&Path) -> bool) -> Result<Entries> {
+    let (count, directory_offset) = zip_directory(&mut reader)?;
+    let archive_len = reader.seek(SeekFrom::End(0))?;
+
+    // Central directory: name, method, sizes and local header offset of each entry
+    let mut files = Vec::new();
+    reader.seek(SeekFrom::Start(directory_offset))?;
+    for _ in 0..count {
+        let mut header = [0u8; 46];
+        reader.read_exact(&mut header)?;
+        if le32(&header[0..]) != ZIP_CENTRAL {
+            return Err(ReaderError::generic("Invalid zip central directory"));
+        }
+        let method = le16(&header[10..]);
+        let crc = le32(&header[16..]);
+        let mut compressed = u64::from(le32(&header[20..]));
+        let mut size = u64::from(le32(&header[24..]));
+        let mut offset = u64::from(le32(&header[42..]));
+        let mut name = vec![0u8; usize::from(le16(&header[28..]))];
+        let mut extra = vec![0u8; usize::from(le16(&header[30..]))];
+        reader.read_exact(&mut name)?;
+        reader.read_exact(&mut extra)?;
+        reader.seek(SeekFrom::Current(i64::from(le16(&header[32..]))))?;
+
+        // ZIP64 extra field with the sizes and offset that did not fit
+        let mut fields = extra.as_slice();
+        while fields.len() >= 4 {
+            let (id, len) = (le16(fields), usize::from(le16(&fields[2..])));
+            let data = &fields[4..(4 + len).min(fields.len())];
+            if id == 0x0001 {
+                let mut values = data.chunks_exact(8).map(le64);
+                for value in [&mut size, &mut compressed, &mut offset] {
+                    if *value == u64::from(u32::MAX) {
+                        *value = values.next().unwrap_or(*value);
+                    }
+                }
+            }
+            fields = &fields[(4 + len).min(fields.len())..];
+        }
+
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if !name.ends_with('/') {
+            files.push((
+                normalize(Path::new(&name)),
+                method,
+                crc,
+                compressed,
+                size,
+                offset,
+            ));
+        }
+    }
+
+    let mut entries = Entries::new();
+    for (name, method, crc, compressed, size, offset) in files {
+        if !keep(&name) {
+            entries.insert(name, None);
+            continue;
+        }
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 30];
+        reader.read_exact(&mut header)?;
+        if le32(&header) != ZIP_LOCAL {
+            return Err(ReaderError::generic("Invalid zip local file header"));
+        }
+        let skip = i64::from(le16(&header[26..])) + i64::from(le16(&header[28..]));
+        reader.seek(SeekFrom::Current(skip))?;
+
+        // Sizes in the headers are untrusted: reserve at most what is left of the file
+        let available = archive_len.saturating_sub(reader.stream_position()?);
+        let stored = (&mut reader).take(compressed);
+        let mut data = Vec::with_capacity(usize::try_from(size.min(available)).unwrap_or(0));
+        match method {
+            0 => BufReader::new(stored).read_to_end(&mut data)?,
+            8 => flate2::read::DeflateDecoder::new(stored).read_to_end(&mut data)?,
+            _ => {
+                return Err(ReaderError::generic(format!(
+                    "Unsupported zip compression method {method} of {}",
+                    name.display()
+                )))
+            }
+        };
+        if data.len() as u64 != size {
+            return Err(ReaderError::generic(format!(
+                "Truncated zip entry {}",
+                name.display()
+            )));
+        }
+        if crc32fast::hash(&data) != crc {
+            return Err(ReaderError::generic(format!(
+                "CRC-32 mismatch of zip entry {}",
+                name.display()
+            )));
+        }
+        entries.insert(name, Some(data));
+    }
+    Ok(entries)
+}
+
+/// Entry count and offset of the central directory
+fn zip_directory<R: Read + Seek>(reader: &mut R) -> Result<(u64, u64)> {
+    // The end record is at most a comment of 64 KiB from the end
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(22 + 65535);
+    reader.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| le32(&tail[i..]) == ZIP_END)
+        .ok_or_else(|| ReaderError::generic("Not a zip archive"))?;
+
+    let count = u64::from(le16(&tail[end + 10..]));
+    let offset = u64::from(le32(&tail[end + 16..]));
+    if count != u64::from(u16::MAX) && offset != u64::from(u32::MAX) {
+        return Ok((count, offset));
+    }
+
+    // ZIP64: the locator right before the end record points to the ZIP64 end record
+    let locator = end
+        .checked_sub(20)
+        .filter(|&i| le32(&tail[i..]) == ZIP64_LOCATOR)
+        .ok_or_else(|| ReaderError::generic("Missing ZIP64 end of central directory"))?;
+    reader.seek(SeekFrom::Start(le64(&tail[locator + 8..])))?;
+    let mut record = [0u8; 56];
+    reader.read_exact(&mut record)?;
+    if le32(&record) != ZIP64_END {
+        return Err(ReaderError::generic(
+            "Invalid ZIP64 end of central directory",
+        ));
+    }
+    Ok((le64(&record[32..]), le64(&record[48..])))
+}
+
+fn le16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn le64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    /// Tar header of an entry with the given size field, with its checksum
+    fn tar_header(name: &str, size: &[u8], kind: u8) -> [u8; BLOCK] {
+        let mut header = [0u8; BLOCK];
+        let len = name.len().min(100);
+        header[..len].copy_from_slice(&name.as_bytes()[..len]);
+        header[124..124 + size.len()].copy_from_slice(size);
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+        header
+    }
+
+    /// Tar archive of `files` (name, contents), with a GNU long name entry for long names
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        fn header(name: &str, size: usize, kind: u8) -> [u8; BLOCK] {
+            tar_header(name, format!("{size:011o}").as_bytes(), kind)
+        }
+        fn pad(archive: &mut Vec<u8>) {
+            archive.resize((archive.len() + BLOCK - 1) / BLOCK * BLOCK, 0);
+        }
+
+        let mut archive = Vec::new();
+        for (name, data) in files {
+            if name.len() > 100 {
+                archive.extend_from_slice(&header("././@LongLink", name.len() + 1, b'L'));
+                archive.extend_from_slice(name.as_bytes());
+                archive.push(0);
+                pad(&mut archive);
+            }
+            archive.extend_from_slice(&header(name, data.len(), b'0'));
+            archive.extend_from_slice(data);
+            pad(&mut archive);
+        }
+        archive.extend_from_slice(&[0; 2 * BLOCK]);
+        archive
+    }
+
+    /// Zip archive of `files`, deflated
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        zip_with_size(files, None)
+    }
+
+    /// Zip archive of `files`, with `size` as the ZIP64 uncompressed size of every
+    /// entry in the central directory if set
+    fn zip_with_size(files: &[(&str, &[u8])], size: Option<u64>) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for (name, data) in files {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let mut fields = Vec::new();
+            fields.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            fields.extend_from_slice(&0u16.to_le_bytes()); // flags
+            fields.extend_from_slice(&8u16.to_le_bytes()); // deflate
+            fields.extend_from_slice(&[0; 4]); // time, date
+            fields.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+            fields.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            let mut central = fields.clone();
+            for fields in [&mut fields, &mut central] {
+                fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            }
+            fields.extend_from_slice(&0u16.to_le_bytes()); // extra length
+            let extra = match size {
+                Some(size) => {
+                    central[18..22].copy_from_slice(&u32::MAX.to_le_bytes());
+                    [
+                        &1u16.to_le_bytes()[..],
+                        &8u16.to_le_bytes(),
+                        &size.to_le_bytes(),
+                    ]
+                    .concat()
+                }
+                None => Vec::new(),
+            };
+            central.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+
+            directory.extend_from_slice(&ZIP_CENTRAL.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+            directory.extend_from_slice(&central);
+            directory.extend_from_slice(&[0; 6]); // comment length, disk, internal attributes
+            directory.extend_from_slice(&[0; 4]); // external attributes
+            directory.extend_from_slice(&(archive.len() as u32).to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+            directory.extend_from_slice(&extra);
+
+            archive.extend_from_slice(&ZIP_LOCAL.to_le_bytes());
+            archive.extend_from_slice(&fields);
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(&compressed);
+        }
+        let offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        archive.extend_from_slice(&ZIP_END.to_le_bytes());
+        archive.extend_from_slice(&[0; 4]); // disk numbers
+        archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&offset.to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        archive
+    }
+
+    #[cfg(feature = "mcap")]
+    #[test]
+    fn test_open_bag_in_archives() {
+        let bag = Path::new("tests/test_bags/test_bag_mcap");
+        let metadata = std::fs::read(bag.join("metadata.yaml")).unwrap();
+        let storage = std::fs::read(bag.join("test_bag_mcap.mcap")).unwrap();
+        let long_dir = format!("./datasets/{}/test_bag_mcap", "scenario".repeat(12));
+        let files: Vec<(String, &[u8])> = vec![
+            (format!("{long_dir}/metadata.yaml"), metadata.as_slice()),
+            (format!("{long_dir}/test_bag_mcap.mcap"), storage.as_slice()),
+            ("README.txt".to_string(), b"not a bag"),
+        ];
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(n, d)| (n.as_str(), *d)).collect();
+
+        let temp_dir = TempDir::new().unwrap();
+        let tar_path = temp_dir.path().join("scenario.tar");
+        std::fs::write(&tar_path, tar(&files)).unwrap();
+        let gz_path = temp_dir.path().join("scenario.tgz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&gz_path).unwrap(),
+            flate2::Compression::fast(),
+        );
+        encoder.write_all(&tar(&files)).unwrap();
+        encoder.finish().unwrap();
+        let zip_path = temp_dir.path().join("scenario.zip");
+        std::fs::write(&zip_path, zip(&files)).unwrap();
+
+        let mut expected = Reader::new(bag).unwrap();
+        expected.open().unwrap();
+        let bag_dir = normalize(Path::new(&long_dir));
+        for path in [&tar_path, &gz_path, &zip_path] {
+            assert!(is_archive(path));
+            assert_eq!(bags(path).unwrap(), vec![bag_dir.clone()]);
+
+            let mut reader = open(path, None, ReaderOptions::default()).unwrap();
+            reader.open().unwrap();
+            assert_eq!(reader.message_count(), expected.message_count());
+            assert_eq!(
+                reader.raw_messages().unwrap().count(),
+                expected.raw_messages().unwrap().count()
+            );
+            assert_eq!(
+                reader.files().unwrap()[0].path,
+                path.join(&bag_dir).join("test_bag_mcap.mcap")
+            );
+
+            assert!(open(path, Some(Path::new("missing")), ReaderOptions::default()).is_err());
+        }
+        assert!(!is_archive(bag));
+    }
+
+    #[test]
+    fn test_corrupt_archives_are_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let read = |name: &str, archive: &[u8]| {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, archive).unwrap();
+            read_entries(&path, |_| true).map(|entries| entries.len())
+        };
+        let files: &[(&str, &[u8])] = &[("bag/bag_0.mcap", b"not really mcap")];
+        assert_eq!(read("valid.tar", &tar(files)).unwrap(), 1);
+        assert_eq!(read("valid.zip", &zip(files)).unwrap(), 1);
+
+        // Sizes far beyond the data, up to the u64 range, fail instead of allocating
+        let mut huge = [0xffu8; 12];
+        huge[..4].copy_from_slice(&[0x80, 0, 0, 0]);
+        for size in [huge.as_slice(), b"77777777777\0"] {
+            let mut archive = tar_header("bag/bag_0.mcap", size, b'0').to_vec();
+            archive.extend_from_slice(&[0; BLOCK]);
+            let error = read("oversized.tar", &archive).unwrap_err();
+            assert!(error.to_string().contains("Truncated tar entry"), "{error}");
+        }
+        let mut overflow = [0xffu8; 12];
+        overflow[0] = 0x80;
+        let archive = tar_header("bag/bag_0.mcap", &overflow, b'0');
+        assert!(read("overflow.tar", &archive).is_err());
+        let mut truncated = tar(files);
+        truncated.truncate(BLOCK + 4);
+        assert!(read("truncated.tar", &truncated).is_err());
+        let mut corrupt = tar(files);
+        corrupt[0] = b'c';
+        let error = read("corrupt.tar", &corrupt).unwrap_err();
+        assert!(error.to_string().contains("checksum"), "{error}");
+
+        let error = read("oversized.zip", &zip_with_size(files, Some(u64::MAX - 1))).unwrap_err();
+        assert!(error.to_string().contains("Truncated zip entry"), "{error}");
+        let mut corrupt = zip(files);
+        let central = corrupt
+            .windows(4)
+            .position(|w| w == ZIP_CENTRAL.to_le_bytes())
+            .unwrap();
+        corrupt[central + 16] ^= 0x01;
+        let error = read("corrupt.zip", &corrupt).unwrap_err();
+        assert!(error.to_string().contains("CRC-32 mismatch"), "{error}");
+        let mut truncated = zip(files);
+        truncated.truncate(40);
+        assert!(read("truncated.zip", &truncated).is_err());
+    }
+
+    #[test]
+    fn test_tar_sizes_and_pax_paths() {
+        assert_eq!(tar_size(b"00000001750\0").unwrap(), 1000);
+        let mut base256 = [0u8; 12];
+        base256[0] = 0x80;
+        base256[7] = 2;
+        assert_eq!(tar_size(&base256).unwrap(), 2 << 32);
+        assert_eq!(
+            pax_path(b"20 mtime=1700000000\n30 path=bags/run/bag_0.mcap\n").as_deref(),
+            Some("bags/run/bag_0.mcap")
+        );
+        assert_eq!(padding(512), 0);
+        assert_eq!(padding(1), 511);
+    }
+}
@@ -0,0 +1,81 @@
+#![cfg(not(feature = "write-only"))]
+//! Example: Generate Rust message structs from a bag's stored definitions
+//!
+//! Reads the message definitions recorded in a ROS2 bag and writes Rust structs with
+//! `FromCdr`/`ToCdr` implementations for every contained message type, including
+//! custom and proprietary interfaces.
+//!
+//! Usage:
+//!   cargo run --bin bag_codegen -- <bag_path> [--output messages.rs] [--topics topic1,topic2,...]
+//!
+//! Examples:
+//!   # Print structs for all topics
+//!   cargo run --bin bag_codegen -- ./my_bag
+//!
+//!   # Write structs for selected topics into a source file
+//!   cargo run --bin bag_codegen -- ./my_bag --output src/my_msgs.rs --topics /robot/state
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use rosbags_rs::codegen::Codegen;
+use rosbags_rs::Reader;
+use std::path::PathBuf;
+
+/// Generate Rust message structs from the message definitions stored in a bag
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input bag directory
+    input: PathBuf,
+
+    /// Output Rust file (prints to stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Topics whose types to generate (comma-separated list, all topics if empty)
+    #[arg(short, long, value_delimiter = ',')]
+    topics: Vec<String>,
+
+    /// Path under which the generated code refers to the rosbags-rs crate
+    #[arg(long, default_value = "rosbags_rs")]
+    crate_path: String,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut reader = Reader::new(&args.input)
+        .with_context(|| format!("Failed to open bag {}", args.input.display()))?;
+    reader.open()?;
+
+    let mut codegen = Codegen::new().crate_path(&args.crate_path);
+    let mut selected = 0;
+    for connection in reader.connections() {
+        if !args.topics.is_empty() && !args.topics.contains(&connection.topic) {
+            continue;
+        }
+        selected += 1;
+        if let Err(e) = codegen.add_connection(connection) {
+            eprintln!("Skipping {}: {e}", connection.topic);
+        }
+    }
+    if selected == 0 {
+        bail!("None of the requested topics are in the bag");
+    }
+
+    let code = codegen.generate();
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, &code)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "Generated {} message types into {}",
+                codegen.message_types().count(),
+                path.display()
+            );
+        }
+        None => print!("{code}"),
+    }
+
+    Ok(())
+}
@@ -5,6 +5,7 @@
 //! - Storage files and sizes
 //! - Duration and timing information
 //! - Topic details with message counts
+//! - Recorded services with event counts
 //! - Human-readable timestamps
 //!
 //! This version is optimized for speed by reading only the metadata.yaml file
@@ -13,7 +14,8 @@
 //! Usage: cargo run --bin bag_info <bag_path>
 
 use chrono::TimeZone;
-use rosbags_rs::{read_bag_metadata_fast, ReaderError};
+use rosbags_rs::metadata::TopicWithMessageCount;
+use rosbags_rs::{read_bag_metadata_fast, ReaderError, TopicKind};
 use std::env;
 use std::path::Path;
 
@@ -53,17 +55,31 @@ fn main() -> Result<(), ReaderError> {
     println!("End:               {}", format_timestamp(end_time_ns));
     println!("Messages:          {message_count}");
 
+    // Service events are listed separately, like `ros2 bag info` does
+    let (services, topics): (Vec<&TopicWithMessageCount>, Vec<&TopicWithMessageCount>) = info
+        .topics_with_message_count
+        .iter()
+        .partition(|topic| topic.topic_metadata.kind() == TopicKind::ServiceEvent);
+
     // Print topic information directly from metadata
-    if !info.topics_with_message_count.is_empty() {
+    if let Some((first, rest)) = topics.split_first() {
         println!(
             "Topic information: {}",
-            format_first_topic_from_metadata(&info.topics_with_message_count[0])
+            format_first_topic_from_metadata(first)
         );
-        for topic in info.topics_with_message_count.iter().skip(1) {
+        for topic in rest {
             println!("                   {}", format_topic_from_metadata(topic));
         }
     }
 
+    if let Some((first, rest)) = services.split_first() {
+        println!("Services:          {}", services.len());
+        println!("Service information: {}", format_service(first));
+        for service in rest {
+            println!("                     {}", format_service(service));
+        }
+    }
+
     Ok(())
 }
 
@@ -162,3 +178,15 @@ fn format_topic_from_metadata(topic: &rosbags_rs::metadata::TopicWithMessageCoun
         topic.topic_metadata.serialization_format
     )
 }
+
+/// Format a service event topic as its service
+fn format_service(topic: &TopicWithMessageCount) -> String {
+    let metadata = &topic.topic_metadata;
+    format!(
+        "Service: {} | Type: {} | Event Count: {} | Serialization Format: {}",
+        metadata.service_name().unwrap_or(&metadata.name),
+        metadata.service_type().unwrap_or(&metadata.message_type),
+        topic.message_count,
+        metadata.serialization_format
+    )
+}
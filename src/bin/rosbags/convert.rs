@@ -0,0 +1,66 @@
+//! `rosbags convert`: rewrite a bag with another storage format or compression
+//!
+//! All topics are copied unchanged as raw messages; this is `rosbags filter` without
+//! any filters.
+//!
+//! Usage:
+//!   rosbags convert <input_bag> <output_bag> --storage mcap [--compression-mode file --compression-format zstd]
+
+use crate::filter::{self, FilterArgs};
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+/// Rewrite a bag with another storage format or compression
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// Input bag directory
+    input: PathBuf,
+
+    /// Output bag directory
+    output: PathBuf,
+
+    /// Storage plugin to use for output (sqlite3 or mcap)
+    #[arg(long)]
+    storage: String,
+
+    /// Compression mode (none, file, message or storage)
+    #[arg(long, default_value = "none")]
+    compression_mode: String,
+
+    /// Compression format (none or zstd)
+    #[arg(long, default_value = "none")]
+    compression_format: String,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Convert the input bag in `args` to the requested storage and compression
+pub fn run(args: ConvertArgs) -> Result<()> {
+    filter::run(FilterArgs {
+        input: args.input,
+        output: args.output,
+        topics: Vec::new(),
+        exclude_topics: Vec::new(),
+        start: None,
+        end: None,
+        storage: args.storage,
+        compression_mode: args.compression_mode,
+        compression_format: args.compression_format,
+        standard_copy: false,
+        buffer_size_mb: 50,
+        batch_size: 1000,
+        downscale_images: None,
+        compress_images: None,
+        jpeg_quality: 80,
+        voxel_downsample: None,
+        truncate_payloads: None,
+        drop_truncated: false,
+        migrate: Vec::new(),
+        manifest: false,
+        list_topics: false,
+        verbose: args.verbose,
+    })
+}
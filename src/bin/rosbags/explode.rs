@@ -0,0 +1,134 @@
+//! `rosbags explode`: split a bag into one bag per topic
+//!
+//! Every topic is written to its own bag in the output directory, named after the
+//! topic (`/imu/data` goes to `imu_data`), or to the bag of the first group whose
+//! patterns match it. Output connections keep the recorded message definitions and
+//! offered QoS profiles.
+//!
+//! Usage:
+//!   rosbags explode <input_bag> -o <output_dir> [--group NAME=PATTERN,...] [--topics PATTERN,...]
+//!
+//! Examples:
+//!   # One bag per topic
+//!   rosbags explode ./input_bag -o ./topics
+//!
+//!   # Stereo cameras in one bag, every other topic on its own, as MCAP
+//!   rosbags explode ./input_bag -o ./topics --group stereo=/camera/*/image_raw --storage mcap
+
+use anyhow::{Context, Result};
+use clap::Args;
+use rosbags_rs::explode::Explode;
+use rosbags_rs::types::{CompressionFormat, CompressionMode, StoragePlugin};
+use rosbags_rs::{Reader, Writer};
+use std::path::PathBuf;
+
+/// Split a bag into one bag per topic or topic group
+#[derive(Args, Debug)]
+pub struct ExplodeArgs {
+    /// Input bag directory
+    input: PathBuf,
+
+    /// Output directory for the bags
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Write the topics matching the comma-separated glob patterns to one bag named
+    /// NAME; may be given several times
+    #[arg(short, long, value_name = "NAME=PATTERN,...")]
+    group: Vec<String>,
+
+    /// Topic glob patterns to include (comma-separated list, if empty, all topics are
+    /// included)
+    #[arg(short, long, value_delimiter = ',')]
+    topics: Vec<String>,
+
+    /// Storage plugin to use for output (sqlite3 or mcap)
+    #[arg(long, default_value = "sqlite3")]
+    storage: String,
+
+    /// Compression mode (none, file, message, or storage)
+    #[arg(long, default_value = "none")]
+    compression_mode: String,
+
+    /// Compression format (none or zstd)
+    #[arg(long, default_value = "none")]
+    compression_format: String,
+}
+
+/// Split the input bag of `args` into bags in the output directory
+pub fn run(args: ExplodeArgs) -> Result<()> {
+    let storage_plugin = match args.storage.as_str() {
+        "sqlite3" => StoragePlugin::Sqlite3,
+        "mcap" => StoragePlugin::Mcap,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported storage plugin: {}. Use 'sqlite3' or 'mcap'",
+                args.storage
+            ));
+        }
+    };
+    let compression_mode = match args.compression_mode.as_str() {
+        "none" => CompressionMode::None,
+        "file" => CompressionMode::File,
+        "message" => CompressionMode::Message,
+        "storage" => CompressionMode::Storage,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported compression mode: {}. Use 'none', 'file', 'message', or 'storage'",
+                args.compression_mode
+            ));
+        }
+    };
+    let compression_format = match args.compression_format.as_str() {
+        "none" => CompressionFormat::None,
+        "zstd" => CompressionFormat::Zstd,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported compression format: {}. Use 'none' or 'zstd'",
+                args.compression_format
+            ));
+        }
+    };
+
+    let mut explode = Explode::new();
+    for group in &args.group {
+        let (name, patterns) = group
+            .split_once('=')
+            .with_context(|| format!("Invalid group '{group}', expected NAME=PATTERN,..."))?;
+        explode = explode.group(name, patterns.split(','));
+    }
+    if !args.topics.is_empty() {
+        explode = explode.topics(args.topics.iter().map(String::as_str));
+    }
+
+    let reader = Reader::builder(&args.input)
+        .open()
+        .with_context(|| format!("Failed to open input bag {}", args.input.display()))?;
+    let bags = explode
+        .run(&reader, &args.output, |path| {
+            Writer::builder(path)
+                .storage(storage_plugin)
+                .compression(compression_mode, compression_format)
+        })
+        .context("Failed to split bag")?;
+
+    if bags.is_empty() {
+        println!("No topics match the filter criteria");
+        return Ok(());
+    }
+    for bag in &bags {
+        println!(
+            "  {} ({} messages): {}",
+            bag.name,
+            bag.message_count,
+            bag.topics.join(", ")
+        );
+    }
+    println!(
+        "Split {} into {} bags in {}",
+        args.input.display(),
+        bags.len(),
+        args.output.display()
+    );
+    Ok(())
+}
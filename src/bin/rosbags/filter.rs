@@ -0,0 +1,673 @@
+//! `rosbags filter`: copy and filter ROS2 bag files
+//!
+//! Reads an existing ROS2 bag file and writes it to a new location with optional
+//! topic filtering. It supports both SQLite3 and MCAP formats. Also built as the
+//! standalone `bag_filter` binary.
+//!
+//! Usage:
+//!   rosbags filter <input_bag> <output_bag> [--topics topic1,topic2,...]
+//!
+//! Arguments:
+//!   input_bag   - Path to the source bag file
+//!   output_bag  - Path where the filtered bag will be created  
+//!   --topics    - Comma-separated list of topics to include (optional)
+//!   --compression - Enable zstd compression for output
+//!   --start     - Start timestamp in nanoseconds (optional)
+//!   --end       - End timestamp in nanoseconds (optional)
+//!   --downscale-images - Downscale image topics by an integer factor (preview bags)
+//!   --truncate-payloads - Empty the data of image and point cloud messages above a size
+//!   --drop-truncated - Drop those messages instead, keeping their topics
+//!   --compress-images - Transcode raw image topics to JPEG or PNG compressed images
+//!   --voxel-downsample - Downsample point cloud topics on a voxel grid (leaf size in meters)
+//!   --migrate   - Upgrade a message type to the .msg definition in a file (TYPE=FILE)
+//!   --manifest  - Write manifest.json with provenance and checksums into the output bag
+//!
+//! Examples:
+//!   # Copy entire bag
+//!   rosbags filter ./input_bag ./output_bag
+//!
+//!   # Copy only specific topics
+//!   rosbags filter ./input_bag ./output_bag --topics /camera/image_raw,/imu/data
+//!
+//!   # Copy with time filtering
+//!   rosbags filter ./input_bag ./output_bag --start 1000000000 --end 2000000000
+//!
+//!   # Copy with compression
+//!   rosbags filter ./input_bag ./output_bag --compression
+//!
+//!   # Create a preview bag with images at 1/4 resolution
+//!   rosbags filter ./input_bag ./preview_bag --downscale-images 4
+//!
+//!   # Archive camera topics as JPEG compressed images
+//!   rosbags filter ./input_bag ./archive_bag --compress-images jpeg
+//!
+//!   # Archive lidar topics with one point per 5 cm voxel
+//!   rosbags filter ./input_bag ./archive_bag --voxel-downsample 0.05
+//!
+//!   # Create a tiny preview bag without image and point cloud data
+//!   rosbags filter ./input_bag ./preview_bag --truncate-payloads 1024
+//!
+//!   # Upgrade messages recorded with an outdated interface
+//!   rosbags filter ./input_bag ./output_bag --migrate my_msgs/msg/Range=Range.msg
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use rosbags_rs::export::manifest::ExportManifest;
+use rosbags_rs::migrations::{Migration, Migrations};
+use rosbags_rs::transform::{
+    ImageDownscale, ImageTranscode, MessageTransform, PayloadTruncation, TranscodeFormat,
+    TransformRegistry, VoxelDownsample,
+};
+use rosbags_rs::types::{CompressionFormat, CompressionMode, Connection, StoragePlugin};
+use rosbags_rs::{Reader, Writer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Arguments for copy functions
+struct CopyArgs<'a> {
+    connections: &'a [Connection],
+    conn_map: &'a HashMap<(String, String), (Connection, Connection)>,
+    start: Option<u64>,
+    end: Option<u64>,
+    batch_size: usize,
+    transforms: &'a TransformRegistry,
+    migrations: &'a Migrations,
+    verbose: bool,
+}
+
+/// Copy a ROS2 bag file with optional topic filtering
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct FilterArgs {
+    /// Input bag directory
+    pub input: PathBuf,
+
+    /// Output bag directory
+    pub output: PathBuf,
+
+    /// Topics to include (comma-separated list, if empty, all topics are included)
+    #[arg(short, long, value_delimiter = ',')]
+    pub topics: Vec<String>,
+
+    /// Topics to exclude (comma-separated list)
+    #[arg(short = 'x', long = "exclude", value_delimiter = ',')]
+    pub exclude_topics: Vec<String>,
+
+    /// Start time (nanoseconds since epoch)
+    #[arg(short, long)]
+    pub start: Option<u64>,
+
+    /// End time (nanoseconds since epoch)
+    #[arg(short, long)]
+    pub end: Option<u64>,
+
+    /// Storage plugin to use for output (sqlite3 or mcap)
+    #[arg(long, default_value = "sqlite3")]
+    pub storage: String,
+
+    /// Compression mode (none, file, or message)
+    #[arg(long, default_value = "none")]
+    pub compression_mode: String,
+
+    /// Compression format (none or zstd)
+    #[arg(long, default_value = "none")]
+    pub compression_format: String,
+
+    /// Use standard (slower) copying with deserialization/serialization.
+    /// Default is a high-performance raw copy.
+    #[arg(long)]
+    pub standard_copy: bool,
+
+    /// Buffer size in MB for raw copy mode (default: 50MB for high-throughput)
+    #[arg(long, default_value = "50")]
+    pub buffer_size_mb: usize,
+
+    /// Batch size for bulk operations in raw copy mode
+    #[arg(long, default_value = "1000")]
+    pub batch_size: usize,
+
+    /// Downscale image topics by this factor (e.g. 4 for 1/4 resolution) to create a
+    /// lightweight preview bag; all other topics are copied unchanged
+    #[arg(long, value_name = "FACTOR")]
+    pub downscale_images: Option<u32>,
+
+    /// Empty the data of image and point cloud messages larger than this many bytes,
+    /// keeping their headers and metadata, to create a tiny preview bag
+    #[arg(long, value_name = "BYTES", conflicts_with = "downscale_images")]
+    pub truncate_payloads: Option<usize>,
+
+    /// Drop the messages selected by --truncate-payloads instead of truncating them;
+    /// their topics are still written
+    #[arg(long, requires = "truncate_payloads")]
+    pub drop_truncated: bool,
+
+    /// Transcode raw image topics to compressed images (jpeg or png), written on the
+    /// image topic with a /compressed suffix
+    #[arg(long, value_name = "FORMAT", conflicts_with = "downscale_images")]
+    pub compress_images: Option<String>,
+
+    /// JPEG quality (1-100) for compressed images re-encoded by --downscale-images or
+    /// written by --compress-images
+    #[arg(long, default_value = "80")]
+    pub jpeg_quality: u8,
+
+    /// Downsample point cloud topics to one point per voxel of this size in meters,
+    /// at the centroid of the points in the voxel
+    #[arg(long, value_name = "LEAF")]
+    pub voxel_downsample: Option<f64>,
+
+    /// Upgrade messages of TYPE recorded with another layout to the `.msg` definition
+    /// in FILE, carrying fields over by name; may be given several times
+    #[arg(long, value_name = "TYPE=FILE")]
+    pub migrate: Vec<String>,
+
+    /// Write manifest.json with inputs, options and output checksums into the output bag
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// List all topics in the bag and exit
+    #[arg(long)]
+    pub list_topics: bool,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+/// Copy the input bag to the output bag as configured by `args`
+pub fn run(args: FilterArgs) -> Result<()> {
+    // Open input bag
+    let mut reader = Reader::new(&args.input).context("Failed to create reader")?;
+    reader.open().context("Failed to open input bag")?;
+
+    // Get all connections
+    let connections = reader.connections();
+
+    // If user just wants to list topics, print them and exit
+    if args.list_topics {
+        println!("Available topics in bag:");
+        for (i, conn) in connections.iter().enumerate() {
+            println!("  {}: {} ({})", i + 1, conn.topic, conn.message_type);
+        }
+        println!("\nTotal topics: {}", connections.len());
+        return Ok(());
+    }
+
+    if args.verbose {
+        println!(
+            "Copying bag from {} to {}",
+            args.input.display(),
+            args.output.display()
+        );
+        if !args.standard_copy {
+            println!(
+                "Using raw copy mode for maximum performance (buffer: {}MB, batch: {})",
+                args.buffer_size_mb, args.batch_size
+            );
+        }
+    }
+
+    // Parse storage plugin
+    let storage_plugin = match args.storage.as_str() {
+        "sqlite3" => StoragePlugin::Sqlite3,
+        "mcap" => StoragePlugin::Mcap,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported storage plugin: {}. Use 'sqlite3' or 'mcap'",
+                args.storage
+            ));
+        }
+    };
+
+    // Parse compression mode
+    let compression_mode = match args.compression_mode.as_str() {
+        "none" => CompressionMode::None,
+        "file" => CompressionMode::File,
+        "message" => CompressionMode::Message,
+        "storage" => CompressionMode::Storage,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported compression mode: {}. Use 'none', 'file', 'message', or 'storage'",
+                args.compression_mode
+            ));
+        }
+    };
+
+    // Parse compression format
+    let compression_format = match args.compression_format.as_str() {
+        "none" => CompressionFormat::None,
+        "zstd" => CompressionFormat::Zstd,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported compression format: {}. Use 'none' or 'zstd'",
+                args.compression_format
+            ));
+        }
+    };
+
+    // Parse image compression format
+    let transcode_format = match args.compress_images.as_deref() {
+        None => None,
+        Some("jpeg") => Some(TranscodeFormat::Jpeg),
+        Some("png") => Some(TranscodeFormat::Png),
+        Some(other) => {
+            return Err(anyhow::anyhow!(
+                "Unsupported image compression format: {}. Use 'jpeg' or 'png'",
+                other
+            ));
+        }
+    };
+
+    if let Some(leaf_size) = args.voxel_downsample {
+        if !(leaf_size.is_finite() && leaf_size > 0.0) {
+            return Err(anyhow::anyhow!(
+                "Invalid voxel leaf size: {}. Use a positive size in meters",
+                leaf_size
+            ));
+        }
+    }
+
+    // Create output bag
+    let mut writer =
+        Writer::new(&args.output, None, Some(storage_plugin)).context("Failed to create writer")?;
+    writer.set_compression(compression_mode, compression_format)?;
+
+    if !args.standard_copy {
+        // Configure buffer for high-performance raw copying
+        writer.configure_buffer(args.buffer_size_mb, args.batch_size)?;
+    }
+
+    writer.open().context("Failed to open output bag")?;
+
+    // Filter connections
+    let filtered_connections: Vec<_> = connections
+        .iter()
+        .filter(|conn| {
+            // Include filter
+            let include = if args.topics.is_empty() {
+                true
+            } else {
+                args.topics.contains(&conn.topic)
+            };
+
+            // Exclude filter
+            let exclude = args.exclude_topics.contains(&conn.topic);
+
+            include && !exclude
+        })
+        .cloned()
+        .collect();
+
+    if filtered_connections.is_empty() {
+        println!("No topics match the filter criteria");
+        return Ok(());
+    }
+
+    if args.verbose {
+        println!(
+            "Selected {} topics for copying:",
+            filtered_connections.len()
+        );
+        for conn in &filtered_connections {
+            println!("  {} ({})", conn.topic, conn.message_type);
+        }
+    }
+
+    let downscale = args
+        .downscale_images
+        .map(|factor| ImageDownscale::new(factor).jpeg_quality(args.jpeg_quality));
+    let transcode =
+        transcode_format.map(|format| ImageTranscode::new(format).jpeg_quality(args.jpeg_quality));
+    let voxel = args.voxel_downsample.map(VoxelDownsample::new);
+    let truncation = args.truncate_payloads.map(|max_size| {
+        let truncation = PayloadTruncation::new(max_size);
+        if args.drop_truncated {
+            truncation.drop_messages()
+        } else {
+            truncation
+        }
+    });
+    if args.verbose {
+        if let Some(downscale) = &downscale {
+            println!("Downscaling image topics by {}", downscale.factor());
+        }
+        if let Some(transcode) = &transcode {
+            println!(
+                "Compressing raw image topics to {}",
+                transcode.format().as_str()
+            );
+        }
+        if let Some(voxel) = &voxel {
+            println!(
+                "Downsampling point cloud topics with {} m voxels",
+                voxel.leaf_size()[0]
+            );
+        }
+        if let Some(truncation) = &truncation {
+            let action = if truncation.drops_messages() {
+                "Dropping"
+            } else {
+                "Truncating"
+            };
+            println!(
+                "{action} image and point cloud messages over {} bytes",
+                truncation.max_size()
+            );
+        }
+    }
+    let mut transforms = TransformRegistry::new();
+    if let Some(downscale) = downscale {
+        transforms.register(downscale);
+    }
+    if let Some(transcode) = transcode {
+        transforms.register(transcode);
+    }
+    // Before truncation, which would otherwise empty clouds that fit once downsampled
+    if let Some(voxel) = voxel {
+        transforms.register(voxel);
+    }
+    if let Some(truncation) = truncation {
+        transforms.register(truncation);
+    }
+
+    let migrations = load_migrations(&args.migrate)?;
+
+    // Create a map from reader topic and type to the connection the transforms see and
+    // the writer connection, for fast lookup; a topic recorded with two types keeps
+    // both connections
+    let mut conn_map = HashMap::new();
+    for r_conn in &filtered_connections {
+        let migrated = migrations.migrate_connection(r_conn)?;
+        if args.verbose && migrated.message_definition != r_conn.message_definition {
+            println!(
+                "Migrating {} ({}) to {}",
+                r_conn.topic, r_conn.message_type, migrated.message_type
+            );
+        }
+        let output = if transforms.applies_to(&migrated) {
+            transforms.connection(&migrated)?
+        } else {
+            migrated.clone()
+        };
+        let existing = writer
+            .connections()
+            .iter()
+            .find(|c| connection_key(c) == connection_key(&output))
+            .cloned();
+        let w_conn = match existing {
+            Some(w_conn) => w_conn,
+            None => writer.add_connection_from(&output)?,
+        };
+        conn_map.insert(connection_key(r_conn), (migrated, w_conn));
+    }
+
+    let copy_args = CopyArgs {
+        connections: &filtered_connections,
+        conn_map: &conn_map,
+        start: args.start,
+        end: args.end,
+        batch_size: args.batch_size,
+        transforms: &transforms,
+        migrations: &migrations,
+        verbose: args.verbose,
+    };
+
+    if !args.standard_copy {
+        // High-performance raw copy mode
+        copy_raw_messages(&mut reader, &mut writer, &copy_args)?;
+    } else {
+        // Standard copy mode
+        copy_messages(&mut reader, &mut writer, &copy_args)?;
+    }
+
+    // Close bags
+    writer.close().context("Failed to close output bag")?;
+    reader.close().context("Failed to close input bag")?;
+
+    if args.manifest {
+        let path = write_manifest(&args).context("Failed to write manifest")?;
+        if args.verbose {
+            println!("Wrote manifest {}", path.display());
+        }
+    }
+
+    println!("Bag copy completed successfully");
+    Ok(())
+}
+
+/// Record the copy's input, options and output checksums next to the output bag
+fn write_manifest(args: &FilterArgs) -> Result<PathBuf> {
+    let mut manifest = ExportManifest::new("bag_filter", &args.output)
+        .input(&args.input)
+        .option("topics", args.topics.join(","))
+        .option("exclude", args.exclude_topics.join(","))
+        .option("storage", &args.storage)
+        .option("compression_mode", &args.compression_mode)
+        .option("compression_format", &args.compression_format);
+    if let Some(start) = args.start {
+        manifest = manifest.option("start", start);
+    }
+    if let Some(end) = args.end {
+        manifest = manifest.option("end", end);
+    }
+    if let Some(factor) = args.downscale_images {
+        manifest = manifest
+            .option("downscale_images", factor)
+            .option("jpeg_quality", args.jpeg_quality);
+    }
+    if let Some(format) = &args.compress_images {
+        manifest = manifest
+            .option("compress_images", format)
+            .option("jpeg_quality", args.jpeg_quality);
+    }
+    if let Some(leaf_size) = args.voxel_downsample {
+        manifest = manifest.option("voxel_downsample", leaf_size);
+    }
+    if let Some(max_size) = args.truncate_payloads {
+        manifest = manifest
+            .option("truncate_payloads", max_size)
+            .option("drop_truncated", args.drop_truncated);
+    }
+    if !args.migrate.is_empty() {
+        manifest = manifest.option("migrate", args.migrate.join(","));
+    }
+    manifest.add_root_contents()?;
+    Ok(manifest.write()?)
+}
+
+/// Parse `TYPE=FILE` migration arguments into migrations to the `.msg` files
+fn load_migrations(specs: &[String]) -> Result<Migrations> {
+    let mut migrations = Migrations::new();
+    for spec in specs {
+        let (message_type, path) = spec
+            .split_once('=')
+            .with_context(|| format!("Expected TYPE=FILE for --migrate, got '{spec}'"))?;
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read message definition {path}"))?;
+        let migration = Migration::msgdef(message_type, &text)
+            .with_context(|| format!("Invalid message definition {path} for {message_type}"))?;
+        migrations.register(migration);
+    }
+    Ok(migrations)
+}
+
+/// Key of a reader connection in the writer connection map
+fn connection_key(connection: &Connection) -> (String, String) {
+    (connection.topic.clone(), connection.message_type.clone())
+}
+
+/// High-performance raw message copying (similar to ROS2 bag convert)
+fn copy_raw_messages(reader: &mut Reader, writer: &mut Writer, args: &CopyArgs) -> Result<()> {
+    if args.verbose {
+        println!("Starting high-performance raw copy...");
+    }
+    let start_time = std::time::Instant::now();
+
+    // Use batch reading for maximum performance
+    let raw_messages = reader
+        .read_raw_messages_batch(Some(args.connections), args.start, args.end)
+        .context("Failed to read raw messages")?;
+
+    if args.verbose {
+        println!(
+            "Read {} messages in {:?}",
+            raw_messages.len(),
+            start_time.elapsed()
+        );
+    }
+
+    let write_start = std::time::Instant::now();
+
+    // Convert to the format expected by write_raw_messages_batch, using the connection map
+    let batch_messages: Result<Vec<(Connection, u64, Vec<u8>)>> = raw_messages
+        .into_iter()
+        .filter_map(|msg| {
+            let w_conn = args
+                .conn_map
+                .get(&connection_key(&msg.connection))
+                .with_context(|| {
+                    format!(
+                        "Connection for topic '{}' not found in writer",
+                        msg.connection.topic
+                    )
+                });
+            let (t_conn, w_conn) = match w_conn {
+                Ok(connections) => connections,
+                Err(e) => return Some(Err(e)),
+            };
+            let data = match migrate(args, &msg.connection, msg.timestamp, msg.raw_data) {
+                Ok(data) => data,
+                Err(e) => return Some(Err(e)),
+            };
+            let (timestamp, data) = apply_transform(args, t_conn, w_conn, msg.timestamp, data)?;
+            Some(Ok((w_conn.clone(), timestamp, data)))
+        })
+        .collect();
+
+    // Process in batches to avoid memory pressure
+    let mut total_written = 0;
+    for chunk in batch_messages?.chunks(args.batch_size) {
+        writer
+            .write_raw_messages_batch(chunk)
+            .context("Failed to write raw message batch")?;
+        total_written += chunk.len();
+    }
+
+    if args.verbose {
+        println!(
+            "Wrote {} messages in {:?}",
+            total_written,
+            write_start.elapsed()
+        );
+        println!("Total time: {:?}", start_time.elapsed());
+    }
+
+    Ok(())
+}
+
+/// Standard message copying with deserialization/serialization
+fn copy_messages(reader: &mut Reader, writer: &mut Writer, args: &CopyArgs) -> Result<()> {
+    if args.verbose {
+        println!("Starting standard copy...");
+    }
+    let start_time = std::time::Instant::now();
+
+    let messages = reader
+        .messages_filtered(Some(args.connections), args.start, args.end)
+        .context("Failed to get message iterator")?;
+
+    let mut count = 0;
+    for message_result in messages {
+        let message = message_result.context("Failed to read message")?;
+
+        let (t_conn, w_conn) = args
+            .conn_map
+            .get(&connection_key(&message.connection))
+            .with_context(|| {
+                format!(
+                    "Connection for topic '{}' not found in writer",
+                    message.connection.topic
+                )
+            })?;
+
+        let data = migrate(args, &message.connection, message.timestamp, message.data)?;
+        let Some((timestamp, data)) =
+            apply_transform(args, t_conn, w_conn, message.timestamp, data)
+        else {
+            continue;
+        };
+        writer
+            .write(w_conn, timestamp, &data)
+            .context("Failed to write message")?;
+
+        count += 1;
+    }
+
+    if args.verbose {
+        println!("Copied {} messages in {:?}", count, start_time.elapsed());
+    }
+    Ok(())
+}
+
+/// Upgrade a message with the configured migrations; unlike transforms, a message
+/// that cannot be upgraded fails the copy, since its connection has the new layout
+fn migrate(
+    args: &CopyArgs,
+    connection: &Connection,
+    timestamp: u64,
+    data: Vec<u8>,
+) -> Result<Vec<u8>> {
+    if args.migrations.is_empty() {
+        return Ok(data);
+    }
+    // Migrations tell layouts apart by the definitions of the reader connections
+    let key = connection_key(connection);
+    let connection = args
+        .connections
+        .iter()
+        .find(|c| connection_key(c) == key)
+        .unwrap_or(connection);
+    if !args.migrations.applies_to(connection) {
+        return Ok(data);
+    }
+    args.migrations.migrate(connection, &data).with_context(|| {
+        format!(
+            "Failed to migrate message on {} at {}",
+            connection.topic, timestamp
+        )
+    })
+}
+
+/// Run the configured transforms on a message of `connection`, written on `output`;
+/// `None` if they drop it
+///
+/// A message that cannot be transformed is copied unchanged, or dropped if the
+/// transforms write its connection with another type.
+fn apply_transform(
+    args: &CopyArgs,
+    connection: &Connection,
+    output: &Connection,
+    timestamp: u64,
+    data: Vec<u8>,
+) -> Option<(u64, Vec<u8>)> {
+    if !args.transforms.applies_to(connection) {
+        return Some((timestamp, data));
+    }
+    match args.transforms.transform(connection, timestamp, &data) {
+        Ok(output) => output,
+        Err(e) if output.message_type != connection.message_type => {
+            eprintln!(
+                "Warning: dropping message on {} at {}: {}",
+                connection.topic, timestamp, e
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: copying message on {} at {} unchanged: {}",
+                connection.topic, timestamp, e
+            );
+            Some((timestamp, data))
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! `rosbags info`: display a summary of a ROS2 bag like `ros2 bag info`
+//!
+//! Prints the storage format, duration, size, compression and, per topic, the
+//! message type, count, average frequency and offered QoS. Everything is read from
+//! metadata.yaml, so the summary is instant even for large bags.
+//!
+//! With `--verify` the storage files are opened and the message counts and time
+//! range of metadata.yaml are checked against their contents, and stored type hashes
+//! against the stored message definitions, as well as the storage files against
+//! their recorded SHA-256 checksums; mismatches are listed and the tool exits with
+//! status 1.
+//!
+//! A bag inside a `.tar`, `.tar.gz` or `.zip` archive is read from the archive
+//! without extracting it; its storage files are loaded to print the summary.
+//! A single `.db3` or `.mcap` file without metadata.yaml is summarized from its
+//! contents.
+//!
+//! Usage: rosbags info <bag_path> [--verify] [--archive-bag <dir>]
+//!
+//! Also built as the standalone `rosbags-info` binary.
+
+use clap::Parser;
+use rosbags_rs::metadata::{
+    BagFileInformation, ChecksumMismatch, QosProfilesField, TopicWithMessageCount,
+};
+use rosbags_rs::types::{QosDurability, QosHistory, QosReliability};
+use rosbags_rs::{read_bag_metadata_fast, time, BagMetadata, Reader, ReaderError};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Print bag information like `ros2 bag info`
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct InfoArgs {
+    /// Bag directory, single storage file, or archive holding one bag
+    pub bag: PathBuf,
+
+    /// Cross-check metadata.yaml against the contents of the storage files
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Bag directory inside the archive, if it holds several bags
+    #[cfg(feature = "archive")]
+    #[arg(long, value_name = "DIR")]
+    pub archive_bag: Option<PathBuf>,
+}
+
+/// Print the summary of the bag in `args`
+pub fn run(args: InfoArgs) -> Result<(), ReaderError> {
+    #[cfg(feature = "archive")]
+    if rosbags_rs::archive::is_archive(&args.bag) {
+        return run_archive(&args);
+    }
+
+    if args.bag.is_file() {
+        return run_storage_file(&args);
+    }
+
+    let metadata = read_bag_metadata_fast(&args.bag)?;
+    let size = total_size(&args.bag, &metadata.info().relative_file_paths);
+    print_summary(&metadata, &format_size(size));
+
+    if args.verify {
+        let mut reader = Reader::new(&args.bag)?;
+        reader.open()?;
+        report_verification(&verify(&reader, &metadata)?);
+    }
+
+    Ok(())
+}
+
+/// Print the summary of the single storage file at `args.bag`, read from its contents
+fn run_storage_file(args: &InfoArgs) -> Result<(), ReaderError> {
+    let mut reader = Reader::new(&args.bag)?;
+    reader.open()?;
+    let metadata = reader.metadata().cloned().expect("readers have metadata");
+    let size = std::fs::metadata(&args.bag)?.len();
+    print_summary(&metadata, &format_size(size));
+
+    if args.verify {
+        report_verification(&verify(&reader, &metadata)?);
+    }
+
+    Ok(())
+}
+
+/// Print the summary of a bag in the archive at `args.bag`
+#[cfg(feature = "archive")]
+fn run_archive(args: &InfoArgs) -> Result<(), ReaderError> {
+    let options = rosbags_rs::ReaderOptions::default();
+    let mut reader = rosbags_rs::archive::open(&args.bag, args.archive_bag.as_deref(), options)?;
+    reader.open()?;
+    let metadata = reader.metadata().cloned().expect("readers have metadata");
+    let size = std::fs::metadata(&args.bag)?.len();
+    print_summary(&metadata, &format!("{} (archive)", format_size(size)));
+
+    if args.verify {
+        report_verification(&verify(&reader, &metadata)?);
+    }
+
+    Ok(())
+}
+
+/// Print the summary of `metadata`, with the bag size already formatted
+fn print_summary(metadata: &BagMetadata, size: &str) {
+    let info = metadata.info();
+    let duration_s = time::secs_f64(metadata.duration());
+
+    println!("Files:             {}", info.relative_file_paths.join(", "));
+    println!("Bag size:          {size}");
+    println!("Storage id:        {}", storage_id(info));
+    if let Some(distro) = &info.ros_distro {
+        println!("ROS Distro:        {distro}");
+    }
+    println!("Duration:          {duration_s:.9}s");
+    println!(
+        "Start:             {}",
+        format_timestamp(metadata.start_time())
+    );
+    println!(
+        "End:               {}",
+        format_timestamp(metadata.end_time())
+    );
+    println!("Messages:          {}", metadata.message_count());
+    println!("Compression:       {}", format_compression(info));
+
+    let mut topics = info.topics_with_message_count.iter();
+    if let Some(first) = topics.next() {
+        println!("Topic information: {}", format_topic(first, duration_s));
+        for topic in topics {
+            println!("                   {}", format_topic(topic, duration_s));
+        }
+    }
+}
+
+/// Print the outcome of `verify`, exiting with status 1 on mismatches
+fn report_verification(mismatches: &[String]) {
+    if mismatches.is_empty() {
+        println!("Verification:      OK (metadata matches storage)");
+    } else {
+        println!("Verification:      FAILED");
+        for mismatch in mismatches {
+            println!("                   {mismatch}");
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Compare metadata.yaml with the contents of the storage opened by `reader`,
+/// returning the differences
+fn verify(reader: &Reader, metadata: &BagMetadata) -> Result<Vec<String>, ReaderError> {
+    let mut mismatches = Vec::new();
+
+    let files = reader.files()?;
+    let stored_count: u64 = files.iter().map(|file| file.message_count).sum();
+    if stored_count != metadata.message_count() {
+        mismatches.push(format!(
+            "Messages: metadata {} | storage {stored_count}",
+            metadata.message_count()
+        ));
+    }
+
+    for (recorded, stored) in metadata.info().files.iter().zip(&files) {
+        if recorded.message_count != stored.message_count {
+            mismatches.push(format!(
+                "File {}: {} messages in metadata | {} in storage",
+                recorded.path, recorded.message_count, stored.message_count
+            ));
+        }
+    }
+
+    let with_messages = files.iter().filter(|file| file.message_count > 0);
+    let start = with_messages.clone().map(|file| file.start_time).min();
+    let end = with_messages.map(|file| file.end_time).max();
+    if let (Some(start), Some(end)) = (start, end) {
+        if start != metadata.start_time() {
+            mismatches.push(format!(
+                "Start: metadata {} | storage {start}",
+                metadata.start_time()
+            ));
+        }
+        if end != metadata.end_time() {
+            mismatches.push(format!(
+                "End: metadata {} | storage {end}",
+                metadata.end_time()
+            ));
+        }
+    }
+
+    let mut stored_topics: BTreeMap<&str, (&str, u64)> = BTreeMap::new();
+    for connection in reader.connections() {
+        let entry = stored_topics
+            .entry(&connection.topic)
+            .or_insert((&connection.message_type, 0));
+        entry.1 += connection.message_count;
+    }
+    for topic in &metadata.info().topics_with_message_count {
+        let name = topic.topic_metadata.name.as_str();
+        match stored_topics.remove(name) {
+            None => mismatches.push(format!("Topic {name}: missing from storage")),
+            Some((message_type, count)) => {
+                if message_type != topic.topic_metadata.message_type {
+                    mismatches.push(format!(
+                        "Topic {name}: type {} in metadata | {message_type} in storage",
+                        topic.topic_metadata.message_type
+                    ));
+                }
+                if count != topic.message_count {
+                    mismatches.push(format!(
+                        "Topic {name}: count {} in metadata | {count} in storage",
+                        topic.message_count
+                    ));
+                }
+            }
+        }
+    }
+    for (name, (_, count)) in stored_topics {
+        mismatches.push(format!(
+            "Topic {name}: {count} messages in storage, missing from metadata"
+        ));
+    }
+    for mismatch in reader.type_hash_mismatches() {
+        mismatches.push(mismatch.to_string());
+    }
+
+    // Archive bags are read from memory, so only bag directories have files to hash
+    if metadata.has_checksums() && !reader.bag_path().as_os_str().is_empty() {
+        for mismatch in reader.verify_checksums()? {
+            mismatches.push(match mismatch {
+                ChecksumMismatch::Missing { path } => format!("File {path}: missing"),
+                ChecksumMismatch::Unrecorded { path } => {
+                    format!("File {path}: no checksum in metadata")
+                }
+                ChecksumMismatch::Checksum {
+                    path,
+                    expected,
+                    actual,
+                } => format!("File {path}: sha256 metadata {expected} | file {actual}"),
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Total size of the storage files in bytes
+fn total_size(bag_path: &Path, files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| std::fs::metadata(bag_path.join(file)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Storage identifier, detected from the file extensions if metadata has none
+fn storage_id(info: &BagFileInformation) -> String {
+    if !info.storage_identifier.is_empty() {
+        return info.storage_identifier.clone();
+    }
+    for file in &info.relative_file_paths {
+        if file.ends_with(".db3") {
+            return "sqlite3".to_string();
+        } else if file.ends_with(".mcap") {
+            return "mcap".to_string();
+        }
+    }
+    "unknown".to_string()
+}
+
+/// Compression format and mode, e.g. `zstd (file)`
+fn format_compression(info: &BagFileInformation) -> String {
+    let mode = info.compression_mode.to_lowercase();
+    if info.compression_format.is_empty() || mode.is_empty() || mode == "none" {
+        "none".to_string()
+    } else {
+        format!("{} ({mode})", info.compression_format)
+    }
+}
+
+/// Size in human-readable units
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
+
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    if unit_index == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit_index])
+    }
+}
+
+/// Timestamp as UTC date and seconds since the epoch
+fn format_timestamp(timestamp_ns: u64) -> String {
+    let secs = timestamp_ns / time::NANOS_PER_SEC;
+    let nanos = timestamp_ns % time::NANOS_PER_SEC;
+    format!(
+        "{} ({secs}.{nanos:09})",
+        time::to_datetime(timestamp_ns).format("%b %e %Y %H:%M:%S%.9f")
+    )
+}
+
+/// One topic line; the frequency is averaged over the bag duration
+fn format_topic(topic: &TopicWithMessageCount, duration_s: f64) -> String {
+    let metadata = &topic.topic_metadata;
+    let frequency = if duration_s > 0.0 {
+        format!("{:.2} Hz", topic.message_count as f64 / duration_s)
+    } else {
+        "-".to_string()
+    };
+    format!(
+        "Topic: {} | Type: {} | Count: {} | Frequency: {frequency} | QoS: {} | Serialization Format: {}",
+        metadata.name,
+        metadata.message_type,
+        topic.message_count,
+        format_qos(&metadata.offered_qos_profiles),
+        metadata.serialization_format
+    )
+}
+
+/// Offered QoS profiles, e.g. `reliable/volatile/keep_last(10)`
+fn format_qos(profiles: &QosProfilesField) -> String {
+    let profiles = profiles.profiles();
+    if profiles.is_empty() {
+        return "-".to_string();
+    }
+    profiles
+        .iter()
+        .map(|profile| {
+            let reliability = match profile.reliability {
+                QosReliability::Reliable => "reliable",
+                QosReliability::BestEffort => "best_effort",
+                QosReliability::SystemDefault => "system_default",
+                QosReliability::BestAvailable => "best_available",
+                QosReliability::Unknown => "unknown",
+            };
+            let durability = match profile.durability {
+                QosDurability::Volatile => "volatile",
+                QosDurability::TransientLocal => "transient_local",
+                QosDurability::SystemDefault => "system_default",
+                QosDurability::BestAvailable => "best_available",
+                QosDurability::Unknown => "unknown",
+            };
+            let history = match profile.history {
+                QosHistory::KeepLast => format!("keep_last({})", profile.depth),
+                QosHistory::KeepAll => "keep_all".to_string(),
+                QosHistory::SystemDefault => "system_default".to_string(),
+                QosHistory::Unknown => "unknown".to_string(),
+            };
+            format!("{reliability}/{durability}/{history}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
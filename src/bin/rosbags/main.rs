@@ -0,0 +1,76 @@
+#![cfg(all(not(feature = "write-only"), feature = "bin-tools"))]
+//! `rosbags`: command line tool for ROS2 bag files
+//!
+//! Bundles the bag tools of this crate as subcommands:
+//!
+//! - `info`: summary of a bag like `ros2 bag info`, optionally verified against storage
+//! - `extract`: export one topic as images, point clouds or CSV
+//! - `convert`: rewrite a bag with another storage format or compression
+//! - `filter`: copy a bag with topic, time and image downscaling filters
+//! - `merge`: combine several bags into one, ordered by timestamp
+//! - `explode`: split a bag into one bag per topic or topic group
+//! - `reindex`: rebuild metadata.yaml from the storage files
+//! - `serve`: play a bag to Foxglove Studio over WebSocket
+//!
+//! Usage:
+//!   cargo run --bin rosbags -- <subcommand> [args...]
+//!
+//! Examples:
+//!   rosbags info ./my_bag --verify
+//!   rosbags convert ./my_bag ./my_bag_mcap --storage mcap
+//!   rosbags merge ./part_a ./part_b -o ./merged
+//!   rosbags explode ./my_bag -o ./topics --group cameras=/camera/**
+//!   rosbags serve ./my_bag --rate 2 --loop
+
+use clap::{Parser, Subcommand};
+
+mod convert;
+mod explode;
+mod extract;
+mod filter;
+mod info;
+mod merge;
+mod reindex;
+mod serve;
+
+/// Inspect, extract and rewrite ROS2 bag files
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print bag information like `ros2 bag info`
+    Info(info::InfoArgs),
+    /// Export one topic as images, point clouds or CSV
+    Extract(extract::ExtractArgs),
+    /// Rewrite a bag with another storage format or compression
+    Convert(convert::ConvertArgs),
+    /// Copy a bag with optional topic and time filtering
+    Filter(filter::FilterArgs),
+    /// Merge several bags into one, ordered by timestamp
+    Merge(merge::MergeArgs),
+    /// Split a bag into one bag per topic or topic group
+    Explode(explode::ExplodeArgs),
+    /// Rebuild metadata.yaml from the storage files
+    Reindex(reindex::ReindexArgs),
+    /// Play a bag to Foxglove Studio over WebSocket
+    Serve(serve::ServeArgs),
+}
+
+fn main() -> anyhow::Result<()> {
+    match Args::parse().command {
+        Command::Info(args) => info::run(args)?,
+        Command::Extract(args) => extract::run(args).map_err(|e| anyhow::anyhow!("{e}"))?,
+        Command::Convert(args) => convert::run(args)?,
+        Command::Filter(args) => filter::run(args)?,
+        Command::Merge(args) => merge::run(args)?,
+        Command::Explode(args) => explode::run(args)?,
+        Command::Reindex(args) => reindex::run(args)?,
+        Command::Serve(args) => serve::run(args)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! `rosbags merge`: combine several bags into one
+//!
+//! Messages of all inputs are written in timestamp order. Topics present in more
+//! than one input with the same message type share a single connection in the
+//! output; a topic with different types gets a connection per type.
+//!
+//! Usage:
+//!   rosbags merge <input_bag>... -o <output_bag> [--storage mcap] [--topics topic1,topic2,...]
+
+use anyhow::{Context, Result};
+use clap::Args;
+use rosbags_rs::types::{Connection, RawMessage, StoragePlugin};
+use rosbags_rs::{Reader, Writer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Merge several bags into one, ordered by timestamp
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    /// Input bag directories
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+
+    /// Output bag directory
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Storage plugin to use for output (sqlite3 or mcap)
+    #[arg(long, default_value = "sqlite3")]
+    storage: String,
+
+    /// Topics to include (comma-separated list, if empty, all topics are included)
+    #[arg(short, long, value_delimiter = ',')]
+    topics: Vec<String>,
+}
+
+/// Merge the input bags of `args` into the output bag
+pub fn run(args: MergeArgs) -> Result<()> {
+    let storage_plugin = match args.storage.as_str() {
+        "sqlite3" => StoragePlugin::Sqlite3,
+        "mcap" => StoragePlugin::Mcap,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unsupported storage plugin: {}. Use 'sqlite3' or 'mcap'",
+                args.storage
+            ));
+        }
+    };
+
+    let mut readers = Vec::with_capacity(args.inputs.len());
+    for input in &args.inputs {
+        let mut reader = Reader::new(input).context("Failed to create reader")?;
+        reader
+            .open()
+            .with_context(|| format!("Failed to open input bag {}", input.display()))?;
+        readers.push(reader);
+    }
+
+    let mut writer =
+        Writer::new(&args.output, None, Some(storage_plugin)).context("Failed to create writer")?;
+    writer.open().context("Failed to open output bag")?;
+
+    // One output connection per topic and type, shared by all inputs
+    let mut conn_map: HashMap<(String, String), Connection> = HashMap::new();
+    let mut selected: Vec<Vec<Connection>> = Vec::with_capacity(readers.len());
+    for (reader, input) in readers.iter().zip(&args.inputs) {
+        let connections: Vec<Connection> = reader
+            .connections()
+            .iter()
+            .filter(|conn| args.topics.is_empty() || args.topics.contains(&conn.topic))
+            .cloned()
+            .collect();
+        for conn in &connections {
+            let key = (conn.topic.clone(), conn.message_type.clone());
+            if conn_map.contains_key(&key) {
+                continue;
+            }
+            // A topic with another type in an earlier input gets its own connection
+            if let Some(((_, message_type), _)) =
+                conn_map.iter().find(|((topic, _), _)| *topic == conn.topic)
+            {
+                eprintln!(
+                    "Warning: topic {} has type {} in {} but {} in an earlier input",
+                    conn.topic,
+                    conn.message_type,
+                    input.display(),
+                    message_type
+                );
+            }
+            let w_conn = writer.add_connection_from(conn)?;
+            conn_map.insert(key, w_conn);
+        }
+        selected.push(connections);
+    }
+
+    let mut streams = Vec::with_capacity(readers.len());
+    for (reader, connections) in readers.iter().zip(&selected) {
+        streams.push(reader.raw_messages_filtered(Some(connections), None, None)?);
+    }
+    let mut heads: Vec<Option<RawMessage>> = streams
+        .iter_mut()
+        .map(|stream| stream.next().transpose())
+        .collect::<rosbags_rs::Result<_>>()?;
+
+    // K-way merge on the head message of every input
+    let mut written = 0u64;
+    while let Some(index) = heads
+        .iter()
+        .enumerate()
+        .filter_map(|(index, head)| head.as_ref().map(|m| (m.timestamp, index)))
+        .min()
+        .map(|(_, index)| index)
+    {
+        let message = heads[index].take().unwrap();
+        let connection = &message.connection;
+        let w_conn = &conn_map[&(connection.topic.clone(), connection.message_type.clone())];
+        writer.write_raw_message(w_conn, message.timestamp, &message.raw_data)?;
+        written += 1;
+        heads[index] = streams[index].next().transpose()?;
+    }
+
+    writer.close().context("Failed to close output bag")?;
+    println!(
+        "Merged {} bags into {}: {} connections, {written} messages",
+        args.inputs.len(),
+        args.output.display(),
+        conn_map.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! `rosbags reindex`: rebuild metadata.yaml from the storage files, like `ros2 bag reindex`
+//!
+//! Usage:
+//!   rosbags reindex <bag_path> [--storage sqlite3|mcap] [--index]
+
+use anyhow::Result;
+use clap::Args;
+use rosbags_rs::types::StoragePlugin;
+use rosbags_rs::Reader;
+use std::path::PathBuf;
+
+/// Rebuild metadata.yaml from the storage files
+#[derive(Args, Debug)]
+pub struct ReindexArgs {
+    /// Bag directory
+    bag: PathBuf,
+
+    /// Only index storage files of this plugin (sqlite3 or mcap)
+    #[arg(long)]
+    storage: Option<String>,
+
+    /// Also write a sidecar index next to every SQLite3 database for fast per-topic
+    /// counts and seeks
+    #[arg(long)]
+    index: bool,
+}
+
+/// Rewrite metadata.yaml of the bag in `args`
+pub fn run(args: ReindexArgs) -> Result<()> {
+    let storage = match args.storage.as_deref() {
+        None => None,
+        Some("sqlite3") => Some(StoragePlugin::Sqlite3),
+        Some("mcap") => Some(StoragePlugin::Mcap),
+        Some(other) => {
+            return Err(anyhow::anyhow!(
+                "Unsupported storage plugin: {other}. Use 'sqlite3' or 'mcap'"
+            ));
+        }
+    };
+
+    let metadata = Reader::reindex(&args.bag, storage)?;
+    let info = metadata.info();
+    println!(
+        "Reindexed {}: {} files, {} topics, {} messages",
+        args.bag.display(),
+        info.files.len(),
+        info.topics_with_message_count.len(),
+        metadata.message_count()
+    );
+
+    if args.index {
+        let mut reader = Reader::new(&args.bag)?;
+        reader.open()?;
+        for path in reader.build_index()? {
+            println!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
+}
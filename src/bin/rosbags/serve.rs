@@ -0,0 +1,60 @@
+//! `rosbags serve`: play a bag to Foxglove Studio over the Foxglove WebSocket protocol
+//!
+//! Usage:
+//!   rosbags serve <bag_path> [--port 8765] [--rate 1.0] [--loop] [--topics /a /b]
+
+use anyhow::Result;
+use clap::Args;
+use rosbags_rs::foxglove::{FoxgloveServer, DEFAULT_PORT};
+use rosbags_rs::{Player, Reader};
+use std::path::PathBuf;
+
+/// Play a bag to Foxglove Studio
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Bag directory
+    bag: PathBuf,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Port to listen on
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    port: u16,
+
+    /// Playback rate multiplier
+    #[arg(long, default_value_t = 1.0)]
+    rate: f64,
+
+    /// Restart from the beginning after the last message
+    #[arg(long = "loop")]
+    looping: bool,
+
+    /// Only play these topics
+    #[arg(long, num_args = 1..)]
+    topics: Vec<String>,
+}
+
+/// Serve the bag in `args` to one Foxglove Studio client after another
+pub fn run(args: ServeArgs) -> Result<()> {
+    let reader = Reader::builder(&args.bag).open()?;
+    let server = FoxgloveServer::bind((args.host.as_str(), args.port))?;
+    println!(
+        "Serving {} at ws://{}",
+        args.bag.display(),
+        server.local_addr()?
+    );
+
+    let topics: Vec<&str> = args.topics.iter().map(String::as_str).collect();
+    loop {
+        let mut player = Player::new(&reader).rate(args.rate).looping(args.looping);
+        if !topics.is_empty() {
+            player = player.topics(&topics);
+        }
+        match server.serve(&player) {
+            Ok(sent) => println!("Client finished after {sent} messages"),
+            Err(e) => eprintln!("Client failed: {e}"),
+        }
+    }
+}
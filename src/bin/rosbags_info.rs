@@ -0,0 +1,13 @@
+#![cfg(not(feature = "write-only"))]
+//! Display a summary of a ROS2 bag like `ros2 bag info`; the same tool as `rosbags info`
+//!
+//! Usage: cargo run --bin rosbags-info -- <bag_path> [--verify]
+
+use clap::Parser;
+
+#[path = "rosbags/info.rs"]
+mod info;
+
+fn main() -> Result<(), rosbags_rs::ReaderError> {
+    info::run(info::InfoArgs::parse())
+}
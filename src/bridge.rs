@@ -0,0 +1,396 @@
+//! Publishing of played-back messages to a live ROS2 graph
+//!
+//! [`RosBridge`] drives a [`Player`] and hands every delivered message to a ROS2 client
+//! library through the [`RosMiddleware`] trait. [`ZenohNode`] implements it for graphs
+//! running `rmw_zenoh`; other bindings (rclrs, DDS) can implement it as well. Messages
+//! are forwarded in their recorded CDR form, each topic gets a publisher whose QoS is
+//! adapted from the recorded offers, and simulated time can be published on `/clock`
+//! so nodes running with `use_sim_time` follow the bag.
+//!
+//! [`ZenohNode`]: crate::rmw_zenoh::ZenohNode
+
+use crate::error::Result;
+use crate::messages::{Clock, Time, ToCdr};
+use crate::player::Player;
+use crate::types::{
+    Connection, QosDurability, QosHistory, QosLiveliness, QosProfile, QosReliability, QosTime,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Topic simulated time is published on
+pub const CLOCK_TOPIC: &str = "/clock";
+
+/// Message type of simulated time
+pub const CLOCK_TYPE: &str = "rosgraph_msgs/msg/Clock";
+
+/// Default `/clock` publishing frequency in Hz, as used by `ros2 bag play --clock`
+pub const DEFAULT_CLOCK_FREQUENCY: f64 = 40.0;
+
+/// Queue depth used when the recorded offers do not specify one
+const DEFAULT_DEPTH: u32 = 10;
+
+/// Binding to a ROS2 client library
+pub trait RosMiddleware {
+    /// Publisher handle created by [`RosMiddleware::create_publisher`]
+    type Publisher;
+
+    /// Create a publisher for the topic of `connection` with the given QoS
+    ///
+    /// `connection` describes the recorded topic: name, message type, definition and
+    /// type hash. Its offered QoS profiles are already folded into `qos`.
+    fn create_publisher(
+        &mut self,
+        connection: &Connection,
+        qos: &QosProfile,
+    ) -> Result<Self::Publisher>;
+
+    /// Publish one CDR-serialized message, including its encapsulation header
+    fn publish(&mut self, publisher: &Self::Publisher, data: &[u8]) -> Result<()>;
+}
+
+/// QoS for republishing a topic, adapted from the profiles offered while recording
+///
+/// Follows `ros2 bag play`: the publisher is reliable or transient-local only if every
+/// recorded publisher was, so that all original subscribers can still match. Without
+/// recorded offers the ROS2 default profile (reliable, volatile, depth 10) is used.
+pub fn playback_qos(offers: &[QosProfile]) -> QosProfile {
+    let mut qos = QosProfile {
+        history: QosHistory::KeepLast,
+        depth: DEFAULT_DEPTH,
+        reliability: QosReliability::Reliable,
+        durability: QosDurability::Volatile,
+        ..QosProfile::default()
+    };
+    let Some(first) = offers.first() else {
+        return qos;
+    };
+
+    if !offers
+        .iter()
+        .all(|offer| offer.reliability == QosReliability::Reliable)
+    {
+        qos.reliability = QosReliability::BestEffort;
+    }
+    if offers
+        .iter()
+        .all(|offer| offer.durability == QosDurability::TransientLocal)
+    {
+        qos.durability = QosDurability::TransientLocal;
+    }
+    if let Some(depth) = offers.iter().map(|offer| offer.depth).max() {
+        if depth > 0 {
+            qos.depth = depth;
+        }
+    }
+    // Timing constraints are only kept when all publishers agreed on them
+    if offers.iter().all(|offer| offer.deadline == first.deadline) {
+        qos.deadline = first.deadline.clone();
+    }
+    if offers.iter().all(|offer| offer.lifespan == first.lifespan) {
+        qos.lifespan = first.lifespan.clone();
+    }
+    if offers.iter().all(|offer| {
+        offer.liveliness == first.liveliness
+            && offer.liveliness_lease_duration == first.liveliness_lease_duration
+    }) {
+        qos.liveliness = first.liveliness.clone();
+        qos.liveliness_lease_duration = first.liveliness_lease_duration.clone();
+    }
+    qos
+}
+
+/// QoS of the `/clock` publisher (best effort, latest value only)
+pub fn clock_qos() -> QosProfile {
+    QosProfile {
+        history: QosHistory::KeepLast,
+        depth: 1,
+        reliability: QosReliability::BestEffort,
+        durability: QosDurability::Volatile,
+        liveliness: QosLiveliness::Automatic,
+        liveliness_lease_duration: QosTime::default(),
+        ..QosProfile::default()
+    }
+}
+
+/// Publishes [`Player`] output to a ROS2 graph
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::bridge::{RosBridge, RosMiddleware};
+/// use rosbags_rs::types::{Connection, QosProfile};
+/// use rosbags_rs::{Player, Reader, Result};
+///
+/// struct Node; // binding to a ROS2 client library
+///
+/// impl RosMiddleware for Node {
+///     type Publisher = String;
+///     fn create_publisher(&mut self, connection: &Connection, _: &QosProfile) -> Result<String> {
+///         Ok(connection.topic.clone())
+///     }
+///     fn publish(&mut self, topic: &String, data: &[u8]) -> Result<()> {
+///         println!("{topic}: {} bytes", data.len());
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+/// let mut bridge = RosBridge::new(Node).publish_clock(100.0);
+/// bridge.play(&Player::new(&reader).rate(0.5))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RosBridge<M: RosMiddleware> {
+    middleware: M,
+    clock_frequency: Option<f64>,
+    qos_overrides: HashMap<String, QosProfile>,
+}
+
+impl<M: RosMiddleware> RosBridge<M> {
+    /// Create a bridge publishing through `middleware`
+    pub fn new(middleware: M) -> Self {
+        Self {
+            middleware,
+            clock_frequency: None,
+            qos_overrides: HashMap::new(),
+        }
+    }
+
+    /// Publish the bag time on `/clock` at most `frequency` times per second
+    ///
+    /// The clock is published just before a message is delivered, so it never runs
+    /// ahead of the data. A frequency of 0 publishes it before every message.
+    pub fn publish_clock(mut self, frequency: f64) -> Self {
+        self.clock_frequency = Some(frequency.max(0.0));
+        self
+    }
+
+    /// Use `qos` for `topic` instead of adapting the recorded offers
+    pub fn qos_override(mut self, topic: impl Into<String>, qos: QosProfile) -> Self {
+        self.qos_overrides.insert(topic.into(), qos);
+        self
+    }
+
+    /// The underlying middleware binding
+    pub fn middleware(&self) -> &M {
+        &self.middleware
+    }
+
+    /// Consume the bridge, returning the middleware binding
+    pub fn into_inner(self) -> M {
+        self.middleware
+    }
+
+    /// Create publishers and play `player`, publishing every delivered message
+    ///
+    /// Returns the number of published messages. The first publishing error stops
+    /// playback and is returned.
+    pub fn play(&mut self, player: &Player) -> Result<u64> {
+        let publishers = self.create_publishers(player.connections())?;
+        let clock = match self.clock_frequency {
+            Some(frequency) => Some((
+                self.middleware
+                    .create_publisher(&Connection::new(CLOCK_TOPIC, CLOCK_TYPE)?, &clock_qos())?,
+                (frequency > 0.0).then(|| Duration::from_secs_f64(1.0 / frequency)),
+            )),
+            None => None,
+        };
+
+        let controls = player.controls();
+        let middleware = &mut self.middleware;
+        let mut last_clock: Option<Instant> = None;
+        let mut error = None;
+        let mut published = 0u64;
+
+        player.play(|message| {
+            if error.is_some() {
+                return;
+            }
+            let mut publish = || -> Result<()> {
+                if let Some((publisher, period)) = &clock {
+                    let now = Instant::now();
+                    let due = match (last_clock, period) {
+                        (Some(last), Some(period)) => now.duration_since(last) >= *period,
+                        _ => true,
+                    };
+                    if due {
+                        let time = Clock {
+                            clock: Time::from_nanos(message.timestamp),
+                        };
+                        middleware.publish(publisher, &time.to_cdr_bytes())?;
+                        last_clock = Some(now);
+                    }
+                }
+                let key = (
+                    message.connection.topic.clone(),
+                    message.connection.message_type.clone(),
+                );
+                if let Some(publisher) = publishers.get(&key) {
+                    middleware.publish(publisher, &message.data)?;
+                    published += 1;
+                }
+                Ok(())
+            };
+            if let Err(e) = publish() {
+                error = Some(e);
+                controls.stop();
+            }
+        })?;
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(published),
+        }
+    }
+
+    /// One publisher per topic and type, combining the offers of duplicate connections
+    fn create_publishers(
+        &mut self,
+        connections: &[Connection],
+    ) -> Result<HashMap<(String, String), M::Publisher>> {
+        let mut offers: Vec<(&Connection, Vec<QosProfile>)> = Vec::new();
+        for connection in connections {
+            match offers.iter_mut().find(|(first, _)| {
+                first.topic == connection.topic && first.message_type == connection.message_type
+            }) {
+                Some((_, profiles)) => {
+                    profiles.extend(connection.offered_qos_profiles.iter().cloned())
+                }
+                None => offers.push((connection, connection.offered_qos_profiles.clone())),
+            }
+        }
+
+        let mut publishers = HashMap::new();
+        for (connection, profiles) in offers {
+            let qos = match self.qos_overrides.get(&connection.topic) {
+                Some(qos) => qos.clone(),
+                None => playback_qos(&profiles),
+            };
+            let publisher = self.middleware.create_publisher(connection, &qos)?;
+            publishers.insert(
+                (connection.topic.clone(), connection.message_type.clone()),
+                publisher,
+            );
+        }
+        Ok(publishers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::CdrDeserializer;
+    use crate::error::BagError;
+    use crate::messages::FromCdr;
+    use crate::reader::Reader;
+
+    #[derive(Default)]
+    struct Recorder {
+        publishers: Vec<(String, String, QosProfile)>,
+        published: Vec<(usize, Vec<u8>)>,
+        fail_after: Option<usize>,
+    }
+
+    impl RosMiddleware for Recorder {
+        type Publisher = usize;
+
+        fn create_publisher(&mut self, connection: &Connection, qos: &QosProfile) -> Result<usize> {
+            self.publishers.push((
+                connection.topic.clone(),
+                connection.message_type.clone(),
+                qos.clone(),
+            ));
+            Ok(self.publishers.len() - 1)
+        }
+
+        fn publish(&mut self, publisher: &usize, data: &[u8]) -> Result<()> {
+            if self.fail_after == Some(self.published.len()) {
+                return Err(BagError::generic("publisher closed"));
+            }
+            self.published.push((*publisher, data.to_vec()));
+            Ok(())
+        }
+    }
+
+    fn reader() -> Reader {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        reader
+    }
+
+    #[test]
+    fn test_playback_qos_adapts_offers() {
+        let reliable_latched = QosProfile {
+            history: QosHistory::KeepLast,
+            depth: 1,
+            reliability: QosReliability::Reliable,
+            durability: QosDurability::TransientLocal,
+            ..QosProfile::default()
+        };
+        let qos = playback_qos(std::slice::from_ref(&reliable_latched));
+        assert_eq!(qos.reliability, QosReliability::Reliable);
+        assert_eq!(qos.durability, QosDurability::TransientLocal);
+        assert_eq!(qos.depth, 1);
+
+        let best_effort = QosProfile {
+            reliability: QosReliability::BestEffort,
+            durability: QosDurability::Volatile,
+            depth: 5,
+            ..reliable_latched.clone()
+        };
+        let qos = playback_qos(&[reliable_latched, best_effort]);
+        assert_eq!(qos.reliability, QosReliability::BestEffort);
+        assert_eq!(qos.durability, QosDurability::Volatile);
+        assert_eq!(qos.depth, 5);
+
+        let qos = playback_qos(&[]);
+        assert_eq!(qos.reliability, QosReliability::Reliable);
+        assert_eq!(qos.depth, DEFAULT_DEPTH);
+    }
+
+    #[test]
+    fn test_publishes_messages_and_clock() {
+        let reader = reader();
+        let topic = reader.connections()[0].topic.clone();
+        let player = Player::new(&reader).rate(1e6).topics(&[topic.as_str()]);
+
+        let mut bridge = RosBridge::new(Recorder::default()).publish_clock(0.0);
+        let published = bridge.play(&player).unwrap();
+        let recorder = bridge.into_inner();
+
+        assert_eq!(recorder.publishers.len(), 2);
+        assert_eq!(recorder.publishers[0].0, topic);
+        assert_eq!(recorder.publishers[1].0, CLOCK_TOPIC);
+        assert_eq!(recorder.published.len() as u64, published * 2);
+
+        // Clock first, carrying the timestamp of the message that follows
+        let first = reader
+            .messages_filtered(Some(&reader.connections()[..1]), None, None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let (clock_publisher, clock_data) = &recorder.published[0];
+        assert_eq!(*clock_publisher, 1);
+        let mut deserializer = CdrDeserializer::new(clock_data).unwrap();
+        let clock = Clock::from_cdr(&mut deserializer).unwrap();
+        assert_eq!(clock.clock, Time::from_nanos(first.timestamp));
+        assert_eq!(recorder.published[1], (0, first.data));
+    }
+
+    #[test]
+    fn test_publish_error_stops_playback() {
+        let reader = reader();
+        let player = Player::new(&reader).rate(1e6);
+        let mut bridge = RosBridge::new(Recorder {
+            fail_after: Some(3),
+            ..Recorder::default()
+        });
+
+        assert!(bridge.play(&player).is_err());
+        assert_eq!(bridge.middleware().published.len(), 3);
+        assert!(player.controls().is_stopped());
+    }
+}
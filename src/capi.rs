@@ -0,0 +1,519 @@
+//! C API of the reader
+//!
+//! Exposes [`Reader`] to C, C++ and other languages with a C FFI through
+//! `rosbags_`-prefixed functions, declared in `include/rosbags.h` (regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/rosbags.h` after changing this
+//! module). Build a shared or static library with
+//! `cargo rustc --release --features capi --crate-type cdylib` (or `staticlib`).
+//!
+//! Functions returning a pointer return `NULL` on failure and functions returning a
+//! [`RosbagsStatus`] return `ROSBAGS_STATUS_ERROR`; `rosbags_last_error` then describes
+//! the failure of the last call on the calling thread. Strings of connections stay
+//! valid until the reader is closed, the payload and strings of a message until the
+//! next call that iterates the same reader.
+//!
+//! ```c
+//! RosbagsReader *reader = rosbags_reader_open("path/to/bag");
+//! if (!reader) {
+//!     fprintf(stderr, "%s\n", rosbags_last_error());
+//!     return 1;
+//! }
+//! RosbagsMessage message;
+//! while (rosbags_reader_next_message(reader, &message) == ROSBAGS_STATUS_OK) {
+//!     printf("%s %" PRIu64 " %zu bytes\n", message.topic, message.timestamp, message.data_len);
+//! }
+//! rosbags_reader_close(reader);
+//! ```
+
+use crate::error::{ReaderError, Result};
+use crate::reader::Reader;
+use crate::types::{Connection, RawMessage};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Outcome of a C API call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RosbagsStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// Iteration has reached the end of the bag
+    End = 1,
+    /// The call failed; see `rosbags_last_error`
+    Error = -1,
+}
+
+/// Connection (topic and type) of a bag
+#[repr(C)]
+#[derive(Debug)]
+pub struct RosbagsConnection {
+    /// Connection id, as in `RosbagsMessage::connection_id`
+    pub id: u32,
+    /// Topic name
+    pub topic: *const c_char,
+    /// Message type, e.g. `sensor_msgs/msg/Image`
+    pub message_type: *const c_char,
+    /// Serialization format, usually `cdr`
+    pub serialization_format: *const c_char,
+    /// Stored message definition, empty if the bag has none
+    pub message_definition: *const c_char,
+    /// Number of messages
+    pub message_count: u64,
+}
+
+/// Message read by `rosbags_reader_next_message`
+#[repr(C)]
+#[derive(Debug)]
+pub struct RosbagsMessage {
+    /// Id of the connection of the message
+    pub connection_id: u32,
+    /// Topic name
+    pub topic: *const c_char,
+    /// Message type
+    pub message_type: *const c_char,
+    /// Log (receive) time in nanoseconds since the epoch
+    pub timestamp: u64,
+    /// Publish time in nanoseconds since the epoch, `timestamp` if not recorded
+    pub publish_time: u64,
+    /// Sequence number of the message on its channel (0 if not recorded)
+    pub sequence: u32,
+    /// Serialized (CDR) payload
+    pub data: *const u8,
+    /// Length of the payload in bytes
+    pub data_len: usize,
+}
+
+/// Connection with the C strings handed out for it
+struct CConnection {
+    connection: Connection,
+    topic: CString,
+    message_type: CString,
+    serialization_format: CString,
+    message_definition: CString,
+}
+
+impl CConnection {
+    fn new(connection: &Connection) -> Self {
+        Self {
+            connection: connection.clone(),
+            topic: c_string(&connection.topic),
+            message_type: c_string(&connection.message_type),
+            serialization_format: c_string(&connection.serialization_format),
+            message_definition: c_string(&connection.message_definition.data),
+        }
+    }
+}
+
+/// Open bag, opaque to C
+pub struct RosbagsReader {
+    reader: Reader,
+    /// Connections handed out to C, in the order of [`Reader::connections`]
+    connections: Vec<CConnection>,
+    /// Connections iterated over, all if `None`
+    selected: Option<Vec<Connection>>,
+    /// Remaining messages of the iteration, read on the first `next_message`
+    messages: Option<std::vec::IntoIter<Result<RawMessage>>>,
+    /// Message whose payload was last handed out
+    current: Option<RawMessage>,
+}
+
+impl RosbagsReader {
+    fn open(path: &str) -> Result<Self> {
+        let reader = Reader::builder(path).open()?;
+        let connections = reader.connections().iter().map(CConnection::new).collect();
+        Ok(Self {
+            reader,
+            connections,
+            selected: None,
+            messages: None,
+            current: None,
+        })
+    }
+
+    /// Restart iteration from the first message
+    fn rewind(&mut self) {
+        self.messages = None;
+        self.current = None;
+    }
+
+    /// Index of the C strings of `connection`, added for connections not listed by
+    /// the reader
+    fn connection_index(&mut self, connection: &Connection) -> usize {
+        let known = self.connections.iter().position(|c| {
+            c.connection.topic == connection.topic
+                && c.connection.message_type == connection.message_type
+        });
+        known.unwrap_or_else(|| {
+            self.connections.push(CConnection::new(connection));
+            self.connections.len() - 1
+        })
+    }
+
+    fn next_message(&mut self) -> Result<Option<RosbagsMessage>> {
+        if self.messages.is_none() {
+            let messages: Vec<_> = self
+                .reader
+                .raw_messages_filtered(self.selected.as_deref(), None, None)?
+                .collect();
+            self.messages = Some(messages.into_iter());
+        }
+        let Some(message) = self.messages.as_mut().and_then(Iterator::next) else {
+            self.current = None;
+            return Ok(None);
+        };
+        let message = message?;
+        let index = self.connection_index(&message.connection);
+        let message = self.current.insert(message);
+        let strings = &self.connections[index];
+        Ok(Some(RosbagsMessage {
+            connection_id: message.connection.id,
+            topic: strings.topic.as_ptr(),
+            message_type: strings.message_type.as_ptr(),
+            timestamp: message.timestamp,
+            publish_time: message.publish_time,
+            sequence: message.sequence,
+            data: message.raw_data.as_ptr(),
+            data_len: message.raw_data.len(),
+        }))
+    }
+}
+
+thread_local! {
+    /// Description of the last failure on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// C string of `value`, dropping interior NUL bytes
+fn c_string(value: &str) -> CString {
+    CString::new(value.replace('\0', "")).unwrap_or_default()
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(c_string(message)));
+}
+
+/// Run `call`, recording its error or panic and returning `failed` instead
+fn guard<T>(failed: T, call: impl FnOnce() -> Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(error)) => {
+            set_last_error(&error.to_string());
+            failed
+        }
+        Err(_) => {
+            set_last_error("rosbags panicked");
+            failed
+        }
+    }
+}
+
+/// Reader behind a handle, failing on `NULL`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+unsafe fn reader_ref<'a>(reader: *mut RosbagsReader) -> Result<&'a mut RosbagsReader> {
+    reader
+        .as_mut()
+        .ok_or_else(|| ReaderError::generic("reader is NULL"))
+}
+
+/// UTF-8 string behind a C string, failing on `NULL`
+///
+/// # Safety
+/// `value` must be `NULL` or a NUL-terminated string.
+unsafe fn str_ref<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    if value.is_null() {
+        return Err(ReaderError::generic(format!("{name} is NULL")));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| ReaderError::generic(format!("{name} is not valid UTF-8")))
+}
+
+/// Version of the library, e.g. `0.3.4`
+#[no_mangle]
+pub extern "C" fn rosbags_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Description of the last failed call on this thread, `NULL` if none failed
+///
+/// The string stays valid until the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn rosbags_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |e| e.as_ptr())
+    })
+}
+
+/// Open the bag directory at `path`, `NULL` on failure
+///
+/// # Safety
+/// `path` must be `NULL` or a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_open(path: *const c_char) -> *mut RosbagsReader {
+    guard(std::ptr::null_mut(), || {
+        let path = str_ref(path, "path")?;
+        Ok(Box::into_raw(Box::new(RosbagsReader::open(path)?)))
+    })
+}
+
+/// Close a reader and release everything handed out for it; `NULL` is ignored
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, which is
+/// invalid afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_close(reader: *mut RosbagsReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+/// Number of messages in the bag, 0 for `NULL`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_message_count(reader: *mut RosbagsReader) -> u64 {
+    reader.as_ref().map_or(0, |r| r.reader.message_count())
+}
+
+/// Time of the first message in nanoseconds since the epoch, 0 for `NULL`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_start_time(reader: *mut RosbagsReader) -> u64 {
+    reader.as_ref().map_or(0, |r| r.reader.start_time())
+}
+
+/// Time of the last message in nanoseconds since the epoch, 0 for `NULL`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_end_time(reader: *mut RosbagsReader) -> u64 {
+    reader.as_ref().map_or(0, |r| r.reader.end_time())
+}
+
+/// Number of connections, 0 for `NULL`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_connection_count(reader: *mut RosbagsReader) -> usize {
+    reader.as_ref().map_or(0, |r| r.reader.connections().len())
+}
+
+/// Fill `connection` with connection `index`, from 0 to
+/// `rosbags_reader_connection_count() - 1`
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and
+/// `connection` `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_connection(
+    reader: *mut RosbagsReader,
+    index: usize,
+    connection: *mut RosbagsConnection,
+) -> RosbagsStatus {
+    guard(RosbagsStatus::Error, || {
+        let reader = reader_ref(reader)?;
+        let out = connection
+            .as_mut()
+            .ok_or_else(|| ReaderError::generic("connection is NULL"))?;
+        let strings = reader.connections.get(index).ok_or_else(|| {
+            ReaderError::generic(format!("connection index {index} is out of range"))
+        })?;
+        *out = RosbagsConnection {
+            id: strings.connection.id,
+            topic: strings.topic.as_ptr(),
+            message_type: strings.message_type.as_ptr(),
+            serialization_format: strings.serialization_format.as_ptr(),
+            message_definition: strings.message_definition.as_ptr(),
+            message_count: strings.connection.message_count,
+        };
+        Ok(RosbagsStatus::Ok)
+    })
+}
+
+/// Only iterate over the `count` topics in `topics`, or over all topics if `count`
+/// is 0, and restart iteration
+///
+/// Fails if a topic is not in the bag.
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and `topics`
+/// point to `count` NUL-terminated UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_set_topics(
+    reader: *mut RosbagsReader,
+    topics: *const *const c_char,
+    count: usize,
+) -> RosbagsStatus {
+    guard(RosbagsStatus::Error, || {
+        let reader = reader_ref(reader)?;
+        let mut selected = Vec::new();
+        for index in 0..count {
+            if topics.is_null() {
+                return Err(ReaderError::generic("topics is NULL"));
+            }
+            let topic = str_ref(*topics.add(index), "topic")?;
+            let connections = reader.reader.connections_for_topic(topic);
+            if connections.is_empty() {
+                return Err(ReaderError::connection_not_found(topic));
+            }
+            selected.extend(connections);
+        }
+        reader.selected = (count > 0).then_some(selected);
+        reader.rewind();
+        Ok(RosbagsStatus::Ok)
+    })
+}
+
+/// Restart iteration from the first message
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_rewind(reader: *mut RosbagsReader) -> RosbagsStatus {
+    guard(RosbagsStatus::Error, || {
+        reader_ref(reader)?.rewind();
+        Ok(RosbagsStatus::Ok)
+    })
+}
+
+/// Fill `message` with the next message in timestamp order
+///
+/// Returns `ROSBAGS_STATUS_END` after the last message. The first call reads the
+/// messages of the bag.
+///
+/// # Safety
+/// `reader` must be `NULL` or a live handle from `rosbags_reader_open`, and `message`
+/// `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rosbags_reader_next_message(
+    reader: *mut RosbagsReader,
+    message: *mut RosbagsMessage,
+) -> RosbagsStatus {
+    guard(RosbagsStatus::Error, || {
+        let reader = reader_ref(reader)?;
+        let out = message
+            .as_mut()
+            .ok_or_else(|| ReaderError::generic("message is NULL"))?;
+        Ok(match reader.next_message()? {
+            Some(next) => {
+                *out = next;
+                RosbagsStatus::Ok
+            }
+            None => RosbagsStatus::End,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Writer;
+    use tempfile::TempDir;
+
+    fn text(value: *const c_char) -> String {
+        unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_read_through_c_api() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("bag");
+        let mut writer = Writer::builder(&bag_path).open().unwrap();
+        let mut connections = Vec::new();
+        for topic in ["/a", "/b"] {
+            connections.push(
+                writer
+                    .add_connection(
+                        topic.to_string(),
+                        "std_msgs/msg/UInt8".to_string(),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .unwrap(),
+            );
+        }
+        for (index, connection) in [0, 1, 0].into_iter().enumerate() {
+            let payload = [0, 1, 0, 0, index as u8];
+            writer
+                .write(&connections[connection], 10 * index as u64, &payload)
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        unsafe {
+            let missing = c_string(temp_dir.path().join("missing").to_str().unwrap());
+            assert!(rosbags_reader_open(missing.as_ptr()).is_null());
+            assert!(!rosbags_last_error().is_null());
+
+            let path = c_string(bag_path.to_str().unwrap());
+            let reader = rosbags_reader_open(path.as_ptr());
+            assert!(!reader.is_null());
+            assert_eq!(rosbags_reader_message_count(reader), 3);
+            assert_eq!(rosbags_reader_end_time(reader), 20);
+            assert_eq!(rosbags_reader_connection_count(reader), 2);
+
+            let mut connection = std::mem::zeroed::<RosbagsConnection>();
+            assert_eq!(
+                rosbags_reader_connection(reader, 1, &mut connection),
+                RosbagsStatus::Ok
+            );
+            assert_eq!(text(connection.topic), "/b");
+            assert_eq!(text(connection.message_type), "std_msgs/msg/UInt8");
+            assert_eq!(connection.message_count, 1);
+            assert_eq!(
+                rosbags_reader_connection(reader, 2, &mut connection),
+                RosbagsStatus::Error
+            );
+
+            let mut message = std::mem::zeroed::<RosbagsMessage>();
+            let mut read = Vec::new();
+            while rosbags_reader_next_message(reader, &mut message) == RosbagsStatus::Ok {
+                let data = std::slice::from_raw_parts(message.data, message.data_len);
+                read.push((text(message.topic), message.timestamp, data[4]));
+            }
+            assert_eq!(
+                read,
+                [
+                    ("/a".to_string(), 0, 0),
+                    ("/b".to_string(), 10, 1),
+                    ("/a".to_string(), 20, 2)
+                ]
+            );
+
+            let topics = [c_string("/a")];
+            let topic_ptrs = [topics[0].as_ptr()];
+            assert_eq!(
+                rosbags_reader_set_topics(reader, topic_ptrs.as_ptr(), 1),
+                RosbagsStatus::Ok
+            );
+            let mut count = 0;
+            while rosbags_reader_next_message(reader, &mut message) == RosbagsStatus::Ok {
+                assert_eq!(text(message.topic), "/a");
+                count += 1;
+            }
+            assert_eq!(count, 2);
+
+            rosbags_reader_close(reader);
+            assert_eq!(
+                rosbags_reader_next_message(std::ptr::null_mut(), &mut message),
+                RosbagsStatus::Error
+            );
+        }
+    }
+}
@@ -6,6 +6,12 @@
 use crate::error::{ReaderError, Result};
 use std::convert::TryInto;
 
+/// Deepest nesting of messages a [`CdrDeserializer`] follows before giving up
+///
+/// Real message definitions nest a handful of levels; the limit only stops
+/// self-referencing or adversarial schemas from exhausting the stack.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
 /// CDR header information
 #[derive(Debug, Clone, Copy)]
 pub struct CdrHeader {
@@ -25,13 +31,18 @@ pub struct CdrDeserializer<'a> {
     data: &'a [u8],
     pos: usize,
     endianness: Endianness,
+    depth: usize,
 }
 
 impl<'a> CdrDeserializer<'a> {
     /// Create a new CDR deserializer from raw message data
     pub fn new(data: &'a [u8]) -> Result<Self> {
         if data.len() < 4 {
-            return Err(ReaderError::generic("CDR data too short for header"));
+            return Err(ReaderError::cdr_deserialization(
+                "data too short for header",
+                0,
+                data.len(),
+            ));
         }
 
         // Parse CDR header (4 bytes)
@@ -41,6 +52,7 @@ impl<'a> CdrDeserializer<'a> {
             data,
             pos: 4, // Skip the 4-byte header
             endianness: header.endianness,
+            depth: 0,
         })
     }
 
@@ -56,7 +68,12 @@ impl<'a> CdrDeserializer<'a> {
 
     /// Check if there are enough bytes remaining from current position
     pub fn has_remaining(&self, bytes: usize) -> bool {
-        self.pos + bytes <= self.data.len()
+        bytes <= self.remaining()
+    }
+
+    /// Number of bytes left after the current position
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
     }
 
     /// Get a reference to the underlying data
@@ -65,8 +82,71 @@ impl<'a> CdrDeserializer<'a> {
     }
 
     /// Align position to the specified boundary
+    ///
+    /// Alignment is relative to the end of the 4-byte encapsulation header.
+    ///
+    /// Padding past the end of the data stops at the end, so the next read
+    /// reports the truncation.
     fn align(&mut self, alignment: usize) {
-        self.pos = (self.pos + alignment - 1) & !(alignment - 1);
+        let offset = self.pos - 4;
+        let aligned = 4 + ((offset + alignment - 1) & !(alignment - 1));
+        self.pos = aligned.min(self.data.len());
+    }
+
+    /// Error for a read of `needed` bytes past the end of the data
+    fn truncated(&self, needed: usize) -> ReaderError {
+        ReaderError::cdr_deserialization(
+            format!("data truncated, need {needed} bytes"),
+            self.pos,
+            self.data.len(),
+        )
+    }
+
+    /// Fail unless `length` more bytes are available
+    fn ensure(&self, length: usize) -> Result<()> {
+        if length > self.remaining() {
+            return Err(self.truncated(length));
+        }
+        Ok(())
+    }
+
+    /// Read a sequence length prefix of elements at least `element_size` bytes each
+    ///
+    /// The length is checked against the remaining data before anything is
+    /// allocated, so a corrupt prefix claiming billions of elements fails right
+    /// away instead of attempting a huge allocation.
+    pub fn read_length(&mut self, element_size: usize) -> Result<usize> {
+        let length = self.read_u32()? as usize;
+        let needed = length.checked_mul(element_size.max(1));
+        if needed.map_or(true, |needed| needed > self.remaining()) {
+            return Err(ReaderError::cdr_deserialization(
+                format!(
+                    "sequence length {length} exceeds the {} remaining bytes",
+                    self.remaining()
+                ),
+                self.pos,
+                self.data.len(),
+            ));
+        }
+        Ok(length)
+    }
+
+    /// Run `decode` one nesting level deeper, failing past [`MAX_NESTING_DEPTH`]
+    ///
+    /// Decoders of nested messages wrap each level in this call so that deeply
+    /// nested input cannot overflow the stack.
+    pub fn nested<T>(&mut self, decode: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(ReaderError::cdr_deserialization(
+                format!("messages nested deeper than {MAX_NESTING_DEPTH} levels"),
+                self.pos,
+                self.data.len(),
+            ));
+        }
+        self.depth += 1;
+        let result = decode(self);
+        self.depth -= 1;
+        result
     }
 
     /// Read a primitive value with proper alignment and endianness
@@ -75,15 +155,7 @@ impl<'a> CdrDeserializer<'a> {
         T: FromBytes,
     {
         self.align(size);
-
-        if self.pos + size > self.data.len() {
-            return Err(ReaderError::generic(format!(
-                "CDR data truncated: need {} bytes at pos {}, but only {} bytes available",
-                size,
-                self.pos,
-                self.data.len()
-            )));
-        }
+        self.ensure(size)?;
 
         let bytes = &self.data[self.pos..self.pos + size];
         self.pos += size;
@@ -101,6 +173,11 @@ impl<'a> CdrDeserializer<'a> {
         self.read_primitive(1)
     }
 
+    /// Read an i16 value
+    pub fn read_i16(&mut self) -> Result<i16> {
+        self.read_primitive(2)
+    }
+
     /// Read a u16 value
     pub fn read_u16(&mut self) -> Result<u16> {
         self.read_primitive(2)
@@ -116,18 +193,37 @@ impl<'a> CdrDeserializer<'a> {
         self.read_primitive(4)
     }
 
+    /// Read an i64 value
+    pub fn read_i64(&mut self) -> Result<i64> {
+        self.read_primitive(8)
+    }
+
+    /// Read a u64 value
+    pub fn read_u64(&mut self) -> Result<u64> {
+        self.read_primitive(8)
+    }
+
+    /// Skip `count` consecutive primitives of `size` bytes, honoring alignment
+    pub fn skip_primitives(&mut self, size: usize, count: usize) -> Result<()> {
+        self.align(size);
+        let length = size
+            .checked_mul(count)
+            .ok_or_else(|| self.truncated(usize::MAX))?;
+        self.skip_bytes(length)
+    }
+
+    /// Skip `length` bytes without alignment
+    pub fn skip_bytes(&mut self, length: usize) -> Result<()> {
+        self.ensure(length)?;
+        self.pos += length;
+        Ok(())
+    }
+
     /// Read an f64 value
     pub fn read_f64(&mut self) -> Result<f64> {
         // In CDR, f64 values are aligned to 8-byte boundaries
         self.align(8);
-
-        if self.pos + 8 > self.data.len() {
-            return Err(ReaderError::generic(format!(
-                "CDR data truncated: need 8 bytes at pos {}, but only {} bytes available",
-                self.pos,
-                self.data.len()
-            )));
-        }
+        self.ensure(8)?;
 
         let bytes = &self.data[self.pos..self.pos + 8];
         self.pos += 8;
@@ -137,16 +233,12 @@ impl<'a> CdrDeserializer<'a> {
 
     /// Read a string value
     pub fn read_string(&mut self) -> Result<String> {
-        let length = self.read_u32()? as usize;
+        let length = self.read_length(1)?;
 
         if length == 0 {
             return Ok(String::new());
         }
 
-        if self.pos + length > self.data.len() {
-            return Err(ReaderError::generic("CDR string data truncated"));
-        }
-
         // String includes null terminator, but we need to handle the case where it might not
         let string_bytes = if length > 0 && self.data[self.pos + length - 1] == 0 {
             // Has null terminator
@@ -161,8 +253,14 @@ impl<'a> CdrDeserializer<'a> {
         // String data is already aligned to 4-byte boundary in CDR
         // No additional alignment needed after reading the string
 
-        String::from_utf8(string_bytes.to_vec())
-            .map_err(|_| ReaderError::generic("Invalid UTF-8 in CDR string"))
+        let start = self.pos - length;
+        String::from_utf8(string_bytes.to_vec()).map_err(|error| {
+            ReaderError::cdr_deserialization(
+                format!("invalid UTF-8 in string: {error}"),
+                start,
+                self.data.len(),
+            )
+        })
     }
 
     /// Read a fixed-size array of f64 values
@@ -175,11 +273,14 @@ impl<'a> CdrDeserializer<'a> {
     }
 
     /// Read a sequence (variable-length array) of elements
+    ///
+    /// Every element takes at least one byte, so lengths beyond the remaining
+    /// data are rejected before allocating.
     pub fn read_sequence<T, F>(&mut self, read_element: F) -> Result<Vec<T>>
     where
         F: Fn(&mut Self) -> Result<T>,
     {
-        let length = self.read_u32()? as usize;
+        let length = self.read_length(1)?;
         let mut vec = Vec::with_capacity(length);
 
         for _ in 0..length {
@@ -189,20 +290,43 @@ impl<'a> CdrDeserializer<'a> {
         Ok(vec)
     }
 
+    /// Read a wide string (`wstring`) stored as 4-byte code units
+    pub fn read_wstring(&mut self) -> Result<String> {
+        let length = self.read_length(4)?;
+        let mut text = String::with_capacity(length);
+        for _ in 0..length {
+            let code = self.read_u32()?;
+            if code != 0 {
+                text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+        }
+        Ok(text)
+    }
+
+    /// Read a fixed-size array (`T[N]`) of elements, which has no length prefix
+    pub fn read_array<T, F, const N: usize>(&mut self, read_element: F) -> Result<[T; N]>
+    where
+        F: Fn(&mut Self) -> Result<T>,
+    {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(read_element(self)?);
+        }
+        items.try_into().map_err(|_| {
+            ReaderError::cdr_deserialization("array length mismatch", self.pos, self.data.len())
+        })
+    }
+
     /// Read a sequence of bytes (for data fields)
     pub fn read_byte_sequence(&mut self) -> Result<Vec<u8>> {
-        let length = self.read_u32()? as usize;
+        self.read_byte_slice().map(<[u8]>::to_vec)
+    }
 
-        if self.pos + length > self.data.len() {
-            return Err(ReaderError::generic(format!(
-                "CDR data truncated: need {} bytes at pos {}, but only {} bytes available",
-                length,
-                self.pos,
-                self.data.len()
-            )));
-        }
+    /// Read a sequence of bytes as a slice borrowing the underlying data
+    pub fn read_byte_slice(&mut self) -> Result<&'a [u8]> {
+        let length = self.read_length(1)?;
 
-        let bytes = self.data[self.pos..self.pos + length].to_vec();
+        let bytes = &self.data[self.pos..self.pos + length];
         self.pos += length;
 
         Ok(bytes)
@@ -217,14 +341,7 @@ impl<'a> CdrDeserializer<'a> {
     /// Read an f32 value
     pub fn read_f32(&mut self) -> Result<f32> {
         self.align(4);
-
-        if self.pos + 4 > self.data.len() {
-            return Err(ReaderError::generic(format!(
-                "CDR data truncated: need 4 bytes at pos {}, but only {} bytes available",
-                self.pos,
-                self.data.len()
-            )));
-        }
+        self.ensure(4)?;
 
         let bytes = &self.data[self.pos..self.pos + 4];
         self.pos += 4;
@@ -233,6 +350,145 @@ impl<'a> CdrDeserializer<'a> {
     }
 }
 
+/// CDR serializer producing little-endian encapsulated message data
+///
+/// Output starts with the `CDR_LE` encapsulation header and uses the same
+/// alignment rules as [`CdrDeserializer`], so serialized messages round-trip.
+#[derive(Debug, Clone)]
+pub struct CdrSerializer {
+    data: Vec<u8>,
+}
+
+impl Default for CdrSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CdrSerializer {
+    /// Create a serializer with the encapsulation header already written
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Create a serializer with room for `capacity` payload bytes
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut data = Vec::with_capacity(capacity + 4);
+        data.extend_from_slice(&[0x00, 0x01, 0x00, 0x00]);
+        Self { data }
+    }
+
+    /// Get current position in the output, including the header
+    pub fn position(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Finish serialization and return the encoded bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Pad with zeros up to the specified boundary
+    fn align(&mut self, alignment: usize) {
+        let offset = self.data.len() - 4;
+        let aligned = (offset + alignment - 1) & !(alignment - 1);
+        self.data.resize(4 + aligned, 0);
+    }
+
+    fn write_aligned(&mut self, bytes: &[u8]) {
+        self.align(bytes.len());
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Write an i8 value
+    pub fn write_i8(&mut self, value: i8) {
+        self.data.push(value as u8);
+    }
+
+    /// Write a u8 value
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    /// Write a boolean value
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    /// Write an i16 value
+    pub fn write_i16(&mut self, value: i16) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write a u16 value
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write an i32 value
+    pub fn write_i32(&mut self, value: i32) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write a u32 value
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write an i64 value
+    pub fn write_i64(&mut self, value: i64) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write a u64 value
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write an f32 value
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write an f64 value
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_aligned(&value.to_le_bytes());
+    }
+
+    /// Write a string with length prefix and null terminator
+    pub fn write_string(&mut self, value: &str) {
+        self.write_u32(value.len() as u32 + 1);
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+    }
+
+    /// Write a wide string (`wstring`) as 4-byte code units with length prefix
+    pub fn write_wstring(&mut self, value: &str) {
+        self.write_u32(value.chars().count() as u32);
+        for c in value.chars() {
+            self.write_u32(c as u32);
+        }
+    }
+
+    /// Write a sequence length prefix
+    pub fn write_sequence_length(&mut self, length: usize) {
+        self.write_u32(length as u32);
+    }
+
+    /// Write a byte sequence (uint8[]) with length prefix
+    pub fn write_byte_sequence(&mut self, bytes: &[u8]) {
+        self.write_sequence_length(bytes.len());
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Write a fixed-size f64 array
+    pub fn write_f64_array(&mut self, values: &[f64]) {
+        for &value in values {
+            self.write_f64(value);
+        }
+    }
+}
+
 impl CdrHeader {
     /// Parse CDR header from the first 4 bytes
     pub fn parse(header_bytes: &[u8]) -> Result<Self> {
@@ -248,7 +504,13 @@ impl CdrHeader {
         let endianness = match header_bytes[1] {
             0 => Endianness::BigEndian,
             1 => Endianness::LittleEndian,
-            _ => return Err(ReaderError::generic("Invalid CDR endianness flag")),
+            flag => {
+                return Err(ReaderError::cdr_deserialization(
+                    format!("invalid endianness flag {flag}"),
+                    1,
+                    header_bytes.len(),
+                ))
+            }
         };
 
         Ok(Self {
@@ -281,6 +543,19 @@ impl FromBytes for u8 {
     }
 }
 
+impl FromBytes for i16 {
+    fn from_bytes(bytes: &[u8], endianness: Endianness) -> Result<Self> {
+        let array: [u8; 2] = bytes
+            .try_into()
+            .map_err(|_| ReaderError::generic("Invalid i16 bytes"))?;
+
+        Ok(match endianness {
+            Endianness::LittleEndian => i16::from_le_bytes(array),
+            Endianness::BigEndian => i16::from_be_bytes(array),
+        })
+    }
+}
+
 impl FromBytes for u16 {
     fn from_bytes(bytes: &[u8], endianness: Endianness) -> Result<Self> {
         let array: [u8; 2] = bytes
@@ -320,6 +595,32 @@ impl FromBytes for u32 {
     }
 }
 
+impl FromBytes for i64 {
+    fn from_bytes(bytes: &[u8], endianness: Endianness) -> Result<Self> {
+        let array: [u8; 8] = bytes
+            .try_into()
+            .map_err(|_| ReaderError::generic("Invalid i64 bytes"))?;
+
+        Ok(match endianness {
+            Endianness::LittleEndian => i64::from_le_bytes(array),
+            Endianness::BigEndian => i64::from_be_bytes(array),
+        })
+    }
+}
+
+impl FromBytes for u64 {
+    fn from_bytes(bytes: &[u8], endianness: Endianness) -> Result<Self> {
+        let array: [u8; 8] = bytes
+            .try_into()
+            .map_err(|_| ReaderError::generic("Invalid u64 bytes"))?;
+
+        Ok(match endianness {
+            Endianness::LittleEndian => u64::from_le_bytes(array),
+            Endianness::BigEndian => u64::from_be_bytes(array),
+        })
+    }
+}
+
 impl FromBytes for f32 {
     fn from_bytes(bytes: &[u8], endianness: Endianness) -> Result<Self> {
         let array: [u8; 4] = bytes
@@ -380,4 +681,103 @@ mod tests {
         let uint_val = deserializer.read_u32().unwrap();
         assert_eq!(uint_val, 0x04030201);
     }
+
+    #[test]
+    fn test_byte_slice_borrows_data() {
+        let data = [
+            0x00, 0x01, 0x00, 0x00, // CDR header (little endian)
+            0x03, 0x00, 0x00, 0x00, // sequence length: 3
+            0x0A, 0x0B, 0x0C, // payload
+        ];
+
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        let bytes = deserializer.read_byte_slice().unwrap();
+        assert_eq!(bytes, &[0x0A, 0x0B, 0x0C]);
+        assert!(std::ptr::eq(bytes.as_ptr(), data[8..].as_ptr()));
+
+        let mut truncated = CdrDeserializer::new(&data[..10]).unwrap();
+        assert!(truncated.read_byte_slice().is_err());
+    }
+
+    #[test]
+    fn test_alignment_relative_to_header() {
+        // 8-byte values are aligned relative to the end of the encapsulation header
+        let data = [
+            0x00, 0x01, 0x00, 0x00, // CDR header (little endian)
+            0x07, // u8: 7 at offset 0
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // padding up to offset 8
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF8, 0x3F, // f64: 1.5 at offset 8
+        ];
+
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert_eq!(deserializer.read_u8().unwrap(), 7);
+        assert_eq!(deserializer.read_f64().unwrap(), 1.5);
+        assert_eq!(deserializer.position(), data.len());
+    }
+
+    #[test]
+    fn test_oversized_lengths_fail_before_allocating() {
+        // A 4 GB length prefix followed by a handful of bytes
+        let data = [0x00, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, b'a', b'b'];
+
+        let error = CdrDeserializer::new(&data)
+            .unwrap()
+            .read_string()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ReaderError::CdrDeserialization { position: 8, .. }
+        ));
+        assert!(CdrDeserializer::new(&data)
+            .unwrap()
+            .read_sequence(CdrDeserializer::read_u8)
+            .is_err());
+        assert!(CdrDeserializer::new(&data).unwrap().read_wstring().is_err());
+        assert!(CdrDeserializer::new(&data)
+            .unwrap()
+            .skip_primitives(8, usize::MAX)
+            .is_err());
+
+        let mut short = CdrDeserializer::new(&data[..6]).unwrap();
+        assert!(short.read_u64().is_err());
+        assert!(short.position() <= data.len());
+    }
+
+    #[test]
+    fn test_nesting_depth_is_capped() {
+        fn descend(deserializer: &mut CdrDeserializer) -> Result<usize> {
+            deserializer.nested(|deserializer| descend(deserializer).map(|depth| depth + 1))
+        }
+
+        let data = [0x00, 0x01, 0x00, 0x00];
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert!(matches!(
+            descend(&mut deserializer),
+            Err(ReaderError::CdrDeserialization { .. })
+        ));
+        assert_eq!(deserializer.depth, 0);
+        assert_eq!(deserializer.nested(|_| Ok(7)).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_serializer_round_trip() {
+        let mut serializer = CdrSerializer::new();
+        serializer.write_u8(7);
+        serializer.write_f64(1.5);
+        serializer.write_string("frame");
+        serializer.write_i16(-3);
+        serializer.write_byte_sequence(&[1, 2, 3]);
+        serializer.write_u64(42);
+        let data = serializer.into_bytes();
+        assert_eq!(&data[..4], &[0x00, 0x01, 0x00, 0x00]);
+
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert_eq!(deserializer.read_u8().unwrap(), 7);
+        assert_eq!(deserializer.read_f64().unwrap(), 1.5);
+        assert_eq!(deserializer.read_string().unwrap(), "frame");
+        assert_eq!(deserializer.read_i16().unwrap(), -3);
+        assert_eq!(deserializer.read_byte_slice().unwrap(), &[1, 2, 3]);
+        assert_eq!(deserializer.read_u64().unwrap(), 42);
+        assert_eq!(deserializer.position(), data.len());
+    }
 }
@@ -0,0 +1,407 @@
+//! Rust code generation from stored message definitions
+//!
+//! Bags record the full definition of every message type they contain, including
+//! proprietary ones. [`Codegen`] turns those definitions into Rust structs with
+//! [`FromCdr`](crate::messages::FromCdr) and [`ToCdr`](crate::messages::ToCdr)
+//! implementations, so recorded data can be accessed with typed structs without
+//! writing them by hand.
+//!
+//! Types are emitted in one module per package (e.g. `sensor_msgs::NavSatFix`), with
+//! message constants as associated constants.
+
+use crate::error::{BagError, Result};
+use crate::schema::{
+    ArraySpec, ConstantDef, ConstantValue, FieldDef, MessageSchema, MessageSchemas,
+};
+use crate::types::{Connection, MessageDefinitionFormat};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// Words that cannot be used as Rust identifiers without escaping
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Generator of Rust message structs from message definitions
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::codegen::Codegen;
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+///
+/// let mut codegen = Codegen::new();
+/// for connection in reader.connections() {
+///     codegen.add_connection(connection)?;
+/// }
+/// std::fs::write("src/messages.rs", codegen.generate())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Codegen {
+    crate_path: String,
+    schemas: BTreeMap<String, MessageSchema>,
+}
+
+impl Default for Codegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Codegen {
+    /// Create a generator for code that depends on the `rosbags_rs` crate
+    pub fn new() -> Self {
+        Self {
+            crate_path: "rosbags_rs".to_string(),
+            schemas: BTreeMap::new(),
+        }
+    }
+
+    /// Path under which generated code refers to this crate (default `rosbags_rs`)
+    pub fn crate_path(mut self, path: impl Into<String>) -> Self {
+        self.crate_path = path.into();
+        self
+    }
+
+    /// Add a message type and all of its dependencies
+    pub fn add_schemas(&mut self, schemas: &MessageSchemas) {
+        for schema in schemas.iter() {
+            self.schemas
+                .entry(schema.name.clone())
+                .or_insert_with(|| schema.clone());
+        }
+    }
+
+    /// Add the message type of a connection from its stored definition
+    pub fn add_connection(&mut self, connection: &Connection) -> Result<()> {
+        if connection.message_definition.format != MessageDefinitionFormat::Msg {
+            return Err(BagError::schema_validation(format!(
+                "No .msg definition stored for {} on {}",
+                connection.message_type, connection.topic
+            )));
+        }
+        let schemas =
+            MessageSchemas::parse(&connection.message_type, &connection.message_definition)?;
+        self.add_schemas(&schemas);
+        Ok(())
+    }
+
+    /// Names of the message types that will be generated, sorted
+    pub fn message_types(&self) -> impl Iterator<Item = &str> {
+        self.schemas.keys().map(String::as_str)
+    }
+
+    /// Generate Rust source code for all added message types
+    pub fn generate(&self) -> String {
+        let mut packages: BTreeMap<&str, Vec<&MessageSchema>> = BTreeMap::new();
+        for schema in self.schemas.values() {
+            packages
+                .entry(package_name(&schema.name))
+                .or_default()
+                .push(schema);
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "// Generated by {} {} from recorded message definitions. Do not edit.",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        for (package, schemas) in packages {
+            out.push('\n');
+            out.push_str(
+                "#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]\n",
+            );
+            let _ = writeln!(out, "pub mod {} {{", identifier(package));
+            for (index, schema) in schemas.iter().enumerate() {
+                if index > 0 {
+                    out.push('\n');
+                }
+                self.generate_message(&mut out, schema);
+            }
+            out.push_str("}\n");
+        }
+        out
+    }
+
+    fn generate_message(&self, out: &mut String, schema: &MessageSchema) {
+        let krate = &self.crate_path;
+        let name = type_identifier(&schema.name);
+
+        let _ = writeln!(out, "    /// `{}`", schema.name);
+        out.push_str("    #[derive(Debug, Clone, PartialEq)]\n");
+        let _ = writeln!(out, "    pub struct {name} {{");
+        for field in &schema.fields {
+            let _ = writeln!(
+                out,
+                "        pub {}: {},",
+                identifier(&field.name),
+                self.field_type(field)
+            );
+        }
+        out.push_str("    }\n\n");
+
+        let _ = writeln!(out, "    impl {name} {{");
+        let _ = writeln!(
+            out,
+            "        pub const MESSAGE_TYPE: &'static str = {:?};",
+            schema.name
+        );
+        for constant in &schema.constants {
+            let _ = writeln!(out, "        pub const {};", constant_declaration(constant));
+        }
+        out.push_str("    }\n\n");
+
+        let _ = writeln!(out, "    impl {krate}::messages::MessageType for {name} {{");
+        let _ = writeln!(
+            out,
+            "        const MESSAGE_TYPE: &'static str = {:?};",
+            schema.name
+        );
+        out.push_str("    }\n\n");
+
+        let _ = writeln!(out, "    impl {krate}::messages::FromCdr for {name} {{");
+        let _ = writeln!(
+            out,
+            "        fn from_cdr(d: &mut {krate}::cdr::CdrDeserializer) -> {krate}::Result<Self> {{"
+        );
+        if schema.fields.is_empty() {
+            // Empty messages are serialized with a single placeholder byte
+            out.push_str("            d.read_u8()?;\n");
+        }
+        out.push_str("            Ok(Self {\n");
+        for field in &schema.fields {
+            let _ = writeln!(
+                out,
+                "                {}: {},",
+                identifier(&field.name),
+                self.read_field(field)
+            );
+        }
+        out.push_str("            })\n        }\n    }\n\n");
+
+        let _ = writeln!(out, "    impl {krate}::messages::ToCdr for {name} {{");
+        let _ = writeln!(
+            out,
+            "        fn to_cdr(&self, s: &mut {krate}::cdr::CdrSerializer) {{"
+        );
+        if schema.fields.is_empty() {
+            out.push_str("            s.write_u8(0);\n");
+        }
+        for field in &schema.fields {
+            self.write_field(out, field);
+        }
+        out.push_str("        }\n    }\n");
+    }
+
+    /// Rust type of a field
+    fn field_type(&self, field: &FieldDef) -> String {
+        let element = element_type(field.base_type());
+        match field.array() {
+            None => element,
+            Some(ArraySpec::Fixed(length)) => format!("[{element}; {length}]"),
+            Some(_) => format!("::std::vec::Vec<{element}>"),
+        }
+    }
+
+    /// Expression decoding a field from `d`
+    fn read_field(&self, field: &FieldDef) -> String {
+        let base = field.base_type();
+        match field.array() {
+            None => format!("{}?", self.read_element(base)),
+            Some(ArraySpec::Fixed(_)) => {
+                format!("d.read_array(|d| {})?", self.read_element(base))
+            }
+            Some(_) if matches!(base, "uint8" | "byte") => "d.read_byte_sequence()?".to_string(),
+            Some(_) => format!("d.read_sequence(|d| {})?", self.read_element(base)),
+        }
+    }
+
+    /// Expression decoding a single element from `d`, evaluating to a `Result`
+    fn read_element(&self, base: &str) -> String {
+        match primitive_method(base) {
+            Some(method) => format!("d.read_{method}()"),
+            None => format!("{}::messages::FromCdr::from_cdr(d)", self.crate_path),
+        }
+    }
+
+    /// Statements encoding a field into `s`
+    fn write_field(&self, out: &mut String, field: &FieldDef) {
+        let base = field.base_type();
+        let value = format!("self.{}", identifier(&field.name));
+        match field.array() {
+            None => {
+                let _ = writeln!(
+                    out,
+                    "            {};",
+                    self.write_element(base, &value, false)
+                );
+            }
+            Some(ArraySpec::Fixed(_)) => {
+                let _ = writeln!(
+                    out,
+                    "            for item in &{value} {{\n                {};\n            }}",
+                    self.write_element(base, "item", true)
+                );
+            }
+            Some(_) if matches!(base, "uint8" | "byte") => {
+                let _ = writeln!(out, "            s.write_byte_sequence(&{value});");
+            }
+            Some(_) => {
+                let _ = writeln!(out, "            s.write_sequence_length({value}.len());");
+                let _ = writeln!(
+                    out,
+                    "            for item in &{value} {{\n                {};\n            }}",
+                    self.write_element(base, "item", true)
+                );
+            }
+        }
+    }
+
+    /// Expression encoding a single element into `s`
+    fn write_element(&self, base: &str, value: &str, by_ref: bool) -> String {
+        match primitive_method(base) {
+            Some(method @ ("string" | "wstring")) => {
+                let value = if by_ref {
+                    value.to_string()
+                } else {
+                    format!("&{value}")
+                };
+                format!("s.write_{method}({value})")
+            }
+            Some(method) => {
+                let value = if by_ref {
+                    format!("*{value}")
+                } else {
+                    value.to_string()
+                };
+                format!("s.write_{method}({value})")
+            }
+            None => {
+                let value = if by_ref {
+                    value.to_string()
+                } else {
+                    format!("&{value}")
+                };
+                format!("{}::messages::ToCdr::to_cdr({value}, s)", self.crate_path)
+            }
+        }
+    }
+}
+
+/// Package of a `pkg/msg/Type` name
+fn package_name(type_name: &str) -> &str {
+    type_name.split('/').next().unwrap_or(type_name)
+}
+
+/// Struct name of a `pkg/msg/Type` name
+fn type_identifier(type_name: &str) -> String {
+    identifier(type_name.rsplit('/').next().unwrap_or(type_name))
+}
+
+/// Escape names that are Rust keywords
+fn identifier(name: &str) -> String {
+    match name {
+        "self" | "Self" | "super" | "crate" => format!("{name}_"),
+        _ if RUST_KEYWORDS.contains(&name) => format!("r#{name}"),
+        _ => name.to_string(),
+    }
+}
+
+/// Deserializer/serializer method suffix of a primitive type
+fn primitive_method(base: &str) -> Option<&'static str> {
+    Some(match base {
+        "bool" => "bool",
+        "byte" | "char" | "uint8" => "u8",
+        "int8" => "i8",
+        "int16" => "i16",
+        "uint16" => "u16",
+        "int32" => "i32",
+        "uint32" => "u32",
+        "int64" => "i64",
+        "uint64" => "u64",
+        "float32" => "f32",
+        "float64" => "f64",
+        "string" => "string",
+        "wstring" => "wstring",
+        _ => return None,
+    })
+}
+
+/// Rust type of a single element of a ROS type
+fn element_type(base: &str) -> String {
+    match primitive_method(base) {
+        Some("string" | "wstring") => "::std::string::String".to_string(),
+        Some(method) => method.to_string(),
+        None => format!(
+            "super::{}::{}",
+            identifier(package_name(base)),
+            type_identifier(base)
+        ),
+    }
+}
+
+/// `NAME: type = value` of a message constant
+fn constant_declaration(constant: &ConstantDef) -> String {
+    let name = identifier(&constant.name);
+    let (rust_type, value) = match &constant.value {
+        ConstantValue::String(text) => ("&'static str".to_string(), format!("{text:?}")),
+        ConstantValue::Float(value) => (element_type(&constant.type_name), format!("{:?}", value)),
+        ConstantValue::Bool(value) => ("bool".to_string(), value.to_string()),
+        ConstantValue::Int(value) => (element_type(&constant.type_name), value.to_string()),
+        ConstantValue::UInt(value) => (element_type(&constant.type_name), value.to_string()),
+    };
+    format!("{name}: {rust_type} = {value}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_nested_arrays_and_constants() {
+        let schemas = MessageSchemas::parse_msg(
+            "pkg/msg/Outer",
+            "uint8 MODE_A=1
+string NAME=\"outer\"
+float32 SCALE=0.5
+Inner[2] pair
+Inner[] items
+uint8[] data
+float64[3] xyz
+string type
+================================================================================
+MSG: pkg/Inner
+int32 value",
+        )
+        .unwrap();
+        let mut codegen = Codegen::new();
+        codegen.add_schemas(&schemas);
+        assert_eq!(
+            codegen.message_types().collect::<Vec<_>>(),
+            ["pkg/msg/Inner", "pkg/msg/Outer"]
+        );
+
+        let code = codegen.generate();
+        assert!(code.contains("pub mod pkg {"));
+        assert!(code.contains("pub pair: [super::pkg::Inner; 2],"));
+        assert!(code.contains("pub items: ::std::vec::Vec<super::pkg::Inner>,"));
+        assert!(code.contains("pub r#type: ::std::string::String,"));
+        assert!(code.contains("pub const MODE_A: u8 = 1;"));
+        assert!(code.contains("pub const NAME: &'static str = \"outer\";"));
+        assert!(code.contains("pub const SCALE: f32 = 0.5;"));
+        assert!(code.contains("data: d.read_byte_sequence()?,"));
+        assert!(code.contains("xyz: d.read_array(|d| d.read_f64())?,"));
+        assert!(code.contains("s.write_string(&self.r#type);"));
+    }
+}
@@ -0,0 +1,322 @@
+//! Bundled definitions of the standard ROS2 interface packages
+//!
+//! Bags written before rosbag2 stored message definitions (SQLite3 schema versions
+//! below 4, as recorded by Humble and earlier) only name the type of every topic, which
+//! leaves the schema-driven decoders with nothing to parse. This module embeds the
+//! `.msg` files of the common interface packages and a [`DefinitionResolver`] that
+//! assembles full definitions, dependencies included, by type name.
+//!
+//! The field layouts of the bundled types are identical in Humble, Iron and Jazzy, so
+//! one set of definitions serves all three distributions.
+
+use crate::error::{BagError, Result};
+use crate::schema::{is_primitive_type, MessageSchemas, DEFINITION_SEPARATOR};
+use crate::types::{Connection, MessageDefinition, MessageDefinitionFormat};
+use std::collections::{HashMap, HashSet};
+
+/// Table of `(pkg/msg/Type, .msg text)` for the `.msg` files under `src/definitions/`
+macro_rules! bundled {
+    ($($package:literal: [$($name:literal),* $(,)?],)*) => {
+        &[$($((
+            concat!($package, "/msg/", $name),
+            include_str!(concat!("definitions/", $package, "/msg/", $name, ".msg")),
+        ),)*)*]
+    };
+}
+
+/// `.msg` text of every bundled type by `pkg/msg/Type`
+static BUNDLED: &[(&str, &str)] = bundled! {
+    "builtin_interfaces": ["Duration", "Time"],
+    "diagnostic_msgs": ["DiagnosticArray", "DiagnosticStatus", "KeyValue"],
+    "geometry_msgs": ["Accel", "AccelStamped", "AccelWithCovariance", "AccelWithCovarianceStamped", "Inertia", "InertiaStamped", "Point", "Point32", "PointStamped", "Polygon", "PolygonStamped", "Pose", "Pose2D", "PoseArray", "PoseStamped", "PoseWithCovariance", "PoseWithCovarianceStamped", "Quaternion", "QuaternionStamped", "Transform", "TransformStamped", "Twist", "TwistStamped", "TwistWithCovariance", "TwistWithCovarianceStamped", "Vector3", "Vector3Stamped", "Wrench", "WrenchStamped"],
+    "nav_msgs": ["GridCells", "MapMetaData", "OccupancyGrid", "Odometry", "Path"],
+    "rosgraph_msgs": ["Clock"],
+    "sensor_msgs": ["BatteryState", "CameraInfo", "ChannelFloat32", "CompressedImage", "FluidPressure", "Illuminance", "Image", "Imu", "JointState", "Joy", "JoyFeedback", "JoyFeedbackArray", "LaserEcho", "LaserScan", "MagneticField", "MultiDOFJointState", "MultiEchoLaserScan", "NavSatFix", "NavSatStatus", "PointCloud", "PointCloud2", "PointField", "Range", "RegionOfInterest", "RelativeHumidity", "Temperature", "TimeReference"],
+    "std_msgs": ["Bool", "Byte", "ByteMultiArray", "Char", "ColorRGBA", "Empty", "Float32", "Float32MultiArray", "Float64", "Float64MultiArray", "Header", "Int16", "Int16MultiArray", "Int32", "Int32MultiArray", "Int64", "Int64MultiArray", "Int8", "Int8MultiArray", "MultiArrayDimension", "MultiArrayLayout", "String", "UInt16", "UInt16MultiArray", "UInt32", "UInt32MultiArray", "UInt64", "UInt64MultiArray", "UInt8", "UInt8MultiArray"],
+    "stereo_msgs": ["DisparityImage"],
+    "tf2_msgs": ["TF2Error", "TFMessage"],
+};
+
+/// Get the bundled `.msg` text of a single type, without its dependencies
+///
+/// Both `pkg/msg/Type` and `pkg/Type` spellings are accepted.
+pub fn bundled_msg(message_type: &str) -> Option<&'static str> {
+    let message_type = normalize_type_name(message_type);
+    BUNDLED
+        .iter()
+        .find(|(name, _)| *name == message_type)
+        .map(|(_, text)| *text)
+}
+
+/// Names (`pkg/msg/Type`) of all bundled types
+pub fn bundled_types() -> impl Iterator<Item = &'static str> {
+    BUNDLED.iter().map(|(name, _)| *name)
+}
+
+/// Assembles full message definitions by type name
+///
+/// Definitions are looked up in the `.msg` texts added with
+/// [`DefinitionResolver::add_msg`] first and in the bundled definitions second. The
+/// result has the layout rosbag2 stores: the root definition followed by a `MSG:`
+/// section for every type it depends on.
+///
+/// # Example
+/// ```
+/// use rosbags_rs::definitions::DefinitionResolver;
+/// use rosbags_rs::schema::MessageSchemas;
+///
+/// let mut resolver = DefinitionResolver::new();
+/// resolver.add_msg("my_msgs/msg/Tagged", "std_msgs/Header header\nstring tag\n");
+///
+/// let definition = resolver.resolve("my_msgs/msg/Tagged").unwrap();
+/// let schemas = MessageSchemas::parse("my_msgs/msg/Tagged", &definition).unwrap();
+/// assert!(schemas.get("builtin_interfaces/msg/Time").is_some());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefinitionResolver {
+    /// User-provided `.msg` texts by `pkg/msg/Type`
+    msgs: HashMap<String, String>,
+}
+
+impl DefinitionResolver {
+    /// Create a resolver that knows the bundled definitions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the `.msg` text of a type, taking precedence over a bundled definition
+    pub fn add_msg(&mut self, message_type: &str, text: impl Into<String>) {
+        self.msgs
+            .insert(normalize_type_name(message_type), text.into());
+    }
+
+    /// Get the `.msg` text of a single type, without its dependencies
+    pub fn msg(&self, message_type: &str) -> Option<&str> {
+        let message_type = normalize_type_name(message_type);
+        self.msgs
+            .get(&message_type)
+            .map(String::as_str)
+            .or_else(|| bundled_msg(&message_type))
+    }
+
+    /// Assemble the definition of `message_type` and every type it depends on
+    pub fn resolve(&self, message_type: &str) -> Result<MessageDefinition> {
+        let root = normalize_type_name(message_type);
+        let text = self
+            .msg(&root)
+            .ok_or_else(|| BagError::message_type_not_found(&root))?;
+
+        let mut data = text.to_string();
+        let mut visited = HashSet::from([root.clone()]);
+        let mut pending = dependencies(&root, text)?;
+        pending.reverse();
+        while let Some(dependency) = pending.pop() {
+            if !visited.insert(dependency.clone()) {
+                continue;
+            }
+            let text = self.msg(&dependency).ok_or_else(|| {
+                BagError::schema_validation(format!(
+                    "No definition of {dependency}, which {root} depends on"
+                ))
+            })?;
+            if !data.is_empty() && !data.ends_with('\n') {
+                data.push('\n');
+            }
+            data.push_str(DEFINITION_SEPARATOR);
+            data.push_str(&format!(
+                "\nMSG: {}\n",
+                dependency.replacen("/msg/", "/", 1)
+            ));
+            data.push_str(text);
+            let mut nested = dependencies(&dependency, text)?;
+            nested.reverse();
+            pending.extend(nested);
+        }
+
+        Ok(MessageDefinition {
+            format: MessageDefinitionFormat::Msg,
+            data,
+        })
+    }
+
+    /// Get the stored definition of `connection`, or the resolved one if it has none
+    pub fn definition_for(&self, connection: &Connection) -> Result<MessageDefinition> {
+        match connection.message_definition.format {
+            MessageDefinitionFormat::None => self.resolve(&connection.message_type),
+            _ => Ok(connection.message_definition.clone()),
+        }
+    }
+
+    /// Get the definition of `connection` with every type it depends on, checking
+    /// that it is complete
+    ///
+    /// A stored definition is used as is; connections without one, or with an empty
+    /// one, get the resolved definition. Fails if there is no definition, if it does
+    /// not parse, or if it lacks a type referenced by a field, which would leave
+    /// tools that decode from the definition alone unable to read the messages.
+    pub fn complete_definition(&self, connection: &Connection) -> Result<MessageDefinition> {
+        let stored = &connection.message_definition;
+        let definition =
+            if stored.format == MessageDefinitionFormat::None || stored.data.trim().is_empty() {
+                match self.resolve(&connection.message_type) {
+                    Ok(definition) => definition,
+                    // An empty `.msg` definition is complete for a type without fields
+                    Err(_) if stored.format != MessageDefinitionFormat::None => stored.clone(),
+                    Err(e) => return Err(e),
+                }
+            } else {
+                stored.clone()
+            };
+
+        let schemas = MessageSchemas::parse(&connection.message_type, &definition)?;
+        let missing = schemas.missing_types();
+        if !missing.is_empty() {
+            return Err(BagError::schema_validation(format!(
+                "Definition of {} lacks referenced types {}",
+                connection.message_type,
+                missing.join(", ")
+            )));
+        }
+        Ok(definition)
+    }
+}
+
+/// Non-primitive field types of a single `.msg` text, in declaration order
+fn dependencies(message_type: &str, text: &str) -> Result<Vec<String>> {
+    let schemas = MessageSchemas::parse_msg(message_type, text)?;
+    Ok(schemas
+        .root()
+        .fields
+        .iter()
+        .map(|field| field.base_type())
+        .filter(|base| !is_primitive_type(base))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Normalize `pkg/Type` to `pkg/msg/Type`
+pub(crate) fn normalize_type_name(message_type: &str) -> String {
+    match message_type.split('/').collect::<Vec<_>>().as_slice() {
+        [package, name] => format!("{package}/msg/{name}"),
+        _ => message_type.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_bundled_type_resolves() {
+        let resolver = DefinitionResolver::new();
+        assert!(bundled_types().count() > 90);
+        for message_type in bundled_types() {
+            let definition = resolver.resolve(message_type).unwrap();
+            let schemas = MessageSchemas::parse(message_type, &definition).unwrap();
+            // Every referenced type has a section
+            for schema in schemas.iter() {
+                for field in &schema.fields {
+                    let base = field.base_type();
+                    assert!(
+                        is_primitive_type(base) || schemas.get(base).is_some(),
+                        "{message_type}: {base}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve_orders_dependencies_like_rosbag2() {
+        let definition = DefinitionResolver::new()
+            .resolve("sensor_msgs/Imu")
+            .unwrap();
+        let sections: Vec<&str> = definition
+            .data
+            .lines()
+            .filter_map(|line| line.strip_prefix("MSG: "))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                "std_msgs/Header",
+                "builtin_interfaces/Time",
+                "geometry_msgs/Quaternion",
+                "geometry_msgs/Vector3"
+            ]
+        );
+        assert!(definition.data.starts_with("std_msgs/Header header\n"));
+    }
+
+    #[test]
+    fn test_added_msgs_and_connection_fallback() {
+        let mut resolver = DefinitionResolver::new();
+        resolver.add_msg("my_msgs/Reading", "my_msgs/Unit unit\nfloat64 value\n");
+        assert!(matches!(
+            resolver.resolve("my_msgs/msg/Reading"),
+            Err(BagError::SchemaValidation { .. })
+        ));
+        resolver.add_msg("my_msgs/msg/Unit", "string name\n");
+        let schemas = MessageSchemas::parse(
+            "my_msgs/msg/Reading",
+            &resolver.resolve("my_msgs/msg/Reading").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(schemas.get("my_msgs/msg/Unit").unwrap().fields.len(), 1);
+        assert!(matches!(
+            resolver.resolve("unknown_msgs/msg/Type"),
+            Err(BagError::MessageTypeNotFound { .. })
+        ));
+
+        // Connections without a stored definition use the bundled one
+        let connection = Connection::new("/imu", "sensor_msgs/msg/Imu").unwrap();
+        assert_eq!(
+            connection.message_definition.format,
+            MessageDefinitionFormat::None
+        );
+        let schemas = connection.message_schemas().unwrap();
+        assert_eq!(schemas.root().fields.len(), 7);
+        assert_eq!(
+            resolver.definition_for(&connection).unwrap().format,
+            MessageDefinitionFormat::Msg
+        );
+        assert!(Connection::new("/x", "unknown_msgs/msg/Type")
+            .unwrap()
+            .message_schemas()
+            .is_err());
+    }
+
+    #[test]
+    fn test_complete_definition_finds_missing_types() {
+        let resolver = DefinitionResolver::new();
+        // Bundled definitions stand in for missing or empty stored ones
+        let imu = Connection::new("/imu", "sensor_msgs/msg/Imu").unwrap();
+        let definition = resolver.complete_definition(&imu).unwrap();
+        assert!(definition.data.contains("MSG: std_msgs/Header"));
+
+        // A stored definition without its dependencies is incomplete
+        let mut truncated = imu.clone();
+        truncated.message_definition = MessageDefinition {
+            format: MessageDefinitionFormat::Msg,
+            data: bundled_msg("sensor_msgs/msg/Imu").unwrap().to_string(),
+        };
+        let schemas =
+            MessageSchemas::parse("sensor_msgs/msg/Imu", &truncated.message_definition).unwrap();
+        assert_eq!(
+            schemas.missing_types(),
+            [
+                "geometry_msgs/msg/Quaternion",
+                "geometry_msgs/msg/Vector3",
+                "std_msgs/msg/Header"
+            ]
+        );
+        let error = resolver.complete_definition(&truncated).unwrap_err();
+        assert!(error.to_string().contains("geometry_msgs/msg/Vector3"));
+
+        // Unknown types without a definition fail, empty stored ones are complete
+        let mut unknown = Connection::new("/x", "unknown_msgs/msg/Empty").unwrap();
+        assert!(resolver.complete_definition(&unknown).is_err());
+        unknown.message_definition.format = MessageDefinitionFormat::Msg;
+        assert_eq!(resolver.complete_definition(&unknown).unwrap().data, "");
+    }
+}
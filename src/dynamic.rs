@@ -0,0 +1,1000 @@
+//! Schema-driven CDR decoding
+//!
+//! Decodes serialized messages using [`MessageSchemas`] parsed from the bag's stored
+//! definitions, so message types without a compiled Rust struct can still be read.
+//! Fields that are not needed can be skipped without allocating. Decoded messages
+//! can be encoded again with [`encode_message`], and new messages built with
+//! [`DynamicMessage::new`] and [`DynamicMessage::set_field`].
+
+use crate::cdr::{CdrDeserializer, CdrSerializer, MAX_NESTING_DEPTH};
+use crate::error::{BagError, Result};
+use crate::schema::{ArraySpec, ConstantValue, DefaultValue, FieldDef, MessageSchemas};
+use crate::types::Connection;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Message type of `builtin_interfaces/msg/Time`
+pub const TIME_TYPE: &str = "builtin_interfaces/msg/Time";
+
+/// Message type of `builtin_interfaces/msg/Duration`
+pub const DURATION_TYPE: &str = "builtin_interfaces/msg/Duration";
+
+/// A decoded primitive value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `bool`
+    Bool(bool),
+    /// `int8` .. `int64`, and `builtin_interfaces` time values in nanoseconds
+    Int(i64),
+    /// `byte`, `char`, `uint8` .. `uint64`
+    UInt(u64),
+    /// `float32`, `float64`
+    Float(f64),
+    /// `string`, `wstring`
+    String(String),
+}
+
+impl From<&ConstantValue> for Value {
+    fn from(value: &ConstantValue) -> Self {
+        match value {
+            ConstantValue::Bool(value) => Value::Bool(*value),
+            ConstantValue::Int(value) => Value::Int(*value),
+            ConstantValue::UInt(value) => Value::UInt(*value),
+            ConstantValue::Float(value) => Value::Float(*value),
+            ConstantValue::String(value) => Value::String(value.clone()),
+        }
+    }
+}
+
+impl Value {
+    /// Get the value as `f64`, converting integers and booleans
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Bool(value) => Some(*value as u8 as f64),
+            Value::Int(value) => Some(*value as f64),
+            Value::UInt(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            Value::String(_) => None,
+        }
+    }
+}
+
+/// A decoded field of any type
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// Primitive or string
+    Value(Value),
+    /// `byte` or `uint8` array or sequence, kept as raw bytes
+    Bytes(Vec<u8>),
+    /// Array or sequence of any other element type
+    Array(Vec<FieldValue>),
+    /// Nested message
+    Message(DynamicMessage),
+}
+
+impl FieldValue {
+    /// Get the primitive value, if this is one
+    pub fn as_value(&self) -> Option<&Value> {
+        match self {
+            FieldValue::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get a primitive value as `f64`, converting integers and booleans
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_value().and_then(Value::as_f64)
+    }
+
+    /// Get the nested message, if this is one
+    pub fn as_message(&self) -> Option<&DynamicMessage> {
+        match self {
+            FieldValue::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! field_value_from {
+    ($($source:ty => $variant:ident as $target:ty),* $(,)?) => {
+        $(
+            impl From<$source> for FieldValue {
+                fn from(value: $source) -> Self {
+                    FieldValue::Value(Value::$variant(<$target>::from(value)))
+                }
+            }
+        )*
+    };
+}
+
+field_value_from! {
+    bool => Bool as bool,
+    i8 => Int as i64,
+    i16 => Int as i64,
+    i32 => Int as i64,
+    i64 => Int as i64,
+    u8 => UInt as u64,
+    u16 => UInt as u64,
+    u32 => UInt as u64,
+    u64 => UInt as u64,
+    f32 => Float as f64,
+    f64 => Float as f64,
+    &str => String as String,
+    String => String as String,
+}
+
+impl From<Value> for FieldValue {
+    fn from(value: Value) -> Self {
+        FieldValue::Value(value)
+    }
+}
+
+impl From<Vec<u8>> for FieldValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        FieldValue::Bytes(bytes)
+    }
+}
+
+impl From<Vec<FieldValue>> for FieldValue {
+    fn from(elements: Vec<FieldValue>) -> Self {
+        FieldValue::Array(elements)
+    }
+}
+
+impl From<DynamicMessage> for FieldValue {
+    fn from(message: DynamicMessage) -> Self {
+        FieldValue::Message(message)
+    }
+}
+
+/// A message decoded from its schema, without a compiled Rust type
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicMessage {
+    /// Message type (e.g. `geometry_msgs/msg/Pose`)
+    pub message_type: String,
+    /// Field names and values in declaration order
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+impl DynamicMessage {
+    /// Create a message of the root type of `schemas` with every field at its default
+    ///
+    /// Fields are then filled in with [`DynamicMessage::set_field`] and the message
+    /// serialized with [`DynamicMessage::to_cdr_bytes`].
+    ///
+    /// # Example
+    /// ```
+    /// use rosbags_rs::dynamic::DynamicMessage;
+    /// use rosbags_rs::schema::MessageSchemas;
+    ///
+    /// let schemas = MessageSchemas::parse_msg("std_msgs/msg/ColorRGBA", "float32 r\nfloat32 g\nfloat32 b\nfloat32 a\n")?;
+    /// let mut color = DynamicMessage::new(&schemas)?;
+    /// color.set_field("r", 1.0)?;
+    /// color.set_field("a", 0.5)?;
+    /// let data = color.to_cdr_bytes(&schemas)?;
+    /// assert_eq!(data.len(), 4 + 16);
+    /// # Ok::<(), rosbags_rs::BagError>(())
+    /// ```
+    pub fn new(schemas: &MessageSchemas) -> Result<Self> {
+        default_message(schemas, &schemas.root().name)
+    }
+
+    /// Get a direct field by name
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Get a nested field by path, e.g. `header.stamp.sec` or `points[2].x`
+    ///
+    /// Indices select elements of arrays; byte arrays ([`FieldValue::Bytes`]) can only
+    /// be selected as a whole.
+    pub fn path(&self, path: &str) -> Option<&FieldValue> {
+        let mut segments = path.split('.');
+        let mut current = self.segment(segments.next()?)?;
+        for segment in segments {
+            current = current.as_message()?.segment(segment)?;
+        }
+        Some(current)
+    }
+
+    /// Get a nested field by path; the counterpart of [`DynamicMessage::set_field`]
+    ///
+    /// Same as [`DynamicMessage::path`].
+    pub fn get_field(&self, path: &str) -> Option<&FieldValue> {
+        self.path(path)
+    }
+
+    /// Get a mutable nested field by path, as addressed by [`DynamicMessage::path`]
+    pub fn path_mut(&mut self, path: &str) -> Option<&mut FieldValue> {
+        let mut segments = path.split('.');
+        let mut current = self.segment_mut(segments.next()?)?;
+        for segment in segments {
+            current = match current {
+                FieldValue::Message(message) => message.segment_mut(segment)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Replace the value of the field at `path`, e.g. `header.frame_id` or `points[2].x`
+    ///
+    /// The field must exist; array elements can only be set within the current
+    /// length, so sequences are set as a whole. Values are checked against the field
+    /// types when the message is serialized, where numbers are converted to the
+    /// declared type if they fit.
+    pub fn set_field(&mut self, path: &str, value: impl Into<FieldValue>) -> Result<()> {
+        match self.path_mut(path) {
+            Some(field) => {
+                *field = value.into();
+                Ok(())
+            }
+            None => Err(BagError::schema_validation(format!(
+                "{} has no field {path}",
+                self.message_type
+            ))),
+        }
+    }
+
+    /// Serialize the message to CDR with its type's schema from `schemas`
+    pub fn to_cdr_bytes(&self, schemas: &MessageSchemas) -> Result<Vec<u8>> {
+        let mut serializer = CdrSerializer::new();
+        encode_message(&mut serializer, schemas, &self.message_type, self)?;
+        Ok(serializer.into_bytes())
+    }
+
+    /// Resolve one path segment (`name` or `name[index]...`)
+    fn segment(&self, segment: &str) -> Option<&FieldValue> {
+        let (name, indices) = segment.split_once('[').unwrap_or((segment, ""));
+        let mut value = self.get(name)?;
+        if !indices.is_empty() {
+            for index in indices.trim_end_matches(']').split("][") {
+                let index: usize = index.parse().ok()?;
+                value = match value {
+                    FieldValue::Array(elements) => elements.get(index)?,
+                    _ => return None,
+                };
+            }
+        }
+        Some(value)
+    }
+
+    /// Resolve one path segment for modification
+    fn segment_mut(&mut self, segment: &str) -> Option<&mut FieldValue> {
+        let (name, indices) = segment.split_once('[').unwrap_or((segment, ""));
+        let mut value = self
+            .fields
+            .iter_mut()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)?;
+        if !indices.is_empty() {
+            for index in indices.trim_end_matches(']').split("][") {
+                let index: usize = index.parse().ok()?;
+                value = match value {
+                    FieldValue::Array(elements) => elements.get_mut(index)?,
+                    _ => return None,
+                };
+            }
+        }
+        Some(value)
+    }
+}
+
+/// Kind of value produced by a primitive type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    String,
+}
+
+impl ValueKind {
+    /// Value kind of a primitive type name, or of a `builtin_interfaces` time type
+    pub fn of(type_name: &str) -> Option<Self> {
+        Some(match type_name {
+            "bool" => ValueKind::Bool,
+            "int8" | "int16" | "int32" | "int64" => ValueKind::Int,
+            "byte" | "char" | "uint8" | "uint16" | "uint32" | "uint64" => ValueKind::UInt,
+            "float32" | "float64" => ValueKind::Float,
+            "string" | "wstring" => ValueKind::String,
+            TIME_TYPE | DURATION_TYPE => ValueKind::Int,
+            _ => return None,
+        })
+    }
+}
+
+/// Serialized size of a fixed-width primitive type
+pub fn primitive_size(type_name: &str) -> Option<usize> {
+    match type_name {
+        "bool" | "byte" | "char" | "int8" | "uint8" => Some(1),
+        "int16" | "uint16" => Some(2),
+        "int32" | "uint32" | "float32" => Some(4),
+        "int64" | "uint64" | "float64" => Some(8),
+        _ => None,
+    }
+}
+
+/// Read a single value of a primitive type or `builtin_interfaces` time type
+///
+/// Time and duration values are returned as nanoseconds.
+pub fn read_value(deserializer: &mut CdrDeserializer, type_name: &str) -> Result<Value> {
+    Ok(match type_name {
+        "bool" => Value::Bool(deserializer.read_bool()?),
+        "int8" => Value::Int(deserializer.read_i8()? as i64),
+        "int16" => Value::Int(deserializer.read_i16()? as i64),
+        "int32" => Value::Int(deserializer.read_i32()? as i64),
+        "int64" => Value::Int(deserializer.read_i64()?),
+        "byte" | "char" | "uint8" => Value::UInt(deserializer.read_u8()? as u64),
+        "uint16" => Value::UInt(deserializer.read_u16()? as u64),
+        "uint32" => Value::UInt(deserializer.read_u32()? as u64),
+        "uint64" => Value::UInt(deserializer.read_u64()?),
+        "float32" => Value::Float(deserializer.read_f32()? as f64),
+        "float64" => Value::Float(deserializer.read_f64()?),
+        "string" => Value::String(deserializer.read_string()?),
+        "wstring" => Value::String(deserializer.read_wstring()?),
+        TIME_TYPE | DURATION_TYPE => {
+            let sec = deserializer.read_i32()? as i64;
+            let nanosec = deserializer.read_u32()? as i64;
+            Value::Int(sec * 1_000_000_000 + nanosec)
+        }
+        _ => {
+            return Err(BagError::schema_validation(format!(
+                "Type {type_name} is not a primitive value"
+            )))
+        }
+    })
+}
+
+/// Decode a complete message of `type_name`, including nested messages and arrays
+///
+/// Time and duration fields are decoded as nested messages with `sec` and `nanosec`.
+pub fn decode_message(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+) -> Result<DynamicMessage> {
+    let schema = schemas
+        .get(type_name)
+        .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+    deserializer.nested(|deserializer| {
+        if schema.fields.is_empty() {
+            // Empty messages are serialized with a single placeholder byte
+            deserializer.read_u8()?;
+        }
+        let fields = schema
+            .fields
+            .iter()
+            .map(|field| {
+                Ok((
+                    field.name.clone(),
+                    decode_field(deserializer, schemas, field)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(DynamicMessage {
+            message_type: schema.name.clone(),
+            fields,
+        })
+    })
+}
+
+/// Decode a complete field, including all array elements
+fn decode_field(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    field: &FieldDef,
+) -> Result<FieldValue> {
+    let base = field.base_type();
+    let is_bytes = matches!(base, "byte" | "uint8");
+    let count = match field.array() {
+        None => return decode_element(deserializer, schemas, base),
+        Some(spec) if spec.is_sequence() && is_bytes => {
+            return Ok(FieldValue::Bytes(deserializer.read_byte_sequence()?));
+        }
+        Some(ArraySpec::Fixed(length)) => length,
+        Some(_) => deserializer.read_length(1)?,
+    };
+    if is_bytes {
+        let bytes = (0..count)
+            .map(|_| deserializer.read_u8())
+            .collect::<Result<_>>()?;
+        return Ok(FieldValue::Bytes(bytes));
+    }
+    let elements = (0..count)
+        .map(|_| decode_element(deserializer, schemas, base))
+        .collect::<Result<_>>()?;
+    Ok(FieldValue::Array(elements))
+}
+
+/// Decode a single primitive, string or nested message
+fn decode_element(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+) -> Result<FieldValue> {
+    if primitive_size(type_name).is_some() || matches!(type_name, "string" | "wstring") {
+        Ok(FieldValue::Value(read_value(deserializer, type_name)?))
+    } else {
+        Ok(FieldValue::Message(decode_message(
+            deserializer,
+            schemas,
+            type_name,
+        )?))
+    }
+}
+
+/// Convert a value to the kind of a primitive type, if it fits without loss
+///
+/// Integers convert between signed and unsigned types within the range of the
+/// target, integral floats convert to integers, and any number converts to a float.
+pub fn cast_value(value: &Value, type_name: &str) -> Option<Value> {
+    let integer = || match value {
+        Value::Int(value) => Some(*value as i128),
+        Value::UInt(value) => Some(*value as i128),
+        Value::Float(value) if value.fract() == 0.0 && value.abs() < 2f64.powi(64) => {
+            Some(*value as i128)
+        }
+        _ => None,
+    };
+    let (min, max): (i128, i128) = match type_name {
+        "int8" => (i8::MIN.into(), i8::MAX.into()),
+        "int16" => (i16::MIN.into(), i16::MAX.into()),
+        "int32" => (i32::MIN.into(), i32::MAX.into()),
+        "int64" | TIME_TYPE | DURATION_TYPE => (i64::MIN.into(), i64::MAX.into()),
+        "byte" | "char" | "uint8" => (0, u8::MAX.into()),
+        "uint16" => (0, u16::MAX.into()),
+        "uint32" => (0, u32::MAX.into()),
+        "uint64" => (0, u64::MAX.into()),
+        _ => (0, 0),
+    };
+    match (ValueKind::of(type_name)?, value) {
+        (ValueKind::Bool, Value::Bool(value)) => Some(Value::Bool(*value)),
+        (ValueKind::Int, _) => integer()
+            .filter(|value| (min..=max).contains(value))
+            .map(|value| Value::Int(value as i64)),
+        (ValueKind::UInt, _) => integer()
+            .filter(|value| (min..=max).contains(value))
+            .map(|value| Value::UInt(value as u64)),
+        (ValueKind::Float, Value::Bool(_) | Value::String(_)) => None,
+        (ValueKind::Float, value) => value.as_f64().map(Value::Float),
+        (ValueKind::String, Value::String(value)) => Some(Value::String(value.clone())),
+        _ => None,
+    }
+}
+
+/// Write a single value of a primitive type or `builtin_interfaces` time type
+///
+/// The inverse of [`read_value`]; values are converted with [`cast_value`] and fail
+/// if they do not fit the type.
+pub fn write_value(serializer: &mut CdrSerializer, type_name: &str, value: &Value) -> Result<()> {
+    let mismatch =
+        || BagError::schema_validation(format!("Value {value:?} does not fit type {type_name}"));
+    match (
+        type_name,
+        cast_value(value, type_name).ok_or_else(mismatch)?,
+    ) {
+        ("bool", Value::Bool(value)) => serializer.write_bool(value),
+        ("int8", Value::Int(value)) => serializer.write_i8(value as i8),
+        ("int16", Value::Int(value)) => serializer.write_i16(value as i16),
+        ("int32", Value::Int(value)) => serializer.write_i32(value as i32),
+        ("int64", Value::Int(value)) => serializer.write_i64(value),
+        ("byte" | "char" | "uint8", Value::UInt(value)) => serializer.write_u8(value as u8),
+        ("uint16", Value::UInt(value)) => serializer.write_u16(value as u16),
+        ("uint32", Value::UInt(value)) => serializer.write_u32(value as u32),
+        ("uint64", Value::UInt(value)) => serializer.write_u64(value),
+        ("float32", Value::Float(value)) => serializer.write_f32(value as f32),
+        ("float64", Value::Float(value)) => serializer.write_f64(value),
+        ("string", Value::String(value)) => serializer.write_string(&value),
+        ("wstring", Value::String(value)) => serializer.write_wstring(&value),
+        (TIME_TYPE | DURATION_TYPE, Value::Int(value)) => {
+            let sec = value.div_euclid(1_000_000_000);
+            let sec = i32::try_from(sec).map_err(|_| mismatch())?;
+            serializer.write_i32(sec);
+            serializer.write_u32(value.rem_euclid(1_000_000_000) as u32);
+        }
+        _ => return Err(mismatch()),
+    }
+    Ok(())
+}
+
+/// Encode a complete message of `type_name`, the inverse of [`decode_message`]
+///
+/// Fields are written in the order of the schema and looked up by name in
+/// `message`; fields of `message` the schema does not declare are ignored. Fails if
+/// a field is missing, a value does not fit its type or an array has a length its
+/// type does not allow.
+pub fn encode_message(
+    serializer: &mut CdrSerializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+    message: &DynamicMessage,
+) -> Result<()> {
+    let schema = schemas
+        .get(type_name)
+        .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+    if schema.fields.is_empty() {
+        // Empty messages are serialized with a single placeholder byte
+        serializer.write_u8(0);
+    }
+    for field in &schema.fields {
+        let value = message.get(&field.name).ok_or_else(|| {
+            BagError::schema_validation(format!(
+                "Field {} of {} is missing",
+                field.name, schema.name
+            ))
+        })?;
+        encode_field(serializer, schemas, field, value)?;
+    }
+    Ok(())
+}
+
+/// Encode a complete field, including all array elements
+fn encode_field(
+    serializer: &mut CdrSerializer,
+    schemas: &MessageSchemas,
+    field: &FieldDef,
+    value: &FieldValue,
+) -> Result<()> {
+    let base = field.base_type();
+    let Some(spec) = field.array() else {
+        return encode_element(serializer, schemas, base, value);
+    };
+    let count = match value {
+        FieldValue::Bytes(bytes) => bytes.len(),
+        FieldValue::Array(elements) => elements.len(),
+        _ => {
+            return Err(BagError::schema_validation(format!(
+                "Field {} of type {} needs an array value",
+                field.name, field.type_name
+            )))
+        }
+    };
+    let fits = match spec {
+        ArraySpec::Fixed(length) => count == length,
+        ArraySpec::Bounded(bound) => count <= bound,
+        ArraySpec::Unbounded => true,
+    };
+    if !fits {
+        return Err(BagError::schema_validation(format!(
+            "Field {} of type {} cannot hold {count} elements",
+            field.name, field.type_name
+        )));
+    }
+    if spec.is_sequence() {
+        serializer.write_sequence_length(count);
+    }
+    match value {
+        FieldValue::Bytes(bytes) => {
+            for &byte in bytes {
+                write_value(serializer, base, &Value::UInt(byte.into()))?;
+            }
+        }
+        FieldValue::Array(elements) => {
+            for element in elements {
+                encode_element(serializer, schemas, base, element)?;
+            }
+        }
+        _ => unreachable!("checked above"),
+    }
+    Ok(())
+}
+
+/// Encode a single primitive, string or nested message
+fn encode_element(
+    serializer: &mut CdrSerializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+    value: &FieldValue,
+) -> Result<()> {
+    match value {
+        FieldValue::Value(value) => write_value(serializer, type_name, value),
+        FieldValue::Message(message) => encode_message(serializer, schemas, type_name, message),
+        _ => Err(BagError::schema_validation(format!(
+            "Array value given for a single {type_name}"
+        ))),
+    }
+}
+
+/// Build a message of `type_name` with every field set to its default value
+///
+/// Fields without a declared default are zero, false or empty. Fixed-size arrays get
+/// their full length of such elements; sequences are empty.
+/// The result has the shape [`decode_message`] produces for the type.
+pub fn default_message(schemas: &MessageSchemas, type_name: &str) -> Result<DynamicMessage> {
+    default_nested(schemas, type_name, 0)
+}
+
+/// Build a default message `depth` levels below the root
+fn default_nested(
+    schemas: &MessageSchemas,
+    type_name: &str,
+    depth: usize,
+) -> Result<DynamicMessage> {
+    if depth >= MAX_NESTING_DEPTH {
+        return Err(BagError::schema_validation(format!(
+            "Type {type_name} nests messages deeper than {MAX_NESTING_DEPTH} levels"
+        )));
+    }
+    let schema = schemas
+        .get(type_name)
+        .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+    let fields = schema
+        .fields
+        .iter()
+        .map(|field| {
+            let base = field.base_type();
+            let value = match (&field.default, field.array()) {
+                (Some(DefaultValue::Value(value)), _) => FieldValue::Value(value.into()),
+                // Defaults are checked to fit the field's type when parsed
+                (Some(DefaultValue::Array(values)), _) if matches!(base, "byte" | "uint8") => {
+                    FieldValue::Bytes(
+                        values
+                            .iter()
+                            .map(|value| value.as_u64().unwrap_or_default() as u8)
+                            .collect(),
+                    )
+                }
+                (Some(DefaultValue::Array(values)), _) => FieldValue::Array(
+                    values
+                        .iter()
+                        .map(|value| FieldValue::Value(value.into()))
+                        .collect(),
+                ),
+                (None, None) => default_element(schemas, base, depth)?,
+                (None, Some(ArraySpec::Fixed(length))) if matches!(base, "byte" | "uint8") => {
+                    FieldValue::Bytes(vec![0; length])
+                }
+                (None, Some(ArraySpec::Fixed(length))) => FieldValue::Array(
+                    (0..length)
+                        .map(|_| default_element(schemas, base, depth))
+                        .collect::<Result<_>>()?,
+                ),
+                (None, Some(_)) if matches!(base, "byte" | "uint8") => {
+                    FieldValue::Bytes(Vec::new())
+                }
+                (None, Some(_)) => FieldValue::Array(Vec::new()),
+            };
+            Ok((field.name.clone(), value))
+        })
+        .collect::<Result<_>>()?;
+    Ok(DynamicMessage {
+        message_type: schema.name.clone(),
+        fields,
+    })
+}
+
+/// Default value of a single primitive, string or nested message
+fn default_element(schemas: &MessageSchemas, type_name: &str, depth: usize) -> Result<FieldValue> {
+    if primitive_size(type_name).is_none() && !matches!(type_name, "string" | "wstring") {
+        return Ok(FieldValue::Message(default_nested(
+            schemas,
+            type_name,
+            depth + 1,
+        )?));
+    }
+    Ok(FieldValue::Value(match ValueKind::of(type_name) {
+        Some(ValueKind::Bool) => Value::Bool(false),
+        Some(ValueKind::Int) => Value::Int(0),
+        Some(ValueKind::UInt) => Value::UInt(0),
+        Some(ValueKind::Float) => Value::Float(0.0),
+        _ => Value::String(String::new()),
+    }))
+}
+
+/// Parsed schemas of every connection decoded so far, by type and definition
+type SchemaCache = Mutex<HashMap<(String, u64), Arc<MessageSchemas>>>;
+
+/// Get the schemas of `connection`, parsing its definition only the first time
+///
+/// Connections with the same message type and definition share one parsed copy, so
+/// decoding message after message does not parse the definition again.
+pub fn connection_schemas(connection: &Connection) -> Result<Arc<MessageSchemas>> {
+    static CACHE: OnceLock<SchemaCache> = OnceLock::new();
+
+    let mut hasher = DefaultHasher::new();
+    connection.message_definition.hash(&mut hasher);
+    let key = (connection.message_type.clone(), hasher.finish());
+
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(schemas) = cache.lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return Ok(schemas);
+    }
+    let schemas = Arc::new(connection.message_schemas()?);
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(key, schemas.clone());
+    }
+    Ok(schemas)
+}
+
+/// Skip a complete field, including all array elements
+pub fn skip_field(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    field: &FieldDef,
+) -> Result<()> {
+    let count = match field.array() {
+        None => 1,
+        Some(ArraySpec::Fixed(length)) => length,
+        Some(_) => deserializer.read_length(1)?,
+    };
+    skip_elements(deserializer, schemas, field.base_type(), count)
+}
+
+/// Skip `count` consecutive elements of `type_name`
+pub fn skip_elements(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+    count: usize,
+) -> Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    if let Some(size) = primitive_size(type_name) {
+        return deserializer.skip_primitives(size, count);
+    }
+    match type_name {
+        "string" => {
+            for _ in 0..count {
+                let length = deserializer.read_length(1)?;
+                deserializer.skip_bytes(length)?;
+            }
+        }
+        "wstring" => {
+            for _ in 0..count {
+                let length = deserializer.read_length(4)?;
+                deserializer.skip_primitives(4, length)?;
+            }
+        }
+        _ => {
+            for _ in 0..count {
+                skip_message(deserializer, schemas, type_name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Skip a complete nested message
+pub fn skip_message(
+    deserializer: &mut CdrDeserializer,
+    schemas: &MessageSchemas,
+    type_name: &str,
+) -> Result<()> {
+    let schema = schemas
+        .get(type_name)
+        .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+    deserializer.nested(|deserializer| {
+        if schema.fields.is_empty() {
+            // Empty messages are serialized with a single placeholder byte
+            deserializer.read_u8()?;
+            return Ok(());
+        }
+        for field in &schema.fields {
+            skip_field(deserializer, schemas, field)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_nested_and_read_value() {
+        let schemas = MessageSchemas::parse_msg(
+            "pkg/msg/Outer",
+            "uint8 flag\nInner[] items\nfloat64 value\n\
+             ================================================================================\n\
+             MSG: pkg/Inner\nstring name\nint16[2] pair\n",
+        )
+        .unwrap();
+
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.push(1); // flag
+        data.extend_from_slice(&[0; 3]);
+        data.extend_from_slice(&1u32.to_le_bytes()); // one item
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"ab\0");
+        data.push(0); // align int16
+        data.extend_from_slice(&5i16.to_le_bytes());
+        data.extend_from_slice(&6i16.to_le_bytes());
+        data.extend_from_slice(&[0; 4]); // align float64 to offset 24
+        data.extend_from_slice(&2.5f64.to_le_bytes());
+
+        let root = schemas.root();
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        skip_field(&mut deserializer, &schemas, &root.fields[0]).unwrap();
+        skip_field(&mut deserializer, &schemas, &root.fields[1]).unwrap();
+        assert_eq!(
+            read_value(&mut deserializer, root.fields[2].base_type()).unwrap(),
+            Value::Float(2.5)
+        );
+        assert_eq!(deserializer.position(), data.len());
+    }
+
+    #[test]
+    fn test_self_referencing_schema_is_rejected() {
+        use crate::cdr::MAX_NESTING_DEPTH;
+
+        let schemas =
+            MessageSchemas::parse_msg("pkg/msg/Node", "uint8 value\nNode child\n").unwrap();
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.resize(4 + 2 * MAX_NESTING_DEPTH, 1);
+
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert!(decode_message(&mut deserializer, &schemas, "pkg/msg/Node").is_err());
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert!(skip_message(&mut deserializer, &schemas, "pkg/msg/Node").is_err());
+
+        // Likewise a sequence claiming more items than there are bytes
+        let schemas = MessageSchemas::parse_msg("pkg/msg/List", "string[] names\n").unwrap();
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert!(matches!(
+            decode_message(&mut deserializer, &schemas, "pkg/msg/List"),
+            Err(BagError::CdrDeserialization { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_message_round_trip() {
+        let schemas = MessageSchemas::parse_msg(
+            "pkg/msg/Outer",
+            "uint8[] blob\nInner[] items\nfloat64 value\nint8[2] pair\nuint16[<=2] bounded\n\
+             ================================================================================\n\
+             MSG: pkg/Inner\nstring name\nbool flag\n",
+        )
+        .unwrap();
+
+        let mut message = default_message(&schemas, "pkg/msg/Outer").unwrap();
+        assert_eq!(
+            message.get("pair"),
+            Some(&FieldValue::Array(vec![
+                FieldValue::Value(Value::Int(0));
+                2
+            ]))
+        );
+        let mut serializer = CdrSerializer::new();
+        encode_message(&mut serializer, &schemas, "pkg/msg/Outer", &message).unwrap();
+        let data = serializer.into_bytes();
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert_eq!(
+            decode_message(&mut deserializer, &schemas, "pkg/msg/Outer").unwrap(),
+            message
+        );
+
+        let mut inner = default_message(&schemas, "pkg/Inner").unwrap();
+        inner.fields[0].1 = FieldValue::Value(Value::String("ab".to_string()));
+        message.fields[0].1 = FieldValue::Bytes(vec![7, 8]);
+        message.fields[1].1 = FieldValue::Array(vec![FieldValue::Message(inner)]);
+        // Values are converted to the declared type when they fit
+        message.fields[2].1 = FieldValue::Value(Value::Int(3));
+        let mut serializer = CdrSerializer::new();
+        encode_message(&mut serializer, &schemas, "pkg/msg/Outer", &message).unwrap();
+        let data = serializer.into_bytes();
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        let decoded = decode_message(&mut deserializer, &schemas, "pkg/msg/Outer").unwrap();
+        assert_eq!(deserializer.position(), data.len());
+        assert_eq!(
+            decoded.path("value"),
+            Some(&FieldValue::Value(Value::Float(3.0)))
+        );
+        assert_eq!(decoded.get("items"), message.get("items"));
+        assert_eq!(decoded.get("blob"), message.get("blob"));
+
+        let encode = |message: &DynamicMessage| {
+            encode_message(
+                &mut CdrSerializer::new(),
+                &schemas,
+                "pkg/msg/Outer",
+                message,
+            )
+        };
+        let mut invalid = message.clone();
+        invalid.fields[3].1 = FieldValue::Array(vec![FieldValue::Value(Value::Int(300)); 2]);
+        assert!(encode(&invalid).is_err());
+        let mut invalid = message.clone();
+        invalid.fields[4].1 = FieldValue::Array(vec![FieldValue::Value(Value::UInt(1)); 3]);
+        assert!(encode(&invalid).is_err());
+        let mut invalid = message.clone();
+        invalid.fields.pop();
+        assert!(encode(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_decode_message_and_paths() {
+        let schemas = MessageSchemas::parse_msg(
+            "pkg/msg/Outer",
+            "uint8[] blob\nInner[] items\nfloat64 value\n\
+             ================================================================================\n\
+             MSG: pkg/Inner\nstring name\nint16[2] pair\n",
+        )
+        .unwrap();
+
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.extend_from_slice(&2u32.to_le_bytes()); // two blob bytes
+        data.extend_from_slice(&[7, 8]);
+        data.extend_from_slice(&[0; 2]); // align sequence length
+        data.extend_from_slice(&1u32.to_le_bytes()); // one item
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"ab\0");
+        data.push(0); // align int16
+        data.extend_from_slice(&5i16.to_le_bytes());
+        data.extend_from_slice(&6i16.to_le_bytes()); // float64 at offset 24 is aligned
+        data.extend_from_slice(&2.5f64.to_le_bytes());
+
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        let message = decode_message(&mut deserializer, &schemas, "pkg/msg/Outer").unwrap();
+        assert_eq!(deserializer.position(), data.len());
+        assert_eq!(message.message_type, "pkg/msg/Outer");
+        assert_eq!(message.get("blob"), Some(&FieldValue::Bytes(vec![7, 8])));
+        assert_eq!(
+            message.path("value").and_then(FieldValue::as_f64),
+            Some(2.5)
+        );
+        assert_eq!(
+            message.path("items[0].name").and_then(FieldValue::as_value),
+            Some(&Value::String("ab".to_string()))
+        );
+        assert_eq!(
+            message
+                .path("items[0].pair[1]")
+                .and_then(FieldValue::as_f64),
+            Some(6.0)
+        );
+        assert_eq!(
+            message
+                .path("items[0]")
+                .and_then(FieldValue::as_message)
+                .map(|m| m.message_type.as_str()),
+            Some("pkg/msg/Inner")
+        );
+        assert!(message.path("items[1].name").is_none());
+        assert!(message.path("value.x").is_none());
+    }
+
+    #[test]
+    fn test_build_message_with_set_field() {
+        let schemas = MessageSchemas::parse_msg(
+            "pkg/msg/Marker",
+            "string ns\nint32 id 7\nPoint[2] points\nuint8[] data\n\
+             ================================================================================\n\
+             MSG: pkg/Point\nfloat64 x\nfloat64 y\n",
+        )
+        .unwrap();
+
+        let mut marker = DynamicMessage::new(&schemas).unwrap();
+        assert_eq!(marker.message_type, "pkg/msg/Marker");
+        assert_eq!(marker.get_field("id"), Some(&FieldValue::from(7)));
+        marker.set_field("ns", "lane").unwrap();
+        marker.set_field("points[1].y", 2.5f32).unwrap();
+        marker.set_field("data", vec![1u8, 2, 3]).unwrap();
+        assert_eq!(
+            marker.get_field("points[1].y"),
+            Some(&FieldValue::from(2.5))
+        );
+        assert!(marker.set_field("points[2].x", 1.0).is_err());
+        assert!(marker.set_field("color", 1.0).is_err());
+
+        let data = marker.to_cdr_bytes(&schemas).unwrap();
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        assert_eq!(
+            decode_message(&mut deserializer, &schemas, "pkg/msg/Marker").unwrap(),
+            marker
+        );
+
+        // Values are checked against the field types when serializing
+        marker.set_field("points[0].x", "far").unwrap();
+        assert!(marker.to_cdr_bytes(&schemas).is_err());
+    }
+}
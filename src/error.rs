@@ -88,10 +88,29 @@ pub enum BagError {
     #[error("Message type not found: {message_type}")]
     MessageTypeNotFound { message_type: String },
 
+    /// Message decoded as a type other than the one its connection carries
+    #[error("Topic '{topic}' carries {actual}, not {expected}")]
+    MessageTypeMismatch {
+        topic: String,
+        expected: String,
+        actual: String,
+    },
+
     /// Schema validation error
     #[error("Schema validation error: {reason}")]
     SchemaValidation { reason: String },
 
+    /// Stored type description hash does not match the stored message definition
+    #[error(
+        "Type hash mismatch for topic '{topic}' ({message_type}): stored {stored}, definition hashes to {computed}"
+    )]
+    TypeHashMismatch {
+        topic: String,
+        message_type: String,
+        stored: String,
+        computed: String,
+    },
+
     /// Connection not found
     #[error("Connection not found for topic: {topic}")]
     ConnectionNotFound { topic: String },
@@ -100,17 +119,130 @@ pub enum BagError {
     #[error("Connection already exists for topic: {topic}")]
     ConnectionAlreadyExists { topic: String },
 
+    /// Connection with an invalid topic name or message type
+    #[error("Invalid connection for topic '{topic}': {reason}")]
+    InvalidConnection { topic: String, reason: String },
+
     /// Invalid QoS profile
     #[error("Invalid QoS profile: {reason}")]
     InvalidQosProfile { reason: String },
 
+    /// Transform lookup failure (unknown or disconnected frames, extrapolation)
+    #[error("Cannot transform from '{source_frame}' to '{target_frame}': {reason}")]
+    TransformLookup {
+        target_frame: String,
+        source_frame: String,
+        reason: String,
+    },
+
     /// Writer error with custom message
     #[error("Writer error: {message}")]
     Writer { message: String },
 
+    /// Export/conversion error with custom message
+    #[error("Export error: {message}")]
+    Export { message: String },
+
     /// Generic error with custom message
     #[error("Bag error: {message}")]
     Generic { message: String },
+
+    /// Error of a specific message, with where in the bag it occurred
+    #[error("{source} ({context})")]
+    WithContext {
+        context: Box<ErrorContext>,
+        source: Box<BagError>,
+    },
+}
+
+/// Where in a bag an error occurred, attached with [`BagError::with_context`]
+///
+/// Byte offsets within a message payload are part of
+/// [`BagError::CdrDeserialization`] itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Topic of the message
+    pub topic: Option<String>,
+    /// Log time of the message in nanoseconds
+    pub timestamp: Option<u64>,
+    /// Storage file holding the message
+    pub file: Option<PathBuf>,
+    /// Index of the record within the storage file
+    pub record: Option<usize>,
+    /// Index of the MCAP chunk within the storage file
+    pub chunk: Option<usize>,
+}
+
+impl ErrorContext {
+    /// Context of the message on `topic` logged at `timestamp`
+    pub fn message(topic: impl Into<String>, timestamp: u64) -> Self {
+        Self {
+            topic: Some(topic.into()),
+            timestamp: Some(timestamp),
+            ..Self::default()
+        }
+    }
+
+    /// Context of the storage file `file` as a whole
+    pub fn file(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: Some(file.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Context of record `record` of the storage file `file`
+    pub fn record(file: impl Into<PathBuf>, record: usize) -> Self {
+        Self {
+            record: Some(record),
+            ..Self::file(file)
+        }
+    }
+
+    /// Context of chunk `chunk` of the MCAP file `file`
+    pub fn chunk(file: impl Into<PathBuf>, chunk: usize) -> Self {
+        Self {
+            chunk: Some(chunk),
+            ..Self::file(file)
+        }
+    }
+
+    /// Set the storage file
+    pub fn with_file(mut self, file: Option<impl Into<PathBuf>>) -> Self {
+        self.file = file.map(Into::into);
+        self
+    }
+
+    /// Fill the fields missing here from `other`
+    fn merge(&mut self, other: ErrorContext) {
+        self.topic = self.topic.take().or(other.topic);
+        self.timestamp = self.timestamp.or(other.timestamp);
+        self.file = self.file.take().or(other.file);
+        self.record = self.record.or(other.record);
+        self.chunk = self.chunk.or(other.chunk);
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(topic) = &self.topic {
+            parts.push(format!("topic {topic}"));
+        }
+        if let Some(timestamp) = self.timestamp {
+            parts.push(format!("timestamp {timestamp}"));
+        }
+        if let Some(file) = &self.file {
+            parts.push(format!("file {}", file.display()));
+        }
+        if let Some(record) = self.record {
+            parts.push(format!("record {record}"));
+        }
+        if let Some(chunk) = self.chunk {
+            parts.push(format!("chunk {chunk}"));
+        }
+        f.write_str(&parts.join(", "))
+    }
 }
 
 /// Type alias for backwards compatibility
@@ -131,6 +263,13 @@ impl BagError {
         }
     }
 
+    /// Create an export error
+    pub fn export(message: impl Into<String>) -> Self {
+        Self::Export {
+            message: message.into(),
+        }
+    }
+
     /// Create a compression error
     pub fn compression(message: impl Into<String>) -> Self {
         Self::Compression(message.into())
@@ -163,6 +302,40 @@ impl BagError {
         }
     }
 
+    /// Create a message type mismatch error
+    pub fn message_type_mismatch(
+        topic: impl Into<String>,
+        expected: impl Into<String>,
+        actual: impl Into<String>,
+    ) -> Self {
+        Self::MessageTypeMismatch {
+            topic: topic.into(),
+            expected: expected.into(),
+            actual: actual.into(),
+        }
+    }
+
+    /// Create an invalid connection error
+    pub fn invalid_connection(topic: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::InvalidConnection {
+            topic: topic.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a transform lookup error
+    pub fn transform_lookup(
+        target_frame: impl Into<String>,
+        source_frame: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self::TransformLookup {
+            target_frame: target_frame.into(),
+            source_frame: source_frame.into(),
+            reason: reason.into(),
+        }
+    }
+
     /// Create a schema validation error
     pub fn schema_validation(reason: impl Into<String>) -> Self {
         Self::SchemaValidation {
@@ -184,6 +357,45 @@ impl BagError {
         }
     }
 
+    /// Attach where in the bag the error occurred
+    ///
+    /// Context already attached takes precedence over `context`, so the innermost,
+    /// most specific location is kept.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::WithContext {
+                context: mut existing,
+                source,
+            } => {
+                existing.merge(context);
+                Self::WithContext {
+                    context: existing,
+                    source,
+                }
+            }
+            error => Self::WithContext {
+                context: Box::new(context),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Where in the bag the error occurred, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context, for matching on the variant
+    pub fn root(&self) -> &BagError {
+        match self {
+            Self::WithContext { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
     /// Create an invalid QoS profile error
     pub fn invalid_qos_profile(reason: impl Into<String>) -> Self {
         Self::InvalidQosProfile {
@@ -0,0 +1,320 @@
+//! Splitting a bag into one bag per topic or topic group
+//!
+//! Downstream tools often expect single-topic bags, e.g. a labeling tool fed only
+//! camera images or a SLAM pipeline reading only lidar scans. [`Explode`] copies the
+//! messages of a bag into a bag per topic, or per named group of topics, in one pass
+//! over the input. Every output connection keeps the message definition, type hash,
+//! serialization format and offered QoS profiles of the recorded one.
+//!
+//! # Example
+//! ```no_run
+//! use rosbags_rs::explode::Explode;
+//! use rosbags_rs::{Reader, StoragePlugin, Writer};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let reader = Reader::builder("path/to/bag").open()?;
+//! let bags = Explode::new()
+//!     .group("cameras", ["/camera/**"])
+//!     .run(&reader, "path/to/topics", |path| {
+//!         Writer::builder(path).storage(StoragePlugin::Mcap)
+//!     })?;
+//! // path/to/topics/cameras, path/to/topics/imu_data, ...
+//! for bag in bags {
+//!     println!("{}: {} messages", bag.path.display(), bag.message_count);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{BagError, Result};
+use crate::reader::Reader;
+use crate::types::{Connection, TopicPattern};
+use crate::writer::{Writer, WriterBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Bag written by [`Explode::run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplodedBag {
+    /// Name of the bag: the group name, or the topic name for ungrouped topics
+    pub name: String,
+    /// Directory of the bag
+    pub path: PathBuf,
+    /// Topics in the bag, in the order of the input connections
+    pub topics: Vec<String>,
+    /// Number of messages written
+    pub message_count: u64,
+}
+
+/// Split a bag into a bag per topic or topic group
+///
+/// Topics matching the patterns of a [group](Explode::group) are written to one bag
+/// named after the group; the first matching group wins. Every other topic is
+/// written to a bag named after the topic, with the leading `/` removed and the
+/// other `/` replaced by `_` (`/camera/image_raw` becomes `camera_image_raw`);
+/// topics that map to the same name, like `/a_b` and `/a/b`, have to be grouped.
+/// Topics recorded with several types keep a connection per type in their bag, and
+/// topics without messages still get a bag listing them.
+#[derive(Debug, Clone, Default)]
+pub struct Explode {
+    groups: Vec<(String, Vec<TopicPattern>)>,
+    topics: Option<Vec<TopicPattern>>,
+}
+
+impl Explode {
+    /// Split every topic into its own bag
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the topics matching any of `patterns` to a single bag named `name`
+    pub fn group<I, P>(mut self, name: impl Into<String>, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<TopicPattern>,
+    {
+        self.groups
+            .push((name.into(), patterns.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Only split the topics matching any of `patterns`; other topics are left out
+    pub fn topics<I, P>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<TopicPattern>,
+    {
+        self.topics = Some(patterns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Name of the bag `topic` is written to, `None` if the topic is left out
+    pub fn bag_name(&self, topic: &str) -> Option<String> {
+        if let Some(patterns) = &self.topics {
+            if !patterns.iter().any(|pattern| pattern.matches(topic)) {
+                return None;
+            }
+        }
+        Some(match self.group_of(topic) {
+            Some(name) => name.to_string(),
+            None => topic.trim_start_matches('/').replace('/', "_"),
+        })
+    }
+
+    /// Name of the first group matching `topic`
+    fn group_of(&self, topic: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches(topic)))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Copy the messages of an open `reader` into bags in `output_dir`
+    ///
+    /// `builder` configures the writer of each bag from its path, e.g.
+    /// `|path| Writer::builder(path)` for the defaults. `output_dir` is created if
+    /// missing. Bag names that are empty, contain path separators or already exist in
+    /// it are rejected before anything is written, as are ungrouped topics whose bag
+    /// name another topic or group already uses (`/a_b` and `/a/b` both map to
+    /// `a_b`). Returns the written bags in the order of their first connection.
+    pub fn run<P, F>(
+        &self,
+        reader: &Reader,
+        output_dir: P,
+        mut builder: F,
+    ) -> Result<Vec<ExplodedBag>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> WriterBuilder,
+    {
+        let output_dir = output_dir.as_ref();
+
+        let mut bags: Vec<ExplodedBag> = Vec::new();
+        // Topic of each bag written for an ungrouped topic, `None` for group bags
+        let mut ungrouped: Vec<Option<&str>> = Vec::new();
+        let mut selected: Vec<(&Connection, usize)> = Vec::new();
+        for connection in reader.connections() {
+            let Some(name) = self.bag_name(&connection.topic) else {
+                continue;
+            };
+            let topic = match self.group_of(&connection.topic) {
+                Some(_) => None,
+                None => Some(connection.topic.as_str()),
+            };
+            let index = match bags.iter().position(|bag| bag.name == name) {
+                Some(index) if ungrouped[index] == topic => index,
+                Some(index) => {
+                    return Err(BagError::generic(format!(
+                        "Topics {} and {} both map to bag name '{name}'",
+                        connection.topic, bags[index].topics[0]
+                    )));
+                }
+                None => {
+                    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+                        return Err(BagError::generic(format!(
+                            "Invalid bag name '{name}' for topic {}",
+                            connection.topic
+                        )));
+                    }
+                    let path = output_dir.join(&name);
+                    if path.exists() {
+                        return Err(BagError::generic(format!(
+                            "Output bag {} already exists",
+                            path.display()
+                        )));
+                    }
+                    bags.push(ExplodedBag {
+                        name,
+                        path,
+                        topics: Vec::new(),
+                        message_count: 0,
+                    });
+                    ungrouped.push(topic);
+                    bags.len() - 1
+                }
+            };
+            let topics = &mut bags[index].topics;
+            if !topics.contains(&connection.topic) {
+                topics.push(connection.topic.clone());
+            }
+            selected.push((connection, index));
+        }
+        if selected.is_empty() {
+            return Ok(bags);
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        let mut writers = bags
+            .iter()
+            .map(|bag| builder(&bag.path).open())
+            .collect::<Result<Vec<Writer>>>()?;
+        let mut connections = HashMap::new();
+        for (connection, index) in &selected {
+            let written = writers[*index].add_connection_from(connection)?;
+            connections.insert(connection.id, (*index, written));
+        }
+
+        let filter: Vec<Connection> = selected.iter().map(|(c, _)| (*c).clone()).collect();
+        for message in reader.raw_messages_filtered(Some(&filter), None, None)? {
+            let message = message?;
+            let (index, written) = &connections[&message.connection.id];
+            writers[*index].write(written, message.timestamp, &message.raw_data)?;
+            bags[*index].message_count += 1;
+        }
+        for writer in &mut writers {
+            writer.close()?;
+        }
+        Ok(bags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explode_by_topic_and_group() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("input");
+        let mut writer = Writer::builder(&input).open().unwrap();
+        let mut connections = Vec::new();
+        for topic in ["/camera/left", "/camera/right", "/imu/data", "/tf"] {
+            let connection = Connection::builder(topic, "std_msgs/msg/UInt8")
+                .offered_qos_profiles(vec![crate::types::QosProfile::default()])
+                .build()
+                .unwrap();
+            connections.push(writer.add_connection_from(&connection).unwrap());
+        }
+        for i in 0..12u8 {
+            let connection = &connections[usize::from(i % 4)];
+            writer
+                .write(connection, u64::from(i), &[0x00, 0x01, 0x00, 0x00, i])
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = Reader::builder(&input).open().unwrap();
+        let explode = Explode::new()
+            .group("cameras", ["/camera/*"])
+            .topics(["/camera/*", "/imu/**"]);
+        assert_eq!(explode.bag_name("/imu/data").as_deref(), Some("imu_data"));
+        assert_eq!(explode.bag_name("/tf"), None);
+
+        let output = temp_dir.path().join("topics");
+        let bags = explode
+            .run(&reader, &output, |path| Writer::builder(path))
+            .unwrap();
+        assert_eq!(
+            bags,
+            [
+                ExplodedBag {
+                    name: "cameras".to_string(),
+                    path: output.join("cameras"),
+                    topics: vec!["/camera/left".to_string(), "/camera/right".to_string()],
+                    message_count: 6,
+                },
+                ExplodedBag {
+                    name: "imu_data".to_string(),
+                    path: output.join("imu_data"),
+                    topics: vec!["/imu/data".to_string()],
+                    message_count: 3,
+                },
+            ]
+        );
+
+        let cameras = Reader::builder(output.join("cameras")).open().unwrap();
+        let timestamps: Vec<_> = cameras
+            .messages()
+            .unwrap()
+            .map(|message| message.unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [0, 1, 4, 5, 8, 9]);
+        for connection in cameras.connections() {
+            let recorded = &reader.connections_for_topic(&connection.topic)[0];
+            assert_eq!(
+                connection.offered_qos_profiles,
+                recorded.offered_qos_profiles
+            );
+            assert_eq!(connection.message_definition, recorded.message_definition);
+            assert_eq!(
+                connection.type_description_hash,
+                recorded.type_description_hash
+            );
+        }
+
+        // Existing bags are not overwritten
+        assert!(explode
+            .run(&reader, &output, |path| Writer::builder(path))
+            .is_err());
+
+        // Ungrouped topics with the same bag name are not merged
+        let input = temp_dir.path().join("colliding");
+        let mut writer = Writer::builder(&input).open().unwrap();
+        for topic in ["/a_b", "/a/b"] {
+            let connection = Connection::new(topic, "std_msgs/msg/UInt8").unwrap();
+            writer.add_connection_from(&connection).unwrap();
+        }
+        writer.close().unwrap();
+        let reader = Reader::builder(&input).open().unwrap();
+        let output = temp_dir.path().join("colliding_topics");
+        let error = Explode::new()
+            .run(&reader, &output, |path| Writer::builder(path))
+            .unwrap_err();
+        assert!(error.to_string().contains("'a_b'"), "{error}");
+        assert!(!output.exists());
+
+        // ...nor an ungrouped topic with a group of the same name
+        assert!(Explode::new()
+            .group("a_b", ["/a_b"])
+            .run(&reader, &output, |path| Writer::builder(path))
+            .is_err());
+
+        // Grouping them writes one bag
+        let bags = Explode::new()
+            .group("ab", ["/a_b", "/a/b"])
+            .run(&reader, &output, |path| Writer::builder(path))
+            .unwrap();
+        assert_eq!(bags.len(), 1);
+        assert_eq!(bags[0].topics, ["/a_b", "/a/b"]);
+    }
+}
@@ -0,0 +1,261 @@
+//! Camera calibration export from `sensor_msgs/msg/CameraInfo` topics
+//!
+//! Recorded datasets usually carry the calibration only as `camera_info` messages,
+//! while calibration and SLAM tools read it from YAML files. [`calibration_yaml`]
+//! renders a [`CameraInfo`] in the layout of `camera_calibration_parsers` (what
+//! `camera_info_manager` loads), of OpenCV `cv::FileStorage`, or of a Kalibr camchain.
+
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{CameraInfo, FromCdr};
+use crate::reader::Reader;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Message type of camera calibrations
+pub const CAMERA_INFO_TYPE: &str = "sensor_msgs/msg/CameraInfo";
+
+/// Layout of the YAML written by [`calibration_yaml`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CalibrationFormat {
+    /// `camera_calibration_parsers` YAML, as loaded by `camera_info_manager`
+    #[default]
+    Ros,
+    /// OpenCV `cv::FileStorage` YAML with `!!opencv-matrix` nodes
+    OpenCv,
+    /// Kalibr camchain with a single camera keyed by the camera name (e.g. `cam0`)
+    Kalibr,
+}
+
+/// Render the calibration of `info` as YAML in `format`
+///
+/// Kalibr only knows the 4-parameter `radtan` and `equidistant` models, so
+/// `plumb_bob` and `rational_polynomial` calibrations with higher-order terms
+/// are rejected for [`CalibrationFormat::Kalibr`].
+///
+/// # Example
+/// ```
+/// use rosbags_rs::export::calibration::{calibration_yaml, CalibrationFormat};
+/// use rosbags_rs::messages::{CameraInfo, Header, RegionOfInterest, Time};
+///
+/// let info = CameraInfo {
+///     header: Header { stamp: Time { sec: 0, nanosec: 0 }, frame_id: "camera".to_string() },
+///     height: 480,
+///     width: 640,
+///     distortion_model: CameraInfo::PLUMB_BOB.to_string(),
+///     d: vec![-0.1, 0.01, 0.0, 0.0, 0.0],
+///     k: [500.0, 0.0, 320.0, 0.0, 500.0, 240.0, 0.0, 0.0, 1.0],
+///     r: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+///     p: [500.0, 0.0, 320.0, 0.0, 0.0, 500.0, 240.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+///     binning_x: 0,
+///     binning_y: 0,
+///     roi: RegionOfInterest::default(),
+/// };
+/// let yaml = calibration_yaml(&info, "cam0", CalibrationFormat::Kalibr).unwrap();
+/// assert!(yaml.contains("  intrinsics: [500, 500, 320, 240]\n"));
+/// ```
+pub fn calibration_yaml(
+    info: &CameraInfo,
+    camera_name: &str,
+    format: CalibrationFormat,
+) -> Result<String> {
+    match format {
+        CalibrationFormat::Ros => Ok(matrices_yaml(info, camera_name, false)),
+        CalibrationFormat::OpenCv => Ok(matrices_yaml(info, camera_name, true)),
+        CalibrationFormat::Kalibr => kalibr_yaml(info, camera_name),
+    }
+}
+
+/// Write the calibration of `info` to `path`
+pub fn write_calibration<P: AsRef<Path>>(
+    info: &CameraInfo,
+    camera_name: &str,
+    path: P,
+    format: CalibrationFormat,
+) -> Result<()> {
+    let path = path.as_ref();
+    let yaml = calibration_yaml(info, camera_name, format)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, yaml)?;
+    Ok(())
+}
+
+/// Write the first calibrated `CameraInfo` of `topic` to `path`
+///
+/// Cameras publish the same calibration with every frame, so only the first
+/// message with a non-zero intrinsic matrix is exported; it is returned as well.
+pub fn extract_calibration<P: AsRef<Path>>(
+    reader: &Reader,
+    topic: &str,
+    camera_name: &str,
+    path: P,
+    format: CalibrationFormat,
+) -> Result<CameraInfo> {
+    let connections = reader.connections_for_topic(topic);
+    if connections.is_empty() {
+        return Err(BagError::connection_not_found(topic));
+    }
+    if let Some(conn) = connections
+        .iter()
+        .find(|c| c.message_type != CAMERA_INFO_TYPE)
+    {
+        return Err(BagError::export(format!(
+            "Topic {topic} has type {}, expected {CAMERA_INFO_TYPE}",
+            conn.message_type
+        )));
+    }
+
+    for message_result in reader.messages_filtered(Some(&connections), None, None)? {
+        let message = message_result?;
+        let info = CameraInfo::from_cdr(&mut CdrDeserializer::new(&message.data)?)?;
+        if info.is_calibrated() {
+            write_calibration(&info, camera_name, path, format)?;
+            return Ok(info);
+        }
+    }
+    Err(BagError::export(format!(
+        "Topic {topic} has no calibrated CameraInfo message"
+    )))
+}
+
+/// `camera_calibration_parsers` layout, with OpenCV matrix tags if `opencv` is set
+fn matrices_yaml(info: &CameraInfo, camera_name: &str, opencv: bool) -> String {
+    let mut yaml = String::new();
+    if opencv {
+        yaml.push_str("%YAML:1.0\n---\n");
+    }
+    let _ = writeln!(yaml, "image_width: {}", info.width);
+    let _ = writeln!(yaml, "image_height: {}", info.height);
+    let _ = writeln!(yaml, "camera_name: {camera_name}");
+    write_matrix(&mut yaml, "camera_matrix", 3, &info.k, opencv);
+    let _ = writeln!(yaml, "distortion_model: {}", info.distortion_model);
+    write_matrix(&mut yaml, "distortion_coefficients", 1, &info.d, opencv);
+    write_matrix(&mut yaml, "rectification_matrix", 3, &info.r, opencv);
+    write_matrix(&mut yaml, "projection_matrix", 3, &info.p, opencv);
+    yaml
+}
+
+/// Matrix node with `rows`, `cols` and the row-major `data`
+fn write_matrix(yaml: &mut String, name: &str, rows: usize, values: &[f64], opencv: bool) {
+    let cols = values.len() / rows;
+    if opencv {
+        let _ = write!(
+            yaml,
+            "{name}: !!opencv-matrix\n   rows: {rows}\n   cols: {cols}\n   dt: d\n   data: {}\n",
+            list(values)
+        );
+    } else {
+        let _ = write!(
+            yaml,
+            "{name}:\n  rows: {rows}\n  cols: {cols}\n  data: {}\n",
+            list(values)
+        );
+    }
+}
+
+/// Kalibr camchain entry of a single camera
+fn kalibr_yaml(info: &CameraInfo, camera_name: &str) -> Result<String> {
+    let (model, terms) = match info.distortion_model.as_str() {
+        CameraInfo::PLUMB_BOB | CameraInfo::RATIONAL_POLYNOMIAL => ("radtan", 4),
+        CameraInfo::EQUIDISTANT => ("equidistant", 4),
+        other => {
+            return Err(BagError::export(format!(
+                "Distortion model '{other}' has no Kalibr equivalent"
+            )))
+        }
+    };
+    if info.d.iter().skip(terms).any(|&value| value != 0.0) {
+        return Err(BagError::export(format!(
+            "{} distortion with non-zero coefficients beyond the first {terms} has no Kalibr equivalent",
+            info.distortion_model
+        )));
+    }
+    let mut coefficients = info.d.clone();
+    coefficients.resize(terms, 0.0);
+
+    Ok(format!(
+        "{camera_name}:\n  camera_model: pinhole\n  intrinsics: {}\n  distortion_model: {model}\n  distortion_coeffs: {}\n  resolution: [{}, {}]\n",
+        list(&info.intrinsics()),
+        list(&coefficients),
+        info.width,
+        info.height,
+    ))
+}
+
+/// Flow sequence like `[1, 0.5, -2]`
+fn list(values: &[f64]) -> String {
+    let values: Vec<String> = values.iter().map(f64::to_string).collect();
+    format!("[{}]", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, RegionOfInterest, Time};
+
+    fn camera_info(distortion_model: &str, d: Vec<f64>) -> CameraInfo {
+        CameraInfo {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: "camera".to_string(),
+            },
+            height: 480,
+            width: 640,
+            distortion_model: distortion_model.to_string(),
+            d,
+            k: [525.5, 0.0, 319.5, 0.0, 524.0, 239.5, 0.0, 0.0, 1.0],
+            r: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            p: [
+                525.5, 0.0, 319.5, 0.0, 0.0, 524.0, 239.5, 0.0, 0.0, 0.0, 1.0, 0.0,
+            ],
+            binning_x: 0,
+            binning_y: 0,
+            roi: RegionOfInterest::default(),
+        }
+    }
+
+    #[test]
+    fn test_ros_and_opencv_yaml() {
+        let info = camera_info(CameraInfo::PLUMB_BOB, vec![-0.25, 0.1, 0.001, -0.002, 0.0]);
+
+        let ros = calibration_yaml(&info, "front", CalibrationFormat::Ros).unwrap();
+        assert!(ros.starts_with("image_width: 640\nimage_height: 480\ncamera_name: front\n"));
+        assert!(ros.contains(
+            "camera_matrix:\n  rows: 3\n  cols: 3\n  data: [525.5, 0, 319.5, 0, 524, 239.5, 0, 0, 1]\n"
+        ));
+        assert!(ros.contains("distortion_model: plumb_bob\n"));
+        assert!(ros.contains(
+            "distortion_coefficients:\n  rows: 1\n  cols: 5\n  data: [-0.25, 0.1, 0.001, -0.002, 0]\n"
+        ));
+        assert!(ros.contains("projection_matrix:\n  rows: 3\n  cols: 4\n"));
+
+        let opencv = calibration_yaml(&info, "front", CalibrationFormat::OpenCv).unwrap();
+        assert!(opencv.starts_with("%YAML:1.0\n---\n"));
+        assert!(opencv
+            .contains("rectification_matrix: !!opencv-matrix\n   rows: 3\n   cols: 3\n   dt: d\n"));
+    }
+
+    #[test]
+    fn test_kalibr_yaml() {
+        let info = camera_info(CameraInfo::PLUMB_BOB, vec![-0.25, 0.1, 0.001, -0.002, 0.0]);
+        let kalibr = calibration_yaml(&info, "cam0", CalibrationFormat::Kalibr).unwrap();
+        assert_eq!(
+            kalibr,
+            "cam0:\n  camera_model: pinhole\n  intrinsics: [525.5, 524, 319.5, 239.5]\n  \
+             distortion_model: radtan\n  distortion_coeffs: [-0.25, 0.1, 0.001, -0.002]\n  \
+             resolution: [640, 480]\n"
+        );
+
+        let fisheye = camera_info(CameraInfo::EQUIDISTANT, vec![0.1, 0.01]);
+        let kalibr = calibration_yaml(&fisheye, "cam1", CalibrationFormat::Kalibr).unwrap();
+        assert!(kalibr
+            .contains("distortion_model: equidistant\n  distortion_coeffs: [0.1, 0.01, 0, 0]\n"));
+
+        let k3 = camera_info(CameraInfo::PLUMB_BOB, vec![-0.25, 0.1, 0.0, 0.0, 0.05]);
+        assert!(calibration_yaml(&k3, "cam0", CalibrationFormat::Kalibr).is_err());
+        let unknown = camera_info("double_sphere", Vec::new());
+        assert!(calibration_yaml(&unknown, "cam0", CalibrationFormat::Kalibr).is_err());
+    }
+}
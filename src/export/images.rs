@@ -0,0 +1,867 @@
+//! Image extraction from `sensor_msgs/msg/Image` and `sensor_msgs/msg/CompressedImage` topics
+//!
+//! Raw images are converted from their ROS encoding (mono, rgb/bgr, bayer, yuv422, depth)
+//! into standard image files. 16-bit encodings are preserved when the output format
+//! supports them (PNG, TIFF).
+//!
+//! Compressed images (JPEG, PNG and the `compressedDepth` payloads of
+//! `compressed_depth_image_transport`) are decoded with [`compressed_to_dynamic`] or
+//! into raw `sensor_msgs/msg/Image` pixel buffers with [`compressed_to_image`].
+
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{CompressedImage, FromCdr, Image};
+use crate::reader::Reader;
+use image::{DynamicImage, ImageBuffer};
+use std::path::{Path, PathBuf};
+
+/// Message type of raw images
+pub const IMAGE_TYPE: &str = "sensor_msgs/msg/Image";
+
+/// Message type of compressed images
+pub const COMPRESSED_IMAGE_TYPE: &str = "sensor_msgs/msg/CompressedImage";
+
+/// Output file format for extracted images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Lossless PNG (preserves 16-bit depth)
+    Png,
+    /// JPEG (8-bit only, 16-bit images are scaled down)
+    Jpeg,
+    /// Lossless TIFF (preserves 16-bit depth)
+    Tiff,
+    /// Keep compressed payloads as recorded; raw images are written as PNG
+    Original,
+}
+
+impl ImageFormat {
+    /// File extension used for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png | ImageFormat::Original => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Tiff => "tiff",
+        }
+    }
+
+    fn as_image_format(&self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Png | ImageFormat::Original => image::ImageFormat::Png,
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Tiff => image::ImageFormat::Tiff,
+        }
+    }
+}
+
+pub use super::ExtractedFrame;
+
+/// Result of an image extraction run
+#[derive(Debug, Clone, Default)]
+pub struct ImageExtraction {
+    /// Frames successfully written, in bag order
+    pub frames: Vec<ExtractedFrame>,
+    /// Messages that could not be converted: (timestamp, reason)
+    pub skipped: Vec<(u64, String)>,
+}
+
+/// Extract all images of `topic` into `output_dir`
+///
+/// Files are named `image_XXXXXX_<timestamp>.<ext>`. Messages that cannot be decoded
+/// (unsupported encoding, truncated data) are recorded in [`ImageExtraction::skipped`]
+/// instead of aborting the extraction.
+///
+/// Each frame records the bag timestamp and, when available, the header stamp;
+/// use [`write_frame_index`](super::write_frame_index) to tabulate both.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::export::images::{extract_images, ImageFormat};
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+///
+/// let extraction = extract_images(&reader, "/camera/image_raw", "frames", ImageFormat::Png)?;
+/// println!("Wrote {} frames", extraction.frames.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn extract_images<P: AsRef<Path>>(
+    reader: &Reader,
+    topic: &str,
+    output_dir: P,
+    format: ImageFormat,
+) -> Result<ImageExtraction> {
+    let output_dir = output_dir.as_ref();
+
+    let connections = reader.connections_for_topic(topic);
+    if connections.is_empty() {
+        return Err(BagError::connection_not_found(topic));
+    }
+    if let Some(conn) = connections
+        .iter()
+        .find(|c| c.message_type != IMAGE_TYPE && c.message_type != COMPRESSED_IMAGE_TYPE)
+    {
+        return Err(BagError::export(format!(
+            "Topic {topic} has type {}, expected an image type",
+            conn.message_type
+        )));
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut extraction = ImageExtraction::default();
+    for message_result in reader.messages_filtered(Some(&connections), None, None)? {
+        let message = message_result?;
+        let index = extraction.frames.len() + extraction.skipped.len();
+        let stem = format!("image_{:06}_{}", index, message.timestamp);
+
+        let written = if message.connection.message_type == COMPRESSED_IMAGE_TYPE {
+            write_compressed_image(&message.data, output_dir, &stem, format)
+        } else {
+            write_raw_image(&message.data, output_dir, &stem, format)
+        };
+
+        match written {
+            Ok(path) => extraction.frames.push(ExtractedFrame {
+                path,
+                timestamp: message.timestamp,
+                header_stamp: super::header_stamp(&message.data),
+            }),
+            Err(e) => extraction.skipped.push((message.timestamp, e.to_string())),
+        }
+    }
+
+    Ok(extraction)
+}
+
+fn write_raw_image(data: &[u8], dir: &Path, stem: &str, format: ImageFormat) -> Result<PathBuf> {
+    let mut deserializer = CdrDeserializer::new(data)?;
+    let msg = Image::from_cdr(&mut deserializer)?;
+    let image = image_to_dynamic(&msg)?;
+    save_dynamic(
+        &image,
+        &dir.join(format!("{stem}.{}", format.extension())),
+        format,
+    )
+}
+
+fn write_compressed_image(
+    data: &[u8],
+    dir: &Path,
+    stem: &str,
+    format: ImageFormat,
+) -> Result<PathBuf> {
+    let mut deserializer = CdrDeserializer::new(data)?;
+    let msg = CompressedImage::from_cdr(&mut deserializer)?;
+
+    if format == ImageFormat::Original {
+        let extension = compressed_extension(&msg.format);
+        let path = dir.join(format!("{stem}.{extension}"));
+        std::fs::write(&path, &msg.data)?;
+        return Ok(path);
+    }
+
+    let image = compressed_to_dynamic(&msg)?;
+    save_dynamic(
+        &image,
+        &dir.join(format!("{stem}.{}", format.extension())),
+        format,
+    )
+}
+
+/// File extension for a CompressedImage `format` string (e.g. "jpeg", "bgr8; png compressed")
+fn compressed_extension(format: &str) -> &'static str {
+    let format = format.to_ascii_lowercase();
+    if format.contains("png") {
+        "png"
+    } else if format.contains("tif") {
+        "tiff"
+    } else {
+        "jpg"
+    }
+}
+
+fn save_dynamic(image: &DynamicImage, path: &Path, format: ImageFormat) -> Result<PathBuf> {
+    // JPEG has no 16-bit or alpha support
+    let converted;
+    let image = if format == ImageFormat::Jpeg {
+        converted = match image {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                DynamicImage::ImageLuma8(image.to_luma8())
+            }
+            _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        &converted
+    } else {
+        image
+    };
+
+    image
+        .save_with_format(path, format.as_image_format())
+        .map_err(|e| BagError::export(format!("Failed to write {}: {e}", path.display())))?;
+    Ok(path.to_path_buf())
+}
+
+/// Decode a `sensor_msgs/msg/CompressedImage` into a [`DynamicImage`]
+///
+/// JPEG and PNG payloads (`image_transport`'s `compressed` format) are decoded as
+/// recorded. `compressedDepth` payloads are decoded like [`image_to_dynamic`] decodes
+/// the raw depth image: `16UC1` as 16-bit millimeters, `32FC1` converted to them.
+pub fn compressed_to_dynamic(msg: &CompressedImage) -> Result<DynamicImage> {
+    if is_compressed_depth(&msg.format) {
+        return image_to_dynamic(&decode_compressed_depth(msg)?);
+    }
+    image::load_from_memory(&msg.data)
+        .map_err(|e| BagError::export(format!("Failed to decode compressed image: {e}")))
+}
+
+/// Decode a `sensor_msgs/msg/CompressedImage` into a raw `sensor_msgs/msg/Image`
+///
+/// The encoding of the result follows the decoded pixels, keeping the original
+/// encoding named in the `format` string where the layouts agree: a
+/// `"bgr8; jpeg compressed bgr8"` image decodes to `bgr8`, a plain `"jpeg"` one to
+/// `rgb8` or `mono8`. Depth images decode to `16UC1` or `32FC1` (NaN where the
+/// compressed image has no depth). Pixels are packed without row padding.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::cdr::CdrDeserializer;
+/// use rosbags_rs::export::images::compressed_to_image;
+/// use rosbags_rs::messages::{CompressedImage, FromCdr};
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// for message in reader.messages()? {
+///     let message = message?;
+///     if message.connection.message_type == "sensor_msgs/msg/CompressedImage" {
+///         let msg = CompressedImage::from_cdr(&mut CdrDeserializer::new(&message.data)?)?;
+///         let image = compressed_to_image(&msg)?;
+///         println!("{}x{} {}", image.width, image.height, image.encoding);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn compressed_to_image(msg: &CompressedImage) -> Result<Image> {
+    if is_compressed_depth(&msg.format) {
+        return decode_compressed_depth(msg);
+    }
+    let decoded = image::load_from_memory(&msg.data)
+        .map_err(|e| BagError::export(format!("Failed to decode compressed image: {e}")))?;
+    let (width, height) = (decoded.width(), decoded.height());
+    let original = original_encoding(&msg.format);
+    let keep = |candidates: &[&str], fallback: &str| {
+        if candidates.contains(&original) {
+            original.to_string()
+        } else {
+            fallback.to_string()
+        }
+    };
+
+    let (encoding, data) = match decoded {
+        DynamicImage::ImageLuma8(buf) => (keep(&["mono8", "8UC1"], "mono8"), buf.into_raw()),
+        DynamicImage::ImageLuma16(buf) => (
+            keep(&["mono16", "16UC1"], "mono16"),
+            u16_bytes(buf.as_raw()),
+        ),
+        DynamicImage::ImageRgb8(buf) => match keep(&["bgr8", "8UC3"], "rgb8") {
+            encoding if encoding == "rgb8" => (encoding, buf.into_raw()),
+            encoding => (encoding, swap_rb(buf.into_raw(), 3)),
+        },
+        DynamicImage::ImageRgba8(buf) => match keep(&["bgra8", "8UC4"], "rgba8") {
+            encoding if encoding == "rgba8" => (encoding, buf.into_raw()),
+            encoding => (encoding, swap_rb(buf.into_raw(), 4)),
+        },
+        DynamicImage::ImageRgb16(buf) => match keep(&["bgr16"], "rgb16") {
+            encoding if encoding == "rgb16" => (encoding, u16_bytes(buf.as_raw())),
+            encoding => (encoding, u16_bytes(&swap_rb(buf.into_raw(), 3))),
+        },
+        other => ("rgb8".to_string(), other.to_rgb8().into_raw()),
+    };
+
+    Ok(raw_image(msg, width, height, encoding, data))
+}
+
+/// Whether a CompressedImage `format` names a `compressed_depth_image_transport` payload
+fn is_compressed_depth(format: &str) -> bool {
+    format.to_ascii_lowercase().contains("compresseddepth")
+}
+
+/// Encoding of the image before compression, e.g. `bgr8` in `"bgr8; jpeg compressed bgr8"`
+fn original_encoding(format: &str) -> &str {
+    match format.split_once(';') {
+        Some((encoding, _)) => encoding.trim(),
+        None => "",
+    }
+}
+
+/// Decode a `compressedDepth` payload into a `16UC1` or `32FC1` image
+///
+/// The PNG is preceded by a 12-byte header: the compression format (`int32`) and
+/// the two inverse depth quantization parameters (`float32`). `16UC1` images are
+/// stored as is; `32FC1` images as quantized inverse depth, where 0 means no depth.
+fn decode_compressed_depth(msg: &CompressedImage) -> Result<Image> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG";
+    const HEADER_SIZE: usize = 12;
+
+    let format = msg.format.to_ascii_lowercase();
+    if format.contains("rvl") {
+        return Err(BagError::export(
+            "RVL compressed depth images are not supported",
+        ));
+    }
+    let (header, png) = if msg.data.starts_with(PNG_SIGNATURE) {
+        (None, msg.data.as_slice())
+    } else if msg.data.len() > HEADER_SIZE && msg.data[HEADER_SIZE..].starts_with(PNG_SIGNATURE) {
+        let parameter = |offset: usize| {
+            let bytes = [
+                msg.data[offset],
+                msg.data[offset + 1],
+                msg.data[offset + 2],
+                msg.data[offset + 3],
+            ];
+            f32::from_le_bytes(bytes)
+        };
+        (Some((parameter(4), parameter(8))), &msg.data[HEADER_SIZE..])
+    } else {
+        return Err(BagError::export(
+            "compressedDepth image does not contain a PNG",
+        ));
+    };
+
+    let decoded = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map_err(|e| BagError::export(format!("Failed to decode compressed depth image: {e}")))?;
+    let (width, height) = (decoded.width(), decoded.height());
+    let samples = decoded.to_luma16().into_raw();
+
+    if original_encoding(&msg.format) != "32FC1" {
+        return Ok(raw_image(
+            msg,
+            width,
+            height,
+            "16UC1".to_string(),
+            u16_bytes(&samples),
+        ));
+    }
+    let (quant_a, quant_b) = header.ok_or_else(|| {
+        BagError::export("32FC1 compressedDepth image lacks its quantization header")
+    })?;
+    let data = samples
+        .iter()
+        .flat_map(|&inverse| {
+            let depth = if inverse == 0 {
+                f32::NAN
+            } else {
+                quant_a / (inverse as f32 - quant_b)
+            };
+            depth.to_le_bytes()
+        })
+        .collect();
+    Ok(raw_image(msg, width, height, "32FC1".to_string(), data))
+}
+
+/// Build a packed little-endian `sensor_msgs/msg/Image` with the header of `msg`
+fn raw_image(
+    msg: &CompressedImage,
+    width: u32,
+    height: u32,
+    encoding: String,
+    data: Vec<u8>,
+) -> Image {
+    let step = if height == 0 {
+        0
+    } else {
+        (data.len() / height as usize) as u32
+    };
+    Image {
+        header: msg.header.clone(),
+        height,
+        width,
+        encoding,
+        is_bigendian: 0,
+        step,
+        data,
+    }
+}
+
+fn u16_bytes(samples: &[u16]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect()
+}
+
+/// Convert a raw `sensor_msgs/msg/Image` into a [`DynamicImage`]
+///
+/// Supported encodings: `mono8`, `mono16`, `8UC1`, `8UC3`, `16UC1`, `32FC1` (depth in meters,
+/// stored as 16-bit millimeters), `rgb8`, `bgr8`, `rgba8`, `bgra8`, `rgb16`, `bgr16`,
+/// `bayer_*8`, `bayer_*16`, and `yuv422`/`uyvy`/`yuv422_yuy2`/`yuyv`.
+pub fn image_to_dynamic(msg: &Image) -> Result<DynamicImage> {
+    let width = msg.width as usize;
+    let height = msg.height as usize;
+    let big_endian = msg.is_bigendian != 0;
+    let encoding = msg.encoding.as_str();
+
+    let bytes_per_pixel = match encoding {
+        "mono8" | "8UC1" => 1,
+        "mono16" | "16UC1" => 2,
+        "32FC1" => 4,
+        "rgb8" | "bgr8" | "8UC3" => 3,
+        "rgba8" | "bgra8" | "8UC4" => 4,
+        "rgb16" | "bgr16" | "16UC3" => 6,
+        "yuv422" | "uyvy" | "yuv422_yuy2" | "yuyv" => 2,
+        e if e.starts_with("bayer_") && e.ends_with('8') => 1,
+        e if e.starts_with("bayer_") && e.ends_with("16") => 2,
+        other => {
+            return Err(BagError::export(format!(
+                "Unsupported image encoding: {other}"
+            )))
+        }
+    };
+
+    let rows = packed_rows(msg, width * bytes_per_pixel)?;
+    let w = msg.width;
+    let h = msg.height;
+
+    let image = match encoding {
+        "mono8" | "8UC1" => DynamicImage::ImageLuma8(buffer(w, h, rows)?),
+        "mono16" | "16UC1" => DynamicImage::ImageLuma16(buffer(w, h, to_u16(&rows, big_endian))?),
+        "32FC1" => {
+            let millimeters = rows
+                .chunks_exact(4)
+                .map(|c| {
+                    let bytes = [c[0], c[1], c[2], c[3]];
+                    let meters = if big_endian {
+                        f32::from_be_bytes(bytes)
+                    } else {
+                        f32::from_le_bytes(bytes)
+                    };
+                    if meters.is_finite() {
+                        (meters * 1000.0).round().clamp(0.0, u16::MAX as f32) as u16
+                    } else {
+                        0
+                    }
+                })
+                .collect();
+            DynamicImage::ImageLuma16(buffer(w, h, millimeters)?)
+        }
+        "rgb8" => DynamicImage::ImageRgb8(buffer(w, h, rows)?),
+        "bgr8" | "8UC3" => DynamicImage::ImageRgb8(buffer(w, h, swap_rb(rows, 3))?),
+        "rgba8" => DynamicImage::ImageRgba8(buffer(w, h, rows)?),
+        "bgra8" | "8UC4" => DynamicImage::ImageRgba8(buffer(w, h, swap_rb(rows, 4))?),
+        "rgb16" | "16UC3" => DynamicImage::ImageRgb16(buffer(w, h, to_u16(&rows, big_endian))?),
+        "bgr16" => DynamicImage::ImageRgb16(buffer(w, h, swap_rb(to_u16(&rows, big_endian), 3))?),
+        "yuv422" | "uyvy" => DynamicImage::ImageRgb8(buffer(w, h, yuv422_to_rgb(&rows, true))?),
+        "yuv422_yuy2" | "yuyv" => {
+            DynamicImage::ImageRgb8(buffer(w, h, yuv422_to_rgb(&rows, false))?)
+        }
+        bayer if bytes_per_pixel == 1 => {
+            let samples: Vec<u16> = rows.iter().map(|&v| v as u16).collect();
+            let rgb = demosaic(&samples, width, height, bayer_pattern(bayer)?);
+            DynamicImage::ImageRgb8(buffer(w, h, rgb.into_iter().map(|v| v as u8).collect())?)
+        }
+        bayer => {
+            let samples = to_u16(&rows, big_endian);
+            let rgb = demosaic(&samples, width, height, bayer_pattern(bayer)?);
+            DynamicImage::ImageRgb16(buffer(w, h, rgb)?)
+        }
+    };
+
+    Ok(image)
+}
+
+/// Copy image rows without the per-row padding implied by `step`
+fn packed_rows(msg: &Image, row_bytes: usize) -> Result<Vec<u8>> {
+    let height = msg.height as usize;
+    let step = (msg.step as usize).max(row_bytes);
+
+    let required = if height == 0 {
+        0
+    } else {
+        step * (height - 1) + row_bytes
+    };
+    if msg.data.len() < required {
+        return Err(BagError::export(format!(
+            "Image data truncated: {}x{} {} needs {} bytes, got {}",
+            msg.width,
+            msg.height,
+            msg.encoding,
+            required,
+            msg.data.len()
+        )));
+    }
+
+    if step == row_bytes {
+        return Ok(msg.data[..required].to_vec());
+    }
+
+    let mut rows = Vec::with_capacity(row_bytes * height);
+    for row in 0..height {
+        rows.extend_from_slice(&msg.data[row * step..row * step + row_bytes]);
+    }
+    Ok(rows)
+}
+
+fn buffer<P: image::Pixel>(
+    width: u32,
+    height: u32,
+    data: Vec<P::Subpixel>,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>> {
+    ImageBuffer::from_raw(width, height, data)
+        .ok_or_else(|| BagError::export("Image buffer size does not match dimensions"))
+}
+
+fn to_u16(bytes: &[u8], big_endian: bool) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|c| {
+            if big_endian {
+                u16::from_be_bytes([c[0], c[1]])
+            } else {
+                u16::from_le_bytes([c[0], c[1]])
+            }
+        })
+        .collect()
+}
+
+fn swap_rb<T>(mut data: Vec<T>, channels: usize) -> Vec<T> {
+    for pixel in data.chunks_exact_mut(channels) {
+        pixel.swap(0, 2);
+    }
+    data
+}
+
+/// Convert packed 4:2:2 YUV (UYVY when `uyvy` is true, YUYV otherwise) to RGB8
+fn yuv422_to_rgb(data: &[u8], uyvy: bool) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(data.len() / 2 * 3);
+    for macro_pixel in data.chunks_exact(4) {
+        let (y0, u, y1, v) = if uyvy {
+            (
+                macro_pixel[1],
+                macro_pixel[0],
+                macro_pixel[3],
+                macro_pixel[2],
+            )
+        } else {
+            (
+                macro_pixel[0],
+                macro_pixel[1],
+                macro_pixel[2],
+                macro_pixel[3],
+            )
+        };
+        for y in [y0, y1] {
+            rgb.extend_from_slice(&yuv_to_rgb(y, u, v));
+        }
+    }
+    rgb
+}
+
+/// BT.601 YUV to RGB conversion
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = y as f32 - 16.0;
+    let d = u as f32 - 128.0;
+    let e = v as f32 - 128.0;
+    let clamp = |x: f32| x.round().clamp(0.0, 255.0) as u8;
+    [
+        clamp(1.164 * c + 1.596 * e),
+        clamp(1.164 * c - 0.392 * d - 0.813 * e),
+        clamp(1.164 * c + 2.017 * d),
+    ]
+}
+
+/// Channel (0 = R, 1 = G, 2 = B) at each position of a 2x2 bayer tile, indexed `[y % 2][x % 2]`
+type BayerPattern = [[usize; 2]; 2];
+
+fn bayer_pattern(encoding: &str) -> Result<BayerPattern> {
+    let name = encoding
+        .trim_start_matches("bayer_")
+        .trim_end_matches("16")
+        .trim_end_matches('8');
+    match name {
+        "rggb" => Ok([[0, 1], [1, 2]]),
+        "bggr" => Ok([[2, 1], [1, 0]]),
+        "gbrg" => Ok([[1, 2], [0, 1]]),
+        "grbg" => Ok([[1, 0], [2, 1]]),
+        _ => Err(BagError::export(format!(
+            "Unsupported bayer pattern: {encoding}"
+        ))),
+    }
+}
+
+/// Bilinear demosaicing: each missing channel is the mean of that channel's samples in the
+/// surrounding 3x3 neighborhood.
+fn demosaic(samples: &[u16], width: usize, height: usize, pattern: BayerPattern) -> Vec<u16> {
+    let mut rgb = vec![0u16; width * height * 3];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sums = [0u32; 3];
+            let mut counts = [0u32; 3];
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let channel = pattern[ny % 2][nx % 2];
+                    sums[channel] += samples[ny * width + nx] as u32;
+                    counts[channel] += 1;
+                }
+            }
+            let own = pattern[y % 2][x % 2];
+            for channel in 0..3 {
+                let out = &mut rgb[(y * width + x) * 3 + channel];
+                *out = if channel == own {
+                    samples[y * width + x]
+                } else {
+                    sums[channel].checked_div(counts[channel]).unwrap_or(0) as u16
+                };
+            }
+        }
+    }
+    rgb
+}
+
+/// Assemble extracted frames into an MP4 video using the `ffmpeg` executable
+///
+/// Frame durations follow the original message timestamps, so the video plays back
+/// at recorded speed; the last frame is shown as long as the one before it. Requires
+/// `ffmpeg` to be available on `PATH`.
+#[cfg(feature = "video")]
+pub fn assemble_video<P: AsRef<Path>>(extraction: &ImageExtraction, output: P) -> Result<()> {
+    use std::fmt::Write as _;
+
+    let output = output.as_ref();
+    if extraction.frames.is_empty() {
+        return Err(BagError::export("No frames to assemble into a video"));
+    }
+
+    // ffmpeg concat demuxer script with per-frame durations
+    let timestamps: Vec<u64> = extraction.frames.iter().map(|f| f.timestamp).collect();
+    let mut script = String::from("ffconcat version 1.0\n");
+    let mut last_file = String::new();
+    for (frame, duration_ns) in extraction.frames.iter().zip(frame_durations(&timestamps)) {
+        let path = std::fs::canonicalize(&frame.path)?;
+        last_file = format!(
+            "file '{}'",
+            path.display().to_string().replace('\'', "'\\''")
+        );
+        let _ = writeln!(script, "{last_file}");
+        let _ = writeln!(script, "duration {:.9}", duration_ns as f64 / 1e9);
+    }
+    // The concat demuxer only applies the duration of the last entry if the file is
+    // listed once more
+    let _ = writeln!(script, "{last_file}");
+
+    let script_path = output.with_extension("ffconcat");
+    std::fs::write(&script_path, script)?;
+
+    let status = std::process::Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "concat",
+            "-safe",
+            "0",
+            "-i",
+        ])
+        .arg(&script_path)
+        .args(["-vsync", "vfr", "-pix_fmt", "yuv420p", "-c:v", "libx264"])
+        .arg(output)
+        .status()
+        .map_err(|e| BagError::export(format!("Failed to run ffmpeg: {e}")))?;
+    let _ = std::fs::remove_file(&script_path);
+
+    if !status.success() {
+        return Err(BagError::export(format!(
+            "ffmpeg exited with status {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Display duration of each frame in nanoseconds: the time until the next frame, and
+/// for the last frame the duration of the one before it (one frame at 30 fps if
+/// there is only one)
+#[cfg(feature = "video")]
+fn frame_durations(timestamps: &[u64]) -> Vec<u64> {
+    const DEFAULT_FRAME_PERIOD_NS: u64 = 1_000_000_000 / 30;
+
+    let mut durations: Vec<u64> = timestamps
+        .windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .collect();
+    if !timestamps.is_empty() {
+        durations.push(durations.last().copied().unwrap_or(DEFAULT_FRAME_PERIOD_NS));
+    }
+    durations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, Time};
+
+    fn image(width: u32, height: u32, encoding: &str, step: u32, data: Vec<u8>) -> Image {
+        Image {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: String::new(),
+            },
+            height,
+            width,
+            encoding: encoding.to_string(),
+            is_bigendian: 0,
+            step,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_bgr8_with_row_padding() {
+        // 1x2 image with 2 bytes of padding per row
+        let msg = image(1, 2, "bgr8", 5, vec![1, 2, 3, 0, 0, 4, 5, 6, 0, 0]);
+        let rgb = image_to_dynamic(&msg).unwrap().to_rgb8();
+        assert_eq!(rgb.into_raw(), vec![3, 2, 1, 6, 5, 4]);
+    }
+
+    #[test]
+    fn test_mono16_preserved() {
+        let msg = image(2, 1, "mono16", 4, vec![0x34, 0x12, 0xff, 0xff]);
+        match image_to_dynamic(&msg).unwrap() {
+            DynamicImage::ImageLuma16(buf) => assert_eq!(buf.into_raw(), vec![0x1234, 0xffff]),
+            other => panic!("expected 16-bit image, got {:?}", other.color()),
+        }
+    }
+
+    #[test]
+    fn test_bayer_uniform_color() {
+        // RGGB pattern with R=200, G=100, B=50 everywhere
+        let data = vec![200, 100, 200, 100, 100, 50, 100, 50];
+        let msg = image(4, 2, "bayer_rggb8", 4, data);
+        let rgb = image_to_dynamic(&msg).unwrap().to_rgb8();
+        for pixel in rgb.pixels() {
+            assert_eq!(pixel.0, [200, 100, 50]);
+        }
+    }
+
+    #[test]
+    fn test_yuv422_gray() {
+        // UYVY with neutral chroma yields gray pixels
+        let msg = image(2, 1, "yuv422", 4, vec![128, 126, 128, 126]);
+        let rgb = image_to_dynamic(&msg).unwrap().to_rgb8();
+        let px = rgb.get_pixel(0, 0).0;
+        assert_eq!(px[0], px[1]);
+        assert_eq!(px[1], px[2]);
+    }
+
+    fn compressed(format: &str, data: Vec<u8>) -> CompressedImage {
+        CompressedImage {
+            header: Header {
+                stamp: Time { sec: 1, nanosec: 2 },
+                frame_id: "camera".to_string(),
+            },
+            format: format.to_string(),
+            data,
+        }
+    }
+
+    fn encode(image: DynamicImage, format: image::ImageOutputFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut data), format)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_compressed_png_keeps_bgr_encoding() {
+        let rgb = ImageBuffer::from_raw(2, 1, vec![10, 20, 30, 40, 50, 60]).unwrap();
+        let png = encode(DynamicImage::ImageRgb8(rgb), image::ImageOutputFormat::Png);
+
+        let msg = compressed("bgr8; png compressed bgr8", png.clone());
+        let image = compressed_to_image(&msg).unwrap();
+        assert_eq!(image.encoding, "bgr8");
+        assert_eq!((image.width, image.height, image.step), (2, 1, 6));
+        assert_eq!(image.data, vec![30, 20, 10, 60, 50, 40]);
+        assert_eq!(image.header, msg.header);
+
+        let image = compressed_to_image(&compressed("png", png)).unwrap();
+        assert_eq!(image.encoding, "rgb8");
+        assert_eq!(image.data, vec![10, 20, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn test_compressed_jpeg_mono() {
+        let gray = ImageBuffer::from_raw(8, 8, vec![100u8; 64]).unwrap();
+        let jpeg = encode(
+            DynamicImage::ImageLuma8(gray),
+            image::ImageOutputFormat::Jpeg(90),
+        );
+        let image = compressed_to_image(&compressed("jpeg", jpeg)).unwrap();
+        assert_eq!(image.encoding, "mono8");
+        assert_eq!(image.data.len(), 64);
+        assert!(image.data.iter().all(|&v| v.abs_diff(100) <= 2));
+    }
+
+    #[test]
+    fn test_compressed_depth() {
+        // Quantization of compressed_depth_image_transport's defaults (100, 10 m)
+        let (quant_a, quant_b): (f32, f32) = (100.0 * 101.0, 1.0 - 100.0 * 101.0 / 10.0);
+        let header = |format: i32| {
+            let mut data = format.to_le_bytes().to_vec();
+            data.extend_from_slice(&quant_a.to_le_bytes());
+            data.extend_from_slice(&quant_b.to_le_bytes());
+            data
+        };
+        let depth_png = |samples: Vec<u16>| {
+            let buf = ImageBuffer::from_raw(2, 1, samples).unwrap();
+            encode(
+                DynamicImage::ImageLuma16(buf),
+                image::ImageOutputFormat::Png,
+            )
+        };
+
+        let mut data = header(0);
+        data.extend(depth_png(vec![1000, 2000]));
+        let image = compressed_to_image(&compressed("16UC1; compressedDepth png", data)).unwrap();
+        assert_eq!(image.encoding, "16UC1");
+        assert_eq!(image.data, vec![0xe8, 0x03, 0xd0, 0x07]);
+
+        // 2 m is stored as inverse depth 10100 / 2 - 1009
+        let mut data = header(0);
+        data.extend(depth_png(vec![4041, 0]));
+        let msg = compressed("32FC1; compressedDepth", data);
+        let image = compressed_to_image(&msg).unwrap();
+        assert_eq!(image.encoding, "32FC1");
+        let depths: Vec<f32> = image
+            .data
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert!((depths[0] - 2.0).abs() < 1e-4);
+        assert!(depths[1].is_nan());
+        match compressed_to_dynamic(&msg).unwrap() {
+            DynamicImage::ImageLuma16(buf) => assert_eq!(buf.into_raw(), vec![2000, 0]),
+            other => panic!("expected 16-bit image, got {:?}", other.color()),
+        }
+    }
+
+    #[test]
+    fn test_truncated_image_rejected() {
+        let msg = image(640, 480, "rgb8", 1920, vec![0; 30]);
+        assert!(matches!(
+            image_to_dynamic(&msg),
+            Err(BagError::Export { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "video")]
+    fn test_last_frame_keeps_previous_duration() {
+        assert_eq!(frame_durations(&[100, 150, 250]), [50, 100, 100]);
+        assert_eq!(frame_durations(&[100]), [33_333_333]);
+        assert!(frame_durations(&[]).is_empty());
+    }
+}
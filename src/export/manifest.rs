@@ -0,0 +1,327 @@
+//! Self-describing manifests for exported datasets
+//!
+//! An [`ExportManifest`] records what an export or conversion consumed (input bags),
+//! how it was configured (options), what it produced (output files with sizes and
+//! SHA-256 checksums) and which crate version produced it. It is written as JSON next
+//! to the outputs so that recipients can verify a delivered dataset with
+//! [`ExportManifest::verify`] and trace it back to its source.
+
+use super::ExtractedFrame;
+use crate::error::{BagError, Result};
+pub use crate::metadata::sha256_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name of manifests written by [`ExportManifest::write`]
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Version of the manifest layout
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// A file with its size and checksum
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Path relative to the manifest directory, or absolute if outside of it
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex-encoded SHA-256 of the file contents
+    pub sha256: String,
+}
+
+/// An input of the export, usually a bag directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestInput {
+    /// Path as given to the export
+    pub path: String,
+    /// Checksummed files of the input, if requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ManifestFile>,
+}
+
+/// Difference between a manifest and the files on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMismatch {
+    /// Output file no longer exists
+    Missing { path: String },
+    /// Output file has a different size
+    Size {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// Output file has different contents
+    Checksum {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Provenance and checksum record of an export
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::export::images::{extract_images, ImageFormat};
+/// use rosbags_rs::export::manifest::ExportManifest;
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+/// let extraction = extract_images(&reader, "/camera/image_raw", "out", ImageFormat::Png)?;
+///
+/// let mut manifest = ExportManifest::new("extract_images", "out")
+///     .input("path/to/bag")
+///     .option("topic", "/camera/image_raw")
+///     .option("format", "png");
+/// manifest.add_frames(&extraction.frames)?;
+/// manifest.write()?;
+///
+/// // Later, on the receiving side
+/// let received = ExportManifest::read("out/manifest.json")?;
+/// assert!(received.verify()?.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// Version of the manifest layout
+    pub manifest_version: u32,
+    /// Name of the producing crate
+    pub generator: String,
+    /// Version of the producing crate
+    pub generator_version: String,
+    /// Export or conversion that produced the outputs (e.g. `extract_images`)
+    pub operation: String,
+    /// Creation time (RFC 3339, UTC)
+    pub created: String,
+    /// Inputs of the operation
+    pub inputs: Vec<ManifestInput>,
+    /// Options the operation was run with
+    pub options: BTreeMap<String, String>,
+    /// Produced files
+    pub outputs: Vec<ManifestFile>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+impl ExportManifest {
+    /// Start a manifest for `operation` whose outputs live under `root`
+    pub fn new(operation: impl Into<String>, root: impl AsRef<Path>) -> Self {
+        Self {
+            manifest_version: MANIFEST_VERSION,
+            generator: env!("CARGO_PKG_NAME").to_string(),
+            generator_version: env!("CARGO_PKG_VERSION").to_string(),
+            operation: operation.into(),
+            created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            inputs: Vec::new(),
+            options: BTreeMap::new(),
+            outputs: Vec::new(),
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Directory output paths are relative to and the manifest is written to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Record an input by path, without checksums
+    pub fn input(mut self, path: impl AsRef<Path>) -> Self {
+        self.inputs.push(ManifestInput {
+            path: path.as_ref().display().to_string(),
+            files: Vec::new(),
+        });
+        self
+    }
+
+    /// Record an option the operation was run with
+    pub fn option(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.options.insert(key.into(), value.to_string());
+        self
+    }
+
+    /// Record an input with checksums of all files in it (e.g. a bag directory)
+    ///
+    /// Input bags can be large; use [`ExportManifest::input`] when only the path matters.
+    pub fn add_input_with_checksums(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut files = Vec::new();
+        for file in list_files(path)? {
+            let (size, sha256) = sha256_file(&file)?;
+            let relative = file.strip_prefix(path).unwrap_or(&file);
+            files.push(ManifestFile {
+                path: portable_path(relative),
+                size,
+                sha256,
+            });
+        }
+        self.inputs.push(ManifestInput {
+            path: path.display().to_string(),
+            files,
+        });
+        Ok(())
+    }
+
+    /// Checksum and record an output file
+    pub fn add_output(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let (size, sha256) = sha256_file(path)?;
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        self.outputs.push(ManifestFile {
+            path: portable_path(relative),
+            size,
+            sha256,
+        });
+        Ok(())
+    }
+
+    /// Checksum and record the files written by an image or point cloud export
+    pub fn add_frames(&mut self, frames: &[ExtractedFrame]) -> Result<()> {
+        for frame in frames {
+            self.add_output(&frame.path)?;
+        }
+        Ok(())
+    }
+
+    /// Checksum and record every file under the root directory, except manifests
+    pub fn add_root_contents(&mut self) -> Result<()> {
+        for file in list_files(&self.root)? {
+            if file
+                .file_name()
+                .is_some_and(|name| name == MANIFEST_FILE_NAME)
+            {
+                continue;
+            }
+            self.add_output(file)?;
+        }
+        Ok(())
+    }
+
+    /// Write the manifest as `manifest.json` in the root directory
+    pub fn write(&self) -> Result<PathBuf> {
+        let path = self.root.join(MANIFEST_FILE_NAME);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| BagError::export(format!("Failed to serialize manifest: {e}")))?;
+        std::fs::write(&path, json + "\n")?;
+        Ok(path)
+    }
+
+    /// Read a manifest; output paths are resolved relative to its directory
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)?;
+        let mut manifest: Self = serde_json::from_str(&json)
+            .map_err(|e| BagError::export(format!("Invalid manifest {}: {e}", path.display())))?;
+        manifest.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(manifest)
+    }
+
+    /// Compare the recorded outputs against the files on disk
+    ///
+    /// Returns an empty list when every output is present and unchanged.
+    pub fn verify(&self) -> Result<Vec<ManifestMismatch>> {
+        let mut mismatches = Vec::new();
+        for output in &self.outputs {
+            let path = self.root.join(&output.path);
+            if !path.is_file() {
+                mismatches.push(ManifestMismatch::Missing {
+                    path: output.path.clone(),
+                });
+                continue;
+            }
+            let (size, sha256) = sha256_file(&path)?;
+            if size != output.size {
+                mismatches.push(ManifestMismatch::Size {
+                    path: output.path.clone(),
+                    expected: output.size,
+                    actual: size,
+                });
+            } else if sha256 != output.sha256 {
+                mismatches.push(ManifestMismatch::Checksum {
+                    path: output.path.clone(),
+                    expected: output.sha256.clone(),
+                    actual: sha256,
+                });
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+/// All files below `path` (or `path` itself if it is a file), sorted
+fn list_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Path with `/` separators so manifests are portable across platforms
+fn portable_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_read_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("frames")).unwrap();
+        std::fs::write(dir.path().join("frames/a.png"), b"first").unwrap();
+        std::fs::write(dir.path().join("index.csv"), b"file,timestamp\n").unwrap();
+
+        let mut manifest = ExportManifest::new("extract_images", dir.path())
+            .input("/data/bag")
+            .option("format", "png");
+        manifest.add_root_contents().unwrap();
+        let path = manifest.write().unwrap();
+
+        let read = ExportManifest::read(&path).unwrap();
+        assert_eq!(read, manifest);
+        assert_eq!(read.generator_version, env!("CARGO_PKG_VERSION"));
+        let paths: Vec<_> = read.outputs.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["frames/a.png", "index.csv"]);
+        // SHA-256 of "first"
+        assert_eq!(
+            read.outputs[0].sha256,
+            "a7937b64b8caa58f03721bb6bacf5c78cb235febe0e70b1b84cd99541461a08e"
+        );
+        assert!(read.verify().unwrap().is_empty());
+
+        // Writing the manifest again must not list itself as an output
+        manifest.outputs.clear();
+        manifest.add_root_contents().unwrap();
+        assert_eq!(manifest.outputs.len(), 2);
+
+        std::fs::write(dir.path().join("frames/a.png"), b"tampered").unwrap();
+        std::fs::remove_file(dir.path().join("index.csv")).unwrap();
+        let mismatches = read.verify().unwrap();
+        assert!(
+            matches!(&mismatches[0], ManifestMismatch::Size { path, .. } if path == "frames/a.png")
+        );
+        assert!(
+            matches!(&mismatches[1], ManifestMismatch::Missing { path } if path == "index.csv")
+        );
+    }
+}
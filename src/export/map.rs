@@ -0,0 +1,270 @@
+//! Occupancy grid export from `nav_msgs/msg/OccupancyGrid` topics
+//!
+//! Grids are written the way `map_saver` of `nav2_map_server` writes them: a binary
+//! PGM image with the top row of the map first, and a YAML file with the resolution,
+//! origin and thresholds `map_server` needs to load the map again.
+
+use super::ExtractedFrame;
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{FromCdr, OccupancyGrid, Quaternion};
+use crate::reader::Reader;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Message type of occupancy grids
+pub const OCCUPANCY_GRID_TYPE: &str = "nav_msgs/msg/OccupancyGrid";
+
+/// PGM value of occupied cells
+const OCCUPIED_PIXEL: u8 = 0;
+
+/// PGM value of free cells
+const FREE_PIXEL: u8 = 254;
+
+/// PGM value of unknown cells and cells between the thresholds
+const UNKNOWN_PIXEL: u8 = 205;
+
+/// Thresholds of the trinary map written by [`write_map`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapThresholds {
+    /// Cells with an occupancy probability of at least this are occupied (default: 0.65)
+    pub occupied: f64,
+    /// Cells with an occupancy probability of at most this are free (default: 0.25)
+    pub free: f64,
+}
+
+impl Default for MapThresholds {
+    fn default() -> Self {
+        Self {
+            occupied: 0.65,
+            free: 0.25,
+        }
+    }
+}
+
+/// Result of a map extraction run
+#[derive(Debug, Clone, Default)]
+pub struct MapExtraction {
+    /// YAML files of the maps successfully written, in bag order
+    pub maps: Vec<ExtractedFrame>,
+    /// Messages that could not be converted: (timestamp, reason)
+    pub skipped: Vec<(u64, String)>,
+}
+
+/// Write `grid` as `<stem>.pgm` and `<stem>.yaml`, returning the path of the YAML file
+///
+/// `stem` is the output path without extension, like the `-f` argument of `map_saver`.
+/// Unknown cells and cells between the free and occupied thresholds are written gray.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::cdr::CdrDeserializer;
+/// use rosbags_rs::export::map::{write_map, MapThresholds};
+/// use rosbags_rs::messages::{FromCdr, OccupancyGrid};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let data: Vec<u8> = Vec::new();
+/// let grid = OccupancyGrid::from_cdr(&mut CdrDeserializer::new(&data)?)?;
+/// let yaml = write_map(&grid, "maps/office", MapThresholds::default())?;
+/// println!("Load with: ros2 run nav2_map_server map_server --ros-args -p yaml_filename:={}", yaml.display());
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_map<P: AsRef<Path>>(
+    grid: &OccupancyGrid,
+    stem: P,
+    thresholds: MapThresholds,
+) -> Result<PathBuf> {
+    let stem = stem.as_ref();
+    let width = grid.info.width as usize;
+    let height = grid.info.height as usize;
+    if grid.data.len() != width * height {
+        return Err(BagError::export(format!(
+            "Occupancy grid of {width}x{height} cells has {} values",
+            grid.data.len()
+        )));
+    }
+
+    let with_extension = |extension: &str| {
+        let mut path = stem.as_os_str().to_owned();
+        path.push(extension);
+        PathBuf::from(path)
+    };
+    let pgm_path = with_extension(".pgm");
+    let yaml_path = with_extension(".yaml");
+    if let Some(parent) = pgm_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let occupied = (thresholds.occupied * 100.0).round() as i8;
+    let free = (thresholds.free * 100.0).round() as i8;
+    let mut pgm = BufWriter::new(File::create(&pgm_path)?);
+    write!(
+        pgm,
+        "P5\n# CREATOR: rosbags-rs {:.3} m/pix\n{width} {height}\n255\n",
+        grid.info.resolution
+    )?;
+    // Grid rows start at the origin (bottom), image rows at the top
+    for row in grid.data.chunks(width.max(1)).rev() {
+        let pixels: Vec<u8> = row
+            .iter()
+            .map(|&value| match value {
+                value if !(0..=100).contains(&value) => UNKNOWN_PIXEL,
+                value if value <= free => FREE_PIXEL,
+                value if value >= occupied => OCCUPIED_PIXEL,
+                _ => UNKNOWN_PIXEL,
+            })
+            .collect();
+        pgm.write_all(&pixels)?;
+    }
+    pgm.flush()?;
+
+    let origin = &grid.info.origin;
+    let image = pgm_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let yaml = format!(
+        "image: {image}\nmode: trinary\nresolution: {}\norigin: [{}, {}, {}]\nnegate: 0\noccupied_thresh: {}\nfree_thresh: {}\n",
+        grid.info.resolution,
+        origin.position.x,
+        origin.position.y,
+        yaw(&origin.orientation),
+        thresholds.occupied,
+        thresholds.free,
+    );
+    std::fs::write(&yaml_path, yaml)?;
+
+    Ok(yaml_path)
+}
+
+/// Extract all occupancy grids of `topic` into `output_dir`
+///
+/// Maps are named `map_XXXXXX_<timestamp>.pgm/.yaml`. Messages that cannot be
+/// decoded are recorded in [`MapExtraction::skipped`] instead of aborting.
+pub fn extract_maps<P: AsRef<Path>>(
+    reader: &Reader,
+    topic: &str,
+    output_dir: P,
+    thresholds: MapThresholds,
+) -> Result<MapExtraction> {
+    let output_dir = output_dir.as_ref();
+
+    let connections = reader.connections_for_topic(topic);
+    if connections.is_empty() {
+        return Err(BagError::connection_not_found(topic));
+    }
+    if let Some(conn) = connections
+        .iter()
+        .find(|c| c.message_type != OCCUPANCY_GRID_TYPE)
+    {
+        return Err(BagError::export(format!(
+            "Topic {topic} has type {}, expected {OCCUPANCY_GRID_TYPE}",
+            conn.message_type
+        )));
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut extraction = MapExtraction::default();
+    for message_result in reader.messages_filtered(Some(&connections), None, None)? {
+        let message = message_result?;
+        let index = extraction.maps.len() + extraction.skipped.len();
+        let stem = output_dir.join(format!("map_{:06}_{}", index, message.timestamp));
+
+        let written = CdrDeserializer::new(&message.data)
+            .and_then(|mut deserializer| OccupancyGrid::from_cdr(&mut deserializer))
+            .and_then(|grid| write_map(&grid, &stem, thresholds));
+        match written {
+            Ok(path) => extraction.maps.push(ExtractedFrame {
+                path,
+                timestamp: message.timestamp,
+                header_stamp: super::header_stamp(&message.data),
+            }),
+            Err(e) => extraction.skipped.push((message.timestamp, e.to_string())),
+        }
+    }
+
+    Ok(extraction)
+}
+
+/// Rotation about the z axis in radians
+fn yaw(q: &Quaternion) -> f64 {
+    (2.0 * (q.w * q.z + q.x * q.y)).atan2(1.0 - 2.0 * (q.y * q.y + q.z * q.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, MapMetaData, Point, Pose, Time};
+
+    fn grid(width: u32, height: u32, data: Vec<i8>) -> OccupancyGrid {
+        OccupancyGrid {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: "map".to_string(),
+            },
+            info: MapMetaData {
+                resolution: 0.05,
+                width,
+                height,
+                origin: Pose {
+                    position: Point {
+                        x: -1.5,
+                        y: 2.0,
+                        z: 0.0,
+                    },
+                    orientation: Quaternion {
+                        x: 0.0,
+                        y: 0.0,
+                        z: std::f64::consts::FRAC_PI_4.sin(),
+                        w: std::f64::consts::FRAC_PI_4.cos(),
+                    },
+                },
+                ..Default::default()
+            },
+            data,
+        }
+    }
+
+    #[test]
+    fn test_write_map() {
+        let dir = tempfile::tempdir().unwrap();
+        // Bottom row: free, occupied, unknown; top row: in between, 25, 65
+        let map = grid(3, 2, vec![0, 100, -1, 50, 25, 65]);
+        let yaml_path =
+            write_map(&map, dir.path().join("office"), MapThresholds::default()).unwrap();
+        assert_eq!(yaml_path, dir.path().join("office.yaml"));
+
+        let pgm = std::fs::read(dir.path().join("office.pgm")).unwrap();
+        assert!(pgm.starts_with(b"P5\n"));
+        assert_eq!(&pgm[pgm.len() - 6..], [205, 254, 0, 254, 0, 205]);
+
+        let yaml = std::fs::read_to_string(&yaml_path).unwrap();
+        assert!(yaml.contains("image: office.pgm\n"));
+        assert!(yaml.contains("resolution: 0.05\n"));
+        let origin = yaml
+            .lines()
+            .find_map(|line| line.strip_prefix("origin: ["))
+            .unwrap();
+        let origin: Vec<f64> = origin
+            .trim_end_matches(']')
+            .split(", ")
+            .map(|value| value.parse().unwrap())
+            .collect();
+        assert_eq!(origin[..2], [-1.5, 2.0]);
+        assert!((origin[2] - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+        assert!(yaml.contains("occupied_thresh: 0.65\nfree_thresh: 0.25\n"));
+    }
+
+    #[test]
+    fn test_write_map_rejects_size_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let map = grid(3, 2, vec![0; 5]);
+        assert!(matches!(
+            write_map(&map, dir.path().join("map"), MapThresholds::default()),
+            Err(BagError::Export { .. })
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+//! Export of bag contents to standard file formats
+//!
+//! This module turns recorded messages into files that external tools understand,
+//! such as image files for camera topics, point cloud files for lidar topics,
+//! `map_server` maps for occupancy grids and calibration files for camera info.
+
+use crate::cdr::CdrDeserializer;
+use crate::error::Result;
+use crate::messages::{FromCdr, Time};
+use crate::types::Connection;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub mod calibration;
+#[cfg(feature = "images")]
+pub mod images;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod map;
+pub mod pointcloud;
+
+/// Message type of the standard header
+const HEADER_TYPE: &str = "std_msgs/msg/Header";
+
+/// A single file written by an exporter
+#[derive(Debug, Clone)]
+pub struct ExtractedFrame {
+    /// Path of the written file
+    pub path: PathBuf,
+    /// Bag timestamp of the source message in nanoseconds
+    pub timestamp: u64,
+    /// `header.stamp` of the source message in nanoseconds, if it has a header
+    pub header_stamp: Option<u64>,
+}
+
+/// Timestamp columns emitted by tabular exports
+///
+/// The bag timestamp is the time the recorder received the message, while the
+/// header stamp is the time the publisher assigned to the data. Latency analysis
+/// needs both, so they can be emitted side by side together with their difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampColumns {
+    /// Only the bag receive time (`timestamp`)
+    #[default]
+    BagTime,
+    /// Bag receive time, header stamp and `timestamp - header_stamp`
+    BagAndHeader,
+}
+
+impl TimestampColumns {
+    /// Column names, in order
+    pub fn headers(&self) -> &'static [&'static str] {
+        match self {
+            TimestampColumns::BagTime => &["timestamp"],
+            TimestampColumns::BagAndHeader => &["timestamp", "header_stamp", "stamp_delta_ns"],
+        }
+    }
+
+    /// Column values for one message; header columns are empty when there is no header
+    pub fn values(&self, timestamp: u64, header_stamp: Option<u64>) -> Vec<String> {
+        match self {
+            TimestampColumns::BagTime => vec![timestamp.to_string()],
+            TimestampColumns::BagAndHeader => vec![
+                timestamp.to_string(),
+                header_stamp.map(|s| s.to_string()).unwrap_or_default(),
+                header_stamp
+                    .map(|s| (timestamp as i128 - s as i128).to_string())
+                    .unwrap_or_default(),
+            ],
+        }
+    }
+}
+
+/// Whether messages on `connection` start with a `std_msgs/msg/Header`
+///
+/// Determined from the stored message definition; returns `false` when the
+/// definition is unavailable.
+pub fn has_header(connection: &Connection) -> bool {
+    connection
+        .message_schemas()
+        .ok()
+        .and_then(|schemas| schemas.root().fields.first().cloned())
+        .is_some_and(|field| field.type_name == HEADER_TYPE)
+}
+
+/// Read `header.stamp` in nanoseconds from a CDR message that starts with a header
+pub fn header_stamp(data: &[u8]) -> Option<u64> {
+    let mut deserializer = CdrDeserializer::new(data).ok()?;
+    Time::from_cdr(&mut deserializer).ok()?.to_nanos()
+}
+
+/// Write a CSV index of exported files with the selected timestamp columns
+pub fn write_frame_index<P: AsRef<Path>>(
+    frames: &[ExtractedFrame],
+    path: P,
+    columns: TimestampColumns,
+) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "file,{}", columns.headers().join(","))?;
+    for frame in frames {
+        let file = frame
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{file},{}",
+            columns
+                .values(frame.timestamp, frame.header_stamp)
+                .join(",")
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_stamp_and_delta() {
+        // CDR header, sec = 2, nanosec = 500, empty frame_id
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.extend_from_slice(&2i32.to_le_bytes());
+        data.extend_from_slice(&500u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.push(0);
+
+        let stamp = header_stamp(&data);
+        assert_eq!(stamp, Some(2_000_000_500));
+
+        let columns = TimestampColumns::BagAndHeader;
+        assert_eq!(columns.headers().len(), 3);
+        assert_eq!(
+            columns.values(2_000_001_000, stamp),
+            vec!["2000001000", "2000000500", "500"]
+        );
+        assert_eq!(columns.values(7, None), vec!["7", "", ""]);
+        assert_eq!(TimestampColumns::BagTime.values(7, stamp), vec!["7"]);
+    }
+}
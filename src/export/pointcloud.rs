@@ -0,0 +1,685 @@
+//! Point cloud export from `sensor_msgs/msg/PointCloud2` topics
+//!
+//! Clouds are written as PCD, PLY or LAS files. The `x`, `y` and `z` fields are
+//! required; `intensity` and packed `rgb`/`rgba` fields are carried over when present.
+//! Point data is decoded according to the message's `is_bigendian` flag and always
+//! written little endian.
+
+use super::ExtractedFrame;
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{FromCdr, PointCloud2, PointField};
+use crate::reader::Reader;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Message type of point clouds
+pub const POINT_CLOUD2_TYPE: &str = "sensor_msgs/msg/PointCloud2";
+
+/// Coordinate resolution used for LAS files (1 mm)
+const LAS_SCALE: f64 = 0.001;
+
+/// Size of a LAS 1.2 public header block
+const LAS_HEADER_SIZE: u16 = 227;
+
+/// Data section encoding for PCD and PLY files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataEncoding {
+    /// Human readable text
+    Ascii,
+    /// Little endian binary
+    Binary,
+}
+
+/// Output file format for exported point clouds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointCloudFormat {
+    /// Point Cloud Library PCD v0.7
+    Pcd(DataEncoding),
+    /// Polygon File Format
+    Ply(DataEncoding),
+    /// ASPRS LAS 1.2 (point format 0, or 2 when colors are present)
+    Las,
+}
+
+impl PointCloudFormat {
+    /// File extension used for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PointCloudFormat::Pcd(_) => "pcd",
+            PointCloudFormat::Ply(_) => "ply",
+            PointCloudFormat::Las => "las",
+        }
+    }
+}
+
+/// A single decoded point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloudPoint {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// Intensity, or 0 when the cloud has no intensity field
+    pub intensity: f32,
+    /// Color as `[r, g, b]`, or black when the cloud has no color field
+    pub rgb: [u8; 3],
+}
+
+impl CloudPoint {
+    /// Whether all coordinates are finite (non-dense clouds mark invalid points with NaN)
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+}
+
+/// Points decoded from a `PointCloud2` message, in row-major order
+#[derive(Debug, Clone, Default)]
+pub struct DecodedCloud {
+    /// Points, including invalid (non-finite) ones
+    pub points: Vec<CloudPoint>,
+    /// Cloud width (points per row)
+    pub width: u32,
+    /// Cloud height (rows, 1 for unorganized clouds)
+    pub height: u32,
+    /// Whether the source cloud has an intensity field
+    pub has_intensity: bool,
+    /// Whether the source cloud has an `rgb` or `rgba` field
+    pub has_rgb: bool,
+    /// Whether any coordinate field is `float64`
+    pub double_precision: bool,
+}
+
+impl DecodedCloud {
+    /// Iterate over points with finite coordinates
+    pub fn finite_points(&self) -> impl Iterator<Item = &CloudPoint> {
+        self.points.iter().filter(|p| p.is_finite())
+    }
+}
+
+/// Result of a point cloud extraction run
+#[derive(Debug, Clone, Default)]
+pub struct PointCloudExtraction {
+    /// Clouds successfully written, in bag order
+    pub clouds: Vec<ExtractedFrame>,
+    /// Messages that could not be converted: (timestamp, reason)
+    pub skipped: Vec<(u64, String)>,
+}
+
+/// Decode the points of a `PointCloud2` message
+///
+/// Fields are located by name: `x`, `y`, `z` (required), `intensity` or `i`, and
+/// `rgb` or `rgba` (packed `0x00RRGGBB` in a 4-byte field).
+pub fn decode_points(cloud: &PointCloud2) -> Result<DecodedCloud> {
+    let find = |names: &[&str]| {
+        cloud
+            .fields
+            .iter()
+            .find(|f| names.contains(&f.name.as_str()))
+    };
+    let coordinate = |name: &str| {
+        find(&[name]).ok_or_else(|| BagError::export(format!("Point cloud has no '{name}' field")))
+    };
+    let (fx, fy, fz) = (coordinate("x")?, coordinate("y")?, coordinate("z")?);
+    let f_intensity = find(&["intensity", "i"]);
+    let f_rgb = find(&["rgb", "rgba"]);
+
+    for field in [Some(fx), Some(fy), Some(fz), f_intensity, f_rgb]
+        .into_iter()
+        .flatten()
+    {
+        let size = field.datatype_size().ok_or_else(|| {
+            BagError::export(format!(
+                "Field '{}' has unknown datatype {}",
+                field.name, field.datatype
+            ))
+        })?;
+        if field.offset as usize + size > cloud.point_step as usize {
+            return Err(BagError::export(format!(
+                "Field '{}' exceeds point step {}",
+                field.name, cloud.point_step
+            )));
+        }
+    }
+    if let Some(field) = f_rgb {
+        if field.datatype_size() != Some(4) {
+            return Err(BagError::export(format!(
+                "Color field '{}' must be 4 bytes wide",
+                field.name
+            )));
+        }
+    }
+
+    let point_step = cloud.point_step as usize;
+    let row_step = cloud.row_step as usize;
+    let width = cloud.width as usize;
+    let height = cloud.height as usize;
+    if point_step == 0 || width * point_step > row_step {
+        return Err(BagError::export(format!(
+            "Inconsistent layout: width {width} x point step {point_step} exceeds row step {row_step}"
+        )));
+    }
+    if cloud.data.len() < row_step * height {
+        return Err(BagError::export(format!(
+            "Point data truncated: expected {} bytes, got {}",
+            row_step * height,
+            cloud.data.len()
+        )));
+    }
+
+    let big_endian = cloud.is_bigendian;
+    let read = |field: &PointField, point: &[u8]| {
+        field.read_value(point, 0, big_endian).unwrap_or(f64::NAN)
+    };
+
+    let mut points = Vec::with_capacity(width * height);
+    for row in cloud.data.chunks(row_step).take(height) {
+        for point in row[..width * point_step].chunks_exact(point_step) {
+            let rgb = f_rgb
+                .and_then(|f| f.read_u32_bits(point, 0, big_endian))
+                .map(|packed| [(packed >> 16) as u8, (packed >> 8) as u8, packed as u8])
+                .unwrap_or_default();
+            points.push(CloudPoint {
+                x: read(fx, point),
+                y: read(fy, point),
+                z: read(fz, point),
+                intensity: f_intensity.map(|f| read(f, point) as f32).unwrap_or(0.0),
+                rgb,
+            });
+        }
+    }
+
+    Ok(DecodedCloud {
+        points,
+        width: cloud.width,
+        height: cloud.height,
+        has_intensity: f_intensity.is_some(),
+        has_rgb: f_rgb.is_some(),
+        double_precision: [fx, fy, fz]
+            .iter()
+            .any(|f| f.datatype == PointField::FLOAT64),
+    })
+}
+
+/// Write a cloud in the given format
+pub fn write_pointcloud<P: AsRef<Path>>(
+    cloud: &PointCloud2,
+    path: P,
+    format: PointCloudFormat,
+) -> Result<()> {
+    match format {
+        PointCloudFormat::Pcd(encoding) => write_pcd(cloud, path, encoding),
+        PointCloudFormat::Ply(encoding) => write_ply(cloud, path, encoding),
+        PointCloudFormat::Las => write_las(cloud, path),
+    }
+}
+
+/// Write a cloud as a PCD v0.7 file
+///
+/// PCD can represent invalid points, so the cloud keeps its organized layout
+/// (width x height) and non-finite points are written as NaN.
+pub fn write_pcd<P: AsRef<Path>>(
+    cloud: &PointCloud2,
+    path: P,
+    encoding: DataEncoding,
+) -> Result<()> {
+    let decoded = decode_points(cloud)?;
+    let mut out = BufWriter::new(File::create(path)?);
+
+    let coord_size = if decoded.double_precision { 8 } else { 4 };
+    let mut fields = vec!["x", "y", "z"];
+    let mut sizes = vec![coord_size; 3];
+    let mut types = vec!["F"; 3];
+    if decoded.has_intensity {
+        fields.push("intensity");
+        sizes.push(4);
+        types.push("F");
+    }
+    if decoded.has_rgb {
+        fields.push("rgb");
+        sizes.push(4);
+        types.push("U");
+    }
+    let join = |values: Vec<String>| values.join(" ");
+
+    writeln!(out, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(out, "VERSION 0.7")?;
+    writeln!(out, "FIELDS {}", fields.join(" "))?;
+    writeln!(
+        out,
+        "SIZE {}",
+        join(sizes.iter().map(|s| s.to_string()).collect())
+    )?;
+    writeln!(out, "TYPE {}", types.join(" "))?;
+    writeln!(out, "COUNT {}", join(vec!["1".to_string(); fields.len()]))?;
+    writeln!(out, "WIDTH {}", decoded.width)?;
+    writeln!(out, "HEIGHT {}", decoded.height)?;
+    writeln!(out, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(out, "POINTS {}", decoded.points.len())?;
+    writeln!(
+        out,
+        "DATA {}",
+        match encoding {
+            DataEncoding::Ascii => "ascii",
+            DataEncoding::Binary => "binary",
+        }
+    )?;
+
+    for point in &decoded.points {
+        let packed_rgb = pack_rgb(point.rgb);
+        match encoding {
+            DataEncoding::Ascii => {
+                if decoded.double_precision {
+                    write!(out, "{} {} {}", point.x, point.y, point.z)?;
+                } else {
+                    write!(
+                        out,
+                        "{} {} {}",
+                        point.x as f32, point.y as f32, point.z as f32
+                    )?;
+                }
+                if decoded.has_intensity {
+                    write!(out, " {}", point.intensity)?;
+                }
+                if decoded.has_rgb {
+                    write!(out, " {packed_rgb}")?;
+                }
+                writeln!(out)?;
+            }
+            DataEncoding::Binary => {
+                if decoded.double_precision {
+                    for value in [point.x, point.y, point.z] {
+                        out.write_all(&value.to_le_bytes())?;
+                    }
+                } else {
+                    for value in [point.x, point.y, point.z] {
+                        out.write_all(&(value as f32).to_le_bytes())?;
+                    }
+                }
+                if decoded.has_intensity {
+                    out.write_all(&point.intensity.to_le_bytes())?;
+                }
+                if decoded.has_rgb {
+                    out.write_all(&packed_rgb.to_le_bytes())?;
+                }
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a cloud as a PLY file
+///
+/// Points with non-finite coordinates are dropped, since PLY has no notion of invalid vertices.
+pub fn write_ply<P: AsRef<Path>>(
+    cloud: &PointCloud2,
+    path: P,
+    encoding: DataEncoding,
+) -> Result<()> {
+    let decoded = decode_points(cloud)?;
+    let points: Vec<&CloudPoint> = decoded.finite_points().collect();
+    let mut out = BufWriter::new(File::create(path)?);
+
+    let coord_type = if decoded.double_precision {
+        "double"
+    } else {
+        "float"
+    };
+    writeln!(out, "ply")?;
+    writeln!(
+        out,
+        "format {} 1.0",
+        match encoding {
+            DataEncoding::Ascii => "ascii",
+            DataEncoding::Binary => "binary_little_endian",
+        }
+    )?;
+    writeln!(out, "comment exported by rosbags-rs")?;
+    writeln!(out, "element vertex {}", points.len())?;
+    for axis in ["x", "y", "z"] {
+        writeln!(out, "property {coord_type} {axis}")?;
+    }
+    if decoded.has_intensity {
+        writeln!(out, "property float intensity")?;
+    }
+    if decoded.has_rgb {
+        for channel in ["red", "green", "blue"] {
+            writeln!(out, "property uchar {channel}")?;
+        }
+    }
+    writeln!(out, "end_header")?;
+
+    for point in points {
+        match encoding {
+            DataEncoding::Ascii => {
+                if decoded.double_precision {
+                    write!(out, "{} {} {}", point.x, point.y, point.z)?;
+                } else {
+                    write!(
+                        out,
+                        "{} {} {}",
+                        point.x as f32, point.y as f32, point.z as f32
+                    )?;
+                }
+                if decoded.has_intensity {
+                    write!(out, " {}", point.intensity)?;
+                }
+                if decoded.has_rgb {
+                    write!(out, " {} {} {}", point.rgb[0], point.rgb[1], point.rgb[2])?;
+                }
+                writeln!(out)?;
+            }
+            DataEncoding::Binary => {
+                if decoded.double_precision {
+                    for value in [point.x, point.y, point.z] {
+                        out.write_all(&value.to_le_bytes())?;
+                    }
+                } else {
+                    for value in [point.x, point.y, point.z] {
+                        out.write_all(&(value as f32).to_le_bytes())?;
+                    }
+                }
+                if decoded.has_intensity {
+                    out.write_all(&point.intensity.to_le_bytes())?;
+                }
+                if decoded.has_rgb {
+                    out.write_all(&point.rgb)?;
+                }
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a cloud as a LAS 1.2 file
+///
+/// Coordinates are stored with millimeter resolution relative to the cloud's minimum.
+/// Intensity is clamped to the `u16` range and colors are scaled to 16 bits.
+/// Points with non-finite coordinates are dropped.
+pub fn write_las<P: AsRef<Path>>(cloud: &PointCloud2, path: P) -> Result<()> {
+    let decoded = decode_points(cloud)?;
+    let points: Vec<&CloudPoint> = decoded.finite_points().collect();
+    let point_count = u32::try_from(points.len())
+        .map_err(|_| BagError::export("Too many points for a LAS 1.2 file"))?;
+
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for point in &points {
+        for (axis, value) in [point.x, point.y, point.z].into_iter().enumerate() {
+            min[axis] = min[axis].min(value);
+            max[axis] = max[axis].max(value);
+        }
+    }
+    if points.is_empty() {
+        min = [0.0; 3];
+        max = [0.0; 3];
+    }
+    let offset = min.map(f64::floor);
+    for axis in 0..3 {
+        if (max[axis] - offset[axis]) / LAS_SCALE > i32::MAX as f64 {
+            return Err(BagError::export(
+                "Point cloud extent too large for LAS millimeter resolution",
+            ));
+        }
+    }
+
+    let (format_id, record_length): (u8, u16) = if decoded.has_rgb { (2, 26) } else { (0, 20) };
+    let mut out = BufWriter::new(File::create(path)?);
+
+    out.write_all(b"LASF")?;
+    out.write_all(&0u16.to_le_bytes())?; // file source id
+    out.write_all(&0u16.to_le_bytes())?; // global encoding
+    out.write_all(&[0u8; 16])?; // project GUID
+    out.write_all(&[1, 2])?; // version 1.2
+    out.write_all(&padded::<32>("rosbags-rs"))?; // system identifier
+    out.write_all(&padded::<32>(concat!(
+        "rosbags-rs ",
+        env!("CARGO_PKG_VERSION")
+    )))?;
+    out.write_all(&0u16.to_le_bytes())?; // creation day of year
+    out.write_all(&0u16.to_le_bytes())?; // creation year
+    out.write_all(&LAS_HEADER_SIZE.to_le_bytes())?;
+    out.write_all(&(LAS_HEADER_SIZE as u32).to_le_bytes())?; // offset to point data
+    out.write_all(&0u32.to_le_bytes())?; // number of variable length records
+    out.write_all(&[format_id])?;
+    out.write_all(&record_length.to_le_bytes())?;
+    out.write_all(&point_count.to_le_bytes())?;
+    out.write_all(&point_count.to_le_bytes())?; // points by return: all first returns
+    out.write_all(&[0u8; 16])?;
+    for _ in 0..3 {
+        out.write_all(&LAS_SCALE.to_le_bytes())?;
+    }
+    for value in offset {
+        out.write_all(&value.to_le_bytes())?;
+    }
+    for axis in 0..3 {
+        out.write_all(&max[axis].to_le_bytes())?;
+        out.write_all(&min[axis].to_le_bytes())?;
+    }
+
+    for point in points {
+        for (axis, value) in [point.x, point.y, point.z].into_iter().enumerate() {
+            let scaled = ((value - offset[axis]) / LAS_SCALE).round() as i32;
+            out.write_all(&scaled.to_le_bytes())?;
+        }
+        let intensity = point.intensity.clamp(0.0, u16::MAX as f32).round() as u16;
+        out.write_all(&intensity.to_le_bytes())?;
+        out.write_all(&[0b0000_1001])?; // return 1 of 1
+        out.write_all(&[0])?; // classification
+        out.write_all(&[0])?; // scan angle rank
+        out.write_all(&[0])?; // user data
+        out.write_all(&0u16.to_le_bytes())?; // point source id
+        if decoded.has_rgb {
+            for channel in point.rgb {
+                out.write_all(&(channel as u16 * 257).to_le_bytes())?;
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Extract all point clouds of `topic` into `output_dir`
+///
+/// Files are named `cloud_XXXXXX_<timestamp>.<ext>`. Messages that cannot be decoded
+/// are recorded in [`PointCloudExtraction::skipped`] instead of aborting the extraction.
+///
+/// Each frame records the bag timestamp and, when available, the header stamp;
+/// use [`write_frame_index`](super::write_frame_index) to tabulate both.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::export::pointcloud::{extract_pointclouds, DataEncoding, PointCloudFormat};
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+///
+/// let format = PointCloudFormat::Pcd(DataEncoding::Binary);
+/// let extraction = extract_pointclouds(&reader, "/lidar/points", "clouds", format)?;
+/// println!("Wrote {} clouds", extraction.clouds.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn extract_pointclouds<P: AsRef<Path>>(
+    reader: &Reader,
+    topic: &str,
+    output_dir: P,
+    format: PointCloudFormat,
+) -> Result<PointCloudExtraction> {
+    let output_dir = output_dir.as_ref();
+
+    let connections = reader.connections_for_topic(topic);
+    if connections.is_empty() {
+        return Err(BagError::connection_not_found(topic));
+    }
+    if let Some(conn) = connections
+        .iter()
+        .find(|c| c.message_type != POINT_CLOUD2_TYPE)
+    {
+        return Err(BagError::export(format!(
+            "Topic {topic} has type {}, expected {POINT_CLOUD2_TYPE}",
+            conn.message_type
+        )));
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut extraction = PointCloudExtraction::default();
+    for message_result in reader.messages_filtered(Some(&connections), None, None)? {
+        let message = message_result?;
+        let index = extraction.clouds.len() + extraction.skipped.len();
+        let path = output_dir.join(format!(
+            "cloud_{:06}_{}.{}",
+            index,
+            message.timestamp,
+            format.extension()
+        ));
+
+        match write_message(&message.data, &path, format) {
+            Ok(()) => extraction.clouds.push(ExtractedFrame {
+                path,
+                timestamp: message.timestamp,
+                header_stamp: super::header_stamp(&message.data),
+            }),
+            Err(e) => extraction.skipped.push((message.timestamp, e.to_string())),
+        }
+    }
+
+    Ok(extraction)
+}
+
+fn write_message(data: &[u8], path: &Path, format: PointCloudFormat) -> Result<()> {
+    let mut deserializer = CdrDeserializer::new(data)?;
+    let cloud = PointCloud2::from_cdr(&mut deserializer)?;
+    write_pointcloud(&cloud, path, format)
+}
+
+fn pack_rgb(rgb: [u8; 3]) -> u32 {
+    (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32
+}
+
+fn padded<const N: usize>(text: &str) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    let len = text.len().min(N);
+    bytes[..len].copy_from_slice(&text.as_bytes()[..len]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Header;
+
+    fn field(name: &str, offset: u32, datatype: u8) -> PointField {
+        PointField {
+            name: name.to_string(),
+            offset,
+            datatype,
+            count: 1,
+        }
+    }
+
+    fn cloud(points: &[[f32; 4]], big_endian: bool) -> PointCloud2 {
+        let mut data = Vec::new();
+        for point in points {
+            for value in point {
+                if big_endian {
+                    data.extend_from_slice(&value.to_be_bytes());
+                } else {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        PointCloud2 {
+            header: Header {
+                stamp: Default::default(),
+                frame_id: "lidar".to_string(),
+            },
+            height: 1,
+            width: points.len() as u32,
+            fields: vec![
+                field("x", 0, PointField::FLOAT32),
+                field("y", 4, PointField::FLOAT32),
+                field("z", 8, PointField::FLOAT32),
+                field("rgb", 12, PointField::FLOAT32),
+            ],
+            is_bigendian: big_endian,
+            point_step: 16,
+            row_step: 16 * points.len() as u32,
+            data,
+            is_dense: false,
+        }
+    }
+
+    fn packed(rgb: u32) -> f32 {
+        f32::from_bits(rgb)
+    }
+
+    #[test]
+    fn test_decode_big_endian_with_color() {
+        let msg = cloud(&[[1.0, 2.0, 3.0, packed(0x00ff8001)]], true);
+        let decoded = decode_points(&msg).unwrap();
+        assert_eq!(decoded.points.len(), 1);
+        let point = decoded.points[0];
+        assert_eq!((point.x, point.y, point.z), (1.0, 2.0, 3.0));
+        assert_eq!(point.rgb, [0xff, 0x80, 0x01]);
+        assert!(decoded.has_rgb && !decoded.has_intensity);
+    }
+
+    #[test]
+    fn test_missing_coordinate_rejected() {
+        let mut msg = cloud(&[[1.0, 2.0, 3.0, 0.0]], false);
+        msg.fields.retain(|f| f.name != "z");
+        assert!(decode_points(&msg).is_err());
+    }
+
+    #[test]
+    fn test_ply_drops_invalid_points() {
+        let dir = tempfile::tempdir().unwrap();
+        let msg = cloud(&[[1.0, 2.0, 3.0, 0.0], [f32::NAN, 0.0, 0.0, 0.0]], false);
+        let path = dir.path().join("cloud.ply");
+        write_ply(&msg, &path, DataEncoding::Ascii).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("element vertex 1\n"));
+        assert!(text.ends_with("1 2 3 0 0 0\n"));
+    }
+
+    #[test]
+    fn test_pcd_keeps_organized_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let msg = cloud(&[[1.0, 2.0, 3.0, 0.0], [f32::NAN, 0.0, 0.0, 0.0]], false);
+        let path = dir.path().join("cloud.pcd");
+        write_pcd(&msg, &path, DataEncoding::Binary).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header_end = bytes
+            .windows(12)
+            .position(|w| w == b"DATA binary\n")
+            .unwrap()
+            + 12;
+        assert_eq!(bytes.len() - header_end, 2 * 16);
+        let text = String::from_utf8_lossy(&bytes[..header_end]);
+        assert!(text.contains("WIDTH 2\n") && text.contains("POINTS 2\n"));
+    }
+
+    #[test]
+    fn test_las_header_and_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let msg = cloud(&[[1.0, 2.0, 3.0, 0.0], [1.5, 2.5, 3.5, 0.0]], false);
+        let path = dir.path().join("cloud.las");
+        write_las(&msg, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..4], b"LASF");
+        assert_eq!(bytes.len(), 227 + 2 * 26);
+        assert_eq!(bytes[104], 2); // point data format with RGB
+                                   // Second point x: (1.5 - 1.0) / 0.001
+        let x = i32::from_le_bytes(bytes[227 + 26..227 + 30].try_into().unwrap());
+        assert_eq!(x, 500);
+    }
+}
@@ -0,0 +1,771 @@
+//! Live playback to Foxglove Studio over the Foxglove WebSocket protocol
+//!
+//! [`FoxgloveServer`] accepts a Foxglove Studio connection ("Open connection" →
+//! "Foxglove WebSocket", e.g. `ws://localhost:8765`), advertises every played topic
+//! as a channel with its full `ros2msg` schema, and streams the messages a [`Player`]
+//! delivers to the channels the client subscribed to. Studio decodes the CDR payloads
+//! itself, so no ROS installation or bag conversion is needed. The server announces
+//! the `playbackControl` capability with the played time span, so Studio's playback
+//! bar pauses, plays, seeks and changes the speed through the player's
+//! [`PlayerControls`] and receives a playback state after each request.
+//!
+//! Only the parts of `foxglove.websocket.v1` needed for playback are implemented:
+//! `serverInfo`, `advertise`, `subscribe`/`unsubscribe`, message data, server time,
+//! playback control requests and playback state.
+//!
+//! [`PlayerControls`]: crate::player::PlayerControls
+
+use crate::definitions::DefinitionResolver;
+use crate::error::{BagError, Result};
+use crate::player::{Player, PlayerControls};
+use crate::types::{Connection, MessageDefinitionFormat};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// WebSocket subprotocol of the Foxglove WebSocket protocol
+pub const SUBPROTOCOL: &str = "foxglove.websocket.v1";
+
+/// Port Foxglove Studio connects to by default
+pub const DEFAULT_PORT: u16 = 8765;
+
+/// GUID appended to the client key in the WebSocket handshake (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest client frame accepted; clients only send small JSON requests
+const MAX_CLIENT_FRAME: u64 = 1 << 20;
+
+/// Binary opcode of message data
+const OP_MESSAGE_DATA: u8 = 0x01;
+
+/// Binary opcode of the server time
+const OP_TIME: u8 = 0x02;
+
+/// Binary opcode of the playback state
+const OP_PLAYBACK_STATE: u8 = 0x05;
+
+/// Binary client opcode of a playback control request
+const OP_PLAYBACK_CONTROL_REQUEST: u8 = 0x03;
+
+/// Playback state statuses
+mod status {
+    pub const PLAYING: u8 = 0;
+    pub const PAUSED: u8 = 1;
+    pub const ENDED: u8 = 3;
+}
+
+/// WebSocket frame opcodes
+mod opcode {
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xA;
+}
+
+/// Serves bag playback to one Foxglove Studio client at a time
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::foxglove::FoxgloveServer;
+/// use rosbags_rs::{Player, Reader};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// let server = FoxgloveServer::bind("127.0.0.1:8765")?;
+/// println!("Connect Foxglove Studio to ws://{}", server.local_addr()?);
+/// let sent = server.serve(&Player::new(&reader).looping(true))?;
+/// println!("Sent {sent} messages");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FoxgloveServer {
+    listener: TcpListener,
+    name: String,
+}
+
+impl FoxgloveServer {
+    /// Listen for Foxglove Studio connections on `addr`
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            name: "rosbags-rs".to_string(),
+        })
+    }
+
+    /// Set the server name shown by Foxglove Studio (default: `rosbags-rs`)
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Address the server listens on, e.g. to find the port after binding port 0
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Wait for a client and stream `player` to it
+    ///
+    /// Playback starts once the client subscribed to a channel, so the first
+    /// messages are not lost while Studio sets up its panels. Returns the number of
+    /// messages sent when playback ends or the client disconnects.
+    pub fn serve(&self, player: &Player) -> Result<u64> {
+        let (stream, _) = self.listener.accept()?;
+        self.serve_client(stream, player)
+    }
+
+    /// Stream `player` to the client connected on `stream`
+    pub fn serve_client(&self, stream: TcpStream, player: &Player) -> Result<u64> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        accept_handshake(&mut reader, &stream)?;
+        let (start, _) = player.time_span();
+        let session = Arc::new(Session {
+            writer: Mutex::new(stream),
+            subscriptions: Mutex::default(),
+            controls: player.controls(),
+            position: AtomicU64::new(start),
+        });
+        let writer = &session.writer;
+        let controls = &session.controls;
+
+        send_text(writer, &server_info(&self.name, player.time_span()))?;
+        send_text(writer, &advertise(player.connections()))?;
+
+        loop {
+            match read_request(&mut reader, writer)? {
+                Some(request) => {
+                    session.handle_request(&request)?;
+                    if !lock(&session.subscriptions).is_empty() {
+                        break;
+                    }
+                }
+                None => return Ok(0),
+            }
+        }
+
+        // Requests arriving during playback are handled on their own thread; a
+        // disconnecting client stops playback
+        let listener = {
+            let session = Arc::clone(&session);
+            std::thread::Builder::new()
+                .name("rosbags-foxglove".to_string())
+                .spawn(move || {
+                    while let Ok(Some(request)) = read_request(&mut reader, &session.writer) {
+                        if session.handle_request(&request).is_err() {
+                            break;
+                        }
+                    }
+                    session.controls.stop();
+                })?
+        };
+
+        let mut sent = 0u64;
+        let mut error = None;
+        let played = player.play(|message| {
+            if error.is_some() {
+                return;
+            }
+            let mut time = vec![OP_TIME];
+            time.extend_from_slice(&message.timestamp.to_le_bytes());
+            session.position.store(message.timestamp, Ordering::Relaxed);
+            let mut send = || -> Result<()> {
+                send_frame(writer, opcode::BINARY, &time)?;
+                let subscription = lock(&session.subscriptions)
+                    .get(&message.connection.id)
+                    .copied();
+                if let Some(subscription) = subscription {
+                    let mut frame = Vec::with_capacity(13 + message.data.len());
+                    frame.push(OP_MESSAGE_DATA);
+                    frame.extend_from_slice(&subscription.to_le_bytes());
+                    frame.extend_from_slice(&message.timestamp.to_le_bytes());
+                    frame.extend_from_slice(&message.data);
+                    send_frame(writer, opcode::BINARY, &frame)?;
+                    sent += 1;
+                }
+                Ok(())
+            };
+            if let Err(e) = send() {
+                error = Some(e);
+                controls.stop();
+            }
+        });
+
+        // Closing the connection ends the request thread
+        if !controls.is_stopped() {
+            let _ = session.send_state(status::ENDED, false, "");
+        }
+        let _ = send_frame(writer, opcode::CLOSE, &[]);
+        let _ = lock(writer).shutdown(std::net::Shutdown::Both);
+        let _ = listener.join();
+        played?;
+        match error {
+            // A client closing the connection mid-write ends playback normally
+            Some(BagError::Io(e)) if is_disconnect(&e) => Ok(sent),
+            Some(e) => Err(e),
+            None => Ok(sent),
+        }
+    }
+}
+
+/// Client connection state shared by the playback and the request thread
+struct Session {
+    writer: Mutex<TcpStream>,
+    /// Channel id -> subscription id
+    subscriptions: Mutex<HashMap<u32, u32>>,
+    controls: PlayerControls,
+    /// Playback position reported in playback states (nanoseconds)
+    position: AtomicU64,
+}
+
+impl Session {
+    /// Apply a client request
+    fn handle_request(&self, request: &Request) -> Result<()> {
+        match request {
+            Request::Json(request) => {
+                handle_subscriptions(request, &self.subscriptions);
+                Ok(())
+            }
+            Request::PlaybackControl(request) => self.control_playback(request),
+        }
+    }
+
+    /// Drive the player as requested and answer with the resulting playback state
+    fn control_playback(&self, request: &PlaybackControl) -> Result<()> {
+        // Seek before resuming, so no message from before the target slips through
+        if let Some(timestamp) = request.seek {
+            self.position.store(timestamp, Ordering::Relaxed);
+            self.controls.seek(timestamp);
+        }
+        self.controls.set_rate(f64::from(request.speed));
+        match request.command {
+            PlaybackCommand::Play => self.controls.resume(),
+            PlaybackCommand::Pause => self.controls.pause(),
+        }
+        let status = if self.controls.is_paused() {
+            status::PAUSED
+        } else {
+            status::PLAYING
+        };
+        self.send_state(status, request.seek.is_some(), &request.request_id)
+    }
+
+    /// Send a playback state answering `request_id` (empty when unsolicited)
+    fn send_state(&self, status: u8, did_seek: bool, request_id: &str) -> Result<()> {
+        let mut frame = Vec::with_capacity(23 + request_id.len());
+        frame.push(OP_PLAYBACK_STATE);
+        frame.push(status);
+        frame.extend_from_slice(&self.position.load(Ordering::Relaxed).to_le_bytes());
+        frame.extend_from_slice(&(self.controls.rate() as f32).to_le_bytes());
+        frame.push(u8::from(did_seek));
+        frame.extend_from_slice(&(request_id.len() as u32).to_le_bytes());
+        frame.extend_from_slice(request_id.as_bytes());
+        send_frame(&self.writer, opcode::BINARY, &frame)
+    }
+}
+
+/// Request sent by the client
+#[derive(Debug)]
+enum Request {
+    /// JSON request such as `subscribe`
+    Json(serde_json::Value),
+    /// Binary playback control request
+    PlaybackControl(PlaybackControl),
+}
+
+/// Command of a playback control request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaybackCommand {
+    Play,
+    Pause,
+}
+
+/// Playback control request of Studio's playback bar
+#[derive(Debug)]
+struct PlaybackControl {
+    command: PlaybackCommand,
+    speed: f32,
+    /// Bag time to continue from (nanoseconds)
+    seek: Option<u64>,
+    request_id: String,
+}
+
+impl PlaybackControl {
+    /// Parse the request following its opcode: command (u8), speed (f32), seek
+    /// flag (u8), seek time (u64) and the length-prefixed request id, little-endian
+    fn parse(data: &[u8]) -> Option<Self> {
+        let command = match data.first()? {
+            0 => PlaybackCommand::Play,
+            1 => PlaybackCommand::Pause,
+            _ => return None,
+        };
+        let speed = f32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+        let had_seek = *data.get(5)? != 0;
+        let seek_time = u64::from_le_bytes(data.get(6..14)?.try_into().ok()?);
+        let len = u32::from_le_bytes(data.get(14..18)?.try_into().ok()?) as usize;
+        let request_id = std::str::from_utf8(data.get(18..18usize.checked_add(len)?)?).ok()?;
+        Some(Self {
+            command,
+            speed,
+            seek: had_seek.then_some(seek_time),
+            request_id: request_id.to_string(),
+        })
+    }
+}
+
+/// `serverInfo` message announcing the server, its capabilities and, for the
+/// playback bar, the first and last timestamp of the played data
+fn server_info(name: &str, (start, end): (u64, u64)) -> String {
+    let time = |nanos: u64| serde_json::json!({ "sec": nanos / 1_000_000_000, "nsec": nanos % 1_000_000_000 });
+    serde_json::json!({
+        "op": "serverInfo",
+        "name": name,
+        "capabilities": ["time", "playbackControl"],
+        "supportedEncodings": [],
+        "metadata": {},
+        "dataStartTime": time(start),
+        "dataEndTime": time(end),
+    })
+    .to_string()
+}
+
+/// `advertise` message with a channel per connection, identified by connection id
+fn advertise(connections: &[Connection]) -> String {
+    let resolver = DefinitionResolver::new();
+    let channels: Vec<serde_json::Value> = connections
+        .iter()
+        .map(|connection| {
+            let definition = resolver
+                .complete_definition(connection)
+                .unwrap_or_else(|_| connection.message_definition.clone());
+            let schema_encoding = match definition.format {
+                MessageDefinitionFormat::Idl => "ros2idl",
+                _ => "ros2msg",
+            };
+            serde_json::json!({
+                "id": connection.id,
+                "topic": connection.topic,
+                "encoding": connection.serialization_format,
+                "schemaName": connection.message_type,
+                "schema": definition.data,
+                "schemaEncoding": schema_encoding,
+            })
+        })
+        .collect();
+    serde_json::json!({ "op": "advertise", "channels": channels }).to_string()
+}
+
+/// Apply a `subscribe` or `unsubscribe` request; other requests are ignored
+fn handle_subscriptions(request: &serde_json::Value, subscriptions: &Mutex<HashMap<u32, u32>>) {
+    let id = |value: &serde_json::Value| value.as_u64().and_then(|id| u32::try_from(id).ok());
+    let mut subscriptions = lock(subscriptions);
+    match request["op"].as_str() {
+        Some("subscribe") => {
+            for subscription in request["subscriptions"].as_array().into_iter().flatten() {
+                if let (Some(id), Some(channel)) =
+                    (id(&subscription["id"]), id(&subscription["channelId"]))
+                {
+                    subscriptions.insert(channel, id);
+                }
+            }
+        }
+        Some("unsubscribe") => {
+            let ids: Vec<u32> = request["subscriptionIds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(id)
+                .collect();
+            subscriptions.retain(|_, subscription| !ids.contains(subscription));
+        }
+        _ => {}
+    }
+}
+
+/// Read the next request, answering pings; `None` once the client closed
+fn read_request(reader: &mut impl Read, writer: &Mutex<TcpStream>) -> Result<Option<Request>> {
+    loop {
+        let Some((op, payload)) = read_message(reader)? else {
+            return Ok(None);
+        };
+        // Malformed requests are skipped like unknown ones
+        match op {
+            opcode::TEXT => {
+                if let Ok(request) = serde_json::from_slice(&payload) {
+                    return Ok(Some(Request::Json(request)));
+                }
+            }
+            opcode::BINARY => {
+                if let Some((&OP_PLAYBACK_CONTROL_REQUEST, data)) = payload.split_first() {
+                    if let Some(request) = PlaybackControl::parse(data) {
+                        return Ok(Some(Request::PlaybackControl(request)));
+                    }
+                }
+            }
+            opcode::PING => send_frame(writer, opcode::PONG, &payload)?,
+            opcode::CLOSE => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Complete the HTTP upgrade of a WebSocket client offering the Foxglove subprotocol
+fn accept_handshake(reader: &mut impl BufRead, mut stream: &TcpStream) -> Result<()> {
+    let mut key = None;
+    let mut protocols = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(BagError::generic(
+                "Client closed the connection during handshake",
+            ));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "sec-websocket-key" => key = Some(value.trim().to_string()),
+                "sec-websocket-protocol" => protocols = value.to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    let Some(key) = key else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Err(BagError::generic(
+            "Client did not request a WebSocket upgrade",
+        ));
+    };
+    if !protocols.split(',').any(|p| p.trim() == SUBPROTOCOL) {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Err(BagError::generic(format!(
+            "Client does not speak the {SUBPROTOCOL} subprotocol"
+        )));
+    }
+
+    let accept = accept_key(&key);
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {accept}\r\nSec-WebSocket-Protocol: {SUBPROTOCOL}\r\n\r\n"
+    )?;
+    Ok(())
+}
+
+/// Read a complete message, joining fragmented frames; `None` at end of stream
+///
+/// Control frames may arrive between fragments and are returned on their own.
+fn read_message(reader: &mut impl Read) -> Result<Option<(u8, Vec<u8>)>> {
+    let mut message: Option<(u8, Vec<u8>)> = None;
+    loop {
+        let Some((fin, op, payload)) = read_frame(reader)? else {
+            return Ok(None);
+        };
+        if op >= opcode::CLOSE {
+            return Ok(Some((op, payload)));
+        }
+        let (_, data) = message.get_or_insert((op, Vec::new()));
+        data.extend_from_slice(&payload);
+        if data.len() as u64 > MAX_CLIENT_FRAME {
+            return Err(BagError::generic("Foxglove client message too large"));
+        }
+        if fin {
+            return Ok(message);
+        }
+    }
+}
+
+/// Read one frame as `(fin, opcode, payload)`, unmasking client data
+fn read_frame(reader: &mut impl Read) -> Result<Option<(bool, u8, Vec<u8>)>> {
+    let mut head = [0u8; 2];
+    match reader.read_exact(&mut head) {
+        Ok(()) => {}
+        Err(e) if is_disconnect(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let fin = head[0] & 0x80 != 0;
+    let op = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if len > MAX_CLIENT_FRAME {
+        return Err(BagError::generic("Foxglove client frame too large"));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok(Some((fin, op, payload)))
+}
+
+/// Send a text frame
+fn send_text(writer: &Mutex<TcpStream>, text: &str) -> Result<()> {
+    send_frame(writer, opcode::TEXT, text.as_bytes())
+}
+
+/// Send one unmasked, unfragmented frame
+fn send_frame(writer: &Mutex<TcpStream>, op: u8, payload: &[u8]) -> Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | op);
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    lock(writer).write_all(&frame)?;
+    Ok(())
+}
+
+/// Whether an I/O error means the peer went away
+fn is_disconnect(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        e.kind(),
+        ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected
+    )
+}
+
+/// Lock `mutex`, ignoring that a panicking thread held it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `Sec-WebSocket-Accept` header value answering the client's `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    use base64::Engine;
+    use sha1::{Digest, Sha1};
+    let digest = Sha1::new()
+        .chain_update(key.as_bytes())
+        .chain_update(WEBSOCKET_GUID.as_bytes())
+        .finalize();
+    base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "sqlite")]
+    use crate::reader::Reader;
+
+    /// Send a masked client frame
+    #[cfg(feature = "sqlite")]
+    fn send_client_frame(stream: &mut TcpStream, op: u8, payload: &[u8]) {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![0x80 | op, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        stream.write_all(&frame).unwrap();
+    }
+
+    #[test]
+    fn test_handshake_digest() {
+        // Example of RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_streams_subscribed_channels() {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        let server = FoxgloveServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                 Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: {SUBPROTOCOL}\r\n\r\n"
+            )
+            .unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut response = String::new();
+            while !response.ends_with("\r\n\r\n") {
+                reader.read_line(&mut response).unwrap();
+            }
+            assert!(response.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+            let mut json = || {
+                let (op, payload) = read_message(&mut reader).unwrap().unwrap();
+                assert_eq!(op, opcode::TEXT);
+                serde_json::from_slice::<serde_json::Value>(&payload).unwrap()
+            };
+            assert_eq!(json()["op"], "serverInfo");
+            let advertise = json();
+            let channel = advertise["channels"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|channel| channel["topic"] == "/test/geometry_msgs/pose")
+                .unwrap()
+                .clone();
+            assert_eq!(channel["schemaEncoding"], "ros2msg");
+            assert!(channel["schema"].as_str().unwrap().contains("MSG: "));
+
+            let subscribe = serde_json::json!({
+                "op": "subscribe",
+                "subscriptions": [{"id": 7, "channelId": channel["id"]}],
+            });
+            send_client_frame(&mut stream, opcode::TEXT, subscribe.to_string().as_bytes());
+
+            let mut messages = 0;
+            while let Some((op, payload)) = read_message(&mut reader).unwrap() {
+                if op == opcode::CLOSE {
+                    break;
+                }
+                assert_eq!(op, opcode::BINARY);
+                if payload[0] == OP_MESSAGE_DATA {
+                    assert_eq!(&payload[1..5], &7u32.to_le_bytes());
+                    messages += 1;
+                }
+            }
+            messages
+        });
+
+        let player = Player::new(&reader).rate(1e6);
+        let sent = server.serve(&player).unwrap();
+        let received = client.join().unwrap();
+        let pose_count = reader
+            .connections()
+            .iter()
+            .filter(|c| c.topic == "/test/geometry_msgs/pose")
+            .map(|c| c.message_count)
+            .sum::<u64>();
+        assert!(pose_count > 0);
+        assert_eq!(sent, pose_count);
+        assert_eq!(received, pose_count);
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_seek_from_playback_control() {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        let pose: Vec<u64> = reader
+            .messages()
+            .unwrap()
+            .map(|m| m.unwrap())
+            .filter(|m| m.topic == "/test/geometry_msgs/pose")
+            .map(|m| m.timestamp)
+            .collect();
+        assert!(pose.len() > 1);
+        let target = pose[1];
+        let server = FoxgloveServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                 Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: {SUBPROTOCOL}\r\n\r\n"
+            )
+            .unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut response = String::new();
+            while !response.ends_with("\r\n\r\n") {
+                reader.read_line(&mut response).unwrap();
+            }
+
+            let mut json = || {
+                let (_, payload) = read_message(&mut reader).unwrap().unwrap();
+                serde_json::from_slice::<serde_json::Value>(&payload).unwrap()
+            };
+            let info = json();
+            assert!(info["capabilities"]
+                .as_array()
+                .unwrap()
+                .contains(&"playbackControl".into()));
+            let advertise = json();
+            let channel = advertise["channels"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|channel| channel["topic"] == "/test/geometry_msgs/pose")
+                .unwrap()
+                .clone();
+            let subscribe = serde_json::json!({
+                "op": "subscribe",
+                "subscriptions": [{"id": 3, "channelId": channel["id"]}],
+            });
+            send_client_frame(&mut stream, opcode::TEXT, subscribe.to_string().as_bytes());
+
+            // Play from the second pose message at full speed
+            let mut request = vec![OP_PLAYBACK_CONTROL_REQUEST, 0];
+            request.extend_from_slice(&1e6f32.to_le_bytes());
+            request.push(1);
+            request.extend_from_slice(&target.to_le_bytes());
+            request.extend_from_slice(&4u32.to_le_bytes());
+            request.extend_from_slice(b"seek");
+            send_client_frame(&mut stream, opcode::BINARY, &request);
+
+            let mut state = None;
+            let mut timestamps = Vec::new();
+            while let Some((op, payload)) = read_message(&mut reader).unwrap() {
+                if op == opcode::CLOSE {
+                    break;
+                }
+                match payload[0] {
+                    OP_MESSAGE_DATA => {
+                        timestamps.push(u64::from_le_bytes(payload[5..13].try_into().unwrap()))
+                    }
+                    OP_PLAYBACK_STATE if payload.ends_with(b"seek") => state = Some(payload),
+                    _ => {}
+                }
+            }
+            (state.unwrap(), timestamps)
+        });
+
+        let player = Player::new(&reader).rate(1e6).start_paused(true);
+        server.serve(&player).unwrap();
+        let (state, timestamps) = client.join().unwrap();
+        assert_eq!(state[1], status::PLAYING);
+        assert_eq!(state[10..14], 1e6f32.to_le_bytes());
+        assert_eq!(state[14], 1);
+        assert_eq!(timestamps.first(), Some(&target));
+        assert_eq!(timestamps, pose[1..]);
+    }
+}
@@ -35,7 +35,7 @@
 //! let mut reader = Reader::new(Path::new("path/to/bag"))?;
 //! reader.open()?;
 //!
-//! println!("Bag duration: {:.2}s", reader.duration() as f64 / 1e9);
+//! println!("Bag duration: {}", rosbags_rs::time::format_duration(reader.duration()));
 //! println!("Topics: {}", reader.topics().len());
 //!
 //! for message_result in reader.messages()? {
@@ -84,7 +84,7 @@
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! let metadata = read_bag_metadata_fast(Path::new("path/to/bag"))?;
 //!
-//! println!("Duration: {:.2}s", metadata.duration() as f64 / 1e9);
+//! println!("Duration: {:.2}s", rosbags_rs::time::secs_f64(metadata.duration()));
 //! println!("Message count: {}", metadata.message_count());
 //! println!("Topics: {}", metadata.info().topics_with_message_count.len());
 //! # Ok(())
@@ -138,21 +138,57 @@
 //! # }
 //! ```
 //!
+//! ### Open-time configuration
+//!
+//! [`Reader::builder`] sets options once instead of passing them to every call:
+//! a topic allowlist, a default time range, a storage plugin override, reading
+//! bags without `metadata.yaml`, memory mapping and decompression of payloads.
+//!
+//! ```rust,no_run
+//! use rosbags_rs::Reader;
+//! # use rosbags_rs::ReaderError;
+//! # fn main() -> Result<(), ReaderError> {
+//! # let bag_path = std::path::Path::new("/path/to/rosbag");
+//! let reader = Reader::builder(bag_path)
+//!     .topics(["/camera/image_raw", "/imu/data"])
+//!     .time_range(Some(1000000000), Some(2000000000))
+//!     .open()?;
+//!
+//! for message_result in reader.messages()? {
+//!     println!("Message on topic: {}", message_result?.topic);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ## Supported Message Types
 //!
 //! The library supports 94+ ROS2 message types including:
 //!
-//! - **std_msgs**: String, Header, Int32, Float64, etc.
-//! - **geometry_msgs**: Point, Pose, Transform, Twist, etc.
+//! - **std_msgs**: String, Header, Int32, Float64, Float64MultiArray and the other MultiArrays, etc.
+//! - **geometry_msgs**: Point, Pose, Pose2D, Transform, Twist, Accel, Wrench, Inertia, Polygon, with stamped and covariance variants
 //! - **sensor_msgs**: Image, PointCloud2, Imu, NavSatFix, etc.
 //! - **nav_msgs**: Odometry, Path, MapMetaData, etc.
 //! - **diagnostic_msgs**: DiagnosticArray, DiagnosticStatus, etc.
+//! - **rosgraph_msgs**: Clock
 //! - **builtin_interfaces**: Time, Duration
 //!
 //! ## Cross-Compatibility
 //!
 //! This library guarantees byte-for-byte identical results compared to the Python rosbags library,
 //! making it a drop-in replacement for performance-critical applications.
+//!
+//! ## Memory Safety
+//!
+//! The default build contains no `unsafe` code and is compiled with `#![forbid(unsafe_code)]`.
+//! Performance fast paths that require `unsafe` (such as memory-mapping MCAP files) are only
+//! compiled in when the opt-in `unsafe-opt` feature is enabled, and the C API when the
+//! `capi` feature is.
+
+#![cfg_attr(
+    not(any(feature = "unsafe-opt", feature = "capi")),
+    forbid(unsafe_code)
+)]
 
 /// Core CDR (Common Data Representation) deserialization functionality.
 ///
@@ -169,6 +205,66 @@ pub mod error;
 /// Contains Rust definitions for common ROS2 message types with full CDR deserialization support.
 pub mod messages;
 
+/// Runtime message schemas.
+///
+/// Parses stored message definitions to expose field layouts and constants dynamically.
+pub mod schema;
+
+/// Bundled message definitions.
+///
+/// Embeds the standard interface packages for bags that do not store message definitions.
+pub mod definitions;
+
+/// Custom .msg/IDL definitions used by Reader and Writer.
+///
+/// [`registry::TypeRegistry`] resolves types that neither the bag nor the bundled definitions describe.
+pub mod registry;
+
+/// Schema-driven message decoding.
+///
+/// Decodes messages from their stored definitions without compiled message types.
+pub mod dynamic;
+
+/// Rust code generation from stored message definitions.
+///
+/// Emits typed message structs with CDR (de)serialization for the types recorded in a bag.
+pub mod codegen;
+
+/// Columnar field selection.
+///
+/// Backs [`Reader::select`] for extracting a few fields from many messages.
+#[cfg(not(feature = "write-only"))]
+pub mod query;
+
+/// Export of bag contents to standard file formats.
+///
+/// Converts recorded messages into files usable by external tools (e.g. images).
+#[cfg(not(feature = "write-only"))]
+pub mod export;
+
+/// Message transforms applied while copying bags.
+///
+/// Rewrites selected topics (e.g. downscaling images for preview bags) and passes the rest through.
+#[cfg(not(feature = "write-only"))]
+pub mod transform;
+
+/// Splitting bags into a bag per topic.
+///
+/// [`explode::Explode`] writes each topic, or named group of topics, to its own bag with the recorded schemas and QoS.
+#[cfg(all(feature = "default", not(feature = "write-only")))]
+pub mod explode;
+
+/// Upgrades of messages recorded with outdated interface definitions.
+///
+/// Rewrites messages of older type layouts into current definitions while copying bags.
+#[cfg(not(feature = "write-only"))]
+pub mod migrations;
+
+/// Validation of written message payloads.
+///
+/// Checks CDR payloads against their message types before they are stored.
+pub mod validation;
+
 /// Metadata parsing and validation.
 ///
 /// Handles parsing of `metadata.yaml` files and validation of bag metadata.
@@ -180,12 +276,110 @@ pub mod metadata;
 #[cfg(not(feature = "write-only"))]
 pub mod reader;
 
+/// Time-based sharding for parallel processing.
+///
+/// [`Reader::shards`] splits a bag into time windows that are read independently.
+#[cfg(not(feature = "write-only"))]
+pub mod shard;
+
+/// Reading several bags as one timeline.
+///
+/// [`MultiReader`] merges the messages of bags recorded side by side into one time-ordered stream.
+#[cfg(not(feature = "write-only"))]
+pub mod multi;
+
+/// Message rate and timing analysis.
+///
+/// [`Reader::histogram`] bins message counts per topic over time to find dropouts and rate anomalies;
+/// [`Reader::stamp_skew`] measures the offset and drift of header stamps from bag time.
+#[cfg(not(feature = "write-only"))]
+pub mod analysis;
+
+/// Message playback with real-time pacing.
+///
+/// The [`Player`] struct replays bag messages to a callback like `ros2 bag play`.
+#[cfg(not(feature = "write-only"))]
+pub mod player;
+
+/// Time synchronization of messages across topics.
+///
+/// [`sync::Synchronizer`] pairs messages with matching timestamps, like ROS `message_filters`.
+#[cfg(not(feature = "write-only"))]
+pub mod sync;
+
+/// Following bags that are still being recorded.
+///
+/// [`Reader::tail`] yields messages as a recorder commits them, like `tail -f` for bags.
+#[cfg(not(feature = "write-only"))]
+pub mod tail;
+
+/// Transform tree reconstruction.
+///
+/// [`tf::TfBuffer`] answers `lookup_transform` queries from recorded `/tf` and `/tf_static` messages.
+#[cfg(not(feature = "write-only"))]
+pub mod tf;
+
+/// Sequential reading with the `rosbag2_py` API.
+///
+/// [`rosbag2::SequentialReader`] offers `open`/`has_next`/`read_next` for porting rosbag2 pipelines.
+#[cfg(not(feature = "write-only"))]
+pub mod rosbag2;
+
+/// Publishing of playback to a live ROS2 graph.
+///
+/// Connects the [`Player`] to a ROS2 client library binding, including `/clock` publishing.
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+pub mod bridge;
+
+/// ROS2 graph access over Zenoh.
+///
+/// [`rmw_zenoh::ZenohNode`] publishes playback to and records from nodes running `rmw_zenoh` (`bridge` and `recorder` features).
+#[cfg(any(
+    all(feature = "bridge", not(feature = "write-only")),
+    all(feature = "recorder", any(feature = "write-only", feature = "default"))
+))]
+pub mod rmw_zenoh;
+
+/// Live playback to Foxglove Studio.
+///
+/// [`foxglove::FoxgloveServer`] streams [`Player`] output over the Foxglove WebSocket protocol (`foxglove` feature).
+#[cfg(all(feature = "foxglove", not(feature = "write-only")))]
+pub mod foxglove;
+
 /// Main writer interface.
 ///
 /// The [`Writer`] struct provides the primary interface for writing ROS2 bag files.
 #[cfg(any(feature = "write-only", feature = "default"))]
 pub mod writer;
 
+/// Live recording of ROS2 topics.
+///
+/// The [`recorder::Recorder`] writes messages from a ROS2 client library binding through a [`Writer`].
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+pub mod recorder;
+
+/// Bags inside tar and zip archives.
+///
+/// Reads a bag from `.tar`, `.tar.gz` or `.zip` archives without extracting them (`archive` feature).
+#[cfg(all(feature = "archive", not(feature = "write-only")))]
+pub mod archive;
+
+/// Property-based round-trip testing.
+///
+/// Random bag generators and a write-read-compare checker over storage, compression and version (`test-utils` feature).
+#[cfg(all(
+    any(test, feature = "test-utils"),
+    feature = "default",
+    not(feature = "write-only")
+))]
+pub mod test_utils;
+
+/// C API of the reader.
+///
+/// `rosbags_`-prefixed functions declared in `include/rosbags.h` (`capi` feature).
+#[cfg(all(feature = "capi", not(feature = "write-only")))]
+pub mod capi;
+
 /// Storage backend implementations.
 ///
 /// Supports both SQLite3 and MCAP storage formats with pluggable architecture.
@@ -196,18 +390,40 @@ pub mod storage;
 /// Defines the fundamental types used throughout the library.
 pub mod types;
 
+/// Time conversions and formatting.
+///
+/// Converts nanosecond timestamps to and from `SystemTime`, `chrono` and ROS stamps.
+pub mod time;
+
 // Re-export main types for convenience
-pub use error::{BagError, ReaderError, Result, WriterResult};
-pub use metadata::{BagMetadata, TopicMetadata};
+pub use error::{BagError, ErrorContext, ReaderError, Result, WriterResult};
+pub use metadata::{BagMetadata, BagStatistics, StatisticsDiscrepancy, TopicMetadata};
+#[cfg(not(feature = "write-only"))]
+pub use multi::MultiReader;
+#[cfg(not(feature = "write-only"))]
+pub use player::{Player, PlayerControls};
 #[cfg(not(feature = "write-only"))]
-pub use reader::Reader;
+pub use reader::{
+    ErrorPolicy, MessageFilter, Reader, ReaderBuilder, ReaderOptions, SkipReport,
+    TypeHashVerification,
+};
+#[cfg(not(feature = "write-only"))]
+pub use shard::{Shard, ShardReader};
+#[cfg(not(feature = "write-only"))]
+pub use storage::StorageInput;
+#[cfg(not(feature = "write-only"))]
+pub use tail::Tail;
 pub use types::{
-    CompressionFormat, CompressionMode, Connection, Message, StoragePlugin, TopicInfo,
+    Attachment, BagFile, CompressionFormat, CompressionMode, Connection, ConnectionBuilder,
+    ConnectionExt, McapMetadata, Message, RawMessage, RawMessageRef, StoragePlugin, TopicInfo,
+    TopicKind, TopicPattern, OFFERED_QOS_PROFILES_KEY,
 };
 
 // Export Writer only when write-only feature is enabled
 #[cfg(any(feature = "write-only", feature = "default"))]
-pub use writer::Writer;
+pub use writer::{
+    OutOfOrderPolicy, RetentionPolicy, SnapshotOptions, SplitPolicy, Writer, WriterBuilder,
+};
 
 #[cfg(not(feature = "write-only"))]
 /// Fast bag metadata reading without opening storage files
@@ -230,7 +446,7 @@ pub use writer::Writer;
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// let metadata = read_bag_metadata_fast(Path::new("path/to/bag"))?;
 ///
-/// println!("Duration: {:.2}s", metadata.duration() as f64 / 1e9);
+/// println!("Duration: {:.2}s", rosbags_rs::time::secs_f64(metadata.duration()));
 /// println!("Message count: {}", metadata.message_count());
 /// println!("Start time: {}", metadata.start_time());
 /// println!("End time: {}", metadata.end_time());
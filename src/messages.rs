@@ -3,16 +3,27 @@
 //! This module contains Rust definitions for common ROS2 message types
 //! that match the official ROS2 API specifications.
 
-use crate::cdr::CdrDeserializer;
+use crate::cdr::{CdrDeserializer, CdrSerializer};
+use crate::definitions::normalize_type_name;
 use crate::error::Result;
 
 /// builtin_interfaces/msg/Time
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Time {
     pub sec: i32,
     pub nanosec: u32,
 }
 
+impl Time {
+    /// Create a time from nanoseconds since the epoch
+    pub fn from_nanos(nanos: u64) -> Self {
+        Self {
+            sec: (nanos / 1_000_000_000) as i32,
+            nanosec: (nanos % 1_000_000_000) as u32,
+        }
+    }
+}
+
 /// std_msgs/msg/Header
 #[derive(Debug, Clone, PartialEq)]
 pub struct Header {
@@ -159,6 +170,133 @@ pub struct PointStamped {
     pub point: Point,
 }
 
+/// geometry_msgs/msg/Vector3Stamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector3Stamped {
+    pub header: Header,
+    pub vector: Vector3,
+}
+
+/// geometry_msgs/msg/QuaternionStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuaternionStamped {
+    pub header: Header,
+    pub quaternion: Quaternion,
+}
+
+/// geometry_msgs/msg/PoseArray
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoseArray {
+    pub header: Header,
+    pub poses: Vec<Pose>,
+}
+
+/// geometry_msgs/msg/Pose2D
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pose2D {
+    pub x: f64,
+    pub y: f64,
+    pub theta: f64,
+}
+
+/// geometry_msgs/msg/TwistStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwistStamped {
+    pub header: Header,
+    pub twist: Twist,
+}
+
+/// geometry_msgs/msg/TwistWithCovarianceStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwistWithCovarianceStamped {
+    pub header: Header,
+    pub twist: TwistWithCovariance,
+}
+
+/// geometry_msgs/msg/Accel
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Accel {
+    pub linear: Vector3,
+    pub angular: Vector3,
+}
+
+/// geometry_msgs/msg/AccelStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccelStamped {
+    pub header: Header,
+    pub accel: Accel,
+}
+
+/// geometry_msgs/msg/AccelWithCovariance
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccelWithCovariance {
+    pub accel: Accel,
+    pub covariance: [f64; 36],
+}
+
+impl Default for AccelWithCovariance {
+    fn default() -> Self {
+        Self {
+            accel: Default::default(),
+            covariance: [0.0; 36],
+        }
+    }
+}
+
+/// geometry_msgs/msg/AccelWithCovarianceStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccelWithCovarianceStamped {
+    pub header: Header,
+    pub accel: AccelWithCovariance,
+}
+
+/// geometry_msgs/msg/Wrench
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Wrench {
+    pub force: Vector3,
+    pub torque: Vector3,
+}
+
+/// geometry_msgs/msg/WrenchStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrenchStamped {
+    pub header: Header,
+    pub wrench: Wrench,
+}
+
+/// geometry_msgs/msg/Inertia
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Inertia {
+    pub m: f64,
+    pub com: Vector3,
+    pub ixx: f64,
+    pub ixy: f64,
+    pub ixz: f64,
+    pub iyy: f64,
+    pub iyz: f64,
+    pub izz: f64,
+}
+
+/// geometry_msgs/msg/InertiaStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct InertiaStamped {
+    pub header: Header,
+    pub inertia: Inertia,
+}
+
+/// geometry_msgs/msg/Polygon
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polygon {
+    pub points: Vec<Point32>,
+}
+
+/// geometry_msgs/msg/PolygonStamped
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolygonStamped {
+    pub header: Header,
+    pub polygon: Polygon,
+}
+
 /// nav_msgs/msg/Path
 #[derive(Debug, Clone, PartialEq)]
 pub struct Path {
@@ -166,6 +304,33 @@ pub struct Path {
     pub poses: Vec<PoseStamped>,
 }
 
+/// nav_msgs/msg/MapMetaData
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MapMetaData {
+    pub map_load_time: Time,
+    pub resolution: f32,
+    pub width: u32,
+    pub height: u32,
+    pub origin: Pose,
+}
+
+/// nav_msgs/msg/OccupancyGrid
+#[derive(Debug, Clone, PartialEq)]
+pub struct OccupancyGrid {
+    pub header: Header,
+    pub info: MapMetaData,
+    pub data: Vec<i8>,
+}
+
+/// nav_msgs/msg/GridCells
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridCells {
+    pub header: Header,
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub cells: Vec<Point>,
+}
+
 /// sensor_msgs/msg/NavSatStatus
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct NavSatStatus {
@@ -191,6 +356,51 @@ pub struct StdString {
     pub data: String,
 }
 
+/// std_msgs/msg/MultiArrayDimension
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MultiArrayDimension {
+    pub label: String,
+    pub size: u32,
+    pub stride: u32,
+}
+
+/// std_msgs/msg/MultiArrayLayout
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MultiArrayLayout {
+    pub dim: Vec<MultiArrayDimension>,
+    pub data_offset: u32,
+}
+
+/// std_msgs/msg/<Type>MultiArray, see the aliases below for the concrete types
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiArray<T> {
+    pub layout: MultiArrayLayout,
+    pub data: Vec<T>,
+}
+
+/// std_msgs/msg/Float32MultiArray
+pub type Float32MultiArray = MultiArray<f32>;
+/// std_msgs/msg/Float64MultiArray
+pub type Float64MultiArray = MultiArray<f64>;
+/// std_msgs/msg/Int8MultiArray
+pub type Int8MultiArray = MultiArray<i8>;
+/// std_msgs/msg/Int16MultiArray
+pub type Int16MultiArray = MultiArray<i16>;
+/// std_msgs/msg/Int32MultiArray
+pub type Int32MultiArray = MultiArray<i32>;
+/// std_msgs/msg/Int64MultiArray
+pub type Int64MultiArray = MultiArray<i64>;
+/// std_msgs/msg/UInt8MultiArray
+pub type UInt8MultiArray = MultiArray<u8>;
+/// std_msgs/msg/ByteMultiArray
+pub type ByteMultiArray = MultiArray<u8>;
+/// std_msgs/msg/UInt16MultiArray
+pub type UInt16MultiArray = MultiArray<u16>;
+/// std_msgs/msg/UInt32MultiArray
+pub type UInt32MultiArray = MultiArray<u32>;
+/// std_msgs/msg/UInt64MultiArray
+pub type UInt64MultiArray = MultiArray<u64>;
+
 /// sensor_msgs/msg/PointField
 #[derive(Debug, Clone, PartialEq)]
 pub struct PointField {
@@ -226,8 +436,42 @@ pub struct Image {
     pub data: Vec<u8>,
 }
 
-/// geometry_msgs/msg/Point32
+/// sensor_msgs/msg/CompressedImage
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedImage {
+    pub header: Header,
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+/// sensor_msgs/msg/RegionOfInterest
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegionOfInterest {
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub height: u32,
+    pub width: u32,
+    pub do_rectify: bool,
+}
+
+/// sensor_msgs/msg/CameraInfo
 #[derive(Debug, Clone, PartialEq)]
+pub struct CameraInfo {
+    pub header: Header,
+    pub height: u32,
+    pub width: u32,
+    pub distortion_model: String,
+    pub d: Vec<f64>,
+    pub k: [f64; 9],
+    pub r: [f64; 9],
+    pub p: [f64; 12],
+    pub binning_x: u32,
+    pub binning_y: u32,
+    pub roi: RegionOfInterest,
+}
+
+/// geometry_msgs/msg/Point32
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Point32 {
     pub x: f32,
     pub y: f32,
@@ -250,6 +494,441 @@ pub struct Duration {
     pub nanosec: u32,
 }
 
+/// action_msgs/msg/GoalInfo
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GoalInfo {
+    pub goal_id: [u8; 16],
+    pub stamp: Time,
+}
+
+/// action_msgs/msg/GoalStatus
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GoalStatus {
+    pub goal_info: GoalInfo,
+    pub status: i8,
+}
+
+/// action_msgs/msg/GoalStatusArray
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GoalStatusArray {
+    pub status_list: Vec<GoalStatus>,
+}
+
+/// rosgraph_msgs/msg/Clock
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Clock {
+    pub clock: Time,
+}
+
+/// tf2_msgs/msg/TFMessage
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TFMessage {
+    pub transforms: Vec<TransformStamped>,
+}
+
+/// diagnostic_msgs/msg/KeyValue
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+/// diagnostic_msgs/msg/DiagnosticStatus
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiagnosticStatus {
+    pub level: u8,
+    pub name: String,
+    pub message: String,
+    pub hardware_id: String,
+    pub values: Vec<KeyValue>,
+}
+
+/// diagnostic_msgs/msg/DiagnosticArray
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticArray {
+    pub header: Header,
+    pub status: Vec<DiagnosticStatus>,
+}
+
+/// sensor_msgs/msg/LaserScan
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaserScan {
+    pub header: Header,
+    pub angle_min: f32,
+    pub angle_max: f32,
+    pub angle_increment: f32,
+    pub time_increment: f32,
+    pub scan_time: f32,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub ranges: Vec<f32>,
+    pub intensities: Vec<f32>,
+}
+
+/// sensor_msgs/msg/Range
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    pub header: Header,
+    pub radiation_type: u8,
+    pub field_of_view: f32,
+    pub min_range: f32,
+    pub max_range: f32,
+    pub range: f32,
+}
+
+/// sensor_msgs/msg/JointState
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointState {
+    pub header: Header,
+    pub name: Vec<String>,
+    pub position: Vec<f64>,
+    pub velocity: Vec<f64>,
+    pub effort: Vec<f64>,
+}
+
+/// sensor_msgs/msg/BatteryState
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryState {
+    pub header: Header,
+    pub voltage: f32,
+    pub temperature: f32,
+    pub current: f32,
+    pub charge: f32,
+    pub capacity: f32,
+    pub design_capacity: f32,
+    pub percentage: f32,
+    pub power_supply_status: u8,
+    pub power_supply_health: u8,
+    pub power_supply_technology: u8,
+    pub present: bool,
+    pub cell_voltage: Vec<f32>,
+    pub cell_temperature: Vec<f32>,
+    pub location: String,
+    pub serial_number: String,
+}
+
+/// sensor_msgs/msg/MagneticField
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagneticField {
+    pub header: Header,
+    pub magnetic_field: Vector3,
+    pub magnetic_field_covariance: [f64; 9],
+}
+
+impl NavSatStatus {
+    pub const STATUS_NO_FIX: i8 = -1;
+    pub const STATUS_FIX: i8 = 0;
+    pub const STATUS_SBAS_FIX: i8 = 1;
+    pub const STATUS_GBAS_FIX: i8 = 2;
+
+    pub const SERVICE_GPS: u16 = 1;
+    pub const SERVICE_GLONASS: u16 = 2;
+    pub const SERVICE_COMPASS: u16 = 4;
+    pub const SERVICE_GALILEO: u16 = 8;
+
+    /// Whether the receiver reports any kind of position fix
+    pub fn has_fix(&self) -> bool {
+        self.status >= Self::STATUS_FIX
+    }
+}
+
+impl NavSatFix {
+    pub const COVARIANCE_TYPE_UNKNOWN: u8 = 0;
+    pub const COVARIANCE_TYPE_APPROXIMATED: u8 = 1;
+    pub const COVARIANCE_TYPE_DIAGONAL_KNOWN: u8 = 2;
+    pub const COVARIANCE_TYPE_KNOWN: u8 = 3;
+}
+
+impl OccupancyGrid {
+    /// Value of an unknown cell
+    pub const UNKNOWN: i8 = -1;
+
+    /// Occupancy probability (0-100) of the cell in column `x` and row `y`, or
+    /// [`OccupancyGrid::UNKNOWN`]; `None` outside the grid
+    pub fn cell(&self, x: u32, y: u32) -> Option<i8> {
+        if x >= self.info.width || y >= self.info.height {
+            return None;
+        }
+        let index = y as usize * self.info.width as usize + x as usize;
+        self.data.get(index).copied()
+    }
+}
+
+impl DiagnosticStatus {
+    pub const OK: u8 = 0;
+    pub const WARN: u8 = 1;
+    pub const ERROR: u8 = 2;
+    pub const STALE: u8 = 3;
+
+    /// Look up the value reported under `key`
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|pair| pair.key == key)
+            .map(|pair| pair.value.as_str())
+    }
+}
+
+impl DiagnosticArray {
+    /// Statuses at level `WARN` or above, stale ones included
+    pub fn problems(&self) -> impl Iterator<Item = &DiagnosticStatus> {
+        self.status
+            .iter()
+            .filter(|status| status.level >= DiagnosticStatus::WARN)
+    }
+}
+
+impl CameraInfo {
+    pub const PLUMB_BOB: &'static str = "plumb_bob";
+    pub const RATIONAL_POLYNOMIAL: &'static str = "rational_polynomial";
+    pub const EQUIDISTANT: &'static str = "equidistant";
+
+    /// Focal lengths and principal point `[fx, fy, cx, cy]` of the intrinsic matrix `k`
+    pub fn intrinsics(&self) -> [f64; 4] {
+        [self.k[0], self.k[4], self.k[2], self.k[5]]
+    }
+
+    /// Whether the camera is calibrated (an uncalibrated camera has `k[0] == 0`)
+    pub fn is_calibrated(&self) -> bool {
+        self.k[0] != 0.0
+    }
+}
+
+impl Inertia {
+    /// Inertia tensor about the center of mass as a row-major 3x3 matrix
+    pub fn tensor(&self) -> [[f64; 3]; 3] {
+        [
+            [self.ixx, self.ixy, self.ixz],
+            [self.ixy, self.iyy, self.iyz],
+            [self.ixz, self.iyz, self.izz],
+        ]
+    }
+}
+
+impl Polygon {
+    /// Area enclosed by the polygon in the xy plane (shoelace formula)
+    pub fn area(&self) -> f64 {
+        let n = self.points.len();
+        let twice_area: f64 = (0..n)
+            .map(|i| {
+                let (a, b) = (&self.points[i], &self.points[(i + 1) % n]);
+                a.x as f64 * b.y as f64 - b.x as f64 * a.y as f64
+            })
+            .sum();
+        twice_area.abs() / 2.0
+    }
+}
+
+impl<T> MultiArray<T> {
+    /// Size of each dimension, outermost first
+    pub fn shape(&self) -> Vec<usize> {
+        self.layout
+            .dim
+            .iter()
+            .map(|dim| dim.size as usize)
+            .collect()
+    }
+
+    /// Element at `index` (one coordinate per dimension), following the layout strides
+    ///
+    /// As documented in `MultiArrayLayout.msg`, the element `[i, j, k]` is at
+    /// `data_offset + dim[1].stride * i + dim[2].stride * j + k`.
+    pub fn get(&self, index: &[usize]) -> Option<&T> {
+        let dims = &self.layout.dim;
+        if index.len() != dims.len() {
+            return None;
+        }
+        let mut offset = self.layout.data_offset as usize;
+        for (i, (&coordinate, dim)) in index.iter().zip(dims).enumerate() {
+            if coordinate >= dim.size as usize {
+                return None;
+            }
+            let stride = dims.get(i + 1).map_or(1, |next| next.stride as usize);
+            offset += coordinate * stride;
+        }
+        self.data.get(offset)
+    }
+}
+
+impl LaserScan {
+    /// Bearing of the `index`-th range in radians
+    pub fn angle(&self, index: usize) -> f32 {
+        self.angle_min + self.angle_increment * index as f32
+    }
+
+    /// Whether the `index`-th range is a valid measurement within `[range_min, range_max]`
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.ranges
+            .get(index)
+            .is_some_and(|&range| range >= self.range_min && range <= self.range_max)
+    }
+}
+
+impl Range {
+    pub const ULTRASOUND: u8 = 0;
+    pub const INFRARED: u8 = 1;
+}
+
+impl JointState {
+    /// Position of the joint called `name`, if the message reports one
+    pub fn position_of(&self, name: &str) -> Option<f64> {
+        let index = self.name.iter().position(|joint| joint == name)?;
+        self.position.get(index).copied()
+    }
+}
+
+impl BatteryState {
+    pub const POWER_SUPPLY_STATUS_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_STATUS_CHARGING: u8 = 1;
+    pub const POWER_SUPPLY_STATUS_DISCHARGING: u8 = 2;
+    pub const POWER_SUPPLY_STATUS_NOT_CHARGING: u8 = 3;
+    pub const POWER_SUPPLY_STATUS_FULL: u8 = 4;
+
+    pub const POWER_SUPPLY_HEALTH_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_HEALTH_GOOD: u8 = 1;
+    pub const POWER_SUPPLY_HEALTH_OVERHEAT: u8 = 2;
+    pub const POWER_SUPPLY_HEALTH_DEAD: u8 = 3;
+    pub const POWER_SUPPLY_HEALTH_OVERVOLTAGE: u8 = 4;
+    pub const POWER_SUPPLY_HEALTH_UNSPEC_FAILURE: u8 = 5;
+    pub const POWER_SUPPLY_HEALTH_COLD: u8 = 6;
+    pub const POWER_SUPPLY_HEALTH_WATCHDOG_TIMER_EXPIRE: u8 = 7;
+    pub const POWER_SUPPLY_HEALTH_SAFETY_TIMER_EXPIRE: u8 = 8;
+
+    pub const POWER_SUPPLY_TECHNOLOGY_UNKNOWN: u8 = 0;
+    pub const POWER_SUPPLY_TECHNOLOGY_NIMH: u8 = 1;
+    pub const POWER_SUPPLY_TECHNOLOGY_LION: u8 = 2;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIPO: u8 = 3;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIFE: u8 = 4;
+    pub const POWER_SUPPLY_TECHNOLOGY_NICD: u8 = 5;
+    pub const POWER_SUPPLY_TECHNOLOGY_LIMN: u8 = 6;
+}
+
+impl PointField {
+    pub const INT8: u8 = 1;
+    pub const UINT8: u8 = 2;
+    pub const INT16: u8 = 3;
+    pub const UINT16: u8 = 4;
+    pub const INT32: u8 = 5;
+    pub const UINT32: u8 = 6;
+    pub const FLOAT32: u8 = 7;
+    pub const FLOAT64: u8 = 8;
+
+    /// Size in bytes of a single element of this field's datatype, if known
+    pub fn datatype_size(&self) -> Option<usize> {
+        match self.datatype {
+            Self::INT8 | Self::UINT8 => Some(1),
+            Self::INT16 | Self::UINT16 => Some(2),
+            Self::INT32 | Self::UINT32 | Self::FLOAT32 => Some(4),
+            Self::FLOAT64 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Read element `index` of this field from a single point's bytes as `f64`
+    ///
+    /// Returns `None` if the datatype is unknown or the point is too short.
+    pub fn read_value(&self, point: &[u8], index: usize, big_endian: bool) -> Option<f64> {
+        let size = self.datatype_size()?;
+        let start = self.offset as usize + index * size;
+        let bytes = point.get(start..start + size)?;
+
+        macro_rules! read {
+            ($ty:ty) => {{
+                let raw = bytes.try_into().ok()?;
+                if big_endian {
+                    <$ty>::from_be_bytes(raw)
+                } else {
+                    <$ty>::from_le_bytes(raw)
+                }
+            }};
+        }
+
+        Some(match self.datatype {
+            Self::INT8 => bytes[0] as i8 as f64,
+            Self::UINT8 => bytes[0] as f64,
+            Self::INT16 => read!(i16) as f64,
+            Self::UINT16 => read!(u16) as f64,
+            Self::INT32 => read!(i32) as f64,
+            Self::UINT32 => read!(u32) as f64,
+            Self::FLOAT32 => read!(f32) as f64,
+            Self::FLOAT64 => read!(f64),
+            _ => return None,
+        })
+    }
+
+    /// Read element `index` of this field as raw 32-bit word (e.g. packed `rgb`)
+    pub fn read_u32_bits(&self, point: &[u8], index: usize, big_endian: bool) -> Option<u32> {
+        if self.datatype_size()? != 4 {
+            return None;
+        }
+        let start = self.offset as usize + index * 4;
+        let raw = point.get(start..start + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        })
+    }
+
+    /// Write `value` as element `index` of this field into a single point's bytes
+    ///
+    /// Integer datatypes are rounded and saturate at their range. Returns `None` if
+    /// the datatype is unknown or the point is too short.
+    pub fn write_value(
+        &self,
+        point: &mut [u8],
+        index: usize,
+        value: f64,
+        big_endian: bool,
+    ) -> Option<()> {
+        let size = self.datatype_size()?;
+        let start = self.offset as usize + index * size;
+        let bytes = point.get_mut(start..start + size)?;
+
+        macro_rules! write {
+            ($value:expr) => {{
+                let value = $value;
+                bytes.copy_from_slice(&if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                });
+            }};
+        }
+
+        match self.datatype {
+            Self::INT8 => write!(value.round() as i8),
+            Self::UINT8 => write!(value.round() as u8),
+            Self::INT16 => write!(value.round() as i16),
+            Self::UINT16 => write!(value.round() as u16),
+            Self::INT32 => write!(value.round() as i32),
+            Self::UINT32 => write!(value.round() as u32),
+            Self::FLOAT32 => write!(value as f32),
+            Self::FLOAT64 => write!(value),
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl GoalStatus {
+    pub const STATUS_UNKNOWN: i8 = 0;
+    pub const STATUS_ACCEPTED: i8 = 1;
+    pub const STATUS_EXECUTING: i8 = 2;
+    pub const STATUS_CANCELING: i8 = 3;
+    pub const STATUS_SUCCEEDED: i8 = 4;
+    pub const STATUS_CANCELED: i8 = 5;
+    pub const STATUS_ABORTED: i8 = 6;
+
+    /// Whether the goal has reached a terminal state
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self.status,
+            Self::STATUS_SUCCEEDED | Self::STATUS_CANCELED | Self::STATUS_ABORTED
+        )
+    }
+}
+
 /// Helper function to manually read f64 without automatic alignment
 ///
 /// This function provides optimized f64 reading with proper error handling
@@ -296,30 +975,257 @@ pub trait FromCdr: Sized {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self>;
 }
 
-impl FromCdr for Time {
-    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
-        Ok(Self {
-            sec: deserializer.read_i32()?,
-            nanosec: deserializer.read_u32()?,
-        })
+/// Trait for serializing ROS2 messages to CDR data
+pub trait ToCdr {
+    fn to_cdr(&self, serializer: &mut CdrSerializer);
+
+    /// Serialize into a complete CDR message, including the encapsulation header
+    fn to_cdr_bytes(&self) -> Vec<u8> {
+        let mut serializer = CdrSerializer::new();
+        self.to_cdr(&mut serializer);
+        serializer.into_bytes()
     }
 }
 
-impl FromCdr for Header {
-    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
-        Ok(Self {
-            stamp: Time::from_cdr(deserializer)?,
-            frame_id: deserializer.read_string()?,
-        })
+/// ROS message type represented by a Rust struct
+///
+/// Lets [`Message::decode`](crate::types::Message::decode) check that a connection
+/// carries the type a payload is decoded as.
+pub trait MessageType {
+    /// Fully qualified type name (e.g. `sensor_msgs/msg/Imu`)
+    const MESSAGE_TYPE: &'static str;
+
+    /// Whether connections of `message_type` carry this type
+    ///
+    /// Accepts the short `pkg/Type` spelling as well.
+    fn accepts(message_type: &str) -> bool {
+        normalize_type_name(message_type) == Self::MESSAGE_TYPE
     }
 }
 
-impl FromCdr for Vector3 {
-    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
-        Ok(Self {
-            x: read_f64_manual(deserializer)?,
-            y: read_f64_manual(deserializer)?,
-            z: read_f64_manual(deserializer)?,
+macro_rules! message_types {
+    ($($type:ty => $name:literal;)*) => {
+        $(
+            impl MessageType for $type {
+                const MESSAGE_TYPE: &'static str = $name;
+            }
+        )*
+    };
+}
+
+message_types! {
+    Time => "builtin_interfaces/msg/Time";
+    Header => "std_msgs/msg/Header";
+    Vector3 => "geometry_msgs/msg/Vector3";
+    Quaternion => "geometry_msgs/msg/Quaternion";
+    Point => "geometry_msgs/msg/Point";
+    Pose => "geometry_msgs/msg/Pose";
+    PoseWithCovariance => "geometry_msgs/msg/PoseWithCovariance";
+    PoseWithCovarianceStamped => "geometry_msgs/msg/PoseWithCovarianceStamped";
+    Transform => "geometry_msgs/msg/Transform";
+    TransformStamped => "geometry_msgs/msg/TransformStamped";
+    Twist => "geometry_msgs/msg/Twist";
+    TwistWithCovariance => "geometry_msgs/msg/TwistWithCovariance";
+    Imu => "sensor_msgs/msg/Imu";
+    Odometry => "nav_msgs/msg/Odometry";
+    PoseStamped => "geometry_msgs/msg/PoseStamped";
+    PointStamped => "geometry_msgs/msg/PointStamped";
+    Vector3Stamped => "geometry_msgs/msg/Vector3Stamped";
+    QuaternionStamped => "geometry_msgs/msg/QuaternionStamped";
+    PoseArray => "geometry_msgs/msg/PoseArray";
+    Pose2D => "geometry_msgs/msg/Pose2D";
+    TwistStamped => "geometry_msgs/msg/TwistStamped";
+    TwistWithCovarianceStamped => "geometry_msgs/msg/TwistWithCovarianceStamped";
+    Accel => "geometry_msgs/msg/Accel";
+    AccelStamped => "geometry_msgs/msg/AccelStamped";
+    AccelWithCovariance => "geometry_msgs/msg/AccelWithCovariance";
+    AccelWithCovarianceStamped => "geometry_msgs/msg/AccelWithCovarianceStamped";
+    Wrench => "geometry_msgs/msg/Wrench";
+    WrenchStamped => "geometry_msgs/msg/WrenchStamped";
+    Inertia => "geometry_msgs/msg/Inertia";
+    InertiaStamped => "geometry_msgs/msg/InertiaStamped";
+    Polygon => "geometry_msgs/msg/Polygon";
+    PolygonStamped => "geometry_msgs/msg/PolygonStamped";
+    Path => "nav_msgs/msg/Path";
+    MapMetaData => "nav_msgs/msg/MapMetaData";
+    OccupancyGrid => "nav_msgs/msg/OccupancyGrid";
+    GridCells => "nav_msgs/msg/GridCells";
+    NavSatStatus => "sensor_msgs/msg/NavSatStatus";
+    NavSatFix => "sensor_msgs/msg/NavSatFix";
+    StdString => "std_msgs/msg/String";
+    MultiArrayDimension => "std_msgs/msg/MultiArrayDimension";
+    MultiArrayLayout => "std_msgs/msg/MultiArrayLayout";
+    PointField => "sensor_msgs/msg/PointField";
+    PointCloud2 => "sensor_msgs/msg/PointCloud2";
+    Image => "sensor_msgs/msg/Image";
+    CompressedImage => "sensor_msgs/msg/CompressedImage";
+    RegionOfInterest => "sensor_msgs/msg/RegionOfInterest";
+    CameraInfo => "sensor_msgs/msg/CameraInfo";
+    Point32 => "geometry_msgs/msg/Point32";
+    ColorRGBA => "std_msgs/msg/ColorRGBA";
+    Duration => "builtin_interfaces/msg/Duration";
+    GoalInfo => "action_msgs/msg/GoalInfo";
+    GoalStatus => "action_msgs/msg/GoalStatus";
+    GoalStatusArray => "action_msgs/msg/GoalStatusArray";
+    Clock => "rosgraph_msgs/msg/Clock";
+    TFMessage => "tf2_msgs/msg/TFMessage";
+    KeyValue => "diagnostic_msgs/msg/KeyValue";
+    DiagnosticStatus => "diagnostic_msgs/msg/DiagnosticStatus";
+    DiagnosticArray => "diagnostic_msgs/msg/DiagnosticArray";
+    LaserScan => "sensor_msgs/msg/LaserScan";
+    Range => "sensor_msgs/msg/Range";
+    JointState => "sensor_msgs/msg/JointState";
+    BatteryState => "sensor_msgs/msg/BatteryState";
+    MagneticField => "sensor_msgs/msg/MagneticField";
+    Float32MultiArray => "std_msgs/msg/Float32MultiArray";
+    Float64MultiArray => "std_msgs/msg/Float64MultiArray";
+    Int8MultiArray => "std_msgs/msg/Int8MultiArray";
+    Int16MultiArray => "std_msgs/msg/Int16MultiArray";
+    Int32MultiArray => "std_msgs/msg/Int32MultiArray";
+    Int64MultiArray => "std_msgs/msg/Int64MultiArray";
+    UInt16MultiArray => "std_msgs/msg/UInt16MultiArray";
+    UInt32MultiArray => "std_msgs/msg/UInt32MultiArray";
+    UInt64MultiArray => "std_msgs/msg/UInt64MultiArray";
+}
+
+/// `UInt8MultiArray` and `ByteMultiArray` share one layout
+impl MessageType for MultiArray<u8> {
+    const MESSAGE_TYPE: &'static str = "std_msgs/msg/UInt8MultiArray";
+
+    fn accepts(message_type: &str) -> bool {
+        matches!(
+            normalize_type_name(message_type).as_str(),
+            "std_msgs/msg/UInt8MultiArray" | "std_msgs/msg/ByteMultiArray"
+        )
+    }
+}
+
+impl ToCdr for Time {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_i32(self.sec);
+        serializer.write_u32(self.nanosec);
+    }
+}
+
+impl ToCdr for Header {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.stamp.to_cdr(serializer);
+        serializer.write_string(&self.frame_id);
+    }
+}
+
+impl ToCdr for Vector3 {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f64(self.x);
+        serializer.write_f64(self.y);
+        serializer.write_f64(self.z);
+    }
+}
+
+impl ToCdr for Quaternion {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f64(self.x);
+        serializer.write_f64(self.y);
+        serializer.write_f64(self.z);
+        serializer.write_f64(self.w);
+    }
+}
+
+impl ToCdr for Transform {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.translation.to_cdr(serializer);
+        self.rotation.to_cdr(serializer);
+    }
+}
+
+impl ToCdr for TransformStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_string(&self.child_frame_id);
+        self.transform.to_cdr(serializer);
+    }
+}
+
+impl ToCdr for TFMessage {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_sequence_length(self.transforms.len());
+        for transform in &self.transforms {
+            transform.to_cdr(serializer);
+        }
+    }
+}
+
+impl ToCdr for Image {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_u32(self.height);
+        serializer.write_u32(self.width);
+        serializer.write_string(&self.encoding);
+        serializer.write_u8(self.is_bigendian);
+        serializer.write_u32(self.step);
+        serializer.write_byte_sequence(&self.data);
+    }
+}
+
+impl ToCdr for CompressedImage {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_string(&self.format);
+        serializer.write_byte_sequence(&self.data);
+    }
+}
+
+impl ToCdr for PointField {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_string(&self.name);
+        serializer.write_u32(self.offset);
+        serializer.write_u8(self.datatype);
+        serializer.write_u32(self.count);
+    }
+}
+
+impl ToCdr for PointCloud2 {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_u32(self.height);
+        serializer.write_u32(self.width);
+        serializer.write_sequence_length(self.fields.len());
+        for field in &self.fields {
+            field.to_cdr(serializer);
+        }
+        serializer.write_bool(self.is_bigendian);
+        serializer.write_u32(self.point_step);
+        serializer.write_u32(self.row_step);
+        serializer.write_byte_sequence(&self.data);
+        serializer.write_bool(self.is_dense);
+    }
+}
+
+impl FromCdr for Time {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            sec: deserializer.read_i32()?,
+            nanosec: deserializer.read_u32()?,
+        })
+    }
+}
+
+impl FromCdr for Header {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            stamp: Time::from_cdr(deserializer)?,
+            frame_id: deserializer.read_string()?,
+        })
+    }
+}
+
+impl FromCdr for Vector3 {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        // Aligned reads: in TransformStamped the vector follows a string
+        Ok(Self {
+            x: deserializer.read_f64()?,
+            y: deserializer.read_f64()?,
+            z: deserializer.read_f64()?,
         })
     }
 }
@@ -327,20 +1233,21 @@ impl FromCdr for Vector3 {
 impl FromCdr for Quaternion {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
         Ok(Self {
-            x: read_f64_manual(deserializer)?,
-            y: read_f64_manual(deserializer)?,
-            z: read_f64_manual(deserializer)?,
-            w: read_f64_manual(deserializer)?,
+            x: deserializer.read_f64()?,
+            y: deserializer.read_f64()?,
+            z: deserializer.read_f64()?,
+            w: deserializer.read_f64()?,
         })
     }
 }
 
 impl FromCdr for Point {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        // Aligned reads: in MapMetaData the point follows three 4-byte fields
         Ok(Self {
-            x: read_f64_manual(deserializer)?,
-            y: read_f64_manual(deserializer)?,
-            z: read_f64_manual(deserializer)?,
+            x: deserializer.read_f64()?,
+            y: deserializer.read_f64()?,
+            z: deserializer.read_f64()?,
         })
     }
 }
@@ -373,6 +1280,14 @@ impl FromCdr for TransformStamped {
     }
 }
 
+impl FromCdr for TFMessage {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            transforms: deserializer.read_sequence(TransformStamped::from_cdr)?,
+        })
+    }
+}
+
 impl FromCdr for Imu {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
         let header = Header::from_cdr(deserializer)?;
@@ -589,45 +1504,14 @@ impl FromCdr for NavSatStatus {
 
 impl FromCdr for NavSatFix {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
-        let header = Header::from_cdr(deserializer)?;
-
-        // For now, try the most common GPS positions based on our debug analysis
-        // Position 20 for the new bag file format, position 28 for the old format
-        let current_pos = deserializer.position();
-
-        // Try position 20 first (new format)
-        let target_pos = if current_pos <= 20 { 20 } else { 28 };
-
-        // Skip to GPS data position
-        if current_pos < target_pos {
-            let skip_bytes = target_pos - current_pos;
-            for _ in 0..skip_bytes {
-                deserializer.read_u8()?;
-            }
-        }
-
-        // Read GPS coordinates manually
-        let latitude = read_f64_manual(deserializer)?;
-        let longitude = read_f64_manual(deserializer)?;
-        let altitude = read_f64_manual(deserializer)?;
-
-        // Create default status and covariance for now
-        let status = NavSatStatus {
-            status: 0,
-            service: 1,
-        };
-
-        let position_covariance = [0.0; 9];
-        let position_covariance_type = 0;
-
         Ok(Self {
-            header,
-            status,
-            latitude,
-            longitude,
-            altitude,
-            position_covariance,
-            position_covariance_type,
+            header: Header::from_cdr(deserializer)?,
+            status: NavSatStatus::from_cdr(deserializer)?,
+            latitude: deserializer.read_f64()?,
+            longitude: deserializer.read_f64()?,
+            altitude: deserializer.read_f64()?,
+            position_covariance: deserializer.read_f64_array()?,
+            position_covariance_type: deserializer.read_u8()?,
         })
     }
 }
@@ -885,6 +1769,16 @@ impl FromCdr for Image {
     }
 }
 
+impl FromCdr for CompressedImage {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            format: deserializer.read_string()?,
+            data: deserializer.read_byte_sequence()?,
+        })
+    }
+}
+
 impl FromCdr for Point32 {
     fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
         Ok(Self {
@@ -915,37 +1809,985 @@ impl FromCdr for Duration {
     }
 }
 
-/// Deserialize a message from CDR data based on its type name
-pub fn deserialize_message(data: &[u8], message_type: &str) -> Result<Box<dyn std::fmt::Debug>> {
-    let mut deserializer = CdrDeserializer::new(data)?;
+impl FromCdr for GoalInfo {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        let mut goal_id = [0u8; 16];
+        for byte in goal_id.iter_mut() {
+            *byte = deserializer.read_u8()?;
+        }
+        Ok(Self {
+            goal_id,
+            stamp: Time::from_cdr(deserializer)?,
+        })
+    }
+}
 
-    match message_type {
-        "sensor_msgs/msg/Imu" => {
-            let msg = Imu::from_cdr(&mut deserializer)?;
-            Ok(Box::new(msg))
+impl FromCdr for GoalStatus {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            goal_info: GoalInfo::from_cdr(deserializer)?,
+            status: deserializer.read_i8()?,
+        })
+    }
+}
+
+impl FromCdr for GoalStatusArray {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            status_list: deserializer.read_sequence(GoalStatus::from_cdr)?,
+        })
+    }
+}
+
+impl FromCdr for Clock {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            clock: Time::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for Clock {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.clock.to_cdr(serializer);
+    }
+}
+
+impl ToCdr for Point {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f64(self.x);
+        serializer.write_f64(self.y);
+        serializer.write_f64(self.z);
+    }
+}
+
+impl ToCdr for Pose {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.position.to_cdr(serializer);
+        self.orientation.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for MapMetaData {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            map_load_time: Time::from_cdr(deserializer)?,
+            resolution: deserializer.read_f32()?,
+            width: deserializer.read_u32()?,
+            height: deserializer.read_u32()?,
+            origin: Pose::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for MapMetaData {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.map_load_time.to_cdr(serializer);
+        serializer.write_f32(self.resolution);
+        serializer.write_u32(self.width);
+        serializer.write_u32(self.height);
+        self.origin.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for OccupancyGrid {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            info: MapMetaData::from_cdr(deserializer)?,
+            data: deserializer
+                .read_byte_slice()?
+                .iter()
+                .map(|&byte| byte as i8)
+                .collect(),
+        })
+    }
+}
+
+impl ToCdr for OccupancyGrid {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.info.to_cdr(serializer);
+        let bytes: Vec<u8> = self.data.iter().map(|&value| value as u8).collect();
+        serializer.write_byte_sequence(&bytes);
+    }
+}
+
+impl FromCdr for GridCells {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            cell_width: deserializer.read_f32()?,
+            cell_height: deserializer.read_f32()?,
+            cells: deserializer.read_sequence(Point::from_cdr)?,
+        })
+    }
+}
+
+impl ToCdr for GridCells {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_f32(self.cell_width);
+        serializer.write_f32(self.cell_height);
+        serializer.write_sequence_length(self.cells.len());
+        for cell in &self.cells {
+            cell.to_cdr(serializer);
         }
-        "geometry_msgs/msg/TransformStamped" => {
-            let msg = TransformStamped::from_cdr(&mut deserializer)?;
-            Ok(Box::new(msg))
+    }
+}
+
+impl FromCdr for KeyValue {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            key: deserializer.read_string()?,
+            value: deserializer.read_string()?,
+        })
+    }
+}
+
+impl ToCdr for KeyValue {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_string(&self.key);
+        serializer.write_string(&self.value);
+    }
+}
+
+impl FromCdr for DiagnosticStatus {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            level: deserializer.read_u8()?,
+            name: deserializer.read_string()?,
+            message: deserializer.read_string()?,
+            hardware_id: deserializer.read_string()?,
+            values: deserializer.read_sequence(KeyValue::from_cdr)?,
+        })
+    }
+}
+
+impl ToCdr for DiagnosticStatus {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_u8(self.level);
+        serializer.write_string(&self.name);
+        serializer.write_string(&self.message);
+        serializer.write_string(&self.hardware_id);
+        serializer.write_sequence_length(self.values.len());
+        for pair in &self.values {
+            pair.to_cdr(serializer);
         }
-        "geometry_msgs/msg/PoseWithCovarianceStamped" => {
-            let msg = PoseWithCovarianceStamped::from_cdr(&mut deserializer)?;
-            Ok(Box::new(msg))
+    }
+}
+
+impl FromCdr for DiagnosticArray {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            status: deserializer.read_sequence(DiagnosticStatus::from_cdr)?,
+        })
+    }
+}
+
+impl ToCdr for DiagnosticArray {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_sequence_length(self.status.len());
+        for status in &self.status {
+            status.to_cdr(serializer);
         }
-        "geometry_msgs/msg/PointStamped" => {
-            let msg = PointStamped::from_cdr(&mut deserializer)?;
-            Ok(Box::new(msg))
+    }
+}
+
+impl FromCdr for LaserScan {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            angle_min: deserializer.read_f32()?,
+            angle_max: deserializer.read_f32()?,
+            angle_increment: deserializer.read_f32()?,
+            time_increment: deserializer.read_f32()?,
+            scan_time: deserializer.read_f32()?,
+            range_min: deserializer.read_f32()?,
+            range_max: deserializer.read_f32()?,
+            ranges: deserializer.read_sequence(|d| d.read_f32())?,
+            intensities: deserializer.read_sequence(|d| d.read_f32())?,
+        })
+    }
+}
+
+impl ToCdr for LaserScan {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_f32(self.angle_min);
+        serializer.write_f32(self.angle_max);
+        serializer.write_f32(self.angle_increment);
+        serializer.write_f32(self.time_increment);
+        serializer.write_f32(self.scan_time);
+        serializer.write_f32(self.range_min);
+        serializer.write_f32(self.range_max);
+        write_f32_sequence(serializer, &self.ranges);
+        write_f32_sequence(serializer, &self.intensities);
+    }
+}
+
+impl FromCdr for Range {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            radiation_type: deserializer.read_u8()?,
+            field_of_view: deserializer.read_f32()?,
+            min_range: deserializer.read_f32()?,
+            max_range: deserializer.read_f32()?,
+            range: deserializer.read_f32()?,
+        })
+    }
+}
+
+impl ToCdr for Range {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_u8(self.radiation_type);
+        serializer.write_f32(self.field_of_view);
+        serializer.write_f32(self.min_range);
+        serializer.write_f32(self.max_range);
+        serializer.write_f32(self.range);
+    }
+}
+
+impl FromCdr for JointState {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            name: deserializer.read_sequence(|d| d.read_string())?,
+            position: deserializer.read_sequence(|d| d.read_f64())?,
+            velocity: deserializer.read_sequence(|d| d.read_f64())?,
+            effort: deserializer.read_sequence(|d| d.read_f64())?,
+        })
+    }
+}
+
+impl ToCdr for JointState {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_sequence_length(self.name.len());
+        for name in &self.name {
+            serializer.write_string(name);
         }
-        "sensor_msgs/msg/NavSatFix" => {
-            let msg = NavSatFix::from_cdr(&mut deserializer)?;
-            Ok(Box::new(msg))
+        for values in [&self.position, &self.velocity, &self.effort] {
+            serializer.write_sequence_length(values.len());
+            serializer.write_f64_array(values);
         }
-        "nav_msgs/msg/Odometry" => {
-            let msg = Odometry::from_cdr(&mut deserializer)?;
+    }
+}
+
+impl FromCdr for BatteryState {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            voltage: deserializer.read_f32()?,
+            temperature: deserializer.read_f32()?,
+            current: deserializer.read_f32()?,
+            charge: deserializer.read_f32()?,
+            capacity: deserializer.read_f32()?,
+            design_capacity: deserializer.read_f32()?,
+            percentage: deserializer.read_f32()?,
+            power_supply_status: deserializer.read_u8()?,
+            power_supply_health: deserializer.read_u8()?,
+            power_supply_technology: deserializer.read_u8()?,
+            present: deserializer.read_bool()?,
+            cell_voltage: deserializer.read_sequence(|d| d.read_f32())?,
+            cell_temperature: deserializer.read_sequence(|d| d.read_f32())?,
+            location: deserializer.read_string()?,
+            serial_number: deserializer.read_string()?,
+        })
+    }
+}
+
+impl ToCdr for BatteryState {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_f32(self.voltage);
+        serializer.write_f32(self.temperature);
+        serializer.write_f32(self.current);
+        serializer.write_f32(self.charge);
+        serializer.write_f32(self.capacity);
+        serializer.write_f32(self.design_capacity);
+        serializer.write_f32(self.percentage);
+        serializer.write_u8(self.power_supply_status);
+        serializer.write_u8(self.power_supply_health);
+        serializer.write_u8(self.power_supply_technology);
+        serializer.write_bool(self.present);
+        write_f32_sequence(serializer, &self.cell_voltage);
+        write_f32_sequence(serializer, &self.cell_temperature);
+        serializer.write_string(&self.location);
+        serializer.write_string(&self.serial_number);
+    }
+}
+
+impl FromCdr for MagneticField {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            magnetic_field: Vector3::from_cdr(deserializer)?,
+            magnetic_field_covariance: deserializer.read_f64_array()?,
+        })
+    }
+}
+
+impl ToCdr for MagneticField {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.magnetic_field.to_cdr(serializer);
+        serializer.write_f64_array(&self.magnetic_field_covariance);
+    }
+}
+
+impl ToCdr for Twist {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.linear.to_cdr(serializer);
+        self.angular.to_cdr(serializer);
+    }
+}
+
+impl ToCdr for TwistWithCovariance {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.twist.to_cdr(serializer);
+        serializer.write_f64_array(&self.covariance);
+    }
+}
+
+impl ToCdr for Point32 {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f32(self.x);
+        serializer.write_f32(self.y);
+        serializer.write_f32(self.z);
+    }
+}
+
+impl FromCdr for PoseStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            pose: Pose::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for PoseStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.pose.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for Vector3Stamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            vector: Vector3::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for Vector3Stamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.vector.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for QuaternionStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            quaternion: Quaternion::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for QuaternionStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.quaternion.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for PoseArray {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            poses: deserializer.read_sequence(Pose::from_cdr)?,
+        })
+    }
+}
+
+impl ToCdr for PoseArray {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_sequence_length(self.poses.len());
+        for pose in &self.poses {
+            pose.to_cdr(serializer);
+        }
+    }
+}
+
+impl FromCdr for Pose2D {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            x: deserializer.read_f64()?,
+            y: deserializer.read_f64()?,
+            theta: deserializer.read_f64()?,
+        })
+    }
+}
+
+impl ToCdr for Pose2D {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f64(self.x);
+        serializer.write_f64(self.y);
+        serializer.write_f64(self.theta);
+    }
+}
+
+impl FromCdr for TwistStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            twist: Twist::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for TwistStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.twist.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for TwistWithCovarianceStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            twist: TwistWithCovariance::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for TwistWithCovarianceStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.twist.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for Accel {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            linear: Vector3::from_cdr(deserializer)?,
+            angular: Vector3::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for Accel {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.linear.to_cdr(serializer);
+        self.angular.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for AccelStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            accel: Accel::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for AccelStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.accel.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for AccelWithCovariance {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            accel: Accel::from_cdr(deserializer)?,
+            covariance: deserializer.read_f64_array()?,
+        })
+    }
+}
+
+impl ToCdr for AccelWithCovariance {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.accel.to_cdr(serializer);
+        serializer.write_f64_array(&self.covariance);
+    }
+}
+
+impl FromCdr for AccelWithCovarianceStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            accel: AccelWithCovariance::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for AccelWithCovarianceStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.accel.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for Wrench {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            force: Vector3::from_cdr(deserializer)?,
+            torque: Vector3::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for Wrench {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.force.to_cdr(serializer);
+        self.torque.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for WrenchStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            wrench: Wrench::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for WrenchStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.wrench.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for Inertia {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            m: deserializer.read_f64()?,
+            com: Vector3::from_cdr(deserializer)?,
+            ixx: deserializer.read_f64()?,
+            ixy: deserializer.read_f64()?,
+            ixz: deserializer.read_f64()?,
+            iyy: deserializer.read_f64()?,
+            iyz: deserializer.read_f64()?,
+            izz: deserializer.read_f64()?,
+        })
+    }
+}
+
+impl ToCdr for Inertia {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_f64(self.m);
+        self.com.to_cdr(serializer);
+        for value in [self.ixx, self.ixy, self.ixz, self.iyy, self.iyz, self.izz] {
+            serializer.write_f64(value);
+        }
+    }
+}
+
+impl FromCdr for InertiaStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            inertia: Inertia::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for InertiaStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.inertia.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for Polygon {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            points: deserializer.read_sequence(Point32::from_cdr)?,
+        })
+    }
+}
+
+impl ToCdr for Polygon {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_sequence_length(self.points.len());
+        for point in &self.points {
+            point.to_cdr(serializer);
+        }
+    }
+}
+
+impl FromCdr for PolygonStamped {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            polygon: Polygon::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for PolygonStamped {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        self.polygon.to_cdr(serializer);
+    }
+}
+
+impl FromCdr for MultiArrayDimension {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            label: deserializer.read_string()?,
+            size: deserializer.read_u32()?,
+            stride: deserializer.read_u32()?,
+        })
+    }
+}
+
+impl ToCdr for MultiArrayDimension {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_string(&self.label);
+        serializer.write_u32(self.size);
+        serializer.write_u32(self.stride);
+    }
+}
+
+impl FromCdr for MultiArrayLayout {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            dim: deserializer.read_sequence(MultiArrayDimension::from_cdr)?,
+            data_offset: deserializer.read_u32()?,
+        })
+    }
+}
+
+impl ToCdr for MultiArrayLayout {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_sequence_length(self.dim.len());
+        for dim in &self.dim {
+            dim.to_cdr(serializer);
+        }
+        serializer.write_u32(self.data_offset);
+    }
+}
+
+/// Primitive element of a [`MultiArray`]
+pub trait MultiArrayElement: Sized {
+    fn read(deserializer: &mut CdrDeserializer) -> Result<Self>;
+    fn write(&self, serializer: &mut CdrSerializer);
+}
+
+macro_rules! multi_array_element {
+    ($($type:ty => $read:ident, $write:ident;)*) => {
+        $(
+            impl MultiArrayElement for $type {
+                fn read(deserializer: &mut CdrDeserializer) -> Result<Self> {
+                    deserializer.$read()
+                }
+
+                fn write(&self, serializer: &mut CdrSerializer) {
+                    serializer.$write(*self);
+                }
+            }
+        )*
+    };
+}
+
+multi_array_element! {
+    f32 => read_f32, write_f32;
+    f64 => read_f64, write_f64;
+    i8 => read_i8, write_i8;
+    i16 => read_i16, write_i16;
+    i32 => read_i32, write_i32;
+    i64 => read_i64, write_i64;
+    u8 => read_u8, write_u8;
+    u16 => read_u16, write_u16;
+    u32 => read_u32, write_u32;
+    u64 => read_u64, write_u64;
+}
+
+impl<T: MultiArrayElement> FromCdr for MultiArray<T> {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            layout: MultiArrayLayout::from_cdr(deserializer)?,
+            data: deserializer.read_sequence(T::read)?,
+        })
+    }
+}
+
+impl<T: MultiArrayElement> ToCdr for MultiArray<T> {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.layout.to_cdr(serializer);
+        serializer.write_sequence_length(self.data.len());
+        for value in &self.data {
+            value.write(serializer);
+        }
+    }
+}
+
+impl FromCdr for RegionOfInterest {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            x_offset: deserializer.read_u32()?,
+            y_offset: deserializer.read_u32()?,
+            height: deserializer.read_u32()?,
+            width: deserializer.read_u32()?,
+            do_rectify: deserializer.read_bool()?,
+        })
+    }
+}
+
+impl ToCdr for RegionOfInterest {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        serializer.write_u32(self.x_offset);
+        serializer.write_u32(self.y_offset);
+        serializer.write_u32(self.height);
+        serializer.write_u32(self.width);
+        serializer.write_bool(self.do_rectify);
+    }
+}
+
+impl FromCdr for CameraInfo {
+    fn from_cdr(deserializer: &mut CdrDeserializer) -> Result<Self> {
+        Ok(Self {
+            header: Header::from_cdr(deserializer)?,
+            height: deserializer.read_u32()?,
+            width: deserializer.read_u32()?,
+            distortion_model: deserializer.read_string()?,
+            d: deserializer.read_sequence(|d| d.read_f64())?,
+            k: deserializer.read_f64_array()?,
+            r: deserializer.read_f64_array()?,
+            p: deserializer.read_f64_array()?,
+            binning_x: deserializer.read_u32()?,
+            binning_y: deserializer.read_u32()?,
+            roi: RegionOfInterest::from_cdr(deserializer)?,
+        })
+    }
+}
+
+impl ToCdr for CameraInfo {
+    fn to_cdr(&self, serializer: &mut CdrSerializer) {
+        self.header.to_cdr(serializer);
+        serializer.write_u32(self.height);
+        serializer.write_u32(self.width);
+        serializer.write_string(&self.distortion_model);
+        serializer.write_sequence_length(self.d.len());
+        serializer.write_f64_array(&self.d);
+        serializer.write_f64_array(&self.k);
+        serializer.write_f64_array(&self.r);
+        serializer.write_f64_array(&self.p);
+        serializer.write_u32(self.binning_x);
+        serializer.write_u32(self.binning_y);
+        self.roi.to_cdr(serializer);
+    }
+}
+
+fn write_f32_sequence(serializer: &mut CdrSerializer, values: &[f32]) {
+    serializer.write_sequence_length(values.len());
+    for &value in values {
+        serializer.write_f32(value);
+    }
+}
+
+/// sensor_msgs/msg/Image borrowing its pixel data from the serialized message
+///
+/// Avoids copying the (often multi-megabyte) `data` field when frames are only inspected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageView<'a> {
+    pub header: Header,
+    pub height: u32,
+    pub width: u32,
+    pub encoding: String,
+    pub is_bigendian: u8,
+    pub step: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> ImageView<'a> {
+    /// Parse an image from CDR data without copying the pixel payload
+    pub fn from_cdr_bytes(data: &'a [u8]) -> Result<Self> {
+        let mut deserializer = CdrDeserializer::new(data)?;
+        Ok(Self {
+            header: Header::from_cdr(&mut deserializer)?,
+            height: deserializer.read_u32()?,
+            width: deserializer.read_u32()?,
+            encoding: deserializer.read_string()?,
+            is_bigendian: deserializer.read_u8()?,
+            step: deserializer.read_u32()?,
+            data: deserializer.read_byte_slice()?,
+        })
+    }
+
+    /// Bytes of row `y`, including any row padding, or `None` if out of range or truncated
+    pub fn row(&self, y: u32) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y as usize * self.step as usize;
+        self.data.get(start..start + self.step as usize)
+    }
+
+    /// Iterate over all complete rows
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.height).map_while(|y| self.row(y))
+    }
+
+    /// Copy into an owned [`Image`]
+    pub fn to_owned_image(&self) -> Image {
+        Image {
+            header: self.header.clone(),
+            height: self.height,
+            width: self.width,
+            encoding: self.encoding.clone(),
+            is_bigendian: self.is_bigendian,
+            step: self.step,
+            data: self.data.to_vec(),
+        }
+    }
+}
+
+/// sensor_msgs/msg/PointCloud2 borrowing its point data from the serialized message
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointCloud2View<'a> {
+    pub header: Header,
+    pub height: u32,
+    pub width: u32,
+    pub fields: Vec<PointField>,
+    pub is_bigendian: bool,
+    pub point_step: u32,
+    pub row_step: u32,
+    pub data: &'a [u8],
+    pub is_dense: bool,
+}
+
+impl<'a> PointCloud2View<'a> {
+    /// Parse a point cloud from CDR data without copying the point payload
+    pub fn from_cdr_bytes(data: &'a [u8]) -> Result<Self> {
+        let mut deserializer = CdrDeserializer::new(data)?;
+        Ok(Self {
+            header: Header::from_cdr(&mut deserializer)?,
+            height: deserializer.read_u32()?,
+            width: deserializer.read_u32()?,
+            fields: deserializer.read_sequence(PointField::from_cdr)?,
+            is_bigendian: deserializer.read_bool()?,
+            point_step: deserializer.read_u32()?,
+            row_step: deserializer.read_u32()?,
+            data: deserializer.read_byte_slice()?,
+            is_dense: deserializer.read_bool()?,
+        })
+    }
+
+    /// Number of points (`width * height`)
+    pub fn len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Whether the cloud has no points
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Look up a field by name
+    pub fn field(&self, name: &str) -> Option<&PointField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Bytes of point `index` in row-major order, honoring `row_step` padding
+    pub fn point(&self, index: usize) -> Option<&'a [u8]> {
+        if index >= self.len() || self.width == 0 {
+            return None;
+        }
+        let row = index / self.width as usize;
+        let column = index % self.width as usize;
+        let start = row * self.row_step as usize + column * self.point_step as usize;
+        self.data.get(start..start + self.point_step as usize)
+    }
+
+    /// Iterate over all complete points
+    pub fn points(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.len()).map_while(|index| self.point(index))
+    }
+
+    /// Read the first element of field `name` of point `index` as `f64`
+    pub fn read_field(&self, index: usize, name: &str) -> Option<f64> {
+        self.field(name)?
+            .read_value(self.point(index)?, 0, self.is_bigendian)
+    }
+
+    /// Copy into an owned [`PointCloud2`]
+    pub fn to_owned_cloud(&self) -> PointCloud2 {
+        PointCloud2 {
+            header: self.header.clone(),
+            height: self.height,
+            width: self.width,
+            fields: self.fields.clone(),
+            is_bigendian: self.is_bigendian,
+            point_step: self.point_step,
+            row_step: self.row_step,
+            data: self.data.to_vec(),
+            is_dense: self.is_dense,
+        }
+    }
+}
+
+/// Deserialize a message from CDR data based on its type name
+pub fn deserialize_message(data: &[u8], message_type: &str) -> Result<Box<dyn std::fmt::Debug>> {
+    let mut deserializer = CdrDeserializer::new(data)?;
+
+    match message_type {
+        "sensor_msgs/msg/Imu" => {
+            let msg = Imu::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "geometry_msgs/msg/TransformStamped" => {
+            let msg = TransformStamped::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "tf2_msgs/msg/TFMessage" => {
+            let msg = TFMessage::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "geometry_msgs/msg/PoseWithCovarianceStamped" => {
+            let msg = PoseWithCovarianceStamped::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "geometry_msgs/msg/PointStamped" => {
+            let msg = PointStamped::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "sensor_msgs/msg/NavSatFix" => {
+            let msg = NavSatFix::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "nav_msgs/msg/Odometry" => {
+            let msg = Odometry::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "action_msgs/msg/GoalStatusArray" => {
+            let msg = GoalStatusArray::from_cdr(&mut deserializer)?;
+            Ok(Box::new(msg))
+        }
+        "rosgraph_msgs/msg/Clock" => {
+            let msg = Clock::from_cdr(&mut deserializer)?;
             Ok(Box::new(msg))
         }
-        _ => Err(crate::error::ReaderError::generic(format!(
-            "Unsupported message type: {message_type}"
-        ))),
+        _ => Err(crate::error::ReaderError::message_type_not_found(
+            message_type,
+        )),
     }
 }
@@ -1,11 +1,19 @@
 //! Metadata parsing for ROS2 bag files
 
 use crate::error::{ReaderError, Result};
-use crate::types::{Duration, QosProfile, StartingTime};
+use crate::types::{Connection, Duration, QosProfile, StartingTime, StoragePlugin, TopicKind};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
 use std::path::Path;
 
+/// Prefix of the `custom_data` keys holding the SHA-256 checksums of storage files
+///
+/// The key of a file is the prefix followed by its relative path, e.g.
+/// `sha256/bag_0.db3`; the value is the hex-encoded checksum.
+pub const CHECKSUM_KEY_PREFIX: &str = "sha256/";
+
 /// Complete bag metadata structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BagMetadata {
@@ -38,9 +46,9 @@ pub struct BagFileInformation {
     /// Per-file information (version 5+)
     #[serde(default)]
     pub files: Vec<FileInformation>,
-    /// Custom metadata (version 6+)
+    /// Custom metadata (version 6+), written in key order
     #[serde(default)]
-    pub custom_data: Option<HashMap<String, String>>,
+    pub custom_data: Option<BTreeMap<String, String>>,
     /// ROS distribution (version 8+)
     #[serde(default)]
     pub ros_distro: Option<String>,
@@ -73,6 +81,23 @@ pub struct TopicMetadata {
     pub type_description_hash: String,
 }
 
+impl TopicMetadata {
+    /// Get what the topic carries: messages, service events or action traffic
+    pub fn kind(&self) -> TopicKind {
+        TopicKind::of(&self.name, &self.message_type)
+    }
+
+    /// Get the service of a service event topic (e.g. `/add_two_ints`)
+    pub fn service_name(&self) -> Option<&str> {
+        crate::types::service_name(&self.name, &self.message_type)
+    }
+
+    /// Get the service type of a service event topic (e.g. `example_interfaces/srv/AddTwoInts`)
+    pub fn service_type(&self) -> Option<&str> {
+        crate::types::service_type(&self.name, &self.message_type)
+    }
+}
+
 /// QoS profiles field that can be either a string or a list
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -89,6 +114,103 @@ impl Default for QosProfilesField {
     }
 }
 
+impl QosProfilesField {
+    /// Get the profiles, parsing the YAML string of older versions
+    ///
+    /// Strings that do not parse as a profile list give no profiles.
+    pub fn profiles(&self) -> Vec<QosProfile> {
+        match self {
+            Self::String(yaml) => serde_yml::from_str(yaml).unwrap_or_default(),
+            Self::List(profiles) => profiles.clone(),
+        }
+    }
+}
+
+/// Difference between a recorded checksum and the storage file on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumMismatch {
+    /// Storage file no longer exists
+    Missing { path: String },
+    /// Storage file has no recorded checksum
+    Unrecorded { path: String },
+    /// Storage file has different contents
+    Checksum {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Message count and time range of a bag read from its storage, see
+/// `Reader::recompute_statistics`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagStatistics {
+    /// Number of messages in the storage
+    pub message_count: u64,
+    /// Timestamp of the first message in nanoseconds, 0 without messages
+    pub start_time: u64,
+    /// Timestamp of the last message in nanoseconds, 0 without messages
+    pub end_time: u64,
+    /// Number of messages of each topic read
+    pub topic_message_counts: BTreeMap<String, u64>,
+    /// Differences from the statistics recorded in the metadata
+    pub discrepancies: Vec<StatisticsDiscrepancy>,
+}
+
+impl BagStatistics {
+    /// Get the time from the first to the last message in nanoseconds
+    pub fn duration(&self) -> u64 {
+        self.end_time - self.start_time
+    }
+
+    /// Whether the metadata agrees with the storage
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Difference between a statistic recorded in the metadata and the storage, e.g.
+/// for bags whose recording was interrupted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatisticsDiscrepancy {
+    /// Total number of messages
+    MessageCount { metadata: u64, storage: u64 },
+    /// Timestamp of the first message
+    StartTime { metadata: u64, storage: u64 },
+    /// Time from the first to the last message
+    Duration { metadata: u64, storage: u64 },
+    /// Number of messages of a topic
+    TopicMessageCount {
+        topic: String,
+        metadata: u64,
+        storage: u64,
+    },
+}
+
+impl std::fmt::Display for StatisticsDiscrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MessageCount { metadata, storage } => {
+                write!(f, "Messages: metadata {metadata} | storage {storage}")
+            }
+            Self::StartTime { metadata, storage } => {
+                write!(f, "Start: metadata {metadata} | storage {storage}")
+            }
+            Self::Duration { metadata, storage } => {
+                write!(f, "Duration: metadata {metadata} | storage {storage}")
+            }
+            Self::TopicMessageCount {
+                topic,
+                metadata,
+                storage,
+            } => write!(
+                f,
+                "Topic {topic}: count {metadata} in metadata | {storage} in storage"
+            ),
+        }
+    }
+}
+
 /// Per-file information (version 5+)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInformation {
@@ -105,12 +227,24 @@ pub struct FileInformation {
 impl BagMetadata {
     /// Load metadata from a metadata.yaml file
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file_with_storage(path, None)
+    }
+
+    /// Load metadata from a metadata.yaml file, replacing its storage identifier with
+    /// `storage` if given
+    pub fn from_file_with_storage<P: AsRef<Path>>(
+        path: P,
+        storage: Option<StoragePlugin>,
+    ) -> Result<Self> {
         let path = path.as_ref();
         let content = std::fs::read_to_string(path).map_err(|_| ReaderError::MetadataNotFound {
             path: path.to_path_buf(),
         })?;
 
-        let metadata: BagMetadata = serde_yml::from_str(&content)?;
+        let mut metadata: BagMetadata = serde_yml::from_str(&content)?;
+        if let Some(storage) = storage {
+            metadata.rosbag2_bagfile_information.storage_identifier = storage.as_str().to_string();
+        }
 
         // Validate the metadata
         metadata.validate()?;
@@ -118,6 +252,198 @@ impl BagMetadata {
         Ok(metadata)
     }
 
+    /// Parse metadata from the contents of a metadata.yaml file
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let metadata: BagMetadata = serde_yml::from_str(content)?;
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Create metadata for a bag directory from its storage files, without reading
+    /// metadata.yaml
+    ///
+    /// The SQLite3 or MCAP files (only those of `storage`, if given) are listed in
+    /// name order. Their format is read from their magic bytes, so renamed or
+    /// extension-less files are found too. Topics, message count and times are empty
+    /// until filled in with [`BagMetadata::fill_from_storage`].
+    pub fn from_storage_files<P: AsRef<Path>>(
+        bag_path: P,
+        storage: Option<StoragePlugin>,
+    ) -> Result<Self> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(bag_path.as_ref())? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(plugin) = crate::storage::detect_format(&entry.path()) {
+                files.push((name, plugin));
+            }
+        }
+        Self::from_storage_file_formats(files, storage)
+    }
+
+    /// Create metadata for the storage files named `names`, like
+    /// [`BagMetadata::from_storage_files`] for a directory holding them
+    ///
+    /// Only the names are known, so the format is chosen by file extension.
+    pub fn from_storage_file_names<I, S>(names: I, storage: Option<StoragePlugin>) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let files = names.into_iter().filter_map(|name| {
+            let name = name.into();
+            let plugin = crate::storage::format_of_extension(Path::new(&name))?;
+            Some((name, plugin))
+        });
+        Self::from_storage_file_formats(files, storage)
+    }
+
+    /// Create metadata for storage files given by name and format
+    pub(crate) fn from_storage_file_formats<I>(
+        files: I,
+        storage: Option<StoragePlugin>,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, StoragePlugin)>,
+    {
+        let mut files: Vec<_> = files
+            .into_iter()
+            .filter(|(_, plugin)| storage.map_or(true, |storage| storage == *plugin))
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let Some(&(_, plugin)) = files.first() else {
+            return Err(ReaderError::UnsupportedStorageFormat {
+                format: "unknown (no SQLite3 or MCAP storage files found)".to_string(),
+            });
+        };
+        if files.iter().any(|(_, other)| *other != plugin) {
+            return Err(ReaderError::UnsupportedStorageFormat {
+                format: "mixed (both SQLite3 and MCAP files found, select a storage plugin)"
+                    .to_string(),
+            });
+        }
+
+        Ok(Self {
+            rosbag2_bagfile_information: BagFileInformation {
+                version: 9,
+                storage_identifier: plugin.as_str().to_string(),
+                relative_file_paths: files.into_iter().map(|(name, _)| name).collect(),
+                duration: Duration { nanoseconds: 0 },
+                starting_time: StartingTime {
+                    nanoseconds_since_epoch: 0,
+                },
+                message_count: 0,
+                compression_format: String::new(),
+                compression_mode: String::new(),
+                topics_with_message_count: Vec::new(),
+                files: Vec::new(),
+                custom_data: None,
+                ros_distro: None,
+            },
+        })
+    }
+
+    /// Fill in topics, message count and times read from the storage
+    ///
+    /// `time_range` holds the first and last message timestamps, if any.
+    pub fn fill_from_storage(
+        &mut self,
+        connections: &[Connection],
+        time_range: Option<(u64, u64)>,
+    ) {
+        let info = &mut self.rosbag2_bagfile_information;
+        info.topics_with_message_count = connections
+            .iter()
+            .map(|connection| TopicWithMessageCount {
+                message_count: connection.message_count,
+                topic_metadata: TopicMetadata {
+                    name: connection.topic.clone(),
+                    message_type: connection.message_type.clone(),
+                    serialization_format: connection.serialization_format.clone(),
+                    offered_qos_profiles: QosProfilesField::List(
+                        connection.offered_qos_profiles.clone(),
+                    ),
+                    type_description_hash: connection.type_description_hash.clone(),
+                },
+            })
+            .collect();
+        info.message_count = connections.iter().map(|c| c.message_count).sum();
+        if let Some((first, last)) = time_range {
+            info.starting_time.nanoseconds_since_epoch = first;
+            info.duration.nanoseconds = last - first;
+        }
+    }
+
+    /// Compare the message count, time range and topic message counts with
+    /// `statistics` read from the storage, listing the differences, and replace them
+    ///
+    /// Topics missing from `statistics` keep their count.
+    #[cfg(not(feature = "write-only"))]
+    pub(crate) fn apply_statistics(
+        &mut self,
+        statistics: &BagStatistics,
+    ) -> Vec<StatisticsDiscrepancy> {
+        let mut discrepancies = Vec::new();
+        if self.message_count() != statistics.message_count {
+            discrepancies.push(StatisticsDiscrepancy::MessageCount {
+                metadata: self.message_count(),
+                storage: statistics.message_count,
+            });
+        }
+        if self.start_time() != statistics.start_time {
+            discrepancies.push(StatisticsDiscrepancy::StartTime {
+                metadata: self.start_time(),
+                storage: statistics.start_time,
+            });
+        }
+        if self.duration() != statistics.duration() {
+            discrepancies.push(StatisticsDiscrepancy::Duration {
+                metadata: self.duration(),
+                storage: statistics.duration(),
+            });
+        }
+
+        let info = &mut self.rosbag2_bagfile_information;
+        let mut recorded: BTreeMap<&str, u64> = BTreeMap::new();
+        for topic in &info.topics_with_message_count {
+            *recorded.entry(&topic.topic_metadata.name).or_default() += topic.message_count;
+        }
+        for (topic, &storage) in &statistics.topic_message_counts {
+            let metadata = recorded.get(topic.as_str()).copied().unwrap_or_default();
+            if metadata != storage {
+                discrepancies.push(StatisticsDiscrepancy::TopicMessageCount {
+                    topic: topic.clone(),
+                    metadata,
+                    storage,
+                });
+            }
+        }
+
+        info.message_count = statistics.message_count;
+        info.starting_time.nanoseconds_since_epoch = statistics.start_time;
+        info.duration.nanoseconds = statistics.duration();
+        // A topic recorded with two types has an entry for each, whose split is unknown
+        let names: Vec<String> = info
+            .topics_with_message_count
+            .iter()
+            .map(|topic| topic.topic_metadata.name.clone())
+            .collect();
+        for topic in &mut info.topics_with_message_count {
+            let name = &topic.topic_metadata.name;
+            if names.iter().filter(|other| *other == name).count() > 1 {
+                continue;
+            }
+            if let Some(&count) = statistics.topic_message_counts.get(name) {
+                topic.message_count = count;
+            }
+        }
+        discrepancies
+    }
+
     /// Validate the metadata structure
     pub fn validate(&self) -> Result<()> {
         let info = &self.rosbag2_bagfile_information;
@@ -131,21 +457,13 @@ impl BagMetadata {
 
         // Check supported storage formats
         match info.storage_identifier.as_str() {
-            "sqlite3" | "mcap" => {}
+            "sqlite3" | "mcap" | "memory" => {}
             "" => {
-                // Auto-detect storage format from file extensions when storage_identifier is empty
-                let has_db3 = info
-                    .relative_file_paths
-                    .iter()
-                    .any(|path| path.ends_with(".db3"));
-                let has_mcap = info
-                    .relative_file_paths
-                    .iter()
-                    .any(|path| path.ends_with(".mcap"));
-
-                if !has_db3 && !has_mcap {
+                // The storage format is detected from the file contents on open when
+                // storage_identifier is empty
+                if info.relative_file_paths.is_empty() {
                     return Err(ReaderError::UnsupportedStorageFormat {
-                        format: "unknown (no .db3 or .mcap files found)".to_string(),
+                        format: "unknown (no storage files listed)".to_string(),
                     });
                 }
             }
@@ -175,30 +493,95 @@ impl BagMetadata {
         Ok(())
     }
 
+    /// Write the metadata to a metadata.yaml file
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_yaml()?)?;
+        Ok(())
+    }
+
+    /// Serialize the metadata in the layout of its bag format version
+    ///
+    /// Fields added after the version are left out (`files` in 5, `custom_data` in 6,
+    /// `type_description_hash` in 7, `ros_distro` in 8) and QoS profiles are stored as
+    /// a YAML string before version 9, so `ros2 bag` releases of that version accept
+    /// the file.
+    pub fn to_yaml(&self) -> Result<String> {
+        let version = self.info().version;
+        let mut metadata = self.clone();
+        if version < 9 {
+            for topic in &mut metadata
+                .rosbag2_bagfile_information
+                .topics_with_message_count
+            {
+                let qos = &mut topic.topic_metadata.offered_qos_profiles;
+                if let QosProfilesField::List(profiles) = qos {
+                    let yaml = if profiles.is_empty() {
+                        String::new()
+                    } else {
+                        serde_yml::to_string(profiles)?.trim().to_string()
+                    };
+                    *qos = QosProfilesField::String(yaml);
+                }
+            }
+        }
+
+        let mut value = serde_yml::to_value(&metadata)?;
+        if let Some(info) = value
+            .get_mut("rosbag2_bagfile_information")
+            .and_then(serde_yml::Value::as_mapping_mut)
+        {
+            for (field, added) in [("files", 5), ("custom_data", 6), ("ros_distro", 8)] {
+                if version < added {
+                    info.remove(field);
+                }
+            }
+            if version < 7 {
+                let topics = info
+                    .get_mut("topics_with_message_count")
+                    .and_then(serde_yml::Value::as_sequence_mut);
+                for topic in topics.into_iter().flatten() {
+                    if let Some(topic_metadata) = topic
+                        .get_mut("topic_metadata")
+                        .and_then(serde_yml::Value::as_mapping_mut)
+                    {
+                        topic_metadata.remove("type_description_hash");
+                    }
+                }
+            }
+        }
+        Ok(serde_yml::to_string(&value)?)
+    }
+
     /// Get the bag file information
     pub fn info(&self) -> &BagFileInformation {
         &self.rosbag2_bagfile_information
     }
 
-    /// Get the duration in nanoseconds
+    /// Get the duration in nanoseconds (0 for bags without messages)
     pub fn duration(&self) -> u64 {
-        self.info().duration.nanoseconds
+        if self.info().message_count == 0 {
+            0
+        } else {
+            self.info().duration.nanoseconds
+        }
     }
 
-    /// Get the start time in nanoseconds since epoch
+    /// Get the start time in nanoseconds since epoch (0 for bags without messages)
+    ///
+    /// rosbag2 stores a sentinel maximum timestamp as the start of empty bags.
     pub fn start_time(&self) -> u64 {
-        self.info().starting_time.nanoseconds_since_epoch
-    }
-
-    /// Get the end time in nanoseconds since epoch
-    pub fn end_time(&self) -> u64 {
         if self.info().message_count == 0 {
             0
         } else {
-            self.start_time() + self.duration()
+            self.info().starting_time.nanoseconds_since_epoch
         }
     }
 
+    /// Get the end time in nanoseconds since epoch (0 for bags without messages)
+    pub fn end_time(&self) -> u64 {
+        self.start_time().saturating_add(self.duration())
+    }
+
     /// Get the total message count
     pub fn message_count(&self) -> u64 {
         self.info().message_count
@@ -217,4 +600,81 @@ impl BagMetadata {
             Some(&self.info().compression_mode)
         }
     }
+
+    /// Get the custom data entries set by the writer, in key order
+    ///
+    /// Checksums recorded under [`CHECKSUM_KEY_PREFIX`] are left out.
+    pub fn custom_data(&self) -> BTreeMap<String, String> {
+        self.info()
+            .custom_data
+            .iter()
+            .flatten()
+            .filter(|(key, _)| !key.starts_with(CHECKSUM_KEY_PREFIX))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Get a custom data entry
+    pub fn custom_value(&self, key: &str) -> Option<&str> {
+        self.info()
+            .custom_data
+            .as_ref()?
+            .get(key)
+            .map(String::as_str)
+    }
+
+    /// Parse a custom data entry, e.g. a number or version written with
+    /// [`WriterBuilder::custom_data`](crate::WriterBuilder::custom_data)
+    ///
+    /// Returns `None` without the entry and an error if it does not parse as `T`.
+    pub fn parse_custom_value<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.custom_value(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| ReaderError::generic(format!("Invalid custom data '{key}': {e}")))
+            })
+            .transpose()
+    }
+
+    /// Get the recorded hex-encoded SHA-256 checksum of a storage file
+    pub fn checksum(&self, path: &str) -> Option<&str> {
+        self.info()
+            .custom_data
+            .as_ref()?
+            .get(&format!("{CHECKSUM_KEY_PREFIX}{path}"))
+            .map(String::as_str)
+    }
+
+    /// Check if checksums of the storage files are recorded
+    pub fn has_checksums(&self) -> bool {
+        self.info().custom_data.as_ref().is_some_and(|custom_data| {
+            custom_data
+                .keys()
+                .any(|key| key.starts_with(CHECKSUM_KEY_PREFIX))
+        })
+    }
+}
+
+/// Size and hex-encoded SHA-256 of a file
+pub fn sha256_file(path: impl AsRef<Path>) -> Result<(u64, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    let digest = hasher.finalize();
+    let hex = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok((size, hex))
 }
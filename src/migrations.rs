@@ -0,0 +1,579 @@
+//! Upgrades of messages recorded with outdated interface definitions
+//!
+//! A [`Migration`] rewrites messages of a type recorded with an older layout into
+//! the current definition of the type: fields present in both layouts are carried
+//! over by name (converting numbers that fit the new type), new fields start at
+//! their declared default value, and an optional function fills in whatever cannot be
+//! carried over, such as renamed fields or changed units. [`Migrations`] holds the
+//! registered migrations, chains them (v1 → v2 → v3) and, as a [`MessageTransform`],
+//! upgrades messages while a bag is copied. The connection written for the upgraded
+//! messages comes from [`Migrations::migrate_connection`].
+//!
+//! Layouts are told apart by their type description hash, so messages already
+//! recorded with the current definition are passed through unchanged.
+
+use crate::cdr::{CdrDeserializer, CdrSerializer};
+use crate::definitions::normalize_type_name;
+use crate::dynamic::{
+    cast_value, connection_schemas, decode_message, default_message, encode_message,
+    DynamicMessage, FieldValue, Value,
+};
+use crate::error::{BagError, Result};
+use crate::registry::TypeRegistry;
+use crate::schema::{ArraySpec, FieldDef, MessageSchemas};
+use crate::transform::MessageTransform;
+use crate::types::{Connection, MessageDefinition};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Function completing an upgraded message from the message as recorded
+///
+/// It gets the recorded message and the upgraded one with the fields carried over,
+/// and changes the upgraded one in place.
+pub type MigrateFn = dyn Fn(&DynamicMessage, &mut DynamicMessage) -> Result<()> + Send + Sync;
+
+/// Upgrade of one message type from an older layout to a new definition
+///
+/// # Example
+/// ```
+/// use rosbags_rs::migrations::{Migration, Migrations};
+///
+/// // `range` was renamed to `distance` and `confidence` was added
+/// let migration = Migration::msgdef(
+///     "my_msgs/msg/Range",
+///     "std_msgs/Header header\nfloat64 distance\nfloat32 confidence\n",
+/// )
+/// .unwrap()
+/// .with(|old, new| {
+///     if let (Some(range), Some((_, distance))) =
+///         (old.get("range"), new.fields.iter_mut().find(|(name, _)| name == "distance"))
+///     {
+///         *distance = range.clone();
+///     }
+///     Ok(())
+/// });
+///
+/// let mut migrations = Migrations::new();
+/// migrations.register(migration);
+/// assert_eq!(migrations.len(), 1);
+/// ```
+pub struct Migration {
+    /// Type of the messages upgraded, as `pkg/msg/Type`
+    message_type: String,
+    /// Type of the upgraded messages, as `pkg/msg/Type`
+    target_type: String,
+    /// Complete definition of the upgraded messages
+    definition: MessageDefinition,
+    schemas: MessageSchemas,
+    /// Type description hash of the new layout
+    hash: String,
+    /// Type description hash of the only layout upgraded, if restricted
+    source_hash: Option<String>,
+    migrate: Option<Box<MigrateFn>>,
+}
+
+impl std::fmt::Debug for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migration")
+            .field("message_type", &self.message_type)
+            .field("target_type", &self.target_type)
+            .field("hash", &self.hash)
+            .field("source_hash", &self.source_hash)
+            .field("migrate", &self.migrate.is_some())
+            .finish()
+    }
+}
+
+impl Migration {
+    /// Upgrade messages of `message_type` recorded with any other layout to `definition`
+    ///
+    /// `definition` must include every type it depends on, as stored in bags; use
+    /// [`Migration::msgdef`] to complete a `.msg` file from the bundled definitions.
+    pub fn new(message_type: &str, definition: MessageDefinition) -> Result<Self> {
+        Self::rename(message_type, message_type, definition)
+    }
+
+    /// Upgrade messages of `message_type` to `definition` of another type name
+    pub fn rename(
+        message_type: &str,
+        target_type: &str,
+        definition: MessageDefinition,
+    ) -> Result<Self> {
+        let target_type = normalize_type_name(target_type);
+        let schemas = MessageSchemas::parse(&target_type, &definition)?;
+        let missing = schemas.missing_types();
+        if !missing.is_empty() {
+            return Err(BagError::schema_validation(format!(
+                "Definition of {target_type} lacks referenced types {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(Self {
+            message_type: normalize_type_name(message_type),
+            hash: schemas.type_description_hash()?,
+            target_type,
+            definition,
+            schemas,
+            source_hash: None,
+            migrate: None,
+        })
+    }
+
+    /// Upgrade messages of `message_type` to the `.msg` definition `text`
+    ///
+    /// Types `text` depends on are taken from the bundled definitions unless `text`
+    /// is a concatenated definition that includes them.
+    pub fn msgdef(message_type: &str, text: &str) -> Result<Self> {
+        let mut registry = TypeRegistry::new();
+        registry.register_msgdef(message_type, text)?;
+        Self::new(message_type, registry.definition(message_type)?)
+    }
+
+    /// Only upgrade messages recorded with `definition`
+    ///
+    /// Messages of the type recorded with other layouts are left to other migrations.
+    pub fn from_definition(mut self, definition: &MessageDefinition) -> Result<Self> {
+        let schemas = MessageSchemas::parse(&self.message_type, definition)?;
+        self.source_hash = Some(schemas.type_description_hash()?);
+        Ok(self)
+    }
+
+    /// Complete each upgraded message with `migrate`
+    pub fn with(
+        mut self,
+        migrate: impl Fn(&DynamicMessage, &mut DynamicMessage) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.migrate = Some(Box::new(migrate));
+        self
+    }
+
+    /// Type of the messages upgraded
+    pub fn message_type(&self) -> &str {
+        &self.message_type
+    }
+
+    /// Type of the upgraded messages
+    pub fn target_type(&self) -> &str {
+        &self.target_type
+    }
+
+    /// Complete definition of the upgraded messages
+    pub fn definition(&self) -> &MessageDefinition {
+        &self.definition
+    }
+
+    /// Whether messages of `message_type` with the layout `hash` are upgraded
+    fn matches(&self, message_type: &str, hash: &str) -> bool {
+        normalize_type_name(message_type) == self.message_type
+            && (hash != self.hash || self.target_type != self.message_type)
+            && self
+                .source_hash
+                .as_deref()
+                .map_or(true, |source| source == hash)
+    }
+
+    /// Connection of the upgraded messages of `connection`
+    fn connection(&self, connection: &Connection) -> Connection {
+        Connection {
+            message_type: self.target_type.clone(),
+            message_definition: self.definition.clone(),
+            type_description_hash: self.hash.clone(),
+            ..connection.clone()
+        }
+    }
+
+    /// Upgrade one decoded message
+    fn upgrade(&self, message: &DynamicMessage) -> Result<DynamicMessage> {
+        let mut upgraded = carry_message(message, &self.schemas, &self.target_type)?;
+        if let Some(migrate) = &self.migrate {
+            migrate(message, &mut upgraded)?;
+        }
+        Ok(upgraded)
+    }
+}
+
+/// Connections of the steps that upgrade messages of one connection, in order
+#[derive(Debug, Default)]
+struct Plan {
+    /// Index of the migration and connection of the messages it upgrades, by step
+    steps: Vec<(usize, Connection)>,
+    /// Connection of the upgraded messages
+    connection: Option<Connection>,
+}
+
+/// Registered migrations, applied in chains while copying bags
+///
+/// A message is upgraded by the first registered migration that matches its type
+/// and layout, then by the first one that matches the result, and so on; each
+/// migration runs at most once per message.
+#[derive(Debug, Default)]
+pub struct Migrations {
+    migrations: Vec<Migration>,
+    /// Plans by message type and hash of the recorded definition
+    plans: Mutex<HashMap<(String, u64), Arc<Plan>>>,
+}
+
+impl Migrations {
+    /// Create an empty set of migrations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a migration, after the ones registered before
+    pub fn register(&mut self, migration: Migration) {
+        self.migrations.push(migration);
+        self.plans.get_mut().map(HashMap::clear).ok();
+    }
+
+    /// Number of registered migrations
+    pub fn len(&self) -> usize {
+        self.migrations.len()
+    }
+
+    /// Whether no migration is registered
+    pub fn is_empty(&self) -> bool {
+        self.migrations.is_empty()
+    }
+
+    /// Connection to write the upgraded messages of `connection` with
+    ///
+    /// It has the type, definition and type description hash of the last migration
+    /// applied; connections without migrations are returned unchanged.
+    pub fn migrate_connection(&self, connection: &Connection) -> Result<Connection> {
+        Ok(self
+            .plan(connection)?
+            .connection
+            .clone()
+            .unwrap_or_else(|| connection.clone()))
+    }
+
+    /// Upgrade one serialized message of `connection`
+    ///
+    /// Messages without migrations are returned unchanged.
+    pub fn migrate(&self, connection: &Connection, data: &[u8]) -> Result<Vec<u8>> {
+        let plan = self.plan(connection)?;
+        let Some(((_, first), _)) = plan.steps.first().zip(plan.connection.as_ref()) else {
+            return Ok(data.to_vec());
+        };
+        let schemas = connection_schemas(first)?;
+        let mut deserializer = CdrDeserializer::new(data)?;
+        let mut message = decode_message(&mut deserializer, &schemas, &first.message_type)?;
+        for (index, _) in &plan.steps {
+            message = self.migrations[*index].upgrade(&message)?;
+        }
+        let (index, _) = plan.steps.last().expect("checked above");
+        let migration = &self.migrations[*index];
+        let mut serializer = CdrSerializer::with_capacity(data.len());
+        encode_message(
+            &mut serializer,
+            &migration.schemas,
+            &migration.target_type,
+            &message,
+        )?;
+        Ok(serializer.into_bytes())
+    }
+
+    /// Get the migrations of `connection`, working them out the first time
+    fn plan(&self, connection: &Connection) -> Result<Arc<Plan>> {
+        let mut hasher = DefaultHasher::new();
+        connection.message_definition.hash(&mut hasher);
+        let key = (connection.message_type.clone(), hasher.finish());
+        if let Some(plan) = self.plans.lock().ok().and_then(|p| p.get(&key).cloned()) {
+            return Ok(plan);
+        }
+
+        let mut plan = Plan::default();
+        if self
+            .migrations
+            .iter()
+            .any(|m| normalize_type_name(&connection.message_type) == m.message_type)
+        {
+            let mut current = connection.clone();
+            let mut hash = connection_schemas(&current)?.type_description_hash()?;
+            let mut used = vec![false; self.migrations.len()];
+            while let Some(index) = (0..self.migrations.len()).find(|&index| {
+                !used[index] && self.migrations[index].matches(&current.message_type, &hash)
+            }) {
+                used[index] = true;
+                let migration = &self.migrations[index];
+                let next = migration.connection(&current);
+                plan.steps
+                    .push((index, std::mem::replace(&mut current, next)));
+                hash = migration.hash.clone();
+            }
+            if !plan.steps.is_empty() {
+                plan.connection = Some(current);
+            }
+        }
+
+        let plan = Arc::new(plan);
+        if let Ok(mut plans) = self.plans.lock() {
+            plans.insert(key, plan.clone());
+        }
+        Ok(plan)
+    }
+}
+
+impl MessageTransform for Migrations {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        self.plan(connection)
+            .is_ok_and(|plan| !plan.steps.is_empty())
+    }
+
+    fn transform(
+        &self,
+        connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        Ok(Some((timestamp, self.migrate(connection, data)?)))
+    }
+
+    fn connection(&self, connection: &Connection) -> Result<Connection> {
+        self.migrate_connection(connection)
+    }
+}
+
+/// Carry the fields of `message` over to a default message of `type_name`
+fn carry_message(
+    message: &DynamicMessage,
+    schemas: &MessageSchemas,
+    type_name: &str,
+) -> Result<DynamicMessage> {
+    let mut carried = default_message(schemas, type_name)?;
+    let schema = schemas
+        .get(type_name)
+        .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+    for (field, (_, value)) in schema.fields.iter().zip(&mut carried.fields) {
+        if let Some(old) = message.get(&field.name) {
+            if let Some(new) = carry_field(old, schemas, field) {
+                *value = new;
+            }
+        }
+    }
+    Ok(carried)
+}
+
+/// Carry a value over to `field` of the new layout, `None` if it does not fit
+fn carry_field(
+    value: &FieldValue,
+    schemas: &MessageSchemas,
+    field: &FieldDef,
+) -> Option<FieldValue> {
+    let base = field.base_type();
+    let Some(spec) = field.array() else {
+        return carry_element(value, schemas, base);
+    };
+    let elements: Vec<FieldValue> = match value {
+        FieldValue::Bytes(bytes) => bytes
+            .iter()
+            .map(|&byte| FieldValue::Value(Value::UInt(byte.into())))
+            .collect(),
+        FieldValue::Array(elements) => elements.clone(),
+        _ => return None,
+    };
+    let fits = match spec {
+        ArraySpec::Fixed(length) => elements.len() == length,
+        ArraySpec::Bounded(bound) => elements.len() <= bound,
+        ArraySpec::Unbounded => true,
+    };
+    if !fits {
+        return None;
+    }
+    let elements = elements
+        .iter()
+        .map(|element| carry_element(element, schemas, base))
+        .collect::<Option<Vec<_>>>()?;
+    if !matches!(base, "byte" | "uint8") {
+        return Some(FieldValue::Array(elements));
+    }
+    elements
+        .iter()
+        .map(|element| match element {
+            FieldValue::Value(Value::UInt(byte)) => Some(*byte as u8),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .map(FieldValue::Bytes)
+}
+
+/// Carry a single primitive, string or nested message over to `type_name`
+fn carry_element(
+    value: &FieldValue,
+    schemas: &MessageSchemas,
+    type_name: &str,
+) -> Option<FieldValue> {
+    match value {
+        FieldValue::Value(value) => cast_value(value, type_name).map(FieldValue::Value),
+        FieldValue::Message(message) => carry_message(message, schemas, type_name)
+            .ok()
+            .map(FieldValue::Message),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageDefinitionFormat;
+
+    const V1: &str = "int32 id\nfloat32 range\nuint8[] tags\n";
+    const V2: &str = "int32 id\nfloat64 distance\nuint8[] tags\nstring label\n";
+    const V3: &str = "int64 id\nfloat64 distance\nstring label\n";
+
+    fn msg(text: &str) -> MessageDefinition {
+        MessageDefinition {
+            format: MessageDefinitionFormat::Msg,
+            data: text.to_string(),
+        }
+    }
+
+    fn connection(text: &str) -> Connection {
+        Connection::builder("/range", "my_msgs/msg/Range")
+            .message_definition(msg(text))
+            .build()
+            .unwrap()
+    }
+
+    fn encode(text: &str, fields: Vec<(&str, FieldValue)>) -> Vec<u8> {
+        let schemas = MessageSchemas::parse_msg("my_msgs/msg/Range", text).unwrap();
+        let message = DynamicMessage {
+            message_type: "my_msgs/msg/Range".to_string(),
+            fields: fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        };
+        let mut serializer = CdrSerializer::new();
+        encode_message(&mut serializer, &schemas, "my_msgs/msg/Range", &message).unwrap();
+        serializer.into_bytes()
+    }
+
+    fn decode(connection: &Connection, data: &[u8]) -> DynamicMessage {
+        let schemas = connection.message_schemas().unwrap();
+        let mut deserializer = CdrDeserializer::new(data).unwrap();
+        decode_message(&mut deserializer, &schemas, &connection.message_type).unwrap()
+    }
+
+    fn value(value: Value) -> FieldValue {
+        FieldValue::Value(value)
+    }
+
+    fn rename_range() -> Migration {
+        Migration::msgdef("my_msgs/msg/Range", V2)
+            .unwrap()
+            .with(|old, new| {
+                let range = old.get("range").and_then(FieldValue::as_f64);
+                for (name, value) in &mut new.fields {
+                    match (name.as_str(), range) {
+                        ("distance", Some(range)) => {
+                            *value = FieldValue::Value(Value::Float(range))
+                        }
+                        ("label", _) => *value = FieldValue::Value(Value::String("v1".into())),
+                        _ => {}
+                    }
+                }
+                Ok(())
+            })
+    }
+
+    #[test]
+    fn test_migration_carries_fields_and_runs_hook() {
+        let mut migrations = Migrations::new();
+        migrations.register(rename_range());
+
+        let old = connection(V1);
+        assert!(migrations.applies_to(&old));
+        let new = migrations.migrate_connection(&old).unwrap();
+        assert_eq!(new.topic, "/range");
+        assert_eq!(new.message_definition.data, V2);
+        assert_eq!(
+            new.type_description_hash,
+            MessageSchemas::parse_msg("my_msgs/msg/Range", V2)
+                .unwrap()
+                .type_description_hash()
+                .unwrap()
+        );
+
+        let data = encode(
+            V1,
+            vec![
+                ("id", value(Value::Int(7))),
+                ("range", value(Value::Float(2.5))),
+                ("tags", FieldValue::Bytes(vec![1, 2])),
+            ],
+        );
+        let upgraded = decode(&new, &migrations.migrate(&old, &data).unwrap());
+        assert_eq!(upgraded.get("id"), Some(&value(Value::Int(7))));
+        assert_eq!(upgraded.get("distance"), Some(&value(Value::Float(2.5))));
+        assert_eq!(upgraded.get("tags"), Some(&FieldValue::Bytes(vec![1, 2])));
+        assert_eq!(
+            upgraded.get("label"),
+            Some(&value(Value::String("v1".into())))
+        );
+
+        // Messages already recorded with the new layout are passed through
+        let current = connection(V2);
+        assert!(!migrations.applies_to(&current));
+        assert_eq!(migrations.migrate_connection(&current).unwrap(), current);
+        assert_eq!(migrations.migrate(&current, &data).unwrap(), data);
+    }
+
+    #[test]
+    fn test_migrations_chain_and_restrict_layouts() {
+        let mut migrations = Migrations::new();
+        migrations.register(
+            Migration::msgdef("my_msgs/msg/Range", V3)
+                .unwrap()
+                .from_definition(&msg(V2))
+                .unwrap(),
+        );
+        migrations.register(rename_range());
+
+        // v1 is upgraded to v2 first, then to v3
+        let old = connection(V1);
+        let new = migrations.migrate_connection(&old).unwrap();
+        assert_eq!(new.message_definition.data, V3);
+        let data = encode(
+            V1,
+            vec![
+                ("id", value(Value::Int(-3))),
+                ("range", value(Value::Float(0.5))),
+                ("tags", FieldValue::Bytes(vec![])),
+            ],
+        );
+        let upgraded = decode(&new, &migrations.migrate(&old, &data).unwrap());
+        assert_eq!(
+            upgraded.fields,
+            vec![
+                ("id".to_string(), value(Value::Int(-3))),
+                ("distance".to_string(), value(Value::Float(0.5))),
+                ("label".to_string(), value(Value::String("v1".into()))),
+            ]
+        );
+
+        // Layouts other than the one a migration is restricted to are not upgraded by it
+        let other = connection("int32 id\n");
+        let migrations = {
+            let mut only_v2 = Migrations::new();
+            only_v2.register(
+                Migration::msgdef("my_msgs/msg/Range", V3)
+                    .unwrap()
+                    .from_definition(&msg(V2))
+                    .unwrap(),
+            );
+            only_v2
+        };
+        assert!(!migrations.applies_to(&other));
+        assert!(migrations.applies_to(&connection(V2)));
+    }
+
+    #[test]
+    fn test_incomplete_definitions_are_rejected() {
+        let definition = msg("my_msgs/Other other\n");
+        assert!(Migration::new("my_msgs/msg/Range", definition).is_err());
+        assert!(Migration::msgdef("my_msgs/msg/Range", "std_msgs/Header header\n").is_ok());
+    }
+}
@@ -0,0 +1,347 @@
+//! Reading several bags as one timeline
+//!
+//! A session recorded by several nodes often ends up as several bags that overlap
+//! in time. [`MultiReader`] opens all of them and presents their messages as one
+//! time-ordered stream with a single connection listing, without merging the bags
+//! on disk first.
+
+use crate::error::{ReaderError, Result};
+use crate::reader::{Reader, ReaderOptions};
+use crate::types::{BagFile, Connection, Message, RawMessage};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+
+/// Reader presenting several bags as one merged, time-ordered bag
+///
+/// Connections of all bags are unified per topic and message type: a topic recorded
+/// by two bags with the same type has one connection, whose message count is the sum
+/// of both. Unified connections are numbered from 1 in order of first appearance,
+/// going through the bags in the order they were given.
+///
+/// Messages come in timestamp order; messages with equal timestamps come in the
+/// order of their bags, and within a bag in the bag's own order. Every message
+/// carries its unified connection, and its `file_index` indexes
+/// [`MultiReader::files`], which lists the files of all bags in order.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::MultiReader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = MultiReader::new(&["session/lidar_bag", "session/camera_bag"])?;
+/// for message in reader.messages()? {
+///     let message = message?;
+///     println!("{} {}", message.timestamp, message.topic);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiReader {
+    /// Open readers, one per bag
+    readers: Vec<Reader>,
+    /// Connections unified per topic and message type
+    connections: Vec<Connection>,
+    /// Index of the first file of each bag in [`MultiReader::files`]
+    file_offsets: Vec<usize>,
+}
+
+impl std::fmt::Debug for MultiReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiReader")
+            .field("bags", &self.bag_paths())
+            .field("connections", &self.connections)
+            .finish()
+    }
+}
+
+impl MultiReader {
+    /// Open the bags at `paths` with default options
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        Self::with_options(paths, ReaderOptions::default())
+    }
+
+    /// Open the bags at `paths`, each with `options`
+    pub fn with_options<P: AsRef<Path>>(paths: &[P], options: ReaderOptions) -> Result<Self> {
+        let readers = paths
+            .iter()
+            .map(|path| {
+                let mut reader = Reader::with_options(path, options.clone())?;
+                reader.open()?;
+                Ok(reader)
+            })
+            .collect::<Result<_>>()?;
+        Self::from_readers(readers)
+    }
+
+    /// Combine readers that are already open, e.g. configured with different options
+    pub fn from_readers(readers: Vec<Reader>) -> Result<Self> {
+        if readers.is_empty() {
+            return Err(ReaderError::generic("MultiReader needs at least one bag"));
+        }
+
+        let mut connections: Vec<Connection> = Vec::new();
+        let mut file_offsets = Vec::with_capacity(readers.len());
+        let mut files = 0;
+        for reader in &readers {
+            for connection in reader.connections() {
+                match connections.iter_mut().find(|c| same_key(c, connection)) {
+                    Some(unified) => unified.message_count += connection.message_count,
+                    None => {
+                        let mut unified = connection.clone();
+                        unified.id = connections.len() as u32 + 1;
+                        connections.push(unified);
+                    }
+                }
+            }
+            file_offsets.push(files);
+            files += reader.files()?.len();
+        }
+
+        Ok(Self {
+            readers,
+            connections,
+            file_offsets,
+        })
+    }
+
+    /// Get the readers of the individual bags, in the order they were given
+    pub fn readers(&self) -> &[Reader] {
+        &self.readers
+    }
+
+    /// Get the paths of the bags
+    pub fn bag_paths(&self) -> Vec<PathBuf> {
+        self.readers
+            .iter()
+            .map(|reader| reader.bag_path().to_path_buf())
+            .collect()
+    }
+
+    /// Get the connections of all bags, unified per topic and message type
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// Get the unified connections of `topic`, ready to pass to the filtered
+    /// iteration methods
+    pub fn connections_for_topic(&self, topic: &str) -> Vec<Connection> {
+        self.connections
+            .iter()
+            .filter(|c| c.topic == topic)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the storage files of all bags, bag by bag
+    pub fn files(&self) -> Result<Vec<BagFile>> {
+        let mut files = Vec::new();
+        for reader in &self.readers {
+            files.extend(reader.files()?);
+        }
+        Ok(files)
+    }
+
+    /// Get the total message count of all bags
+    pub fn message_count(&self) -> u64 {
+        self.readers.iter().map(Reader::message_count).sum()
+    }
+
+    /// Get the earliest start time of the bags in nanoseconds since epoch
+    pub fn start_time(&self) -> u64 {
+        self.readers
+            .iter()
+            .filter(|reader| reader.message_count() > 0)
+            .map(Reader::start_time)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Get the latest end time of the bags in nanoseconds since epoch
+    pub fn end_time(&self) -> u64 {
+        self.readers
+            .iter()
+            .filter(|reader| reader.message_count() > 0)
+            .map(Reader::end_time)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Get the duration from the earliest start to the latest end in nanoseconds
+    pub fn duration(&self) -> u64 {
+        self.end_time() - self.start_time()
+    }
+
+    /// Iterate over the messages of all bags in timestamp order
+    pub fn messages(&self) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        self.messages_filtered(None, None, None)
+    }
+
+    /// Iterate over the messages of all bags with optional filters
+    ///
+    /// `connections` are unified connections of this reader; `start` is inclusive
+    /// and `stop` exclusive.
+    pub fn messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        let mut streams = Vec::with_capacity(self.readers.len());
+        for (bag, reader) in self.readers.iter().enumerate() {
+            let Some(selected) = self.selected(reader, connections) else {
+                continue;
+            };
+            let iterator = reader.messages_filtered(selected.as_deref(), start, stop)?;
+            streams.push(Box::new(iterator.map(move |message| {
+                let mut message = message?;
+                message.connection = self.unified(&message.connection)?;
+                message.file_index += self.file_offsets[bag];
+                Ok(message)
+            })) as Box<dyn Iterator<Item = Result<Message>>>);
+        }
+        Ok(Box::new(Merged::new(streams, |m: &Message| m.timestamp)))
+    }
+
+    /// Iterate over the raw messages of all bags in timestamp order
+    pub fn raw_messages(&self) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        self.raw_messages_filtered(None, None, None)
+    }
+
+    /// Iterate over the raw messages of all bags with optional filters
+    ///
+    /// See [`MultiReader::messages_filtered`].
+    pub fn raw_messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        let mut streams = Vec::with_capacity(self.readers.len());
+        for (bag, reader) in self.readers.iter().enumerate() {
+            let Some(selected) = self.selected(reader, connections) else {
+                continue;
+            };
+            let iterator = reader.raw_messages_filtered(selected.as_deref(), start, stop)?;
+            streams.push(Box::new(iterator.map(move |message| {
+                let mut message = message?;
+                message.connection = self.unified(&message.connection)?;
+                message.file_index += self.file_offsets[bag];
+                Ok(message)
+            }))
+                as Box<dyn Iterator<Item = Result<RawMessage>>>);
+        }
+        Ok(Box::new(Merged::new(streams, |m: &RawMessage| m.timestamp)))
+    }
+
+    /// Connections of `reader` matching the unified `connections`
+    ///
+    /// `Some(None)` reads every connection, `None` skips a bag without any of them.
+    fn selected(
+        &self,
+        reader: &Reader,
+        connections: Option<&[Connection]>,
+    ) -> Option<Option<Vec<Connection>>> {
+        let Some(connections) = connections else {
+            return Some(None);
+        };
+        let selected: Vec<_> = reader
+            .connections()
+            .iter()
+            .filter(|c| connections.iter().any(|unified| same_key(unified, c)))
+            .cloned()
+            .collect();
+        (!selected.is_empty()).then_some(Some(selected))
+    }
+
+    /// The unified connection of a bag's `connection`
+    fn unified(&self, connection: &Connection) -> Result<Connection> {
+        self.connections
+            .iter()
+            .find(|c| same_key(c, connection))
+            .cloned()
+            .ok_or_else(|| ReaderError::connection_not_found(&connection.topic))
+    }
+}
+
+/// Whether two connections have the same topic and message type
+fn same_key(a: &Connection, b: &Connection) -> bool {
+    a.topic == b.topic && a.message_type == b.message_type
+}
+
+/// K-way merge of time-ordered streams
+///
+/// Keeps the head message of every stream in a heap ordered by timestamp, then by
+/// stream index. Errors are passed on as they are read, and the failing stream is
+/// read on afterwards.
+struct Merged<'a, T> {
+    streams: Vec<Box<dyn Iterator<Item = Result<T>> + 'a>>,
+    heads: Vec<Option<T>>,
+    order: BinaryHeap<Reverse<(u64, usize)>>,
+    /// Streams whose next message has to be read before picking the next head
+    refill: Vec<usize>,
+    timestamp: fn(&T) -> u64,
+}
+
+impl<'a, T> Merged<'a, T> {
+    fn new(
+        streams: Vec<Box<dyn Iterator<Item = Result<T>> + 'a>>,
+        timestamp: fn(&T) -> u64,
+    ) -> Self {
+        Self {
+            heads: streams.iter().map(|_| None).collect(),
+            refill: (0..streams.len()).rev().collect(),
+            order: BinaryHeap::with_capacity(streams.len()),
+            streams,
+            timestamp,
+        }
+    }
+}
+
+impl<T> Iterator for Merged<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(index) = self.refill.pop() {
+            match self.streams[index].next() {
+                Some(Ok(message)) => {
+                    self.order
+                        .push(Reverse(((self.timestamp)(&message), index)));
+                    self.heads[index] = Some(message);
+                }
+                Some(Err(error)) => {
+                    self.refill.push(index);
+                    return Some(Err(error));
+                }
+                None => {}
+            }
+        }
+        let Reverse((_, index)) = self.order.pop()?;
+        self.refill.push(index);
+        self.heads[index].take().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_orders_by_timestamp_then_stream() {
+        let streams = [vec![1u64, 5, 5, 9], vec![], vec![0, 5, 10]]
+            .into_iter()
+            .enumerate()
+            .map(|(index, timestamps)| {
+                Box::new(timestamps.into_iter().map(move |t| Ok((t, index))))
+                    as Box<dyn Iterator<Item = Result<(u64, usize)>>>
+            })
+            .collect();
+        let merged: Vec<_> = Merged::new(streams, |m: &(u64, usize)| m.0)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            merged,
+            [(0, 2), (1, 0), (5, 0), (5, 0), (5, 2), (9, 0), (10, 2)]
+        );
+    }
+}
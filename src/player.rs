@@ -0,0 +1,433 @@
+//! Message playback with real-time pacing
+//!
+//! [`Player`] delivers the messages of an open [`Reader`] to a callback, spacing them
+//! according to their recorded timestamps scaled by a rate multiplier. Playback can
+//! be paused, resumed, sought and stopped from any thread through [`PlayerControls`].
+//! This is the core of a `ros2 bag play` equivalent without any publishing backend.
+
+use crate::error::{BagError, Result};
+use crate::reader::Reader;
+use crate::types::{Connection, Message};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Shared playback state manipulated by [`PlayerControls`]
+#[derive(Debug)]
+struct ControlState {
+    paused: bool,
+    stopped: bool,
+    seek: Option<u64>,
+    rate: f64,
+}
+
+/// Handle for controlling a running [`Player`]
+///
+/// Cheap to clone and safe to use from other threads or from inside the
+/// playback callback.
+#[derive(Debug, Clone)]
+pub struct PlayerControls {
+    inner: Arc<(Mutex<ControlState>, Condvar)>,
+}
+
+impl PlayerControls {
+    fn new(rate: f64) -> Self {
+        Self {
+            inner: Arc::new((
+                Mutex::new(ControlState {
+                    paused: false,
+                    stopped: false,
+                    seek: None,
+                    rate,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ControlState> {
+        self.inner
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut ControlState)) {
+        change(&mut self.state());
+        self.inner.1.notify_all();
+    }
+
+    /// Pause playback before the next message
+    pub fn pause(&self) {
+        self.update(|state| state.paused = true);
+    }
+
+    /// Resume paused playback
+    pub fn resume(&self) {
+        self.update(|state| state.paused = false);
+    }
+
+    /// Whether playback is paused
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    /// Stop playback; [`Player::play`] returns after the current message
+    pub fn stop(&self) {
+        self.update(|state| state.stopped = true);
+    }
+
+    /// Whether playback has been stopped
+    pub fn is_stopped(&self) -> bool {
+        self.state().stopped
+    }
+
+    /// Continue playback from the first message at or after `timestamp` (nanoseconds)
+    pub fn seek(&self, timestamp: u64) {
+        self.update(|state| state.seek = Some(timestamp));
+    }
+
+    /// Change the rate multiplier of a running playback
+    ///
+    /// Non-positive or non-finite rates are ignored.
+    pub fn set_rate(&self, rate: f64) {
+        if rate.is_finite() && rate > 0.0 {
+            self.update(|state| state.rate = rate);
+        }
+    }
+
+    /// Current rate multiplier
+    pub fn rate(&self) -> f64 {
+        self.state().rate
+    }
+}
+
+/// Outcome of waiting for a message's delivery time
+enum Wait {
+    Deliver,
+    Seek(u64),
+    Stop,
+}
+
+/// Paced playback of a bag's messages
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::{Player, Reader};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+///
+/// let delivered = Player::new(&reader)
+///     .rate(2.0)
+///     .looping(false)
+///     .play(|msg| println!("{} {}", msg.timestamp, msg.topic))?;
+/// println!("Played {delivered} messages");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Player<'a> {
+    reader: &'a Reader,
+    looping: bool,
+    connections: Option<Vec<Connection>>,
+    start: Option<u64>,
+    stop: Option<u64>,
+    controls: PlayerControls,
+}
+
+impl<'a> Player<'a> {
+    /// Create a player for an open reader at real-time rate
+    pub fn new(reader: &'a Reader) -> Self {
+        Self {
+            reader,
+            looping: false,
+            connections: None,
+            start: None,
+            stop: None,
+            controls: PlayerControls::new(1.0),
+        }
+    }
+
+    /// Set the rate multiplier (2.0 plays twice as fast)
+    ///
+    /// Non-positive or non-finite rates are ignored and keep the previous rate.
+    pub fn rate(self, rate: f64) -> Self {
+        if rate.is_finite() && rate > 0.0 {
+            self.controls.set_rate(rate);
+        }
+        self
+    }
+
+    /// Restart from the beginning after the last message
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Only play messages of the given topics
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.connections = Some(
+            self.reader
+                .connections()
+                .iter()
+                .filter(|c| topics.contains(&c.topic.as_str()))
+                .cloned()
+                .collect(),
+        );
+        self
+    }
+
+    /// Only play messages with timestamps in `[start, stop)` (nanoseconds)
+    pub fn time_range(mut self, start: Option<u64>, stop: Option<u64>) -> Self {
+        self.start = start;
+        self.stop = stop;
+        self
+    }
+
+    /// Start in the paused state; call [`PlayerControls::resume`] to begin
+    pub fn start_paused(self, paused: bool) -> Self {
+        self.controls.update(|state| state.paused = paused);
+        self
+    }
+
+    /// Connections whose messages are played
+    pub fn connections(&self) -> &[Connection] {
+        self.connections
+            .as_deref()
+            .unwrap_or_else(|| self.reader.connections())
+    }
+
+    /// First and last timestamp playback can reach (nanoseconds)
+    #[cfg(feature = "foxglove")]
+    pub(crate) fn time_span(&self) -> (u64, u64) {
+        let start = self.start.map_or(self.reader.start_time(), |start| {
+            start.max(self.reader.start_time())
+        });
+        let end = self.stop.map_or(self.reader.end_time(), |stop| {
+            stop.saturating_sub(1).min(self.reader.end_time())
+        });
+        (start, end.max(start))
+    }
+
+    /// Handle for pausing, resuming, seeking and stopping playback
+    pub fn controls(&self) -> PlayerControls {
+        self.controls.clone()
+    }
+
+    /// Play messages, invoking `callback` for each at its scheduled time
+    ///
+    /// Blocks until the bag is exhausted (never, when looping) or playback is
+    /// stopped, and returns the number of delivered messages.
+    pub fn play<F>(&self, mut callback: F) -> Result<u64>
+    where
+        F: FnMut(&Message),
+    {
+        if !self.reader.is_open() {
+            return Err(BagError::BagNotOpen);
+        }
+
+        let mut delivered = 0u64;
+        let mut start = self.start;
+        let mut anchor: Option<Anchor> = None;
+
+        'playback: loop {
+            let messages =
+                self.reader
+                    .messages_filtered(self.connections.as_deref(), start, self.stop)?;
+
+            let mut any = false;
+            for message in messages {
+                let message = message?;
+                any = true;
+
+                match self.wait_until_due(&mut anchor, message.timestamp) {
+                    Wait::Stop => break 'playback,
+                    Wait::Seek(timestamp) => {
+                        start = Some(timestamp);
+                        anchor = None;
+                        continue 'playback;
+                    }
+                    Wait::Deliver => {}
+                }
+
+                callback(&message);
+                delivered += 1;
+            }
+
+            if !self.looping || !any || self.controls.is_stopped() {
+                break;
+            }
+            start = self.start;
+            anchor = None;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Block until `timestamp` is due, handling control changes in the meantime
+    fn wait_until_due(&self, anchor: &mut Option<Anchor>, timestamp: u64) -> Wait {
+        let condvar = &self.controls.inner.1;
+        let mut state = self.controls.state();
+
+        loop {
+            if state.stopped {
+                return Wait::Stop;
+            }
+            if let Some(target) = state.seek.take() {
+                return Wait::Seek(target);
+            }
+            if state.paused {
+                state = condvar
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                // Re-anchor so the pause does not cause a burst of catch-up messages
+                *anchor = None;
+                continue;
+            }
+
+            let now = Instant::now();
+            let current = anchor.get_or_insert(Anchor {
+                wall: now,
+                bag: timestamp,
+                rate: state.rate,
+            });
+            if current.rate != state.rate {
+                // Keep the current playback position, continue at the new speed
+                *current = Anchor {
+                    wall: now,
+                    bag: current.bag_time_at(now).min(timestamp),
+                    rate: state.rate,
+                };
+            }
+
+            let due = current.wall_time_of(timestamp);
+            if now >= due {
+                return Wait::Deliver;
+            }
+            state = condvar
+                .wait_timeout(state, due - now)
+                .map(|(state, _)| state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+    }
+}
+
+/// Longest wait for a single message; the wait is cut short by any control change
+const MAX_WAIT: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Mapping between bag time and wall clock time at a given rate
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    wall: Instant,
+    bag: u64,
+    rate: f64,
+}
+
+impl Anchor {
+    /// Wall clock time at which `timestamp` is due, at most [`MAX_WAIT`] after the
+    /// anchor, so tiny rates and huge timestamp gaps cannot overflow
+    fn wall_time_of(&self, timestamp: u64) -> Instant {
+        let offset = timestamp.saturating_sub(self.bag) as f64 / self.rate;
+        let offset = Duration::try_from_secs_f64(offset / 1e9)
+            .unwrap_or(MAX_WAIT)
+            .min(MAX_WAIT);
+        self.wall
+            .checked_add(offset)
+            .unwrap_or(self.wall + Duration::from_secs(1))
+    }
+
+    fn bag_time_at(&self, wall: Instant) -> u64 {
+        let elapsed = wall.saturating_duration_since(self.wall).as_secs_f64();
+        self.bag.saturating_add((elapsed * self.rate * 1e9) as u64)
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    fn reader() -> Reader {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        reader
+    }
+
+    #[test]
+    fn test_stop_from_callback() {
+        let reader = reader();
+        let player = Player::new(&reader).rate(1e6);
+        let controls = player.controls();
+
+        let delivered = player.play(|_| controls.stop()).unwrap();
+        assert_eq!(delivered, 1);
+        assert!(controls.is_stopped());
+    }
+
+    #[test]
+    fn test_looping_and_seek() {
+        let reader = reader();
+        let timestamps: Vec<u64> = reader
+            .messages()
+            .unwrap()
+            .map(|m| m.unwrap().timestamp)
+            .collect();
+        let last = *timestamps.last().unwrap();
+
+        let player = Player::new(&reader).rate(1e6).looping(true);
+        let controls = player.controls();
+        let mut seen = Vec::new();
+        player
+            .play(|msg| {
+                seen.push(msg.timestamp);
+                if seen.len() == 1 {
+                    controls.seek(last);
+                }
+                if seen.len() == 4 {
+                    controls.stop();
+                }
+            })
+            .unwrap();
+
+        // First message, then the last one after seeking, then the loop restarts
+        assert_eq!(seen[1], last);
+        assert_eq!(seen[2], timestamps[0]);
+    }
+
+    #[test]
+    fn test_pacing_respects_rate() {
+        let reader = reader();
+        let connection = reader.connections()[0].clone();
+        let timestamps: Vec<u64> = reader
+            .messages_filtered(Some(std::slice::from_ref(&connection)), None, None)
+            .unwrap()
+            .map(|m| m.unwrap().timestamp)
+            .collect();
+        let span = (timestamps.last().unwrap() - timestamps[0]) as f64 / 1e9;
+        let rate = span / 0.05; // whole topic in ~50 ms
+
+        let started = Instant::now();
+        Player::new(&reader)
+            .topics(&[connection.topic.as_str()])
+            .rate(rate)
+            .play(|_| {})
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(45));
+    }
+
+    #[test]
+    fn test_extreme_rates_do_not_overflow() {
+        let reader = reader();
+        for invalid in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let player = Player::new(&reader).rate(2.0).rate(invalid);
+            assert_eq!(player.controls().rate(), 2.0);
+        }
+
+        let wall = Instant::now();
+        for rate in [f64::MIN_POSITIVE, 1e-300, 1.0, 1e300] {
+            let anchor = Anchor { wall, bag: 0, rate };
+            let due = anchor.wall_time_of(u64::MAX);
+            assert!(due >= wall && due <= wall + MAX_WAIT);
+            anchor.bag_time_at(due);
+        }
+    }
+}
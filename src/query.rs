@@ -0,0 +1,608 @@
+//! Columnar field selection over recorded messages
+//!
+//! A selection such as `["header.stamp", "linear_acceleration.x"]` is compiled once
+//! per connection against the stored message definition. Each message is then walked
+//! with the dynamic decoder, reading only the selected fields and skipping the rest,
+//! so a few values can be pulled from millions of messages without building structs.
+
+use crate::cdr::CdrDeserializer;
+use crate::dynamic::{self, Value, ValueKind};
+use crate::error::{BagError, Result};
+use crate::schema::{ArraySpec, FieldNode, MessageSchemas};
+use crate::types::Connection;
+use std::collections::HashMap;
+
+/// Values of one selected field, one entry per message
+///
+/// Entries are `None` when the field is absent from a message, e.g. an index
+/// past the end of a sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Bool(Vec<Option<bool>>),
+    Int(Vec<Option<i64>>),
+    UInt(Vec<Option<u64>>),
+    Float(Vec<Option<f64>>),
+    String(Vec<Option<String>>),
+}
+
+impl ColumnData {
+    fn new(kind: ValueKind) -> Self {
+        match kind {
+            ValueKind::Bool => ColumnData::Bool(Vec::new()),
+            ValueKind::Int => ColumnData::Int(Vec::new()),
+            ValueKind::UInt => ColumnData::UInt(Vec::new()),
+            ValueKind::Float => ColumnData::Float(Vec::new()),
+            ValueKind::String => ColumnData::String(Vec::new()),
+        }
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Bool(values) => values.len(),
+            ColumnData::Int(values) => values.len(),
+            ColumnData::UInt(values) => values.len(),
+            ColumnData::Float(values) => values.len(),
+            ColumnData::String(values) => values.len(),
+        }
+    }
+
+    /// Whether the column has no rows
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Value at `row` as a [`Value`]
+    pub fn get(&self, row: usize) -> Option<Value> {
+        match self {
+            ColumnData::Bool(values) => values.get(row)?.map(Value::Bool),
+            ColumnData::Int(values) => values.get(row)?.map(Value::Int),
+            ColumnData::UInt(values) => values.get(row)?.map(Value::UInt),
+            ColumnData::Float(values) => values.get(row)?.map(Value::Float),
+            ColumnData::String(values) => values.get(row)?.clone().map(Value::String),
+        }
+    }
+
+    /// Values as `f64`, converting integer and boolean columns
+    pub fn to_f64(&self) -> Vec<Option<f64>> {
+        (0..self.len())
+            .map(|row| self.get(row).and_then(|value| value.as_f64()))
+            .collect()
+    }
+
+    fn push(&mut self, value: Option<Value>) {
+        match (self, value) {
+            (ColumnData::Bool(values), Some(Value::Bool(v))) => values.push(Some(v)),
+            (ColumnData::Int(values), Some(Value::Int(v))) => values.push(Some(v)),
+            (ColumnData::UInt(values), Some(Value::UInt(v))) => values.push(Some(v)),
+            (ColumnData::Float(values), Some(Value::Float(v))) => values.push(Some(v)),
+            (ColumnData::String(values), Some(Value::String(v))) => values.push(Some(v)),
+            (ColumnData::Bool(values), _) => values.push(None),
+            (ColumnData::Int(values), _) => values.push(None),
+            (ColumnData::UInt(values), _) => values.push(None),
+            (ColumnData::Float(values), _) => values.push(None),
+            (ColumnData::String(values), _) => values.push(None),
+        }
+    }
+}
+
+/// A selected field and its values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Field path as requested
+    pub path: String,
+    /// Values, one per message
+    pub data: ColumnData,
+}
+
+/// Result of [`Reader::select`](crate::Reader::select)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Selection {
+    /// Bag timestamp of each row in nanoseconds
+    pub timestamps: Vec<u64>,
+    /// Selected columns in request order
+    pub columns: Vec<Column>,
+}
+
+impl Selection {
+    /// Number of rows (messages)
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Whether no messages were selected
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Look up a column by its path
+    pub fn column(&self, path: &str) -> Option<&ColumnData> {
+        self.columns
+            .iter()
+            .find(|column| column.path == path)
+            .map(|column| &column.data)
+    }
+}
+
+/// Paths of every value a selection can read from messages of the root type
+///
+/// Nested messages are descended into and fixed-size arrays give one path per
+/// element (`covariance[0]`, `covariance[1]`, ..). Sequences, whose length varies
+/// from message to message, are left out. Time and duration fields are one column.
+pub fn column_paths(schemas: &MessageSchemas) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    collect_columns(&schemas.field_tree()?, "", &mut paths);
+    Ok(paths)
+}
+
+fn collect_columns(nodes: &[FieldNode], prefix: &str, paths: &mut Vec<String>) {
+    for node in nodes {
+        let path = format!("{prefix}{}", node.name());
+        let elements = match node.field.array() {
+            None => vec![path],
+            Some(ArraySpec::Fixed(length)) => (0..length).map(|i| format!("{path}[{i}]")).collect(),
+            Some(_) => continue,
+        };
+        for element in elements {
+            if ValueKind::of(node.field.base_type()).is_some() {
+                paths.push(element);
+            } else {
+                collect_columns(&node.children, &format!("{element}."), paths);
+            }
+        }
+    }
+}
+
+/// One step of a field path: `name` or `name[index]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathSegment {
+    name: String,
+    index: Option<usize>,
+}
+
+fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || BagError::schema_validation(format!("Invalid field path '{path}'"));
+    path.split('.')
+        .map(|segment| {
+            let (name, index) = match segment.split_once('[') {
+                Some((name, rest)) => {
+                    let index = rest
+                        .strip_suffix(']')
+                        .and_then(|i| i.parse().ok())
+                        .ok_or_else(invalid)?;
+                    (name, Some(index))
+                }
+                None => (segment, None),
+            };
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            Ok(PathSegment {
+                name: name.to_string(),
+                index,
+            })
+        })
+        .collect()
+}
+
+/// What to do with a selected field (or array element)
+#[derive(Debug)]
+enum Target {
+    /// Read the value into the column with this index
+    Column(usize),
+    /// Descend into a nested message
+    Nested(Plan),
+}
+
+/// Selections on one field of a message
+#[derive(Debug, Default)]
+struct FieldPlan {
+    /// Selection of the field as a whole (non-array fields)
+    whole: Option<Target>,
+    /// Selections of individual elements (array fields), sorted by index
+    elements: Vec<(usize, Option<Target>)>,
+}
+
+impl FieldPlan {
+    fn is_empty(&self) -> bool {
+        self.whole.is_none() && self.elements.is_empty()
+    }
+}
+
+/// Compiled selection for one message type
+#[derive(Debug)]
+struct Plan {
+    type_name: String,
+    fields: Vec<FieldPlan>,
+}
+
+impl Plan {
+    fn new(schemas: &MessageSchemas, type_name: &str) -> Result<Self> {
+        let schema = schemas
+            .get(type_name)
+            .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+        Ok(Self {
+            type_name: schema.name.clone(),
+            fields: schema.fields.iter().map(|_| FieldPlan::default()).collect(),
+        })
+    }
+
+    fn insert(
+        &mut self,
+        schemas: &MessageSchemas,
+        path: &str,
+        segments: &[PathSegment],
+        column: usize,
+    ) -> Result<ValueKind> {
+        let schema = schemas
+            .get(&self.type_name)
+            .ok_or_else(|| BagError::message_type_not_found(&self.type_name))?;
+        let segment = &segments[0];
+        let field_index = schema
+            .fields
+            .iter()
+            .position(|field| field.name == segment.name)
+            .ok_or_else(|| {
+                BagError::schema_validation(format!(
+                    "Field '{}' not found in {} (path '{path}')",
+                    segment.name, schema.name
+                ))
+            })?;
+        let field = &schema.fields[field_index];
+        let base_type = field.base_type();
+
+        match (field.array(), segment.index) {
+            (Some(_), None) => {
+                return Err(BagError::schema_validation(format!(
+                    "Field '{}' is an array; select an element like '{}[0]' (path '{path}')",
+                    segment.name, segment.name
+                )))
+            }
+            (None, Some(_)) => {
+                return Err(BagError::schema_validation(format!(
+                    "Field '{}' is not an array (path '{path}')",
+                    segment.name
+                )))
+            }
+            (Some(ArraySpec::Fixed(length)), Some(index)) if index >= length => {
+                return Err(BagError::schema_validation(format!(
+                    "Index {index} out of bounds for '{}' of length {length} (path '{path}')",
+                    segment.name
+                )))
+            }
+            _ => {}
+        }
+
+        let field_plan = &mut self.fields[field_index];
+        let slot = match segment.index {
+            None => &mut field_plan.whole,
+            Some(index) => {
+                let position = match field_plan.elements.binary_search_by_key(&index, |e| e.0) {
+                    Ok(position) => position,
+                    Err(position) => {
+                        field_plan.elements.insert(position, (index, None));
+                        position
+                    }
+                };
+                &mut field_plan.elements[position].1
+            }
+        };
+
+        match slot {
+            Some(target) => extend_target(target, schemas, path, &segments[1..], column),
+            None => {
+                let (target, kind) = new_target(schemas, path, &segments[1..], base_type, column)?;
+                *slot = Some(target);
+                Ok(kind)
+            }
+        }
+    }
+}
+
+/// Create the target for the remainder of a path
+fn new_target(
+    schemas: &MessageSchemas,
+    path: &str,
+    rest: &[PathSegment],
+    base_type: &str,
+    column: usize,
+) -> Result<(Target, ValueKind)> {
+    if rest.is_empty() {
+        let kind = ValueKind::of(base_type).ok_or_else(|| {
+            BagError::schema_validation(format!(
+                "Path '{path}' selects a {base_type} message; select one of its fields instead"
+            ))
+        })?;
+        return Ok((Target::Column(column), kind));
+    }
+    if ValueKind::of(base_type).is_some() && schemas.get(base_type).is_none() {
+        return Err(BagError::schema_validation(format!(
+            "Path '{path}' descends into primitive type {base_type}"
+        )));
+    }
+    let mut plan = Plan::new(schemas, base_type)?;
+    let kind = plan.insert(schemas, path, rest, column)?;
+    Ok((Target::Nested(plan), kind))
+}
+
+/// Add a path to an existing target
+fn extend_target(
+    target: &mut Target,
+    schemas: &MessageSchemas,
+    path: &str,
+    rest: &[PathSegment],
+    column: usize,
+) -> Result<ValueKind> {
+    match target {
+        Target::Nested(plan) if !rest.is_empty() => plan.insert(schemas, path, rest, column),
+        _ => Err(BagError::schema_validation(format!(
+            "Path '{path}' overlaps another selected path"
+        ))),
+    }
+}
+
+/// Walk one message, storing selected values into `row`
+fn execute(
+    plan: &Plan,
+    schemas: &MessageSchemas,
+    deserializer: &mut CdrDeserializer,
+    row: &mut [Option<Value>],
+) -> Result<()> {
+    let schema = schemas
+        .get(&plan.type_name)
+        .ok_or_else(|| BagError::message_type_not_found(&plan.type_name))?;
+    if schema.fields.is_empty() {
+        deserializer.read_u8()?;
+        return Ok(());
+    }
+
+    for (field, field_plan) in schema.fields.iter().zip(&plan.fields) {
+        if field_plan.is_empty() {
+            dynamic::skip_field(deserializer, schemas, field)?;
+            continue;
+        }
+
+        let base_type = field.base_type();
+        let Some(array) = field.array() else {
+            if let Some(target) = &field_plan.whole {
+                execute_target(target, schemas, deserializer, base_type, row)?;
+            }
+            continue;
+        };
+
+        let count = match array {
+            ArraySpec::Fixed(length) => length,
+            _ => deserializer.read_length(1)?,
+        };
+        let mut next = 0;
+        for (index, target) in &field_plan.elements {
+            let Some(target) = target else {
+                continue;
+            };
+            if *index >= count {
+                break;
+            }
+            dynamic::skip_elements(deserializer, schemas, base_type, index - next)?;
+            execute_target(target, schemas, deserializer, base_type, row)?;
+            next = index + 1;
+        }
+        dynamic::skip_elements(deserializer, schemas, base_type, count.saturating_sub(next))?;
+    }
+    Ok(())
+}
+
+fn execute_target(
+    target: &Target,
+    schemas: &MessageSchemas,
+    deserializer: &mut CdrDeserializer,
+    base_type: &str,
+    row: &mut [Option<Value>],
+) -> Result<()> {
+    match target {
+        Target::Column(column) => {
+            row[*column] = Some(dynamic::read_value(deserializer, base_type)?);
+        }
+        Target::Nested(plan) => {
+            deserializer.nested(|deserializer| execute(plan, schemas, deserializer, row))?
+        }
+    }
+    Ok(())
+}
+
+/// Compiled selection for one connection
+pub(crate) struct ConnectionQuery {
+    schemas: MessageSchemas,
+    plan: Plan,
+    kinds: Vec<ValueKind>,
+}
+
+impl ConnectionQuery {
+    /// Compile `paths` against the connection's message definition
+    pub(crate) fn compile(connection: &Connection, paths: &[&str]) -> Result<Self> {
+        let schemas = connection.message_schemas()?;
+        let mut plan = Plan::new(&schemas, &connection.message_type)?;
+        let mut kinds = Vec::with_capacity(paths.len());
+        for (column, path) in paths.iter().enumerate() {
+            let segments = parse_path(path)?;
+            kinds.push(plan.insert(&schemas, path, &segments, column)?);
+        }
+        Ok(Self {
+            schemas,
+            plan,
+            kinds,
+        })
+    }
+
+    /// Decode the selected values of one serialized message
+    pub(crate) fn decode(&self, data: &[u8]) -> Result<Vec<Option<Value>>> {
+        let mut row = vec![None; self.kinds.len()];
+        let mut deserializer = CdrDeserializer::new(data)?;
+        execute(&self.plan, &self.schemas, &mut deserializer, &mut row)?;
+        Ok(row)
+    }
+}
+
+/// Accumulates decoded rows into a [`Selection`]
+pub(crate) struct SelectionBuilder {
+    selection: Selection,
+    kinds: Option<Vec<ValueKind>>,
+}
+
+impl SelectionBuilder {
+    pub(crate) fn new(paths: &[&str]) -> Self {
+        Self {
+            selection: Selection {
+                timestamps: Vec::new(),
+                columns: paths
+                    .iter()
+                    .map(|path| Column {
+                        path: path.to_string(),
+                        data: ColumnData::Bool(Vec::new()),
+                    })
+                    .collect(),
+            },
+            kinds: None,
+        }
+    }
+
+    /// Register the column kinds of a compiled query; all connections must agree
+    pub(crate) fn check_kinds(&mut self, query: &ConnectionQuery) -> Result<()> {
+        match &self.kinds {
+            None => {
+                for (column, kind) in self.selection.columns.iter_mut().zip(&query.kinds) {
+                    column.data = ColumnData::new(*kind);
+                }
+                self.kinds = Some(query.kinds.clone());
+                Ok(())
+            }
+            Some(kinds) if *kinds == query.kinds => Ok(()),
+            Some(_) => Err(BagError::schema_validation(
+                "Selected fields have different types across connections of the topic",
+            )),
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: u64, row: Vec<Option<Value>>) {
+        self.selection.timestamps.push(timestamp);
+        for (column, value) in self.selection.columns.iter_mut().zip(row) {
+            column.data.push(value);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Selection {
+        self.selection
+    }
+}
+
+/// Compile one query per connection, keyed by connection id
+pub(crate) fn compile_queries(
+    connections: &[Connection],
+    paths: &[&str],
+    builder: &mut SelectionBuilder,
+) -> Result<HashMap<u32, ConnectionQuery>> {
+    let mut queries = HashMap::new();
+    for connection in connections {
+        let query = ConnectionQuery::compile(connection, paths)?;
+        builder.check_kinds(&query)?;
+        queries.insert(connection.id, query);
+    }
+    Ok(queries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITION: &str = "std_msgs/Header header
+float64[4] values
+geometry_msgs/Point[] points
+================================================================================
+MSG: std_msgs/Header
+builtin_interfaces/Time stamp
+string frame_id
+================================================================================
+MSG: builtin_interfaces/Time
+int32 sec
+uint32 nanosec
+================================================================================
+MSG: geometry_msgs/Point
+float64 x
+float64 y
+float64 z
+";
+
+    fn message() -> Vec<u8> {
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.extend_from_slice(&3i32.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"map\0");
+        // values at offset 16
+        for value in [1.0f64, 2.0, 3.0, 4.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0; 4]); // align to offset 56
+        for value in [10.0f64, 11.0, 12.0, 20.0, 21.0, 22.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    fn compile(paths: &[&str]) -> Result<ConnectionQuery> {
+        let schemas = MessageSchemas::parse_msg("pkg/msg/Sample", DEFINITION)?;
+        let mut plan = Plan::new(&schemas, "pkg/msg/Sample")?;
+        let mut kinds = Vec::new();
+        for (column, path) in paths.iter().enumerate() {
+            kinds.push(plan.insert(&schemas, path, &parse_path(path)?, column)?);
+        }
+        Ok(ConnectionQuery {
+            schemas,
+            plan,
+            kinds,
+        })
+    }
+
+    #[test]
+    fn test_select_nested_and_indexed_fields() {
+        let query = compile(&[
+            "header.stamp",
+            "header.frame_id",
+            "values[2]",
+            "points[1].y",
+            "points[5].x",
+        ])
+        .unwrap();
+        assert_eq!(
+            query.kinds,
+            vec![
+                ValueKind::Int,
+                ValueKind::String,
+                ValueKind::Float,
+                ValueKind::Float,
+                ValueKind::Float
+            ]
+        );
+
+        let row = query.decode(&message()).unwrap();
+        assert_eq!(
+            row,
+            vec![
+                Some(Value::Int(3_000_000_007)),
+                Some(Value::String("map".to_string())),
+                Some(Value::Float(3.0)),
+                Some(Value::Float(21.0)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_paths_rejected() {
+        assert!(compile(&["header"]).is_err());
+        assert!(compile(&["values"]).is_err());
+        assert!(compile(&["values[4]"]).is_err());
+        assert!(compile(&["missing"]).is_err());
+        assert!(compile(&["header.stamp", "header.stamp.sec"]).is_err());
+    }
+}
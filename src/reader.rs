@@ -1,16 +1,466 @@
 //! Main reader implementation for ROS2 bag files
 
-use crate::error::{ReaderError, Result};
-use crate::metadata::BagMetadata;
-use crate::storage::{create_storage_reader, StorageReader};
-use crate::types::{Connection, Message, MessageDefinition, RawMessage, TopicInfo};
-use std::collections::HashMap;
+use crate::analysis::{Histogram, StampSkew};
+use crate::cdr::CdrDeserializer;
+use crate::dynamic::{decode_message, DynamicMessage};
+use crate::error::{ErrorContext, ReaderError, Result};
+use crate::metadata::{sha256_file, BagMetadata, BagStatistics, ChecksumMismatch, FileInformation};
+use crate::query::{self, Selection, SelectionBuilder};
+use crate::registry::TypeRegistry;
+use crate::schema::{FieldNode, MessageSchema, MessageSchemas, TYPE_HASH_PREFIX};
+use crate::shard::Shard;
+use crate::storage::{create_storage_reader_from_inputs, memory, StorageInput, StorageReader};
+use crate::types::{
+    glob_match, Attachment, BagFile, CompressionFormat, Connection, Duration, McapMetadata,
+    Message, MessageDefinition, MessageDefinitionFormat, RawMessage, RawMessageRef, StartingTime,
+    StoragePlugin, TopicInfo, TopicPattern,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Open-time configuration of a [`Reader`]
+///
+/// Set the fields directly or use [`Reader::builder`]. The topic allowlist and time
+/// range apply to every iteration method whose corresponding argument is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderOptions {
+    /// Storage plugin to use instead of the one named in `metadata.yaml`
+    ///
+    /// With [`ReaderOptions::skip_metadata`], only files of this storage are read.
+    pub storage: Option<StoragePlugin>,
+    /// Ignore `metadata.yaml` and discover the bag from its storage files
+    ///
+    /// The `.db3` or `.mcap` files of the bag directory are read in name order, and
+    /// topics, message counts and times are taken from the storage when the reader
+    /// is opened. Useful for bags whose recording was interrupted before the metadata
+    /// was written.
+    pub skip_metadata: bool,
+    /// Access storage files through memory maps instead of read syscalls
+    ///
+    /// SQLite3 databases are mapped by SQLite itself (`PRAGMA mmap_size`). MCAP files
+    /// are mapped with `memmap2`, which requires the `unsafe-opt` feature; without it
+    /// they are read into memory as usual. Enabled by default with `unsafe-opt`.
+    pub memory_map: bool,
+    /// Maximum number of bytes of each SQLite3 database that is mapped
+    ///
+    /// SQLite clamps the value to its compile-time limit (2 GiB for the bundled
+    /// library); the remainder of larger databases is read with syscalls.
+    pub sqlite_mmap_size: u64,
+    /// Decompress the payloads of bags compressed per message (default: on)
+    ///
+    /// When off, payloads are returned as stored, e.g. to copy them unchanged.
+    pub decode_on_read: bool,
+    /// Only expose these topics; others are left out of [`Reader::connections`] and
+    /// iteration. Topics missing from the bag are ignored.
+    pub topics: Option<Vec<String>>,
+    /// Default start of iteration in nanoseconds (inclusive)
+    pub start: Option<u64>,
+    /// Default stop of iteration in nanoseconds (exclusive)
+    pub stop: Option<u64>,
+    /// Check stored type description hashes against the stored message definitions
+    /// when opening (default: off)
+    pub verify_type_hashes: TypeHashVerification,
+    /// Definitions for connections whose storage has none (e.g. custom types in bags
+    /// recorded before rosbag2 stored definitions)
+    pub type_registry: Option<TypeRegistry>,
+    /// What iteration does with messages that cannot be read, decompressed or, by
+    /// [`Reader::select`], decoded (default: [`ErrorPolicy::Fail`])
+    pub on_error: ErrorPolicy,
+    /// Load message definitions on first use instead of when opening (default: off)
+    ///
+    /// [`Reader::connections`] then carry no definitions; [`Reader::message_definition`],
+    /// [`Reader::decode_dynamic`] and [`Reader::select`] load the definition of a type
+    /// from the storage the first time it is needed. Speeds up opening bags with many
+    /// custom types when only a few topics are decoded. Ignored when type hashes are
+    /// verified, which needs every definition.
+    pub lazy_definitions: bool,
+    /// Worker threads decompressing MCAP chunks (default: 1, on the reading thread)
+    ///
+    /// Chunks of files with a chunk index are decompressed in parallel and their
+    /// messages kept in order, which speeds up reading compressed bags on machines
+    /// with several cores. `0` uses one thread per available core. SQLite3 bags are
+    /// read on the calling thread regardless.
+    pub decode_threads: usize,
+    /// Bytes of decompressed chunks kept for repeated range queries (default: 0, off)
+    ///
+    /// Decompressed MCAP chunks are kept in a least recently used cache, so tools that
+    /// read the same time window again, e.g. when scrubbing back and forth, skip
+    /// decompressing its chunks again. SQLite3 databases get the same budget as page
+    /// cache (`PRAGMA cache_size`).
+    pub chunk_cache_size: u64,
+}
+
+/// What [`Reader::open`] does when a stored type description hash does not match
+/// the hash of the stored message definition
+///
+/// Only topics with an `RIHS01` hash (ROS 2 Iron and later) and a `.msg` definition
+/// are checked. A mismatch means the bag was recorded with a different version of the
+/// message than the one whose definition it stores, so decoding may misparse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeHashVerification {
+    /// Do not check hashes
+    #[default]
+    Off,
+    /// Print a warning for every mismatch
+    Warn,
+    /// Fail to open with [`ReaderError::TypeHashMismatch`]
+    Error,
+}
+
+/// What a [`Reader`] does with messages and records that cannot be read or decoded
+///
+/// With `Skip` and `CollectReport`, a corrupt message, MCAP chunk or SQLite3 database
+/// only loses its own messages: iteration continues with the rest of the bag and
+/// [`Reader::take_skip_report`] tells what was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Yield the error as an item of the iteration
+    #[default]
+    Fail,
+    /// Drop what cannot be read and continue, counting it in the report
+    Skip,
+    /// Drop what cannot be read and continue, keeping its error in the report
+    CollectReport,
+}
+
+/// Messages and records dropped under [`ErrorPolicy::Skip`] or
+/// [`ErrorPolicy::CollectReport`], returned by [`Reader::take_skip_report`]
+#[derive(Debug, Default)]
+pub struct SkipReport {
+    /// Number of dropped messages and unreadable records (MCAP chunks, databases)
+    pub skipped: usize,
+    /// Errors of what was dropped, with their [`ErrorContext`]
+    /// (only with [`ErrorPolicy::CollectReport`])
+    pub errors: Vec<ReaderError>,
+}
+
+impl SkipReport {
+    /// Whether nothing was dropped
+    pub fn is_empty(&self) -> bool {
+        self.skipped == 0
+    }
+}
+
+/// Selection of messages for [`Reader::messages_with`] and [`Reader::raw_messages_with`]
+///
+/// A message passes when its topic is one of `topics` or matches one of
+/// `topic_patterns` (globs or regular expressions, see [`TopicPattern`]), its type is
+/// one of `message_types` and its connection is one of `connection_ids`; an empty
+/// list does not restrict. Each storage only reads the selected topics in the time
+/// range: a filter on topics alone is handed to the storage, which matches the
+/// patterns in its SQL query or once per MCAP channel; other filters are resolved to
+/// connections first.
+///
+/// # Example
+/// ```no_run
+/// # use rosbags_rs::{MessageFilter, Reader, TopicPattern};
+/// # let reader = Reader::builder("bag").open()?;
+/// let filter = MessageFilter::new()
+///     .topic("/imu")
+///     .topic_pattern("/camera/**")
+///     .topic_pattern(TopicPattern::regex(r"/lidar_\d+/points")?)
+///     .time_range(Some(1_700_000_000_000_000_000), None);
+/// for message in reader.messages_with(&filter)? {
+///     println!("{}", message?.topic);
+/// }
+/// # Ok::<(), rosbags_rs::ReaderError>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageFilter {
+    /// Topic names
+    pub topics: Vec<String>,
+    /// Patterns of topic names
+    pub topic_patterns: Vec<TopicPattern>,
+    /// Message types, e.g. `sensor_msgs/msg/Image`
+    pub message_types: Vec<String>,
+    /// Connection IDs
+    pub connection_ids: Vec<u32>,
+    /// Start in nanoseconds (inclusive)
+    pub start: Option<u64>,
+    /// Stop in nanoseconds (exclusive)
+    pub stop: Option<u64>,
+}
+
+impl MessageFilter {
+    /// Create a filter passing every message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pass messages on `topic`
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topics.push(topic.into());
+        self
+    }
+
+    /// Pass messages on `topics`
+    pub fn topics<I, S>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.topics.extend(topics.into_iter().map(Into::into));
+        self
+    }
+
+    /// Pass messages on topics matching `pattern`, a glob if given as a string
+    pub fn topic_pattern(mut self, pattern: impl Into<TopicPattern>) -> Self {
+        self.topic_patterns.push(pattern.into());
+        self
+    }
+
+    /// Pass messages of type `message_type`
+    pub fn message_type(mut self, message_type: impl Into<String>) -> Self {
+        self.message_types.push(message_type.into());
+        self
+    }
+
+    /// Pass messages of the connection `id`
+    pub fn connection_id(mut self, id: u32) -> Self {
+        self.connection_ids.push(id);
+        self
+    }
+
+    /// Pass messages from `start` (inclusive) to `stop` (exclusive)
+    pub fn time_range(mut self, start: Option<u64>, stop: Option<u64>) -> Self {
+        self.start = start;
+        self.stop = stop;
+        self
+    }
+
+    /// Whether the messages of `connection` pass the filter, ignoring the time range
+    pub fn matches(&self, connection: &Connection) -> bool {
+        let topic_matches = (self.topics.is_empty() && self.topic_patterns.is_empty())
+            || self.topics.contains(&connection.topic)
+            || self
+                .topic_patterns
+                .iter()
+                .any(|pattern| pattern.matches(&connection.topic));
+        topic_matches
+            && (self.message_types.is_empty()
+                || self.message_types.contains(&connection.message_type))
+            && (self.connection_ids.is_empty() || self.connection_ids.contains(&connection.id))
+    }
+
+    /// Whether the filter passes messages of every connection
+    fn passes_all_connections(&self) -> bool {
+        self.topics.is_empty()
+            && self.topic_patterns.is_empty()
+            && self.message_types.is_empty()
+            && self.connection_ids.is_empty()
+    }
+
+    /// The topic criteria as patterns, if topic patterns are the only criteria
+    fn only_topic_patterns(&self) -> Result<Option<Vec<TopicPattern>>> {
+        if self.topic_patterns.is_empty()
+            || !self.message_types.is_empty()
+            || !self.connection_ids.is_empty()
+        {
+            return Ok(None);
+        }
+        let mut patterns = self.topic_patterns.clone();
+        for topic in &self.topics {
+            patterns.push(TopicPattern::regex(&regex::escape(topic))?);
+        }
+        Ok(Some(patterns))
+    }
+}
+
+/// Topics read for a [`MessageFilter`]
+enum FilterTopics {
+    /// All topics
+    All,
+    /// The topics of these connections
+    Connections(Vec<Connection>),
+    /// Topics matching these patterns, selected by the storage
+    Patterns(Vec<TopicPattern>),
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            storage: None,
+            skip_metadata: false,
+            memory_map: cfg!(feature = "unsafe-opt"),
+            sqlite_mmap_size: Self::DEFAULT_SQLITE_MMAP_SIZE,
+            decode_on_read: true,
+            topics: None,
+            start: None,
+            stop: None,
+            verify_type_hashes: TypeHashVerification::Off,
+            type_registry: None,
+            on_error: ErrorPolicy::Fail,
+            lazy_definitions: false,
+            decode_threads: 1,
+            chunk_cache_size: 0,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Default [`ReaderOptions::sqlite_mmap_size`] (2 GiB)
+    pub const DEFAULT_SQLITE_MMAP_SIZE: u64 = 1 << 31;
+
+    /// Options that memory-map the storage files
+    pub fn memory_mapped() -> Self {
+        Self {
+            memory_map: true,
+            ..Self::default()
+        }
+    }
+
+    /// Number of threads decompressing MCAP chunks, resolving `0` to the available
+    /// parallelism
+    pub fn decode_threads(&self) -> usize {
+        match self.decode_threads {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            threads => threads,
+        }
+    }
+}
+
+/// Builder for a [`Reader`] configured at open time
+///
+/// Created by [`Reader::builder`].
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::{Reader, StoragePlugin};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = Reader::builder("path/to/bag")
+///     .storage(StoragePlugin::Mcap)
+///     .skip_metadata(true)
+///     .topics(["/imu/data", "/gps/fix"])
+///     .time_range(Some(1_700_000_000_000_000_000), None)
+///     .open()?;
+///
+/// for message in reader.messages()? {
+///     println!("{} at {}", message?.topic, reader.start_time());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReaderBuilder {
+    bag_path: PathBuf,
+    options: ReaderOptions,
+}
+
+impl ReaderBuilder {
+    /// Use `storage` instead of the storage plugin named in `metadata.yaml`
+    pub fn storage(mut self, storage: StoragePlugin) -> Self {
+        self.options.storage = Some(storage);
+        self
+    }
+
+    /// Ignore `metadata.yaml` and discover the bag from its storage files
+    pub fn skip_metadata(mut self, skip_metadata: bool) -> Self {
+        self.options.skip_metadata = skip_metadata;
+        self
+    }
+
+    /// Access storage files through memory maps
+    pub fn memory_map(mut self, memory_map: bool) -> Self {
+        self.options.memory_map = memory_map;
+        self
+    }
+
+    /// Set the maximum number of bytes of each SQLite3 database that is mapped
+    pub fn sqlite_mmap_size(mut self, sqlite_mmap_size: u64) -> Self {
+        self.options.sqlite_mmap_size = sqlite_mmap_size;
+        self
+    }
+
+    /// Decompress the payloads of bags compressed per message
+    pub fn decode_on_read(mut self, decode_on_read: bool) -> Self {
+        self.options.decode_on_read = decode_on_read;
+        self
+    }
+
+    /// Only expose `topics`
+    pub fn topics<I, S>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.topics = Some(topics.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the default time range of iteration (`start` inclusive, `stop` exclusive)
+    pub fn time_range(mut self, start: Option<u64>, stop: Option<u64>) -> Self {
+        self.options.start = start;
+        self.options.stop = stop;
+        self
+    }
+
+    /// Check stored type description hashes when opening
+    pub fn verify_type_hashes(mut self, verification: TypeHashVerification) -> Self {
+        self.options.verify_type_hashes = verification;
+        self
+    }
+
+    /// Take the definitions of connections without a stored one from `registry`
+    pub fn type_registry(mut self, registry: TypeRegistry) -> Self {
+        self.options.type_registry = Some(registry);
+        self
+    }
+
+    /// Set what iteration does with messages that cannot be read or decoded
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.options.on_error = policy;
+        self
+    }
+
+    /// Load message definitions on first use instead of when opening
+    pub fn lazy_definitions(mut self, lazy_definitions: bool) -> Self {
+        self.options.lazy_definitions = lazy_definitions;
+        self
+    }
+
+    /// Decompress MCAP chunks on `threads` worker threads (`0`: one per core)
+    pub fn decode_threads(mut self, threads: usize) -> Self {
+        self.options.decode_threads = threads;
+        self
+    }
+
+    /// Keep up to `bytes` of decompressed chunks for repeated range queries
+    pub fn chunk_cache_size(mut self, bytes: u64) -> Self {
+        self.options.chunk_cache_size = bytes;
+        self
+    }
+
+    /// Get the options configured so far
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Create the reader without opening it
+    pub fn build(self) -> Result<Reader> {
+        Reader::with_options(self.bag_path, self.options)
+    }
+
+    /// Create and open the reader
+    pub fn open(self) -> Result<Reader> {
+        let mut reader = self.build()?;
+        reader.open()?;
+        Ok(reader)
+    }
+}
+
+/// Connections, start and stop arguments of an iteration method
+type Filters<'a> = (Option<&'a [Connection]>, Option<u64>, Option<u64>);
 
 /// Main reader for ROS2 bag files
 pub struct Reader {
-    /// Path to the bag directory
+    /// Path to the bag directory or single storage file (empty for readers over
+    /// storage inputs)
     bag_path: PathBuf,
+    /// Storage files read instead of those listed in the metadata
+    inputs: Option<Vec<StorageInput>>,
     /// Parsed metadata
     metadata: Option<BagMetadata>,
     /// Storage backend
@@ -19,31 +469,177 @@ pub struct Reader {
     connections: Vec<Connection>,
     /// Whether the reader is currently open
     is_open: bool,
+    /// Storage access options
+    options: ReaderOptions,
+    /// Messages dropped under `ReaderOptions::on_error`
+    skip_report: Mutex<SkipReport>,
+    /// Definitions loaded on first use under `ReaderOptions::lazy_definitions`
+    definitions: Mutex<HashMap<String, MessageDefinition>>,
+    /// Schemas parsed by the first decode of each message type
+    schemas: Mutex<HashMap<String, Arc<MessageSchemas>>>,
 }
 
 impl Reader {
     /// Create a new reader for the given bag path
+    ///
+    /// The path is a bag directory, or a single `.db3` or `.mcap` file. A single file
+    /// is read without `metadata.yaml`: topics, message count and times come from the
+    /// storage as with [`ReaderOptions::skip_metadata`].
     pub fn new<P: AsRef<Path>>(bag_path: P) -> Result<Self> {
+        Self::with_options(bag_path, ReaderOptions::default())
+    }
+
+    /// Start configuring a reader for the given bag path
+    pub fn builder<P: AsRef<Path>>(bag_path: P) -> ReaderBuilder {
+        ReaderBuilder {
+            bag_path: bag_path.as_ref().to_path_buf(),
+            options: ReaderOptions::default(),
+        }
+    }
+
+    /// Create a new reader for the given bag path with open-time options
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::{Reader, ReaderOptions};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = Reader::with_options("path/to/bag", ReaderOptions::memory_mapped())?;
+    /// reader.open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options<P: AsRef<Path>>(bag_path: P, options: ReaderOptions) -> Result<Self> {
         let bag_path = bag_path.as_ref().to_path_buf();
 
-        // Check if the bag directory exists
-        if !bag_path.exists() {
+        // Check if the bag directory exists, on disk or in memory
+        if !bag_path.exists() && !memory::contains(&bag_path) {
             return Err(ReaderError::BagNotFound { path: bag_path });
         }
 
-        // Load metadata
-        let metadata_path = bag_path.join("metadata.yaml");
-        let metadata = BagMetadata::from_file(&metadata_path)?;
+        // A single storage file is read like a bag of only that file
+        if bag_path.is_file() {
+            let input = StorageInput::Path(bag_path.clone());
+            let mut reader = Self::from_inputs(vec![input], None, options)?;
+            reader.bag_path = bag_path;
+            return Ok(reader);
+        }
+
+        let metadata = Self::load_metadata(&bag_path, &options)?;
 
         Ok(Self {
             bag_path,
+            inputs: None,
             metadata: Some(metadata),
             storage: None,
             connections: Vec::new(),
             is_open: false,
+            options,
+            skip_report: Mutex::new(SkipReport::default()),
+            definitions: Mutex::new(HashMap::new()),
+            schemas: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Create a reader over storage files on disk or in memory, e.g. read from an
+    /// archive or a network stream
+    ///
+    /// The files are read instead of those listed in `metadata`. Without metadata,
+    /// topics, message count and times are read from the storage as with
+    /// [`ReaderOptions::skip_metadata`], and the format is read from the file contents
+    /// (or else the file names).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::{Reader, ReaderOptions, StorageInput};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bytes = std::fs::read("path/to/bag/bag_0.mcap")?;
+    /// let input = StorageInput::from_reader("bag_0.mcap", std::io::Cursor::new(bytes))?;
+    /// let mut reader = Reader::from_inputs(vec![input], None, ReaderOptions::default())?;
+    /// reader.open()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_inputs(
+        inputs: Vec<StorageInput>,
+        metadata: Option<BagMetadata>,
+        mut options: ReaderOptions,
+    ) -> Result<Self> {
+        let metadata = match metadata {
+            Some(mut metadata) => {
+                if let Some(storage) = options.storage {
+                    metadata.rosbag2_bagfile_information.storage_identifier =
+                        storage.as_str().to_string();
+                }
+                metadata
+            }
+            None => {
+                options.skip_metadata = true;
+                let files = inputs.iter().filter_map(|input| {
+                    let name = input.path().to_string_lossy().into_owned();
+                    Some((name, input.format()?))
+                });
+                BagMetadata::from_storage_file_formats(files, options.storage)?
+            }
+        };
+
+        Ok(Self {
+            bag_path: PathBuf::new(),
+            inputs: Some(inputs),
+            metadata: Some(metadata),
+            storage: None,
+            connections: Vec::new(),
+            is_open: false,
+            options,
+            skip_report: Mutex::new(SkipReport::default()),
+            definitions: Mutex::new(HashMap::new()),
+            schemas: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Load `metadata.yaml`, or list the storage files if the options skip it
+    fn load_metadata(bag_path: &Path, options: &ReaderOptions) -> Result<BagMetadata> {
+        if memory::contains(bag_path) {
+            return if options.skip_metadata {
+                BagMetadata::from_storage_file_names(memory::file_names(bag_path), options.storage)
+            } else {
+                let yaml =
+                    memory::metadata(bag_path).ok_or_else(|| ReaderError::MetadataNotFound {
+                        path: bag_path.join("metadata.yaml"),
+                    })?;
+                BagMetadata::from_yaml(&yaml)
+            };
+        }
+        if options.skip_metadata {
+            BagMetadata::from_storage_files(bag_path, options.storage)
+        } else {
+            BagMetadata::from_file_with_storage(bag_path.join("metadata.yaml"), options.storage)
+        }
+    }
+
+    /// Get the open-time options
+    pub fn options(&self) -> &ReaderOptions {
+        &self.options
+    }
+
+    /// Set the open-time options (only before opening)
+    ///
+    /// Readers over storage inputs keep their metadata, and `skip_metadata` as it was.
+    pub fn set_options(&mut self, mut options: ReaderOptions) -> Result<()> {
+        if self.is_open {
+            return Err(ReaderError::generic(
+                "Cannot change reader options after opening",
+            ));
+        }
+        match self.inputs {
+            Some(_) => options.skip_metadata = self.options.skip_metadata,
+            None => self.metadata = Some(Self::load_metadata(&self.bag_path, &options)?),
+        }
+        self.options = options;
+        Ok(())
+    }
+
     /// Open the bag for reading
     pub fn open(&mut self) -> Result<()> {
         if self.is_open {
@@ -59,44 +655,46 @@ impl Reader {
             .iter()
             .enumerate()
             .map(|(idx, topic)| {
-                let qos_profiles = match &topic.topic_metadata.offered_qos_profiles {
-                    crate::metadata::QosProfilesField::String(_) => Vec::new(),
-                    crate::metadata::QosProfilesField::List(profiles) => profiles.clone(),
-                };
+                let qos_profiles = topic.topic_metadata.offered_qos_profiles.profiles();
 
-                Connection {
-                    id: (idx + 1) as u32,
-                    topic: topic.topic_metadata.name.clone(),
-                    message_type: topic.topic_metadata.message_type.clone(),
-                    message_definition: MessageDefinition::default(),
-                    type_description_hash: topic.topic_metadata.type_description_hash.clone(),
-                    message_count: topic.message_count,
-                    serialization_format: topic.topic_metadata.serialization_format.clone(),
-                    offered_qos_profiles: qos_profiles,
-                }
+                Connection::builder(
+                    topic.topic_metadata.name.clone(),
+                    topic.topic_metadata.message_type.clone(),
+                )
+                .id((idx + 1) as u32)
+                .type_description_hash(topic.topic_metadata.type_description_hash.clone())
+                .message_count(topic.message_count)
+                .serialization_format(topic.topic_metadata.serialization_format.clone())
+                .offered_qos_profiles(qos_profiles)
+                .build()
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        // Resolve storage file paths
-        let storage_paths: Vec<PathBuf> = info
-            .relative_file_paths
-            .iter()
-            .map(|path| self.bag_path.join(path))
-            .collect();
+        // Resolve storage files, given directly or listed in the metadata
+        let inputs = match &self.inputs {
+            Some(inputs) => inputs.clone(),
+            None => info
+                .relative_file_paths
+                .iter()
+                .map(|path| StorageInput::Path(self.bag_path.join(path)))
+                .collect(),
+        };
 
         // Check that all storage files exist
-        for path in &storage_paths {
-            if !path.exists() {
-                return Err(ReaderError::StorageFileNotFound { path: path.clone() });
+        for input in &inputs {
+            if let StorageInput::Path(path) = input {
+                if !path.exists() && !memory::contains_file(path) {
+                    return Err(ReaderError::StorageFileNotFound { path: path.clone() });
+                }
             }
         }
 
         // Create storage reader
-        let storage_path_refs: Vec<&Path> = storage_paths.iter().map(|p| p.as_path()).collect();
-        let mut storage = create_storage_reader(
+        let mut storage = create_storage_reader_from_inputs(
             &info.storage_identifier,
-            storage_path_refs,
+            inputs,
             self.connections.clone(),
+            &self.options,
         )?;
 
         // Open storage
@@ -109,7 +707,22 @@ impl Reader {
             .downcast_ref::<crate::storage::sqlite::SqliteReader>()
         {
             match sqlite_storage.get_topics_from_database() {
-                Ok(db_connections) => {
+                Ok(mut db_connections) => {
+                    // Topics are read from the database without their type hash
+                    for db_conn in &mut db_connections {
+                        if let Some(metadata_conn) = self.connections.iter().find(|c| {
+                            c.topic == db_conn.topic && c.message_type == db_conn.message_type
+                        }) {
+                            if db_conn.type_description_hash.is_empty() {
+                                db_conn.type_description_hash =
+                                    metadata_conn.type_description_hash.clone();
+                            }
+                            if db_conn.offered_qos_profiles.is_empty() {
+                                db_conn.offered_qos_profiles =
+                                    metadata_conn.offered_qos_profiles.clone();
+                            }
+                        }
+                    }
                     if !db_connections.is_empty() {
                         // Use database connections if available (more reliable)
                         self.connections = db_connections;
@@ -135,13 +748,20 @@ impl Reader {
                         // This gives us the correct ROS2 message types from metadata.yaml
                         // but accurate message counts from the actual MCAP file
                         for mcap_conn in &mcap_connections {
-                            if let Some(metadata_conn) = self
-                                .connections
-                                .iter_mut()
-                                .find(|c| c.topic == mcap_conn.topic)
-                            {
+                            if let Some(metadata_conn) = self.connections.iter_mut().find(|c| {
+                                c.topic == mcap_conn.topic
+                                    && c.message_type == mcap_conn.message_type
+                            }) {
                                 // Update message count from MCAP (more accurate)
                                 metadata_conn.message_count = mcap_conn.message_count;
+                                // The channel metadata keeps the strings as recorded
+                                if metadata_conn.metadata.is_empty() {
+                                    metadata_conn.metadata = mcap_conn.metadata.clone();
+                                }
+                                if metadata_conn.offered_qos_profiles.is_empty() {
+                                    metadata_conn.offered_qos_profiles =
+                                        mcap_conn.offered_qos_profiles.clone();
+                                }
                             } else {
                                 // Topic exists in MCAP but not in metadata - add it
                                 self.connections.push(mcap_conn.clone());
@@ -162,14 +782,49 @@ impl Reader {
             }
         }
 
-        // Get message definitions from storage and update connections
-        let definitions = storage.get_definitions()?;
-        for connection in &mut self.connections {
-            if let Some(def) = definitions.get(&connection.message_type) {
-                connection.message_definition = def.clone();
+        // In-memory storage keeps the connections as written
+        if let Some(memory_storage) = storage.as_any().downcast_ref::<memory::MemoryReader>() {
+            if !memory_storage.connections().is_empty() {
+                self.connections = memory_storage.connections().to_vec();
+            }
+        }
+
+        // Get message definitions from storage and update connections, unless they
+        // are loaded on first use
+        if !self.lazy_definitions() {
+            let definitions = storage.get_definitions()?;
+            let registry = self.options.type_registry.as_ref();
+            for connection in &mut self.connections {
+                let definition = definitions
+                    .get(&connection.message_type)
+                    .cloned()
+                    .unwrap_or_else(|| connection.message_definition.clone());
+                connection.message_definition =
+                    with_registry_fallback(registry, &connection.message_type, definition)?;
             }
         }
 
+        if self.options.skip_metadata {
+            let time_range = storage.time_range()?;
+            if let Some(metadata) = self.metadata.as_mut() {
+                metadata.fill_from_storage(&self.connections, time_range);
+            }
+        }
+        if let Some(topics) = &self.options.topics {
+            self.connections.retain(|c| topics.contains(&c.topic));
+        }
+
+        let mismatches = match self.options.verify_type_hashes {
+            TypeHashVerification::Off => Vec::new(),
+            _ => type_hash_mismatches(&self.connections),
+        };
+        for mismatch in mismatches {
+            if self.options.verify_type_hashes == TypeHashVerification::Error {
+                return Err(mismatch);
+            }
+            eprintln!("Warning: {mismatch}");
+        }
+
         self.storage = Some(storage);
         self.is_open = true;
 
@@ -177,19 +832,36 @@ impl Reader {
     }
 
     /// Close the bag
+    ///
+    /// Releases every file handle held by the storage backend, so the bag can be
+    /// moved or deleted afterwards (also on Windows). The handles are released even
+    /// if closing reports an error. The reader can be opened again with [`Reader::open`].
     pub fn close(&mut self) -> Result<()> {
         if !self.is_open {
             return Ok(());
         }
 
+        self.is_open = false;
+        lock(&self.definitions).clear();
+        lock(&self.schemas).clear();
         if let Some(mut storage) = self.storage.take() {
             storage.close()?;
         }
-
-        self.is_open = false;
         Ok(())
     }
 
+    /// Close and open the bag again, re-reading `metadata.yaml`
+    ///
+    /// Picks up changes made to the bag while it was closed, such as a reindexed
+    /// metadata file. Readers over storage inputs keep their metadata.
+    pub fn reopen(&mut self) -> Result<()> {
+        self.close()?;
+        if self.inputs.is_none() {
+            self.metadata = Some(Self::load_metadata(&self.bag_path, &self.options)?);
+        }
+        self.open()
+    }
+
     /// Get the bag duration in nanoseconds
     pub fn duration(&self) -> u64 {
         self.metadata.as_ref().map_or(0, |m| m.duration())
@@ -241,12 +913,246 @@ impl Reader {
         &self.connections
     }
 
+    /// Get the message definition of `connection`
+    ///
+    /// Under [`ReaderOptions::lazy_definitions`] the definition of a type is loaded
+    /// from the storage the first time it is asked for, and kept until the bag is
+    /// closed. Types without a stored definition get one from
+    /// [`ReaderOptions::type_registry`], if it knows them, or an empty one.
+    pub fn message_definition(&self, connection: &Connection) -> Result<MessageDefinition> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        if !is_missing(&connection.message_definition) {
+            return Ok(connection.message_definition.clone());
+        }
+        let message_type = &connection.message_type;
+        if let Some(definition) = lock(&self.definitions).get(message_type) {
+            return Ok(definition.clone());
+        }
+        let loaded = self
+            .connections
+            .iter()
+            .find(|c| c.message_type == *message_type && !is_missing(&c.message_definition));
+        if let Some(loaded) = loaded {
+            return Ok(loaded.message_definition.clone());
+        }
+
+        let storage = self.storage.as_ref().unwrap();
+        let stored = storage.get_definition(message_type)?.unwrap_or_default();
+        let definition =
+            with_registry_fallback(self.options.type_registry.as_ref(), message_type, stored)?;
+        lock(&self.definitions).insert(message_type.clone(), definition.clone());
+        Ok(definition)
+    }
+
+    /// Get the parsed schemas of the message type of `connection`
+    ///
+    /// The definition is parsed when a type is first asked for, so opening a bag
+    /// never parses definitions of topics that are not decoded.
+    pub fn message_schemas(&self, connection: &Connection) -> Result<Arc<MessageSchemas>> {
+        if let Some(schemas) = lock(&self.schemas).get(&connection.message_type) {
+            return Ok(schemas.clone());
+        }
+        let schemas = Arc::new(self.with_definition(connection)?.message_schemas()?);
+        lock(&self.schemas).insert(connection.message_type.clone(), schemas.clone());
+        Ok(schemas)
+    }
+
+    /// Decode the payload of `message` with the definition of its type in this bag
+    ///
+    /// Unlike [`Message::decode_dynamic`], this works for messages of connections
+    /// without a definition of their own, such as those read under
+    /// [`ReaderOptions::lazy_definitions`].
+    pub fn decode_dynamic(&self, message: &Message) -> Result<DynamicMessage> {
+        self.message_schemas(&message.connection)
+            .and_then(|schemas| {
+                let mut deserializer = CdrDeserializer::new(&message.data)?;
+                decode_message(&mut deserializer, &schemas, &schemas.root().name)
+            })
+            .map_err(|e| e.with_context(message.error_context()))
+    }
+
+    /// Get the schema of the message type recorded on `topic`
+    ///
+    /// Lists the fields of the type with their types and array specifications;
+    /// [`Reader::topic_fields`] adds the fields of nested types. A topic recorded with
+    /// more than one message type gives the schema of its first connection.
+    pub fn topic_schema(&self, topic: &str) -> Result<MessageSchema> {
+        Ok(self.topic_schemas(topic)?.root().clone())
+    }
+
+    /// Get the fields of the message type recorded on `topic`, nested types included
+    ///
+    /// Suits building field pickers; [`query::column_paths`] flattens the schemas of a
+    /// connection into the paths [`Reader::select`] accepts.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # use rosbags_rs::schema::FieldNode;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// fn print(nodes: &[FieldNode], indent: usize) {
+    ///     for node in nodes {
+    ///         println!("{:indent$}{} {}", "", node.field.type_name, node.name());
+    ///         print(&node.children, indent + 2);
+    ///     }
+    /// }
+    /// print(&reader.topic_fields("/imu")?, 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn topic_fields(&self, topic: &str) -> Result<Vec<FieldNode>> {
+        self.topic_schemas(topic)?.field_tree()
+    }
+
+    /// Schemas of the message type of the first connection of `topic`
+    fn topic_schemas(&self, topic: &str) -> Result<Arc<MessageSchemas>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        let connection = self
+            .connections_for_topic(topic)
+            .into_iter()
+            .next()
+            .ok_or_else(|| ReaderError::connection_not_found(topic))?;
+        self.message_schemas(&connection)
+    }
+
+    /// `connection` with its message definition loaded
+    fn with_definition(&self, connection: &Connection) -> Result<Connection> {
+        let mut connection = connection.clone();
+        connection.message_definition = self.message_definition(&connection)?;
+        Ok(connection)
+    }
+
+    /// Whether definitions are loaded on first use instead of when opening
+    fn lazy_definitions(&self) -> bool {
+        self.options.lazy_definitions
+            && self.options.verify_type_hashes == TypeHashVerification::Off
+    }
+
+    /// Get the connections of `topic`, ready to pass to the filtered iteration methods
+    ///
+    /// A topic recorded with more than one message type has a connection per type.
+    pub fn connections_for_topic(&self, topic: &str) -> Vec<Connection> {
+        self.connections_where(|c| c.topic == topic)
+    }
+
+    /// Get the connections with the message type `message_type`, e.g.
+    /// `sensor_msgs/msg/Image`
+    pub fn connections_for_type(&self, message_type: &str) -> Vec<Connection> {
+        self.connections_where(|c| c.message_type == message_type)
+    }
+
+    /// Get the connections of the topics matching a glob `pattern`
+    ///
+    /// `*` matches any part of one topic name segment, `**` any part of the topic
+    /// including `/`, and `?` a single character other than `/`. `/camera/*` matches
+    /// `/camera/image_raw` but not `/camera/left/image_raw`, which `/camera/**` does.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// let cameras = reader.topics_matching("/camera/**");
+    /// for message in reader.messages_filtered(Some(&cameras), None, None)? {
+    ///     println!("{}", message?.topic);
+    /// }
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn topics_matching(&self, pattern: &str) -> Vec<Connection> {
+        self.connections_where(|c| glob_match(pattern.as_bytes(), c.topic.as_bytes()))
+    }
+
+    /// Get the topics read for `filter`
+    ///
+    /// With a topic allowlist in the options, patterns are resolved against the
+    /// allowed connections instead of being handed to the storage.
+    fn filter_topics(&self, filter: &MessageFilter) -> Result<FilterTopics> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        if filter.passes_all_connections() {
+            return Ok(FilterTopics::All);
+        }
+        if self.options.topics.is_none() {
+            if let Some(patterns) = filter.only_topic_patterns()? {
+                return Ok(FilterTopics::Patterns(patterns));
+            }
+        }
+        Ok(FilterTopics::Connections(
+            self.connections_where(|c| filter.matches(c)),
+        ))
+    }
+
+    /// Clone the connections passing `filter`
+    fn connections_where(&self, filter: impl Fn(&Connection) -> bool) -> Vec<Connection> {
+        self.connections
+            .iter()
+            .filter(|c| filter(c))
+            .cloned()
+            .collect()
+    }
+
+    /// Check the stored type description hash of every connection against the hash
+    /// of its stored message definition
+    ///
+    /// Returns a [`ReaderError::TypeHashMismatch`] per mismatching topic. Topics
+    /// without an `RIHS01` hash or without a parsable `.msg` definition are skipped.
+    /// [`ReaderOptions::verify_type_hashes`] runs the same check when opening.
+    pub fn type_hash_mismatches(&self) -> Vec<ReaderError> {
+        type_hash_mismatches(&self.connections)
+    }
+
+    /// Get the storage files of the bag with the time span of their messages
+    ///
+    /// Messages name their file with `file_index`, the position in this list.
+    pub fn files(&self) -> Result<Vec<BagFile>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        self.storage.as_ref().unwrap().files()
+    }
+
+    /// Get the files attached to the storage files, e.g. calibration files or URDFs
+    ///
+    /// Only MCAP storage holds attachments; SQLite3 bags have none.
+    pub fn attachments(&self) -> Result<Vec<Attachment>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        self.storage.as_ref().unwrap().attachments()
+    }
+
+    /// Get the named metadata records of the storage files
+    ///
+    /// Only MCAP storage holds metadata records; SQLite3 bags have none. The `rosbag2`
+    /// record duplicating the bag metadata is left out, see [`Reader::metadata`].
+    pub fn mcap_metadata(&self) -> Result<Vec<McapMetadata>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        self.storage.as_ref().unwrap().mcap_metadata()
+    }
+
+    /// Get the storage files holding messages from `start` (inclusive) to `stop` (exclusive)
+    pub fn files_in_range(&self, start: u64, stop: u64) -> Result<Vec<BagFile>> {
+        let mut files = self.files()?;
+        files.retain(|file| file.overlaps(start, stop));
+        Ok(files)
+    }
+
     /// Iterate over all messages in the bag
+    ///
+    /// Messages come in timestamp order; ties are broken by connection id, then by
+    /// storage order, so every storage backend yields the same sequence.
     pub fn messages(&self) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
         self.messages_filtered(None, None, None)
     }
 
     /// Iterate over messages with optional filters
+    ///
+    /// `start` is inclusive and `stop` exclusive.
     pub fn messages_filtered(
         &self,
         connections: Option<&[Connection]>,
@@ -257,66 +1163,1034 @@ impl Reader {
             return Err(ReaderError::BagNotOpen);
         }
 
+        let Some((connections, start, stop)) = self.filters(connections, start, stop) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
         let storage = self.storage.as_ref().unwrap();
         let iterator = storage.messages_filtered(connections, start, stop)?;
-        Ok(iterator)
+        Ok(self.decoded_messages(iterator))
     }
 
-    /// Get raw message data without deserialization for maximum performance
-    /// This is equivalent to ROS2's SerializedBagMessage for high-speed copying
-    pub fn raw_messages(&self) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
-        if !self.is_open {
-            return Err(ReaderError::BagNotOpen);
+    /// Iterate over the messages passing `filter`
+    ///
+    /// Like [`Reader::messages_filtered`] with the connections matching `filter`;
+    /// an unset start or stop falls back to [`ReaderOptions`].
+    pub fn messages_with(
+        &self,
+        filter: &MessageFilter,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        let (start, stop) = (filter.start, filter.stop);
+        match self.filter_topics(filter)? {
+            FilterTopics::All => self.messages_filtered(None, start, stop),
+            FilterTopics::Connections(connections) if connections.is_empty() => {
+                Ok(Box::new(std::iter::empty()))
+            }
+            FilterTopics::Connections(connections) => {
+                self.messages_filtered(Some(&connections), start, stop)
+            }
+            FilterTopics::Patterns(patterns) => {
+                let storage = self.storage.as_ref().unwrap();
+                let iterator = storage.messages_matching(
+                    &patterns,
+                    start.or(self.options.start),
+                    stop.or(self.options.stop),
+                )?;
+                Ok(self.decoded_messages(iterator))
+            }
         }
+    }
 
-        let storage = self.storage.as_ref().unwrap();
-        let iterator = storage.raw_messages()?;
-        Ok(iterator)
+    /// Get the first message on `topic`, `None` if it has no messages
+    ///
+    /// The message is looked up with the storage indexes instead of reading the
+    /// topic: a single ordered query per SQLite3 database, and only the chunks that
+    /// can hold it in MCAP files. Handy to check when a sensor started publishing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// let first = reader.first_message("/imu/data")?;
+    /// let last = reader.last_message("/imu/data")?;
+    /// if let (Some(first), Some(last)) = (first, last) {
+    ///     println!("/imu/data from {} to {}", first.timestamp, last.timestamp);
+    /// }
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn first_message(&self, topic: &str) -> Result<Option<Message>> {
+        self.edge_message(topic, StorageReader::first_message)
     }
 
-    /// Get filtered raw message data without deserialization
-    /// This provides the highest performance way to read specific topics/time ranges
-    pub fn raw_messages_filtered(
+    /// Get the last message on `topic`, `None` if it has no messages
+    ///
+    /// See [`Reader::first_message`].
+    pub fn last_message(&self, topic: &str) -> Result<Option<Message>> {
+        self.edge_message(topic, StorageReader::last_message)
+    }
+
+    /// Count the messages with optional filters, without reading them
+    ///
+    /// Takes the same filters as [`Reader::messages_filtered`]. SQLite3 bags count in
+    /// the database, or with their sidecar index (see [`Reader::build_index`]); other
+    /// storage reads the messages to count them.
+    pub fn count_messages(
         &self,
         connections: Option<&[Connection]>,
         start: Option<u64>,
         stop: Option<u64>,
-    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+    ) -> Result<u64> {
         if !self.is_open {
             return Err(ReaderError::BagNotOpen);
         }
 
+        let Some((connections, start, stop)) = self.filters(connections, start, stop) else {
+            return Ok(0);
+        };
         let storage = self.storage.as_ref().unwrap();
-        let iterator = storage.raw_messages_filtered(connections, start, stop)?;
-        Ok(iterator)
+        storage.count_messages(connections, start, stop)
     }
 
-    /// Read all messages in raw format as a batch for bulk operations
-    /// This is the fastest way to copy entire bags or large chunks of data
-    pub fn read_raw_messages_batch(
+    /// Look up the message of `topic` returned by `lookup`, within the time range of
+    /// the options
+    fn edge_message(
         &self,
-        connections: Option<&[Connection]>,
-        start: Option<u64>,
-        stop: Option<u64>,
-    ) -> Result<Vec<RawMessage>> {
+        topic: &str,
+        lookup: impl FnOnce(
+            &(dyn StorageReader + 'static),
+            &[Connection],
+            Option<u64>,
+            Option<u64>,
+        ) -> Result<Option<Message>>,
+    ) -> Result<Option<Message>> {
         if !self.is_open {
             return Err(ReaderError::BagNotOpen);
         }
 
+        let connections = self.connections_for_topic(topic);
+        if connections.is_empty() {
+            return Err(ReaderError::connection_not_found(topic));
+        }
         let storage = self.storage.as_ref().unwrap();
-        storage.read_raw_messages_batch(connections, start, stop)
-    }
-
-    /// Check if the bag is open
-    pub fn is_open(&self) -> bool {
-        self.is_open
+        let message = lookup(
+            storage.as_ref(),
+            &connections,
+            self.options.start,
+            self.options.stop,
+        )?;
+        self.decoded_messages(Box::new(message.into_iter().map(Ok)))
+            .next()
+            .transpose()
     }
 
-    /// Get the metadata
-    pub fn metadata(&self) -> Option<&BagMetadata> {
-        self.metadata.as_ref()
-    }
-}
+    /// Get about `n` messages of `topic` spread evenly over the time it was recorded
+    ///
+    /// The time from the first to the last message of the topic is split into `n`
+    /// equal windows and the first message of each window is looked up like
+    /// [`Reader::first_message`], so only the sampled messages are read, e.g. to
+    /// preview a long camera recording. Windows without messages are left out, so
+    /// fewer than `n` messages may be returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// for frame in reader.sample("/camera/image_raw", 20)? {
+    ///     println!("thumbnail at {}", frame.timestamp);
+    /// }
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn sample(&self, topic: &str, n: usize) -> Result<Vec<Message>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        let connections = self.connections_for_topic(topic);
+        if connections.is_empty() {
+            return Err(ReaderError::connection_not_found(topic));
+        }
+        let storage = self.storage.as_ref().unwrap();
+        let (start, stop) = (self.options.start, self.options.stop);
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let Some(first) = storage.first_message(&connections, start, stop)? else {
+            return Ok(Vec::new());
+        };
+        let last = storage
+            .last_message(&connections, start, stop)?
+            .map_or(first.timestamp, |last| last.timestamp);
+
+        // Window `i` starts at `first + span * i / n`; a bound past `u64::MAX` is open
+        let first_timestamp = first.timestamp;
+        let from = u128::from(first_timestamp);
+        let span = u128::from(last) + 1 - from;
+        let bound = |i: usize| u64::try_from(from + span * i as u128 / n as u128).ok();
+        let mut samples = vec![first];
+        for i in 1..n {
+            let Some(window_start) = bound(i) else {
+                break;
+            };
+            // Windows starting at the first message hold no other sample
+            let window_stop = bound(i + 1);
+            if window_start == first_timestamp || window_stop == Some(window_start) {
+                continue;
+            }
+            let window_stop = window_stop.or(stop);
+            let message = storage.first_message(&connections, Some(window_start), window_stop)?;
+            if let Some(message) = message {
+                samples.push(message);
+            }
+        }
+        self.decoded_messages(Box::new(samples.into_iter().map(Ok)))
+            .collect()
+    }
+
+    /// Extract selected fields of all messages on `topic` as typed columns
+    ///
+    /// Paths use dots for nested fields and brackets for array elements, e.g.
+    /// `header.stamp`, `linear_acceleration.x` or `position_covariance[0]`.
+    /// `builtin_interfaces` time values are returned as nanoseconds. Only the
+    /// selected fields are decoded; the message definitions stored in the bag
+    /// are used, so no compiled message type is required.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::Reader;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = Reader::new("path/to/bag")?;
+    /// reader.open()?;
+    ///
+    /// let selection = reader.select("/imu/data", &["header.stamp", "linear_acceleration.x"])?;
+    /// let accel_x = selection.column("linear_acceleration.x").unwrap().to_f64();
+    /// println!("{} samples", accel_x.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn select(&self, topic: &str, paths: &[&str]) -> Result<Selection> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        let connections = self
+            .connections_for_topic(topic)
+            .iter()
+            .map(|connection| self.with_definition(connection))
+            .collect::<Result<Vec<_>>>()?;
+        if connections.is_empty() {
+            return Err(ReaderError::connection_not_found(topic));
+        }
+
+        let mut builder = SelectionBuilder::new(paths);
+        let queries = query::compile_queries(&connections, paths, &mut builder)?;
+        let fallback = &queries[&connections[0].id];
+
+        for message in self.raw_messages_filtered(Some(&connections), None, None)? {
+            let message = message?;
+            let query = queries.get(&message.connection.id).unwrap_or(fallback);
+            match query.decode(&message.raw_data) {
+                Ok(values) => builder.push(message.timestamp, values),
+                Err(e) => {
+                    let error = e.with_context(self.message_context(
+                        topic,
+                        message.timestamp,
+                        message.file_index,
+                    ));
+                    if self.options.on_error == ErrorPolicy::Fail {
+                        return Err(error);
+                    }
+                    self.skip(error);
+                }
+            }
+        }
+
+        Ok(builder.finish())
+    }
+
+    /// Get raw message data without deserialization for maximum performance
+    /// This is equivalent to ROS2's SerializedBagMessage for high-speed copying
+    pub fn raw_messages(&self) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        self.raw_messages_filtered(None, None, None)
+    }
+
+    /// Get filtered raw message data without deserialization
+    /// This provides the highest performance way to read specific topics/time ranges
+    pub fn raw_messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        let Some((connections, start, stop)) = self.filters(connections, start, stop) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+        let storage = self.storage.as_ref().unwrap();
+        let iterator = storage.raw_messages_filtered(connections, start, stop)?;
+        Ok(self.decoded_raw_messages(iterator))
+    }
+
+    /// Iterate over the raw messages passing `filter`
+    ///
+    /// See [`Reader::messages_with`].
+    pub fn raw_messages_with(
+        &self,
+        filter: &MessageFilter,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        let (start, stop) = (filter.start, filter.stop);
+        match self.filter_topics(filter)? {
+            FilterTopics::All => self.raw_messages_filtered(None, start, stop),
+            FilterTopics::Connections(connections) if connections.is_empty() => {
+                Ok(Box::new(std::iter::empty()))
+            }
+            FilterTopics::Connections(connections) => {
+                self.raw_messages_filtered(Some(&connections), start, stop)
+            }
+            FilterTopics::Patterns(patterns) => {
+                let storage = self.storage.as_ref().unwrap();
+                let iterator = storage.raw_messages_matching(
+                    &patterns,
+                    start.or(self.options.start),
+                    stop.or(self.options.stop),
+                )?;
+                Ok(self.decoded_raw_messages(iterator))
+            }
+        }
+    }
+
+    /// Iterate over raw messages without copying their data where the storage allows it
+    ///
+    /// Payloads of uncompressed MCAP chunks are borrowed from the loaded file (memory-mapped
+    /// with the `unsafe-opt` feature), which avoids one allocation and copy per message on
+    /// bags of large messages such as images. SQLite3 payloads are owned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::Reader;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = Reader::new("path/to/bag")?;
+    /// reader.open()?;
+    ///
+    /// let mut total_bytes = 0;
+    /// for message in reader.raw_messages_borrowed()? {
+    ///     total_bytes += message?.data.len();
+    /// }
+    /// println!("{total_bytes} bytes");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_messages_borrowed(
+        &self,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        self.raw_messages_borrowed_filtered(None, None, None)
+    }
+
+    /// Iterate over filtered raw messages without copying their data where the storage allows it
+    ///
+    /// See [`Reader::raw_messages_borrowed`]. `start` is inclusive and `stop` exclusive.
+    pub fn raw_messages_borrowed_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        let Some((connections, start, stop)) = self.filters(connections, start, stop) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+        let storage = self.storage.as_ref().unwrap();
+        let iterator = storage.raw_messages_borrowed(connections, start, stop)?;
+        if !self.decompress_payloads() {
+            return Ok(self.recover(iterator));
+        }
+        Ok(self.recover(Box::new(iterator.map(|message| {
+            let mut message = message?;
+            if is_compressed(&message.data) {
+                let data = decompress_payload(&message.data).map_err(|e| {
+                    e.with_context(self.message_context(
+                        &message.connection.topic,
+                        message.timestamp,
+                        message.file_index,
+                    ))
+                })?;
+                message.data = Cow::Owned(data);
+            }
+            Ok(message)
+        }))))
+    }
+
+    /// Read all messages in raw format as a batch for bulk operations
+    /// This is the fastest way to copy entire bags or large chunks of data
+    pub fn read_raw_messages_batch(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Vec<RawMessage>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        let Some((connections, start, stop)) = self.filters(connections, start, stop) else {
+            return Ok(Vec::new());
+        };
+        let storage = self.storage.as_ref().unwrap();
+        let mut messages = storage.read_raw_messages_batch(connections, start, stop)?;
+        if self.decompress_payloads() {
+            for message in messages
+                .iter_mut()
+                .filter(|message| is_compressed(&message.raw_data))
+            {
+                message.raw_data = decompress_payload(&message.raw_data)?;
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Apply the topic allowlist and default time range of the options to the
+    /// arguments of an iteration method
+    ///
+    /// Returns `None` if the allowlist leaves no connection to read.
+    fn filters<'a>(
+        &'a self,
+        connections: Option<&'a [Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Option<Filters<'a>> {
+        let connections = match (connections, &self.options.topics) {
+            (None, Some(_)) if self.connections.is_empty() => return None,
+            (None, Some(_)) => Some(self.connections.as_slice()),
+            (connections, _) => connections,
+        };
+        Some((
+            connections,
+            start.or(self.options.start),
+            stop.or(self.options.stop),
+        ))
+    }
+
+    /// Topic, timestamp and storage file of a message, attached to its errors
+    fn message_context(&self, topic: &str, timestamp: u64, file_index: usize) -> ErrorContext {
+        let file = self
+            .storage
+            .as_ref()
+            .and_then(|storage| storage.file_paths().get(file_index));
+        ErrorContext::message(topic, timestamp).with_file(file)
+    }
+
+    /// Decompress the payloads of messages read from the storage and apply the error
+    /// policy
+    fn decoded_messages<'a>(
+        &'a self,
+        iterator: Box<dyn Iterator<Item = Result<Message>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Result<Message>> + 'a> {
+        if !self.decompress_payloads() {
+            return self.recover(iterator);
+        }
+        self.recover(Box::new(iterator.map(|message| {
+            let mut message = message?;
+            if is_compressed(&message.data) {
+                message.data = decompress_payload(&message.data).map_err(|e| {
+                    e.with_context(self.message_context(
+                        &message.topic,
+                        message.timestamp,
+                        message.file_index,
+                    ))
+                })?;
+            }
+            Ok(message)
+        })))
+    }
+
+    /// Decompress the payloads of raw messages read from the storage and apply the
+    /// error policy
+    fn decoded_raw_messages<'a>(
+        &'a self,
+        iterator: Box<dyn Iterator<Item = Result<RawMessage>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Result<RawMessage>> + 'a> {
+        if !self.decompress_payloads() {
+            return self.recover(iterator);
+        }
+        self.recover(Box::new(iterator.map(|message| {
+            let mut message = message?;
+            if is_compressed(&message.raw_data) {
+                message.raw_data = decompress_payload(&message.raw_data).map_err(|e| {
+                    e.with_context(self.message_context(
+                        &message.connection.topic,
+                        message.timestamp,
+                        message.file_index,
+                    ))
+                })?;
+            }
+            Ok(message)
+        })))
+    }
+
+    /// Drop failed messages into the skip report unless the error policy is `Fail`
+    fn recover<'a, T: 'a>(
+        &'a self,
+        iterator: Box<dyn Iterator<Item = Result<T>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Result<T>> + 'a> {
+        if self.options.on_error == ErrorPolicy::Fail {
+            return iterator;
+        }
+        Box::new(iterator.filter_map(|message| match message {
+            Ok(message) => Some(Ok(message)),
+            Err(e) => {
+                self.skip(e);
+                None
+            }
+        }))
+    }
+
+    /// Record a dropped message in the skip report
+    fn skip(&self, error: ReaderError) {
+        let mut report = self.skip_report();
+        report.skipped += 1;
+        if self.options.on_error == ErrorPolicy::CollectReport {
+            report.errors.push(error);
+        }
+    }
+
+    fn skip_report(&self) -> MutexGuard<'_, SkipReport> {
+        lock(&self.skip_report)
+    }
+
+    /// Whether payloads are stored compressed per message and are decompressed on read
+    fn decompress_payloads(&self) -> bool {
+        self.options.decode_on_read
+            && self
+                .metadata
+                .as_ref()
+                .and_then(|m| m.compression_mode())
+                .is_some_and(|mode| mode.eq_ignore_ascii_case("message"))
+    }
+
+    /// Take the report of what was dropped since the last call
+    ///
+    /// Only filled under [`ErrorPolicy::Skip`] and [`ErrorPolicy::CollectReport`].
+    /// Errors of individual messages carry an [`ErrorContext`] naming the topic,
+    /// timestamp and storage file; errors of unreadable records name the storage
+    /// file and the record or MCAP chunk.
+    pub fn take_skip_report(&self) -> SkipReport {
+        std::mem::take(&mut *self.skip_report())
+    }
+
+    /// Check if the bag is open
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    /// Get the metadata
+    pub fn metadata(&self) -> Option<&BagMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Get the custom data of the metadata, in key order
+    ///
+    /// These are the entries set with [`WriterBuilder::custom_data`] or `ros2 bag
+    /// record --custom-data`, without recorded checksums. Bags read without
+    /// `metadata.yaml` have none.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let reader = Reader::new("/path/to/bag")?;
+    /// for (key, value) in reader.custom_data() {
+    ///     println!("{key}: {value}");
+    /// }
+    /// let dataset_id: Option<u64> = reader.parse_custom_value("dataset_id")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`WriterBuilder::custom_data`]: crate::WriterBuilder::custom_data
+    pub fn custom_data(&self) -> BTreeMap<String, String> {
+        self.metadata
+            .as_ref()
+            .map(BagMetadata::custom_data)
+            .unwrap_or_default()
+    }
+
+    /// Get a custom data entry of the metadata
+    pub fn custom_value(&self, key: &str) -> Option<&str> {
+        self.metadata.as_ref()?.custom_value(key)
+    }
+
+    /// Parse a custom data entry of the metadata, `None` without the entry
+    pub fn parse_custom_value<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        match &self.metadata {
+            Some(metadata) => metadata.parse_custom_value(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the path of the bag directory or single storage file, empty for readers
+    /// over storage inputs
+    pub fn bag_path(&self) -> &Path {
+        &self.bag_path
+    }
+
+    /// Check the storage files against the SHA-256 checksums recorded in the metadata
+    ///
+    /// Returns an empty list when every storage file is present and unchanged. Fails
+    /// for bags without checksums (see [`WriterBuilder::checksums`]) and for readers
+    /// over storage inputs or a single storage file. The reader does not need to be
+    /// open.
+    ///
+    /// [`WriterBuilder::checksums`]: crate::WriterBuilder::checksums
+    pub fn verify_checksums(&self) -> Result<Vec<ChecksumMismatch>> {
+        if self.inputs.is_some() {
+            return Err(ReaderError::generic(
+                "Checksums can only be verified for bag directories",
+            ));
+        }
+        let metadata = self
+            .metadata
+            .as_ref()
+            .filter(|metadata| metadata.has_checksums())
+            .ok_or_else(|| {
+                ReaderError::generic(format!(
+                    "Bag {} has no recorded checksums",
+                    self.bag_path.display()
+                ))
+            })?;
+
+        let mut mismatches = Vec::new();
+        for path in &metadata.info().relative_file_paths {
+            let Some(expected) = metadata.checksum(path) else {
+                mismatches.push(ChecksumMismatch::Unrecorded { path: path.clone() });
+                continue;
+            };
+            let file = self.bag_path.join(path);
+            if !file.is_file() {
+                mismatches.push(ChecksumMismatch::Missing { path: path.clone() });
+                continue;
+            }
+            let (_, actual) = sha256_file(&file)?;
+            if actual != expected {
+                mismatches.push(ChecksumMismatch::Checksum {
+                    path: path.clone(),
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Read the message count and time range from the storage instead of the metadata
+    ///
+    /// `metadata.yaml` of a bag whose recording was interrupted, or that was edited
+    /// since, can disagree with the storage. The first and last timestamps and the
+    /// message counts are queried from the storage (`MIN`/`MAX`/`COUNT` for SQLite3,
+    /// the messages of the chunks or records for MCAP) and the differences from the
+    /// metadata are listed in [`BagStatistics::discrepancies`]. The reader's metadata and
+    /// connections take the storage values, so [`Reader::duration`] and
+    /// [`Reader::message_count`] are accurate afterwards.
+    ///
+    /// Topic counts cover the topics the reader reads (see [`ReaderOptions::topics`]).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::Reader;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = Reader::builder("path/to/bag").open()?;
+    /// let statistics = reader.recompute_statistics()?;
+    /// for discrepancy in &statistics.discrepancies {
+    ///     println!("{discrepancy}");
+    /// }
+    /// println!("{} messages over {} ns", reader.message_count(), reader.duration());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn recompute_statistics(&mut self) -> Result<BagStatistics> {
+        let storage = self.storage.as_ref().ok_or(ReaderError::BagNotOpen)?;
+
+        let (start_time, end_time) = storage.time_range()?.unwrap_or((0, 0));
+        let mut statistics = BagStatistics {
+            message_count: storage.count_messages(None, None, None)?,
+            start_time,
+            end_time,
+            topic_message_counts: BTreeMap::new(),
+            discrepancies: Vec::new(),
+        };
+        for connection in &mut self.connections {
+            let count =
+                storage.count_messages(Some(std::slice::from_ref(connection)), None, None)?;
+            connection.message_count = count;
+            *statistics
+                .topic_message_counts
+                .entry(connection.topic.clone())
+                .or_default() += count;
+        }
+
+        if let Some(metadata) = self.metadata.as_mut() {
+            statistics.discrepancies = metadata.apply_statistics(&statistics);
+        }
+        Ok(statistics)
+    }
+
+    /// Split the bag into consecutive time windows of `window` for parallel processing
+    ///
+    /// Each [`Shard`] opens its own reader limited to its window, so shards can be
+    /// handed to worker threads for map-reduce style processing. Every message
+    /// belongs to exactly one shard. The reader does not need to be open.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::Reader;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let reader = Reader::new("path/to/bag")?;
+    /// let shards = reader.shards(Duration::from_secs(60))?;
+    ///
+    /// let total = std::thread::scope(|scope| {
+    ///     let handles: Vec<_> = shards
+    ///         .iter()
+    ///         .map(|shard| {
+    ///             scope.spawn(move || -> rosbags_rs::Result<usize> {
+    ///                 Ok(shard.open()?.messages()?.count())
+    ///             })
+    ///         })
+    ///         .collect();
+    ///     handles
+    ///         .into_iter()
+    ///         .map(|h| h.join().unwrap())
+    ///         .sum::<rosbags_rs::Result<usize>>()
+    /// })?;
+    /// println!("{total} messages");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shards(&self, window: std::time::Duration) -> Result<Vec<Shard>> {
+        Shard::split(self, window)
+    }
+
+    /// Count the messages of every topic in consecutive time windows of `window`
+    ///
+    /// Windows start at the first message; the topic allowlist and time range of the
+    /// options apply. The [`Histogram`] gives the counts and rates per window and
+    /// finds dropouts and rate anomalies.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # use std::time::Duration;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// let histogram = reader.histogram(Duration::from_secs(1))?;
+    /// for gap in histogram.dropouts("/imu/data") {
+    ///     let (from, to) = (histogram.window_range(gap.start), histogram.window_range(gap.end - 1));
+    ///     println!("no /imu/data from {} to {}", from.start, to.end);
+    /// }
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn histogram(&self, window: std::time::Duration) -> Result<Histogram> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        Histogram::compute(self, window)
+    }
+
+    /// Compare the `header.stamp` of messages with their bag timestamps, per topic
+    ///
+    /// Reads the topics whose messages start with a `std_msgs/msg/Header` and
+    /// reports the offset of the bag time from the header stamp and its drift, e.g.
+    /// to find a sensor whose clock is not synchronized. Topics are in name order.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// # let reader = Reader::builder("bag").open()?;
+    /// for skew in reader.stamp_skew()? {
+    ///     println!(
+    ///         "{}: {:.1} ms late, drifting {:.1} ppm",
+    ///         skew.topic,
+    ///         skew.mean_offset / 1e6,
+    ///         skew.drift * 1e6
+    ///     );
+    /// }
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn stamp_skew(&self) -> Result<Vec<StampSkew>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        StampSkew::compute(self)
+    }
+
+    /// Write a sidecar index next to every SQLite3 database of the bag and reopen the
+    /// bag to use it
+    ///
+    /// rosbag2 only indexes messages by timestamp; the index adds the timestamps and
+    /// row ids of the messages of each topic, so [`Reader::count_messages`], message
+    /// counts when opening and [`Reader::first_message`] and [`Reader::last_message`]
+    /// (and so [`Reader::sample`]) take a binary search instead of a table scan. An
+    /// index is ignored once its database changes. Returns the paths of the index
+    /// files, `<database>.index`. Fails for MCAP bags, whose files carry their own
+    /// chunk and message indexes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Reader;
+    /// let mut reader = Reader::builder("bag").open()?;
+    /// if !reader.has_index() {
+    ///     reader.build_index()?;
+    /// }
+    /// let count = reader.count_messages(None, Some(1_700_000_000_000_000_000), None)?;
+    /// # Ok::<(), rosbags_rs::ReaderError>(())
+    /// ```
+    pub fn build_index(&mut self) -> Result<Vec<PathBuf>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite_storage) = self.storage.as_ref().and_then(|storage| {
+            storage
+                .as_any()
+                .downcast_ref::<crate::storage::sqlite::SqliteReader>()
+        }) {
+            let paths = sqlite_storage.build_indexes()?;
+            self.close()?;
+            self.open()?;
+            return Ok(paths);
+        }
+        Err(ReaderError::generic(
+            "Sidecar indexes are only built for SQLite3 bags",
+        ))
+    }
+
+    /// Whether every SQLite3 database of the bag has an up-to-date sidecar index
+    ///
+    /// See [`Reader::build_index`]. Always `false` for other storage.
+    pub fn has_index(&self) -> bool {
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite_storage) = self.storage.as_ref().and_then(|storage| {
+            storage
+                .as_any()
+                .downcast_ref::<crate::storage::sqlite::SqliteReader>()
+        }) {
+            return sqlite_storage.has_indexes();
+        }
+        false
+    }
+
+    /// Rebuild `metadata.yaml` of a bag from its storage files, like `ros2 bag reindex`
+    ///
+    /// Topics, message counts, the time range and per-file information are read from
+    /// the `.db3` or `.mcap` files (only those of `storage`, if given); an existing
+    /// metadata file is ignored and replaced.
+    pub fn reindex<P: AsRef<Path>>(
+        bag_path: P,
+        storage: Option<StoragePlugin>,
+    ) -> Result<BagMetadata> {
+        let bag_path = bag_path.as_ref();
+        let mut builder = Self::builder(bag_path).skip_metadata(true);
+        if let Some(storage) = storage {
+            builder = builder.storage(storage);
+        }
+        let mut reader = builder.open()?;
+
+        let files = reader.files()?;
+        let mut metadata = reader.metadata.take().unwrap();
+        reader.close()?;
+
+        let info = &mut metadata.rosbag2_bagfile_information;
+        info.files = info
+            .relative_file_paths
+            .iter()
+            .zip(files)
+            .map(|(path, file)| FileInformation {
+                path: path.clone(),
+                starting_time: StartingTime {
+                    nanoseconds_since_epoch: file.start_time,
+                },
+                duration: Duration {
+                    nanoseconds: file.end_time - file.start_time,
+                },
+                message_count: file.message_count,
+            })
+            .collect();
+        metadata.to_file(bag_path.join("metadata.yaml"))?;
+        Ok(metadata)
+    }
+}
+
+/// Lock `mutex`, also after a panic of another thread holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whether `definition` is absent; writers without a definition store an empty
+/// `ros2msg` one
+fn is_missing(definition: &MessageDefinition) -> bool {
+    definition.format == MessageDefinitionFormat::None || definition.data.trim().is_empty()
+}
+
+/// `definition` of `message_type`, or the one in `registry` if it is missing and
+/// the registry knows the type
+fn with_registry_fallback(
+    registry: Option<&TypeRegistry>,
+    message_type: &str,
+    definition: MessageDefinition,
+) -> Result<MessageDefinition> {
+    match registry {
+        Some(registry) if is_missing(&definition) && registry.contains(message_type) => {
+            registry.definition(message_type)
+        }
+        _ => Ok(definition),
+    }
+}
+
+fn type_hash_mismatches(connections: &[Connection]) -> Vec<ReaderError> {
+    connections
+        .iter()
+        .filter(|c| {
+            c.type_description_hash.starts_with(TYPE_HASH_PREFIX)
+                && c.message_type.contains("/msg/")
+        })
+        .filter_map(|c| {
+            let schemas = MessageSchemas::parse(&c.message_type, &c.message_definition).ok()?;
+            let computed = schemas.type_description_hash().ok()?;
+            (computed != c.type_description_hash).then(|| ReaderError::TypeHashMismatch {
+                topic: c.topic.clone(),
+                message_type: c.message_type.clone(),
+                stored: c.type_description_hash.clone(),
+                computed,
+            })
+        })
+        .collect()
+}
+
+/// Whether a payload of a bag compressed per message is compressed
+///
+/// Bags with per-topic compression store the payloads of other topics as they are.
+pub(crate) fn is_compressed(data: &[u8]) -> bool {
+    CompressionFormat::of_payload(data) == CompressionFormat::Zstd
+}
+
+/// Largest zstd compression ratio, reached by run-length blocks of 128 KiB in 4 bytes
+///
+/// Frame content sizes beyond it are corrupt and must not size an allocation.
+#[cfg(feature = "compression")]
+const MAX_COMPRESSION_RATIO: u64 = 1 << 15;
+
+/// Capacity of the decompression scratch buffer kept between messages
+#[cfg(feature = "compression")]
+const SCRATCH_RETAINED: usize = 16 << 20;
+
+/// zstd context and scratch buffer reused for every payload decompressed on a thread
+#[cfg(feature = "compression")]
+struct PayloadDecompressor {
+    context: zstd::zstd_safe::DCtx<'static>,
+    scratch: Vec<u8>,
+}
+
+#[cfg(feature = "compression")]
+thread_local! {
+    static PAYLOAD_DECOMPRESSOR: std::cell::RefCell<PayloadDecompressor> =
+        std::cell::RefCell::new(PayloadDecompressor {
+            context: zstd::zstd_safe::DCtx::create(),
+            scratch: Vec::new(),
+        });
+}
+
+#[cfg(feature = "compression")]
+impl PayloadDecompressor {
+    /// Decompress `data` into a vector of exactly the decompressed size
+    ///
+    /// Frames recording their content size, like those of rosbag2 and this crate's
+    /// writer, are decompressed in one pass into the result. Other frames are streamed
+    /// into the scratch buffer, which grows to the largest payload, and copied out.
+    fn decompress(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        use zstd::zstd_safe::{get_error_name, get_frame_content_size, ResetDirective};
+
+        let error = |code| {
+            ReaderError::Compression(format!(
+                "Failed to decompress message: {}",
+                get_error_name(code)
+            ))
+        };
+
+        let ratio_bound = (data.len() as u64).saturating_mul(MAX_COMPRESSION_RATIO);
+        let content_size = get_frame_content_size(data)
+            .ok()
+            .flatten()
+            .filter(|&size| size <= ratio_bound)
+            .and_then(|size| usize::try_from(size).ok());
+        if let Some(size) = content_size {
+            let mut output = Vec::with_capacity(size);
+            // Several concatenated frames do not fit, the stream below handles them
+            if self.context.decompress(&mut output, data).is_ok() {
+                return Ok(output);
+            }
+        }
+
+        self.context
+            .reset(ResetDirective::SessionOnly)
+            .map_err(error)?;
+        if self.scratch.capacity() > SCRATCH_RETAINED {
+            self.scratch = Vec::new();
+        }
+        self.scratch.clear();
+        let mut input = zstd::zstd_safe::InBuffer::around(data);
+        loop {
+            if self.scratch.len() == self.scratch.capacity() {
+                self.scratch
+                    .reserve(self.scratch.capacity().max(4 * data.len()).max(4096));
+            }
+            let pos = self.scratch.len();
+            let mut output = zstd::zstd_safe::OutBuffer::around_pos(&mut self.scratch, pos);
+            let remaining = self
+                .context
+                .decompress_stream(&mut output, &mut input)
+                .map_err(error)?;
+            let output_full = output.pos() == output.capacity();
+            if input.pos() == data.len() {
+                if remaining == 0 {
+                    break;
+                }
+                if !output_full {
+                    return Err(ReaderError::Compression(
+                        "Failed to decompress message: truncated zstd frame".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(self.scratch.to_vec())
+    }
+}
+
+/// Decompress a payload of a bag compressed per message
+#[cfg(feature = "compression")]
+pub(crate) fn decompress_payload(data: &[u8]) -> Result<Vec<u8>> {
+    PAYLOAD_DECOMPRESSOR.with(|decompressor| decompressor.borrow_mut().decompress(data))
+}
+
+/// Decompress a payload of a bag compressed per message
+#[cfg(not(feature = "compression"))]
+pub(crate) fn decompress_payload(_data: &[u8]) -> Result<Vec<u8>> {
+    Err(ReaderError::UnsupportedCompressionFormat {
+        format: "zstd (feature not enabled)".to_string(),
+    })
+}
 
 impl Drop for Reader {
     fn drop(&mut self) {
@@ -388,4 +2262,911 @@ rosbag2_bagfile_information:
         assert_eq!(reader.duration(), 1000000000);
         assert_eq!(reader.message_count(), 10);
     }
+
+    #[test]
+    fn test_empty_bag_times_ignore_sentinel() {
+        // rosbag2 records empty bags with the maximum timestamp as starting time
+        let temp_dir = TempDir::new().unwrap();
+        let metadata = create_test_metadata()
+            .replace("1000000000\n", "0\n")
+            .replace("1234567890000000000", "9223372036854775807")
+            .replace("count: 10", "count: 0");
+        fs::write(temp_dir.path().join("metadata.yaml"), metadata).unwrap();
+        fs::write(temp_dir.path().join("test.db3"), b"").unwrap();
+
+        let reader = Reader::new(temp_dir.path()).unwrap();
+        assert_eq!(reader.message_count(), 0);
+        assert_eq!(reader.start_time(), 0);
+        assert_eq!(reader.end_time(), 0);
+        assert_eq!(reader.duration(), 0);
+    }
+
+    /// Copy a test bag so that no other test holds handles to its files
+    fn copy_test_bag(name: &str) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for entry in fs::read_dir(Path::new("tests/test_bags").join(name)).unwrap() {
+            let entry = entry.unwrap();
+            fs::copy(entry.path(), temp_dir.path().join(entry.file_name())).unwrap();
+        }
+        temp_dir
+    }
+
+    /// Number of file descriptors and memory maps of this process pointing into `dir`
+    #[cfg(all(target_os = "linux", feature = "sqlite", feature = "mcap"))]
+    fn open_handles(dir: &Path) -> usize {
+        let dir = dir.canonicalize().unwrap();
+        let descriptors = fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|entry| fs::read_link(entry.ok()?.path()).ok())
+            .filter(|target| target.starts_with(&dir))
+            .count();
+        descriptors + memory_maps(&dir)
+    }
+
+    /// Number of memory maps of this process pointing into `dir`
+    #[cfg(all(target_os = "linux", feature = "sqlite", feature = "mcap"))]
+    fn memory_maps(dir: &Path) -> usize {
+        let dir = dir.canonicalize().unwrap();
+        let maps = fs::read_to_string("/proc/self/maps").unwrap();
+        maps.lines()
+            .filter(|line| line.contains(dir.to_str().unwrap()))
+            .count()
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_memory_mapped_reading_matches_buffered() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let read = |memory_map: bool| {
+                let options = ReaderOptions {
+                    memory_map,
+                    ..ReaderOptions::default()
+                };
+                let mut reader = Reader::with_options(bag.path(), options).unwrap();
+                reader.open().unwrap();
+                let messages: Vec<(u64, String, Vec<u8>)> = reader
+                    .raw_messages()
+                    .unwrap()
+                    .map(|m| m.map(|m| (m.timestamp, m.connection.topic, m.raw_data)))
+                    .collect::<Result<_>>()
+                    .unwrap();
+                #[cfg(target_os = "linux")]
+                {
+                    let mapped = name == "test_bag_sqlite3" || cfg!(feature = "unsafe-opt");
+                    assert_eq!(memory_maps(bag.path()) > 0, memory_map && mapped, "{name}");
+                }
+                messages
+            };
+
+            let buffered = read(false);
+            assert!(!buffered.is_empty());
+            assert_eq!(read(true), buffered, "{name}");
+        }
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_recompute_statistics_flags_stale_metadata() {
+        use crate::metadata::StatisticsDiscrepancy;
+
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let mut reader = Reader::builder(bag.path()).open().unwrap();
+            let statistics = reader.recompute_statistics().unwrap();
+            assert!(statistics.is_consistent(), "{name}: {statistics:?}");
+            let (count, start, duration) = (
+                reader.message_count(),
+                reader.start_time(),
+                reader.duration(),
+            );
+            assert_eq!(statistics.message_count, count);
+            reader.close().unwrap();
+
+            // Metadata of a recording that stopped being updated midway
+            let path = bag.path().join("metadata.yaml");
+            let mut metadata = BagMetadata::from_file(&path).unwrap();
+            let info = &mut metadata.rosbag2_bagfile_information;
+            info.message_count = count - 10;
+            info.duration.nanoseconds = duration / 2;
+            let topic = &mut info.topics_with_message_count[0];
+            topic.message_count += 1;
+            let topic_name = topic.topic_metadata.name.clone();
+            let topic_count = topic.message_count - 1;
+            metadata.to_file(&path).unwrap();
+
+            let mut reader = Reader::builder(bag.path()).open().unwrap();
+            assert_eq!(reader.message_count(), count - 10);
+            let statistics = reader.recompute_statistics().unwrap();
+            assert_eq!(
+                statistics.discrepancies,
+                [
+                    StatisticsDiscrepancy::MessageCount {
+                        metadata: count - 10,
+                        storage: count
+                    },
+                    StatisticsDiscrepancy::Duration {
+                        metadata: duration / 2,
+                        storage: duration
+                    },
+                    StatisticsDiscrepancy::TopicMessageCount {
+                        topic: topic_name.clone(),
+                        metadata: topic_count + 1,
+                        storage: topic_count
+                    },
+                ],
+                "{name}"
+            );
+            assert_eq!(reader.message_count(), count);
+            assert_eq!(reader.start_time(), start);
+            assert_eq!(reader.duration(), duration);
+            let info = reader.metadata().unwrap().info();
+            assert_eq!(info.topics_with_message_count[0].message_count, topic_count);
+            assert!(reader.recompute_statistics().unwrap().is_consistent());
+        }
+
+        let mut reader = Reader::new(Path::new("tests/test_bags/test_bag_mcap")).unwrap();
+        assert!(reader.recompute_statistics().is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_skip_metadata_reads_bag_from_storage() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let mut expected = Reader::new(bag.path()).unwrap();
+            expected.open().unwrap();
+            fs::remove_file(bag.path().join("metadata.yaml")).unwrap();
+            assert!(Reader::new(bag.path()).is_err());
+
+            let reader = Reader::builder(bag.path())
+                .skip_metadata(true)
+                .open()
+                .unwrap();
+            assert_eq!(reader.message_count(), expected.message_count(), "{name}");
+            assert_eq!(reader.start_time(), expected.start_time(), "{name}");
+            assert_eq!(reader.end_time(), expected.end_time(), "{name}");
+            let topics = |reader: &Reader| {
+                let mut topics: Vec<_> = reader
+                    .connections()
+                    .iter()
+                    .map(|c| {
+                        let definition = c.message_definition.data.clone();
+                        (c.topic.clone(), c.message_type.clone(), definition)
+                    })
+                    .collect();
+                topics.sort();
+                topics
+            };
+            assert_eq!(topics(&reader), topics(&expected), "{name}");
+            assert_eq!(
+                reader.raw_messages().unwrap().count() as u64,
+                expected.message_count()
+            );
+
+            // Selecting the other storage finds no files
+            let other = if name == "test_bag_mcap" {
+                StoragePlugin::Sqlite3
+            } else {
+                StoragePlugin::Mcap
+            };
+            assert!(Reader::builder(bag.path())
+                .skip_metadata(true)
+                .storage(other)
+                .build()
+                .is_err());
+        }
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_storage_format_detected_from_contents() {
+        for (name, file) in [
+            ("test_bag_sqlite3", "test_bag_sqlite3.db3"),
+            ("test_bag_mcap", "test_bag_mcap.mcap"),
+        ] {
+            let bag = copy_test_bag(name);
+            let mut expected = Reader::new(bag.path()).unwrap();
+            expected.open().unwrap();
+            let count = expected.message_count();
+
+            // Extension-less copy, listed in metadata without a storage identifier
+            fs::rename(bag.path().join(file), bag.path().join("bag_0")).unwrap();
+            let path = bag.path().join("metadata.yaml");
+            let mut metadata = BagMetadata::from_file(&path).unwrap();
+            metadata.rosbag2_bagfile_information.storage_identifier = String::new();
+            metadata.rosbag2_bagfile_information.relative_file_paths = vec!["bag_0".into()];
+            metadata.to_file(&path).unwrap();
+            let mut reader = Reader::new(bag.path()).unwrap();
+            reader.open().unwrap();
+            assert_eq!(
+                reader.raw_messages().unwrap().count() as u64,
+                count,
+                "{name}"
+            );
+
+            // Found without metadata
+            fs::remove_file(&path).unwrap();
+            let reader = Reader::builder(bag.path())
+                .skip_metadata(true)
+                .open()
+                .unwrap();
+            assert_eq!(reader.message_count(), count, "{name}");
+
+            // The contents win over a misleading extension
+            let data = fs::read(bag.path().join("bag_0")).unwrap();
+            let input = StorageInput::from_bytes("bag_0.memory", data);
+            let mut reader =
+                Reader::from_inputs(vec![input], None, ReaderOptions::default()).unwrap();
+            reader.open().unwrap();
+            assert_eq!(reader.message_count(), count, "{name}");
+        }
+
+        let bag = TempDir::new().unwrap();
+        fs::write(bag.path().join("notes.db3"), "not a database").unwrap();
+        assert!(BagMetadata::from_storage_files(bag.path(), None)
+            .is_ok_and(|metadata| metadata.info().relative_file_paths == ["notes.db3"]));
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_reader_from_inputs_in_memory() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let mut expected = Reader::new(bag.path()).unwrap();
+            expected.open().unwrap();
+            let expected_messages: Vec<_> = expected
+                .raw_messages()
+                .unwrap()
+                .map(|m| m.unwrap())
+                .map(|m| (m.connection.topic, m.timestamp, m.raw_data))
+                .collect();
+
+            let metadata = fs::read_to_string(bag.path().join("metadata.yaml")).unwrap();
+            let metadata = BagMetadata::from_yaml(&metadata).unwrap();
+            let inputs: Vec<_> = metadata
+                .info()
+                .relative_file_paths
+                .iter()
+                .map(|file| {
+                    let reader = fs::File::open(bag.path().join(file)).unwrap();
+                    StorageInput::from_reader(file, reader).unwrap()
+                })
+                .collect();
+            // Nothing is read from disk once the inputs are loaded
+            drop(bag);
+
+            for metadata in [Some(metadata), None] {
+                let with_metadata = metadata.is_some();
+                let mut reader =
+                    Reader::from_inputs(inputs.clone(), metadata, ReaderOptions::default())
+                        .unwrap();
+                reader.open().unwrap();
+                assert_eq!(reader.message_count(), expected.message_count());
+                assert_eq!(reader.start_time(), expected.start_time());
+                assert_eq!(reader.end_time(), expected.end_time());
+                let messages: Vec<_> = reader
+                    .raw_messages()
+                    .unwrap()
+                    .map(|m| m.unwrap())
+                    .map(|m| (m.connection.topic, m.timestamp, m.raw_data))
+                    .collect();
+                assert_eq!(messages, expected_messages, "{name} {with_metadata}");
+                reader.reopen().unwrap();
+                let files = reader.files().unwrap();
+                assert_eq!(files[0].path, inputs[0].path());
+            }
+        }
+    }
+
+    #[cfg(feature = "default")]
+    #[test]
+    fn test_reader_from_inputs_wal_database() {
+        use crate::storage::sqlite::SqliteJournalMode;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("wal");
+        let mut writer = crate::Writer::builder(&bag_path)
+            .sqlite_journal_mode(SqliteJournalMode::Wal)
+            .open()
+            .unwrap();
+        let connection = writer
+            .add_connection(
+                "/data".to_string(),
+                "std_msgs/msg/String".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let payload = [0, 1, 0, 0, 6, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0];
+        writer.write(&connection, 42, &payload).unwrap();
+        writer.close().unwrap();
+
+        let data = fs::read(bag_path.join("wal.db3")).unwrap();
+        assert_eq!(data[18..20], [2, 2]);
+        let input = StorageInput::from_bytes("wal.db3", data);
+        let mut reader = Reader::from_inputs(vec![input], None, ReaderOptions::default()).unwrap();
+        reader.open().unwrap();
+        let messages: Vec<_> = reader.raw_messages().unwrap().map(|m| m.unwrap()).collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].raw_data, payload);
+    }
+
+    #[cfg(feature = "mcap")]
+    #[test]
+    fn test_storage_override() {
+        let bag = copy_test_bag("test_bag_mcap");
+        let metadata_path = bag.path().join("metadata.yaml");
+        let metadata = fs::read_to_string(&metadata_path).unwrap();
+        fs::write(
+            &metadata_path,
+            metadata.replace(
+                "storage_identifier: mcap",
+                "storage_identifier: rosbag2_mcap",
+            ),
+        )
+        .unwrap();
+
+        assert!(Reader::new(bag.path()).is_err());
+        let reader = Reader::builder(bag.path())
+            .storage(StoragePlugin::Mcap)
+            .open()
+            .unwrap();
+        assert!(reader.messages().unwrap().count() > 0);
+    }
+
+    #[test]
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    fn test_topic_allowlist_and_default_time_range() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let topics = [
+                "/test/geometry_msgs/pose",
+                "/test/geometry_msgs/point",
+                "/test/geometry_msgs/twist",
+            ];
+            let full = Reader::builder(bag.path())
+                .topics(topics.iter().copied().chain(["/not/in/bag"]))
+                .open()
+                .unwrap();
+            assert_eq!(full.connections().len(), 3);
+            assert!(full
+                .connections()
+                .iter()
+                .all(|c| topics.contains(&c.topic.as_str())));
+            let timestamps: Vec<u64> = full
+                .messages()
+                .unwrap()
+                .map(|m| {
+                    let m = m.unwrap();
+                    assert!(topics.contains(&m.topic.as_str()));
+                    m.timestamp
+                })
+                .collect();
+            assert_eq!(timestamps.len(), 6, "{name}");
+
+            let (start, stop) = (timestamps[1], timestamps[5]);
+            let reader = Reader::builder(bag.path())
+                .topics(topics)
+                .time_range(Some(start), Some(stop))
+                .open()
+                .unwrap();
+            let ranged: Vec<u64> = reader
+                .raw_messages()
+                .unwrap()
+                .map(|m| m.unwrap().timestamp)
+                .collect();
+            let expected: Vec<u64> = timestamps
+                .iter()
+                .copied()
+                .filter(|t| (start..stop).contains(t))
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(ranged, expected, "{name}");
+            // Per-call arguments take precedence over the defaults
+            assert_eq!(
+                reader
+                    .raw_messages_filtered(None, Some(timestamps[0]), Some(timestamps[5] + 1))
+                    .unwrap()
+                    .count(),
+                timestamps.len()
+            );
+
+            let empty = Reader::builder(bag.path())
+                .topics(["/not/in/bag"])
+                .open()
+                .unwrap();
+            assert!(empty.connections().is_empty());
+            assert_eq!(empty.messages().unwrap().count(), 0);
+        }
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "compression"))]
+    #[test]
+    fn test_decode_on_read_decompresses_message_compression() {
+        use crate::types::{CompressionFormat, CompressionMode};
+        use crate::Writer;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("bag");
+        let mut writer = Writer::new(&bag_path, None, None).unwrap();
+        writer
+            .set_compression(CompressionMode::Message, CompressionFormat::Zstd)
+            .unwrap();
+        writer.open().unwrap();
+        let connection = writer
+            .add_connection(
+                "/data".to_string(),
+                "std_msgs/msg/String".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let payload = [0, 1, 0, 0, 6, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0];
+        writer.write(&connection, 42, &payload).unwrap();
+        writer.close().unwrap();
+
+        let reader = Reader::builder(&bag_path).open().unwrap();
+        let message = reader.messages().unwrap().next().unwrap().unwrap();
+        assert_eq!(message.data, payload);
+        let borrowed = reader.raw_messages_borrowed().unwrap().next().unwrap();
+        assert_eq!(*borrowed.unwrap().data, payload);
+
+        let reader = Reader::builder(&bag_path)
+            .decode_on_read(false)
+            .open()
+            .unwrap();
+        let stored = reader.raw_messages().unwrap().next().unwrap().unwrap();
+        assert_ne!(stored.raw_data, payload);
+        assert_eq!(
+            zstd::decode_all(stored.raw_data.as_slice()).unwrap(),
+            payload
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decompress_payload_reuses_context() {
+        let payload: Vec<u8> = (0..200_000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect();
+
+        // One-shot frames record their size, streamed frames do not
+        let sized = zstd::bulk::compress(&payload, 3).unwrap();
+        let streamed = zstd::encode_all(payload.as_slice(), 3).unwrap();
+        for _ in 0..2 {
+            assert_eq!(decompress_payload(&sized).unwrap(), payload);
+            assert_eq!(decompress_payload(&streamed).unwrap(), payload);
+        }
+        let decompressed = decompress_payload(&streamed).unwrap();
+        assert_eq!(decompressed.capacity(), payload.len());
+
+        // Concatenated frames decompress to the concatenated payloads
+        let mut frames = sized.clone();
+        frames.extend_from_slice(&zstd::bulk::compress(b"tail", 0).unwrap());
+        let mut expected = payload.clone();
+        expected.extend_from_slice(b"tail");
+        assert_eq!(decompress_payload(&frames).unwrap(), expected);
+
+        // Broken payloads fail without spoiling the context for the next one
+        assert!(decompress_payload(&sized[..sized.len() / 2]).is_err());
+        assert!(decompress_payload(&streamed[..streamed.len() / 2]).is_err());
+        assert!(decompress_payload(b"not zstd").is_err());
+        assert_eq!(decompress_payload(&streamed).unwrap(), payload);
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "compression"))]
+    #[test]
+    fn test_per_topic_compression() {
+        use crate::types::{CompressionFormat, CompressionMode};
+        use crate::Writer;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("bag");
+        let mut writer = Writer::builder(&bag_path)
+            .topic_compression("/camera/*", CompressionFormat::Zstd, 3)
+            .open()
+            .unwrap();
+        let mut add = |topic: &str| {
+            writer
+                .add_connection(
+                    topic.to_string(),
+                    "std_msgs/msg/String".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap()
+        };
+        let (image, imu, nested) = (add("/camera/image"), add("/imu"), add("/camera/left/image"));
+        let payload = [0, 1, 0, 0, 6, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0];
+        for (timestamp, connection) in [&image, &imu, &nested].into_iter().enumerate() {
+            writer
+                .write(connection, timestamp as u64, &payload)
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        // Only topics directly below /camera are stored compressed
+        let reader = Reader::builder(&bag_path)
+            .decode_on_read(false)
+            .open()
+            .unwrap();
+        assert_eq!(
+            reader.metadata().unwrap().compression_mode(),
+            Some(CompressionMode::Message.as_str())
+        );
+        let stored: Vec<_> = reader
+            .raw_messages()
+            .unwrap()
+            .map(|m| m.unwrap().raw_data)
+            .collect();
+        assert_ne!(stored[0], payload);
+        assert_eq!(stored[1], payload);
+        assert_eq!(stored[2], payload);
+
+        let reader = Reader::builder(&bag_path).open().unwrap();
+        for message in reader.messages().unwrap() {
+            assert_eq!(message.unwrap().data, payload);
+        }
+        for message in reader.raw_messages_borrowed().unwrap() {
+            assert_eq!(*message.unwrap().data, payload);
+        }
+
+        // Topics can opt out of message compression, file compression has no topics
+        let bag_path = temp_dir.path().join("excluded");
+        let mut writer = Writer::builder(&bag_path)
+            .compression(CompressionMode::Message, CompressionFormat::Zstd)
+            .topic_compression("/imu", CompressionFormat::None, 0)
+            .open()
+            .unwrap();
+        let imu = writer
+            .add_connection(
+                "/imu".to_string(),
+                "std_msgs/msg/String".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        writer.write(&imu, 0, &payload).unwrap();
+        writer.close().unwrap();
+        let reader = Reader::builder(&bag_path)
+            .decode_on_read(false)
+            .open()
+            .unwrap();
+        let stored = reader.raw_messages().unwrap().next().unwrap().unwrap();
+        assert_eq!(stored.raw_data, payload);
+        assert!(Writer::builder(temp_dir.path().join("file"))
+            .compression(CompressionMode::File, CompressionFormat::Zstd)
+            .topic_compression("/camera/*", CompressionFormat::Zstd, 0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_set_options_only_before_open() {
+        let bag = copy_test_bag("test_bag_sqlite3");
+        let mut reader = Reader::new(bag.path()).unwrap();
+        assert_eq!(reader.options(), &ReaderOptions::default());
+        reader.set_options(ReaderOptions::memory_mapped()).unwrap();
+        assert!(reader.options().memory_map);
+
+        reader.open().unwrap();
+        assert!(reader.set_options(ReaderOptions::default()).is_err());
+        // Shards open their readers with the same options
+        let shards = reader.shards(std::time::Duration::from_secs(3600)).unwrap();
+        assert!(shards[0].open().unwrap().reader().options().memory_map);
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_borrowed_raw_messages_match_owned() {
+        for bag in [
+            "tests/test_bags/test_bag_sqlite3",
+            "tests/test_bags/test_bag_mcap",
+        ] {
+            let mut reader = Reader::new(bag).unwrap();
+            reader.open().unwrap();
+            let owned: Vec<RawMessage> = reader
+                .raw_messages()
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap();
+            let borrowed: Vec<RawMessageRef<'_>> = reader
+                .raw_messages_borrowed()
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(owned.len(), borrowed.len());
+            for (owned, borrowed) in owned.iter().zip(&borrowed) {
+                assert_eq!(owned.timestamp, borrowed.timestamp);
+                assert_eq!(owned.connection, *borrowed.connection);
+                assert_eq!(owned.raw_data, *borrowed.data);
+            }
+        }
+    }
+
+    #[cfg(feature = "default")]
+    #[test]
+    fn test_uncompressed_mcap_messages_are_borrowed() {
+        use crate::types::{CompressionFormat, CompressionMode, StoragePlugin};
+        use crate::Writer;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for (name, mode, borrowed) in [
+            ("plain", CompressionMode::None, true),
+            ("storage", CompressionMode::Storage, false),
+        ] {
+            let bag_path = temp_dir.path().join(name);
+            let mut writer = Writer::new(&bag_path, None, Some(StoragePlugin::Mcap)).unwrap();
+            writer
+                .set_compression(mode, CompressionFormat::Zstd)
+                .unwrap();
+            writer.open().unwrap();
+            let connection = writer
+                .add_connection(
+                    "/image".to_string(),
+                    "sensor_msgs/msg/Image".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            for i in 0..3u8 {
+                let mut data = vec![0, 1, 0, 0];
+                data.extend(std::iter::repeat(i).take(4096));
+                writer.write(&connection, u64::from(i), &data).unwrap();
+            }
+            writer.close().unwrap();
+
+            let mut reader = Reader::new(&bag_path).unwrap();
+            reader.open().unwrap();
+            let messages: Vec<RawMessageRef<'_>> = reader
+                .raw_messages_borrowed()
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(messages.len(), 3);
+            assert!(messages.iter().all(|m| m.is_borrowed() == borrowed));
+            assert_eq!(messages[2].data[4..], [2; 4096]);
+            assert_eq!(messages[2].connection.topic, "/image");
+        }
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_files_report_split_boundaries() {
+        use crate::types::StoragePlugin;
+        use crate::Writer;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for storage in [StoragePlugin::Sqlite3, StoragePlugin::Mcap] {
+            let bag_path = temp_dir.path().join(storage.as_str());
+            let mut writer = Writer::builder(&bag_path)
+                .storage(storage)
+                .max_bagfile_duration(std::time::Duration::from_secs(3))
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/count".to_string(),
+                    "std_msgs/msg/UInt32".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            for i in 0..10u64 {
+                writer
+                    .write(&connection, i * 1_000_000_000, &[0, 1, 0, 0, 0, 0, 0, 0])
+                    .unwrap();
+            }
+            writer.close().unwrap();
+
+            let mut reader = Reader::new(&bag_path).unwrap();
+            assert!(matches!(reader.files(), Err(ReaderError::BagNotOpen)));
+            reader.open().unwrap();
+            let files = reader.files().unwrap();
+            let spans: Vec<(usize, u64, u64, u64)> = files
+                .iter()
+                .map(|f| (f.index, f.start_time, f.end_time, f.message_count))
+                .collect();
+            assert_eq!(
+                spans,
+                [
+                    (0, 0, 2_000_000_000, 3),
+                    (1, 3_000_000_000, 5_000_000_000, 3),
+                    (2, 6_000_000_000, 8_000_000_000, 3),
+                    (3, 9_000_000_000, 9_000_000_000, 1),
+                ],
+                "{storage:?}"
+            );
+            assert!(files.iter().all(|f| f.path.starts_with(&bag_path)));
+
+            let in_range: Vec<usize> = reader
+                .files_in_range(2_500_000_000, 6_000_000_001)
+                .unwrap()
+                .iter()
+                .map(|f| f.index)
+                .collect();
+            assert_eq!(in_range, [1, 2]);
+
+            for message in reader.raw_messages().unwrap() {
+                let message = message.unwrap();
+                assert_eq!(message.file_index as u64, message.timestamp / 3_000_000_000);
+            }
+            for message in reader.messages().unwrap() {
+                let message = message.unwrap();
+                assert!(files[message.file_index].start_time <= message.timestamp);
+                assert!(message.timestamp <= files[message.file_index].end_time);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    fn test_reindex_rebuilds_metadata() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let original =
+                BagMetadata::from_file(format!("tests/test_bags/{name}/metadata.yaml")).unwrap();
+            let bag = copy_test_bag(name);
+            fs::remove_file(bag.path().join("metadata.yaml")).unwrap();
+
+            let reindexed = Reader::reindex(bag.path(), None).unwrap();
+            assert_eq!(
+                reindexed.message_count(),
+                original.message_count(),
+                "{name}"
+            );
+            assert_eq!(reindexed.start_time(), original.start_time(), "{name}");
+            assert_eq!(reindexed.duration(), original.duration(), "{name}");
+            let info = reindexed.info();
+            assert_eq!(info.files.len(), 1);
+            assert_eq!(info.files[0].path, info.relative_file_paths[0]);
+            assert_eq!(info.files[0].message_count, original.message_count());
+
+            // The written metadata.yaml opens like the original
+            let mut reader = Reader::new(bag.path()).unwrap();
+            reader.open().unwrap();
+            assert_eq!(reader.message_count(), original.message_count());
+            let mut topics: Vec<(String, u64)> = reader
+                .metadata()
+                .unwrap()
+                .info()
+                .topics_with_message_count
+                .iter()
+                .map(|t| (t.topic_metadata.name.clone(), t.message_count))
+                .collect();
+            let mut expected: Vec<(String, u64)> = original
+                .info()
+                .topics_with_message_count
+                .iter()
+                .map(|t| (t.topic_metadata.name.clone(), t.message_count))
+                .collect();
+            topics.sort();
+            expected.sort();
+            assert_eq!(topics, expected, "{name}");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    fn test_close_releases_handles_and_allows_reopen() {
+        for name in ["test_bag_sqlite3", "test_bag_mcap"] {
+            let bag = copy_test_bag(name);
+            let mut reader = Reader::new(bag.path()).unwrap();
+            reader.open().unwrap();
+            let count = reader.messages().unwrap().count();
+            assert!(count > 0);
+            #[cfg(target_os = "linux")]
+            if name == "test_bag_sqlite3" {
+                assert!(open_handles(bag.path()) > 0);
+            }
+
+            reader.close().unwrap();
+            assert!(!reader.is_open());
+            assert!(reader.messages().is_err());
+            #[cfg(target_os = "linux")]
+            assert_eq!(open_handles(bag.path()), 0, "{name} left files open");
+
+            reader.open().unwrap();
+            assert_eq!(reader.messages().unwrap().count(), count);
+            reader.reopen().unwrap();
+            assert_eq!(reader.messages().unwrap().count(), count);
+
+            // Closing twice is a no-op, and the files can be removed afterwards
+            reader.close().unwrap();
+            reader.close().unwrap();
+            let path = bag.path().to_path_buf();
+            bag.close().unwrap();
+            assert!(!path.exists());
+        }
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_verify_type_hashes() {
+        use crate::types::{MessageDefinition, MessageDefinitionFormat, StoragePlugin};
+        use crate::Writer;
+
+        let string_hash = "RIHS01_df668c740482bbd48fb39d76a70dfd4bd59db1288021743503259e948f6b1a18";
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for plugin in [StoragePlugin::Sqlite3, StoragePlugin::Mcap] {
+            let bag_path = temp_dir.path().join(plugin.as_str());
+            let mut writer = Writer::new(&bag_path, None, Some(plugin)).unwrap();
+            writer.open().unwrap();
+            // The second type claims the hash of std_msgs/msg/String with other fields
+            for (topic, message_type, definition) in [
+                ("/matching", "std_msgs/msg/String", "string data"),
+                ("/changed", "example_msgs/msg/Text", "string text"),
+            ] {
+                writer
+                    .add_connection(
+                        topic.to_string(),
+                        message_type.to_string(),
+                        Some(MessageDefinition {
+                            format: MessageDefinitionFormat::Msg,
+                            data: definition.to_string(),
+                        }),
+                        Some(string_hash.to_string()),
+                        None,
+                        None,
+                    )
+                    .unwrap();
+            }
+            writer.close().unwrap();
+
+            // Off by default; the check is still available on demand
+            let reader = Reader::builder(&bag_path).open().unwrap();
+            let mismatches = reader.type_hash_mismatches();
+            assert_eq!(mismatches.len(), 1, "{plugin:?}");
+            assert!(matches!(
+                &mismatches[0],
+                ReaderError::TypeHashMismatch { topic, stored, .. }
+                    if topic == "/changed" && stored == string_hash
+            ));
+
+            let warned = Reader::builder(&bag_path)
+                .verify_type_hashes(TypeHashVerification::Warn)
+                .open();
+            assert!(warned.is_ok());
+            let strict = Reader::builder(&bag_path)
+                .verify_type_hashes(TypeHashVerification::Error)
+                .open();
+            assert!(matches!(strict, Err(ReaderError::TypeHashMismatch { .. })));
+            let only_matching = Reader::builder(&bag_path)
+                .topics(["/matching"])
+                .verify_type_hashes(TypeHashVerification::Error)
+                .open();
+            assert!(only_matching.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let matches = |pattern: &str, topic: &str| glob_match(pattern.as_bytes(), topic.as_bytes());
+        assert!(matches("/camera/*", "/camera/image_raw"));
+        assert!(!matches("/camera/*", "/camera/left/image_raw"));
+        assert!(matches("/camera/**", "/camera/left/image_raw"));
+        assert!(matches("/camera/*/image_raw", "/camera/left/image_raw"));
+        assert!(matches("/*/imu", "/robot1/imu"));
+        assert!(matches("**/tf", "/robot1/tf"));
+        assert!(!matches("**/tf", "/robot1/tf_static"));
+        assert!(matches("/tf*", "/tf_static"));
+        assert!(matches("/imu?", "/imu2"));
+        assert!(!matches("/imu?", "/imu"));
+        assert!(!matches("/imu?", "/imu/"));
+        assert!(matches("/chatter", "/chatter"));
+        assert!(!matches("/chatter", "/chatter2"));
+    }
 }
@@ -0,0 +1,248 @@
+//! Registry of user-provided message definitions
+//!
+//! Bags that predate stored definitions, and connections created by writers that do
+//! not pass one, leave the schema-driven decoders and payload validation without a
+//! layout for custom message types. A [`TypeRegistry`] holds the `.msg` or IDL
+//! definitions of such types; set on a [`Reader`](crate::Reader) it fills in the
+//! definitions of connections that have none, and set on a
+//! [`Writer`](crate::Writer) it supplies the definition stored for new connections.
+//!
+//! Types not registered are looked up in the bundled standard definitions (see
+//! [`crate::definitions`]).
+
+use crate::definitions::{normalize_type_name, DefinitionResolver};
+use crate::error::{BagError, Result};
+use crate::schema::{definition_sections, MessageSchemas};
+use crate::types::{Connection, MessageDefinition, MessageDefinitionFormat};
+
+/// Message definitions of custom types by `pkg/msg/Type`
+///
+/// # Example
+/// ```
+/// use rosbags_rs::registry::TypeRegistry;
+///
+/// let mut registry = TypeRegistry::new();
+/// registry
+///     .register_msgdef("my_pkg/msg/Custom", "std_msgs/Header header\nfloat64[3] values\n")
+///     .unwrap();
+///
+/// let schemas = registry.schemas("my_pkg/msg/Custom").unwrap();
+/// assert_eq!(schemas.root().fields[1].type_name, "float64[3]");
+/// assert!(schemas.get("std_msgs/msg/Header").is_some());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeRegistry {
+    resolver: DefinitionResolver,
+    /// Registered types in registration order
+    registered: Vec<String>,
+}
+
+impl TypeRegistry {
+    /// Create a registry that knows only the bundled definitions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the `.msg` definition of `message_type`
+    ///
+    /// `text` is either the `.msg` file of the type alone or a concatenated
+    /// definition as stored in bags, in which case every `MSG:` section is registered
+    /// as well. Registered types take precedence over bundled ones.
+    pub fn register_msgdef(&mut self, message_type: &str, text: &str) -> Result<()> {
+        // Parse first so that malformed definitions are rejected when registered
+        MessageSchemas::parse_msg(message_type, text)?;
+
+        let mut sections = definition_sections(text).into_iter();
+        self.insert(
+            message_type,
+            sections.next().unwrap_or_default().to_string(),
+        );
+        for section in sections {
+            let section = section.trim_start_matches(['\r', '\n']);
+            let (first_line, body) = section.split_once('\n').unwrap_or((section, ""));
+            let name = first_line.trim().strip_prefix("MSG:").ok_or_else(|| {
+                BagError::schema_validation(format!(
+                    "Expected 'MSG:' line in definition of {message_type}, found '{}'",
+                    first_line.trim()
+                ))
+            })?;
+            self.insert(name.trim(), body.to_string());
+        }
+        Ok(())
+    }
+
+    /// Register the IDL definition of `message_type`
+    ///
+    /// Every struct declared in `text` is registered, converted to `.msg` notation.
+    pub fn register_idl(&mut self, message_type: &str, text: &str) -> Result<()> {
+        let schemas = MessageSchemas::parse_idl(message_type, text)?;
+        let root = schemas.root().name.clone();
+        self.insert(&root, schemas.root().to_msg());
+        let mut others: Vec<_> = schemas.iter().filter(|s| s.name != root).collect();
+        others.sort_by(|a, b| a.name.cmp(&b.name));
+        for schema in others {
+            self.insert(&schema.name, schema.to_msg());
+        }
+        Ok(())
+    }
+
+    /// Register a definition in whichever format it is stored
+    pub fn register(&mut self, message_type: &str, definition: &MessageDefinition) -> Result<()> {
+        match definition.format {
+            MessageDefinitionFormat::Msg => self.register_msgdef(message_type, &definition.data),
+            MessageDefinitionFormat::Idl => self.register_idl(message_type, &definition.data),
+            MessageDefinitionFormat::None => Err(BagError::schema_validation(format!(
+                "No message definition to register for {message_type}"
+            ))),
+        }
+    }
+
+    /// Whether `message_type` was registered (bundled types are not included)
+    pub fn contains(&self, message_type: &str) -> bool {
+        let message_type = normalize_type_name(message_type);
+        self.registered.contains(&message_type)
+    }
+
+    /// Names (`pkg/msg/Type`) of the registered types in registration order
+    pub fn types(&self) -> impl Iterator<Item = &str> {
+        self.registered.iter().map(String::as_str)
+    }
+
+    /// Assemble the definition of `message_type` and every type it depends on
+    pub fn definition(&self, message_type: &str) -> Result<MessageDefinition> {
+        self.resolver.resolve(message_type)
+    }
+
+    /// Parse the schemas of `message_type` and every type it depends on
+    pub fn schemas(&self, message_type: &str) -> Result<MessageSchemas> {
+        MessageSchemas::parse(message_type, &self.definition(message_type)?)
+    }
+
+    /// Get the stored definition of `connection`, or the registered one if it has none
+    pub fn definition_for(&self, connection: &Connection) -> Result<MessageDefinition> {
+        self.resolver.definition_for(connection)
+    }
+
+    fn insert(&mut self, message_type: &str, text: String) {
+        let message_type = normalize_type_name(message_type);
+        self.resolver.add_msg(&message_type, text);
+        if !self.registered.contains(&message_type) {
+            self.registered.push(message_type);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ConstantValue;
+
+    const CUSTOM_IDL: &str = r#"// generated from rosidl_adapter/resource/msg.idl.em
+
+#include "std_msgs/msg/Header.idl"
+
+module my_pkg {
+  module msg {
+    typedef double double__9[9];
+    module Reading_Constants {
+      const uint8 MODE_RAW = 0;
+      const uint8 MODE_FILTERED = 1;
+      const string UNIT = "m/s";
+    };
+    @verbatim (language="comment", text=
+      "A custom reading")
+    struct Reading {
+      std_msgs::msg::Header header;
+
+      @default (value=0)
+      uint8 mode;
+
+      double__9 covariance;
+      sequence<my_pkg::msg::Sample> samples;
+      sequence<int32, 5> bounded;
+      string<16> label;
+      boolean valid;
+    };
+    struct Sample {
+      float value;
+      unsigned long long index;
+    };
+  };
+};
+"#;
+
+    #[test]
+    fn test_register_idl() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register_idl("my_pkg/msg/Reading", CUSTOM_IDL)
+            .unwrap();
+        assert!(registry.contains("my_pkg/Reading"));
+        assert!(registry.contains("my_pkg/msg/Sample"));
+        assert!(!registry.contains("std_msgs/msg/Header"));
+
+        let schemas = registry.schemas("my_pkg/msg/Reading").unwrap();
+        let types: Vec<(&str, &str)> = schemas
+            .root()
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.type_name.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("header", "std_msgs/msg/Header"),
+                ("mode", "uint8"),
+                ("covariance", "float64[9]"),
+                ("samples", "my_pkg/msg/Sample[]"),
+                ("bounded", "int32[<=5]"),
+                ("label", "string<=16"),
+                ("valid", "bool"),
+            ]
+        );
+        assert_eq!(
+            schemas.constant("my_pkg/msg/Reading", "MODE_FILTERED"),
+            Some(&ConstantValue::UInt(1))
+        );
+        assert_eq!(
+            schemas.constant("my_pkg/msg/Reading", "UNIT"),
+            Some(&ConstantValue::String("m/s".to_string()))
+        );
+        assert_eq!(
+            schemas.get("my_pkg/msg/Sample").unwrap().fields[1].type_name,
+            "uint64"
+        );
+        // Bundled dependencies are resolved too
+        assert!(schemas.get("builtin_interfaces/msg/Time").is_some());
+    }
+
+    #[test]
+    fn test_register_concatenated_msgdef() {
+        let definition = DefinitionResolver::new()
+            .resolve("geometry_msgs/msg/PoseStamped")
+            .unwrap();
+        let text = definition
+            .data
+            .replace("geometry_msgs/Pose pose", "my_pkg/Pose pose")
+            .replace("MSG: geometry_msgs/Pose", "MSG: my_pkg/Pose");
+
+        let mut registry = TypeRegistry::new();
+        registry
+            .register_msgdef("my_pkg/msg/PoseStamped", &text)
+            .unwrap();
+        let types: Vec<&str> = registry.types().collect();
+        assert!(types.contains(&"my_pkg/msg/PoseStamped"));
+        assert!(types.contains(&"my_pkg/msg/Pose"));
+
+        let schemas = registry.schemas("my_pkg/PoseStamped").unwrap();
+        assert_eq!(
+            schemas.root().field("pose").unwrap().type_name,
+            "my_pkg/msg/Pose"
+        );
+
+        assert!(registry
+            .register_msgdef("my_pkg/msg/Broken", "int8 X=not_a_number\n")
+            .is_err());
+        assert!(registry.definition("my_pkg/msg/Unknown").is_err());
+    }
+}
@@ -0,0 +1,809 @@
+//! ROS2 graph access over Zenoh, compatible with `rmw_zenoh`
+//!
+//! [`ZenohNode`] joins a ROS2 graph whose nodes run `rmw_zenoh_cpp` (ROS 2 Jazzy and
+//! later) without linking a ROS2 installation. It implements `RosMiddleware`, so a
+//! `RosBridge` can publish playback to the graph (`bridge` feature), and `RecordSource`,
+//! so a `Recorder` can record from it (`recorder` feature). Publishers and subscribers
+//! use the key expressions of `rmw_zenoh` and are announced with its liveliness tokens,
+//! so `ros2 topic list` sees them like those of any other node, and topics are
+//! discovered from the tokens of other nodes. Messages keep their CDR bytes; published
+//! ones carry the sequence number, source timestamp and GID attachment.
+//!
+//! QoS maps to Zenoh as in `rmw_zenoh`: reliable keep-all publishers block under
+//! congestion and all others drop. Transient-local durability is announced, but
+//! samples are neither cached for subscribers that join late nor requested from
+//! publishers when subscribing.
+
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use crate::bridge::RosMiddleware;
+use crate::definitions::{normalize_type_name, DefinitionResolver};
+use crate::error::{BagError, Result};
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use crate::recorder::{DiscoveredTopic, ReceivedMessage, RecordSource};
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use crate::schema::MessageSchemas;
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use crate::types::Connection;
+use crate::types::{QosDurability, QosHistory, QosLiveliness, QosProfile, QosReliability, QosTime};
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use sha2::{Digest, Sha256};
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use std::cell::Cell;
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use zenoh::handlers::FifoChannelHandler;
+use zenoh::liveliness::LivelinessToken;
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use zenoh::pubsub::Publisher;
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use zenoh::pubsub::Subscriber;
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+use zenoh::qos::CongestionControl;
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+use zenoh::sample::{Sample, SampleKind};
+use zenoh::{Config, Session, Wait};
+
+/// Environment variable selecting the ROS2 domain
+pub const DOMAIN_ID_ENV: &str = "ROS_DOMAIN_ID";
+
+/// Environment variable naming a Zenoh session config file, as read by `rmw_zenoh`
+pub const SESSION_CONFIG_ENV: &str = "ZENOH_SESSION_CONFIG_URI";
+
+/// Zenoh router `rmw_zenoh` sessions connect to by default
+pub const DEFAULT_ROUTER: &str = "tcp/localhost:7447";
+
+/// Prefix of the liveliness tokens announcing ROS2 graph entities
+const LIVELINESS_PREFIX: &str = "@ros2_lv";
+
+/// Queue depth `rmw_zenoh` leaves out of QoS keys
+const DEFAULT_DEPTH: u32 = 10;
+
+/// Source of node and entity IDs, unique within the process
+static NEXT_ENTITY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Zenoh session config used by `rmw_zenoh` nodes
+///
+/// Loads the file named by `ZENOH_SESSION_CONFIG_URI` if it is set. Otherwise the
+/// session is a peer that connects to the router on `localhost:7447`, with multicast
+/// scouting disabled, like the default config shipped with `rmw_zenoh`.
+pub fn session_config() -> Result<Config> {
+    if let Ok(path) = std::env::var(SESSION_CONFIG_ENV) {
+        return Config::from_file(path).map_err(zenoh_error);
+    }
+    let mut config = Config::default();
+    config
+        .insert_json5("mode", "\"peer\"")
+        .map_err(zenoh_error)?;
+    config
+        .insert_json5("connect/endpoints", &format!("[\"{DEFAULT_ROUTER}\"]"))
+        .map_err(zenoh_error)?;
+    config
+        .insert_json5("scouting/multicast/enabled", "false")
+        .map_err(zenoh_error)?;
+    Ok(config)
+}
+
+/// A ROS2 node on a Zenoh session
+///
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "bridge")]
+/// # {
+/// use rosbags_rs::bridge::RosBridge;
+/// use rosbags_rs::rmw_zenoh::ZenohNode;
+/// use rosbags_rs::{Player, Reader};
+///
+/// # fn main() -> rosbags_rs::Result<()> {
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// let node = ZenohNode::open("rosbags_player")?;
+/// RosBridge::new(node)
+///     .publish_clock(40.0)
+///     .play(&Player::new(&reader))?;
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+pub struct ZenohNode {
+    session: Session,
+    domain_id: u32,
+    name: String,
+    id: u64,
+    definitions: DefinitionResolver,
+    #[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+    recording: Recording,
+    _token: LivelinessToken,
+}
+
+/// Graph and subscription state of a node used as a [`RecordSource`]
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+struct Recording {
+    /// Liveliness tokens of the graph, declared on the first discovery
+    graph: Option<Subscriber<FifoChannelHandler<Sample>>>,
+    /// Topics offered by publishers of other nodes, by liveliness token
+    offers: BTreeMap<String, DiscoveredTopic>,
+    subscriptions: Vec<(Subscriber<()>, LivelinessToken)>,
+    sender: Sender<ReceivedMessage>,
+    receiver: Receiver<ReceivedMessage>,
+}
+
+/// Publisher created by [`ZenohNode`]
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+pub struct ZenohPublisher {
+    publisher: Publisher<'static>,
+    gid: [u8; 16],
+    sequence_number: Cell<i64>,
+    _token: LivelinessToken,
+}
+
+impl ZenohNode {
+    /// Open a session with [`session_config`] and join the domain in `ROS_DOMAIN_ID`
+    ///
+    /// The domain defaults to 0 when the variable is unset or not a number.
+    pub fn open(name: &str) -> Result<Self> {
+        let session = zenoh::open(session_config()?).wait().map_err(zenoh_error)?;
+        let domain_id = std::env::var(DOMAIN_ID_ENV)
+            .ok()
+            .and_then(|id| id.trim().parse().ok())
+            .unwrap_or(0);
+        Self::with_session(session, name, domain_id)
+    }
+
+    /// Create a node named `name` in `domain_id` on an open session
+    pub fn with_session(session: Session, name: &str, domain_id: u32) -> Result<Self> {
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(BagError::generic(format!("Invalid node name: {name}")));
+        }
+
+        let id = NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed);
+        let token = session
+            .liveliness()
+            .declare_token(format!(
+                "{LIVELINESS_PREFIX}/{domain_id}/{}/{id}/{id}/NN/%/%/{name}",
+                session.zid()
+            ))
+            .wait()
+            .map_err(zenoh_error)?;
+        Ok(Self {
+            session,
+            domain_id,
+            name: name.to_string(),
+            id,
+            definitions: DefinitionResolver::new(),
+            #[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+            recording: {
+                let (sender, receiver) = mpsc::channel();
+                Recording {
+                    graph: None,
+                    offers: BTreeMap::new(),
+                    subscriptions: Vec::new(),
+                    sender,
+                    receiver,
+                }
+            },
+            _token: token,
+        })
+    }
+
+    /// The underlying Zenoh session
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// ROS2 domain of the node
+    pub fn domain_id(&self) -> u32 {
+        self.domain_id
+    }
+
+    /// Name of the node
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `RIHS01` hash of the connection's type, computed from its definition if not
+    /// recorded
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    fn type_hash(&self, connection: &Connection) -> Result<String> {
+        if !connection.type_description_hash.is_empty() {
+            return Ok(connection.type_description_hash.clone());
+        }
+        let definition = self.definitions.complete_definition(connection)?;
+        MessageSchemas::parse(&connection.message_type, &definition)?.type_description_hash()
+    }
+}
+
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+impl RosMiddleware for ZenohNode {
+    type Publisher = ZenohPublisher;
+
+    fn create_publisher(
+        &mut self,
+        connection: &Connection,
+        qos: &QosProfile,
+    ) -> Result<ZenohPublisher> {
+        let type_name = dds_type_name(&connection.message_type);
+        let type_hash = self.type_hash(connection)?;
+        let topic = connection.topic.trim_start_matches('/');
+
+        let congestion_control =
+            if qos.reliability == QosReliability::Reliable && qos.history == QosHistory::KeepAll {
+                CongestionControl::Block
+            } else {
+                CongestionControl::Drop
+            };
+        let publisher = self
+            .session
+            .declare_publisher(format!(
+                "{}/{topic}/{type_name}/{type_hash}",
+                self.domain_id
+            ))
+            .congestion_control(congestion_control)
+            .wait()
+            .map_err(zenoh_error)?;
+
+        let entity_id = NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed);
+        let token_key = format!(
+            "{LIVELINESS_PREFIX}/{}/{}/{}/{entity_id}/MP/%/%/{}/%{}/{type_name}/{type_hash}/{}",
+            self.domain_id,
+            self.session.zid(),
+            self.id,
+            self.name,
+            topic.replace('/', "%"),
+            qos_key(qos)
+        );
+        let token = self
+            .session
+            .liveliness()
+            .declare_token(token_key.as_str())
+            .wait()
+            .map_err(zenoh_error)?;
+
+        let mut gid = [0u8; 16];
+        gid.copy_from_slice(&Sha256::digest(token_key.as_bytes())[..16]);
+        Ok(ZenohPublisher {
+            publisher,
+            gid,
+            sequence_number: Cell::new(0),
+            _token: token,
+        })
+    }
+
+    fn publish(&mut self, publisher: &ZenohPublisher, data: &[u8]) -> Result<()> {
+        let sequence_number = publisher.sequence_number.get() + 1;
+        publisher.sequence_number.set(sequence_number);
+        publisher
+            .publisher
+            .put(data.to_vec())
+            .attachment(attachment(
+                sequence_number,
+                now_nanos() as i64,
+                &publisher.gid,
+            ))
+            .wait()
+            .map_err(zenoh_error)
+    }
+}
+
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+impl RecordSource for ZenohNode {
+    fn discover(&mut self) -> Result<Vec<DiscoveredTopic>> {
+        let graph = match &mut self.recording.graph {
+            Some(graph) => graph,
+            graph => graph.insert(
+                self.session
+                    .liveliness()
+                    .declare_subscriber(format!("{LIVELINESS_PREFIX}/{}/**", self.domain_id))
+                    .history(true)
+                    .wait()
+                    .map_err(zenoh_error)?,
+            ),
+        };
+        let own = format!(
+            "{LIVELINESS_PREFIX}/{}/{}/{}/",
+            self.domain_id,
+            self.session.zid(),
+            self.id
+        );
+        for sample in graph.drain() {
+            let key = sample.key_expr().as_str();
+            if key.starts_with(&own) {
+                continue;
+            }
+            match sample.kind() {
+                SampleKind::Put => {
+                    if let Some(offer) = parse_publisher_token(key) {
+                        self.recording.offers.insert(key.to_string(), offer);
+                    }
+                }
+                SampleKind::Delete => {
+                    self.recording.offers.remove(key);
+                }
+            }
+        }
+
+        let mut topics: Vec<DiscoveredTopic> = Vec::new();
+        for offer in self.recording.offers.values() {
+            match topics.iter_mut().find(|topic| {
+                topic.topic == offer.topic && topic.message_type == offer.message_type
+            }) {
+                Some(topic) => topic
+                    .offered_qos_profiles
+                    .extend(offer.offered_qos_profiles.iter().cloned()),
+                None => topics.push(DiscoveredTopic {
+                    message_definition: self.definitions.resolve(&offer.message_type).ok(),
+                    ..offer.clone()
+                }),
+            }
+        }
+        Ok(topics)
+    }
+
+    fn subscribe(&mut self, topic: &DiscoveredTopic) -> Result<()> {
+        let type_name = dds_type_name(&topic.message_type);
+        let type_hash = topic
+            .type_description_hash
+            .as_deref()
+            .ok_or_else(|| BagError::generic(format!("No type hash known for {}", topic.topic)))?;
+        let key = topic.topic.trim_start_matches('/');
+
+        let name = topic.topic.clone();
+        let sender = self.recording.sender.clone();
+        let subscriber = self
+            .session
+            .declare_subscriber(format!("{}/{key}/{type_name}/{type_hash}", self.domain_id))
+            .callback(move |sample| {
+                let _ = sender.send(ReceivedMessage {
+                    topic: name.clone(),
+                    timestamp: now_nanos(),
+                    data: sample.payload().to_bytes().into_owned(),
+                });
+            })
+            .wait()
+            .map_err(zenoh_error)?;
+
+        let entity_id = NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed);
+        let token = self
+            .session
+            .liveliness()
+            .declare_token(format!(
+                "{LIVELINESS_PREFIX}/{}/{}/{}/{entity_id}/MS/%/%/{}/%{}/{type_name}/{type_hash}/{}",
+                self.domain_id,
+                self.session.zid(),
+                self.id,
+                self.name,
+                key.replace('/', "%"),
+                qos_key(&subscription_qos(&topic.offered_qos_profiles))
+            ))
+            .wait()
+            .map_err(zenoh_error)?;
+        self.recording.subscriptions.push((subscriber, token));
+        Ok(())
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<Option<ReceivedMessage>> {
+        Ok(self.recording.receiver.recv_timeout(timeout).ok())
+    }
+}
+
+/// Time since the epoch in nanoseconds
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// DDS-style type name used in `rmw_zenoh` keys, e.g. `std_msgs::msg::dds_::String_`
+fn dds_type_name(message_type: &str) -> String {
+    let message_type = normalize_type_name(message_type);
+    match message_type.rsplit_once('/') {
+        Some((namespace, name)) => format!("{}::dds_::{name}_", namespace.replace('/', "::")),
+        None => format!("{message_type}_"),
+    }
+}
+
+/// QoS as encoded in `rmw_zenoh` liveliness tokens
+///
+/// The format is `reliability:durability:history,depth:deadline:lifespan:liveliness`,
+/// with times as `sec,nsec` and liveliness as `kind,sec,nsec`. Values equal to the
+/// ROS2 default profile are left empty.
+fn qos_key(qos: &QosProfile) -> String {
+    fn elide<T: PartialEq + ToString>(value: T, default: T) -> String {
+        if value == default {
+            String::new()
+        } else {
+            value.to_string()
+        }
+    }
+    fn time(time: &QosTime) -> String {
+        format!("{},{}", elide(time.sec, 0), elide(time.nsec, 0))
+    }
+
+    let reliability = match qos.reliability {
+        QosReliability::BestEffort => "2",
+        _ => "",
+    };
+    let durability = match qos.durability {
+        QosDurability::TransientLocal => "1",
+        _ => "",
+    };
+    let history = match qos.history {
+        QosHistory::KeepAll => "2",
+        _ => "",
+    };
+    let liveliness = match qos.liveliness {
+        QosLiveliness::ManualByTopic => "3",
+        _ => "",
+    };
+    format!(
+        "{reliability}:{durability}:{history},{}:{}:{}:{liveliness},{}",
+        elide(qos.depth, DEFAULT_DEPTH),
+        time(&qos.deadline),
+        time(&qos.lifespan),
+        time(&qos.liveliness_lease_duration)
+    )
+}
+
+/// Topic offered by a publisher, from its liveliness token
+///
+/// Tokens have the form `@ros2_lv/<domain>/<zid>/<node id>/<entity id>/MP/<enclave>/
+/// <namespace>/<node>/<topic>/<type>/<type hash>/<qos>`, with `/` in names replaced
+/// by `%`. Tokens of other entities are skipped.
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+fn parse_publisher_token(key: &str) -> Option<DiscoveredTopic> {
+    let parts: Vec<&str> = key.split('/').collect();
+    let [_, _, _, _, _, "MP", _, _, _, topic, type_name, type_hash, qos] = parts.as_slice() else {
+        return None;
+    };
+    let message_type = type_name
+        .strip_suffix('_')?
+        .replacen("::dds_::", "::", 1)
+        .replace("::", "/");
+    Some(DiscoveredTopic {
+        topic: topic.replace('%', "/"),
+        message_type,
+        message_definition: None,
+        type_description_hash: Some(type_hash.to_string()),
+        offered_qos_profiles: vec![parse_qos_key(qos)?],
+    })
+}
+
+/// QoS of a liveliness token, the inverse of [`qos_key`]
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+fn parse_qos_key(key: &str) -> Option<QosProfile> {
+    fn number<T: std::str::FromStr>(value: &str, default: T) -> Option<T> {
+        if value.is_empty() {
+            Some(default)
+        } else {
+            value.parse().ok()
+        }
+    }
+    fn time(value: &str) -> Option<QosTime> {
+        let (sec, nsec) = value.split_once(',')?;
+        let (sec, nsec): (i64, u64) = (number(sec, 0)?, number(nsec, 0)?);
+        // Durations beyond the range of the metadata are infinite, stored like rosbag2 does
+        Some(match (i32::try_from(sec), u32::try_from(nsec)) {
+            (Ok(sec), Ok(nsec)) => QosTime { sec, nsec },
+            _ => QosTime {
+                sec: i32::MAX,
+                nsec: u32::MAX,
+            },
+        })
+    }
+
+    let parts: Vec<&str> = key.split(':').collect();
+    let [reliability, durability, history, deadline, lifespan, liveliness] = parts.as_slice()
+    else {
+        return None;
+    };
+    let (history, depth) = history.split_once(',')?;
+    let (liveliness, lease) = liveliness.split_once(',')?;
+    Some(QosProfile {
+        history: match number(history, 1u8)? {
+            0 => QosHistory::SystemDefault,
+            1 => QosHistory::KeepLast,
+            2 => QosHistory::KeepAll,
+            _ => QosHistory::Unknown,
+        },
+        depth: number(depth, DEFAULT_DEPTH)?,
+        reliability: match number(reliability, 1u8)? {
+            0 => QosReliability::SystemDefault,
+            1 => QosReliability::Reliable,
+            2 => QosReliability::BestEffort,
+            _ => QosReliability::Unknown,
+        },
+        durability: match number(durability, 2u8)? {
+            0 => QosDurability::SystemDefault,
+            1 => QosDurability::TransientLocal,
+            2 => QosDurability::Volatile,
+            _ => QosDurability::Unknown,
+        },
+        deadline: time(deadline)?,
+        lifespan: time(lifespan)?,
+        liveliness: match number(liveliness, 1u8)? {
+            0 => QosLiveliness::SystemDefault,
+            1 => QosLiveliness::Automatic,
+            3 => QosLiveliness::ManualByTopic,
+            _ => QosLiveliness::Unknown,
+        },
+        liveliness_lease_duration: time(lease)?,
+        avoid_ros_namespace_conventions: false,
+    })
+}
+
+/// QoS of a recording subscription, matching every offer like `ros2 bag record`
+///
+/// Reliable and transient-local only if all publishers offer it.
+#[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+fn subscription_qos(offers: &[QosProfile]) -> QosProfile {
+    let reliable = offers
+        .iter()
+        .all(|offer| offer.reliability == QosReliability::Reliable);
+    let transient_local = !offers.is_empty()
+        && offers
+            .iter()
+            .all(|offer| offer.durability == QosDurability::TransientLocal);
+    QosProfile {
+        history: QosHistory::KeepLast,
+        depth: DEFAULT_DEPTH,
+        reliability: if reliable {
+            QosReliability::Reliable
+        } else {
+            QosReliability::BestEffort
+        },
+        durability: if transient_local {
+            QosDurability::TransientLocal
+        } else {
+            QosDurability::Volatile
+        },
+        liveliness: QosLiveliness::Automatic,
+        ..QosProfile::default()
+    }
+}
+
+/// Attachment `rmw_zenoh` sends with every sample
+///
+/// Serialized like `zenoh-ext` does: strings with a LEB128 length prefix, integers in
+/// little endian and the GID as a fixed-size array.
+#[cfg(all(feature = "bridge", not(feature = "write-only")))]
+fn attachment(sequence_number: i64, source_timestamp: i64, gid: &[u8; 16]) -> Vec<u8> {
+    fn push_str(bytes: &mut Vec<u8>, value: &str) {
+        let mut len = value.len();
+        while len >= 0x80 {
+            bytes.push((len as u8 & 0x7f) | 0x80);
+            len >>= 7;
+        }
+        bytes.push(len as u8);
+        bytes.extend_from_slice(value.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(64);
+    push_str(&mut bytes, "sequence_number");
+    bytes.extend_from_slice(&sequence_number.to_le_bytes());
+    push_str(&mut bytes, "source_timestamp");
+    bytes.extend_from_slice(&source_timestamp.to_le_bytes());
+    push_str(&mut bytes, "source_gid");
+    bytes.extend_from_slice(gid);
+    bytes
+}
+
+fn zenoh_error(error: zenoh::Error) -> BagError {
+    BagError::generic(format!("Zenoh: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    use crate::bridge::{RosBridge, CLOCK_TOPIC};
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    use crate::player::Player;
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    use crate::reader::Reader;
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    use std::time::Duration;
+
+    /// Peer session that neither scouts nor connects, so tests stay in-process
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    fn local_session() -> Session {
+        let mut config = Config::default();
+        config
+            .insert_json5("scouting/multicast/enabled", "false")
+            .unwrap();
+        config
+            .insert_json5("listen/endpoints", "[\"tcp/127.0.0.1:0\"]")
+            .unwrap();
+        zenoh::open(config).wait().unwrap()
+    }
+
+    #[test]
+    fn test_type_names_and_qos_keys() {
+        assert_eq!(
+            dds_type_name("std_msgs/msg/String"),
+            "std_msgs::msg::dds_::String_"
+        );
+        assert_eq!(
+            dds_type_name("geometry_msgs/Pose"),
+            "geometry_msgs::msg::dds_::Pose_"
+        );
+
+        let qos = QosProfile {
+            history: QosHistory::KeepLast,
+            depth: 7,
+            reliability: QosReliability::Reliable,
+            durability: QosDurability::Volatile,
+            ..QosProfile::default()
+        };
+        assert_eq!(qos_key(&qos), "::,7:,:,:,,");
+        let qos = QosProfile {
+            reliability: QosReliability::BestEffort,
+            durability: QosDurability::TransientLocal,
+            depth: 10,
+            deadline: QosTime { sec: 1, nsec: 5 },
+            ..qos
+        };
+        assert_eq!(qos_key(&qos), "2:1:,:1,5:,:,,");
+    }
+
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    #[test]
+    fn test_attachment_layout() {
+        let bytes = attachment(3, -1, &[7; 16]);
+        assert_eq!(bytes[0] as usize, "sequence_number".len());
+        assert_eq!(&bytes[1..16], b"sequence_number");
+        assert_eq!(&bytes[16..24], &3i64.to_le_bytes());
+        assert_eq!(&bytes[25..41], b"source_timestamp");
+        assert_eq!(&bytes[41..49], &(-1i64).to_le_bytes());
+        assert_eq!(&bytes[50..60], b"source_gid");
+        assert_eq!(&bytes[60..], &[7; 16]);
+    }
+
+    #[cfg(all(feature = "bridge", not(feature = "write-only")))]
+    #[test]
+    fn test_publishes_to_rmw_zenoh_keys() {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        let connection = reader.connections()[0].clone();
+        let first = reader
+            .messages_filtered(Some(std::slice::from_ref(&connection)), None, None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let session = local_session();
+        let data = session.declare_subscriber("0/**").wait().unwrap();
+        let liveliness = session
+            .liveliness()
+            .declare_subscriber(format!("{LIVELINESS_PREFIX}/0/**"))
+            .wait()
+            .unwrap();
+        let node = ZenohNode::with_session(session.clone(), "rosbags_test", 0).unwrap();
+        let mut bridge = RosBridge::new(node).publish_clock(0.0);
+        let player = Player::new(&reader)
+            .rate(1e6)
+            .topics(&[connection.topic.as_str()]);
+        assert!(bridge.play(&player).unwrap() > 0);
+
+        let topic = connection.topic.trim_start_matches('/');
+        let type_name = dds_type_name(&connection.message_type);
+        let mut clock = None;
+        let mut message = None;
+        while let Ok(Some(sample)) = data.recv_timeout(Duration::from_secs(1)) {
+            let key = sample.key_expr().as_str().to_string();
+            if key.starts_with(&format!(
+                "0{CLOCK_TOPIC}/rosgraph_msgs::msg::dds_::Clock_/RIHS01_"
+            )) {
+                clock.get_or_insert(sample);
+            } else if key.starts_with(&format!("0/{topic}/{type_name}/RIHS01_")) {
+                message.get_or_insert(sample);
+            }
+            if clock.is_some() && message.is_some() {
+                break;
+            }
+        }
+        assert!(clock.is_some());
+        let message = message.expect("no message published on the topic key");
+        assert_eq!(message.payload().to_bytes().as_ref(), first.data.as_slice());
+        let attachment = message.attachment().unwrap().to_bytes();
+        assert_eq!(&attachment[16..24], &1i64.to_le_bytes());
+
+        // Publisher tokens are undeclared again when playback ends
+        let tokens: Vec<String> = liveliness
+            .drain()
+            .map(|sample| sample.key_expr().as_str().to_string())
+            .collect();
+        assert!(tokens
+            .iter()
+            .any(|token| token.ends_with("/NN/%/%/rosbags_test")));
+        assert!(tokens.iter().any(|token| token.contains(&format!(
+            "/MP/%/%/rosbags_test/%{}/{type_name}/",
+            topic.replace('/', "%")
+        ))));
+    }
+
+    #[cfg(all(feature = "recorder", any(feature = "write-only", feature = "default")))]
+    #[test]
+    fn test_parses_publisher_tokens() {
+        let token = "@ros2_lv/0/1f2e/3/4/MP/%/%/talker/%ns%chatter/std_msgs::msg::dds_::String_/\
+                     RIHS01_df66/2:1:,5:,:,:,,";
+        let topic = parse_publisher_token(token).unwrap();
+        assert_eq!(topic.topic, "/ns/chatter");
+        assert_eq!(topic.message_type, "std_msgs/msg/String");
+        assert_eq!(topic.type_description_hash.as_deref(), Some("RIHS01_df66"));
+        let qos = &topic.offered_qos_profiles[0];
+        assert_eq!(qos.reliability, QosReliability::BestEffort);
+        assert_eq!(qos.durability, QosDurability::TransientLocal);
+        assert_eq!(qos.history, QosHistory::KeepLast);
+        assert_eq!(qos.depth, 5);
+        assert_eq!(qos_key(qos), "2:1:,5:,:,:,,");
+
+        assert!(parse_publisher_token(&token.replace("/MP/", "/MS/")).is_none());
+        let infinite = parse_qos_key("::,:9223372036,854775807:,:,,").unwrap();
+        assert_eq!(infinite.deadline.sec, i32::MAX);
+        assert_eq!(infinite.deadline.nsec, u32::MAX);
+    }
+
+    #[cfg(all(feature = "bridge", feature = "recorder", feature = "default"))]
+    #[test]
+    fn test_records_from_zenoh_publishers() {
+        let session = local_session();
+        let mut talker = ZenohNode::with_session(session.clone(), "talker", 0).unwrap();
+        let mut recorder = ZenohNode::with_session(session, "recorder", 0).unwrap();
+        let discover = |recorder: &mut ZenohNode, count: usize| {
+            for _ in 0..100 {
+                let topics = recorder.discover().unwrap();
+                if topics.len() == count {
+                    return topics;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            panic!("expected {count} discovered topics");
+        };
+
+        let connection = Connection::new("/chatter", "std_msgs/msg/String").unwrap();
+        let qos = QosProfile {
+            history: QosHistory::KeepLast,
+            depth: 5,
+            reliability: QosReliability::BestEffort,
+            durability: QosDurability::Volatile,
+            liveliness: QosLiveliness::Automatic,
+            ..QosProfile::default()
+        };
+        let publisher = talker.create_publisher(&connection, &qos).unwrap();
+
+        let topics = discover(&mut recorder, 1);
+        let topic = &topics[0];
+        assert_eq!(topic.topic, "/chatter");
+        assert_eq!(topic.message_type, "std_msgs/msg/String");
+        assert_eq!(
+            topic.type_description_hash,
+            Some(talker.type_hash(&connection).unwrap())
+        );
+        assert!(topic.message_definition.is_some());
+        assert_eq!(topic.offered_qos_profiles, vec![qos]);
+
+        recorder.subscribe(topic).unwrap();
+        let data = vec![0, 1, 0, 0, 3, 0, 0, 0, b'h', b'i', 0];
+        talker.publish(&publisher, &data).unwrap();
+        let message = recorder
+            .receive(Duration::from_secs(1))
+            .unwrap()
+            .expect("no message received");
+        assert_eq!(message.topic, "/chatter");
+        assert_eq!(message.data, data);
+        assert!(message.timestamp > 0);
+
+        // The topic disappears with its last publisher
+        drop(publisher);
+        discover(&mut recorder, 0);
+    }
+}
@@ -0,0 +1,412 @@
+//! Sequential reading with the API of `rosbag2_py`
+//!
+//! [`SequentialReader`] mirrors `rosbag2_py.SequentialReader` (and the C++
+//! `rosbag2_cpp::readers::SequentialReader`): it is opened with [`StorageOptions`]
+//! and [`ConverterOptions`], narrowed with a [`StorageFilter`], and stepped through
+//! with `has_next`/`read_next`, which return `(topic, data, t)` tuples. Loops written
+//! against rosbag2 port line by line:
+//!
+//! ```no_run
+//! use rosbags_rs::rosbag2::{ConverterOptions, SequentialReader, StorageOptions};
+//!
+//! # fn main() -> rosbags_rs::Result<()> {
+//! let mut reader = SequentialReader::new();
+//! reader.open(
+//!     &StorageOptions::new("path/to/bag", "sqlite3"),
+//!     &ConverterOptions::new("cdr", "cdr"),
+//! )?;
+//! while reader.has_next() {
+//!     let (topic, data, t) = reader.read_next()?;
+//!     println!("{t} {topic}: {} bytes", data.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Messages come in the order of [`Reader::messages`]. Payloads are returned in
+//! their stored serialization format; no converter plugins exist, so the converter
+//! options must not ask for another format.
+
+use crate::error::{BagError, Result};
+use crate::metadata::{BagMetadata, QosProfilesField, TopicMetadata};
+use crate::reader::Reader;
+use crate::types::{Connection, RawMessage, StoragePlugin, TopicPattern};
+use std::path::PathBuf;
+
+/// Where the bag is and which storage plugin reads it, like `rosbag2_py.StorageOptions`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageOptions {
+    /// Path of the bag directory
+    pub uri: PathBuf,
+    /// Storage plugin (`sqlite3`, `mcap` or `memory`), detected from the bag when empty
+    pub storage_id: String,
+}
+
+impl StorageOptions {
+    /// Options for the bag at `uri`, read with the `storage_id` plugin
+    pub fn new(uri: impl Into<PathBuf>, storage_id: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            storage_id: storage_id.into(),
+        }
+    }
+
+    /// The storage plugin named by `storage_id`, `None` to detect it
+    fn storage_plugin(&self) -> Result<Option<StoragePlugin>> {
+        match self.storage_id.as_str() {
+            "" => Ok(None),
+            "sqlite3" => Ok(Some(StoragePlugin::Sqlite3)),
+            "mcap" => Ok(Some(StoragePlugin::Mcap)),
+            "memory" => Ok(Some(StoragePlugin::Memory)),
+            other => Err(BagError::UnsupportedStorageFormat {
+                format: format!("Unknown storage id '{other}', use 'sqlite3', 'mcap' or 'memory'"),
+            }),
+        }
+    }
+}
+
+/// Serialization formats of the stored and the returned messages, like
+/// `rosbag2_py.ConverterOptions`
+///
+/// Empty formats stand for the stored format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConverterOptions {
+    /// Serialization format of the stored messages
+    pub input_serialization_format: String,
+    /// Serialization format messages are returned in
+    pub output_serialization_format: String,
+}
+
+impl ConverterOptions {
+    /// Options converting from `input` to `output` serialization format
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            input_serialization_format: input.into(),
+            output_serialization_format: output.into(),
+        }
+    }
+
+    /// Fail unless messages of `connection` are returned unconverted
+    fn check(&self, connection: &Connection) -> Result<()> {
+        let stored = connection.serialization_format.as_str();
+        for format in [
+            &self.input_serialization_format,
+            &self.output_serialization_format,
+        ] {
+            if !format.is_empty() && format != stored {
+                return Err(BagError::invalid_connection(
+                    &connection.topic,
+                    format!(
+                        "Converting serialization format '{stored}' to '{format}' is not supported"
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Topics read by a [`SequentialReader`], like `rosbag2_py.StorageFilter`
+///
+/// A topic is read if it is listed in `topics` or matches `topics_regex`, or if
+/// both are empty, and it is neither listed in `exclude_topics` nor matches
+/// `exclude_regex`. Regular expressions match whole topic names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageFilter {
+    /// Topics to read
+    pub topics: Vec<String>,
+    /// Regular expression of topics to read
+    pub topics_regex: String,
+    /// Topics not to read
+    pub exclude_topics: Vec<String>,
+    /// Regular expression of topics not to read
+    pub exclude_regex: String,
+}
+
+impl StorageFilter {
+    /// Filter reading only `topics`
+    pub fn topics<I, S>(topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            topics: topics.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The connections of `connections` passing the filter
+    fn select(&self, connections: &[Connection]) -> Result<Vec<Connection>> {
+        let regex = |pattern: &str| {
+            (!pattern.is_empty())
+                .then(|| TopicPattern::regex(pattern))
+                .transpose()
+        };
+        let include = regex(&self.topics_regex)?;
+        let exclude = regex(&self.exclude_regex)?;
+        let included = |topic: &str| {
+            (self.topics.is_empty() && include.is_none())
+                || self.topics.iter().any(|t| t == topic)
+                || include.as_ref().is_some_and(|p| p.matches(topic))
+        };
+        let excluded = |topic: &str| {
+            self.exclude_topics.iter().any(|t| t == topic)
+                || exclude.as_ref().is_some_and(|p| p.matches(topic))
+        };
+        Ok(connections
+            .iter()
+            .filter(|c| included(&c.topic) && !excluded(&c.topic))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Reads a bag message by message, like `rosbag2_py.SequentialReader`
+#[derive(Default)]
+pub struct SequentialReader {
+    reader: Option<Reader>,
+    /// Connections read, all if `None`
+    selected: Option<Vec<Connection>>,
+    /// Timestamp reading starts at after [`SequentialReader::seek`]
+    start: Option<u64>,
+    /// Remaining messages, read when first needed
+    messages: Option<std::vec::IntoIter<Result<RawMessage>>>,
+}
+
+impl std::fmt::Debug for SequentialReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SequentialReader")
+            .field("reader", &self.reader.as_ref().map(|_| "<reader>"))
+            .field("selected", &self.selected)
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+impl SequentialReader {
+    /// Create a reader; call [`SequentialReader::open`] before reading
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the bag of `storage_options`
+    ///
+    /// Fails if the bag cannot be opened or `converter_options` ask for a
+    /// serialization format other than the stored one.
+    pub fn open(
+        &mut self,
+        storage_options: &StorageOptions,
+        converter_options: &ConverterOptions,
+    ) -> Result<()> {
+        let mut builder = Reader::builder(&storage_options.uri);
+        if let Some(storage) = storage_options.storage_plugin()? {
+            builder = builder.storage(storage);
+        }
+        let reader = builder.open()?;
+        for connection in reader.connections() {
+            converter_options.check(connection)?;
+        }
+        *self = Self {
+            reader: Some(reader),
+            ..Self::default()
+        };
+        Ok(())
+    }
+
+    /// Close the bag
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The open reader
+    fn reader(&self) -> Result<&Reader> {
+        self.reader.as_ref().ok_or(BagError::BagNotOpen)
+    }
+
+    /// Whether another message can be read
+    ///
+    /// An error reading the bag also counts as a next message, so the following
+    /// [`SequentialReader::read_next`] returns it.
+    pub fn has_next(&mut self) -> bool {
+        if self.messages.is_none() {
+            let messages: Vec<Result<RawMessage>> = match self.reader() {
+                Ok(_) if self.selected.as_ref().is_some_and(Vec::is_empty) => Vec::new(),
+                Ok(reader) => {
+                    match reader.raw_messages_filtered(self.selected.as_deref(), self.start, None) {
+                        Ok(messages) => messages.collect(),
+                        Err(e) => vec![Err(e)],
+                    }
+                }
+                Err(_) => Vec::new(),
+            };
+            self.messages = Some(messages.into_iter());
+        }
+        self.messages
+            .as_ref()
+            .is_some_and(|messages| messages.len() > 0)
+    }
+
+    /// Read the next message as `(topic, serialized data, timestamp in nanoseconds)`
+    pub fn read_next(&mut self) -> Result<(String, Vec<u8>, i64)> {
+        self.reader()?;
+        if !self.has_next() {
+            return Err(BagError::generic("No more messages to read"));
+        }
+        let message = self.messages.as_mut().and_then(Iterator::next).unwrap()?;
+        let timestamp = i64::try_from(message.timestamp).unwrap_or(i64::MAX);
+        Ok((message.connection.topic, message.raw_data, timestamp))
+    }
+
+    /// Only read the topics passing `filter`, from the current position
+    pub fn set_filter(&mut self, filter: &StorageFilter) -> Result<()> {
+        let selected = filter.select(self.reader()?.connections())?;
+        self.restart_at(Some(selected), self.position());
+        Ok(())
+    }
+
+    /// Read all topics again, from the current position
+    pub fn reset_filter(&mut self) {
+        self.restart_at(None, self.position());
+    }
+
+    /// Continue with the first message at or after `timestamp` (nanoseconds)
+    pub fn seek(&mut self, timestamp: i64) -> Result<()> {
+        self.reader()?;
+        let start = u64::try_from(timestamp).unwrap_or(0);
+        self.restart_at(self.selected.clone(), Some(start));
+        Ok(())
+    }
+
+    /// Timestamp of the next message, `None` before reading started
+    fn position(&self) -> Option<u64> {
+        let next = self.messages.as_ref()?.as_slice().first();
+        match next {
+            Some(Ok(message)) => Some(message.timestamp),
+            Some(Err(_)) => self.start,
+            // Past the last message nothing is left to read; storages take i64 timestamps
+            None => Some(i64::MAX as u64),
+        }
+    }
+
+    fn restart_at(&mut self, selected: Option<Vec<Connection>>, start: Option<u64>) {
+        self.selected = selected;
+        self.start = start;
+        self.messages = None;
+    }
+
+    /// Topics of the bag with their type, serialization format and QoS offers
+    pub fn get_all_topics_and_types(&self) -> Result<Vec<TopicMetadata>> {
+        let mut topics: Vec<TopicMetadata> = Vec::new();
+        for connection in self.reader()?.connections() {
+            let known = topics.iter().any(|topic| {
+                topic.name == connection.topic && topic.message_type == connection.message_type
+            });
+            if !known {
+                topics.push(TopicMetadata {
+                    name: connection.topic.clone(),
+                    message_type: connection.message_type.clone(),
+                    serialization_format: connection.serialization_format.clone(),
+                    offered_qos_profiles: QosProfilesField::List(
+                        connection.offered_qos_profiles.clone(),
+                    ),
+                    type_description_hash: connection.type_description_hash.clone(),
+                });
+            }
+        }
+        Ok(topics)
+    }
+
+    /// Metadata of the bag, `None` for bags opened without metadata
+    pub fn get_metadata(&self) -> Result<Option<&BagMetadata>> {
+        Ok(self.reader()?.metadata())
+    }
+
+    /// The underlying [`Reader`], for everything beyond the rosbag2 API
+    pub fn reader_ref(&self) -> Option<&Reader> {
+        self.reader.as_ref()
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_reads_like_rosbag2_py() {
+        let mut reader = SequentialReader::new();
+        assert!(!reader.has_next());
+        assert!(reader.read_next().is_err());
+        reader
+            .open(
+                &StorageOptions::new("tests/test_bags/test_bag_sqlite3", "sqlite3"),
+                &ConverterOptions::new("cdr", "cdr"),
+            )
+            .unwrap();
+        let topics = reader.get_all_topics_and_types().unwrap();
+        assert_eq!(
+            topics.len(),
+            reader.reader_ref().unwrap().connections().len()
+        );
+
+        let topic = topics[0].name.clone();
+        let mut count = 0;
+        let mut on_topic = 0;
+        let mut last = 0;
+        while reader.has_next() {
+            let (name, data, t) = reader.read_next().unwrap();
+            assert!(!data.is_empty());
+            assert!(t >= last);
+            last = t;
+            count += 1;
+            on_topic += usize::from(name == topic);
+        }
+        assert_eq!(count, reader.reader_ref().unwrap().message_count());
+
+        // Filtering and seeking restart from the given position
+        reader.seek(0).unwrap();
+        reader
+            .set_filter(&StorageFilter::topics([topic.as_str()]))
+            .unwrap();
+        let (first, _, first_t) = reader.read_next().unwrap();
+        assert_eq!(first, topic);
+        let mut filtered = 1;
+        while reader.has_next() {
+            assert_eq!(reader.read_next().unwrap().0, topic);
+            filtered += 1;
+        }
+        assert_eq!(filtered, on_topic);
+
+        reader.reset_filter();
+        assert!(!reader.has_next());
+        reader.seek(first_t + 1).unwrap();
+        assert!(reader.read_next().unwrap().2 > first_t);
+
+        let exclude = StorageFilter {
+            exclude_regex: "/test/.*".to_string(),
+            ..StorageFilter::default()
+        };
+        reader.seek(0).unwrap();
+        reader.set_filter(&exclude).unwrap();
+        assert!(!reader.has_next());
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_rejects_conversions_and_unknown_storage() {
+        let mut reader = SequentialReader::new();
+        let storage = StorageOptions::new("tests/test_bags/test_bag_sqlite3", "");
+        assert!(reader
+            .open(&storage, &ConverterOptions::new("cdr", "json"))
+            .is_err());
+        assert!(reader
+            .open(
+                &StorageOptions::new("tests/test_bags/test_bag_sqlite3", "rosbag_v1"),
+                &ConverterOptions::default(),
+            )
+            .is_err());
+        reader.open(&storage, &ConverterOptions::default()).unwrap();
+        assert!(reader.get_metadata().unwrap().is_some());
+    }
+}
@@ -0,0 +1,1167 @@
+//! Runtime message schemas parsed from stored message definitions
+//!
+//! Bags record the full `.msg` definition of every topic type, including all nested
+//! types. This module parses those definitions into [`MessageSchema`] values so that
+//! field layouts and constants can be inspected without compiled message types.
+
+use crate::cdr::MAX_NESTING_DEPTH;
+use crate::error::{BagError, Result};
+use crate::types::{MessageDefinition, MessageDefinitionFormat};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+mod idl;
+
+/// Separator line between the root definition and its dependencies
+pub(crate) const DEFINITION_SEPARATOR: &str =
+    "================================================================================";
+
+/// Whether `line` separates two sections of a concatenated definition
+///
+/// rosbag2 writes [`DEFINITION_SEPARATOR`], but other tools use any run of `=`.
+pub(crate) fn is_separator_line(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3 && line.bytes().all(|byte| byte == b'=')
+}
+
+/// Split a concatenated definition into its sections, dropping the separator lines
+pub(crate) fn definition_sections(text: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if is_separator_line(line) {
+            sections.push(&text[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    sections.push(&text[start..]);
+    sections
+}
+
+/// Primitive type names defined by the ROS2 interface specification
+const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "byte", "char", "float32", "float64", "int8", "uint8", "int16", "uint16", "int32",
+    "uint32", "int64", "uint64", "string", "wstring",
+];
+
+/// Prefix of type description hashes computed with version 1 of the ROS 2 algorithm
+pub const TYPE_HASH_PREFIX: &str = "RIHS01_";
+
+/// Interface kinds besides `msg` whose types can appear in stored definitions
+const INTERFACE_KINDS: &[&str] = &["srv", "action"];
+
+/// Value of a constant declared in a message definition
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    /// Boolean constant
+    Bool(bool),
+    /// Signed integer constant (`int8` .. `int64`)
+    Int(i64),
+    /// Unsigned integer constant (`byte`, `char`, `uint8` .. `uint64`)
+    UInt(u64),
+    /// Floating point constant (`float32`, `float64`)
+    Float(f64),
+    /// String constant (`string`, `wstring`)
+    String(String),
+}
+
+impl ConstantValue {
+    /// Get the value as a signed integer, if it is an integer that fits
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ConstantValue::Int(value) => Some(*value),
+            ConstantValue::UInt(value) => i64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the value as an unsigned integer, if it is a non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            ConstantValue::UInt(value) => Some(*value),
+            ConstantValue::Int(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a float, converting integers
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ConstantValue::Float(value) => Some(*value),
+            ConstantValue::Int(value) => Some(*value as f64),
+            ConstantValue::UInt(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ConstantValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a string slice
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConstantValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A constant declared in a message definition (e.g. `int8 STATUS_FIX=0`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantDef {
+    /// Constant name
+    pub name: String,
+    /// Primitive type of the constant
+    pub type_name: String,
+    /// Parsed constant value
+    pub value: ConstantValue,
+}
+
+/// Default value of a field declared in a message definition (e.g. `int32 x 5`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultValue {
+    /// Default of a single primitive or string field
+    Value(ConstantValue),
+    /// Default of an array field, one value per element
+    Array(Vec<ConstantValue>),
+}
+
+/// A field declared in a message definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDef {
+    /// Field name
+    pub name: String,
+    /// Field type, including any array suffix (e.g. `float64[9]`, `sensor_msgs/msg/PointField[]`)
+    pub type_name: String,
+    /// Default value, if the definition declares one
+    pub default: Option<DefaultValue>,
+}
+
+/// Array specification of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArraySpec {
+    /// Fixed-size array (`T[N]`), serialized without a length prefix
+    Fixed(usize),
+    /// Bounded sequence (`T[<=N]`)
+    Bounded(usize),
+    /// Unbounded sequence (`T[]`)
+    Unbounded,
+}
+
+impl ArraySpec {
+    /// Whether the array is serialized with a length prefix
+    pub fn is_sequence(&self) -> bool {
+        !matches!(self, ArraySpec::Fixed(_))
+    }
+}
+
+impl FieldDef {
+    /// Element type without array suffix or string bound (e.g. `float64`, `string`)
+    pub fn base_type(&self) -> &str {
+        let base = self.type_name.split('[').next().unwrap_or(&self.type_name);
+        base.split("<=").next().unwrap_or(base)
+    }
+
+    /// Maximum length of a bounded string (`string<=N`), or `None` if unbounded
+    pub fn string_bound(&self) -> Option<usize> {
+        let element = self.type_name.split('[').next().unwrap_or(&self.type_name);
+        element.split_once("<=")?.1.parse().ok()
+    }
+
+    /// Array specification, or `None` for a single value
+    pub fn array(&self) -> Option<ArraySpec> {
+        let (_, suffix) = self.type_name.split_once('[')?;
+        let inner = suffix.strip_suffix(']')?;
+        Some(if inner.is_empty() {
+            ArraySpec::Unbounded
+        } else if let Some(bound) = inner.strip_prefix("<=") {
+            ArraySpec::Bounded(bound.parse().ok()?)
+        } else {
+            ArraySpec::Fixed(inner.parse().ok()?)
+        })
+    }
+}
+
+/// A field with the fields of its nested message type, as listed by [`MessageSchemas::field_tree`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNode {
+    /// The field as declared in its message type
+    pub field: FieldDef,
+    /// Fields of the field's message type (of its elements, for arrays); empty for
+    /// primitives and strings
+    pub children: Vec<FieldNode>,
+}
+
+impl FieldNode {
+    /// Field name
+    pub fn name(&self) -> &str {
+        &self.field.name
+    }
+
+    /// Whether the field is a primitive or string rather than a nested message
+    pub fn is_leaf(&self) -> bool {
+        is_primitive_type(self.field.base_type())
+    }
+}
+
+/// Schema of a single message type
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MessageSchema {
+    /// Fully qualified type name (e.g. `sensor_msgs/msg/NavSatStatus`)
+    pub name: String,
+    /// Fields in declaration order
+    pub fields: Vec<FieldDef>,
+    /// Constants in declaration order
+    pub constants: Vec<ConstantDef>,
+}
+
+impl MessageSchema {
+    /// Look up a constant by name
+    pub fn constant(&self, name: &str) -> Option<&ConstantValue> {
+        self.constants
+            .iter()
+            .find(|constant| constant.name == name)
+            .map(|constant| &constant.value)
+    }
+
+    /// Look up a field by name
+    pub fn field(&self, name: &str) -> Option<&FieldDef> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Render the schema as `.msg` text: constants first, then fields
+    ///
+    /// Nested types are written as `pkg/Type`, the spelling rosbag2 stores.
+    pub fn to_msg(&self) -> String {
+        let mut text = String::new();
+        for constant in &self.constants {
+            let value = match &constant.value {
+                ConstantValue::String(value) => value.clone(),
+                value => msg_literal(value),
+            };
+            text.push_str(&format!(
+                "{} {}={value}\n",
+                constant.type_name, constant.name
+            ));
+        }
+        for field in &self.fields {
+            let type_name = field.type_name.replacen("/msg/", "/", 1);
+            match &field.default {
+                Some(DefaultValue::Value(value)) => text.push_str(&format!(
+                    "{type_name} {} {}\n",
+                    field.name,
+                    msg_literal(value)
+                )),
+                Some(DefaultValue::Array(values)) => {
+                    let values: Vec<String> = values.iter().map(msg_literal).collect();
+                    text.push_str(&format!(
+                        "{type_name} {} [{}]\n",
+                        field.name,
+                        values.join(", ")
+                    ));
+                }
+                None => text.push_str(&format!("{type_name} {}\n", field.name)),
+            }
+        }
+        text
+    }
+
+    /// Find the names of all constants with the given value
+    ///
+    /// Useful for turning a decoded enumeration value back into a readable name,
+    /// e.g. `0` on a `NavSatStatus` resolves to `STATUS_FIX`.
+    pub fn constant_names_for(&self, value: &ConstantValue) -> Vec<&str> {
+        self.constants
+            .iter()
+            .filter(|constant| constant_eq(&constant.value, value))
+            .map(|constant| constant.name.as_str())
+            .collect()
+    }
+}
+
+/// Schemas for a message type and all of the types it depends on
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageSchemas {
+    root: String,
+    schemas: HashMap<String, MessageSchema>,
+}
+
+impl MessageSchemas {
+    /// Parse a stored message definition for the given root type
+    pub fn parse(message_type: &str, definition: &MessageDefinition) -> Result<Self> {
+        match definition.format {
+            MessageDefinitionFormat::Msg => Self::parse_msg(message_type, &definition.data),
+            MessageDefinitionFormat::Idl => Self::parse_idl(message_type, &definition.data),
+            MessageDefinitionFormat::None => Err(BagError::schema_validation(format!(
+                "No message definition available for {message_type}"
+            ))),
+        }
+    }
+
+    /// Parse a concatenated `.msg` definition as stored in rosbag2 and MCAP files
+    pub fn parse_msg(message_type: &str, text: &str) -> Result<Self> {
+        let root = normalize_type_name(message_type, "");
+        let mut schemas = HashMap::new();
+
+        for (index, section) in definition_sections(text).into_iter().enumerate() {
+            let (name, body) = if index == 0 {
+                (root.clone(), section)
+            } else {
+                let section = section.trim_start_matches(['\r', '\n']);
+                let (first_line, body) = section.split_once('\n').unwrap_or((section, ""));
+                let name = first_line
+                    .trim()
+                    .strip_prefix("MSG:")
+                    .ok_or_else(|| {
+                        BagError::schema_validation(format!(
+                            "Expected 'MSG:' line in definition of {message_type}, found '{}'",
+                            first_line.trim()
+                        ))
+                    })?
+                    .trim();
+                (normalize_type_name(name, ""), body)
+            };
+
+            let schema = parse_msg_body(&name, body)?;
+            schemas.insert(name, schema);
+        }
+        resolve_interface_kinds(&mut schemas);
+
+        Ok(Self { root, schemas })
+    }
+
+    /// Parse a concatenated IDL definition as stored by rosbag2 for `ros2idl` types
+    ///
+    /// IDL types are converted to `.msg` notation: `sequence<double>` becomes
+    /// `float64[]`, `string<10>` becomes `string<=10` and `pkg::msg::Type` becomes
+    /// `pkg/msg/Type`. Constants are taken from the `<Type>_Constants` modules.
+    pub fn parse_idl(message_type: &str, text: &str) -> Result<Self> {
+        let root = normalize_type_name(message_type, "");
+        let schemas = idl::parse(text)?;
+        if !schemas.contains_key(&root) {
+            return Err(BagError::schema_validation(format!(
+                "IDL definition of {message_type} does not declare struct {root}"
+            )));
+        }
+        Ok(Self { root, schemas })
+    }
+
+    /// Get the schema of the root message type
+    pub fn root(&self) -> &MessageSchema {
+        &self.schemas[&self.root]
+    }
+
+    /// Get the schema of a message type by name
+    ///
+    /// Both `pkg/msg/Type` and `pkg/Type` spellings are accepted, and types of
+    /// services and actions (e.g. `pkg/srv/Type_Request`) are also found by those.
+    pub fn get(&self, message_type: &str) -> Option<&MessageSchema> {
+        self.schemas
+            .get(message_type)
+            .or_else(|| find_interface(&self.schemas, &normalize_type_name(message_type, "")))
+    }
+
+    /// Look up a constant on any message type in the set
+    pub fn constant(&self, message_type: &str, name: &str) -> Option<&ConstantValue> {
+        self.get(message_type)?.constant(name)
+    }
+
+    /// Iterate over all schemas in the set
+    pub fn iter(&self) -> impl Iterator<Item = &MessageSchema> {
+        self.schemas.values()
+    }
+
+    /// Get the types referenced by a field that have no schema in the set, sorted
+    ///
+    /// Tools that decode from the definition alone, such as Foxglove Studio reading
+    /// MCAP schemas, cannot decode messages whose definition lacks a type.
+    pub fn missing_types(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = self
+            .iter()
+            .flat_map(|schema| &schema.fields)
+            .map(FieldDef::base_type)
+            .filter(|base| !is_primitive_type(base) && self.get(base).is_none())
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        missing
+    }
+
+    /// List the fields of the root type with the fields of every nested type below them
+    ///
+    /// Fails if a nested type has no schema in the set or types nest each other
+    /// without end.
+    pub fn field_tree(&self) -> Result<Vec<FieldNode>> {
+        self.field_nodes(&self.root, 0)
+    }
+
+    fn field_nodes(&self, type_name: &str, depth: usize) -> Result<Vec<FieldNode>> {
+        if depth >= MAX_NESTING_DEPTH {
+            return Err(BagError::schema_validation(format!(
+                "Type {type_name} nests messages deeper than {MAX_NESTING_DEPTH} levels"
+            )));
+        }
+        let schema = self
+            .get(type_name)
+            .ok_or_else(|| BagError::message_type_not_found(type_name))?;
+        schema
+            .fields
+            .iter()
+            .map(|field| {
+                let base = field.base_type();
+                let children = if is_primitive_type(base) {
+                    Vec::new()
+                } else {
+                    self.field_nodes(base, depth + 1)?
+                };
+                Ok(FieldNode {
+                    field: field.clone(),
+                    children,
+                })
+            })
+            .collect()
+    }
+
+    /// Compute the `RIHS01` type description hash of the root type
+    ///
+    /// This is the hash ROS 2 Iron and later record as `type_description_hash`: the
+    /// SHA-256 of the JSON type description of the root type and of every type it
+    /// references. Only field names and types contribute; constants, default values
+    /// and comments do not.
+    pub fn type_description_hash(&self) -> Result<String> {
+        let root = self.root();
+        let mut referenced: BTreeMap<&str, &MessageSchema> = BTreeMap::new();
+        let mut pending = vec![root];
+        while let Some(schema) = pending.pop() {
+            for field in &schema.fields {
+                let base = field.base_type();
+                if is_primitive_type(base) || base == root.name || referenced.contains_key(base) {
+                    continue;
+                }
+                let nested = self.get(base).ok_or_else(|| {
+                    BagError::schema_validation(format!(
+                        "Definition of {} lacks referenced type {base}",
+                        root.name
+                    ))
+                })?;
+                referenced.insert(base, nested);
+                pending.push(nested);
+            }
+        }
+
+        let referenced: Vec<String> = referenced
+            .iter()
+            .map(|(name, schema)| type_description_json(name, schema))
+            .collect::<Result<_>>()?;
+        let json = format!(
+            "{{\"type_description\": {}, \"referenced_type_descriptions\": [{}]}}",
+            type_description_json(&root.name, root)?,
+            referenced.join(", ")
+        );
+        let digest = Sha256::digest(json.as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        Ok(format!("{TYPE_HASH_PREFIX}{hex}"))
+    }
+}
+
+/// Serialize a type like `json.dumps` of the hashable `TypeDescription` of rosidl
+fn type_description_json(name: &str, schema: &MessageSchema) -> Result<String> {
+    // Empty messages get a placeholder field, as in their generated IDL
+    let placeholder = [FieldDef {
+        name: "structure_needs_at_least_one_member".to_string(),
+        type_name: "uint8".to_string(),
+        default: None,
+    }];
+    let fields = if schema.fields.is_empty() {
+        &placeholder[..]
+    } else {
+        &schema.fields
+    };
+
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let (type_id, capacity, string_capacity, nested_type_name) = field_type(field)?;
+            Ok(format!(
+                "{{\"name\": \"{}\", \"type\": {{\"type_id\": {type_id}, \"capacity\": {capacity}, \
+                 \"string_capacity\": {string_capacity}, \"nested_type_name\": \"{nested_type_name}\"}}}}",
+                field.name
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(format!(
+        "{{\"type_name\": \"{name}\", \"fields\": [{}]}}",
+        fields.join(", ")
+    ))
+}
+
+/// `type_description_interfaces/msg/FieldType` id, capacity, string capacity and nested type of a field
+fn field_type(field: &FieldDef) -> Result<(u8, usize, usize, &str)> {
+    let base = field.base_type();
+    let element = field
+        .type_name
+        .split('[')
+        .next()
+        .unwrap_or(&field.type_name);
+    let string_capacity = match element.split_once("<=") {
+        Some((_, bound)) => bound.parse().map_err(|_| {
+            BagError::schema_validation(format!(
+                "Invalid string bound in field {}: '{}'",
+                field.name, field.type_name
+            ))
+        })?,
+        None => 0,
+    };
+
+    let (type_id, nested_type_name) = match base {
+        "int8" => (2, ""),
+        "uint8" => (3, ""),
+        "int16" => (4, ""),
+        "uint16" => (5, ""),
+        "int32" => (6, ""),
+        "uint32" => (7, ""),
+        "int64" => (8, ""),
+        "uint64" => (9, ""),
+        "float32" => (10, ""),
+        "float64" => (11, ""),
+        "char" => (13, ""),
+        "bool" => (15, ""),
+        "byte" => (16, ""),
+        "string" if string_capacity > 0 => (21, ""),
+        "string" => (17, ""),
+        "wstring" if string_capacity > 0 => (22, ""),
+        "wstring" => (18, ""),
+        nested => (1, nested),
+    };
+    let (type_id, capacity) = match field.array() {
+        None if field.type_name.contains('[') => {
+            return Err(BagError::schema_validation(format!(
+                "Invalid array in field {}: '{}'",
+                field.name, field.type_name
+            )))
+        }
+        None => (type_id, 0),
+        Some(ArraySpec::Fixed(size)) => (type_id + 48, size),
+        Some(ArraySpec::Bounded(size)) => (type_id + 96, size),
+        Some(ArraySpec::Unbounded) => (type_id + 144, 0),
+    };
+    Ok((type_id, capacity, string_capacity, nested_type_name))
+}
+
+/// Check whether a type name is a ROS2 primitive type (without array suffix or bound)
+pub fn is_primitive_type(type_name: &str) -> bool {
+    let base = type_name.split("<=").next().unwrap_or(type_name);
+    PRIMITIVE_TYPES.contains(&base)
+}
+
+/// Normalize a message type name to the `pkg/msg/Type` form
+///
+/// Names without a package are resolved against `package`, except `Header`
+/// which always refers to `std_msgs/msg/Header`.
+fn normalize_type_name(type_name: &str, package: &str) -> String {
+    let parts: Vec<&str> = type_name.split('/').collect();
+    match parts.as_slice() {
+        [package, name] => format!("{package}/msg/{name}"),
+        ["Header"] => "std_msgs/msg/Header".to_string(),
+        [name] if !package.is_empty() => format!("{package}/msg/{name}"),
+        _ => type_name.to_string(),
+    }
+}
+
+/// Look up `pkg/msg/Type`, falling back to `pkg/srv/Type` and `pkg/action/Type`
+///
+/// Definitions of service events and actions declare their request, response, goal,
+/// result and feedback types as plain `Type` or `pkg/Type`, while their sections may
+/// be headed by the `srv` or `action` name.
+fn find_interface<'a>(
+    schemas: &'a HashMap<String, MessageSchema>,
+    type_name: &str,
+) -> Option<&'a MessageSchema> {
+    if let Some(schema) = schemas.get(type_name) {
+        return Some(schema);
+    }
+    let (package, name) = type_name.split_once("/msg/")?;
+    INTERFACE_KINDS
+        .iter()
+        .find_map(|kind| schemas.get(&format!("{package}/{kind}/{name}")))
+}
+
+/// Point field types at the `srv` or `action` sections they refer to
+fn resolve_interface_kinds(schemas: &mut HashMap<String, MessageSchema>) {
+    let mut renames = Vec::new();
+    for (name, schema) in schemas.iter() {
+        for (index, field) in schema.fields.iter().enumerate() {
+            let base = field.base_type();
+            if is_primitive_type(base) || schemas.contains_key(base) {
+                continue;
+            }
+            if let Some(target) = find_interface(schemas, base) {
+                let suffix = &field.type_name[base.len()..];
+                renames.push((name.clone(), index, format!("{}{suffix}", target.name)));
+            }
+        }
+    }
+    for (name, index, type_name) in renames {
+        if let Some(schema) = schemas.get_mut(&name) {
+            schema.fields[index].type_name = type_name;
+        }
+    }
+}
+
+fn parse_msg_body(name: &str, body: &str) -> Result<MessageSchema> {
+    let package = name.split('/').next().unwrap_or_default();
+    let mut schema = MessageSchema {
+        name: name.to_string(),
+        ..Default::default()
+    };
+
+    for raw_line in body.lines() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (type_name, rest) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            BagError::schema_validation(format!("Invalid line in {name}: '{line}'"))
+        })?;
+        let rest = rest.trim_start();
+
+        if let Some((constant_name, value)) = constant_parts(rest) {
+            let value = parse_constant_value(type_name, value).ok_or_else(|| {
+                BagError::schema_validation(format!(
+                    "Invalid value for constant {name}::{constant_name}: '{}'",
+                    value.trim()
+                ))
+            })?;
+            schema.constants.push(ConstantDef {
+                name: constant_name.to_string(),
+                type_name: type_name.to_string(),
+                value,
+            });
+            continue;
+        }
+
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '#')
+            .unwrap_or(rest.len());
+        let (field_name, default) = rest.split_at(name_end);
+        if field_name.is_empty() {
+            return Err(BagError::schema_validation(format!(
+                "Missing field name in {name}: '{line}'"
+            )));
+        }
+        let mut field = FieldDef {
+            name: field_name.to_string(),
+            type_name: resolve_field_type(type_name, package),
+            default: None,
+        };
+        // Rejects malformed array suffixes and string bounds
+        field_type(&field)?;
+        field.default = parse_default(&field, default.trim()).map_err(|reason| {
+            BagError::schema_validation(format!(
+                "Invalid default value for field {name}::{field_name}: {reason}"
+            ))
+        })?;
+        schema.fields.push(field);
+    }
+
+    Ok(schema)
+}
+
+/// Parse the default value written after a field name, if there is one
+///
+/// Strings may be quoted and arrays are written as `[1, 2, 3]`. The value must fit
+/// the field's type, array length and string bound.
+fn parse_default(
+    field: &FieldDef,
+    text: &str,
+) -> std::result::Result<Option<DefaultValue>, String> {
+    if text.is_empty() || text.starts_with('#') {
+        return Ok(None);
+    }
+    let base = field.base_type();
+    if !is_primitive_type(base) {
+        return Err(format!("fields of type {base} cannot have one"));
+    }
+
+    let default = match field.array() {
+        None => {
+            let (value, rest) = default_element(base, text).ok_or_else(|| format!("'{text}'"))?;
+            if !is_trailing(rest) {
+                return Err(format!("'{text}'"));
+            }
+            DefaultValue::Value(value)
+        }
+        Some(spec) => {
+            let values = default_array(base, text).ok_or_else(|| format!("'{text}'"))?;
+            match spec {
+                ArraySpec::Fixed(size) if values.len() != size => {
+                    return Err(format!("{} values for {size} elements", values.len()))
+                }
+                ArraySpec::Bounded(bound) if values.len() > bound => {
+                    return Err(format!("{} values for at most {bound}", values.len()))
+                }
+                _ => DefaultValue::Array(values),
+            }
+        }
+    };
+
+    if let Some(bound) = field.string_bound() {
+        let values = match &default {
+            DefaultValue::Value(value) => std::slice::from_ref(value),
+            DefaultValue::Array(values) => values.as_slice(),
+        };
+        let too_long = values
+            .iter()
+            .filter_map(ConstantValue::as_str)
+            .find(|value| value.chars().count() > bound);
+        if let Some(value) = too_long {
+            return Err(format!("'{value}' is longer than {bound} characters"));
+        }
+    }
+    Ok(Some(default))
+}
+
+/// Parse a `[a, b, ...]` list of default values
+fn default_array(base: &str, text: &str) -> Option<Vec<ConstantValue>> {
+    let mut rest = text.strip_prefix('[')?.trim_start();
+    let mut values = Vec::new();
+    if let Some(after) = rest.strip_prefix(']') {
+        return is_trailing(after).then_some(values);
+    }
+    loop {
+        let (value, after) = default_element(base, rest)?;
+        values.push(value);
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(',') {
+            rest = after.trim_start();
+        } else {
+            return is_trailing(after.strip_prefix(']')?).then_some(values);
+        }
+    }
+}
+
+/// Parse one default value from the start of `text`, returning it and the rest
+///
+/// Unquoted values end at a comma, closing bracket or comment.
+fn default_element<'a>(base: &str, text: &'a str) -> Option<(ConstantValue, &'a str)> {
+    let text = text.trim_start();
+    if matches!(base, "string" | "wstring") && text.starts_with(['"', '\'']) {
+        let (value, rest) = quoted_literal(text)?;
+        return Some((ConstantValue::String(value), rest));
+    }
+    let end = text.find([',', ']', '#']).unwrap_or(text.len());
+    let value = text[..end].trim();
+    if value.is_empty() {
+        return None;
+    }
+    let value = parse_constant_value(base, value)?;
+    let fits = match base {
+        "int8" => value.as_i64().is_some_and(|v| i8::try_from(v).is_ok()),
+        "int16" => value.as_i64().is_some_and(|v| i16::try_from(v).is_ok()),
+        "int32" => value.as_i64().is_some_and(|v| i32::try_from(v).is_ok()),
+        "byte" | "char" | "uint8" => value.as_u64().is_some_and(|v| u8::try_from(v).is_ok()),
+        "uint16" => value.as_u64().is_some_and(|v| u16::try_from(v).is_ok()),
+        "uint32" => value.as_u64().is_some_and(|v| u32::try_from(v).is_ok()),
+        _ => true,
+    };
+    fits.then_some((value, &text[end..]))
+}
+
+/// Whether only whitespace or a comment follows a value
+fn is_trailing(text: &str) -> bool {
+    let text = text.trim_start();
+    text.is_empty() || text.starts_with('#')
+}
+
+/// Split a leading `"..."` or `'...'` literal off `text`, resolving escape sequences
+pub(crate) fn quoted_literal(text: &str) -> Option<(String, &str)> {
+    let quote = text.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => value.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                other => other,
+            }),
+            c if c == quote => return Some((value, &text[index + 1..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Write a value the way `.msg` files spell it, quoting strings
+fn msg_literal(value: &ConstantValue) -> String {
+    match value {
+        ConstantValue::Bool(value) => value.to_string(),
+        ConstantValue::Int(value) => value.to_string(),
+        ConstantValue::UInt(value) => value.to_string(),
+        ConstantValue::Float(value) => format!("{value:?}"),
+        ConstantValue::String(value) => format!("{value:?}"),
+    }
+}
+
+/// Split `NAME=value` into its parts if the declaration is a constant
+///
+/// Field default values (`int32 x 5`) are not constants and are left to the caller.
+fn constant_parts(rest: &str) -> Option<(&str, &str)> {
+    let (name, value) = rest.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((name, value))
+}
+
+fn resolve_field_type(type_name: &str, package: &str) -> String {
+    let (base, suffix) = match type_name.find('[') {
+        Some(index) => type_name.split_at(index),
+        None => (type_name, ""),
+    };
+    if is_primitive_type(base) {
+        type_name.to_string()
+    } else {
+        format!("{}{suffix}", normalize_type_name(base, package))
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.split('#').next().unwrap_or_default()
+}
+
+fn parse_constant_value(type_name: &str, value: &str) -> Option<ConstantValue> {
+    match type_name {
+        // String constants take the rest of the line verbatim, comments included
+        "string" | "wstring" => {
+            let value = value.trim();
+            let unquoted = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some(ConstantValue::String(unquoted.to_string()))
+        }
+        "bool" => match strip_comment(value).trim() {
+            "true" | "True" | "1" => Some(ConstantValue::Bool(true)),
+            "false" | "False" | "0" => Some(ConstantValue::Bool(false)),
+            _ => None,
+        },
+        "float32" | "float64" => strip_comment(value)
+            .trim()
+            .parse()
+            .ok()
+            .map(ConstantValue::Float),
+        "int8" | "int16" | "int32" | "int64" => strip_comment(value)
+            .trim()
+            .parse()
+            .ok()
+            .map(ConstantValue::Int),
+        "byte" | "char" | "uint8" | "uint16" | "uint32" | "uint64" => strip_comment(value)
+            .trim()
+            .parse()
+            .ok()
+            .map(ConstantValue::UInt),
+        _ => None,
+    }
+}
+
+fn constant_eq(a: &ConstantValue, b: &ConstantValue) -> bool {
+    match (a, b) {
+        (
+            ConstantValue::Int(_) | ConstantValue::UInt(_),
+            ConstantValue::Int(_) | ConstantValue::UInt(_),
+        ) => {
+            a.as_i64().is_some() && a.as_i64() == b.as_i64()
+                || a.as_u64().is_some() && a.as_u64() == b.as_u64()
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAV_SAT_FIX: &str = "uint8 COVARIANCE_TYPE_UNKNOWN=0
+uint8 COVARIANCE_TYPE_KNOWN=3
+std_msgs/Header header
+NavSatStatus status
+float64[9] position_covariance # row-major
+uint8 position_covariance_type
+================================================================================
+MSG: sensor_msgs/NavSatStatus
+int8 STATUS_NO_FIX=-1 # unable to fix position
+int8 STATUS_FIX=0
+uint16 SERVICE_GPS=1
+int8 status
+uint16 service
+";
+
+    const ADD_TWO_INTS_EVENT: &str = "service_msgs/ServiceEventInfo info
+AddTwoInts_Request[<=1] request
+AddTwoInts_Response[<=1] response
+================================================================================
+MSG: service_msgs/ServiceEventInfo
+uint8 REQUEST_SENT = 0
+uint8 event_type
+builtin_interfaces/Time stamp
+char[16] client_gid
+int64 sequence_number
+================================================================================
+MSG: example_interfaces/srv/AddTwoInts_Request
+int64 a
+int64 b
+================================================================================
+MSG: example_interfaces/AddTwoInts_Response
+int64 sum
+================================================================================
+MSG: builtin_interfaces/Time
+int32 sec
+uint32 nanosec
+";
+
+    #[test]
+    fn test_parse_service_event() {
+        let schemas = MessageSchemas::parse_msg(
+            "example_interfaces/srv/AddTwoInts_Event",
+            ADD_TWO_INTS_EVENT,
+        )
+        .unwrap();
+
+        let root = schemas.root();
+        assert_eq!(root.name, "example_interfaces/srv/AddTwoInts_Event");
+        assert_eq!(
+            root.field("info").unwrap().type_name,
+            "service_msgs/msg/ServiceEventInfo"
+        );
+        // Request and response resolve to their sections whichever kind heads them
+        let request = root.field("request").unwrap();
+        assert_eq!(
+            request.type_name,
+            "example_interfaces/srv/AddTwoInts_Request[<=1]"
+        );
+        assert_eq!(request.array(), Some(ArraySpec::Bounded(1)));
+        assert!(schemas.get(request.base_type()).is_some());
+        let response = root.field("response").unwrap();
+        assert!(schemas.get(response.base_type()).is_some());
+        assert!(schemas
+            .get("example_interfaces/AddTwoInts_Request")
+            .is_some());
+    }
+
+    #[test]
+    fn test_parse_constants_and_fields() {
+        let schemas = MessageSchemas::parse_msg("sensor_msgs/msg/NavSatFix", NAV_SAT_FIX).unwrap();
+
+        let root = schemas.root();
+        assert_eq!(root.name, "sensor_msgs/msg/NavSatFix");
+        assert_eq!(
+            root.constant("COVARIANCE_TYPE_KNOWN"),
+            Some(&ConstantValue::UInt(3))
+        );
+        assert_eq!(root.fields.len(), 4);
+        assert_eq!(
+            root.field("header").unwrap().type_name,
+            "std_msgs/msg/Header"
+        );
+        assert_eq!(
+            root.field("status").unwrap().type_name,
+            "sensor_msgs/msg/NavSatStatus"
+        );
+        let covariance = root.field("position_covariance").unwrap();
+        assert_eq!(covariance.type_name, "float64[9]");
+        assert_eq!(covariance.base_type(), "float64");
+        assert_eq!(covariance.array(), Some(ArraySpec::Fixed(9)));
+        assert_eq!(
+            root.field("position_covariance_type").unwrap().array(),
+            None
+        );
+
+        let status = schemas.get("sensor_msgs/NavSatStatus").unwrap();
+        assert_eq!(
+            status
+                .constant("STATUS_NO_FIX")
+                .and_then(ConstantValue::as_i64),
+            Some(-1)
+        );
+        assert_eq!(
+            schemas.constant("sensor_msgs/msg/NavSatStatus", "SERVICE_GPS"),
+            Some(&ConstantValue::UInt(1))
+        );
+        assert_eq!(
+            status.constant_names_for(&ConstantValue::UInt(0)),
+            vec!["STATUS_FIX"]
+        );
+    }
+
+    #[test]
+    fn test_string_constant_keeps_hash() {
+        let schemas =
+            MessageSchemas::parse_msg("pkg/msg/Example", "string FRAME=\"map#1\"\nstring name\n")
+                .unwrap();
+        assert_eq!(
+            schemas
+                .root()
+                .constant("FRAME")
+                .and_then(ConstantValue::as_str),
+            Some("map#1")
+        );
+        assert_eq!(schemas.root().fields.len(), 1);
+    }
+
+    #[test]
+    fn test_type_description_hash() {
+        // Hashes recorded by ROS 2 Iron and later for these types
+        let string = MessageSchemas::parse_msg("std_msgs/msg/String", "string data\n").unwrap();
+        assert_eq!(
+            string.type_description_hash().unwrap(),
+            "RIHS01_df668c740482bbd48fb39d76a70dfd4bd59db1288021743503259e948f6b1a18"
+        );
+        let header = "# Standard metadata\nbuiltin_interfaces/Time stamp\nstring frame_id\n\
+            ================================================================================\n\
+            MSG: builtin_interfaces/Time\nint32 sec # seconds\nuint32 nanosec\n";
+        let header = MessageSchemas::parse_msg("std_msgs/msg/Header", header).unwrap();
+        assert_eq!(
+            header.type_description_hash().unwrap(),
+            "RIHS01_f49fb3ae2cf070f793645ff749683ac6b06203e41c891e17701b1cb597ce6a01"
+        );
+
+        // Constants do not contribute, field types do
+        let with_constant =
+            MessageSchemas::parse_msg("std_msgs/msg/String", "int8 MODE=1\nstring data\n").unwrap();
+        assert_eq!(
+            with_constant.type_description_hash().unwrap(),
+            string.type_description_hash().unwrap()
+        );
+        let bounded = MessageSchemas::parse_msg("std_msgs/msg/String", "string<=8 data\n").unwrap();
+        assert_ne!(
+            bounded.type_description_hash().unwrap(),
+            string.type_description_hash().unwrap()
+        );
+
+        let missing =
+            MessageSchemas::parse_msg("pkg/msg/Example", "other_pkg/Type value\n").unwrap();
+        assert!(missing.type_description_hash().is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_type_description_hash_matches_recorded_hashes() {
+        // The test bag stores the definition and hash of every type it recorded
+        let db =
+            rusqlite::Connection::open("tests/test_bags/test_bag_sqlite3/test_bag_sqlite3.db3")
+                .unwrap();
+        let mut statement = db
+            .prepare("SELECT topic_type, encoded_message_definition, type_description_hash FROM message_definitions")
+            .unwrap();
+        let rows: Vec<(String, String, String)> = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows.len(), 94);
+        for (message_type, definition, hash) in rows {
+            let schemas = MessageSchemas::parse_msg(&message_type, &definition).unwrap();
+            assert_eq!(
+                schemas.type_description_hash().unwrap(),
+                hash,
+                "{message_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_constant_rejected() {
+        assert!(MessageSchemas::parse_msg("pkg/msg/Example", "int8 BAD=abc\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_field_defaults() {
+        let text = "int32 count 5 # five\n\
+            string<=8 label \"a #1\"\n\
+            string plain hello world\n\
+            float64[3] weights [1, 2.5, -3]\n\
+            string[<=2] names [\"x, y\", 'z']\n\
+            uint8[] empty []\n\
+            bool flag\n\
+            ===\n\
+            MSG: pkg/Inner\n\
+            bool on true\n";
+        let schemas = MessageSchemas::parse_msg("pkg/msg/Example", text).unwrap();
+        let root = schemas.root();
+        let default = |name: &str| root.field(name).unwrap().default.clone();
+        assert_eq!(
+            default("count"),
+            Some(DefaultValue::Value(ConstantValue::Int(5)))
+        );
+        assert_eq!(
+            default("label"),
+            Some(DefaultValue::Value(ConstantValue::String(
+                "a #1".to_string()
+            )))
+        );
+        assert_eq!(root.field("label").unwrap().string_bound(), Some(8));
+        assert_eq!(
+            default("plain"),
+            Some(DefaultValue::Value(ConstantValue::String(
+                "hello world".to_string()
+            )))
+        );
+        assert_eq!(
+            default("weights"),
+            Some(DefaultValue::Array(vec![
+                ConstantValue::Float(1.0),
+                ConstantValue::Float(2.5),
+                ConstantValue::Float(-3.0),
+            ]))
+        );
+        assert_eq!(
+            default("names"),
+            Some(DefaultValue::Array(vec![
+                ConstantValue::String("x, y".to_string()),
+                ConstantValue::String("z".to_string()),
+            ]))
+        );
+        assert_eq!(default("empty"), Some(DefaultValue::Array(Vec::new())));
+        assert_eq!(default("flag"), None);
+        assert!(schemas.get("pkg/msg/Inner").unwrap().fields[0]
+            .default
+            .is_some());
+
+        // Defaults survive rendering back to `.msg` text
+        let rendered = MessageSchemas::parse_msg("pkg/msg/Example", &root.to_msg()).unwrap();
+        assert_eq!(rendered.root().fields, root.fields);
+    }
+
+    #[test]
+    fn test_invalid_field_rejected() {
+        for line in [
+            "float64[abc] values",
+            "string<=x name",
+            "int32 count five",
+            "uint8 small 256",
+            "int32[2] pair [1, 2, 3]",
+            "int32[<=1] bounded [1, 2]",
+            "string<=2 label \"abc\"",
+            "int32[] open [1, 2",
+            "pkg/Inner inner 0",
+        ] {
+            assert!(
+                MessageSchemas::parse_msg("pkg/msg/Example", line).is_err(),
+                "{line}"
+            );
+        }
+    }
+}
@@ -0,0 +1,676 @@
+//! Parser for the IDL definitions stored by rosbag2 (`ros2idl` encoding)
+//!
+//! Supports the subset of OMG IDL that `rosidl` generates for messages: nested
+//! modules, structs, typedefs, `<Type>_Constants` modules, sequences, bounded strings
+//! and fixed-size arrays. Bounds and array sizes may name integer constants, members
+//! may declare several names, and string constants may be split into concatenated
+//! literals. `@default` values are kept; other annotations such as `@verbatim` are
+//! skipped.
+
+use super::{
+    is_separator_line, parse_constant_value, parse_default, quoted_literal, ConstantDef,
+    ConstantValue, DefaultValue, FieldDef, MessageSchema,
+};
+use crate::error::{BagError, Result};
+use std::collections::HashMap;
+
+/// Suffix of the modules holding the constants of a struct
+const CONSTANTS_SUFFIX: &str = "_Constants";
+
+/// Parse every struct of a concatenated IDL definition, by `pkg/msg/Type`
+pub(super) fn parse(text: &str) -> Result<HashMap<String, MessageSchema>> {
+    let mut parser = Parser {
+        tokens: tokenize(&strip_section_headers(text))?,
+        position: 0,
+        typedefs: HashMap::new(),
+        values: HashMap::new(),
+        schemas: HashMap::new(),
+        constants: Vec::new(),
+    };
+    parser.definitions(&mut Vec::new())?;
+    if let Some(token) = parser.peek() {
+        return Err(error(format!("unexpected '{token}'")));
+    }
+
+    let mut schemas = parser.schemas;
+    for (owner, constant) in parser.constants {
+        schemas
+            .entry(owner.clone())
+            .or_insert_with(|| MessageSchema {
+                name: owner,
+                ..Default::default()
+            })
+            .constants
+            .push(constant);
+    }
+    Ok(schemas)
+}
+
+fn error(reason: impl std::fmt::Display) -> BagError {
+    BagError::schema_validation(format!("Invalid IDL definition: {reason}"))
+}
+
+/// Drop the separator, `IDL:` and `#include` lines between the sections
+fn strip_section_headers(text: &str) -> String {
+    text.lines()
+        .filter(|line| {
+            let line = line.trim();
+            !is_separator_line(line) && !line.starts_with("IDL:") && !line.starts_with('#')
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split IDL text into identifiers, literals and punctuation, dropping comments
+fn tokenize(text: &str) -> Result<Vec<String>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            if i >= chars.len() {
+                return Err(error("unterminated literal"));
+            }
+            i += 1;
+            tokens.push(chars[start..i].iter().collect());
+        } else if c == ':' && next == Some(':') {
+            tokens.push("::".to_string());
+            i += 2;
+        } else if c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.') {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.')) {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            tokens.push(c.to_string());
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+    /// Typedef names (plain and scoped) and the types they stand for
+    typedefs: HashMap<String, String>,
+    /// Values of the constants declared so far, by plain and scoped name
+    values: HashMap<String, ConstantValue>,
+    schemas: HashMap<String, MessageSchema>,
+    /// Constants with the `pkg/msg/Type` they belong to
+    constants: Vec<(String, ConstantDef)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| error("unexpected end of definition"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(error(format!("expected '{expected}', found '{token}'")))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        let token = self.next()?;
+        if token.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            Ok(token)
+        } else {
+            Err(error(format!("expected a name, found '{token}'")))
+        }
+    }
+
+    /// Skip annotations like `@key` or `@verbatim (language="comment", text="...")`
+    ///
+    /// Returns the value given by a `@default (value=...)` annotation among them.
+    fn annotations(&mut self) -> Result<Option<String>> {
+        let mut default = None;
+        while self.peek() == Some("@") {
+            self.position += 1;
+            let name = self.scoped_name()?;
+            if self.peek() == Some("(") {
+                let start = self.position;
+                let mut depth = 0;
+                loop {
+                    match self.next()?.as_str() {
+                        "(" => depth += 1,
+                        ")" if depth == 1 => break,
+                        ")" => depth -= 1,
+                        _ => {}
+                    }
+                }
+                if let [key, equals, value @ ..] = &self.tokens[start + 1..self.position - 1] {
+                    if name == ["default"] && key == "value" && equals == "=" {
+                        default = Some(value.concat());
+                    }
+                }
+            }
+        }
+        Ok(default)
+    }
+
+    /// Parse a name like `Type`, `msg::Type` or `::pkg::msg::Type`
+    fn scoped_name(&mut self) -> Result<Vec<String>> {
+        if self.peek() == Some("::") {
+            self.position += 1;
+        }
+        let mut parts = vec![self.identifier()?];
+        while self.peek() == Some("::") {
+            self.position += 1;
+            parts.push(self.identifier()?);
+        }
+        Ok(parts)
+    }
+
+    /// Parse module contents until the closing brace or the end of the text
+    fn definitions(&mut self, scope: &mut Vec<String>) -> Result<()> {
+        loop {
+            self.annotations()?;
+            match self.peek() {
+                None | Some("}") => return Ok(()),
+                Some("module") => {
+                    self.position += 1;
+                    let name = self.identifier()?;
+                    self.expect("{")?;
+                    scope.push(name);
+                    self.definitions(scope)?;
+                    scope.pop();
+                    self.expect("}")?;
+                    self.expect(";")?;
+                }
+                Some("struct")
+                    if self.tokens.get(self.position + 2).map(String::as_str) == Some(";") =>
+                {
+                    // Forward declaration
+                    self.position += 3;
+                }
+                Some("struct") => {
+                    self.position += 1;
+                    let schema = self.structure(scope)?;
+                    self.schemas.insert(schema.name.clone(), schema);
+                }
+                Some("typedef") => {
+                    self.position += 1;
+                    let type_name = self.type_spec(scope)?;
+                    for (name, type_name) in self.declarators(scope, &type_name)? {
+                        let mut scoped = scope.clone();
+                        scoped.push(name.clone());
+                        self.typedefs.insert(scoped.join("::"), type_name.clone());
+                        self.typedefs.insert(name, type_name);
+                    }
+                }
+                Some("const") => {
+                    self.position += 1;
+                    let constant = self.constant(scope)?;
+                    let mut scoped = scope.clone();
+                    scoped.push(constant.name.clone());
+                    self.values
+                        .insert(scoped.join("::"), constant.value.clone());
+                    self.values
+                        .insert(constant.name.clone(), constant.value.clone());
+                    // Constants outside of a `_Constants` module only serve as bounds
+                    if let Some(owner) = scope
+                        .last()
+                        .and_then(|module| module.strip_suffix(CONSTANTS_SUFFIX))
+                    {
+                        let mut owner_scope = scope[..scope.len() - 1].to_vec();
+                        owner_scope.push(owner.to_string());
+                        self.constants.push((owner_scope.join("/"), constant));
+                    }
+                }
+                Some(other) => return Err(error(format!("unsupported declaration '{other}'"))),
+            }
+        }
+    }
+
+    fn structure(&mut self, scope: &[String]) -> Result<MessageSchema> {
+        let name = self.identifier()?;
+        self.expect("{")?;
+        let mut fields = Vec::new();
+        loop {
+            let default = self.annotations()?;
+            if self.peek() == Some("}") {
+                break;
+            }
+            let type_name = self.type_spec(scope)?;
+            for (name, type_name) in self.declarators(scope, &type_name)? {
+                let mut field = FieldDef {
+                    name,
+                    type_name,
+                    default: None,
+                };
+                if let Some(value) = &default {
+                    field.default = default_value(&field, value)?;
+                }
+                fields.push(field);
+            }
+        }
+        self.expect("}")?;
+        self.expect(";")?;
+
+        let mut qualified = scope.to_vec();
+        qualified.push(name);
+        Ok(MessageSchema {
+            name: qualified.join("/"),
+            fields,
+            constants: Vec::new(),
+        })
+    }
+
+    /// Parse the names declared with one type, up to the closing `;`
+    ///
+    /// Each name may have its own array size, as in `double x, y[3];`.
+    fn declarators(&mut self, scope: &[String], type_name: &str) -> Result<Vec<(String, String)>> {
+        let mut declared = Vec::new();
+        loop {
+            let name = self.identifier()?;
+            let type_name = self.array_suffix(scope, type_name.to_string())?;
+            declared.push((name, type_name));
+            match self.next()?.as_str() {
+                "," => {}
+                ";" => return Ok(declared),
+                other => return Err(error(format!("expected ',' or ';', found '{other}'"))),
+            }
+        }
+    }
+
+    fn constant(&mut self, scope: &[String]) -> Result<ConstantDef> {
+        let type_name = self.type_spec(scope)?;
+        let name = self.identifier()?;
+        self.expect("=")?;
+        let mut tokens = Vec::new();
+        while self.peek() != Some(";") {
+            tokens.push(self.next()?);
+        }
+        self.expect(";")?;
+
+        let base = type_name.split("<=").next().unwrap_or(&type_name);
+        let invalid = || {
+            error(format!(
+                "invalid value for constant {name}: '{}'",
+                tokens.concat()
+            ))
+        };
+        let value = match base {
+            // Adjacent string literals are concatenated
+            "string" | "wstring" => {
+                let parts = tokens
+                    .iter()
+                    .map(|token| unquote(token, '"'))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|parts| !parts.is_empty())
+                    .ok_or_else(invalid)?;
+                ConstantValue::String(parts.concat())
+            }
+            _ => {
+                let value = tokens.concat();
+                match value.as_str() {
+                    "TRUE" => ConstantValue::Bool(true),
+                    "FALSE" => ConstantValue::Bool(false),
+                    value => literal_value(base, value)
+                        .or_else(|| self.lookup(scope, &tokens))
+                        .ok_or_else(invalid)?,
+                }
+            }
+        };
+        Ok(ConstantDef {
+            name,
+            type_name: base.to_string(),
+            value,
+        })
+    }
+
+    /// Value of a constant referred to by a possibly scoped name
+    fn lookup(&self, scope: &[String], tokens: &[String]) -> Option<ConstantValue> {
+        let name = tokens.concat();
+        let name = name.trim_start_matches("::");
+        (0..=scope.len())
+            .rev()
+            .find_map(|depth| {
+                let mut scoped = scope[..depth].to_vec();
+                scoped.push(name.to_string());
+                self.values.get(&scoped.join("::"))
+            })
+            .or_else(|| self.values.get(name))
+            .cloned()
+    }
+
+    /// Parse a bound or array size: an integer literal or the name of an integer constant
+    fn bound(&mut self, scope: &[String]) -> Result<String> {
+        if self
+            .peek()
+            .is_some_and(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let token = self.next()?;
+            return literal_value("uint64", &token)
+                .and_then(|value| value.as_u64())
+                .map(|value| value.to_string())
+                .ok_or_else(|| error(format!("invalid bound '{token}'")));
+        }
+        let parts = self.scoped_name()?;
+        self.lookup(scope, &[parts.join("::")])
+            .and_then(|value| value.as_u64())
+            .map(|value| value.to_string())
+            .ok_or_else(|| error(format!("unknown integer constant '{}'", parts.join("::"))))
+    }
+
+    /// Parse a type in `.msg` notation (e.g. `float64`, `string<=10`, `pkg/msg/Type[]`)
+    fn type_spec(&mut self, scope: &[String]) -> Result<String> {
+        let token = self.next()?;
+        Ok(match token.as_str() {
+            "boolean" => "bool".to_string(),
+            "octet" => "byte".to_string(),
+            "char" => "char".to_string(),
+            "float" => "float32".to_string(),
+            "double" => "float64".to_string(),
+            "short" => "int16".to_string(),
+            "long" if self.peek() == Some("long") => {
+                self.position += 1;
+                "int64".to_string()
+            }
+            "long" if self.peek() == Some("double") => {
+                return Err(error("long double is not supported"));
+            }
+            "long" => "int32".to_string(),
+            "unsigned" => match self.next()?.as_str() {
+                "short" => "uint16".to_string(),
+                "long" if self.peek() == Some("long") => {
+                    self.position += 1;
+                    "uint64".to_string()
+                }
+                "long" => "uint32".to_string(),
+                other => return Err(error(format!("unsupported type 'unsigned {other}'"))),
+            },
+            "int8" | "uint8" | "int16" | "uint16" | "int32" | "uint32" | "int64" | "uint64" => {
+                token
+            }
+            "string" | "wstring" => {
+                if self.peek() == Some("<") {
+                    self.position += 1;
+                    let bound = self.bound(scope)?;
+                    self.expect(">")?;
+                    format!("{token}<={bound}")
+                } else {
+                    token
+                }
+            }
+            "sequence" => {
+                self.expect("<")?;
+                let element = self.type_spec(scope)?;
+                if element.contains('[') {
+                    return Err(error("sequences of arrays are not supported"));
+                }
+                let bound = if self.peek() == Some(",") {
+                    self.position += 1;
+                    self.bound(scope)?
+                } else {
+                    String::new()
+                };
+                self.expect(">")?;
+                if bound.is_empty() {
+                    format!("{element}[]")
+                } else {
+                    format!("{element}[<={bound}]")
+                }
+            }
+            "wchar" => return Err(error("wchar is not supported")),
+            _ => {
+                self.position -= 1;
+                let parts = self.scoped_name()?;
+                let name = parts.join("::");
+                if let Some(type_name) = self.typedefs.get(&name) {
+                    type_name.clone()
+                } else if parts.len() >= 3 {
+                    parts.join("/")
+                } else {
+                    // Relative names are resolved from the innermost enclosing module
+                    // that holds their first part, or else from the current module
+                    let depth = scope
+                        .iter()
+                        .rposition(|module| *module == parts[0])
+                        .unwrap_or(scope.len());
+                    let mut qualified = scope[..depth].to_vec();
+                    qualified.extend(parts);
+                    qualified.join("/")
+                }
+            }
+        })
+    }
+
+    /// Apply a `[N]` array declarator after a member or typedef name
+    fn array_suffix(&mut self, scope: &[String], type_name: String) -> Result<String> {
+        if self.peek() != Some("[") {
+            return Ok(type_name);
+        }
+        self.position += 1;
+        let size = self.bound(scope)?;
+        self.expect("]")?;
+        if type_name.contains('[') || self.peek() == Some("[") {
+            return Err(error("multi-dimensional arrays are not supported"));
+        }
+        Ok(format!("{type_name}[{size}]"))
+    }
+}
+
+/// Parse a numeric, boolean or character literal of a primitive type
+fn literal_value(type_name: &str, literal: &str) -> Option<ConstantValue> {
+    if let Some(character) = unquote(literal, '\'') {
+        let mut chars = character.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => c as u64,
+            _ => return None,
+        };
+        return parse_constant_value(type_name, &code.to_string());
+    }
+    let (negative, digits) = match literal.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        let value = u64::from_str_radix(hex, 16).ok()?;
+        let value = if negative {
+            format!("-{value}")
+        } else {
+            value.to_string()
+        };
+        return parse_constant_value(type_name, &value);
+    }
+    parse_constant_value(type_name, literal)
+}
+
+/// Convert the value of a `@default` annotation to the field's default
+///
+/// Array defaults are string literals of a tuple, like `"(1, 2)"`.
+fn default_value(field: &FieldDef, value: &str) -> Result<Option<DefaultValue>> {
+    let text = match value {
+        "TRUE" => "true".to_string(),
+        "FALSE" => "false".to_string(),
+        // Wide string literals
+        value if value.starts_with("L\"") => value[1..].to_string(),
+        value if field.array().is_some() => {
+            let tuple = unquote(value, '"').unwrap_or_else(|| value.to_string());
+            let tuple = tuple.trim();
+            match tuple.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+                Some(items) => format!("[{items}]"),
+                None => tuple.to_string(),
+            }
+        }
+        value => value.to_string(),
+    };
+    parse_default(field, &text).map_err(|reason| {
+        error(format!(
+            "invalid default value for member {}: {reason}",
+            field.name
+        ))
+    })
+}
+
+/// Contents of a literal enclosed in `quote`, with escape sequences resolved
+fn unquote(token: &str, quote: char) -> Option<String> {
+    if !token.starts_with(quote) {
+        return None;
+    }
+    let (text, rest) = quoted_literal(token)?;
+    rest.is_empty().then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdr::{CdrDeserializer, CdrSerializer};
+    use crate::dynamic::{decode_message, default_message, encode_message, FieldValue, Value};
+    use crate::schema::MessageSchemas;
+
+    const TRACK_IDL: &str = r#"================================================================================
+IDL: my_pkg/msg/Track
+// generated from rosidl_adapter/resource/msg.idl.em
+
+module my_pkg {
+  module msg {
+    const uint32 MAX_POINTS = 0x10;
+    module Track_Constants {
+      const uint8 STATE_LOST = 0;
+      const char FLAG = 'F';
+      const uint16 NAME_LENGTH = 32;
+      const string GREETING = "a \"quoted\" "
+        "greeting";
+    };
+    struct Point;
+    typedef float float__2[2];
+    struct Track {
+      @default (value="lane \"1\"")
+      string<Track_Constants::NAME_LENGTH> name;
+      @default (value=-1.5)
+      double x, y, z;
+      sequence<msg::Point, MAX_POINTS> points;
+      uint8 flags[::my_pkg::msg::MAX_POINTS], mode;
+      @default (value="(0.5, 2.0)")
+      float__2 weights;
+    };
+    struct Point {
+      float x, y;
+    };
+  };
+};
+"#;
+
+    #[test]
+    fn test_parse_bounds_declarators_and_literals() {
+        let schemas = parse(TRACK_IDL).unwrap();
+        let track = &schemas["my_pkg/msg/Track"];
+        let types: Vec<(&str, &str)> = track
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.type_name.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("name", "string<=32"),
+                ("x", "float64"),
+                ("y", "float64"),
+                ("z", "float64"),
+                ("points", "my_pkg/msg/Point[<=16]"),
+                ("flags", "uint8[16]"),
+                ("mode", "uint8"),
+                ("weights", "float32[2]"),
+            ]
+        );
+        assert_eq!(schemas["my_pkg/msg/Point"].fields.len(), 2);
+        assert_eq!(track.constant("FLAG"), Some(&ConstantValue::UInt(70)));
+        assert_eq!(
+            track.constant("GREETING"),
+            Some(&ConstantValue::String("a \"quoted\" greeting".to_string()))
+        );
+        assert_eq!(
+            track.field("name").unwrap().default,
+            Some(DefaultValue::Value(ConstantValue::String(
+                "lane \"1\"".to_string()
+            )))
+        );
+        // Every name of a member gets its default
+        assert_eq!(
+            track.field("z").unwrap().default,
+            Some(DefaultValue::Value(ConstantValue::Float(-1.5)))
+        );
+        assert_eq!(
+            track.field("weights").unwrap().default,
+            Some(DefaultValue::Array(vec![
+                ConstantValue::Float(0.5),
+                ConstantValue::Float(2.0)
+            ]))
+        );
+        // Module-level constants only serve as bounds
+        assert!(track.constant("MAX_POINTS").is_none());
+
+        let error = parse("module a { struct B { sequence<long, LIMIT> c; }; };").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unknown integer constant 'LIMIT'"));
+    }
+
+    #[test]
+    fn test_decode_with_idl_schema() {
+        let schemas = MessageSchemas::parse_idl("my_pkg/msg/Track", TRACK_IDL).unwrap();
+        let mut message = default_message(&schemas, "my_pkg/msg/Track").unwrap();
+        assert_eq!(
+            message.get("y"),
+            Some(&FieldValue::Value(Value::Float(-1.5)))
+        );
+        message.fields[0].1 = FieldValue::Value(Value::String("lane".to_string()));
+        message.fields[3].1 = FieldValue::Value(Value::Float(1.5));
+        let mut point = default_message(&schemas, "my_pkg/msg/Point").unwrap();
+        point.fields[1].1 = FieldValue::Value(Value::Float(-2.0));
+        message.fields[4].1 = FieldValue::Array(vec![FieldValue::Message(point)]);
+
+        let mut serializer = CdrSerializer::new();
+        encode_message(&mut serializer, &schemas, "my_pkg/msg/Track", &message).unwrap();
+        let data = serializer.into_bytes();
+        let mut deserializer = CdrDeserializer::new(&data).unwrap();
+        let decoded = decode_message(&mut deserializer, &schemas, "my_pkg/msg/Track").unwrap();
+        assert_eq!(deserializer.position(), data.len());
+        assert_eq!(decoded, message);
+        assert_eq!(
+            decoded.path("points[0].y"),
+            Some(&FieldValue::Value(Value::Float(-2.0)))
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Time-based sharding of bags for parallel processing
+//!
+//! [`Reader::shards`] splits a bag's time range into consecutive windows. Each
+//! [`Shard`] only holds the bag path and its window, so shards can be sent to other
+//! threads or processes, where [`Shard::open`] creates an independent reader that
+//! iterates over the messages of that window only.
+
+use crate::error::{ReaderError, Result};
+use crate::reader::{Reader, ReaderOptions};
+use crate::types::{Connection, Message, RawMessage, RawMessageRef};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A time window of a bag that can be read independently of other shards
+///
+/// Windows are half-open: a shard covers messages with `start <= timestamp < end`,
+/// so every message of the bag belongs to exactly one shard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shard {
+    /// Position of the shard in time order
+    index: usize,
+    /// Path to the bag directory
+    bag_path: PathBuf,
+    /// First timestamp of the window in nanoseconds (inclusive)
+    start: u64,
+    /// End of the window in nanoseconds (exclusive)
+    end: u64,
+    /// Storage access options of the reader the shard was split from
+    options: ReaderOptions,
+}
+
+impl Shard {
+    /// Split the time range of `reader`'s bag into windows of `window`
+    ///
+    /// Windows start at the bag's first message; the last one ends right after its
+    /// last message. Bags without messages have no shards.
+    pub(crate) fn split(reader: &Reader, window: Duration) -> Result<Vec<Self>> {
+        let window = u64::try_from(window.as_nanos()).unwrap_or(u64::MAX);
+        if window == 0 {
+            return Err(ReaderError::generic(
+                "Shard window must be longer than zero",
+            ));
+        }
+        if reader.message_count() == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first = reader.start_time();
+        let stop = reader.end_time().saturating_add(1);
+        let mut shards = Vec::new();
+        let mut start = first;
+        while start < stop {
+            let end = start.saturating_add(window).min(stop);
+            shards.push(Self {
+                index: shards.len(),
+                bag_path: reader.bag_path().to_path_buf(),
+                start,
+                end,
+                options: reader.options().clone(),
+            });
+            start = end;
+        }
+        Ok(shards)
+    }
+
+    /// Get the position of the shard in time order
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the path of the bag the shard belongs to
+    pub fn bag_path(&self) -> &Path {
+        &self.bag_path
+    }
+
+    /// Get the first timestamp of the window in nanoseconds (inclusive)
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Get the end of the window in nanoseconds (exclusive)
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Whether `timestamp` falls into the window
+    pub fn contains(&self, timestamp: u64) -> bool {
+        (self.start..self.end).contains(&timestamp)
+    }
+
+    /// Open an independent reader for the window
+    ///
+    /// The reader uses the [`ReaderOptions`] of the reader the shard was split from.
+    pub fn open(&self) -> Result<ShardReader> {
+        let mut reader = Reader::with_options(&self.bag_path, self.options.clone())?;
+        reader.open()?;
+        Ok(ShardReader {
+            shard: self.clone(),
+            reader,
+        })
+    }
+}
+
+/// Reader restricted to the time window of a [`Shard`]
+pub struct ShardReader {
+    shard: Shard,
+    reader: Reader,
+}
+
+impl std::fmt::Debug for ShardReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShardReader")
+            .field("shard", &self.shard)
+            .field("reader", &"<reader>")
+            .finish()
+    }
+}
+
+impl ShardReader {
+    /// Get the shard being read
+    pub fn shard(&self) -> &Shard {
+        &self.shard
+    }
+
+    /// Get the underlying reader (e.g. for connections and metadata)
+    pub fn reader(&self) -> &Reader {
+        &self.reader
+    }
+
+    /// Get all connections of the bag
+    pub fn connections(&self) -> &[Connection] {
+        self.reader.connections()
+    }
+
+    /// Iterate over the messages in the window
+    pub fn messages(&self) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        self.messages_filtered(None)
+    }
+
+    /// Iterate over the messages of `connections` in the window
+    pub fn messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        self.reader
+            .messages_filtered(connections, Some(self.shard.start), Some(self.shard.end))
+    }
+
+    /// Iterate over the raw messages in the window
+    pub fn raw_messages(&self) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        self.raw_messages_filtered(None)
+    }
+
+    /// Iterate over the raw messages of `connections` in the window
+    pub fn raw_messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        self.reader
+            .raw_messages_filtered(connections, Some(self.shard.start), Some(self.shard.end))
+    }
+
+    /// Iterate over the raw messages of `connections` in the window without copying
+    /// their data where the storage allows it
+    pub fn raw_messages_borrowed(
+        &self,
+        connections: Option<&[Connection]>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        self.reader.raw_messages_borrowed_filtered(
+            connections,
+            Some(self.shard.start),
+            Some(self.shard.end),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_shards_partition_messages() {
+        for bag in [
+            "tests/test_bags/test_bag_sqlite3",
+            "tests/test_bags/test_bag_mcap",
+        ] {
+            let reader = Reader::new(bag).unwrap();
+            let shards = reader.shards(Duration::from_secs(5)).unwrap();
+            // 18.7 s of messages in 5 s windows
+            assert_eq!(shards.len(), 4);
+            assert_eq!(shards[0].start(), reader.start_time());
+            assert_eq!(shards[3].end(), reader.end_time() + 1);
+            assert!(shards.windows(2).all(|w| w[0].end() == w[1].start()));
+
+            // Shards are processed on separate threads and cover every message once
+            let counts: Vec<u64> = std::thread::scope(|scope| {
+                let handles: Vec<_> = shards
+                    .iter()
+                    .map(|shard| {
+                        scope.spawn(move || {
+                            let reader = shard.open().unwrap();
+                            let mut count = 0;
+                            for message in reader.raw_messages().unwrap() {
+                                assert!(shard.contains(message.unwrap().timestamp));
+                                count += 1;
+                            }
+                            count
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            assert_eq!(counts.iter().sum::<u64>(), reader.message_count());
+            assert!(counts.iter().all(|&count| count > 0));
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_shards_reject_empty_window() {
+        let reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        assert!(reader.shards(Duration::ZERO).is_err());
+        let shards = reader.shards(Duration::from_secs(3600)).unwrap();
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].index(), 0);
+    }
+}
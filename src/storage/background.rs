@@ -0,0 +1,250 @@
+//! Storage writer that writes message batches on a background thread
+//!
+//! [`BackgroundStorage`] wraps another [`StorageWriter`] and hands the batches flushed
+//! by the [`Writer`](crate::Writer) to a worker thread, so the producer keeps
+//! buffering the next batch while the previous one reaches the disk. The queue between
+//! them is bounded: once it holds the configured number of batches, flushing blocks
+//! until the worker has caught up.
+
+use crate::error::{BagError, Result};
+use crate::storage::StorageWriter;
+use crate::types::{Attachment, Connection, McapMetadata};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+/// A batch of messages queued for the worker
+type Batch = Vec<(Connection, u64, Vec<u8>)>;
+
+/// Storage writer writing message batches on a worker thread
+pub(crate) struct BackgroundStorage {
+    /// Wrapped storage, locked by the worker while it writes a batch
+    storage: Arc<Mutex<Box<dyn StorageWriter>>>,
+    /// Queue of batches to write, `None` once the worker is stopped
+    sender: Option<SyncSender<Batch>>,
+    /// The worker thread
+    worker: Option<JoinHandle<()>>,
+    /// First error of the worker, which then stops writing
+    error: Arc<Mutex<Option<BagError>>>,
+}
+
+impl BackgroundStorage {
+    /// Write the batches of `storage` on a new worker thread, queueing up to
+    /// `max_queued_batches` batches before flushing blocks
+    pub(crate) fn spawn(
+        storage: Box<dyn StorageWriter>,
+        max_queued_batches: usize,
+    ) -> Result<Self> {
+        let storage = Arc::new(Mutex::new(storage));
+        let error = Arc::new(Mutex::new(None));
+        let (sender, receiver) = sync_channel::<Batch>(max_queued_batches);
+
+        let worker = {
+            let storage = Arc::clone(&storage);
+            let error = Arc::clone(&error);
+            std::thread::Builder::new()
+                .name("rosbags-writer".to_string())
+                .spawn(move || {
+                    for batch in receiver {
+                        if let Err(e) = lock(&storage).write_batch(&batch) {
+                            *lock(&error) = Some(e);
+                            // Dropping the receiver fails the next send
+                            return;
+                        }
+                    }
+                })?
+        };
+
+        Ok(Self {
+            storage,
+            sender: Some(sender),
+            worker: Some(worker),
+            error,
+        })
+    }
+
+    /// Queue `batch`, waiting while the queue is full
+    fn send(&mut self, batch: Batch) -> Result<()> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(batch).is_ok(),
+            None => false,
+        };
+        if sent {
+            Ok(())
+        } else {
+            self.finish()?;
+            Err(BagError::writer("Background writer is stopped"))
+        }
+    }
+
+    /// Write the queued batches and stop the worker, returning its first error
+    fn finish(&mut self) -> Result<()> {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            worker
+                .join()
+                .map_err(|_| BagError::writer("Background writer thread panicked"))?;
+        }
+        match lock(&self.error).take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail with the first error of the worker, if it stopped
+    fn check(&mut self) -> Result<()> {
+        if lock(&self.error).is_some() {
+            self.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageWriter for BackgroundStorage {
+    fn open(&mut self) -> Result<()> {
+        lock(&self.storage).open()
+    }
+
+    fn close(&mut self, version: u32, metadata: &str) -> Result<()> {
+        self.finish()?;
+        lock(&self.storage).close(version, metadata)
+    }
+
+    fn add_msgtype(&mut self, connection: &Connection) -> Result<()> {
+        self.check()?;
+        lock(&self.storage).add_msgtype(connection)
+    }
+
+    fn add_connection(
+        &mut self,
+        connection: &Connection,
+        offered_qos_profiles: &str,
+    ) -> Result<()> {
+        self.check()?;
+        lock(&self.storage).add_connection(connection, offered_qos_profiles)
+    }
+
+    fn write(&mut self, connection: &Connection, timestamp: u64, data: &[u8]) -> Result<()> {
+        self.send(vec![(connection.clone(), timestamp, data.to_vec())])
+    }
+
+    fn write_batch(&mut self, messages: &[(Connection, u64, Vec<u8>)]) -> Result<()> {
+        self.send(messages.to_vec())
+    }
+
+    fn write_owned_batch(&mut self, messages: Vec<(Connection, u64, Vec<u8>)>) -> Result<()> {
+        self.send(messages)
+    }
+
+    fn add_attachment(&mut self, attachment: &Attachment) -> Result<()> {
+        self.check()?;
+        lock(&self.storage).add_attachment(attachment)
+    }
+
+    fn add_mcap_metadata(&mut self, metadata: &McapMetadata) -> Result<()> {
+        self.check()?;
+        lock(&self.storage).add_mcap_metadata(metadata)
+    }
+
+    fn is_open(&self) -> bool {
+        lock(&self.storage).is_open()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Drop for BackgroundStorage {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Lock `mutex`, ignoring that a panicking thread held it
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Storage accepting `capacity` messages, then failing
+    struct LimitedStorage {
+        written: Arc<Mutex<Vec<u64>>>,
+        capacity: usize,
+    }
+
+    impl StorageWriter for LimitedStorage {
+        fn open(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn close(&mut self, _version: u32, _metadata: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn add_msgtype(&mut self, _connection: &Connection) -> Result<()> {
+            Ok(())
+        }
+
+        fn add_connection(&mut self, _connection: &Connection, _qos: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn write(&mut self, _connection: &Connection, timestamp: u64, _data: &[u8]) -> Result<()> {
+            let mut written = lock(&self.written);
+            if written.len() == self.capacity {
+                return Err(BagError::writer("Storage is full"));
+            }
+            written.push(timestamp);
+            Ok(())
+        }
+
+        fn is_open(&self) -> bool {
+            true
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    fn batch(timestamps: std::ops::Range<u64>) -> Batch {
+        let connection = Connection::new("/count", "std_msgs/msg/UInt32").unwrap();
+        timestamps
+            .map(|timestamp| (connection.clone(), timestamp, Vec::new()))
+            .collect()
+    }
+
+    #[test]
+    fn test_background_storage_reports_worker_errors() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let storage = LimitedStorage {
+            written: Arc::clone(&written),
+            capacity: 100,
+        };
+        let mut background = BackgroundStorage::spawn(Box::new(storage), 1).unwrap();
+        background.write_owned_batch(batch(0..10)).unwrap();
+        background.write_batch(&batch(10..20)).unwrap();
+        background.close(9, "").unwrap();
+        assert_eq!(*lock(&written), (0..20).collect::<Vec<_>>());
+
+        // A failing batch stops the worker; its error is returned once, then writes fail
+        let storage = LimitedStorage {
+            written: Arc::new(Mutex::new(Vec::new())),
+            capacity: 15,
+        };
+        let mut background = BackgroundStorage::spawn(Box::new(storage), 1).unwrap();
+        let error = (0..10)
+            .map(|i| background.write_owned_batch(batch(i * 10..(i + 1) * 10)))
+            .find_map(Result::err)
+            .or_else(|| background.close(9, "").err())
+            .unwrap();
+        assert!(error.to_string().contains("Storage is full"));
+        assert!(background.write_owned_batch(batch(0..1)).is_err());
+    }
+}
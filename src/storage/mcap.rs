@@ -3,35 +3,396 @@
 //! This module provides support for reading ROS2 bag files stored in MCAP format.
 //! MCAP is a modern, efficient container format for multimodal log data.
 
-use crate::error::{ReaderError, Result};
-use crate::storage::StorageReader;
-use crate::types::{Connection, Message, MessageDefinition};
+use crate::definitions::DefinitionResolver;
+use crate::error::{ErrorContext, ReaderError, Result};
+use crate::reader::{ErrorPolicy, ReaderOptions};
+use crate::storage::{sort_messages, StorageInput, StorageReader, TopicFilter};
+use crate::types::{
+    topics_regex, Attachment, BagFile, Connection, McapMetadata, Message, MessageDefinition,
+    MessageDefinitionFormat, RawMessageRef, TopicPattern, OFFERED_QOS_PROFILES_KEY,
+};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[cfg(feature = "mcap")]
 use mcap::MessageStream;
 
+/// Backing buffer for an opened MCAP file.
+///
+/// Files are memory-mapped when [`ReaderOptions::memory_map`] is set and the
+/// `unsafe-opt` feature is enabled; otherwise they are read into memory so the
+/// default build stays free of `unsafe` code.
+#[cfg(feature = "mcap")]
+enum McapBuffer {
+    /// File contents read into memory
+    Loaded(Vec<u8>),
+    /// Contents of an in-memory input
+    Shared(std::sync::Arc<[u8]>),
+    /// Memory-mapped file
+    #[cfg(feature = "unsafe-opt")]
+    Mapped(memmap2::Mmap),
+}
+
+#[cfg(feature = "mcap")]
+impl std::ops::Deref for McapBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Loaded(buffer) => buffer,
+            Self::Shared(buffer) => buffer,
+            #[cfg(feature = "unsafe-opt")]
+            Self::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+/// Name of the metadata record holding the serialized bag metadata, as written by
+/// rosbag2's MCAP storage plugin
+pub const ROSBAG2_METADATA: &str = "rosbag2";
+
+/// Chunk compressions the MCAP reader can decompress
+pub const SUPPORTED_CHUNK_COMPRESSIONS: &[&str] = &["zstd", "lz4"];
+
+/// Convert an error of the `mcap` crate while reading, naming unsupported chunk
+/// compressions instead of passing on the generic message
+#[cfg(feature = "mcap")]
+pub(crate) fn read_error(context: &str, e: mcap::McapError) -> ReaderError {
+    match e {
+        mcap::McapError::UnsupportedCompression(format) => {
+            ReaderError::UnsupportedCompressionFormat {
+                format: format!(
+                    "{format} (MCAP chunk compression; supported: {})",
+                    SUPPORTED_CHUNK_COMPRESSIONS.join(", ")
+                ),
+            }
+        }
+        e => ReaderError::generic(format!("{context}: {e}")),
+    }
+}
+
+/// Whether a message at `timestamp` is in the time range of the iteration
+fn in_range(start: Option<u64>, stop: Option<u64>, timestamp: u64) -> bool {
+    start.map_or(true, |start| timestamp >= start) && stop.map_or(true, |stop| timestamp < stop)
+}
+
+/// Connections of the channels of one file, resolved and checked against the topic
+/// filter of an iteration once per channel rather than once per message
+///
+/// Messages of a channel share its `Arc`, so channels are told apart by address.
+#[cfg(feature = "mcap")]
+struct SelectedChannels<'r, 't, 'a> {
+    topics: TopicFilter<'t>,
+    /// Each channel seen so far, kept so its address is not reused, with its
+    /// connection or `None` if its messages are not read
+    channels:
+        HashMap<*const mcap::Channel<'a>, (Arc<mcap::Channel<'a>>, Option<Cow<'r, Connection>>)>,
+}
+
+#[cfg(feature = "mcap")]
+impl<'r, 't, 'a> SelectedChannels<'r, 't, 'a> {
+    fn new(topics: TopicFilter<'t>) -> Self {
+        Self {
+            topics,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Connection of `channel` if its messages are read, found with `connection`
+    /// when the channel is first seen
+    fn get(
+        &mut self,
+        channel: &Arc<mcap::Channel<'a>>,
+        connection: impl FnOnce(&mcap::Channel) -> Cow<'r, Connection>,
+    ) -> Option<&Cow<'r, Connection>> {
+        let topics = self.topics;
+        let (_, connection) = self
+            .channels
+            .entry(Arc::as_ptr(channel))
+            .or_insert_with(|| {
+                let connection = Some(connection(channel)).filter(|c| topics.passes(c));
+                (Arc::clone(channel), connection)
+            });
+        connection.as_ref()
+    }
+}
+
+/// Message of a decompressed chunk held by a [`ChunkCache`]
+#[cfg(feature = "mcap")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CachedMessage {
+    channel_id: u16,
+    sequence: u32,
+    log_time: u64,
+    publish_time: u64,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "mcap")]
+impl CachedMessage {
+    fn new(channel_id: u16, message: &mcap::Message) -> Self {
+        Self {
+            channel_id,
+            sequence: message.sequence,
+            log_time: message.log_time,
+            publish_time: message.publish_time,
+            data: message.data.to_vec(),
+        }
+    }
+
+    /// The message with its channel from `summary`
+    fn to_message<'a>(&self, summary: &mcap::Summary<'a>) -> Option<mcap::Message<'a>> {
+        Some(mcap::Message {
+            channel: Arc::clone(summary.channels.get(&self.channel_id)?),
+            sequence: self.sequence,
+            log_time: self.log_time,
+            publish_time: self.publish_time,
+            data: Cow::Owned(self.data.clone()),
+        })
+    }
+}
+
+/// Least recently used cache of the messages of decompressed chunks, keyed by file
+/// and chunk index and bounded by the bytes of their payloads
+///
+/// Lets repeated range queries over the same part of a bag, as when scrubbing back
+/// and forth in a viewer, skip decompressing the same chunks again.
+#[cfg(feature = "mcap")]
+#[derive(Debug)]
+struct ChunkCache {
+    /// Maximum bytes of cached payloads
+    capacity: u64,
+    state: std::sync::Mutex<ChunkCacheState>,
+}
+
+#[cfg(feature = "mcap")]
+#[derive(Debug, Default)]
+struct ChunkCacheState {
+    /// Cached chunks by file and chunk index
+    chunks: HashMap<(usize, usize), CachedChunk>,
+    /// Payload bytes of all cached chunks
+    size: u64,
+    /// Counter ordering the uses of chunks
+    clock: u64,
+}
+
+/// Chunk held by a [`ChunkCache`]
+#[cfg(feature = "mcap")]
+#[derive(Debug)]
+struct CachedChunk {
+    messages: Arc<[CachedMessage]>,
+    /// Bytes of the payloads of `messages`
+    size: u64,
+    /// Clock of the cache at the last use
+    used: u64,
+}
+
+#[cfg(feature = "mcap")]
+impl ChunkCache {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            state: Default::default(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ChunkCacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Messages of chunk `chunk` of file `file`, if cached
+    fn get(&self, file: usize, chunk: usize) -> Option<Arc<[CachedMessage]>> {
+        let mut state = self.state();
+        state.clock += 1;
+        let clock = state.clock;
+        let cached = state.chunks.get_mut(&(file, chunk))?;
+        cached.used = clock;
+        Some(Arc::clone(&cached.messages))
+    }
+
+    /// Cache the messages of a chunk, evicting the least recently used chunks to make
+    /// room; chunks larger than the whole cache are not kept
+    fn insert(&self, file: usize, chunk: usize, messages: Arc<[CachedMessage]>) {
+        let size = messages.iter().map(|m| m.data.len() as u64).sum::<u64>();
+        if size > self.capacity {
+            return;
+        }
+        let mut state = self.state();
+        if let Some(replaced) = state.chunks.remove(&(file, chunk)) {
+            state.size -= replaced.size;
+        }
+        while state.size + size > self.capacity {
+            let Some((&oldest, _)) = state.chunks.iter().min_by_key(|(_, cached)| cached.used)
+            else {
+                break;
+            };
+            if let Some(evicted) = state.chunks.remove(&oldest) {
+                state.size -= evicted.size;
+            }
+        }
+        state.clock += 1;
+        let used = state.clock;
+        state.chunks.insert(
+            (file, chunk),
+            CachedChunk {
+                messages,
+                size,
+                used,
+            },
+        );
+        state.size += size;
+    }
+
+    fn clear(&self) {
+        *self.state() = ChunkCacheState::default();
+    }
+}
+
+/// Run `work` on every item on `threads` worker threads, handing the results to
+/// `collect` on the calling thread in the order of `items`
+///
+/// Results that finish early wait in a reordering queue until those before them are
+/// collected. Workers do not start items more than `2 * threads` ahead of the next
+/// result to collect, which bounds the results held in memory at once. A panic in
+/// `work` is resumed on the calling thread.
+#[cfg(feature = "mcap")]
+fn parallel_ordered<I: Sync, R: Send>(
+    items: &[I],
+    threads: usize,
+    work: impl Fn(&I) -> R + Sync,
+    mut collect: impl FnMut(R),
+) {
+    use std::collections::BTreeMap;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Condvar, Mutex};
+
+    /// Progress shared between the workers and the collecting thread
+    struct Queue<R> {
+        /// Next item a worker starts
+        next: usize,
+        /// Next item whose result is collected
+        collected: usize,
+        /// Finished results waiting for the results before them
+        ready: BTreeMap<usize, std::thread::Result<R>>,
+    }
+
+    let window = threads * 2;
+    let queue = Mutex::new(Queue {
+        next: 0,
+        collected: 0,
+        ready: BTreeMap::new(),
+    });
+    // Signals workers that the window moved and the collector that a result arrived
+    let progress = Condvar::new();
+    let lock = || queue.lock().unwrap_or_else(|e| e.into_inner());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(items.len()) {
+            scope.spawn(|| loop {
+                let item = {
+                    let mut state = lock();
+                    while state.next < items.len() && state.next >= state.collected + window {
+                        state = progress.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    if state.next >= items.len() {
+                        return;
+                    }
+                    state.next += 1;
+                    state.next - 1
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| work(&items[item])));
+                lock().ready.insert(item, result);
+                progress.notify_all();
+            });
+        }
+
+        for item in 0..items.len() {
+            let result = {
+                let mut state = lock();
+                loop {
+                    if let Some(result) = state.ready.remove(&item) {
+                        state.collected = item + 1;
+                        break result;
+                    }
+                    state = progress.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+            progress.notify_all();
+            match result {
+                Ok(result) => collect(result),
+                Err(payload) => {
+                    // Let the workers stop so the scope can join them
+                    lock().next = items.len();
+                    progress.notify_all();
+                    panic::resume_unwind(payload)
+                }
+            }
+        }
+    });
+}
+
+/// End of the iteration order looked up by `first_message` and `last_message`
+#[cfg(feature = "mcap")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    First,
+    Last,
+}
+
 /// MCAP storage reader implementation
 pub struct McapStorageReader {
-    /// Paths to MCAP files
+    /// MCAP files, on disk or in memory
+    inputs: Vec<StorageInput>,
+    /// Paths to MCAP files (names of in-memory files)
     mcap_paths: Vec<std::path::PathBuf>,
     /// Topic connections discovered from MCAP files
     topic_connections: Vec<Connection>,
     /// Whether the storage is currently open
     is_open: bool,
-    /// Memory-mapped MCAP files
+    /// Loaded (or memory-mapped) MCAP files
     #[cfg(feature = "mcap")]
-    mapped_files: Vec<memmap2::Mmap>,
+    mapped_files: Vec<McapBuffer>,
     #[cfg(not(feature = "mcap"))]
     mapped_files: Vec<()>, // Placeholder when MCAP feature is disabled
+    /// Whether to memory-map the files (only possible with `unsafe-opt`)
+    #[cfg_attr(not(feature = "unsafe-opt"), allow(dead_code))]
+    memory_map: bool,
+    /// Read files with a summary chunk by chunk, so a corrupt chunk only loses its messages
+    read_by_chunk: bool,
+    /// Threads decompressing chunks; more than one also reads files chunk by chunk
+    #[cfg_attr(not(feature = "mcap"), allow(dead_code))]
+    decode_threads: usize,
+    /// Recently decompressed chunks; a cache also reads files chunk by chunk
+    #[cfg(feature = "mcap")]
+    chunk_cache: Option<ChunkCache>,
 }
 
 impl McapStorageReader {
     /// Create a new MCAP storage reader
     pub fn new(paths: Vec<&Path>, connections: Vec<Connection>) -> Result<Self> {
+        Self::with_options(paths, connections, &ReaderOptions::default())
+    }
+
+    /// Create a new MCAP storage reader with storage access options
+    pub fn with_options(
+        paths: Vec<&Path>,
+        connections: Vec<Connection>,
+        options: &ReaderOptions,
+    ) -> Result<Self> {
+        let inputs = paths.into_iter().map(StorageInput::from).collect();
+        Self::from_inputs(inputs, connections, options)
+    }
+
+    /// Create a new MCAP storage reader over files on disk or in memory
+    #[allow(unused_variables)]
+    pub fn from_inputs(
+        inputs: Vec<StorageInput>,
+        connections: Vec<Connection>,
+        options: &ReaderOptions,
+    ) -> Result<Self> {
         #[cfg(not(feature = "mcap"))]
         {
             return Err(ReaderError::UnsupportedStorageFormat {
@@ -41,14 +402,24 @@ impl McapStorageReader {
 
         #[cfg(feature = "mcap")]
         {
-            let mcap_paths: Vec<std::path::PathBuf> =
-                paths.iter().map(|p| p.to_path_buf()).collect();
+            let mcap_paths: Vec<std::path::PathBuf> = inputs
+                .iter()
+                .map(|input| input.path().to_path_buf())
+                .collect();
 
             Ok(Self {
+                inputs,
                 mcap_paths,
                 topic_connections: connections,
                 is_open: false,
                 mapped_files: Vec::new(),
+                memory_map: options.memory_map,
+                read_by_chunk: options.on_error != ErrorPolicy::Fail
+                    || options.decode_threads() > 1
+                    || options.chunk_cache_size > 0,
+                decode_threads: options.decode_threads(),
+                chunk_cache: (options.chunk_cache_size > 0)
+                    .then(|| ChunkCache::new(options.chunk_cache_size)),
             })
         }
     }
@@ -57,25 +428,37 @@ impl McapStorageReader {
     #[cfg(feature = "mcap")]
     pub fn get_topics_from_mcap(&self) -> Result<Vec<Connection>> {
         let mut all_connections = Vec::new();
-        let mut topic_map: HashMap<String, (String, u64)> = HashMap::new(); // topic_name -> (message_type, count)
+        // (topic_name, message_type) -> (count, metadata of the first channel), ordered
+        // for stable connection ids
+        let mut topic_map: std::collections::BTreeMap<
+            (String, String),
+            (u64, std::collections::BTreeMap<String, String>),
+        > = std::collections::BTreeMap::new();
 
         for mapped_file in &self.mapped_files {
             // Create message stream from mapped file
-            let message_stream = MessageStream::new(mapped_file).map_err(|e| {
-                ReaderError::generic(format!("Failed to create message stream: {e}"))
-            })?;
+            let message_stream = MessageStream::new(mapped_file)
+                .map_err(|e| read_error("Failed to create message stream", e))?;
 
             // Read all messages to count them by topic
             for message_result in message_stream {
                 match message_result {
                     Ok(message) => {
                         let topic_name = &message.channel.topic;
-                        let message_type = &message.channel.message_encoding;
-
-                        let entry = topic_map
-                            .entry(topic_name.clone())
-                            .or_insert((message_type.clone(), 0));
-                        entry.1 += 1;
+                        // The schema names the ROS2 type; the encoding is only "cdr"
+                        let message_type = message
+                            .channel
+                            .schema
+                            .as_ref()
+                            .map_or(&message.channel.message_encoding, |schema| &schema.name);
+
+                        topic_map
+                            .entry((topic_name.clone(), message_type.clone()))
+                            .or_insert_with(|| (0, message.channel.metadata.clone()))
+                            .0 += 1;
+                    }
+                    Err(e @ mcap::McapError::UnsupportedCompression(_)) => {
+                        return Err(read_error("Failed to read MCAP message", e));
                     }
                     Err(e) => {
                         eprintln!("Warning: Failed to read MCAP message: {e}");
@@ -85,7 +468,13 @@ impl McapStorageReader {
         }
 
         // Convert to connections
-        for (idx, (topic_name, (message_type, count))) in topic_map.into_iter().enumerate() {
+        for (idx, ((topic_name, message_type), (count, metadata))) in
+            topic_map.into_iter().enumerate()
+        {
+            let offered_qos_profiles = metadata
+                .get(OFFERED_QOS_PROFILES_KEY)
+                .map(|qos| crate::metadata::QosProfilesField::String(qos.clone()).profiles())
+                .unwrap_or_default();
             let connection = Connection {
                 id: (idx + 1) as u32,
                 topic: topic_name,
@@ -94,7 +483,8 @@ impl McapStorageReader {
                 type_description_hash: String::new(),
                 message_count: count,
                 serialization_format: "cdr".to_string(),
-                offered_qos_profiles: Vec::new(),
+                offered_qos_profiles,
+                metadata,
             };
             all_connections.push(connection);
         }
@@ -102,6 +492,557 @@ impl McapStorageReader {
         Ok(all_connections)
     }
 
+    /// Messages of the chunks listed in the summary of file `file_index`, read one
+    /// chunk at a time
+    ///
+    /// A chunk that cannot be read or decompressed yields a single error and the other
+    /// chunks are still read. Chunks outside the time range and chunks whose message
+    /// index only lists channels for which `read_channel` is false are skipped without
+    /// decompressing them. With more than one decode thread, chunks are decompressed in
+    /// parallel; their messages still come in chunk order. `None` if the file has no
+    /// readable summary or no chunks, e.g. when the recording was interrupted.
+    #[cfg(feature = "mcap")]
+    fn chunked_messages<'a>(
+        &'a self,
+        file_index: usize,
+        start: Option<u64>,
+        stop: Option<u64>,
+        mut read_channel: impl FnMut(&Arc<mcap::Channel<'a>>) -> bool,
+    ) -> Option<Vec<Result<mcap::Message<'a>>>> {
+        let summary = mcap::Summary::read(&self.mapped_files[file_index]).ok()??;
+        if summary.chunk_indexes.is_empty() {
+            return None;
+        }
+
+        let chunks: Vec<(usize, &mcap::records::ChunkIndex)> = summary
+            .chunk_indexes
+            .iter()
+            .enumerate()
+            .filter(|(_, index)| {
+                let reaches_range = start.map_or(true, |start| index.message_end_time >= start)
+                    && stop.map_or(true, |stop| index.message_start_time < stop);
+                let skipped = !index.message_index_offsets.is_empty()
+                    && index.message_index_offsets.keys().all(|id| {
+                        summary
+                            .channels
+                            .get(id)
+                            .is_some_and(|channel| !read_channel(channel))
+                    });
+                reaches_range && !skipped
+            })
+            .collect();
+
+        let read = |(chunk, index): (usize, &mcap::records::ChunkIndex)| {
+            self.chunk_messages(&summary, file_index, chunk, index)
+        };
+        let mut messages = Vec::new();
+        if self.decode_threads > 1 && chunks.len() > 1 {
+            parallel_ordered(
+                &chunks,
+                self.decode_threads,
+                |&chunk| read(chunk),
+                |chunk_messages| messages.extend(chunk_messages),
+            );
+        } else {
+            for chunk in chunks {
+                messages.extend(read(chunk));
+            }
+        }
+        Some(messages)
+    }
+
+    /// Messages of chunk number `chunk` of file `file_index`, ending with an error if
+    /// it cannot be read
+    ///
+    /// Compressed chunks are taken from and added to the chunk cache, if any.
+    #[cfg(feature = "mcap")]
+    fn chunk_messages<'a>(
+        &'a self,
+        summary: &mcap::Summary<'a>,
+        file_index: usize,
+        chunk: usize,
+        index: &mcap::records::ChunkIndex,
+    ) -> Vec<Result<mcap::Message<'a>>> {
+        let path = &self.mcap_paths[file_index];
+        let error = |e| {
+            read_error("Failed to read MCAP chunk", e)
+                .with_context(ErrorContext::chunk(path, chunk))
+        };
+        let cache = self
+            .chunk_cache
+            .as_ref()
+            .filter(|_| !index.compression.is_empty());
+        if let Some(cached) = cache.and_then(|cache| cache.get(file_index, chunk)) {
+            let messages: Option<Vec<_>> = cached
+                .iter()
+                .map(|message| message.to_message(summary).map(Ok))
+                .collect();
+            if let Some(messages) = messages {
+                return messages;
+            }
+        }
+
+        let mut messages = Vec::new();
+        match summary.stream_chunk(&self.mapped_files[file_index], index) {
+            Ok(chunk_messages) => {
+                for message in chunk_messages {
+                    match message {
+                        Ok(message) => messages.push(Ok(message)),
+                        Err(e) => {
+                            messages.push(Err(error(e)));
+                            return messages;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                messages.push(Err(error(e)));
+                return messages;
+            }
+        }
+        if let Some(cache) = cache {
+            // Channels are not stored with their id, so look them up by address
+            let ids: HashMap<_, _> = summary
+                .channels
+                .iter()
+                .map(|(&id, channel)| (Arc::as_ptr(channel), id))
+                .collect();
+            let cached: Option<Arc<[_]>> = messages
+                .iter()
+                .flatten()
+                .map(|message| {
+                    let id = *ids.get(&Arc::as_ptr(&message.channel))?;
+                    Some(CachedMessage::new(id, message))
+                })
+                .collect();
+            if let Some(cached) = cached {
+                cache.insert(file_index, chunk, cached);
+            }
+        }
+        messages
+    }
+
+    /// Messages of one file, read linearly or, with `read_by_chunk`, chunk by chunk
+    /// skipping chunks outside the time range or without channels passing
+    /// `read_channel`
+    #[cfg(feature = "mcap")]
+    fn file_messages<'a>(
+        &'a self,
+        file_index: usize,
+        start: Option<u64>,
+        stop: Option<u64>,
+        read_channel: impl FnMut(&Arc<mcap::Channel<'a>>) -> bool,
+    ) -> Result<Box<dyn Iterator<Item = Result<mcap::Message<'a>>> + 'a>> {
+        let path = self.mcap_paths[file_index].clone();
+        if self.read_by_chunk {
+            if let Some(messages) = self.chunked_messages(file_index, start, stop, read_channel) {
+                return Ok(Box::new(messages.into_iter()));
+            }
+        }
+        let message_stream = MessageStream::new(&self.mapped_files[file_index])
+            .map_err(|e| read_error("Failed to create message stream", e))?;
+        Ok(Box::new(message_stream.enumerate().map(
+            move |(record, message)| {
+                message.map_err(|e| {
+                    read_error("Failed to read MCAP message", e)
+                        .with_context(ErrorContext::record(&path, record))
+                })
+            },
+        )))
+    }
+
+    /// Read the messages of `topics` from all files, in iteration order
+    fn read_messages(
+        &self,
+        topics: TopicFilter<'_>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        #[cfg(not(feature = "mcap"))]
+        {
+            return Err(ReaderError::UnsupportedStorageFormat {
+                format: "MCAP support not enabled".to_string(),
+            });
+        }
+
+        #[cfg(feature = "mcap")]
+        {
+            // Create a vector to collect all messages from all MCAP files
+            let mut all_messages = Vec::new();
+
+            for file_index in 0..self.mapped_files.len() {
+                let mut selected = SelectedChannels::new(topics);
+                let messages = self.file_messages(file_index, start, stop, |channel| {
+                    selected
+                        .get(channel, |channel| self.channel_connection(channel))
+                        .is_some()
+                })?;
+                for message_result in messages {
+                    let message = match message_result {
+                        Ok(message) => message,
+                        Err(e) => {
+                            all_messages.push(Err(e));
+                            continue;
+                        }
+                    };
+                    let channel = &message.channel;
+                    let Some(connection) =
+                        selected.get(channel, |channel| self.channel_connection(channel))
+                    else {
+                        continue;
+                    };
+                    if !in_range(start, stop, message.log_time) {
+                        continue;
+                    }
+
+                    all_messages.push(Ok(Message {
+                        connection: connection.clone().into_owned(),
+                        topic: channel.topic.clone(),
+                        timestamp: message.log_time,
+                        publish_time: message.publish_time,
+                        sequence: message.sequence,
+                        data: message.data.into_owned(),
+                        file_index,
+                    }));
+                }
+            }
+
+            sort_messages(&mut all_messages, |msg| (msg.timestamp, msg.connection.id));
+
+            Ok(Box::new(all_messages.into_iter()))
+        }
+    }
+
+    /// Read the raw messages of `topics` from all files, in iteration order, borrowing
+    /// payloads of uncompressed chunks from the file buffers
+    fn read_raw_messages(
+        &self,
+        topics: TopicFilter<'_>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        #[cfg(not(feature = "mcap"))]
+        {
+            return Err(ReaderError::UnsupportedStorageFormat {
+                format: "MCAP support not enabled".to_string(),
+            });
+        }
+
+        #[cfg(feature = "mcap")]
+        {
+            // Collect views of all messages; payloads of uncompressed chunks stay in the file
+            // buffer. `MessageStream` copies every payload, so records are read directly.
+            let mut all_messages = Vec::new();
+
+            for (file_index, mapped_file) in self.mapped_files.iter().enumerate() {
+                if self.read_by_chunk {
+                    let mut selected = SelectedChannels::new(topics);
+                    let messages = self.chunked_messages(file_index, start, stop, |channel| {
+                        selected
+                            .get(channel, |channel| self.channel_connection(channel))
+                            .is_some()
+                    });
+                    if let Some(messages) = messages {
+                        for message_result in messages {
+                            let message = match message_result {
+                                Ok(message) => message,
+                                Err(e) => {
+                                    all_messages.push(Err(e));
+                                    continue;
+                                }
+                            };
+                            let channel = &message.channel;
+                            let Some(connection) =
+                                selected.get(channel, |channel| self.channel_connection(channel))
+                            else {
+                                continue;
+                            };
+                            if !in_range(start, stop, message.log_time) {
+                                continue;
+                            }
+                            all_messages.push(Ok(RawMessageRef {
+                                connection: connection.clone(),
+                                timestamp: message.log_time,
+                                publish_time: message.publish_time,
+                                sequence: message.sequence,
+                                data: message.data,
+                                file_index,
+                            }));
+                        }
+                        continue;
+                    }
+                }
+
+                let records = mcap::read::ChunkFlattener::new(mapped_file)
+                    .map_err(|e| read_error("Failed to create message stream", e))?;
+                // Schema names by schema id and connections by channel id, `None` for
+                // channels whose messages are not read
+                let mut schemas: HashMap<u16, String> = HashMap::new();
+                let mut channels: HashMap<u16, Option<Cow<'_, Connection>>> = HashMap::new();
+                let context = |record| ErrorContext::record(&self.mcap_paths[file_index], record);
+                let mut next_record = 0;
+
+                for record_result in records {
+                    let (header, data) = match record_result {
+                        Ok(mcap::records::Record::Schema { header, .. }) => {
+                            schemas.insert(header.id, header.name);
+                            continue;
+                        }
+                        Ok(mcap::records::Record::Channel(channel)) => {
+                            let connection = self.connection_for(
+                                &channel.topic,
+                                schemas.get(&channel.schema_id).map(String::as_str),
+                                &channel.message_encoding,
+                            );
+                            let selected = Some(connection).filter(|c| topics.passes(c));
+                            channels.insert(channel.id, selected);
+                            continue;
+                        }
+                        Ok(mcap::records::Record::Message { header, data }) => (header, data),
+                        // Only the summary, which holds no messages, follows the data section
+                        Ok(mcap::records::Record::DataEnd(_)) => break,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            all_messages.push(Err(read_error("Failed to read MCAP message", e)
+                                .with_context(context(next_record))));
+                            break;
+                        }
+                    };
+                    let record = next_record;
+                    next_record += 1;
+                    let Some(connection) = channels.get(&header.channel_id) else {
+                        all_messages.push(Err(ReaderError::generic(format!(
+                            "Failed to read MCAP message: unknown channel {}",
+                            header.channel_id
+                        ))
+                        .with_context(context(record))));
+                        continue;
+                    };
+
+                    let Some(connection) = connection else {
+                        continue;
+                    };
+                    let timestamp = header.log_time;
+                    if !in_range(start, stop, timestamp) {
+                        continue;
+                    }
+
+                    all_messages.push(Ok(RawMessageRef {
+                        connection: connection.clone(),
+                        timestamp,
+                        publish_time: header.publish_time,
+                        sequence: header.sequence,
+                        data,
+                        file_index,
+                    }));
+                }
+            }
+
+            sort_messages(&mut all_messages, |msg| (msg.timestamp, msg.connection.id));
+
+            Ok(Box::new(all_messages.into_iter()))
+        }
+    }
+
+    /// Read the first or last message of `connections` in iteration order
+    ///
+    /// Files with a chunk index only decompress the chunks that can hold the message:
+    /// those with a selected channel whose time span reaches the iteration range,
+    /// nearest the edge first, until a chunk starts (or ends) past the message found.
+    /// Files without one are read fully.
+    #[cfg(feature = "mcap")]
+    fn edge_message(
+        &self,
+        connections: &[Connection],
+        start: Option<u64>,
+        stop: Option<u64>,
+        edge: Edge,
+    ) -> Result<Option<Message>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
+        if connections.is_empty() {
+            return Ok(None);
+        }
+
+        // Messages are told apart by their iteration order: timestamp, connection id,
+        // then file, chunk and position within the chunk
+        type Key = (u64, u32, usize, usize, usize);
+        let mut found: Option<(Key, Message)> = None;
+        let consider = |found: &mut Option<(Key, Message)>,
+                        key: Key,
+                        message: &mcap::Message,
+                        connection: &Connection| {
+            let closer = found.as_ref().map_or(true, |(best, _)| match edge {
+                Edge::First => key < *best,
+                Edge::Last => key > *best,
+            });
+            if closer {
+                let message = Message {
+                    connection: connection.clone(),
+                    topic: message.channel.topic.clone(),
+                    timestamp: message.log_time,
+                    publish_time: message.publish_time,
+                    sequence: message.sequence,
+                    data: message.data.clone().into_owned(),
+                    file_index: key.2,
+                };
+                *found = Some((key, message));
+            }
+        };
+
+        let topics = TopicFilter::Connections(Some(connections));
+        for (file_index, mapped_file) in self.mapped_files.iter().enumerate() {
+            let mut selected = SelectedChannels::new(topics);
+            let summary = mcap::Summary::read(mapped_file)
+                .ok()
+                .flatten()
+                .filter(|summary| !summary.chunk_indexes.is_empty());
+            let Some(summary) = summary else {
+                let messages = self.file_messages(file_index, None, None, |_| true)?;
+                for (position, message) in messages.enumerate() {
+                    let message = message?;
+                    let Some(connection) =
+                        selected.get(&message.channel, |channel| self.channel_connection(channel))
+                    else {
+                        continue;
+                    };
+                    if in_range(start, stop, message.log_time) {
+                        let key = (message.log_time, connection.id, file_index, 0, position);
+                        consider(&mut found, key, &message, connection);
+                    }
+                }
+                continue;
+            };
+
+            let mut chunks: Vec<(usize, &mcap::records::ChunkIndex)> = summary
+                .chunk_indexes
+                .iter()
+                .enumerate()
+                .filter(|(_, index)| {
+                    let reaches_range = start.map_or(true, |start| index.message_end_time >= start)
+                        && stop.map_or(true, |stop| index.message_start_time < stop);
+                    let skipped = !index.message_index_offsets.is_empty()
+                        && index.message_index_offsets.keys().all(|id| {
+                            summary.channels.get(id).is_some_and(|channel| {
+                                selected
+                                    .get(channel, |channel| self.channel_connection(channel))
+                                    .is_none()
+                            })
+                        });
+                    reaches_range && !skipped
+                })
+                .collect();
+            match edge {
+                Edge::First => chunks.sort_by_key(|(_, index)| index.message_start_time),
+                Edge::Last => {
+                    chunks.sort_by_key(|(_, index)| std::cmp::Reverse(index.message_end_time))
+                }
+            }
+
+            for (chunk, index) in chunks {
+                if let Some(((timestamp, ..), _)) = &found {
+                    let past = match edge {
+                        Edge::First => index.message_start_time > *timestamp,
+                        Edge::Last => index.message_end_time < *timestamp,
+                    };
+                    if past {
+                        break;
+                    }
+                }
+                let messages = self.chunk_messages(&summary, file_index, chunk, index);
+                for (position, message) in messages.into_iter().enumerate() {
+                    let message = message?;
+                    let Some(connection) =
+                        selected.get(&message.channel, |channel| self.channel_connection(channel))
+                    else {
+                        continue;
+                    };
+                    if in_range(start, stop, message.log_time) {
+                        let key = (message.log_time, connection.id, file_index, chunk, position);
+                        consider(&mut found, key, &message, connection);
+                    }
+                }
+            }
+        }
+        Ok(found.map(|(_, message)| message))
+    }
+
+    /// Connection of a channel on `topic` with the schema `message_type`, or a
+    /// temporary one for topics missing from the metadata
+    ///
+    /// A channel without a schema takes the first connection of its topic.
+    fn connection_for(
+        &self,
+        topic: &str,
+        message_type: Option<&str>,
+        message_encoding: &str,
+    ) -> Cow<'_, Connection> {
+        let mut of_topic = self.topic_connections.iter().filter(|c| c.topic == topic);
+        let connection = match message_type {
+            Some(message_type) => of_topic.find(|c| c.message_type == message_type),
+            None => of_topic.next(),
+        };
+        match connection {
+            Some(connection) => Cow::Borrowed(connection),
+            None => Cow::Owned(Connection {
+                id: 1, // Use a default ID since MCAP doesn't have connection IDs
+                topic: topic.to_string(),
+                message_type: message_type.unwrap_or(message_encoding).to_string(),
+                message_definition: MessageDefinition::default(),
+                type_description_hash: String::new(),
+                message_count: 0,
+                serialization_format: "cdr".to_string(),
+                offered_qos_profiles: Vec::new(),
+                metadata: Default::default(),
+            }),
+        }
+    }
+
+    /// Connection of the channel of a message
+    #[cfg(feature = "mcap")]
+    fn channel_connection(&self, channel: &mcap::Channel) -> Cow<'_, Connection> {
+        let message_type = channel.schema.as_ref().map(|schema| schema.name.as_str());
+        self.connection_for(&channel.topic, message_type, &channel.message_encoding)
+    }
+
+    /// Visit the records of a file without a usable summary, up to the first malformed
+    /// record, as interrupted recordings end mid-record
+    #[cfg(feature = "mcap")]
+    fn scan_records<'a>(buffer: &'a [u8], mut visit: impl FnMut(mcap::records::Record<'a>)) {
+        let Ok(records) = mcap::read::LinearReader::new_with_options(
+            buffer,
+            mcap::read::Options::IgnoreEndMagic.into(),
+        ) else {
+            return;
+        };
+        for record in records {
+            match record {
+                Ok(record) => visit(record),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Memory-map an MCAP file if requested (fast path, requires the `unsafe-opt`
+    /// feature), or read it fully into memory
+    #[cfg(feature = "mcap")]
+    fn load_file(&self, file: &File) -> std::io::Result<McapBuffer> {
+        #[cfg(feature = "unsafe-opt")]
+        if self.memory_map {
+            // SAFETY: the mapping is read-only and the bag is not expected to be
+            // modified while it is open for reading.
+            return unsafe { memmap2::Mmap::map(file) }.map(McapBuffer::Mapped);
+        }
+
+        use std::io::Read;
+
+        let mut buffer = Vec::new();
+        let mut reader = file;
+        reader.read_to_end(&mut buffer)?;
+        Ok(McapBuffer::Loaded(buffer))
+    }
+
     #[cfg(not(feature = "mcap"))]
     pub fn get_topics_from_mcap(&self) -> Result<Vec<Connection>> {
         Err(ReaderError::UnsupportedStorageFormat {
@@ -122,8 +1063,18 @@ impl StorageReader for McapStorageReader {
         #[cfg(feature = "mcap")]
         {
             self.mapped_files.clear();
+            if let Some(cache) = &self.chunk_cache {
+                cache.clear();
+            }
 
-            for path in &self.mcap_paths {
+            for input in &self.inputs {
+                let path = match input {
+                    StorageInput::Path(path) => path,
+                    StorageInput::Memory { data, .. } => {
+                        self.mapped_files.push(McapBuffer::Shared(data.clone()));
+                        continue;
+                    }
+                };
                 let file = File::open(path).map_err(|e| {
                     ReaderError::generic(format!(
                         "Failed to open MCAP file {}: {}",
@@ -132,9 +1083,9 @@ impl StorageReader for McapStorageReader {
                     ))
                 })?;
 
-                let mapped_file = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| {
+                let mapped_file = self.load_file(&file).map_err(|e| {
                     ReaderError::generic(format!(
-                        "Failed to memory-map MCAP file {}: {}",
+                        "Failed to load MCAP file {}: {}",
                         path.display(),
                         e
                     ))
@@ -143,6 +1094,11 @@ impl StorageReader for McapStorageReader {
                 self.mapped_files.push(mapped_file);
             }
 
+            // Without metadata topics, map messages to the channels of the files
+            if self.topic_connections.is_empty() {
+                self.topic_connections = self.get_topics_from_mcap()?;
+            }
+
             self.is_open = true;
             Ok(())
         }
@@ -150,14 +1106,42 @@ impl StorageReader for McapStorageReader {
 
     fn close(&mut self) -> Result<()> {
         self.mapped_files.clear();
+        #[cfg(feature = "mcap")]
+        if let Some(cache) = &self.chunk_cache {
+            cache.clear();
+        }
         self.is_open = false;
         Ok(())
     }
 
     fn get_definitions(&self) -> Result<HashMap<String, MessageDefinition>> {
-        // MCAP stores schema information differently than SQLite
-        // For now, return empty definitions - this can be enhanced later
-        Ok(HashMap::new())
+        let mut definitions = HashMap::new();
+
+        #[cfg(feature = "mcap")]
+        for mapped_file in &self.mapped_files {
+            let summary = mcap::Summary::read(mapped_file)
+                .map_err(|e| read_error("Failed to read MCAP summary", e))?;
+            let Some(summary) = summary else {
+                continue;
+            };
+
+            for schema in summary.schemas.values() {
+                let format = match schema.encoding.as_str() {
+                    "ros2msg" => MessageDefinitionFormat::Msg,
+                    "ros2idl" => MessageDefinitionFormat::Idl,
+                    _ => MessageDefinitionFormat::None,
+                };
+                definitions.insert(
+                    schema.name.clone(),
+                    MessageDefinition {
+                        format,
+                        data: String::from_utf8_lossy(&schema.data).into_owned(),
+                    },
+                );
+            }
+        }
+
+        Ok(definitions)
     }
 
     fn messages_filtered(
@@ -166,96 +1150,37 @@ impl StorageReader for McapStorageReader {
         start: Option<u64>,
         stop: Option<u64>,
     ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
-        #[cfg(not(feature = "mcap"))]
-        {
-            return Err(ReaderError::UnsupportedStorageFormat {
-                format: "MCAP support not enabled".to_string(),
-            });
-        }
-
-        #[cfg(feature = "mcap")]
-        {
-            // Create a vector to collect all messages from all MCAP files
-            let mut all_messages = Vec::new();
-
-            for mapped_file in &self.mapped_files {
-                // Create message stream from mapped file
-                let message_stream = MessageStream::new(mapped_file).map_err(|e| {
-                    ReaderError::generic(format!("Failed to create message stream: {e}"))
-                })?;
-
-                for message_result in message_stream {
-                    match message_result {
-                        Ok(message) => {
-                            // Check if this message matches the requested connections
-                            if let Some(conns) = connections {
-                                let topic_matches =
-                                    conns.iter().any(|c| c.topic == message.channel.topic);
-                                if !topic_matches {
-                                    continue;
-                                }
-                            }
-
-                            // Check time bounds
-                            let timestamp = message.log_time;
-                            if let Some(start_time) = start {
-                                if timestamp < start_time {
-                                    continue;
-                                }
-                            }
-                            if let Some(stop_time) = stop {
-                                if timestamp > stop_time {
-                                    continue;
-                                }
-                            }
-
-                            // Find or create a connection for this topic
-                            let connection = if let Some(conn) = self
-                                .topic_connections
-                                .iter()
-                                .find(|c| c.topic == message.channel.topic)
-                            {
-                                conn.clone()
-                            } else {
-                                // Create a temporary connection
-                                Connection {
-                                    id: 1, // Use a default ID since MCAP doesn't have connection IDs
-                                    topic: message.channel.topic.clone(),
-                                    message_type: message.channel.message_encoding.clone(),
-                                    message_definition: MessageDefinition::default(),
-                                    type_description_hash: String::new(),
-                                    message_count: 0,
-                                    serialization_format: "cdr".to_string(),
-                                    offered_qos_profiles: Vec::new(),
-                                }
-                            };
-
-                            let msg = Message {
-                                connection,
-                                topic: message.channel.topic.clone(),
-                                timestamp,
-                                data: message.data.to_vec(),
-                            };
+        self.read_messages(TopicFilter::Connections(connections), start, stop)
+    }
 
-                            all_messages.push(Ok(msg));
-                        }
-                        Err(e) => {
-                            all_messages.push(Err(ReaderError::generic(format!(
-                                "Failed to read MCAP message: {e}"
-                            ))));
-                        }
-                    }
-                }
-            }
+    fn messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        let regex = topics_regex(patterns)?;
+        self.read_messages(TopicFilter::Regex(&regex), start, stop)
+    }
 
-            // Sort messages by timestamp
-            all_messages.sort_by(|a, b| match (a, b) {
-                (Ok(msg_a), Ok(msg_b)) => msg_a.timestamp.cmp(&msg_b.timestamp),
-                _ => std::cmp::Ordering::Equal,
-            });
+    #[cfg(feature = "mcap")]
+    fn first_message(
+        &self,
+        connections: &[Connection],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Option<Message>> {
+        self.edge_message(connections, start, stop, Edge::First)
+    }
 
-            Ok(Box::new(all_messages.into_iter()))
-        }
+    #[cfg(feature = "mcap")]
+    fn last_message(
+        &self,
+        connections: &[Connection],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Option<Message>> {
+        self.edge_message(connections, start, stop, Edge::Last)
     }
 
     fn is_open(&self) -> bool {
@@ -274,96 +1199,177 @@ impl StorageReader for McapStorageReader {
         start: Option<u64>,
         stop: Option<u64>,
     ) -> Result<Box<dyn Iterator<Item = Result<crate::types::RawMessage>> + '_>> {
-        #[cfg(not(feature = "mcap"))]
-        {
-            return Err(ReaderError::UnsupportedStorageFormat {
-                format: "MCAP support not enabled".to_string(),
-            });
+        let messages = self.raw_messages_borrowed(connections, start, stop)?;
+        Ok(Box::new(
+            messages.map(|message| message.map(RawMessageRef::into_owned)),
+        ))
+    }
+
+    fn raw_messages_borrowed(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        self.read_raw_messages(TopicFilter::Connections(connections), start, stop)
+    }
+
+    fn raw_messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<crate::types::RawMessage>> + '_>> {
+        let regex = topics_regex(patterns)?;
+        let messages = self.read_raw_messages(TopicFilter::Regex(&regex), start, stop)?;
+        Ok(Box::new(
+            messages.map(|message| message.map(RawMessageRef::into_owned)),
+        ))
+    }
+
+    fn files(&self) -> Result<Vec<BagFile>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
         }
 
+        // (message_count, start_time, end_time) per file, from the summary statistics
+        let mut spans: Vec<Option<(u64, u64, u64)>> = vec![None; self.mapped_files.len()];
         #[cfg(feature = "mcap")]
-        {
-            // Create a vector to collect all raw messages from all MCAP files
-            let mut all_messages = Vec::new();
-
-            for mapped_file in &self.mapped_files {
-                // Create message stream from mapped file
-                let message_stream = MessageStream::new(mapped_file).map_err(|e| {
-                    ReaderError::generic(format!("Failed to create message stream: {e}"))
-                })?;
+        for (span, mapped_file) in spans.iter_mut().zip(&self.mapped_files) {
+            let summary = mcap::Summary::read(mapped_file)
+                .map_err(|e| read_error("Failed to read MCAP summary", e))?;
+            *span = summary.and_then(|summary| summary.stats).map(|stats| {
+                if stats.message_count == 0 {
+                    (0, 0, 0)
+                } else {
+                    (
+                        stats.message_count,
+                        stats.message_start_time,
+                        stats.message_end_time,
+                    )
+                }
+            });
+        }
 
-                for message_result in message_stream {
-                    match message_result {
-                        Ok(message) => {
-                            // Check if this message matches the requested connections
-                            if let Some(conns) = connections {
-                                let topic_matches =
-                                    conns.iter().any(|c| c.topic == message.channel.topic);
-                                if !topic_matches {
-                                    continue;
-                                }
-                            }
+        // Files written without statistics are scanned
+        if spans.iter().any(Option::is_none) {
+            let mut scanned = vec![(0u64, u64::MAX, 0u64); spans.len()];
+            for message in self.raw_messages_borrowed(None, None, None)? {
+                let message = message?;
+                let (count, first, last) = &mut scanned[message.file_index];
+                *count += 1;
+                *first = (*first).min(message.timestamp);
+                *last = (*last).max(message.timestamp);
+            }
+            for (span, (count, first, last)) in spans.iter_mut().zip(scanned) {
+                if span.is_none() {
+                    *span = Some(if count == 0 {
+                        (0, 0, 0)
+                    } else {
+                        (count, first, last)
+                    });
+                }
+            }
+        }
 
-                            // Check time bounds
-                            let timestamp = message.log_time;
-                            if let Some(start_time) = start {
-                                if timestamp < start_time {
-                                    continue;
-                                }
-                            }
-                            if let Some(stop_time) = stop {
-                                if timestamp > stop_time {
-                                    continue;
-                                }
-                            }
+        Ok(spans
+            .into_iter()
+            .zip(&self.mcap_paths)
+            .enumerate()
+            .map(|(index, (span, path))| {
+                let (message_count, start_time, end_time) = span.unwrap_or_default();
+                BagFile {
+                    index,
+                    path: path.clone(),
+                    start_time,
+                    end_time,
+                    message_count,
+                }
+            })
+            .collect())
+    }
 
-                            // Find or create a connection for this topic
-                            let connection = if let Some(conn) = self
-                                .topic_connections
-                                .iter()
-                                .find(|c| c.topic == message.channel.topic)
-                            {
-                                conn.clone()
-                            } else {
-                                // Create a temporary connection
-                                Connection {
-                                    id: 1, // Use a default ID since MCAP doesn't have connection IDs
-                                    topic: message.channel.topic.clone(),
-                                    message_type: message.channel.message_encoding.clone(),
-                                    message_definition: MessageDefinition::default(),
-                                    type_description_hash: String::new(),
-                                    message_count: 0,
-                                    serialization_format: "cdr".to_string(),
-                                    offered_qos_profiles: Vec::new(),
-                                }
-                            };
+    fn file_paths(&self) -> &[PathBuf] {
+        &self.mcap_paths
+    }
 
-                            // Create RawMessage with minimal processing (no CDR parsing)
-                            let raw_msg = crate::types::RawMessage {
-                                connection,
-                                timestamp,
-                                raw_data: message.data.to_vec(),
-                            };
+    fn attachments(&self) -> Result<Vec<Attachment>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
 
-                            all_messages.push(Ok(raw_msg));
-                        }
-                        Err(e) => {
-                            all_messages.push(Err(ReaderError::generic(format!(
-                                "Failed to read MCAP message: {e}"
-                            ))));
-                        }
+        let mut attachments = Vec::new();
+        #[cfg(feature = "mcap")]
+        for (file_index, mapped_file) in self.mapped_files.iter().enumerate() {
+            let attachment = |a: mcap::Attachment<'_>| Attachment {
+                name: a.name,
+                media_type: a.media_type,
+                log_time: a.log_time,
+                create_time: a.create_time,
+                data: a.data.into_owned(),
+                file_index,
+            };
+            match mcap::Summary::read(mapped_file).ok().flatten() {
+                Some(summary) => {
+                    for index in &summary.attachment_indexes {
+                        let a = mcap::read::attachment(mapped_file, index).map_err(|e| {
+                            read_error("Failed to read MCAP attachment", e)
+                                .with_context(ErrorContext::file(&self.mcap_paths[file_index]))
+                        })?;
+                        attachments.push(attachment(a));
                     }
                 }
+                None => Self::scan_records(mapped_file, |record| {
+                    if let mcap::records::Record::Attachment { header, data } = record {
+                        attachments.push(attachment(mcap::Attachment {
+                            log_time: header.log_time,
+                            create_time: header.create_time,
+                            name: header.name,
+                            media_type: header.media_type,
+                            data,
+                        }));
+                    }
+                }),
             }
+        }
+        Ok(attachments)
+    }
 
-            // Sort messages by timestamp
-            all_messages.sort_by(|a, b| match (a, b) {
-                (Ok(msg_a), Ok(msg_b)) => msg_a.timestamp.cmp(&msg_b.timestamp),
-                _ => std::cmp::Ordering::Equal,
-            });
+    fn mcap_metadata(&self) -> Result<Vec<McapMetadata>> {
+        if !self.is_open {
+            return Err(ReaderError::BagNotOpen);
+        }
 
-            Ok(Box::new(all_messages.into_iter()))
+        let mut records = Vec::new();
+        #[cfg(feature = "mcap")]
+        for (file_index, mapped_file) in self.mapped_files.iter().enumerate() {
+            let mut add = |record: mcap::records::Metadata| {
+                if record.name != ROSBAG2_METADATA {
+                    records.push(McapMetadata {
+                        name: record.name,
+                        metadata: record.metadata,
+                        file_index,
+                    });
+                }
+            };
+            match mcap::Summary::read(mapped_file).ok().flatten() {
+                Some(summary) => {
+                    for index in &summary.metadata_indexes {
+                        let record = mcap::read::metadata(mapped_file, index).map_err(|e| {
+                            read_error("Failed to read MCAP metadata", e)
+                                .with_context(ErrorContext::file(&self.mcap_paths[file_index]))
+                        })?;
+                        add(record);
+                    }
+                }
+                None => Self::scan_records(mapped_file, |record| {
+                    if let mcap::records::Record::Metadata(record) = record {
+                        add(record);
+                    }
+                }),
+            }
         }
+        Ok(records)
     }
 
     fn read_raw_messages_batch(
@@ -372,98 +1378,40 @@ impl StorageReader for McapStorageReader {
         start: Option<u64>,
         stop: Option<u64>,
     ) -> Result<Vec<crate::types::RawMessage>> {
-        #[cfg(not(feature = "mcap"))]
-        {
-            return Err(ReaderError::UnsupportedStorageFormat {
-                format: "MCAP support not enabled".to_string(),
-            });
-        }
-
-        #[cfg(feature = "mcap")]
-        {
-            // Create a vector to collect all raw messages from all MCAP files
-            let mut all_messages = Vec::new();
-
-            for mapped_file in &self.mapped_files {
-                // Create message stream from mapped file
-                let message_stream = MessageStream::new(mapped_file).map_err(|e| {
-                    ReaderError::generic(format!("Failed to create message stream: {e}"))
-                })?;
-
-                for message_result in message_stream {
-                    match message_result {
-                        Ok(message) => {
-                            // Check if this message matches the requested connections
-                            if let Some(conns) = connections {
-                                let topic_matches =
-                                    conns.iter().any(|c| c.topic == message.channel.topic);
-                                if !topic_matches {
-                                    continue;
-                                }
-                            }
-
-                            // Check time bounds
-                            let timestamp = message.log_time;
-                            if let Some(start_time) = start {
-                                if timestamp < start_time {
-                                    continue;
-                                }
-                            }
-                            if let Some(stop_time) = stop {
-                                if timestamp > stop_time {
-                                    continue;
-                                }
-                            }
-
-                            // Find or create a connection for this topic
-                            let connection = if let Some(conn) = self
-                                .topic_connections
-                                .iter()
-                                .find(|c| c.topic == message.channel.topic)
-                            {
-                                conn.clone()
-                            } else {
-                                // Create a temporary connection
-                                Connection {
-                                    id: 1, // Use a default ID since MCAP doesn't have connection IDs
-                                    topic: message.channel.topic.clone(),
-                                    message_type: message.channel.message_encoding.clone(),
-                                    message_definition: MessageDefinition::default(),
-                                    type_description_hash: String::new(),
-                                    message_count: 0,
-                                    serialization_format: "cdr".to_string(),
-                                    offered_qos_profiles: Vec::new(),
-                                }
-                            };
-
-                            // Create RawMessage with minimal processing (no CDR parsing)
-                            let raw_msg = crate::types::RawMessage {
-                                connection,
-                                timestamp,
-                                raw_data: message.data.to_vec(),
-                            };
+        self.raw_messages_borrowed(connections, start, stop)?
+            .map(|message| message.map(RawMessageRef::into_owned))
+            .collect()
+    }
 
-                            all_messages.push(raw_msg);
-                        }
-                        Err(e) => {
-                            return Err(ReaderError::generic(format!(
-                                "Failed to read MCAP message: {e}"
-                            )));
-                        }
-                    }
-                }
-            }
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
 
-            // Sort messages by timestamp
-            all_messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+/// Tuning of how [`McapWriter`] lays out the file
+#[cfg(feature = "mcap")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McapWriteOptions {
+    /// Target uncompressed size of each chunk in bytes
+    ///
+    /// Larger chunks compress better with storage compression; smaller chunks let
+    /// readers seek to a time more precisely.
+    pub chunk_size: u64,
+}
 
-            Ok(all_messages)
+#[cfg(feature = "mcap")]
+impl Default for McapWriteOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
         }
     }
+}
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
+#[cfg(feature = "mcap")]
+impl McapWriteOptions {
+    /// Default [`McapWriteOptions::chunk_size`] (768 KiB, as used by the `mcap` crate)
+    pub const DEFAULT_CHUNK_SIZE: u64 = 768 * 1024;
 }
 
 /// MCAP storage writer implementation
@@ -471,28 +1419,39 @@ impl StorageReader for McapStorageReader {
 pub struct McapWriter {
     /// Path to the MCAP file
     mcap_path: PathBuf,
-    /// File writer
-    writer: Option<std::fs::File>,
-    /// Whether compression is enabled (reserved for future use)
-    _compression_mode: crate::types::CompressionMode,
-    /// Whether the writer is currently open
-    is_open: bool,
-    /// Schema definitions written
-    schemas: Vec<Schema>,
-    /// Channel definitions written
-    channels: Vec<Channel>,
-    /// Next schema ID
-    next_schema_id: u64,
-    /// Next channel ID
-    next_channel_id: u64,
-    /// Channel ID mapping: topic -> MCAP channel_id
-    channel_id_map: HashMap<String, u64>,
+    /// MCAP writer, present while open
+    writer: Option<mcap::Writer<'static, std::io::BufWriter<File>>>,
+    /// Compression mode; storage compression compresses chunks with zstd
+    compression_mode: crate::types::CompressionMode,
+    /// Schemas by message type
+    schemas: HashMap<String, std::sync::Arc<mcap::Schema<'static>>>,
+    /// Channel ID mapping: connection id -> MCAP channel_id
+    channel_id_map: HashMap<u32, u16>,
+    /// Sequence number of the next message
+    sequence: u32,
+    /// Chunk layout tuning
+    options: McapWriteOptions,
 }
 
 #[cfg(feature = "mcap")]
 impl McapWriter {
     /// Create a new MCAP writer
     pub fn new(path: &Path, compression_mode: crate::types::CompressionMode) -> Result<Self> {
+        Self::with_options(path, compression_mode, McapWriteOptions::default())
+    }
+
+    /// Create a new MCAP writer with chunk layout tuning
+    pub fn with_options(
+        path: &Path,
+        compression_mode: crate::types::CompressionMode,
+        options: McapWriteOptions,
+    ) -> Result<Self> {
+        if options.chunk_size == 0 {
+            return Err(crate::error::BagError::writer(
+                "MCAP chunk size must be larger than zero",
+            ));
+        }
+
         let mcap_path = path.join(format!(
             "{}.mcap",
             path.file_name().unwrap().to_string_lossy()
@@ -501,151 +1460,182 @@ impl McapWriter {
         Ok(Self {
             mcap_path,
             writer: None,
-            _compression_mode: compression_mode,
-            is_open: false,
-            schemas: Vec::new(),
-            channels: Vec::new(),
-            next_schema_id: 1,
-            next_channel_id: 1,
+            compression_mode,
+            schemas: HashMap::new(),
             channel_id_map: HashMap::new(),
+            sequence: 0,
+            options,
         })
     }
 
-    /// Write MCAP header
-    fn write_header(&mut self) -> Result<()> {
-        if let Some(writer) = &mut self.writer {
-            // Write MCAP magic + version
-            writer.write_all(b"\x89MCAP0\r\n")?;
+    /// Store the data in `file_name` inside the bag directory instead of
+    /// `<bag name>.mcap`
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.mcap_path.set_file_name(file_name);
+        self
+    }
+
+    /// Get the chunk layout tuning
+    pub fn options(&self) -> &McapWriteOptions {
+        &self.options
+    }
 
-            // Write header record (placeholder implementation)
-            // This would need proper MCAP format implementation
-        }
-        Ok(())
+    /// Get the open MCAP writer
+    fn writer(&mut self) -> Result<&mut mcap::Writer<'static, std::io::BufWriter<File>>> {
+        self.writer
+            .as_mut()
+            .ok_or(crate::error::BagError::BagNotOpen)
     }
 }
 
+#[cfg(feature = "mcap")]
+fn mcap_error(e: mcap::McapError) -> crate::error::BagError {
+    crate::error::BagError::generic(format!("MCAP write error: {e}"))
+}
+
 #[cfg(feature = "mcap")]
 impl crate::storage::StorageWriter for McapWriter {
     fn open(&mut self) -> Result<()> {
-        if self.is_open {
+        if self.writer.is_some() {
             return Err(crate::error::BagError::BagAlreadyOpen);
         }
 
-        // Create the MCAP file
-        let file = std::fs::File::create(&self.mcap_path)?;
-        self.writer = Some(file);
-
-        // Write MCAP header
-        self.write_header()?;
-
-        self.is_open = true;
+        // Uncompressed chunks let readers borrow message data straight from the file
+        let compression = (self.compression_mode == crate::types::CompressionMode::Storage)
+            .then_some(mcap::Compression::Zstd);
+
+        let file = File::create(&self.mcap_path)?;
+        let writer = mcap::WriteOptions::new()
+            .profile("ros2")
+            .compression(compression)
+            .chunk_size(Some(self.options.chunk_size))
+            .create(std::io::BufWriter::new(file))
+            .map_err(mcap_error)?;
+        self.writer = Some(writer);
         Ok(())
     }
 
-    fn close(&mut self, _version: u32, _metadata: &str) -> Result<()> {
-        if !self.is_open {
+    fn close(&mut self, _version: u32, metadata: &str) -> Result<()> {
+        let Some(mut writer) = self.writer.take() else {
             return Ok(());
-        }
+        };
 
-        // Write MCAP footer and close
-        // This would need proper MCAP format implementation
-        if let Some(writer) = &mut self.writer {
-            writer.flush()?;
-        }
+        // Embed the bag metadata like rosbag2's MCAP storage plugin does
+        let mut fields = std::collections::BTreeMap::new();
+        fields.insert("serialized_metadata".to_string(), metadata.to_string());
+        writer
+            .write_metadata(&mcap::records::Metadata {
+                name: ROSBAG2_METADATA.to_string(),
+                metadata: fields,
+            })
+            .map_err(mcap_error)?;
+        // Writes the summary and footer, so a bag without messages is still valid
+        writer.finish().map_err(mcap_error)?;
 
-        self.writer = None;
-        self.is_open = false;
         self.schemas.clear();
-        self.channels.clear();
         self.channel_id_map.clear();
-        self.next_schema_id = 1;
-        self.next_channel_id = 1;
-
+        self.sequence = 0;
         Ok(())
     }
 
     fn add_msgtype(&mut self, connection: &Connection) -> Result<()> {
-        if !self.is_open {
+        if self.writer.is_none() {
             return Err(crate::error::BagError::BagNotOpen);
         }
 
-        // Create schema record for this message type
-        let schema = Schema {
-            id: self.next_schema_id,
-            name: connection.message_type.clone(),
-            _encoding: "ros2msg".to_string(), // Default encoding
-            _data: connection.message_definition.data.as_bytes().to_vec(),
-        };
-
-        self.schemas.push(schema);
-        self.next_schema_id += 1;
-
-        // Write schema to MCAP file (placeholder implementation)
-        // This would need proper MCAP format implementation
+        if self.schemas.contains_key(&connection.message_type) {
+            return Ok(());
+        }
 
+        // Foxglove Studio and other MCAP tools decode from the schema alone, so it
+        // holds the full definition; types without a stored one get the bundled one
+        let definition = DefinitionResolver::new()
+            .complete_definition(connection)
+            .unwrap_or_else(|_| connection.message_definition.clone());
+        let encoding = match definition.format {
+            MessageDefinitionFormat::Idl => "ros2idl",
+            _ => "ros2msg",
+        };
+        self.schemas.insert(
+            connection.message_type.clone(),
+            std::sync::Arc::new(mcap::Schema {
+                name: connection.message_type.clone(),
+                encoding: encoding.to_string(),
+                data: definition.data.into_bytes().into(),
+            }),
+        );
         Ok(())
     }
 
     fn add_connection(
         &mut self,
         connection: &Connection,
-        _offered_qos_profiles: &str,
+        offered_qos_profiles: &str,
     ) -> Result<()> {
-        if !self.is_open {
-            return Err(crate::error::BagError::BagNotOpen);
-        }
-
-        // Find schema for this message type
-        let schema_id = self
-            .schemas
-            .iter()
-            .find(|s| s.name == connection.message_type)
-            .map(|s| s.id)
-            .unwrap_or(0);
-
-        // Create channel record for this topic
-        let channel = Channel {
-            _id: self.next_channel_id,
-            _schema_id: schema_id,
-            _topic: connection.topic.clone(),
-            _message_encoding: connection.message_type.clone(),
-            _metadata: HashMap::new(),
+        // Keep the other keys of the connection, such as those of copied channels
+        let mut metadata = connection.metadata.clone();
+        metadata.insert(
+            OFFERED_QOS_PROFILES_KEY.to_string(),
+            offered_qos_profiles.to_string(),
+        );
+        let channel = mcap::Channel {
+            topic: connection.topic.clone(),
+            schema: self.schemas.get(&connection.message_type).cloned(),
+            message_encoding: connection.serialization_format.clone(),
+            metadata,
         };
 
-        self.channel_id_map
-            .insert(connection.topic.clone(), self.next_channel_id);
-        self.channels.push(channel);
-        self.next_channel_id += 1;
-
-        // Write channel to MCAP file (placeholder implementation)
-        // This would need proper MCAP format implementation
-
+        let channel_id = self.writer()?.add_channel(&channel).map_err(mcap_error)?;
+        self.channel_id_map.insert(connection.id, channel_id);
         Ok(())
     }
 
-    fn write(&mut self, connection: &Connection, _timestamp: u64, _data: &[u8]) -> Result<()> {
-        if !self.is_open {
-            return Err(crate::error::BagError::BagNotOpen);
-        }
-
-        let _channel_id = self
+    fn write(&mut self, connection: &Connection, timestamp: u64, data: &[u8]) -> Result<()> {
+        let channel_id = *self
             .channel_id_map
-            .get(&connection.topic)
+            .get(&connection.id)
             .ok_or_else(|| crate::error::BagError::connection_not_found(&connection.topic))?;
+        let header = mcap::records::MessageHeader {
+            channel_id,
+            sequence: self.sequence,
+            log_time: timestamp,
+            publish_time: timestamp,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
 
-        // Write message to MCAP file (placeholder implementation)
-        // This would need proper MCAP format implementation
+        self.writer()?
+            .write_to_known_channel(&header, data)
+            .map_err(mcap_error)
+    }
 
-        // For now, just ensure we have a valid file handle
-        if self.writer.is_none() {
-            return Err(crate::error::BagError::BagNotOpen);
-        }
+    fn add_attachment(&mut self, attachment: &Attachment) -> Result<()> {
+        self.writer()?
+            .attach(&mcap::Attachment {
+                log_time: attachment.log_time,
+                create_time: attachment.create_time,
+                name: attachment.name.clone(),
+                media_type: attachment.media_type.clone(),
+                data: Cow::Borrowed(&attachment.data),
+            })
+            .map_err(mcap_error)
+    }
 
-        Ok(())
+    fn add_mcap_metadata(&mut self, metadata: &McapMetadata) -> Result<()> {
+        if metadata.name == ROSBAG2_METADATA {
+            return Err(crate::error::BagError::writer(format!(
+                "Metadata record name '{ROSBAG2_METADATA}' is reserved for the bag metadata"
+            )));
+        }
+        self.writer()?
+            .write_metadata(&mcap::records::Metadata {
+                name: metadata.name.clone(),
+                metadata: metadata.metadata.clone(),
+            })
+            .map_err(mcap_error)
     }
 
     fn is_open(&self) -> bool {
-        self.is_open
+        self.writer.is_some()
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -709,22 +1699,42 @@ impl crate::storage::StorageWriter for McapWriter {
     }
 }
 
-// Helper structs for MCAP format (simplified)
-#[cfg(feature = "mcap")]
-#[derive(Debug, Clone)]
-struct Schema {
-    id: u64,
-    name: String,
-    _encoding: String,
-    _data: Vec<u8>,
-}
+#[cfg(all(test, feature = "mcap"))]
+mod tests {
+    use super::*;
+
+    fn chunk(size: usize) -> Arc<[CachedMessage]> {
+        Arc::from(vec![CachedMessage {
+            channel_id: 1,
+            sequence: 0,
+            log_time: 0,
+            publish_time: 0,
+            data: vec![0; size],
+        }])
+    }
 
-#[cfg(feature = "mcap")]
-#[derive(Debug, Clone)]
-struct Channel {
-    _id: u64,
-    _schema_id: u64,
-    _topic: String,
-    _message_encoding: String,
-    _metadata: HashMap<String, String>,
+    #[test]
+    fn test_chunk_cache_evicts_least_recently_used() {
+        let cache = ChunkCache::new(300);
+        cache.insert(0, 0, chunk(100));
+        cache.insert(0, 1, chunk(100));
+        cache.insert(1, 0, chunk(100));
+        // Using chunk 0 makes chunk 1 the least recently used one
+        assert!(cache.get(0, 0).is_some());
+        cache.insert(1, 1, chunk(150));
+        assert!(cache.get(0, 1).is_none());
+        assert!(cache.get(1, 0).is_none());
+        assert!(cache.get(0, 0).is_some());
+        assert!(cache.get(1, 1).is_some());
+        assert_eq!(cache.state().size, 250);
+
+        // Chunks larger than the cache are not kept
+        cache.insert(2, 0, chunk(301));
+        assert!(cache.get(2, 0).is_none());
+        assert_eq!(cache.state().size, 250);
+
+        cache.clear();
+        assert!(cache.get(0, 0).is_none());
+        assert_eq!(cache.state().size, 0);
+    }
 }
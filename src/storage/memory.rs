@@ -0,0 +1,597 @@
+//! In-memory storage for tests
+//!
+//! Bags written with [`StoragePlugin::Memory`](crate::StoragePlugin::Memory) never touch
+//! the filesystem: their storage files and `metadata.yaml` are kept in a process-wide
+//! store keyed by bag path, and [`Reader`](crate::Reader) opens paths found in the store
+//! from memory. Tests of code built on this crate can write and read bags without
+//! temporary directories:
+//!
+//! ```
+//! use rosbags_rs::{Reader, StoragePlugin, Writer};
+//!
+//! # fn main() -> rosbags_rs::Result<()> {
+//! let mut writer = Writer::builder("memory/doc_bag")
+//!     .storage(StoragePlugin::Memory)
+//!     .open()?;
+//! let connection = writer.add_connection(
+//!     "/chatter".to_string(),
+//!     "std_msgs/msg/String".to_string(),
+//!     None,
+//!     None,
+//!     None,
+//!     None,
+//! )?;
+//! writer.write(&connection, 42, &[0, 1, 0, 0, 3, 0, 0, 0, b'h', b'i', 0, 0])?;
+//! writer.close()?;
+//!
+//! let reader = Reader::builder("memory/doc_bag").open()?;
+//! assert_eq!(reader.message_count(), 1);
+//! rosbags_rs::storage::memory::remove("memory/doc_bag");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Bags stay in the store until [`remove`] drops them or the process ends.
+
+use crate::error::{BagError, Result};
+use crate::storage::StorageWriter;
+use crate::types::{Connection, MessageDefinition};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+#[cfg(not(feature = "write-only"))]
+use {
+    crate::storage::{sort_messages, StorageInput, StorageReader, TopicFilter},
+    crate::types::{BagFile, Message, RawMessage, TopicPattern},
+};
+
+/// File extension of in-memory storage files
+pub const EXTENSION: &str = "memory";
+
+/// A bag held in memory
+#[derive(Debug, Default)]
+struct MemoryBag {
+    /// Contents of `metadata.yaml`, once the writer closed
+    metadata: Option<String>,
+    /// Storage files by name
+    files: BTreeMap<String, Arc<MemoryFile>>,
+}
+
+/// A storage file held in memory
+#[derive(Debug, Clone, Default)]
+struct MemoryFile {
+    /// Connections in the order they were added
+    connections: Vec<Connection>,
+    /// Message definitions by type
+    definitions: HashMap<String, MessageDefinition>,
+    /// Messages as connection index, timestamp and data, in write order
+    messages: Vec<(usize, u64, Vec<u8>)>,
+}
+
+/// Lock the store of in-memory bags
+fn store() -> MutexGuard<'static, HashMap<PathBuf, MemoryBag>> {
+    static STORE: OnceLock<Mutex<HashMap<PathBuf, MemoryBag>>> = OnceLock::new();
+    STORE
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Split the path of a storage file into bag path and file name
+fn split_path(path: &Path) -> Option<(&Path, String)> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    Some((path.parent()?, name))
+}
+
+/// Whether an in-memory bag exists at `bag_path`
+pub fn contains(bag_path: impl AsRef<Path>) -> bool {
+    store().contains_key(bag_path.as_ref())
+}
+
+/// Drop the in-memory bag at `bag_path`, returning whether it existed
+pub fn remove(bag_path: impl AsRef<Path>) -> bool {
+    store().remove(bag_path.as_ref()).is_some()
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Names of the storage files of the in-memory bag at `bag_path`, in name order
+pub(crate) fn file_names(bag_path: &Path) -> Vec<String> {
+    store()
+        .get(bag_path)
+        .map(|bag| bag.files.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Contents of `metadata.yaml` of the in-memory bag at `bag_path`
+pub(crate) fn metadata(bag_path: &Path) -> Option<String> {
+    store().get(bag_path)?.metadata.clone()
+}
+
+#[cfg(any(feature = "write-only", feature = "default"))]
+/// Store the contents of `metadata.yaml` of the in-memory bag at `bag_path`
+pub(crate) fn set_metadata(bag_path: &Path, metadata: &str) {
+    store().entry(bag_path.to_path_buf()).or_default().metadata = Some(metadata.to_string());
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Whether the in-memory storage file at `path` exists
+pub(crate) fn contains_file(path: &Path) -> bool {
+    split_path(path).is_some_and(|(bag_path, name)| {
+        store()
+            .get(bag_path)
+            .is_some_and(|bag| bag.files.contains_key(&name))
+    })
+}
+
+#[cfg(any(feature = "write-only", feature = "default"))]
+/// Total payload size in bytes of the in-memory storage file at `path`
+pub(crate) fn file_size(path: &Path) -> Result<u64> {
+    let file = load_file(path)?;
+    Ok(file
+        .messages
+        .iter()
+        .map(|(_, _, data)| data.len() as u64)
+        .sum())
+}
+
+#[cfg(any(feature = "write-only", feature = "default"))]
+/// Drop the in-memory storage file at `path`
+pub(crate) fn remove_file(path: &Path) -> Result<()> {
+    let removed = split_path(path).and_then(|(bag_path, name)| {
+        store()
+            .get_mut(bag_path)
+            .and_then(|bag| bag.files.remove(&name))
+    });
+    match removed {
+        Some(_) => Ok(()),
+        None => Err(BagError::StorageFileNotFound {
+            path: path.to_path_buf(),
+        }),
+    }
+}
+
+/// Get the in-memory storage file at `path`
+fn load_file(path: &Path) -> Result<Arc<MemoryFile>> {
+    split_path(path)
+        .and_then(|(bag_path, name)| store().get(bag_path)?.files.get(&name).cloned())
+        .ok_or_else(|| BagError::StorageFileNotFound {
+            path: path.to_path_buf(),
+        })
+}
+
+/// Storage writer keeping the file in memory
+///
+/// The file is visible to readers from [`StorageWriter::open`], and holds the written
+/// messages once the writer closes.
+pub struct MemoryWriter {
+    /// Path of the bag the file belongs to
+    bag_path: PathBuf,
+    /// Name of the file within the bag
+    file_name: String,
+    /// Contents written so far
+    file: MemoryFile,
+    /// Connection ID mapping: connection id -> index in the file's connections
+    connection_indexes: HashMap<u32, usize>,
+    /// Whether the writer is currently open
+    is_open: bool,
+}
+
+impl MemoryWriter {
+    /// Create a writer of `<bag name>.memory` in the in-memory bag at `path`
+    pub fn new(path: &Path) -> Result<Self> {
+        let stem = path
+            .file_name()
+            .ok_or_else(|| BagError::writer("In-memory bags need a path with a file name"))?
+            .to_string_lossy();
+        Ok(Self {
+            file_name: format!("{stem}.{EXTENSION}"),
+            bag_path: path.to_path_buf(),
+            file: MemoryFile::default(),
+            connection_indexes: HashMap::new(),
+            is_open: false,
+        })
+    }
+
+    /// Store the data as `file_name` in the bag instead of `<bag name>.memory`
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = file_name.to_string();
+        self
+    }
+
+    /// Publish the file contents to the store
+    fn publish(&self) {
+        store()
+            .entry(self.bag_path.clone())
+            .or_default()
+            .files
+            .insert(self.file_name.clone(), Arc::new(self.file.clone()));
+    }
+}
+
+impl StorageWriter for MemoryWriter {
+    fn open(&mut self) -> Result<()> {
+        self.file = MemoryFile::default();
+        self.connection_indexes.clear();
+        self.publish();
+        self.is_open = true;
+        Ok(())
+    }
+
+    fn close(&mut self, _version: u32, _metadata: &str) -> Result<()> {
+        if !self.is_open {
+            return Ok(());
+        }
+        self.publish();
+        self.is_open = false;
+        Ok(())
+    }
+
+    fn add_msgtype(&mut self, connection: &Connection) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        self.file.definitions.insert(
+            connection.message_type.clone(),
+            connection.message_definition.clone(),
+        );
+        Ok(())
+    }
+
+    fn add_connection(
+        &mut self,
+        connection: &Connection,
+        _offered_qos_profiles: &str,
+    ) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        self.connection_indexes
+            .insert(connection.id, self.file.connections.len());
+        self.file.connections.push(connection.clone());
+        Ok(())
+    }
+
+    fn write(&mut self, connection: &Connection, timestamp: u64, data: &[u8]) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        let index = *self
+            .connection_indexes
+            .get(&connection.id)
+            .ok_or_else(|| BagError::connection_not_found(&connection.topic))?;
+        self.file.messages.push((index, timestamp, data.to_vec()));
+        Ok(())
+    }
+
+    fn write_owned_batch(&mut self, messages: Vec<(Connection, u64, Vec<u8>)>) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        for (connection, timestamp, data) in messages {
+            let index = *self
+                .connection_indexes
+                .get(&connection.id)
+                .ok_or_else(|| BagError::connection_not_found(&connection.topic))?;
+            self.file.messages.push((index, timestamp, data));
+        }
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Storage reader of in-memory storage files
+pub struct MemoryReader {
+    /// Paths of the storage files
+    paths: Vec<PathBuf>,
+    /// Contents of the storage files, loaded on open
+    files: Vec<Arc<MemoryFile>>,
+    /// Connections of all files, one per topic and type
+    connections: Vec<Connection>,
+    /// Index in `connections` of each connection of each file
+    connection_indexes: Vec<Vec<usize>>,
+    /// Whether the reader is currently open
+    is_open: bool,
+}
+
+#[cfg(not(feature = "write-only"))]
+impl MemoryReader {
+    /// Create a reader of the in-memory storage files at the paths of `inputs`
+    pub fn from_inputs(inputs: Vec<StorageInput>) -> Result<Self> {
+        let paths = inputs
+            .into_iter()
+            .map(|input| match input {
+                StorageInput::Path(path) => Ok(path),
+                StorageInput::Memory { name, .. } => Err(BagError::UnsupportedStorageFormat {
+                    format: format!(
+                        "memory (file contents of {} cannot be read as in-memory storage)",
+                        name.display()
+                    ),
+                }),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            paths,
+            files: Vec::new(),
+            connections: Vec::new(),
+            connection_indexes: Vec::new(),
+            is_open: false,
+        })
+    }
+
+    /// Get the connections of all files with their message counts and definitions
+    ///
+    /// Connections of the same topic and type in several files are merged; IDs are
+    /// assigned in the order the connections were first added.
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// Collect the messages passing `passes` from `start` (inclusive) to `stop`
+    /// (exclusive) in iteration order
+    fn collect(
+        &self,
+        passes: impl Fn(&Connection) -> bool,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Vec<RawMessage>> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        let in_range = |timestamp: u64| {
+            start.map_or(true, |start| timestamp >= start)
+                && stop.map_or(true, |stop| timestamp < stop)
+        };
+        let mut messages = Vec::new();
+        for (file_index, (file, indexes)) in
+            self.files.iter().zip(&self.connection_indexes).enumerate()
+        {
+            for (index, timestamp, data) in &file.messages {
+                let connection = &self.connections[indexes[*index]];
+                if in_range(*timestamp) && passes(connection) {
+                    messages.push(Ok(RawMessage {
+                        connection: connection.clone(),
+                        timestamp: *timestamp,
+                        publish_time: *timestamp,
+                        sequence: 0,
+                        raw_data: data.clone(),
+                        file_index,
+                    }));
+                }
+            }
+        }
+        sort_messages(&mut messages, |message| {
+            (message.timestamp, message.connection.id)
+        });
+        messages.into_iter().collect()
+    }
+
+    /// Box `messages` as an iterator
+    fn iter<T: 'static>(messages: Vec<T>) -> Box<dyn Iterator<Item = Result<T>>> {
+        Box::new(messages.into_iter().map(Ok))
+    }
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Convert a raw message into a message
+fn into_message(message: RawMessage) -> Message {
+    Message {
+        topic: message.connection.topic.clone(),
+        connection: message.connection,
+        timestamp: message.timestamp,
+        publish_time: message.publish_time,
+        sequence: message.sequence,
+        data: message.raw_data,
+        file_index: message.file_index,
+    }
+}
+
+#[cfg(not(feature = "write-only"))]
+impl StorageReader for MemoryReader {
+    fn open(&mut self) -> Result<()> {
+        let files = self
+            .paths
+            .iter()
+            .map(|path| load_file(path))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut connections: Vec<Connection> = Vec::new();
+        let mut connection_indexes = Vec::with_capacity(files.len());
+        for file in &files {
+            let mut indexes = Vec::with_capacity(file.connections.len());
+            for connection in &file.connections {
+                let known = connections.iter().position(|c| {
+                    c.topic == connection.topic && c.message_type == connection.message_type
+                });
+                let index = known.unwrap_or_else(|| {
+                    let mut merged = connection.clone();
+                    merged.id = connections.len() as u32 + 1;
+                    merged.message_count = 0;
+                    if let Some(definition) = file.definitions.get(&merged.message_type) {
+                        merged.message_definition = definition.clone();
+                    }
+                    connections.push(merged);
+                    connections.len() - 1
+                });
+                indexes.push(index);
+            }
+            for (index, _, _) in &file.messages {
+                connections[indexes[*index]].message_count += 1;
+            }
+            connection_indexes.push(indexes);
+        }
+
+        self.files = files;
+        self.connections = connections;
+        self.connection_indexes = connection_indexes;
+        self.is_open = true;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.files.clear();
+        self.is_open = false;
+        Ok(())
+    }
+
+    fn get_definitions(&self) -> Result<HashMap<String, MessageDefinition>> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        Ok(self
+            .files
+            .iter()
+            .flat_map(|file| file.definitions.clone())
+            .collect())
+    }
+
+    fn messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        let filter = TopicFilter::Connections(connections);
+        let messages = self.collect(|c| filter.passes(c), start, stop)?;
+        Ok(Self::iter(messages.into_iter().map(into_message).collect()))
+    }
+
+    fn raw_messages(&self) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        self.raw_messages_filtered(None, None, None)
+    }
+
+    fn raw_messages_filtered(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        Ok(Self::iter(self.read_raw_messages_batch(
+            connections,
+            start,
+            stop,
+        )?))
+    }
+
+    fn messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>> {
+        let messages = self.collect(
+            |c| patterns.iter().any(|pattern| pattern.matches(&c.topic)),
+            start,
+            stop,
+        )?;
+        Ok(Self::iter(messages.into_iter().map(into_message).collect()))
+    }
+
+    fn raw_messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>> {
+        let messages = self.collect(
+            |c| patterns.iter().any(|pattern| pattern.matches(&c.topic)),
+            start,
+            stop,
+        )?;
+        Ok(Self::iter(messages))
+    }
+
+    fn files(&self) -> Result<Vec<BagFile>> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        Ok(self
+            .files
+            .iter()
+            .zip(&self.paths)
+            .enumerate()
+            .map(|(index, (file, path))| {
+                let timestamps = file.messages.iter().map(|(_, timestamp, _)| *timestamp);
+                BagFile {
+                    index,
+                    path: path.clone(),
+                    start_time: timestamps.clone().min().unwrap_or(0),
+                    end_time: timestamps.max().unwrap_or(0),
+                    message_count: file.messages.len() as u64,
+                }
+            })
+            .collect())
+    }
+
+    fn file_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    fn read_raw_messages_batch(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Vec<RawMessage>> {
+        let filter = TopicFilter::Connections(connections);
+        self.collect(|c| filter.passes(c), start, stop)
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(all(test, feature = "default", not(feature = "write-only")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_round_trip_through_the_store() {
+        let bag_path = Path::new("memory/unit_round_trip");
+        let imu = Connection::builder("/imu", "sensor_msgs/msg/Imu")
+            .id(7)
+            .build()
+            .unwrap();
+        let mut writer = MemoryWriter::new(bag_path).unwrap();
+        writer.open().unwrap();
+        assert!(contains(bag_path));
+        writer.add_msgtype(&imu).unwrap();
+        writer.add_connection(&imu, "").unwrap();
+        writer.write(&imu, 20, &[1, 2]).unwrap();
+        writer.write(&imu, 10, &[3]).unwrap();
+        writer.close(9, "").unwrap();
+        assert_eq!(file_names(bag_path), ["unit_round_trip.memory"]);
+
+        let file_path = bag_path.join("unit_round_trip.memory");
+        assert_eq!(file_size(&file_path).unwrap(), 3);
+        let mut reader = MemoryReader::from_inputs(vec![file_path.clone().into()]).unwrap();
+        reader.open().unwrap();
+        assert_eq!(reader.connections()[0].id, 1);
+        assert_eq!(reader.connections()[0].message_count, 2);
+        let timestamps: Vec<u64> = reader
+            .raw_messages()
+            .unwrap()
+            .map(|message| message.unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, [10, 20]);
+        assert_eq!(reader.files().unwrap()[0].start_time, 10);
+
+        remove_file(&file_path).unwrap();
+        assert!(remove_file(&file_path).is_err());
+        assert!(remove(bag_path));
+        assert!(!contains(bag_path));
+    }
+}
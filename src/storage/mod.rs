@@ -1,23 +1,125 @@
 //! Storage backend implementations for ROS2 bag files
 
-use crate::error::Result;
-use crate::types::{
-    CompressionMode, Connection, StoragePlugin,
-};
+use crate::error::{BagError, Result};
+use crate::types::{Attachment, CompressionMode, Connection, McapMetadata, StoragePlugin};
 #[cfg(not(feature = "write-only"))]
-use crate::types::{Message, MessageDefinition, RawMessage};
+use crate::types::{BagFile, Message, MessageDefinition, RawMessage, RawMessageRef, TopicPattern};
 #[cfg(not(feature = "write-only"))]
 use std::collections::HashMap;
+#[cfg(not(feature = "write-only"))]
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
+#[cfg(not(feature = "write-only"))]
+use std::path::PathBuf;
+#[cfg(not(feature = "write-only"))]
+use std::sync::Arc;
+
+#[cfg(any(feature = "write-only", feature = "default"))]
+pub(crate) mod background;
 
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
+#[cfg(all(feature = "sqlite", not(feature = "write-only")))]
+pub mod sqlite_index;
+
 #[cfg(feature = "mcap")]
 pub mod mcap;
 
+pub mod memory;
+
+/// Leading bytes of SQLite3 database files
+pub const SQLITE3_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Leading bytes of MCAP files
+pub const MCAP_MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+/// Storage format of a file starting with `header`, from its magic bytes
+pub fn format_of_header(header: &[u8]) -> Option<StoragePlugin> {
+    if header.starts_with(SQLITE3_MAGIC) {
+        Some(StoragePlugin::Sqlite3)
+    } else if header.starts_with(MCAP_MAGIC) {
+        Some(StoragePlugin::Mcap)
+    } else {
+        None
+    }
+}
+
+/// Storage format named by the extension of `path`
+pub fn format_of_extension(path: &Path) -> Option<StoragePlugin> {
+    match path.extension()?.to_str()? {
+        "db3" => Some(StoragePlugin::Sqlite3),
+        "mcap" => Some(StoragePlugin::Mcap),
+        memory::EXTENSION => Some(StoragePlugin::Memory),
+        _ => None,
+    }
+}
+
+/// Storage format of the file at `path`
+///
+/// The format is read from the file's magic bytes, so storage files that were renamed
+/// or lost their extension still open. Files that cannot be read or match no magic,
+/// such as in-memory storage files, fall back to their extension.
+pub fn detect_format(path: &Path) -> Option<StoragePlugin> {
+    use std::io::Read as _;
+
+    let mut header = Vec::with_capacity(SQLITE3_MAGIC.len());
+    std::fs::File::open(path)
+        .and_then(|file| {
+            file.take(SQLITE3_MAGIC.len() as u64)
+                .read_to_end(&mut header)
+        })
+        .ok()
+        .and_then(|_| format_of_header(&header))
+        .or_else(|| format_of_extension(path))
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Sort messages into the order every backend returns them in
+///
+/// Messages are ordered by bag timestamp, then by connection id. Messages that tie on
+/// both keep their storage order (file index, then position within the file): the sort
+/// is stable and backends collect messages in storage order. Read errors sort first,
+/// so they are reported before any message.
+pub(crate) fn sort_messages<T>(messages: &mut [Result<T>], key: impl Fn(&T) -> (u64, u32)) {
+    messages.sort_by_key(|message| message.as_ref().ok().map(&key));
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Topics read by an iteration
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TopicFilter<'a> {
+    /// The topics and types of these connections, or all topics
+    Connections(Option<&'a [Connection]>),
+    /// Topics whose name matches the expression
+    Regex(&'a regex::Regex),
+}
+
+#[cfg(not(feature = "write-only"))]
+impl TopicFilter<'_> {
+    /// Whether messages of `connection` are read
+    ///
+    /// Connections are told apart by topic and type, so a topic recorded with two
+    /// types only matches the requested one.
+    pub(crate) fn passes(&self, connection: &Connection) -> bool {
+        match self {
+            Self::Connections(connections) => connections.map_or(true, |conns| {
+                conns.iter().any(|c| {
+                    c.topic == connection.topic && c.message_type == connection.message_type
+                })
+            }),
+            Self::Regex(regex) => regex.is_match(&connection.topic),
+        }
+    }
+}
+
 #[cfg(not(feature = "write-only"))]
 /// Trait for storage backend implementations (reading)
+///
+/// Every iteration method returns messages in the same deterministic order, whatever
+/// the backend: by timestamp, then connection id, then storage order (file index, then
+/// position within the file). Pipelines reading the same bag from SQLite3 or MCAP
+/// therefore see messages with equal timestamps in the same order.
 pub trait StorageReader {
     /// Open the storage files for reading
     fn open(&mut self) -> Result<()>;
@@ -28,7 +130,16 @@ pub trait StorageReader {
     /// Get message definitions from the storage
     fn get_definitions(&self) -> Result<HashMap<String, MessageDefinition>>;
 
-    /// Iterate over messages, optionally filtered by connections, start time, and stop time
+    /// Get the message definition of `message_type`, `None` if the storage has none
+    ///
+    /// The default implementation picks it from [`StorageReader::get_definitions`];
+    /// backends that can look up a single definition override it.
+    fn get_definition(&self, message_type: &str) -> Result<Option<MessageDefinition>> {
+        Ok(self.get_definitions()?.remove(message_type))
+    }
+
+    /// Iterate over messages, optionally filtered by connections, start time (inclusive),
+    /// and stop time (exclusive)
     fn messages_filtered(
         &self,
         connections: Option<&[Connection]>,
@@ -47,6 +158,133 @@ pub trait StorageReader {
         stop: Option<u64>,
     ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>>;
 
+    /// Iterate over messages on the topics matching any of `patterns`, from `start`
+    /// (inclusive) to `stop` (exclusive)
+    ///
+    /// Backends select the matching topics themselves, in the SQL query or once per
+    /// MCAP channel, instead of comparing every message against a connection list.
+    fn messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Message>> + '_>>;
+
+    /// Iterate over raw messages on the topics matching any of `patterns`
+    ///
+    /// See [`StorageReader::messages_matching`].
+    fn raw_messages_matching(
+        &self,
+        patterns: &[TopicPattern],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessage>> + '_>>;
+
+    /// Get the first message of `connections` from `start` (inclusive) to `stop`
+    /// (exclusive) in iteration order, `None` if there is none
+    ///
+    /// The default implementation reads the messages until the first one; backends
+    /// look the message up with their indexes.
+    fn first_message(
+        &self,
+        connections: &[Connection],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Option<Message>> {
+        self.messages_filtered(Some(connections), start, stop)?
+            .next()
+            .transpose()
+    }
+
+    /// Get the last message of `connections` from `start` (inclusive) to `stop`
+    /// (exclusive) in iteration order, `None` if there is none
+    ///
+    /// The default implementation reads all messages.
+    fn last_message(
+        &self,
+        connections: &[Connection],
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Option<Message>> {
+        let mut last = None;
+        for message in self.messages_filtered(Some(connections), start, stop)? {
+            last = Some(message?);
+        }
+        Ok(last)
+    }
+
+    /// Count the messages of `connections` (all with `None`) from `start` (inclusive)
+    /// to `stop` (exclusive)
+    ///
+    /// The default implementation reads the messages; backends count them with their
+    /// indexes.
+    fn count_messages(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<u64> {
+        let mut count = 0;
+        for message in self.raw_messages_borrowed(connections, start, stop)? {
+            message?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Iterate over filtered raw messages whose data is borrowed from the storage buffer
+    /// where the backend allows it
+    ///
+    /// The default implementation wraps the owned messages of
+    /// [`StorageReader::raw_messages_filtered`].
+    fn raw_messages_borrowed(
+        &self,
+        connections: Option<&[Connection]>,
+        start: Option<u64>,
+        stop: Option<u64>,
+    ) -> Result<Box<dyn Iterator<Item = Result<RawMessageRef<'_>>> + '_>> {
+        let messages = self.raw_messages_filtered(connections, start, stop)?;
+        Ok(Box::new(
+            messages.map(|message| message.map(RawMessageRef::from)),
+        ))
+    }
+
+    /// Get the storage files with the time span and number of their messages
+    fn files(&self) -> Result<Vec<BagFile>>;
+
+    /// Paths of the storage files, indexed by the `file_index` of their messages
+    fn file_paths(&self) -> &[PathBuf];
+
+    /// Get the timestamps of the first and last message, or `None` without messages
+    ///
+    /// The default implementation scans all messages.
+    fn time_range(&self) -> Result<Option<(u64, u64)>> {
+        let mut range: Option<(u64, u64)> = None;
+        for message in self.raw_messages_borrowed(None, None, None)? {
+            let timestamp = message?.timestamp;
+            range = Some(range.map_or((timestamp, timestamp), |(first, last)| {
+                (first.min(timestamp), last.max(timestamp))
+            }));
+        }
+        Ok(range)
+    }
+
+    /// Get the attachments of all storage files, in file order
+    ///
+    /// The default implementation returns none, as SQLite3 storage has no attachments.
+    fn attachments(&self) -> Result<Vec<Attachment>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the metadata records of all storage files, in file order, without the
+    /// `rosbag2` record holding the bag metadata
+    ///
+    /// The default implementation returns none, as SQLite3 storage has no metadata
+    /// records.
+    fn mcap_metadata(&self) -> Result<Vec<McapMetadata>> {
+        Ok(Vec::new())
+    }
+
     /// Read all raw messages as a batch for bulk operations
     fn read_raw_messages_batch(
         &self,
@@ -63,7 +301,7 @@ pub trait StorageReader {
 }
 
 /// Storage writer trait for writing bag data
-pub trait StorageWriter: std::any::Any {
+pub trait StorageWriter: std::any::Any + Send {
     /// Open the storage for writing
     fn open(&mut self) -> Result<()>;
 
@@ -89,6 +327,31 @@ pub trait StorageWriter: std::any::Any {
         Ok(())
     }
 
+    /// Write a batch of messages the storage may keep, e.g. to write it later
+    ///
+    /// Default implementation writes the batch with [`StorageWriter::write_batch`]
+    fn write_owned_batch(&mut self, messages: Vec<(Connection, u64, Vec<u8>)>) -> Result<()> {
+        self.write_batch(&messages)
+    }
+
+    /// Attach a file to the storage
+    ///
+    /// Default implementation fails, as only MCAP storage holds attachments
+    fn add_attachment(&mut self, _attachment: &Attachment) -> Result<()> {
+        Err(BagError::UnsupportedStorageFormat {
+            format: "Attachments require MCAP storage".to_string(),
+        })
+    }
+
+    /// Write a named metadata record to the storage
+    ///
+    /// Default implementation fails, as only MCAP storage holds metadata records
+    fn add_mcap_metadata(&mut self, _metadata: &McapMetadata) -> Result<()> {
+        Err(BagError::UnsupportedStorageFormat {
+            format: "Metadata records require MCAP storage".to_string(),
+        })
+    }
+
     /// Check if the storage is open
     fn is_open(&self) -> bool;
 
@@ -96,61 +359,135 @@ pub trait StorageWriter: std::any::Any {
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
+#[cfg(not(feature = "write-only"))]
+/// Source of a storage file: a path on disk or file contents held in memory
+///
+/// Memory inputs let bags be read from buffers, archives or network streams. Their
+/// name stands in for the path: it appears in errors and [`StorageReader::file_paths`]
+/// and selects the storage format by extension if the contents have no known magic.
+#[derive(Debug, Clone)]
+pub enum StorageInput {
+    /// Storage file on disk
+    Path(PathBuf),
+    /// Storage file contents
+    Memory {
+        /// File name, e.g. `bag_0.mcap`
+        name: PathBuf,
+        /// File contents
+        data: Arc<[u8]>,
+    },
+}
+
+#[cfg(not(feature = "write-only"))]
+impl StorageInput {
+    /// Input of the file contents in `data`
+    pub fn from_bytes(name: impl Into<PathBuf>, data: impl Into<Arc<[u8]>>) -> Self {
+        Self::Memory {
+            name: name.into(),
+            data: data.into(),
+        }
+    }
+
+    /// Input of everything `reader` holds, from its start
+    ///
+    /// The contents are read into memory right away; both backends need random
+    /// access to a whole file.
+    pub fn from_reader<R: Read + Seek>(name: impl Into<PathBuf>, mut reader: R) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        let mut data = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+        reader.read_to_end(&mut data)?;
+        Ok(Self::from_bytes(name, data))
+    }
+
+    /// Path of the file, or name of the in-memory file
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Path(path) => path,
+            Self::Memory { name, .. } => name,
+        }
+    }
+
+    /// Storage format of the input, from its magic bytes or else its name
+    ///
+    /// See [`detect_format`].
+    pub fn format(&self) -> Option<StoragePlugin> {
+        match self {
+            Self::Path(path) => detect_format(path),
+            Self::Memory { name, data } => {
+                format_of_header(data).or_else(|| format_of_extension(name))
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "write-only"))]
+impl From<PathBuf> for StorageInput {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+#[cfg(not(feature = "write-only"))]
+impl From<&Path> for StorageInput {
+    fn from(path: &Path) -> Self {
+        Self::Path(path.to_path_buf())
+    }
+}
+
 #[cfg(not(feature = "write-only"))]
 /// Create a storage reader for the given storage identifier
 pub fn create_storage_reader(
     storage_id: &str,
     paths: Vec<&Path>,
+    connections: Vec<Connection>,
+    options: &crate::reader::ReaderOptions,
+) -> Result<Box<dyn StorageReader>> {
+    let inputs = paths.into_iter().map(StorageInput::from).collect();
+    create_storage_reader_from_inputs(storage_id, inputs, connections, options)
+}
+
+#[cfg(not(feature = "write-only"))]
+/// Create a storage reader for the given storage identifier over files on disk or in
+/// memory
+pub fn create_storage_reader_from_inputs(
+    storage_id: &str,
+    inputs: Vec<StorageInput>,
     #[allow(unused_variables)] connections: Vec<Connection>,
+    #[allow(unused_variables)] options: &crate::reader::ReaderOptions,
 ) -> Result<Box<dyn StorageReader>> {
     match storage_id {
         #[cfg(feature = "sqlite")]
-        "sqlite3" => Ok(Box::new(sqlite::SqliteReader::new(paths, connections)?)),
+        "sqlite3" => Ok(Box::new(sqlite::SqliteReader::from_inputs(
+            inputs,
+            connections,
+            options,
+        )?)),
         #[cfg(not(feature = "sqlite"))]
         "sqlite3" => Err(crate::error::BagError::UnsupportedStorageFormat {
             format: "sqlite3 (feature not enabled)".to_string(),
         }),
         #[cfg(feature = "mcap")]
-        "mcap" => Ok(Box::new(mcap::McapStorageReader::new(paths, connections)?)),
+        "mcap" => Ok(Box::new(mcap::McapStorageReader::from_inputs(
+            inputs,
+            connections,
+            options,
+        )?)),
         #[cfg(not(feature = "mcap"))]
         "mcap" => Err(crate::error::BagError::UnsupportedStorageFormat {
             format: "mcap (feature not enabled)".to_string(),
         }),
+        "memory" => Ok(Box::new(memory::MemoryReader::from_inputs(inputs)?)),
         "" => {
-            // Auto-detect storage format from file extensions when storage_identifier is empty
-            let has_db3 = paths
-                .iter()
-                .any(|path| path.extension().is_some_and(|ext| ext == "db3"));
-            let has_mcap = paths
-                .iter()
-                .any(|path| path.extension().is_some_and(|ext| ext == "mcap"));
-
-            if has_db3 {
-                #[cfg(feature = "sqlite")]
-                {
-                    Ok(Box::new(sqlite::SqliteReader::new(paths, connections)?))
-                }
-                #[cfg(not(feature = "sqlite"))]
-                {
-                    Err(crate::error::BagError::UnsupportedStorageFormat {
-                        format: "sqlite3 (feature not enabled)".to_string(),
-                    })
+            // Auto-detect the storage format from the file contents when
+            // storage_identifier is empty
+            match inputs.iter().find_map(StorageInput::format) {
+                Some(plugin) => {
+                    create_storage_reader_from_inputs(plugin.as_str(), inputs, connections, options)
                 }
-            } else if has_mcap {
-                #[cfg(feature = "mcap")]
-                {
-                    Ok(Box::new(mcap::McapStorageReader::new(paths, connections)?))
-                }
-                #[cfg(not(feature = "mcap"))]
-                {
-                    Err(crate::error::BagError::UnsupportedStorageFormat {
-                        format: "mcap (feature not enabled)".to_string(),
-                    })
-                }
-            } else {
-                Err(crate::error::BagError::UnsupportedStorageFormat {
-                    format: "unknown (no .db3 or .mcap files found)".to_string(),
-                })
+                None => Err(crate::error::BagError::UnsupportedStorageFormat {
+                    format: "unknown (no SQLite3 or MCAP storage files found)".to_string(),
+                }),
             }
         }
         _ => Err(crate::error::BagError::UnsupportedStorageFormat {
@@ -178,5 +515,6 @@ pub fn create_storage_writer(
         StoragePlugin::Mcap => Err(crate::error::BagError::UnsupportedStorageFormat {
             format: "mcap (feature not enabled)".to_string(),
         }),
+        StoragePlugin::Memory => Ok(Box::new(memory::MemoryWriter::new(path)?)),
     }
 }
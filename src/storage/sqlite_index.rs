@@ -0,0 +1,339 @@
+//! Sidecar indexes of SQLite3 databases
+//!
+//! rosbag2 databases only index messages by timestamp, so counting the messages of a
+//! topic in a time range or finding its first message after some time scans the
+//! messages of every topic. A sidecar index, written next to the database by
+//! [`Reader::build_index`](crate::Reader::build_index), lists the timestamps and row
+//! ids of the messages of each topic in iteration order: counts then take two binary
+//! searches, and seeks a binary search and one lookup by row id.
+//!
+//! An index is only used while the database (and its write-ahead log) has the size and
+//! modification time it had when the index was built; a stale index is ignored.
+
+use crate::error::{ReaderError, Result};
+use rusqlite::Connection as SqliteConnection;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Extension appended to the file name of a database for its sidecar index
+pub const INDEX_EXTENSION: &str = "index";
+
+/// Leading bytes of index files; the last byte is the version of the layout
+const MAGIC: &[u8; 8] = b"RBAGIDX\x01";
+
+/// Path of the sidecar index of the database at `db_path`, e.g. `bag_0.db3.index`
+pub fn index_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(INDEX_EXTENSION);
+    db_path.with_file_name(name)
+}
+
+/// Index of the messages of one topic row of a database, in iteration order
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TopicIndex {
+    /// Id of the topic in the `topics` table
+    pub topic_id: i64,
+    /// Topic name
+    pub topic: String,
+    /// Message type of the topic
+    pub message_type: String,
+    /// Timestamps of the messages, ascending
+    timestamps: Vec<u64>,
+    /// Row ids of the messages in the `messages` table, by position
+    row_ids: Vec<i64>,
+}
+
+impl TopicIndex {
+    /// Number of messages of the topic
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Whether the topic has no messages
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Positions of the messages from `start` (inclusive) to `stop` (exclusive)
+    pub fn range(&self, start: Option<u64>, stop: Option<u64>) -> std::ops::Range<usize> {
+        let position = |time: Option<u64>, default| {
+            time.map_or(default, |time| {
+                self.timestamps.partition_point(|&t| t < time)
+            })
+        };
+        let first = position(start, 0);
+        let end = position(stop, self.len()).max(first);
+        first..end
+    }
+
+    /// Number of messages from `start` (inclusive) to `stop` (exclusive)
+    pub fn count_in_range(&self, start: Option<u64>, stop: Option<u64>) -> usize {
+        self.range(start, stop).len()
+    }
+
+    /// Timestamp and row id of the message at `position`
+    pub fn entry(&self, position: usize) -> Option<(u64, i64)> {
+        Some((*self.timestamps.get(position)?, self.row_ids[position]))
+    }
+
+    /// Timestamps of the first and last message, or `None` without messages
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        Some((*self.timestamps.first()?, *self.timestamps.last()?))
+    }
+}
+
+/// Size and modification time of a database and its write-ahead log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    size: u64,
+    modified: u64,
+    wal_size: u64,
+}
+
+impl Fingerprint {
+    fn of(db_path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(db_path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        let mut wal = db_path.as_os_str().to_os_string();
+        wal.push("-wal");
+        let wal_size = std::fs::metadata(wal).map_or(0, |wal| wal.len());
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+            wal_size,
+        })
+    }
+}
+
+/// Sidecar index of the messages of one database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseIndex {
+    /// The database when it was indexed
+    fingerprint: Fingerprint,
+    /// Indexes of the topic rows of the database, by topic id
+    pub topics: Vec<TopicIndex>,
+}
+
+impl DatabaseIndex {
+    /// Index the messages of the database at `db_path`, open as `conn`
+    pub fn build(conn: &SqliteConnection, db_path: &Path) -> Result<Self> {
+        let fingerprint = Fingerprint::of(db_path)?;
+
+        let mut topics = Vec::new();
+        let mut positions = HashMap::new();
+        let mut stmt = conn.prepare("SELECT id, name, type FROM topics ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        for row in rows {
+            let (topic_id, topic, message_type) = row?;
+            positions.insert(topic_id, topics.len());
+            topics.push(TopicIndex {
+                topic_id,
+                topic,
+                message_type,
+                ..TopicIndex::default()
+            });
+        }
+
+        // Iteration order within a topic: timestamp, then storage order
+        let mut stmt =
+            conn.prepare("SELECT topic_id, timestamp, id FROM messages ORDER BY timestamp, id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get(2)?))
+        })?;
+        for row in rows {
+            let (topic_id, timestamp, row_id) = row?;
+            let Some(&position) = positions.get(&topic_id) else {
+                continue;
+            };
+            let topic = &mut topics[position];
+            topic.timestamps.push(timestamp as u64);
+            topic.row_ids.push(row_id);
+        }
+
+        Ok(Self {
+            fingerprint,
+            topics,
+        })
+    }
+
+    /// Load the index of the database at `db_path`, `None` if it has none or the
+    /// database changed since it was indexed
+    pub fn load(db_path: &Path) -> Option<Self> {
+        let data = std::fs::read(index_path(db_path)).ok()?;
+        let index = Self::from_bytes(&data).ok()?;
+        (Fingerprint::of(db_path).ok()? == index.fingerprint).then_some(index)
+    }
+
+    /// Write the index next to the database at `db_path`, returning its path
+    pub fn write(&self, db_path: &Path) -> Result<PathBuf> {
+        let path = index_path(db_path);
+        std::fs::write(&path, self.to_bytes())?;
+        Ok(path)
+    }
+
+    /// Index of the topic row `topic_id`
+    pub fn topic(&self, topic_id: i64) -> Option<&TopicIndex> {
+        self.topics.iter().find(|topic| topic.topic_id == topic_id)
+    }
+
+    /// Number of messages in the database
+    pub fn message_count(&self) -> u64 {
+        self.topics.iter().map(|topic| topic.len() as u64).sum()
+    }
+
+    /// Timestamps of the first and last message, or `None` without messages
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        let ranges = self.topics.iter().filter_map(TopicIndex::time_range);
+        ranges.reduce(|(first, last), (start, end)| (first.min(start), last.max(end)))
+    }
+
+    /// Serialize the index, all integers little-endian
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        let fingerprint = &self.fingerprint;
+        for value in [fingerprint.size, fingerprint.modified, fingerprint.wal_size] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&(self.topics.len() as u32).to_le_bytes());
+        for topic in &self.topics {
+            data.extend_from_slice(&topic.topic_id.to_le_bytes());
+            for text in [&topic.topic, &topic.message_type] {
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+            }
+            data.extend_from_slice(&(topic.len() as u64).to_le_bytes());
+            for timestamp in &topic.timestamps {
+                data.extend_from_slice(&timestamp.to_le_bytes());
+            }
+            for row_id in &topic.row_ids {
+                data.extend_from_slice(&row_id.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    /// Parse an index serialized by [`DatabaseIndex::to_bytes`]
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        let rest = data.strip_prefix(MAGIC.as_slice()).ok_or_else(invalid)?;
+        let mut bytes = Bytes(rest);
+        let fingerprint = Fingerprint {
+            size: bytes.u64()?,
+            modified: bytes.u64()?,
+            wal_size: bytes.u64()?,
+        };
+        let topic_count = bytes.u32()?;
+        let mut topics = Vec::new();
+        for _ in 0..topic_count {
+            let topic_id = bytes.u64()? as i64;
+            let topic = bytes.string()?;
+            let message_type = bytes.string()?;
+            // Both columns must be present before allocating for them
+            let count = usize::try_from(bytes.u64()?)
+                .ok()
+                .filter(|count| {
+                    count
+                        .checked_mul(16)
+                        .is_some_and(|len| len <= bytes.0.len())
+                })
+                .ok_or_else(invalid)?;
+            let timestamps = (0..count).map(|_| bytes.u64()).collect::<Result<_>>()?;
+            let row_ids = (0..count)
+                .map(|_| Ok(bytes.u64()? as i64))
+                .collect::<Result<_>>()?;
+            topics.push(TopicIndex {
+                topic_id,
+                topic,
+                message_type,
+                timestamps,
+                row_ids,
+            });
+        }
+        Ok(Self {
+            fingerprint,
+            topics,
+        })
+    }
+}
+
+/// Error for index files that cannot be parsed
+fn invalid() -> ReaderError {
+    ReaderError::generic("Invalid or truncated SQLite3 index file")
+}
+
+/// Unread rest of a serialized index
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(topic_id: i64, timestamps: &[u64]) -> TopicIndex {
+        TopicIndex {
+            topic_id,
+            topic: format!("/topic_{topic_id}"),
+            message_type: "std_msgs/msg/String".to_string(),
+            timestamps: timestamps.to_vec(),
+            row_ids: (1..=timestamps.len() as i64).collect(),
+        }
+    }
+
+    #[test]
+    fn test_topic_index_ranges() {
+        let index = topic(1, &[10, 20, 20, 30, 40]);
+        assert_eq!(index.count_in_range(None, None), 5);
+        assert_eq!(index.range(Some(20), Some(40)), 1..4);
+        assert_eq!(index.count_in_range(Some(21), Some(30)), 0);
+        assert_eq!(index.count_in_range(Some(50), Some(10)), 0);
+        assert_eq!(index.entry(3), Some((30, 4)));
+        assert_eq!(index.entry(5), None);
+        assert_eq!(index.time_range(), Some((10, 40)));
+    }
+
+    #[test]
+    fn test_index_round_trip_and_truncation() {
+        let index = DatabaseIndex {
+            fingerprint: Fingerprint {
+                size: 4096,
+                modified: 1_700_000_000_000_000_000,
+                wal_size: 0,
+            },
+            topics: vec![topic(1, &[5, 7]), topic(3, &[]), topic(4, &[6])],
+        };
+        let data = index.to_bytes();
+        assert_eq!(DatabaseIndex::from_bytes(&data).unwrap(), index);
+        assert_eq!(index.message_count(), 3);
+        assert_eq!(index.time_range(), Some((5, 7)));
+        for len in 0..data.len() {
+            assert!(DatabaseIndex::from_bytes(&data[..len]).is_err());
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Time synchronization of messages across topics
+//!
+//! [`Synchronizer`] pairs messages from several topics whose timestamps match, like
+//! the `ExactTime` and `ApproximateTime` policies of ROS `message_filters`. Typical
+//! uses are stereo pairs from two cameras or a camera frame with the closest IMU sample.
+//!
+//! The approximate policy works on the merged, time-ordered stream: the topic whose
+//! oldest queued message is the newest becomes the pivot, every other topic contributes
+//! its message closest to the pivot, and the set is emitted if its spread is within the
+//! slop. Otherwise the pivot message cannot be matched and is dropped. Messages older
+//! than an emitted set are discarded, so every message appears in at most one set.
+
+use crate::error::{BagError, Result};
+use crate::export::header_stamp;
+use crate::reader::Reader;
+use crate::types::{Connection, Message};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Default number of messages queued per topic while waiting for a match
+pub const DEFAULT_QUEUE_SIZE: usize = 1000;
+
+/// Timestamp the messages are matched on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncTime {
+    /// Time the message was recorded
+    #[default]
+    BagTime,
+    /// `header.stamp` of the message (all topics must have a header)
+    HeaderStamp,
+}
+
+/// How messages are matched across topics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncPolicy {
+    /// Largest allowed difference between the timestamps of a set in nanoseconds
+    pub slop: u64,
+    /// Timestamp the messages are matched on
+    pub time: SyncTime,
+    /// Messages queued per topic; the oldest are dropped when a topic lags behind
+    pub queue_size: usize,
+}
+
+impl SyncPolicy {
+    /// Match only messages with identical timestamps
+    pub fn exact_time() -> Self {
+        Self {
+            slop: 0,
+            time: SyncTime::default(),
+            queue_size: DEFAULT_QUEUE_SIZE,
+        }
+    }
+
+    /// Match messages whose timestamps differ by at most `slop`
+    pub fn approximate_time(slop: Duration) -> Self {
+        Self {
+            slop: u64::try_from(slop.as_nanos()).unwrap_or(u64::MAX),
+            ..Self::exact_time()
+        }
+    }
+
+    /// Match on `header.stamp` instead of the bag timestamp
+    pub fn header_stamps(mut self) -> Self {
+        self.time = SyncTime::HeaderStamp;
+        self
+    }
+
+    /// Set the number of messages queued per topic
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+}
+
+/// Iterator over sets of time-matched messages, one message per topic
+///
+/// Created by [`Reader::synchronize`] or [`Synchronizer::new`]. Each set holds the
+/// messages in the order of the requested topics.
+pub struct Synchronizer<'a> {
+    /// Time-ordered messages of the synchronized topics
+    source: Box<dyn Iterator<Item = Result<Message>> + 'a>,
+    /// Index of each topic in the emitted sets
+    topic_index: HashMap<String, usize>,
+    /// Queued (time, message) per topic, sorted by time
+    queues: Vec<VecDeque<(u64, Message)>>,
+    policy: SyncPolicy,
+    /// Whether the source has no more messages
+    exhausted: bool,
+}
+
+impl<'a> Synchronizer<'a> {
+    /// Synchronize `topics` over a time-ordered message stream
+    pub fn new(
+        source: impl Iterator<Item = Result<Message>> + 'a,
+        topics: &[&str],
+        policy: SyncPolicy,
+    ) -> Result<Self> {
+        if topics.is_empty() {
+            return Err(BagError::generic("At least one topic must be synchronized"));
+        }
+        if policy.queue_size == 0 {
+            return Err(BagError::generic(
+                "Synchronizer queue size must be at least 1",
+            ));
+        }
+        let mut topic_index = HashMap::new();
+        for (index, topic) in topics.iter().enumerate() {
+            if topic_index.insert(topic.to_string(), index).is_some() {
+                return Err(BagError::generic(format!(
+                    "Topic '{topic}' is synchronized more than once"
+                )));
+            }
+        }
+
+        Ok(Self {
+            source: Box::new(source),
+            topic_index,
+            queues: vec![VecDeque::new(); topics.len()],
+            policy,
+            exhausted: false,
+        })
+    }
+
+    /// Queue a message of a synchronized topic
+    fn push(&mut self, message: Message) -> Result<()> {
+        let Some(&index) = self.topic_index.get(&message.topic) else {
+            return Ok(());
+        };
+        let time = match self.policy.time {
+            SyncTime::BagTime => message.timestamp,
+            SyncTime::HeaderStamp => header_stamp(&message.data).ok_or_else(|| {
+                BagError::invalid_message_data(format!(
+                    "Message on '{}' has no valid header stamp",
+                    message.topic
+                ))
+            })?,
+        };
+
+        let queue = &mut self.queues[index];
+        let position = queue.partition_point(|(queued, _)| *queued <= time);
+        queue.insert(position, (time, message));
+        if queue.len() > self.policy.queue_size {
+            queue.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Emit the next matched set from the queues, or `None` if more messages are needed
+    fn try_match(&mut self) -> Option<Vec<Message>> {
+        loop {
+            if self.queues.iter().any(VecDeque::is_empty) {
+                return None;
+            }
+
+            // The topic whose oldest message is the newest
+            let (pivot, pivot_time) = self
+                .queues
+                .iter()
+                .map(|queue| queue[0].0)
+                .enumerate()
+                .max_by_key(|(_, time)| *time)?;
+
+            // Every other topic needs a message at or after the pivot to pick the closest
+            let waiting = !self.exhausted
+                && self
+                    .queues
+                    .iter()
+                    .any(|queue| queue[queue.len() - 1].0 < pivot_time);
+            if waiting {
+                return None;
+            }
+
+            let chosen: Vec<usize> = self
+                .queues
+                .iter()
+                .map(|queue| {
+                    let after = queue.partition_point(|(time, _)| *time < pivot_time);
+                    match after.checked_sub(1) {
+                        Some(before)
+                            if after == queue.len()
+                                || pivot_time - queue[before].0 <= queue[after].0 - pivot_time =>
+                        {
+                            before
+                        }
+                        _ => after,
+                    }
+                })
+                .collect();
+            let times = chosen
+                .iter()
+                .zip(&self.queues)
+                .map(|(&index, queue)| queue[index].0);
+            let spread = times.clone().max()? - times.min()?;
+
+            if spread <= self.policy.slop {
+                let set = chosen
+                    .iter()
+                    .zip(&mut self.queues)
+                    .map(|(&index, queue)| {
+                        let (_, message) = queue.drain(..=index).next_back().unwrap();
+                        message
+                    })
+                    .collect();
+                return Some(set);
+            }
+            self.queues[pivot].pop_front();
+        }
+    }
+}
+
+impl Iterator for Synchronizer<'_> {
+    type Item = Result<Vec<Message>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(set) = self.try_match() {
+                return Some(Ok(set));
+            }
+            if self.exhausted {
+                return None;
+            }
+            match self.source.next() {
+                Some(Ok(message)) => {
+                    if let Err(e) = self.push(message) {
+                        return Some(Err(e));
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.exhausted = true,
+            }
+        }
+    }
+}
+
+impl Reader {
+    /// Iterate over sets of messages from `topics` matched by `policy`
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::sync::SyncPolicy;
+    /// use rosbags_rs::Reader;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut reader = Reader::new("path/to/bag")?;
+    /// reader.open()?;
+    ///
+    /// let policy = SyncPolicy::approximate_time(Duration::from_millis(5)).header_stamps();
+    /// for set in reader.synchronize(&["/left/image_raw", "/right/image_raw"], policy)? {
+    ///     let set = set?;
+    ///     println!("stereo pair at {}", set[0].timestamp);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn synchronize(&self, topics: &[&str], policy: SyncPolicy) -> Result<Synchronizer<'_>> {
+        for topic in topics {
+            if !self.connections().iter().any(|c| c.topic == *topic) {
+                return Err(BagError::connection_not_found(*topic));
+            }
+        }
+        let connections: Vec<Connection> = self
+            .connections()
+            .iter()
+            .filter(|c| topics.contains(&c.topic.as_str()))
+            .cloned()
+            .collect();
+        let messages = self.messages_filtered(Some(&connections), None, None)?;
+        Synchronizer::new(messages, topics, policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, Time, ToCdr};
+
+    fn message(topic: &str, timestamp: u64) -> Result<Message> {
+        Ok(Message {
+            connection: Connection::new(topic, "std_msgs/msg/Header").unwrap(),
+            topic: topic.to_string(),
+            timestamp,
+            publish_time: timestamp,
+            sequence: 0,
+            data: Header {
+                stamp: Time::from_nanos(timestamp / 10),
+                frame_id: String::new(),
+            }
+            .to_cdr_bytes(),
+            file_index: 0,
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn set_topics(set: &[Message]) -> Vec<&str> {
+        set.iter().map(|m| m.topic.as_str()).collect()
+    }
+
+    fn pairs(messages: Vec<Result<Message>>, policy: SyncPolicy) -> Vec<Vec<u64>> {
+        Synchronizer::new(messages.into_iter(), &["/a", "/b"], policy)
+            .unwrap()
+            .map(|set| set.unwrap().iter().map(|m| m.timestamp).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_exact_time_pairs_identical_stamps() {
+        let messages = vec![
+            message("/a", 10),
+            message("/b", 10),
+            message("/a", 20),
+            message("/b", 25),
+            message("/b", 30),
+            message("/a", 30),
+        ];
+        assert_eq!(
+            pairs(messages, SyncPolicy::exact_time()),
+            [vec![10, 10], vec![30, 30]]
+        );
+    }
+
+    #[test]
+    fn test_approximate_time_picks_closest_within_slop() {
+        // /b runs at twice the rate of /a; /a at 100 has no partner within the slop
+        let messages = vec![
+            message("/a", 0),
+            message("/b", 3),
+            message("/b", 48),
+            message("/a", 50),
+            message("/b", 52),
+            message("/a", 100),
+            message("/b", 160),
+            message("/a", 200),
+            message("/b", 201),
+        ];
+        let policy = SyncPolicy::approximate_time(Duration::from_nanos(5));
+        assert_eq!(
+            pairs(messages, policy),
+            [vec![0, 3], vec![50, 48], vec![200, 201]]
+        );
+    }
+
+    #[test]
+    fn test_header_stamps_and_queue_size() {
+        // Header stamps are a tenth of the bag time, so only they fall within the slop
+        let messages = || vec![message("/a", 1000), message("/b", 1040)];
+        let slop = Duration::from_nanos(5);
+        assert!(pairs(messages(), SyncPolicy::approximate_time(slop)).is_empty());
+        let policy = SyncPolicy::approximate_time(slop).header_stamps();
+        assert_eq!(pairs(messages(), policy), [vec![1000, 1040]]);
+
+        // A queue of one keeps only the newest /a message
+        let messages = vec![message("/a", 10), message("/a", 20), message("/b", 11)];
+        let policy = SyncPolicy::approximate_time(Duration::from_nanos(100)).queue_size(1);
+        assert_eq!(pairs(messages, policy), [vec![20, 11]]);
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_reader_synchronize() {
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        let topics = ["/test/geometry_msgs/pose", "/test/std_msgs/string"];
+        let sets: Vec<Vec<Message>> = reader
+            .synchronize(
+                &topics,
+                SyncPolicy::approximate_time(Duration::from_secs(3)),
+            )
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        // Both topics have two messages; the second pose is closest to the first string
+        assert_eq!(sets.len(), 1);
+        assert_eq!(set_topics(&sets[0]), topics);
+        assert_eq!(sets[0][0].timestamp - sets[0][1].timestamp, 2_300_000_000);
+
+        assert!(matches!(
+            reader.synchronize(&["/missing"], SyncPolicy::exact_time()),
+            Err(BagError::ConnectionNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,736 @@
+//! Following bags that are still being recorded
+//!
+//! [`Reader::tail`] follows a bag directory like `tail -f`: the storage files are
+//! read again as the recorder commits messages to them, split files are picked up as
+//! they appear, and new messages are yielded as they arrive. SQLite3 databases are
+//! read by message row id, so each poll only queries the rows committed since the
+//! last one. MCAP files are parsed up to their last complete record, so messages
+//! appear chunk by chunk as the recorder writes chunks out.
+//!
+//! Messages of a file come in storage order and files in the order they appeared,
+//! which is recording order rather than strict timestamp order. No `metadata.yaml`
+//! is needed; connections are read from the storage as topics appear.
+
+use crate::error::{ReaderError, Result};
+use crate::reader::{decompress_payload, is_compressed, Reader};
+use crate::types::{
+    Connection, Message, MessageDefinition, MessageDefinitionFormat, StoragePlugin,
+};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Default [`Tail::poll_interval`]
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Follower of a bag being recorded, created by [`Reader::tail`]
+///
+/// Iterating blocks until the next message is committed, polling the storage every
+/// [`Tail::poll_interval`], and ends only after [`Tail::idle_timeout`] without new
+/// messages. [`Tail::poll`] reads what is available without waiting, for callers
+/// running their own loop, e.g. a dashboard refreshing at a fixed rate.
+pub struct Tail {
+    /// Bag directory, or the single storage file followed
+    path: PathBuf,
+    /// Storage files followed, in the order they appeared
+    files: Vec<TailFile>,
+    /// Connections of all files
+    topics: Topics,
+    /// Time between polls of the storage while iterating
+    poll_interval: Duration,
+    /// Time without new messages after which iteration ends, never if `None`
+    idle_timeout: Option<Duration>,
+    /// Whether messages in the bag before the first poll are skipped
+    skip_existing: bool,
+    /// Whether the storage was polled yet
+    polled: bool,
+    /// Messages read but not yet yielded by the iterator
+    pending: VecDeque<Message>,
+    /// Time of the last poll that found messages, or of the first poll
+    last_message: Option<Instant>,
+}
+
+impl Tail {
+    /// Follow the bag directory or single storage file at `path`
+    pub(crate) fn new(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(ReaderError::BagNotFound {
+                path: path.to_path_buf(),
+            });
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            files: Vec::new(),
+            topics: Topics::default(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            idle_timeout: None,
+            skip_existing: false,
+            polled: false,
+            pending: VecDeque::new(),
+            last_message: None,
+        })
+    }
+
+    /// Set the time between polls of the storage while iterating
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// End iteration after `timeout` without new messages, e.g. once the recorder
+    /// stopped; by default iteration never ends
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Only read messages of `topics`
+    pub fn topics<I, S>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.topics.selected = Some(topics.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Skip the messages already in the bag, only yielding those recorded later
+    pub fn skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Get the connections seen so far, with the number of messages read of each
+    pub fn connections(&self) -> &[Connection] {
+        &self.topics.connections
+    }
+
+    /// Get the paths of the storage files followed so far, in the order they appeared
+    ///
+    /// The [`Message::file_index`] of a message indexes this list.
+    pub fn file_paths(&self) -> Vec<&Path> {
+        self.files.iter().map(|file| file.path.as_path()).collect()
+    }
+
+    /// Read the messages committed since the last poll, without waiting
+    ///
+    /// Looks for new storage files first, then reads every file from where the last
+    /// poll stopped. Messages already read by the iterator but not yet yielded by it
+    /// come first.
+    pub fn poll(&mut self) -> Result<Vec<Message>> {
+        self.read_storage()?;
+        Ok(self.pending.drain(..).collect())
+    }
+
+    /// Read new messages into `pending`, returning how many were read
+    fn read_storage(&mut self) -> Result<usize> {
+        self.discover_files()?;
+        let before = self.pending.len();
+        for (file_index, file) in self.files.iter_mut().enumerate() {
+            let reading = Reading {
+                file_index,
+                topics: &mut self.topics,
+                pending: &mut self.pending,
+            };
+            file.source.read(&file.path, reading)?;
+        }
+        if !self.polled {
+            self.polled = true;
+            if self.skip_existing {
+                self.pending.truncate(before);
+            }
+        }
+        Ok(self.pending.len() - before)
+    }
+
+    /// Start following storage files that appeared since the last poll
+    ///
+    /// Files are recognized by their magic bytes like [`crate::storage::detect_format`];
+    /// those of formats whose feature is not enabled are left out.
+    fn discover_files(&mut self) -> Result<()> {
+        let mut found = Vec::new();
+        if self.path.is_dir() {
+            for entry in std::fs::read_dir(&self.path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    found.push(entry.path());
+                }
+            }
+            found.sort();
+        } else {
+            found.push(self.path.clone());
+        }
+
+        for path in found {
+            if self.files.iter().any(|file| file.path == path) {
+                continue;
+            }
+            let source = match crate::storage::detect_format(&path) {
+                #[cfg(feature = "sqlite")]
+                Some(StoragePlugin::Sqlite3) => Source::Sqlite(SqliteSource::default()),
+                #[cfg(feature = "mcap")]
+                Some(StoragePlugin::Mcap) => Source::Mcap(McapSource::default()),
+                _ => continue,
+            };
+            self.files.push(TailFile { path, source });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Tail {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Some(Ok(message));
+            }
+            match self.read_storage() {
+                Ok(0) => {}
+                Ok(_) => {
+                    self.last_message = Some(Instant::now());
+                    continue;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            let idle_since = *self.last_message.get_or_insert_with(Instant::now);
+            if self
+                .idle_timeout
+                .is_some_and(|timeout| idle_since.elapsed() >= timeout)
+            {
+                return None;
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+impl std::fmt::Debug for Tail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tail")
+            .field("path", &self.path)
+            .field("files", &self.file_paths())
+            .field("poll_interval", &self.poll_interval)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Connections of the followed files, shared by all files so that a topic recorded
+/// across split files keeps one connection
+#[derive(Debug, Default)]
+struct Topics {
+    /// Connections in the order their topics appeared, numbered from 1
+    connections: Vec<Connection>,
+    /// Topics read, all if `None`
+    selected: Option<Vec<String>>,
+}
+
+impl Topics {
+    /// Index of the connection of `topic` with `message_type`, added on first sight,
+    /// `None` if the topic is not read
+    fn connection(
+        &mut self,
+        topic: &str,
+        message_type: &str,
+        serialization_format: &str,
+        definition: MessageDefinition,
+    ) -> Option<usize> {
+        if let Some(selected) = &self.selected {
+            if !selected.iter().any(|selected| selected == topic) {
+                return None;
+            }
+        }
+        let existing = self
+            .connections
+            .iter()
+            .position(|c| c.topic == topic && c.message_type == message_type);
+        if let Some(index) = existing {
+            let connection = &mut self.connections[index];
+            if connection.message_definition.data.is_empty() {
+                connection.message_definition = definition;
+            }
+            return Some(index);
+        }
+        self.connections.push(Connection {
+            id: self.connections.len() as u32 + 1,
+            topic: topic.to_string(),
+            message_type: message_type.to_string(),
+            message_definition: definition,
+            type_description_hash: String::new(),
+            message_count: 0,
+            serialization_format: serialization_format.to_string(),
+            offered_qos_profiles: Vec::new(),
+            metadata: Default::default(),
+        });
+        Some(self.connections.len() - 1)
+    }
+}
+
+/// Message definition stored with `encoding`
+fn definition(encoding: &str, data: String) -> MessageDefinition {
+    let format = match encoding {
+        "ros2msg" => MessageDefinitionFormat::Msg,
+        "ros2idl" => MessageDefinitionFormat::Idl,
+        _ => MessageDefinitionFormat::None,
+    };
+    MessageDefinition { format, data }
+}
+
+/// A followed storage file
+struct TailFile {
+    /// Path of the file
+    path: PathBuf,
+    /// Read position in the file
+    source: Source,
+}
+
+/// State a storage file is read into
+struct Reading<'a> {
+    /// Index of the file in [`Tail::file_paths`]
+    file_index: usize,
+    /// Connections of all files
+    topics: &'a mut Topics,
+    /// Messages read but not yet yielded
+    pending: &'a mut VecDeque<Message>,
+}
+
+impl Reading<'_> {
+    /// Queue a message of the connection at `index`, decompressing payloads of bags
+    /// compressed per message
+    fn push(
+        &mut self,
+        index: usize,
+        timestamp: u64,
+        publish_time: u64,
+        sequence: u32,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let data = if is_compressed(&data) {
+            decompress_payload(&data)?
+        } else {
+            data
+        };
+        let connection = &mut self.topics.connections[index];
+        connection.message_count += 1;
+        self.pending.push_back(Message {
+            connection: connection.clone(),
+            topic: connection.topic.clone(),
+            timestamp,
+            publish_time,
+            sequence,
+            data,
+            file_index: self.file_index,
+        });
+        Ok(())
+    }
+}
+
+/// Read position in a followed storage file, by format
+enum Source {
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteSource),
+    #[cfg(feature = "mcap")]
+    Mcap(McapSource),
+}
+
+impl Source {
+    /// Queue the messages committed to the file at `path` since the last read
+    #[allow(unused_variables)]
+    fn read(&mut self, path: &Path, reading: Reading<'_>) -> Result<()> {
+        match *self {
+            #[cfg(feature = "sqlite")]
+            Source::Sqlite(ref mut source) => source.read(path, reading),
+            #[cfg(feature = "mcap")]
+            Source::Mcap(ref mut source) => source.read(path, reading),
+        }
+    }
+}
+
+/// Read position in a followed SQLite3 database
+#[cfg(feature = "sqlite")]
+#[derive(Default)]
+struct SqliteSource {
+    /// Read-only connection, opened once the recorder created the tables
+    conn: Option<rusqlite::Connection>,
+    /// Whether the database stores message definitions (schema version 4+)
+    has_definitions: bool,
+    /// Connection index of each topic row read so far, `None` if not read
+    topics: std::collections::HashMap<i64, Option<usize>>,
+    /// Largest topic row id read
+    last_topic: i64,
+    /// Largest message row id read
+    last_message: i64,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSource {
+    /// How long a poll waits for the recorder to finish committing a transaction
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Queue the rows committed since the last read
+    ///
+    /// Rows are read by increasing id, which rosbag2 and this crate's writer assign
+    /// in insertion order.
+    fn read(&mut self, path: &Path, mut reading: Reading<'_>) -> Result<()> {
+        if !self.connect(path)? {
+            return Ok(());
+        }
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, name, type, serialization_format FROM topics WHERE id > ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([self.last_topic], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, name, message_type, serialization_format) = row?;
+            let definition = if self.has_definitions {
+                conn.prepare_cached(
+                    "SELECT encoding, encoded_message_definition FROM message_definitions
+                     WHERE topic_type = ?1 ORDER BY id LIMIT 1",
+                )?
+                .query_row([&message_type], |row| {
+                    Ok(definition(&row.get::<_, String>(0)?, row.get(1)?))
+                })
+                .unwrap_or_default()
+            } else {
+                MessageDefinition::default()
+            };
+            let index =
+                reading
+                    .topics
+                    .connection(&name, &message_type, &serialization_format, definition);
+            self.topics.insert(id, index);
+            self.last_topic = id;
+        }
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, topic_id, timestamp, data FROM messages WHERE id > ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([self.last_message], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, topic_id, timestamp, data) = row?;
+            self.last_message = id;
+            if let Some(&Some(index)) = self.topics.get(&topic_id) {
+                let timestamp = timestamp as u64;
+                reading.push(index, timestamp, timestamp, 0, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect to the database once the recorder created its tables, returning
+    /// whether it is connected
+    fn connect(&mut self, path: &Path) -> Result<bool> {
+        use rusqlite::OpenFlags;
+
+        if self.conn.is_none() {
+            let conn = rusqlite::Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+            conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+            let tables: Vec<String> = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            let has = |name: &str| tables.iter().any(|table| table == name);
+            if !has("topics") || !has("messages") {
+                return Ok(false);
+            }
+            self.has_definitions = has("message_definitions");
+            self.conn = Some(conn);
+        }
+        Ok(true)
+    }
+}
+
+/// Read position in a followed MCAP file
+#[cfg(feature = "mcap")]
+#[derive(Default)]
+struct McapSource {
+    /// Bytes of the file parsed so far, including the leading magic
+    offset: u64,
+    /// Message type and definition of each schema id
+    schemas: std::collections::HashMap<u16, (String, MessageDefinition)>,
+    /// Connection index of each channel id, `None` if not read
+    channels: std::collections::HashMap<u16, Option<usize>>,
+    /// Whether the data section ended, so that nothing more is written to it
+    finished: bool,
+}
+
+#[cfg(feature = "mcap")]
+impl McapSource {
+    /// Queue the messages of the records completed since the last read
+    ///
+    /// The file is read from the end of the last complete record; a record still
+    /// being written is read again by the next poll once it is complete.
+    fn read(&mut self, path: &Path, mut reading: Reading<'_>) -> Result<()> {
+        use crate::storage::mcap::read_error;
+        use std::io::{Read, Seek, SeekFrom};
+
+        if self.finished {
+            return Ok(());
+        }
+        let mut file = std::fs::File::open(path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        let mut data = buffer.as_slice();
+        if self.offset == 0 {
+            let magic = crate::storage::MCAP_MAGIC;
+            if data.len() < magic.len() {
+                return Ok(());
+            }
+            if !data.starts_with(magic) {
+                return Err(ReaderError::generic(format!(
+                    "{} is not an MCAP file",
+                    path.display()
+                )));
+            }
+            data = &data[magic.len()..];
+            self.offset = magic.len() as u64;
+        }
+
+        // Records are an opcode, a little-endian length and the body
+        let mut complete = 0;
+        while let Some(header) = data.get(complete..complete + 9) {
+            let length = u64::from_le_bytes(header[1..].try_into().unwrap());
+            let end = usize::try_from(length)
+                .ok()
+                .and_then(|length| (complete + 9).checked_add(length));
+            match end {
+                Some(end) if end <= data.len() => complete = end,
+                _ => break,
+            }
+        }
+
+        for record in mcap::read::LinearReader::sans_magic(&data[..complete]) {
+            let record = record.map_err(|e| read_error("Failed to read MCAP record", e))?;
+            if let mcap::records::Record::Chunk { header, data } = record {
+                let records = mcap::read::ChunkReader::new(header, &data)
+                    .map_err(|e| read_error("Failed to read MCAP chunk", e))?;
+                for record in records {
+                    let record = record.map_err(|e| read_error("Failed to read MCAP chunk", e))?;
+                    self.visit(record, &mut reading)?;
+                }
+            } else {
+                self.visit(record, &mut reading)?;
+            }
+            if self.finished {
+                break;
+            }
+        }
+        self.offset += complete as u64;
+        Ok(())
+    }
+
+    /// Handle a record of the data section
+    fn visit(
+        &mut self,
+        record: mcap::records::Record<'_>,
+        reading: &mut Reading<'_>,
+    ) -> Result<()> {
+        use mcap::records::Record;
+
+        match record {
+            Record::Schema { header, data } => {
+                let data = String::from_utf8_lossy(&data).into_owned();
+                let definition = definition(&header.encoding, data);
+                self.schemas.insert(header.id, (header.name, definition));
+            }
+            Record::Channel(channel) => {
+                let (message_type, definition) = match self.schemas.get(&channel.schema_id) {
+                    Some((name, definition)) => (name.as_str(), definition.clone()),
+                    None => (
+                        channel.message_encoding.as_str(),
+                        MessageDefinition::default(),
+                    ),
+                };
+                let index =
+                    reading
+                        .topics
+                        .connection(&channel.topic, message_type, "cdr", definition);
+                self.channels.insert(channel.id, index);
+            }
+            Record::Message { header, data } => {
+                let Some(&index) = self.channels.get(&header.channel_id) else {
+                    return Err(ReaderError::generic(format!(
+                        "Failed to read MCAP message: unknown channel {}",
+                        header.channel_id
+                    )));
+                };
+                if let Some(index) = index {
+                    reading.push(
+                        index,
+                        header.log_time,
+                        header.publish_time,
+                        header.sequence,
+                        data.into_owned(),
+                    )?;
+                }
+            }
+            Record::DataEnd(_) | Record::Footer(_) => self.finished = true,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Reader {
+    /// Follow the bag at `bag_path` while it is being recorded, like `tail -f`
+    ///
+    /// `bag_path` is a bag directory, whose split files are followed as they appear,
+    /// or a single `.db3` or `.mcap` file. The directory may still be empty. See
+    /// [`Tail`] for how long iteration waits for new messages.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::Reader;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let tail = Reader::tail("path/to/bag")?
+    ///     .topics(["/imu/data"])
+    ///     .idle_timeout(Duration::from_secs(10));
+    /// for message in tail {
+    ///     let message = message?;
+    ///     println!("{} at {}", message.topic, message.timestamp);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tail<P: AsRef<Path>>(bag_path: P) -> Result<Tail> {
+        Tail::new(bag_path.as_ref())
+    }
+}
+
+#[cfg(all(test, feature = "default"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// CDR of a `std_msgs/msg/String` holding `text`
+    fn string_payload(text: &str) -> Vec<u8> {
+        let mut payload = vec![0, 1, 0, 0];
+        payload.extend_from_slice(&(text.len() as u32 + 1).to_le_bytes());
+        payload.extend_from_slice(text.as_bytes());
+        payload.push(0);
+        payload
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_tail_follows_sqlite_writer() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("live");
+        let mut writer = crate::Writer::builder(&bag_path).open().unwrap();
+        let mut tail = Reader::tail(&bag_path).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+
+        let chatter = writer
+            .add_connection(
+                "/chatter".to_string(),
+                "std_msgs/msg/String".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        for timestamp in 1..=3 {
+            let payload = string_payload(&format!("hello {timestamp}"));
+            writer.write(&chatter, timestamp, &payload).unwrap();
+        }
+        writer.flush_buffer().unwrap();
+        let messages = tail.poll().unwrap();
+        let timestamps: Vec<_> = messages.iter().map(|m| m.timestamp).collect();
+        assert_eq!(timestamps, [1, 2, 3]);
+        let decoded = messages[2].decode_dynamic().unwrap();
+        let data = decoded.get("data").and_then(|v| v.as_value()).cloned();
+        assert_eq!(data, Some(crate::dynamic::Value::String("hello 3".into())));
+
+        // Only messages committed since the last poll are read
+        writer
+            .write(&chatter, 4, &string_payload("hello 4"))
+            .unwrap();
+        writer.flush_buffer().unwrap();
+        let messages = tail.poll().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].timestamp, 4);
+        assert_eq!(tail.connections()[0].message_count, 4);
+
+        writer.close().unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+
+        // Following from the end skips what is already recorded
+        let mut tail = Reader::tail(&bag_path).unwrap().skip_existing(true);
+        assert!(tail.poll().unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_tail_reads_mcap_up_to_complete_records() {
+        let source = Path::new("tests/test_bags/test_bag_mcap");
+        let mut reader = Reader::new(source).unwrap();
+        reader.open().unwrap();
+        let mut expected: Vec<_> = reader
+            .raw_messages()
+            .unwrap()
+            .map(|m| m.unwrap())
+            .map(|m| (m.connection.topic, m.timestamp, m.raw_data))
+            .collect();
+        expected.sort();
+
+        // The recording grows in pieces that end mid-record
+        let data = fs::read(source.join("test_bag_mcap.mcap")).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bag_0.mcap");
+        let mut tail = Reader::tail(temp_dir.path()).unwrap();
+        let mut messages = Vec::new();
+        for end in (0..data.len()).step_by(997).chain([data.len()]) {
+            fs::write(&path, &data[..end]).unwrap();
+            messages.extend(tail.poll().unwrap());
+        }
+        let mut read: Vec<_> = messages
+            .into_iter()
+            .map(|m| (m.topic, m.timestamp, m.data))
+            .collect();
+        read.sort();
+        assert_eq!(read, expected);
+
+        // A new split file is followed as it appears
+        fs::copy(
+            "tests/test_bags/test_bag_sqlite3/test_bag_sqlite3.db3",
+            temp_dir.path().join("bag_1.db3"),
+        )
+        .unwrap();
+        let tail = tail
+            .topics(["/test/std_msgs/string"])
+            .idle_timeout(Duration::ZERO);
+        let messages: Vec<_> = tail.map(|m| m.unwrap()).collect();
+        assert!(!messages.is_empty());
+        assert!(messages
+            .iter()
+            .all(|m| m.file_index == 1 && m.topic == "/test/std_msgs/string"));
+    }
+}
@@ -0,0 +1,463 @@
+//! Property-based round-trip testing
+//!
+//! Generators for random connections and messages, and a checker that writes them to
+//! a bag, reads the bag back and compares what was read with what was written. Each
+//! check runs over a [`RoundTripConfig`] of storage plugin, compression and bag
+//! format version; [`RoundTripConfig::all`] lists every combination the enabled
+//! features support. Crates building on this one enable the `test-utils` feature to
+//! fuzz their integrations against the same generators:
+//!
+//! ```no_run
+//! use rosbags_rs::test_utils::{fuzz_round_trips, GeneratedBag, RoundTripConfig, Rng};
+//!
+//! # fn main() -> rosbags_rs::Result<()> {
+//! let dir = std::env::temp_dir().join("round_trips");
+//! // 20 random bags through every storage, compression and version
+//! fuzz_round_trips(&dir, 42, 20)?;
+//!
+//! // Or one bag through one configuration
+//! let bag = GeneratedBag::random(&mut Rng::new(7), 3, 100);
+//! bag.check_round_trip(&dir.join("bag"), &RoundTripConfig::default())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Generation is deterministic: the same seed gives the same bag, and failures name
+//! the seed of the failing case.
+
+use crate::error::{BagError, Result};
+use crate::storage::memory;
+use crate::types::{
+    CompressionFormat, CompressionMode, Connection, QosDurability, QosHistory, QosProfile,
+    QosReliability, StoragePlugin,
+};
+use crate::{Reader, Writer};
+use std::path::Path;
+
+/// Message types of generated connections
+pub const GENERATED_TYPES: &[&str] = &[
+    "std_msgs/msg/UInt32",
+    "std_msgs/msg/Float64",
+    "std_msgs/msg/String",
+];
+
+/// Small deterministic random number generator (SplitMix64)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Get the next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a random number below `bound` (0 if `bound` is 0)
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /// Get a random index into a collection of `len` elements
+    pub fn index(&mut self, len: usize) -> usize {
+        self.below(len as u64) as usize
+    }
+
+    /// Get a random boolean
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
+
+/// Generate a connection for `topic` with a random type and QoS profiles
+pub fn random_connection(rng: &mut Rng, topic: &str) -> Result<Connection> {
+    let message_type = GENERATED_TYPES[rng.index(GENERATED_TYPES.len())];
+    let profiles = (0..rng.below(3))
+        .map(|_| QosProfile {
+            history: QosHistory::KeepLast,
+            depth: rng.below(100) as u32 + 1,
+            reliability: if rng.bool() {
+                QosReliability::Reliable
+            } else {
+                QosReliability::BestEffort
+            },
+            durability: if rng.bool() {
+                QosDurability::Volatile
+            } else {
+                QosDurability::TransientLocal
+            },
+            ..QosProfile::default()
+        })
+        .collect();
+    Connection::builder(topic, message_type)
+        .offered_qos_profiles(profiles)
+        .build()
+}
+
+/// Generate a random CDR payload of `message_type`, one of [`GENERATED_TYPES`]
+pub fn random_payload(rng: &mut Rng, message_type: &str) -> Result<Vec<u8>> {
+    // Little-endian CDR encapsulation header
+    let mut data = vec![0x00, 0x01, 0x00, 0x00];
+    match message_type {
+        "std_msgs/msg/UInt32" => data.extend_from_slice(&(rng.next_u64() as u32).to_le_bytes()),
+        "std_msgs/msg/Float64" => {
+            let value = rng.next_u64() as f64 / u64::MAX as f64 * 2e6 - 1e6;
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        "std_msgs/msg/String" => {
+            let text: Vec<u8> = (0..rng.below(40))
+                .map(|_| b' ' + rng.below(95) as u8)
+                .collect();
+            data.extend_from_slice(&(text.len() as u32 + 1).to_le_bytes());
+            data.extend_from_slice(&text);
+            data.push(0);
+        }
+        other => return Err(BagError::message_type_not_found(other)),
+    }
+    Ok(data)
+}
+
+/// A generated message, written on connection `connection` of its [`GeneratedBag`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedMessage {
+    /// Index of the connection in [`GeneratedBag::connections`]
+    pub connection: usize,
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Serialized message
+    pub data: Vec<u8>,
+}
+
+/// Randomly generated connections and messages, in write order
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedBag {
+    /// Connections, one per topic
+    pub connections: Vec<Connection>,
+    /// Messages in the order they are written
+    pub messages: Vec<GeneratedMessage>,
+}
+
+impl GeneratedBag {
+    /// Generate 1 to `max_connections` connections and up to `max_messages` messages
+    ///
+    /// Timestamps are drawn from a narrow range and written out of order, so
+    /// messages share timestamps and the ordering of every backend is exercised.
+    pub fn random(rng: &mut Rng, max_connections: usize, max_messages: usize) -> Self {
+        let count = 1 + rng.index(max_connections.max(1));
+        let connections: Vec<Connection> = (0..count)
+            .map(|index| {
+                let topic = format!("/generated/ns_{}/topic_{index}", rng.below(3));
+                random_connection(rng, &topic).expect("generated connections are valid")
+            })
+            .collect();
+        let start = 1_700_000_000_000_000_000 + rng.below(1_000_000_000);
+        let span = 1 + rng.below(1_000);
+        let messages = (0..rng.index(max_messages + 1))
+            .map(|_| {
+                let connection = rng.index(connections.len());
+                let message_type = &connections[connection].message_type;
+                GeneratedMessage {
+                    connection,
+                    timestamp: start + rng.below(span),
+                    data: random_payload(rng, message_type).expect("generated types are known"),
+                }
+            })
+            .collect();
+        Self {
+            connections,
+            messages,
+        }
+    }
+
+    /// Get the messages in the order readers return them: by timestamp, then
+    /// connection, then write order
+    pub fn read_order(&self) -> Vec<&GeneratedMessage> {
+        let mut messages: Vec<&GeneratedMessage> = self.messages.iter().collect();
+        messages.sort_by_key(|message| (message.timestamp, message.connection));
+        messages
+    }
+
+    /// Write the bag to `bag_path` with `config`, read it back and compare
+    ///
+    /// `bag_path` must not exist yet. The bag is deleted afterwards, also when the
+    /// check fails, so `bag_path` can be reused. Mismatches are returned as errors
+    /// naming the configuration.
+    pub fn check_round_trip(&self, bag_path: &Path, config: &RoundTripConfig) -> Result<()> {
+        let exists = if config.storage == StoragePlugin::Memory {
+            memory::contains(bag_path)
+        } else {
+            bag_path.exists()
+        };
+        if exists {
+            return Err(BagError::generic(format!(
+                "Round trip bag {} already exists",
+                bag_path.display()
+            )));
+        }
+
+        // Everything at `bag_path` from here on was created by this call
+        let result = self
+            .write(bag_path, config)
+            .and_then(|()| self.compare(bag_path));
+        if config.storage == StoragePlugin::Memory {
+            memory::remove(bag_path);
+        } else if bag_path.exists() {
+            std::fs::remove_dir_all(bag_path)?;
+        }
+        result.map_err(|e| BagError::generic(format!("Round trip with {config:?} failed: {e}")))
+    }
+
+    /// Write the bag to `bag_path` with `config`
+    fn write(&self, bag_path: &Path, config: &RoundTripConfig) -> Result<()> {
+        let (mode, format) = config.compression;
+        let mut writer = Writer::builder(bag_path)
+            .storage(config.storage)
+            .version(config.version)
+            .compression(mode, format)
+            .open()?;
+        let connections = self
+            .connections
+            .iter()
+            .map(|connection| writer.add_connection_from(connection))
+            .collect::<Result<Vec<_>>>()?;
+        for message in &self.messages {
+            writer.write(
+                &connections[message.connection],
+                message.timestamp,
+                &message.data,
+            )?;
+        }
+        writer.close()
+    }
+
+    /// Compare the bag at `bag_path` with the generated one
+    fn compare(&self, bag_path: &Path) -> Result<()> {
+        let reader = Reader::builder(bag_path).open()?;
+        let mismatch =
+            |what: &str, expected: &dyn std::fmt::Debug, actual: &dyn std::fmt::Debug| {
+                Err(BagError::generic(format!(
+                    "{what} differs: expected {expected:?}, read {actual:?}"
+                )))
+            };
+
+        if reader.connections().len() != self.connections.len() {
+            return mismatch(
+                "connection count",
+                &self.connections.len(),
+                &reader.connections().len(),
+            );
+        }
+        for (index, expected) in self.connections.iter().enumerate() {
+            let actual = &reader.connections()[index];
+            let count = self
+                .messages
+                .iter()
+                .filter(|message| message.connection == index)
+                .count() as u64;
+            let expected_fields = (
+                &expected.topic,
+                &expected.message_type,
+                &expected.serialization_format,
+                &expected.offered_qos_profiles,
+                count,
+            );
+            let actual_fields = (
+                &actual.topic,
+                &actual.message_type,
+                &actual.serialization_format,
+                &actual.offered_qos_profiles,
+                actual.message_count,
+            );
+            if expected_fields != actual_fields {
+                return mismatch("connection", &expected_fields, &actual_fields);
+            }
+        }
+
+        let expected = self.read_order();
+        if reader.message_count() != expected.len() as u64 {
+            return mismatch("message count", &expected.len(), &reader.message_count());
+        }
+        let mut read = 0;
+        for (position, message) in reader.raw_messages()?.enumerate() {
+            let message = message?;
+            let Some(generated) = expected.get(position) else {
+                return mismatch("message count", &expected.len(), &(position + 1));
+            };
+            let expected_fields = (
+                &self.connections[generated.connection].topic,
+                generated.timestamp,
+                &generated.data,
+            );
+            let actual_fields = (
+                &message.connection.topic,
+                message.timestamp,
+                &message.raw_data,
+            );
+            if expected_fields != actual_fields {
+                return mismatch(
+                    &format!("message {position}"),
+                    &expected_fields,
+                    &actual_fields,
+                );
+            }
+            read += 1;
+        }
+        if read != expected.len() {
+            return mismatch("messages read", &expected.len(), &read);
+        }
+
+        if let (Some(first), Some(last)) = (expected.first(), expected.last()) {
+            let times = (first.timestamp, last.timestamp);
+            if (reader.start_time(), reader.end_time()) != times {
+                return mismatch(
+                    "time range",
+                    &times,
+                    &(reader.start_time(), reader.end_time()),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Storage plugin, compression and bag format version of a round trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundTripConfig {
+    /// Storage plugin written and read
+    pub storage: StoragePlugin,
+    /// Compression mode and format
+    pub compression: (CompressionMode, CompressionFormat),
+    /// Bag format version
+    pub version: u32,
+}
+
+impl Default for RoundTripConfig {
+    fn default() -> Self {
+        Self {
+            storage: StoragePlugin::Memory,
+            compression: (CompressionMode::None, CompressionFormat::None),
+            version: Writer::VERSION_LATEST,
+        }
+    }
+}
+
+impl RoundTripConfig {
+    /// Every combination of storage plugin, compression and version the enabled
+    /// features can write and read back
+    ///
+    /// File compression is left out, as readers do not decompress whole files.
+    pub fn all() -> Vec<Self> {
+        let storages = [
+            #[cfg(feature = "sqlite")]
+            StoragePlugin::Sqlite3,
+            #[cfg(feature = "mcap")]
+            StoragePlugin::Mcap,
+            StoragePlugin::Memory,
+        ];
+
+        let mut configs = Vec::new();
+        for storage in storages {
+            let mut compressions = vec![(CompressionMode::None, CompressionFormat::None)];
+            #[cfg(feature = "compression")]
+            {
+                compressions.push((CompressionMode::Message, CompressionFormat::Zstd));
+                if storage == StoragePlugin::Mcap {
+                    compressions.push((CompressionMode::Storage, CompressionFormat::Zstd));
+                }
+            }
+            for compression in compressions {
+                for version in Writer::VERSION_OLDEST..=Writer::VERSION_LATEST {
+                    configs.push(Self {
+                        storage,
+                        compression,
+                        version,
+                    });
+                }
+            }
+        }
+        configs
+    }
+}
+
+/// Check `cases` random bags, generated from seeds counting up from `seed`, against
+/// every configuration of [`RoundTripConfig::all`]
+///
+/// Bags on disk are written below `dir` and deleted after their check. The error of
+/// the first failing round trip names the seed that generates its bag.
+pub fn fuzz_round_trips(dir: &Path, seed: u64, cases: u64) -> Result<()> {
+    let configs = RoundTripConfig::all();
+    for case_seed in (0..cases).map(|case| seed.wrapping_add(case)) {
+        let bag = GeneratedBag::random(&mut Rng::new(case_seed), 4, 64);
+        for (index, config) in configs.iter().enumerate() {
+            let bag_path = dir.join(format!("case_{case_seed}_{index}"));
+            bag.check_round_trip(&bag_path, config)
+                .map_err(|e| BagError::generic(format!("Case with seed {case_seed}: {e}")))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        let first = GeneratedBag::random(&mut Rng::new(3), 4, 64);
+        assert_eq!(first, GeneratedBag::random(&mut Rng::new(3), 4, 64));
+        assert_ne!(first, GeneratedBag::random(&mut Rng::new(4), 4, 64));
+        for message in &first.messages {
+            assert!(first.connections.get(message.connection).is_some());
+        }
+    }
+
+    #[test]
+    fn test_random_bags_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fuzz_round_trips(temp_dir.path(), 1, 3).unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_existing_bag_path_is_kept() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("existing");
+        std::fs::create_dir(&bag_path).unwrap();
+        let file = bag_path.join("notes.txt");
+        std::fs::write(&file, "keep me").unwrap();
+
+        let bag = GeneratedBag::random(&mut Rng::new(1), 2, 8);
+        let configs = RoundTripConfig::all();
+        let (memory, files): (Vec<_>, Vec<_>) = configs
+            .iter()
+            .partition(|config| config.storage == StoragePlugin::Memory);
+        for config in files {
+            assert!(bag.check_round_trip(&bag_path, config).is_err());
+        }
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        let memory_path = Path::new("memory/test_existing_bag_path_is_kept");
+        let mut writer = Writer::builder(memory_path)
+            .storage(StoragePlugin::Memory)
+            .open()
+            .unwrap();
+        writer.close().unwrap();
+        for config in memory {
+            assert!(bag.check_round_trip(memory_path, config).is_err());
+        }
+        assert!(memory::remove(memory_path));
+    }
+}
@@ -0,0 +1,637 @@
+//! Transform tree reconstruction from recorded `/tf` and `/tf_static` messages
+//!
+//! [`TfBuffer`] collects every transform of a bag and answers
+//! `lookup_transform(target, source, time)` queries like `tf2_ros::Buffer`, without
+//! a ROS install. Dynamic transforms are interpolated between the two samples around
+//! the requested time (linear for translation, slerp for rotation); static transforms
+//! hold at all times. Unlike tf2 the buffer keeps the complete history of the bag.
+
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{
+    FromCdr, Header, Quaternion, TFMessage, Time, Transform, TransformStamped, Vector3,
+};
+use crate::reader::Reader;
+use crate::types::Connection;
+use std::collections::{BTreeSet, HashMap};
+
+/// Topic of dynamic transforms
+pub const TF_TOPIC: &str = "/tf";
+
+/// Topic of static transforms
+pub const TF_STATIC_TOPIC: &str = "/tf_static";
+
+/// Message type published on the transform topics
+pub const TF_MESSAGE_TYPE: &str = "tf2_msgs/msg/TFMessage";
+
+/// Longest parent chain followed before the tree is considered cyclic
+const MAX_TREE_DEPTH: usize = 1000;
+
+/// Transforms from one child frame to its parent
+#[derive(Debug, Clone)]
+struct FrameTransforms {
+    /// Parent frame ID (`header.frame_id` of the transforms)
+    parent: String,
+    /// Transform from `/tf_static`, valid at all times
+    static_transform: Option<Transform>,
+    /// Transforms from `/tf`, sorted by stamp in nanoseconds
+    samples: Vec<(u64, Transform)>,
+}
+
+impl FrameTransforms {
+    /// Stamp of the newest sample, `None` for static transforms
+    fn latest_stamp(&self) -> Option<u64> {
+        if self.static_transform.is_some() {
+            return None;
+        }
+        self.samples.last().map(|(stamp, _)| *stamp)
+    }
+
+    /// Transform to the parent at `time`, interpolating between samples
+    fn at(&self, time: u64) -> std::result::Result<Transform, String> {
+        if let Some(transform) = &self.static_transform {
+            return Ok(transform.clone());
+        }
+        let index = self.samples.partition_point(|(stamp, _)| *stamp < time);
+        match (
+            index.checked_sub(1).map(|i| &self.samples[i]),
+            self.samples.get(index),
+        ) {
+            (_, Some((stamp, transform))) if *stamp == time => Ok(transform.clone()),
+            (Some((before, from)), Some((after, to))) => {
+                let ratio = (time - before) as f64 / (after - before) as f64;
+                Ok(interpolate(from, to, ratio))
+            }
+            _ => {
+                let (first, last) = (self.samples[0].0, self.samples[self.samples.len() - 1].0);
+                Err(format!(
+                    "time {time} is outside the recorded range [{first}, {last}] of '{}'",
+                    self.parent
+                ))
+            }
+        }
+    }
+}
+
+/// Transform tree over time, built from `tf2_msgs/msg/TFMessage` messages
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::tf::TfBuffer;
+/// use rosbags_rs::Reader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+/// let buffer = TfBuffer::from_reader(&reader)?;
+/// let base_in_map = buffer.lookup_transform("map", "base_link", reader.start_time())?;
+/// println!("{:?}", base_in_map.transform.translation);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TfBuffer {
+    /// Transforms keyed by child frame ID
+    frames: HashMap<String, FrameTransforms>,
+}
+
+impl TfBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect all transforms published on `/tf` and `/tf_static` in the bag
+    pub fn from_reader(reader: &Reader) -> Result<Self> {
+        Self::from_reader_topics(reader, &[TF_TOPIC], &[TF_STATIC_TOPIC])
+    }
+
+    /// Collect all transforms from the given dynamic and static topics
+    ///
+    /// For bags recorded with remapped topics, e.g. `/robot1/tf`.
+    pub fn from_reader_topics(
+        reader: &Reader,
+        topics: &[&str],
+        static_topics: &[&str],
+    ) -> Result<Self> {
+        let mut buffer = Self::new();
+        let connections: Vec<Connection> = reader
+            .connections()
+            .iter()
+            .filter(|c| {
+                c.message_type == TF_MESSAGE_TYPE
+                    && (topics.contains(&c.topic.as_str())
+                        || static_topics.contains(&c.topic.as_str()))
+            })
+            .cloned()
+            .collect();
+        if connections.is_empty() {
+            return Ok(buffer);
+        }
+
+        for message in reader.raw_messages_filtered(Some(&connections), None, None)? {
+            let message = message?;
+            let is_static = static_topics.contains(&message.connection.topic.as_str());
+            buffer.insert_message(&message.raw_data, is_static)?;
+        }
+        Ok(buffer)
+    }
+
+    /// Add all transforms of a CDR-encoded `tf2_msgs/msg/TFMessage`
+    pub fn insert_message(&mut self, data: &[u8], is_static: bool) -> Result<()> {
+        let mut deserializer = CdrDeserializer::new(data)?;
+        let message = TFMessage::from_cdr(&mut deserializer)?;
+        for transform in &message.transforms {
+            self.insert(transform, is_static);
+        }
+        Ok(())
+    }
+
+    /// Add a transform from `header.frame_id` (parent) to `child_frame_id`
+    ///
+    /// Like tf2, a frame has a single parent: a transform naming a different
+    /// parent replaces the history of the child frame.
+    pub fn insert(&mut self, transform: &TransformStamped, is_static: bool) {
+        let parent = transform.header.frame_id.trim_start_matches('/');
+        let child = transform.child_frame_id.trim_start_matches('/');
+        let entry = self
+            .frames
+            .entry(child.to_string())
+            .or_insert_with(|| FrameTransforms {
+                parent: parent.to_string(),
+                static_transform: None,
+                samples: Vec::new(),
+            });
+        if entry.parent != parent {
+            entry.parent = parent.to_string();
+            entry.static_transform = None;
+            entry.samples.clear();
+        }
+
+        if is_static {
+            entry.static_transform = Some(transform.transform.clone());
+            entry.samples.clear();
+            return;
+        }
+        entry.static_transform = None;
+        let stamp = stamp_nanos(&transform.header.stamp);
+        match entry.samples.binary_search_by_key(&stamp, |(s, _)| *s) {
+            Ok(index) => entry.samples[index].1 = transform.transform.clone(),
+            Err(index) => entry
+                .samples
+                .insert(index, (stamp, transform.transform.clone())),
+        }
+    }
+
+    /// Whether the buffer holds no transforms
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// All frame IDs in the tree, sorted
+    pub fn frames(&self) -> Vec<&str> {
+        let mut frames = BTreeSet::new();
+        for (child, transforms) in &self.frames {
+            frames.insert(child.as_str());
+            frames.insert(transforms.parent.as_str());
+        }
+        frames.into_iter().collect()
+    }
+
+    /// Parent of a frame, `None` for root or unknown frames
+    pub fn parent(&self, frame: &str) -> Option<&str> {
+        self.frames
+            .get(frame.trim_start_matches('/'))
+            .map(|transforms| transforms.parent.as_str())
+    }
+
+    /// Whether [`TfBuffer::lookup_transform`] succeeds for the arguments
+    pub fn can_transform(&self, target: &str, source: &str, time: u64) -> bool {
+        self.lookup_transform(target, source, time).is_ok()
+    }
+
+    /// Transform that maps coordinates in the `source` frame to the `target` frame
+    ///
+    /// `time` is in nanoseconds; `0` selects the latest time at which every transform
+    /// of the chain is available, like `tf2::TimePointZero`. The returned message has
+    /// `header.frame_id = target`, `child_frame_id = source` and the stamp used.
+    pub fn lookup_transform(
+        &self,
+        target: &str,
+        source: &str,
+        time: u64,
+    ) -> Result<TransformStamped> {
+        let target = target.trim_start_matches('/');
+        let source = source.trim_start_matches('/');
+        let error = |reason: String| BagError::transform_lookup(target, source, reason);
+
+        let target_chain = self.chain(target).map_err(error)?;
+        let source_chain = self.chain(source).map_err(error)?;
+
+        // Common ancestor: the first frame of the source chain also on the target chain
+        let (source_len, target_len) = source_chain
+            .iter()
+            .enumerate()
+            .find_map(|(i, frame)| {
+                target_chain
+                    .iter()
+                    .position(|other| other == frame)
+                    .map(|j| (i, j))
+            })
+            .ok_or_else(|| {
+                error(format!(
+                    "frames are not connected (roots '{}' and '{}')",
+                    source_chain[source_chain.len() - 1],
+                    target_chain[target_chain.len() - 1]
+                ))
+            })?;
+        let source_edges = &source_chain[..source_len];
+        let target_edges = &target_chain[..target_len];
+
+        let time = if time == 0 {
+            source_edges
+                .iter()
+                .chain(target_edges)
+                .filter_map(|frame| self.frames[*frame].latest_stamp())
+                .min()
+                .unwrap_or(0)
+        } else {
+            time
+        };
+
+        let ancestor_from_source = self.compose_chain(source_edges, time).map_err(error)?;
+        let ancestor_from_target = self.compose_chain(target_edges, time).map_err(error)?;
+        let transform = compose(&inverse(&ancestor_from_target), &ancestor_from_source);
+
+        Ok(TransformStamped {
+            header: Header {
+                stamp: Time::from_nanos(time),
+                frame_id: target.to_string(),
+            },
+            child_frame_id: source.to_string(),
+            transform,
+        })
+    }
+
+    /// Frames from `frame` up to its root, inclusive
+    fn chain<'a>(&'a self, frame: &'a str) -> std::result::Result<Vec<&'a str>, String> {
+        let known =
+            self.frames.contains_key(frame) || self.frames.values().any(|t| t.parent == frame);
+        if !known {
+            return Err(format!("frame '{frame}' does not exist"));
+        }
+
+        let mut chain = vec![frame];
+        let mut current = frame;
+        while let Some(transforms) = self.frames.get(current) {
+            if chain.len() > MAX_TREE_DEPTH || chain.contains(&transforms.parent.as_str()) {
+                return Err(format!("transform tree has a loop at '{current}'"));
+            }
+            current = &transforms.parent;
+            chain.push(current);
+        }
+        Ok(chain)
+    }
+
+    /// Transform from the first frame of `edges` to the parent of the last one
+    fn compose_chain(&self, edges: &[&str], time: u64) -> std::result::Result<Transform, String> {
+        let mut result = identity();
+        for frame in edges {
+            let parent_from_child = self.frames[*frame]
+                .at(time)
+                .map_err(|reason| format!("lookup of '{frame}': {reason}"))?;
+            result = compose(&parent_from_child, &result);
+        }
+        Ok(result)
+    }
+}
+
+/// Stamp in nanoseconds, clamping times before the epoch to 0
+fn stamp_nanos(stamp: &Time) -> u64 {
+    stamp.to_nanos().unwrap_or(0)
+}
+
+fn identity() -> Transform {
+    Transform {
+        translation: Vector3::default(),
+        rotation: Quaternion::default(),
+    }
+}
+
+fn normalize(q: &Quaternion) -> Quaternion {
+    let norm = (q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w).sqrt();
+    if norm == 0.0 {
+        return Quaternion::default();
+    }
+    Quaternion {
+        x: q.x / norm,
+        y: q.y / norm,
+        z: q.z / norm,
+        w: q.w / norm,
+    }
+}
+
+fn multiply(a: &Quaternion, b: &Quaternion) -> Quaternion {
+    Quaternion {
+        x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+    }
+}
+
+fn conjugate(q: &Quaternion) -> Quaternion {
+    Quaternion {
+        x: -q.x,
+        y: -q.y,
+        z: -q.z,
+        w: q.w,
+    }
+}
+
+fn rotate(q: &Quaternion, v: &Vector3) -> Vector3 {
+    let p = Quaternion {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+        w: 0.0,
+    };
+    let r = multiply(&multiply(q, &p), &conjugate(q));
+    Vector3 {
+        x: r.x,
+        y: r.y,
+        z: r.z,
+    }
+}
+
+/// `a * b`: apply `b`, then `a`
+fn compose(a: &Transform, b: &Transform) -> Transform {
+    let rotation = normalize(&a.rotation);
+    let moved = rotate(&rotation, &b.translation);
+    Transform {
+        translation: Vector3 {
+            x: a.translation.x + moved.x,
+            y: a.translation.y + moved.y,
+            z: a.translation.z + moved.z,
+        },
+        rotation: normalize(&multiply(&rotation, &normalize(&b.rotation))),
+    }
+}
+
+fn inverse(t: &Transform) -> Transform {
+    let rotation = conjugate(&normalize(&t.rotation));
+    let moved = rotate(&rotation, &t.translation);
+    Transform {
+        translation: Vector3 {
+            x: -moved.x,
+            y: -moved.y,
+            z: -moved.z,
+        },
+        rotation,
+    }
+}
+
+/// Linear interpolation of the translation and slerp of the rotation
+fn interpolate(from: &Transform, to: &Transform, ratio: f64) -> Transform {
+    let lerp = |a: f64, b: f64| a + (b - a) * ratio;
+    Transform {
+        translation: Vector3 {
+            x: lerp(from.translation.x, to.translation.x),
+            y: lerp(from.translation.y, to.translation.y),
+            z: lerp(from.translation.z, to.translation.z),
+        },
+        rotation: slerp(&normalize(&from.rotation), &normalize(&to.rotation), ratio),
+    }
+}
+
+fn slerp(a: &Quaternion, b: &Quaternion, ratio: f64) -> Quaternion {
+    let mut dot = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+    // Take the shorter arc
+    let b = if dot < 0.0 {
+        dot = -dot;
+        Quaternion {
+            x: -b.x,
+            y: -b.y,
+            z: -b.z,
+            w: -b.w,
+        }
+    } else {
+        b.clone()
+    };
+
+    let (wa, wb) = if dot > 0.9995 {
+        // Nearly parallel: linear interpolation avoids dividing by sin(0)
+        (1.0 - ratio, ratio)
+    } else {
+        let theta = dot.acos();
+        let sin = theta.sin();
+        (
+            ((1.0 - ratio) * theta).sin() / sin,
+            (ratio * theta).sin() / sin,
+        )
+    };
+    normalize(&Quaternion {
+        x: wa * a.x + wb * b.x,
+        y: wa * a.y + wb * b.y,
+        z: wa * a.z + wb * b.z,
+        w: wa * a.w + wb * b.w,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaw(angle: f64) -> Quaternion {
+        Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: (angle / 2.0).sin(),
+            w: (angle / 2.0).cos(),
+        }
+    }
+
+    fn stamped(
+        parent: &str,
+        child: &str,
+        stamp: u64,
+        xyz: [f64; 3],
+        rotation: Quaternion,
+    ) -> TransformStamped {
+        TransformStamped {
+            header: Header {
+                stamp: Time::from_nanos(stamp),
+                frame_id: parent.to_string(),
+            },
+            child_frame_id: child.to_string(),
+            transform: Transform {
+                translation: Vector3 {
+                    x: xyz[0],
+                    y: xyz[1],
+                    z: xyz[2],
+                },
+                rotation,
+            },
+        }
+    }
+
+    fn assert_close(actual: &Vector3, expected: [f64; 3]) {
+        let actual = [actual.x, actual.y, actual.z];
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} != {expected:?}");
+        }
+    }
+
+    /// map -(static)-> odom -(dynamic)-> base_link -(static)-> laser, plus map -> camera
+    fn buffer() -> TfBuffer {
+        let mut buffer = TfBuffer::new();
+        buffer.insert(&stamped("map", "odom", 0, [10.0, 0.0, 0.0], yaw(0.0)), true);
+        buffer.insert(
+            &stamped("odom", "base_link", 1_000, [0.0, 0.0, 0.0], yaw(0.0)),
+            false,
+        );
+        buffer.insert(
+            &stamped(
+                "odom",
+                "base_link",
+                3_000,
+                [2.0, 0.0, 0.0],
+                yaw(std::f64::consts::PI),
+            ),
+            false,
+        );
+        buffer.insert(
+            &stamped("base_link", "laser", 0, [1.0, 0.0, 0.5], yaw(0.0)),
+            true,
+        );
+        buffer.insert(
+            &stamped("/map", "/camera", 500, [0.0, 5.0, 0.0], yaw(0.0)),
+            false,
+        );
+        buffer
+    }
+
+    #[test]
+    fn test_lookup_interpolates_along_chain() {
+        let buffer = buffer();
+        assert_eq!(
+            buffer.frames(),
+            ["base_link", "camera", "laser", "map", "odom"]
+        );
+        assert_eq!(buffer.parent("laser"), Some("base_link"));
+
+        // Halfway: base_link at x=1, rotated by 90 degrees, laser 1 m ahead of it
+        let result = buffer.lookup_transform("map", "laser", 2_000).unwrap();
+        assert_eq!(result.header.frame_id, "map");
+        assert_eq!(result.child_frame_id, "laser");
+        assert_close(&result.transform.translation, [11.0, 1.0, 0.5]);
+        let rotation = &result.transform.rotation;
+        assert!((rotation.z.abs() - (std::f64::consts::FRAC_PI_4).sin()).abs() < 1e-9);
+
+        // The inverse lookup maps back
+        let inverse = buffer.lookup_transform("laser", "map", 2_000).unwrap();
+        let round_trip = compose(&result.transform, &inverse.transform);
+        assert_close(&round_trip.translation, [0.0, 0.0, 0.0]);
+
+        // Sibling branch: camera (static at x=0,y=5 from map) seen from odom
+        let camera = buffer.lookup_transform("odom", "camera", 500).unwrap();
+        assert_close(&camera.transform.translation, [-10.0, 5.0, 0.0]);
+
+        let identity = buffer.lookup_transform("laser", "laser", 42).unwrap();
+        assert_close(&identity.transform.translation, [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_latest_time_and_errors() {
+        let buffer = buffer();
+        // Latest common time of the laser chain is the last base_link sample
+        let latest = buffer.lookup_transform("map", "laser", 0).unwrap();
+        assert_eq!(stamp_nanos(&latest.header.stamp), 3_000);
+        assert_close(&latest.transform.translation, [11.0, 0.0, 0.5]);
+
+        assert!(matches!(
+            buffer.lookup_transform("map", "base_link", 4_000),
+            Err(BagError::TransformLookup { .. })
+        ));
+        assert!(!buffer.can_transform("map", "base_link", 999));
+        assert!(!buffer.can_transform("map", "wheel", 2_000));
+
+        let mut disconnected = buffer.clone();
+        disconnected.insert(&stamped("world", "gps", 0, [0.0; 3], yaw(0.0)), true);
+        let error = disconnected
+            .lookup_transform("map", "gps", 2_000)
+            .unwrap_err();
+        assert!(error.to_string().contains("not connected"), "{error}");
+    }
+
+    #[test]
+    fn test_reparenting_replaces_history() {
+        let mut buffer = buffer();
+        buffer.insert(
+            &stamped("map", "base_link", 5_000, [7.0, 0.0, 0.0], yaw(0.0)),
+            false,
+        );
+        assert_eq!(buffer.parent("base_link"), Some("map"));
+        assert!(!buffer.can_transform("map", "base_link", 2_000));
+        let result = buffer.lookup_transform("map", "laser", 5_000).unwrap();
+        assert_close(&result.transform.translation, [8.0, 0.0, 0.5]);
+    }
+
+    #[cfg(feature = "default")]
+    #[test]
+    fn test_from_reader_collects_tf_topics() {
+        use crate::messages::ToCdr;
+        use crate::Writer;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("tf_bag");
+        let mut writer = Writer::new(&bag_path, None, None).unwrap();
+        writer.open().unwrap();
+        let tf = writer
+            .add_connection(
+                TF_TOPIC.to_string(),
+                TF_MESSAGE_TYPE.to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let tf_static = writer
+            .add_connection(
+                TF_STATIC_TOPIC.to_string(),
+                TF_MESSAGE_TYPE.to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let message = TFMessage {
+            transforms: vec![stamped("map", "odom", 0, [1.0, 2.0, 3.0], yaw(0.0))],
+        };
+        writer
+            .write(&tf_static, 1, &message.to_cdr_bytes())
+            .unwrap();
+        for (stamp, x) in [(1_000_000_000, 0.0), (2_000_000_000, 4.0)] {
+            let message = TFMessage {
+                transforms: vec![stamped("odom", "base_link", stamp, [x, 0.0, 0.0], yaw(0.0))],
+            };
+            writer.write(&tf, stamp, &message.to_cdr_bytes()).unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut reader = Reader::new(&bag_path).unwrap();
+        reader.open().unwrap();
+        let buffer = TfBuffer::from_reader(&reader).unwrap();
+        let result = buffer
+            .lookup_transform("map", "base_link", 1_500_000_000)
+            .unwrap();
+        assert_close(&result.transform.translation, [3.0, 2.0, 3.0]);
+
+        // Bags without transform topics give an empty buffer
+        let mut reader = Reader::new("tests/test_bags/test_bag_sqlite3").unwrap();
+        reader.open().unwrap();
+        assert!(TfBuffer::from_reader(&reader).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+//! Conversions between bag timestamps and other time representations
+//!
+//! Bag timestamps are `u64` nanoseconds since the Unix epoch. The functions here
+//! convert them to and from `std::time::SystemTime`, `chrono::DateTime<Utc>` and
+//! seconds, and format timestamps and durations for display. [`Time`] and
+//! [`Duration`] (`builtin_interfaces`) convert to the same representations and
+//! support the arithmetic of `rclcpp::Time`.
+//!
+//! # Example
+//! ```
+//! use rosbags_rs::messages::{Duration, Time};
+//! use rosbags_rs::time;
+//!
+//! let timestamp = 1_700_000_000_250_000_000;
+//! assert_eq!(time::secs_f64(timestamp), 1_700_000_000.25);
+//! assert_eq!(time::format_timestamp(timestamp), "2023-11-14T22:13:20.250000000Z");
+//! assert_eq!(time::format_duration(90_500_000_000), "1m 30.500s");
+//!
+//! let stamp = Time::from_nanos(timestamp) + Duration::from_nanos(-500_000_000);
+//! assert_eq!(stamp.to_nanos(), Some(1_699_999_999_750_000_000));
+//! ```
+
+use crate::messages::{Duration, Time};
+use chrono::{DateTime, TimeZone, Utc};
+use std::ops::{Add, Sub};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Nanoseconds per second
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Timestamp or duration in seconds
+pub fn secs_f64(nanos: u64) -> f64 {
+    // Whole and fractional seconds apart, to keep the fraction of recent timestamps
+    (nanos / NANOS_PER_SEC) as f64 + (nanos % NANOS_PER_SEC) as f64 / NANOS_PER_SEC as f64
+}
+
+/// Timestamp or duration of `secs` seconds, clamped to the `u64` range
+pub fn from_secs_f64(secs: f64) -> u64 {
+    // Float to integer casts saturate, and NaN becomes 0
+    (secs * NANOS_PER_SEC as f64).round() as u64
+}
+
+/// System time of a timestamp
+pub fn to_system_time(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + std::time::Duration::from_nanos(nanos)
+}
+
+/// Timestamp of a system time, `None` before the epoch or after the year 2554
+pub fn from_system_time(time: SystemTime) -> Option<u64> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// UTC date and time of a timestamp
+pub fn to_datetime(nanos: u64) -> DateTime<Utc> {
+    let secs = (nanos / NANOS_PER_SEC) as i64;
+    let subsec = (nanos % NANOS_PER_SEC) as u32;
+    Utc.timestamp_opt(secs, subsec)
+        .single()
+        .expect("u64 timestamps are within the chrono range")
+}
+
+/// Timestamp of a date and time, `None` before the epoch or after the year 2262
+pub fn from_datetime<Tz: TimeZone>(datetime: &DateTime<Tz>) -> Option<u64> {
+    u64::try_from(datetime.timestamp_nanos_opt()?).ok()
+}
+
+/// Timestamp as RFC 3339 UTC date and time with nanoseconds,
+/// e.g. `2023-11-14T22:13:20.250000000Z`
+pub fn format_timestamp(nanos: u64) -> String {
+    to_datetime(nanos)
+        .format("%Y-%m-%dT%H:%M:%S%.9fZ")
+        .to_string()
+}
+
+/// Duration in the largest fitting unit, e.g. `850ns`, `12.5ms`, `3.250s` or
+/// `1h 2m 3.000s`
+pub fn format_duration(nanos: u64) -> String {
+    const MINUTE: u64 = 60 * NANOS_PER_SEC;
+    const HOUR: u64 = 60 * MINUTE;
+
+    if nanos < 1_000 {
+        format!("{nanos}ns")
+    } else if nanos < 1_000_000 {
+        format!("{:.1}µs", nanos as f64 / 1e3)
+    } else if nanos < NANOS_PER_SEC {
+        format!("{:.1}ms", nanos as f64 / 1e6)
+    } else if nanos < MINUTE {
+        format!("{:.3}s", secs_f64(nanos))
+    } else {
+        let seconds = secs_f64(nanos % MINUTE);
+        let minutes = nanos % HOUR / MINUTE;
+        match nanos / HOUR {
+            0 => format!("{minutes}m {seconds:.3}s"),
+            hours => format!("{hours}h {minutes}m {seconds:.3}s"),
+        }
+    }
+}
+
+impl Time {
+    /// Create a time from signed nanoseconds since the epoch
+    fn from_signed_nanos(nanos: i64) -> Self {
+        Self {
+            sec: nanos.div_euclid(NANOS_PER_SEC as i64) as i32,
+            nanosec: nanos.rem_euclid(NANOS_PER_SEC as i64) as u32,
+        }
+    }
+
+    /// Signed nanoseconds since the epoch
+    fn signed_nanos(&self) -> i64 {
+        i64::from(self.sec) * NANOS_PER_SEC as i64 + i64::from(self.nanosec)
+    }
+
+    /// Nanoseconds since the epoch like bag timestamps, `None` before the epoch
+    pub fn to_nanos(&self) -> Option<u64> {
+        u64::try_from(self.signed_nanos()).ok()
+    }
+
+    /// Seconds since the epoch
+    pub fn as_secs_f64(&self) -> f64 {
+        f64::from(self.sec) + f64::from(self.nanosec) / NANOS_PER_SEC as f64
+    }
+
+    /// System time of the stamp
+    pub fn to_system_time(&self) -> SystemTime {
+        let nanos = self.signed_nanos();
+        let offset = std::time::Duration::from_nanos(nanos.unsigned_abs());
+        if nanos < 0 {
+            UNIX_EPOCH - offset
+        } else {
+            UNIX_EPOCH + offset
+        }
+    }
+
+    /// UTC date and time of the stamp
+    pub fn to_datetime(&self) -> DateTime<Utc> {
+        Utc.timestamp_nanos(self.signed_nanos())
+    }
+}
+
+impl From<SystemTime> for Time {
+    fn from(time: SystemTime) -> Self {
+        let nanos = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_nanos() as i64,
+            Err(before) => -(before.duration().as_nanos() as i64),
+        };
+        Self::from_signed_nanos(nanos)
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for Time {
+    fn from(datetime: DateTime<Tz>) -> Self {
+        Self {
+            sec: datetime.timestamp() as i32,
+            nanosec: datetime.timestamp_subsec_nanos(),
+        }
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, duration: Duration) -> Time {
+        Time::from_signed_nanos(self.signed_nanos() + duration.to_nanos())
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, duration: Duration) -> Time {
+        Time::from_signed_nanos(self.signed_nanos() - duration.to_nanos())
+    }
+}
+
+impl Sub for Time {
+    type Output = Duration;
+
+    fn sub(self, other: Time) -> Duration {
+        Duration::from_nanos(self.signed_nanos() - other.signed_nanos())
+    }
+}
+
+impl Duration {
+    /// Create a duration from signed nanoseconds
+    ///
+    /// Negative durations have a negative `sec` and a `nanosec` below one second,
+    /// like `rclcpp::Duration`.
+    pub fn from_nanos(nanos: i64) -> Self {
+        Self {
+            sec: nanos.div_euclid(NANOS_PER_SEC as i64) as i32,
+            nanosec: nanos.rem_euclid(NANOS_PER_SEC as i64) as u32,
+        }
+    }
+
+    /// Signed nanoseconds
+    pub fn to_nanos(&self) -> i64 {
+        i64::from(self.sec) * NANOS_PER_SEC as i64 + i64::from(self.nanosec)
+    }
+
+    /// Signed seconds
+    pub fn as_secs_f64(&self) -> f64 {
+        self.to_nanos() as f64 / NANOS_PER_SEC as f64
+    }
+
+    /// Standard duration, `None` if negative
+    pub fn to_std(&self) -> Option<std::time::Duration> {
+        u64::try_from(self.to_nanos())
+            .ok()
+            .map(std::time::Duration::from_nanos)
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Self::from_nanos(duration.as_nanos() as i64)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.to_nanos() + other.to_nanos())
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.to_nanos() - other.to_nanos())
+    }
+}
+
+impl From<crate::types::Duration> for std::time::Duration {
+    fn from(duration: crate::types::Duration) -> Self {
+        std::time::Duration::from_nanos(duration.nanoseconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_conversions() {
+        let nanos = 1_700_000_000_123_456_789;
+        assert_eq!(from_system_time(to_system_time(nanos)), Some(nanos));
+        assert_eq!(from_datetime(&to_datetime(nanos)), Some(nanos));
+        assert_eq!(from_secs_f64(secs_f64(1_500_000_000)), 1_500_000_000);
+        assert_eq!(from_secs_f64(-1.0), 0);
+        assert_eq!(
+            from_system_time(UNIX_EPOCH - std::time::Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000000000Z");
+
+        let stamp = Time::from_nanos(nanos);
+        assert_eq!(stamp.to_nanos(), Some(nanos));
+        assert_eq!(Time::from(stamp.to_system_time()), stamp);
+        assert_eq!(Time::from(stamp.to_datetime()), stamp);
+        assert_eq!(
+            Time {
+                sec: -1,
+                nanosec: 0
+            }
+            .to_nanos(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_time_arithmetic() {
+        let start = Time {
+            sec: 10,
+            nanosec: 900_000_000,
+        };
+        let step = Duration::from(std::time::Duration::from_millis(200));
+        let end = start.clone() + step.clone();
+        assert_eq!(
+            end,
+            Time {
+                sec: 11,
+                nanosec: 100_000_000
+            }
+        );
+        assert_eq!(end.clone() - step.clone(), start);
+        assert_eq!((end.clone() - start.clone()).to_nanos(), 200_000_000);
+
+        let negative = start - end;
+        assert_eq!(
+            negative,
+            Duration {
+                sec: -1,
+                nanosec: 800_000_000
+            }
+        );
+        assert_eq!(negative.as_secs_f64(), -0.2);
+        assert_eq!(negative.to_std(), None);
+        assert_eq!((negative + step).to_std(), Some(std::time::Duration::ZERO));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(850), "850ns");
+        assert_eq!(format_duration(12_500), "12.5µs");
+        assert_eq!(format_duration(12_500_000), "12.5ms");
+        assert_eq!(format_duration(3_250_000_000), "3.250s");
+        assert_eq!(format_duration(3_723_000_000_000), "1h 2m 3.000s");
+    }
+}
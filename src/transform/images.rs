@@ -0,0 +1,497 @@
+//! Downscaling of camera topics for preview bags
+//!
+//! [`ImageDownscale`] shrinks `sensor_msgs/msg/Image` and `sensor_msgs/msg/CompressedImage`
+//! messages by an integer factor. Common raw encodings are box-filtered in place and keep
+//! their encoding; depth images (`16UC1`, `32FC1`) are subsampled instead so that no
+//! depth values are invented at object edges. Bayer and YUV images are debayered or
+//! converted to `rgb8`, and compressed images are re-encoded in their original format.
+
+use super::MessageTransform;
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::export::images::{image_to_dynamic, COMPRESSED_IMAGE_TYPE, IMAGE_TYPE};
+use crate::messages::{CompressedImage, FromCdr, Image, ToCdr};
+use crate::types::Connection;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::io::Cursor;
+
+/// Default JPEG quality for re-encoded compressed images
+pub const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Downscale image topics by an integer factor
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::transform::{self, ImageDownscale};
+/// use rosbags_rs::{Reader, Writer};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+/// let mut writer = Writer::new("path/to/preview", None, None)?;
+/// writer.open()?;
+///
+/// let downscale = ImageDownscale::new(4).jpeg_quality(70);
+/// let mut connections = std::collections::HashMap::new();
+/// for conn in reader.connections() {
+///     let out = writer.add_connection_from(conn)?;
+///     connections.insert(conn.topic.clone(), out);
+/// }
+/// for msg in reader.messages()? {
+///     let msg = msg?;
+///     let output = transform::apply(&downscale, &msg.connection, msg.timestamp, &msg.data)?;
+///     if let Some((timestamp, data)) = output {
+///         writer.write(&connections[&msg.topic], timestamp, &data)?;
+///     }
+/// }
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageDownscale {
+    factor: u32,
+    jpeg_quality: u8,
+    topics: Option<Vec<String>>,
+}
+
+impl ImageDownscale {
+    /// Downscale every image topic by `factor` in both dimensions (4 gives 1/4 resolution)
+    ///
+    /// A factor of 0 is treated as 1.
+    pub fn new(factor: u32) -> Self {
+        Self {
+            factor: factor.max(1),
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+            topics: None,
+        }
+    }
+
+    /// JPEG quality (1-100) used when re-encoding compressed images
+    pub fn jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = quality.clamp(1, 100);
+        self
+    }
+
+    /// Only downscale the given topics; other image topics are passed through
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.topics = Some(topics.iter().map(|t| t.to_string()).collect());
+        self
+    }
+
+    /// Downscale factor
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+
+    fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |size: u32| {
+            if size == 0 {
+                0
+            } else {
+                (size / self.factor).max(1)
+            }
+        };
+        (scale(width), scale(height))
+    }
+
+    /// Downscale a raw image
+    pub fn downscale_image(&self, msg: &Image) -> Result<Image> {
+        if self.factor == 1 {
+            return Ok(msg.clone());
+        }
+        match RawLayout::of(&msg.encoding) {
+            Some(layout) => self.downscale_raw(msg, layout),
+            None => {
+                let image = image_to_dynamic(msg)
+                    .map_err(|e| BagError::invalid_message_data(e.to_string()))?;
+                let (width, height) = self.output_size(msg.width, msg.height);
+                let resized = image.resize_exact(width, height, FilterType::Triangle);
+                Ok(dynamic_to_image(msg, resized))
+            }
+        }
+    }
+
+    /// Downscale a compressed image, re-encoding it in its original format
+    pub fn downscale_compressed(&self, msg: &CompressedImage) -> Result<CompressedImage> {
+        if self.factor == 1 {
+            return Ok(msg.clone());
+        }
+        if msg.format.to_ascii_lowercase().contains("compresseddepth") {
+            return Err(BagError::invalid_message_data(
+                "compressedDepth images cannot be downscaled",
+            ));
+        }
+
+        let source_format = image::guess_format(&msg.data).map_err(|e| {
+            BagError::invalid_message_data(format!("Unknown compressed image format: {e}"))
+        })?;
+        let image = image::load_from_memory_with_format(&msg.data, source_format).map_err(|e| {
+            BagError::invalid_message_data(format!("Failed to decode compressed image: {e}"))
+        })?;
+        let (width, height) = self.output_size(image.width(), image.height());
+        let resized = image.resize_exact(width, height, FilterType::Triangle);
+
+        let mut data = Vec::new();
+        let format = if source_format == image::ImageFormat::Png {
+            resized
+                .write_to(&mut Cursor::new(&mut data), image::ImageOutputFormat::Png)
+                .map_err(|e| BagError::invalid_message_data(format!("PNG encoding failed: {e}")))?;
+            msg.format.clone()
+        } else {
+            let (pixels, color) = match resized {
+                DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                    (resized.to_luma8().into_raw(), image::ColorType::L8)
+                }
+                _ => (resized.to_rgb8().into_raw(), image::ColorType::Rgb8),
+            };
+            JpegEncoder::new_with_quality(&mut data, self.jpeg_quality)
+                .encode(&pixels, width, height, color)
+                .map_err(|e| {
+                    BagError::invalid_message_data(format!("JPEG encoding failed: {e}"))
+                })?;
+            if source_format == image::ImageFormat::Jpeg {
+                msg.format.clone()
+            } else {
+                "jpeg".to_string()
+            }
+        };
+
+        Ok(CompressedImage {
+            header: msg.header.clone(),
+            format,
+            data,
+        })
+    }
+
+    fn downscale_raw(&self, msg: &Image, layout: RawLayout) -> Result<Image> {
+        let factor = self.factor as usize;
+        let width = msg.width as usize;
+        let height = msg.height as usize;
+        let sample_size = layout.sample.size();
+        let pixel_bytes = layout.channels * sample_size;
+        let row_bytes = width * pixel_bytes;
+        let step = msg.step as usize;
+
+        let required = if height == 0 {
+            0
+        } else {
+            step * (height - 1) + row_bytes
+        };
+        if step < row_bytes || msg.data.len() < required {
+            return Err(BagError::invalid_message_data(format!(
+                "Image data truncated: {}x{} {} needs {} bytes, got {}",
+                msg.width,
+                msg.height,
+                msg.encoding,
+                required.max(row_bytes * height),
+                msg.data.len()
+            )));
+        }
+
+        let (out_width, out_height) = self.output_size(msg.width, msg.height);
+        let big_endian = msg.is_bigendian != 0;
+        let mut data = Vec::with_capacity(out_width as usize * out_height as usize * pixel_bytes);
+
+        for out_y in 0..out_height as usize {
+            let y0 = out_y * factor;
+            let y1 = (y0 + factor).min(height);
+            for out_x in 0..out_width as usize {
+                let x0 = out_x * factor;
+                let x1 = (x0 + factor).min(width);
+                for channel in 0..layout.channels {
+                    let offset =
+                        |x: usize, y: usize| y * step + x * pixel_bytes + channel * sample_size;
+                    if layout.nearest {
+                        let start = offset(x0, y0);
+                        data.extend_from_slice(&msg.data[start..start + sample_size]);
+                        continue;
+                    }
+                    let mut sum = 0.0;
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            sum += layout.sample.read(&msg.data[offset(x, y)..], big_endian);
+                        }
+                    }
+                    let count = ((y1 - y0) * (x1 - x0)) as f64;
+                    layout.sample.write(&mut data, sum / count, big_endian);
+                }
+            }
+        }
+
+        Ok(Image {
+            header: msg.header.clone(),
+            height: out_height,
+            width: out_width,
+            encoding: msg.encoding.clone(),
+            is_bigendian: msg.is_bigendian,
+            step: out_width * pixel_bytes as u32,
+            data,
+        })
+    }
+}
+
+impl MessageTransform for ImageDownscale {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        let is_image = connection.message_type == IMAGE_TYPE
+            || connection.message_type == COMPRESSED_IMAGE_TYPE;
+        is_image
+            && self
+                .topics
+                .as_ref()
+                .map_or(true, |topics| topics.contains(&connection.topic))
+    }
+
+    fn transform(
+        &self,
+        connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        let mut deserializer = CdrDeserializer::new(data)?;
+        let data = if connection.message_type == COMPRESSED_IMAGE_TYPE {
+            let msg = CompressedImage::from_cdr(&mut deserializer)?;
+            self.downscale_compressed(&msg)?.to_cdr_bytes()
+        } else {
+            let msg = Image::from_cdr(&mut deserializer)?;
+            self.downscale_image(&msg)?.to_cdr_bytes()
+        };
+        Ok(Some((timestamp, data)))
+    }
+}
+
+/// Channel sample type of a raw encoding
+#[derive(Debug, Clone, Copy)]
+enum Sample {
+    U8,
+    U16,
+    F32,
+}
+
+impl Sample {
+    fn size(self) -> usize {
+        match self {
+            Sample::U8 => 1,
+            Sample::U16 => 2,
+            Sample::F32 => 4,
+        }
+    }
+
+    fn read(self, bytes: &[u8], big_endian: bool) -> f64 {
+        match self {
+            Sample::U8 => bytes[0] as f64,
+            Sample::U16 => {
+                let raw = [bytes[0], bytes[1]];
+                let value = if big_endian {
+                    u16::from_be_bytes(raw)
+                } else {
+                    u16::from_le_bytes(raw)
+                };
+                value as f64
+            }
+            Sample::F32 => {
+                let raw = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                let value = if big_endian {
+                    f32::from_be_bytes(raw)
+                } else {
+                    f32::from_le_bytes(raw)
+                };
+                value as f64
+            }
+        }
+    }
+
+    fn write(self, out: &mut Vec<u8>, value: f64, big_endian: bool) {
+        match self {
+            Sample::U8 => out.push(value.round().clamp(0.0, u8::MAX as f64) as u8),
+            Sample::U16 => {
+                let value = value.round().clamp(0.0, u16::MAX as f64) as u16;
+                out.extend_from_slice(&if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                });
+            }
+            Sample::F32 => {
+                let value = value as f32;
+                out.extend_from_slice(&if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                });
+            }
+        }
+    }
+}
+
+/// Pixel layout of encodings that are downscaled without conversion
+#[derive(Debug, Clone, Copy)]
+struct RawLayout {
+    channels: usize,
+    sample: Sample,
+    /// Subsample instead of averaging (depth images)
+    nearest: bool,
+}
+
+impl RawLayout {
+    fn of(encoding: &str) -> Option<Self> {
+        let (channels, sample, nearest) = match encoding {
+            "mono8" | "8UC1" => (1, Sample::U8, false),
+            "rgb8" | "bgr8" | "8UC3" => (3, Sample::U8, false),
+            "rgba8" | "bgra8" | "8UC4" => (4, Sample::U8, false),
+            "mono16" => (1, Sample::U16, false),
+            "16UC1" => (1, Sample::U16, true),
+            "rgb16" | "bgr16" | "16UC3" => (3, Sample::U16, false),
+            "32FC1" => (1, Sample::F32, true),
+            _ => return None,
+        };
+        Some(Self {
+            channels,
+            sample,
+            nearest,
+        })
+    }
+}
+
+/// Build a raw image message from a converted [`DynamicImage`]
+fn dynamic_to_image(source: &Image, image: DynamicImage) -> Image {
+    let (width, height) = (image.width(), image.height());
+    let (encoding, pixel_bytes, data) = match image {
+        DynamicImage::ImageLuma8(buffer) => ("mono8", 1, buffer.into_raw()),
+        DynamicImage::ImageLuma16(buffer) => ("mono16", 2, le_bytes(buffer.into_raw())),
+        DynamicImage::ImageRgba8(buffer) => ("rgba8", 4, buffer.into_raw()),
+        DynamicImage::ImageRgb16(buffer) => ("rgb16", 6, le_bytes(buffer.into_raw())),
+        other => ("rgb8", 3, other.to_rgb8().into_raw()),
+    };
+    Image {
+        header: source.header.clone(),
+        height,
+        width,
+        encoding: encoding.to_string(),
+        is_bigendian: 0,
+        step: width * pixel_bytes,
+        data,
+    }
+}
+
+fn le_bytes(samples: Vec<u16>) -> Vec<u8> {
+    samples.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, Time};
+    use crate::types::MessageDefinition;
+
+    fn header() -> Header {
+        Header {
+            stamp: Time { sec: 1, nanosec: 2 },
+            frame_id: "camera".to_string(),
+        }
+    }
+
+    fn connection(topic: &str, message_type: &str) -> Connection {
+        Connection {
+            id: 1,
+            topic: topic.to_string(),
+            message_type: message_type.to_string(),
+            message_definition: MessageDefinition::default(),
+            type_description_hash: String::new(),
+            message_count: 0,
+            serialization_format: "cdr".to_string(),
+            offered_qos_profiles: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_raw_box_filter_and_depth_subsampling() {
+        // 4x2 rgb8 with one padding byte per row
+        let mut data = Vec::new();
+        for row in 0..2u8 {
+            for col in 0..4u8 {
+                data.extend_from_slice(&[row * 40 + col * 10, 100, 200]);
+            }
+            data.push(0xff);
+        }
+        let rgb = Image {
+            header: header(),
+            height: 2,
+            width: 4,
+            encoding: "rgb8".to_string(),
+            is_bigendian: 0,
+            step: 13,
+            data,
+        };
+        let small = ImageDownscale::new(2).downscale_image(&rgb).unwrap();
+        assert_eq!((small.width, small.height, small.step), (2, 1, 6));
+        assert_eq!(small.encoding, "rgb8");
+        assert_eq!(small.data, vec![25, 100, 200, 45, 100, 200]);
+        assert_eq!(small.header, rgb.header);
+
+        let depth = Image {
+            header: header(),
+            height: 2,
+            width: 2,
+            encoding: "16UC1".to_string(),
+            is_bigendian: 0,
+            step: 4,
+            data: [1000u16, 0, 0, 0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        };
+        let small = ImageDownscale::new(2).downscale_image(&depth).unwrap();
+        assert_eq!(small.data, 1000u16.to_le_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_compressed_round_trip_through_cdr() {
+        let source =
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 6, image::Rgb([10, 20, 30])));
+        let mut png = Vec::new();
+        source
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        let msg = CompressedImage {
+            header: header(),
+            format: "rgb8; png compressed bgr8".to_string(),
+            data: png,
+        };
+
+        let downscale = ImageDownscale::new(2);
+        let conn = connection("/camera/compressed", COMPRESSED_IMAGE_TYPE);
+        assert!(downscale.applies_to(&conn));
+        let (_, output) = downscale
+            .transform(&conn, 7, &msg.to_cdr_bytes())
+            .unwrap()
+            .unwrap();
+
+        let mut deserializer = CdrDeserializer::new(&output).unwrap();
+        let small = CompressedImage::from_cdr(&mut deserializer).unwrap();
+        assert_eq!(small.format, msg.format);
+        assert_eq!(small.header, msg.header);
+        let decoded = image::load_from_memory(&small.data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+        assert_eq!(decoded.to_rgb8().get_pixel(1, 1).0, [10, 20, 30]);
+    }
+
+    #[test]
+    fn test_other_topics_pass_through() {
+        let downscale = ImageDownscale::new(4).topics(&["/front/image"]);
+        assert!(downscale.applies_to(&connection("/front/image", IMAGE_TYPE)));
+        assert!(!downscale.applies_to(&connection("/rear/image", IMAGE_TYPE)));
+
+        let imu = connection("/imu", "sensor_msgs/msg/Imu");
+        let data = [0x00, 0x01, 0x00, 0x00, 1, 2, 3];
+        let (timestamp, output) = super::super::apply(&downscale, &imu, 7, &data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp, 7);
+        assert!(matches!(output, std::borrow::Cow::Borrowed(_)));
+        assert_eq!(&*output, &data);
+    }
+}
@@ -0,0 +1,398 @@
+//! Message transforms applied while copying bags
+//!
+//! A [`MessageTransform`] rewrites the serialized payload and timestamp of selected
+//! connections, for example to shrink camera topics into a lightweight preview bag,
+//! or drops their messages. Messages of connections a transform does not apply to
+//! are passed through byte for byte.
+//!
+//! Transforms are plain trait objects, so other crates add their own filters
+//! (downsampling, redaction, re-stamping, re-encoding) without changes to this
+//! crate. A [`TransformRegistry`] chains several of them in registration order and
+//! [`rewrite`] copies a bag through a transform.
+
+use crate::error::Result;
+#[cfg(feature = "default")]
+use crate::reader::Reader;
+use crate::types::Connection;
+#[cfg(feature = "default")]
+use crate::writer::Writer;
+use std::borrow::Cow;
+#[cfg(feature = "default")]
+use std::collections::HashMap;
+
+#[cfg(feature = "images")]
+pub mod images;
+#[cfg(feature = "images")]
+pub mod transcode;
+pub mod truncate;
+pub mod voxel;
+
+#[cfg(feature = "images")]
+pub use images::ImageDownscale;
+#[cfg(feature = "images")]
+pub use transcode::{ImageTranscode, TranscodeFormat};
+pub use truncate::PayloadTruncation;
+pub use voxel::VoxelDownsample;
+
+/// Rewrites serialized messages of selected connections
+///
+/// # Example
+/// ```
+/// use rosbags_rs::error::Result;
+/// use rosbags_rs::transform::MessageTransform;
+/// use rosbags_rs::types::Connection;
+///
+/// /// Keep every `n`th message of a topic
+/// struct Decimate {
+///     topic: String,
+///     n: u64,
+///     counter: std::sync::atomic::AtomicU64,
+/// }
+///
+/// impl MessageTransform for Decimate {
+///     fn applies_to(&self, connection: &Connection) -> bool {
+///         connection.topic == self.topic
+///     }
+///
+///     fn transform(
+///         &self,
+///         _connection: &Connection,
+///         timestamp: u64,
+///         data: &[u8],
+///     ) -> Result<Option<(u64, Vec<u8>)>> {
+///         let index = self
+///             .counter
+///             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+///         Ok((index % self.n == 0).then(|| (timestamp, data.to_vec())))
+///     }
+/// }
+/// ```
+pub trait MessageTransform {
+    /// Whether messages of `connection` are rewritten by this transform
+    fn applies_to(&self, connection: &Connection) -> bool;
+
+    /// Rewrite one serialized message of a connection this transform applies to
+    ///
+    /// Returns the timestamp and payload to write, or `None` to leave the message out
+    /// of the copy. Its connection is still written, so the topic stays listed in the
+    /// metadata.
+    fn transform(
+        &self,
+        connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>>;
+
+    /// Connection to write the rewritten messages of `connection` on
+    ///
+    /// Transforms that change the topic, type or definition of messages return the
+    /// new connection; by default it is unchanged.
+    fn connection(&self, connection: &Connection) -> Result<Connection> {
+        Ok(connection.clone())
+    }
+}
+
+/// Apply `transform` to a message, borrowing the input when it does not apply
+///
+/// Returns `None` if the transform drops the message.
+pub fn apply<'a, T>(
+    transform: &T,
+    connection: &Connection,
+    timestamp: u64,
+    data: &'a [u8],
+) -> Result<Option<(u64, Cow<'a, [u8]>)>>
+where
+    T: MessageTransform + ?Sized,
+{
+    if !transform.applies_to(connection) {
+        return Ok(Some((timestamp, Cow::Borrowed(data))));
+    }
+    let output = transform.transform(connection, timestamp, data)?;
+    Ok(output.map(|(timestamp, data)| (timestamp, Cow::Owned(data))))
+}
+
+/// Chain of transforms applied in registration order
+///
+/// Each transform sees the output of the previous ones, with the connection they
+/// rewrote it to; a message dropped by one transform is not passed to the next.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::transform::{self, PayloadTruncation, TransformRegistry};
+/// use rosbags_rs::{Reader, Writer};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut transforms = TransformRegistry::new();
+/// transforms.register(PayloadTruncation::new(4096).topics(&["/camera/image_raw"]));
+///
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// let mut writer = Writer::builder("path/to/preview").open()?;
+/// transform::rewrite(&reader, &mut writer, &transforms)?;
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct TransformRegistry {
+    transforms: Vec<Box<dyn MessageTransform + Send + Sync>>,
+}
+
+impl std::fmt::Debug for TransformRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransformRegistry")
+            .field("transforms", &self.transforms.len())
+            .finish()
+    }
+}
+
+impl TransformRegistry {
+    /// Create an empty registry, which passes every message through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a transform after the registered ones
+    pub fn register<T>(&mut self, transform: T)
+    where
+        T: MessageTransform + Send + Sync + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// Get the number of registered transforms
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Check if no transforms are registered
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+impl MessageTransform for TransformRegistry {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        // Later transforms only see a rewritten connection once an earlier one applied
+        self.transforms
+            .iter()
+            .any(|transform| transform.applies_to(connection))
+    }
+
+    fn transform(
+        &self,
+        connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        let mut connection = Cow::Borrowed(connection);
+        let mut message = (timestamp, Cow::Borrowed(data));
+        for transform in &self.transforms {
+            if !transform.applies_to(&connection) {
+                continue;
+            }
+            let Some((timestamp, data)) =
+                transform.transform(&connection, message.0, &message.1)?
+            else {
+                return Ok(None);
+            };
+            message = (timestamp, Cow::Owned(data));
+            connection = Cow::Owned(transform.connection(&connection)?);
+        }
+        Ok(Some((message.0, message.1.into_owned())))
+    }
+
+    fn connection(&self, connection: &Connection) -> Result<Connection> {
+        let mut connection = connection.clone();
+        for transform in &self.transforms {
+            if transform.applies_to(&connection) {
+                connection = transform.connection(&connection)?;
+            }
+        }
+        Ok(connection)
+    }
+}
+
+/// Copy all messages of an open `reader` into an open `writer` through `transform`
+///
+/// Every connection of the reader is added to the writer, rewritten by the transform
+/// where it applies, and messages are written in the order they are read. Returns
+/// the number of messages written.
+#[cfg(feature = "default")]
+pub fn rewrite<T>(reader: &Reader, writer: &mut Writer, transform: &T) -> Result<u64>
+where
+    T: MessageTransform + ?Sized,
+{
+    // Reader connections carry the recorded definitions, which transforms may rely on
+    let mut connections = HashMap::new();
+    for connection in reader.connections() {
+        let output = if transform.applies_to(connection) {
+            transform.connection(connection)?
+        } else {
+            connection.clone()
+        };
+        // Connections rewritten to the same topic and type share one output connection
+        let written = writer
+            .connections()
+            .iter()
+            .find(|c| c.topic == output.topic && c.message_type == output.message_type)
+            .cloned();
+        let written = match written {
+            Some(written) => written,
+            None => writer.add_connection_from(&output)?,
+        };
+        connections.insert(connection.id, (connection, written));
+    }
+
+    let mut count = 0;
+    for message in reader.messages()? {
+        let message = message?;
+        let (connection, written) = &connections[&message.connection.id];
+        if let Some((timestamp, data)) =
+            apply(transform, connection, message.timestamp, &message.data)?
+        {
+            writer.write(written, timestamp, &data)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shift messages of a topic in time and rename it
+    struct Shift(u64);
+
+    impl MessageTransform for Shift {
+        fn applies_to(&self, connection: &Connection) -> bool {
+            connection.topic == "/counter"
+        }
+
+        fn transform(
+            &self,
+            _connection: &Connection,
+            timestamp: u64,
+            data: &[u8],
+        ) -> Result<Option<(u64, Vec<u8>)>> {
+            Ok(Some((timestamp + self.0, data.to_vec())))
+        }
+
+        fn connection(&self, connection: &Connection) -> Result<Connection> {
+            let mut connection = connection.clone();
+            connection.topic = "/shifted".to_string();
+            Ok(connection)
+        }
+    }
+
+    /// Drop messages of odd payload bytes, on any topic
+    struct DropOdd;
+
+    impl MessageTransform for DropOdd {
+        fn applies_to(&self, connection: &Connection) -> bool {
+            connection.topic == "/shifted"
+        }
+
+        fn transform(
+            &self,
+            _connection: &Connection,
+            timestamp: u64,
+            data: &[u8],
+        ) -> Result<Option<(u64, Vec<u8>)>> {
+            Ok((data[4] % 2 == 0).then(|| (timestamp, data.to_vec())))
+        }
+    }
+
+    #[test]
+    fn test_registry_chains_transforms() {
+        let counter = Connection::new("/counter", "std_msgs/msg/UInt8").unwrap();
+        let imu = Connection::new("/imu", "sensor_msgs/msg/Imu").unwrap();
+
+        let mut transforms = TransformRegistry::new();
+        assert!(!transforms.applies_to(&counter));
+        transforms.register(Shift(1_000));
+        // Sees the topic the first transform renamed the connection to
+        transforms.register(DropOdd);
+        assert_eq!(transforms.len(), 2);
+        assert!(transforms.applies_to(&counter));
+        assert!(!transforms.applies_to(&imu));
+        assert_eq!(transforms.connection(&counter).unwrap().topic, "/shifted");
+
+        let even = [0x00, 0x01, 0x00, 0x00, 2];
+        let odd = [0x00, 0x01, 0x00, 0x00, 3];
+        assert_eq!(
+            transforms.transform(&counter, 5, &even).unwrap(),
+            Some((1_005, even.to_vec()))
+        );
+        assert_eq!(transforms.transform(&counter, 5, &odd).unwrap(), None);
+
+        let output = apply(&transforms, &imu, 5, &odd).unwrap().unwrap();
+        assert_eq!(output.0, 5);
+        assert!(matches!(output.1, Cow::Borrowed(_)));
+    }
+
+    #[cfg(feature = "default")]
+    #[test]
+    fn test_rewrite_copies_through_transforms() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("input");
+        let mut writer = Writer::builder(&input).open().unwrap();
+        let counter = writer
+            .add_connection(
+                "/counter".to_string(),
+                "std_msgs/msg/UInt8".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let status = writer
+            .add_connection(
+                "/status".to_string(),
+                "std_msgs/msg/UInt8".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        for i in 0..10u8 {
+            let connection = if i < 8 { &counter } else { &status };
+            writer
+                .write(connection, u64::from(i), &[0x00, 0x01, 0x00, 0x00, i])
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut transforms = TransformRegistry::new();
+        transforms.register(Shift(100));
+        transforms.register(DropOdd);
+        let reader = Reader::builder(&input).open().unwrap();
+        let output = temp_dir.path().join("output");
+        let mut writer = Writer::builder(&output).open().unwrap();
+        assert_eq!(rewrite(&reader, &mut writer, &transforms).unwrap(), 6);
+        writer.close().unwrap();
+
+        let reader = Reader::builder(&output).open().unwrap();
+        let messages: Vec<_> = reader
+            .messages()
+            .unwrap()
+            .map(|message| {
+                let message = message.unwrap();
+                (message.topic, message.timestamp, message.data[4])
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                ("/status".to_string(), 8, 8),
+                ("/status".to_string(), 9, 9),
+                ("/shifted".to_string(), 100, 0),
+                ("/shifted".to_string(), 102, 2),
+                ("/shifted".to_string(), 104, 4),
+                ("/shifted".to_string(), 106, 6),
+            ]
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! Transcoding of raw camera topics to compressed images for archival
+//!
+//! [`ImageTranscode`] converts `sensor_msgs/msg/Image` messages to
+//! `sensor_msgs/msg/CompressedImage` in JPEG or PNG, the `compressed` format of
+//! `image_transport`, which typically shrinks camera topics by an order of magnitude.
+//! The messages move to a connection of the compressed type, on the topic with
+//! `image_transport`'s `/compressed` suffix by default.
+
+use super::MessageTransform;
+use crate::cdr::CdrDeserializer;
+use crate::definitions::DefinitionResolver;
+use crate::error::{BagError, Result};
+use crate::export::images::{image_to_dynamic, COMPRESSED_IMAGE_TYPE, IMAGE_TYPE};
+use crate::messages::{CompressedImage, FromCdr, Image, ToCdr};
+use crate::schema::MessageSchemas;
+use crate::types::Connection;
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use std::io::Cursor;
+
+/// Default JPEG quality of transcoded images, as in `image_transport`
+pub const DEFAULT_JPEG_QUALITY: u8 = 95;
+
+/// Default suffix appended to the topics of transcoded images
+pub const DEFAULT_TOPIC_SUFFIX: &str = "/compressed";
+
+/// Compressed format of transcoded images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodeFormat {
+    /// Lossy JPEG, 8 bits per channel
+    Jpeg,
+    /// Lossless PNG, keeping 16-bit images
+    Png,
+}
+
+impl TranscodeFormat {
+    /// Name of the format in the `format` field of compressed images
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TranscodeFormat::Jpeg => "jpeg",
+            TranscodeFormat::Png => "png",
+        }
+    }
+}
+
+/// Transcode raw image topics to compressed images
+///
+/// Every supported raw encoding is converted like
+/// [`image_to_dynamic`] converts it; `32FC1` depth images are rejected, since
+/// neither format holds their values without loss. Transcoded messages keep their
+/// header and are written with the `format` string of `image_transport`, e.g.
+/// `"rgb8; jpeg compressed bgr8"`, so
+/// [`compressed_to_image`](crate::export::images::compressed_to_image) restores the
+/// original encoding.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::transform::{self, ImageTranscode, TranscodeFormat};
+/// use rosbags_rs::{Reader, Writer};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// let mut writer = Writer::builder("path/to/archive").open()?;
+/// let transcode = ImageTranscode::new(TranscodeFormat::Jpeg).jpeg_quality(90);
+/// // /camera/image_raw is written as /camera/image_raw/compressed
+/// transform::rewrite(&reader, &mut writer, &transcode)?;
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageTranscode {
+    format: TranscodeFormat,
+    jpeg_quality: u8,
+    topic_suffix: String,
+    topics: Option<Vec<String>>,
+}
+
+impl ImageTranscode {
+    /// Transcode every raw image topic to `format`
+    pub fn new(format: TranscodeFormat) -> Self {
+        Self {
+            format,
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+            topic_suffix: DEFAULT_TOPIC_SUFFIX.to_string(),
+            topics: None,
+        }
+    }
+
+    /// JPEG quality (1-100)
+    pub fn jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = quality.clamp(1, 100);
+        self
+    }
+
+    /// Suffix appended to the topics of transcoded images (default `/compressed`);
+    /// an empty suffix keeps the topic names
+    pub fn topic_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.topic_suffix = suffix.into();
+        self
+    }
+
+    /// Only transcode the given topics; other image topics are passed through
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.topics = Some(topics.iter().map(|t| t.to_string()).collect());
+        self
+    }
+
+    /// Compressed format of transcoded images
+    pub fn format(&self) -> TranscodeFormat {
+        self.format
+    }
+
+    /// Compress a raw image
+    pub fn compress_image(&self, msg: &Image) -> Result<CompressedImage> {
+        if msg.encoding == "32FC1" {
+            return Err(BagError::invalid_message_data(
+                "32FC1 depth images cannot be transcoded without loss",
+            ));
+        }
+        let image =
+            image_to_dynamic(msg).map_err(|e| BagError::invalid_message_data(e.to_string()))?;
+        let (width, height) = (image.width(), image.height());
+
+        let mut data = Vec::new();
+        let target = match self.format {
+            TranscodeFormat::Jpeg => {
+                let (pixels, color, target) = match image {
+                    DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                        (image.to_luma8().into_raw(), image::ColorType::L8, "mono8")
+                    }
+                    _ => (image.to_rgb8().into_raw(), image::ColorType::Rgb8, "bgr8"),
+                };
+                JpegEncoder::new_with_quality(&mut data, self.jpeg_quality)
+                    .encode(&pixels, width, height, color)
+                    .map_err(|e| {
+                        BagError::invalid_message_data(format!("JPEG encoding failed: {e}"))
+                    })?;
+                target
+            }
+            TranscodeFormat::Png => {
+                let target = match image {
+                    DynamicImage::ImageLuma8(_) => "mono8",
+                    DynamicImage::ImageLuma16(_) => "mono16",
+                    DynamicImage::ImageRgba8(_) => "bgra8",
+                    DynamicImage::ImageRgb16(_) => "bgr16",
+                    _ => "bgr8",
+                };
+                image
+                    .write_to(&mut Cursor::new(&mut data), image::ImageOutputFormat::Png)
+                    .map_err(|e| {
+                        BagError::invalid_message_data(format!("PNG encoding failed: {e}"))
+                    })?;
+                target
+            }
+        };
+
+        Ok(CompressedImage {
+            header: msg.header.clone(),
+            format: format!(
+                "{}; {} compressed {target}",
+                msg.encoding,
+                self.format.as_str()
+            ),
+            data,
+        })
+    }
+}
+
+impl MessageTransform for ImageTranscode {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        connection.message_type == IMAGE_TYPE
+            && self
+                .topics
+                .as_ref()
+                .map_or(true, |topics| topics.contains(&connection.topic))
+    }
+
+    fn transform(
+        &self,
+        _connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        let msg = Image::from_cdr(&mut CdrDeserializer::new(data)?)?;
+        Ok(Some((timestamp, self.compress_image(&msg)?.to_cdr_bytes())))
+    }
+
+    fn connection(&self, connection: &Connection) -> Result<Connection> {
+        let definition = DefinitionResolver::new().resolve(COMPRESSED_IMAGE_TYPE)?;
+        let type_description_hash =
+            MessageSchemas::parse(COMPRESSED_IMAGE_TYPE, &definition)?.type_description_hash()?;
+        Ok(Connection {
+            topic: format!("{}{}", connection.topic, self.topic_suffix),
+            message_type: COMPRESSED_IMAGE_TYPE.to_string(),
+            message_definition: definition,
+            type_description_hash,
+            ..connection.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::images::compressed_to_image;
+    use crate::messages::{Header, Time};
+
+    fn image(encoding: &str, step: u32, data: Vec<u8>) -> Image {
+        Image {
+            header: Header {
+                stamp: Time { sec: 1, nanosec: 2 },
+                frame_id: "camera".to_string(),
+            },
+            height: 2,
+            width: 2,
+            encoding: encoding.to_string(),
+            is_bigendian: 0,
+            step,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_png_round_trip_keeps_pixels() {
+        let bgr = image("bgr8", 6, (0..12).map(|v| v * 20).collect());
+        let compressed = ImageTranscode::new(TranscodeFormat::Png)
+            .compress_image(&bgr)
+            .unwrap();
+        assert_eq!(compressed.format, "bgr8; png compressed bgr8");
+        assert_eq!(compressed.header, bgr.header);
+        assert_eq!(compressed_to_image(&compressed).unwrap(), bgr);
+
+        let depth = image(
+            "16UC1",
+            4,
+            [1000u16, 2000, 0, 65535]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        );
+        let compressed = ImageTranscode::new(TranscodeFormat::Png)
+            .compress_image(&depth)
+            .unwrap();
+        assert_eq!(compressed.format, "16UC1; png compressed mono16");
+        assert_eq!(compressed_to_image(&compressed).unwrap().data, depth.data);
+
+        let float_depth = image("32FC1", 8, vec![0; 16]);
+        assert!(ImageTranscode::new(TranscodeFormat::Png)
+            .compress_image(&float_depth)
+            .is_err());
+    }
+
+    #[test]
+    fn test_jpeg_transcoding_moves_connection() {
+        let transcode = ImageTranscode::new(TranscodeFormat::Jpeg).topics(&["/front/image_raw"]);
+        let front = Connection::new("/front/image_raw", IMAGE_TYPE).unwrap();
+        assert!(transcode.applies_to(&front));
+        assert!(!transcode.applies_to(&Connection::new("/rear/image_raw", IMAGE_TYPE).unwrap()));
+
+        let output = transcode.connection(&front).unwrap();
+        assert_eq!(output.topic, "/front/image_raw/compressed");
+        assert_eq!(output.message_type, COMPRESSED_IMAGE_TYPE);
+        let schemas =
+            MessageSchemas::parse(COMPRESSED_IMAGE_TYPE, &output.message_definition).unwrap();
+        assert!(schemas.missing_types().is_empty());
+        assert!(output.type_description_hash.starts_with("RIHS01_"));
+
+        let mono = image("mono8", 2, vec![10, 20, 30, 40]);
+        let (timestamp, data) = transcode
+            .transform(&front, 7, &mono.to_cdr_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp, 7);
+        let compressed =
+            CompressedImage::from_cdr(&mut CdrDeserializer::new(&data).unwrap()).unwrap();
+        assert_eq!(compressed.format, "mono8; jpeg compressed mono8");
+        let decoded = compressed_to_image(&compressed).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 2));
+        assert_eq!(decoded.encoding, "mono8");
+    }
+}
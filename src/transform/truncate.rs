@@ -0,0 +1,238 @@
+//! Payload truncation of bulky topics for preview bags
+//!
+//! [`PayloadTruncation`] empties the pixel and point data of large
+//! `sensor_msgs/msg/Image`, `sensor_msgs/msg/CompressedImage` and
+//! `sensor_msgs/msg/PointCloud2` messages while keeping every other field, or drops
+//! those messages altogether. Small topics are copied unchanged, so the preview bag
+//! keeps the topics, timing and headers of the original at a fraction of its size.
+
+use super::MessageTransform;
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::messages::{CompressedImage, FromCdr, Image, PointCloud2, ToCdr};
+use crate::types::Connection;
+
+/// Message types whose bulk data is truncated
+pub const TRUNCATED_TYPES: &[&str] = &[
+    "sensor_msgs/msg/Image",
+    "sensor_msgs/msg/CompressedImage",
+    "sensor_msgs/msg/PointCloud2",
+];
+
+/// Default size in bytes above which payloads are truncated
+pub const DEFAULT_MAX_SIZE: usize = 1024;
+
+/// Truncate the payloads of large image and point cloud messages
+///
+/// A truncated message keeps its header and metadata (dimensions, encoding, point
+/// fields) with an empty `data` field, so it still decodes as its type. Messages of
+/// at most `max_size` bytes and messages of other types are passed through.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::transform::{self, PayloadTruncation};
+/// use rosbags_rs::{Reader, Writer};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Reader::new("path/to/bag")?;
+/// reader.open()?;
+/// let mut writer = Writer::new("path/to/preview", None, None)?;
+/// writer.open()?;
+///
+/// let truncation = PayloadTruncation::new(4096);
+/// let mut connections = std::collections::HashMap::new();
+/// for conn in reader.connections() {
+///     let out = writer.add_connection_from(conn)?;
+///     connections.insert(conn.topic.clone(), out);
+/// }
+/// for msg in reader.messages()? {
+///     let msg = msg?;
+///     let output = transform::apply(&truncation, &msg.connection, msg.timestamp, &msg.data)?;
+///     if let Some((timestamp, data)) = output {
+///         writer.write(&connections[&msg.topic], timestamp, &data)?;
+///     }
+/// }
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PayloadTruncation {
+    max_size: usize,
+    drop_messages: bool,
+    topics: Option<Vec<String>>,
+}
+
+impl Default for PayloadTruncation {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SIZE)
+    }
+}
+
+impl PayloadTruncation {
+    /// Truncate image and point cloud messages larger than `max_size` bytes
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            drop_messages: false,
+            topics: None,
+        }
+    }
+
+    /// Drop large messages instead of truncating them
+    ///
+    /// The connections of their topics are still written, so the preview lists the
+    /// same topics as the original.
+    pub fn drop_messages(mut self) -> Self {
+        self.drop_messages = true;
+        self
+    }
+
+    /// Only truncate the given topics; other image and point cloud topics are passed through
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.topics = Some(topics.iter().map(|t| t.to_string()).collect());
+        self
+    }
+
+    /// Size in bytes above which payloads are truncated
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Whether large messages are dropped instead of truncated
+    pub fn drops_messages(&self) -> bool {
+        self.drop_messages
+    }
+}
+
+impl MessageTransform for PayloadTruncation {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        TRUNCATED_TYPES.contains(&connection.message_type.as_str())
+            && self
+                .topics
+                .as_ref()
+                .map_or(true, |topics| topics.contains(&connection.topic))
+    }
+
+    fn transform(
+        &self,
+        connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        if data.len() <= self.max_size {
+            return Ok(Some((timestamp, data.to_vec())));
+        }
+        if self.drop_messages {
+            return Ok(None);
+        }
+        let mut deserializer = CdrDeserializer::new(data)?;
+        let data = match connection.message_type.as_str() {
+            "sensor_msgs/msg/Image" => {
+                let mut msg = Image::from_cdr(&mut deserializer)?;
+                msg.data.clear();
+                msg.to_cdr_bytes()
+            }
+            "sensor_msgs/msg/CompressedImage" => {
+                let mut msg = CompressedImage::from_cdr(&mut deserializer)?;
+                msg.data.clear();
+                msg.to_cdr_bytes()
+            }
+            "sensor_msgs/msg/PointCloud2" => {
+                let mut msg = PointCloud2::from_cdr(&mut deserializer)?;
+                msg.data.clear();
+                msg.to_cdr_bytes()
+            }
+            other => return Err(BagError::message_type_not_found(other)),
+        };
+        Ok(Some((timestamp, data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Header, PointField, Time};
+
+    fn header() -> Header {
+        Header {
+            stamp: Time { sec: 1, nanosec: 2 },
+            frame_id: "lidar".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_truncated_messages_keep_their_metadata() {
+        let cloud = PointCloud2 {
+            header: header(),
+            height: 1,
+            width: 100,
+            fields: vec![PointField {
+                name: "x".to_string(),
+                offset: 0,
+                datatype: 7,
+                count: 1,
+            }],
+            is_bigendian: false,
+            point_step: 4,
+            row_step: 400,
+            data: vec![7; 400],
+            is_dense: true,
+        };
+        let connection = Connection::new("/points", "sensor_msgs/msg/PointCloud2").unwrap();
+        let truncation = PayloadTruncation::new(64);
+        assert!(truncation.applies_to(&connection));
+        let data = cloud.to_cdr_bytes();
+
+        let (timestamp, output) = truncation
+            .transform(&connection, 7, &data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp, 7);
+        assert!(output.len() < 100);
+        let mut deserializer = CdrDeserializer::new(&output).unwrap();
+        let small = PointCloud2::from_cdr(&mut deserializer).unwrap();
+        assert_eq!(
+            small,
+            PointCloud2 {
+                data: Vec::new(),
+                ..cloud
+            }
+        );
+
+        let image = Image {
+            header: header(),
+            height: 2,
+            width: 2,
+            encoding: "mono8".to_string(),
+            is_bigendian: 0,
+            step: 2,
+            data: vec![1, 2, 3, 4],
+        };
+        let camera = Connection::new("/camera", "sensor_msgs/msg/Image").unwrap();
+        // Small messages are passed through
+        let data = image.to_cdr_bytes();
+        assert_eq!(
+            truncation.transform(&camera, 7, &data).unwrap(),
+            Some((7, data))
+        );
+    }
+
+    #[test]
+    fn test_drop_messages_and_topic_selection() {
+        let truncation = PayloadTruncation::new(8)
+            .drop_messages()
+            .topics(&["/front/image"]);
+        let front = Connection::new("/front/image", "sensor_msgs/msg/Image").unwrap();
+        let rear = Connection::new("/rear/image", "sensor_msgs/msg/Image").unwrap();
+        let imu = Connection::new("/imu", "sensor_msgs/msg/Imu").unwrap();
+        assert!(truncation.applies_to(&front));
+        assert!(!truncation.applies_to(&rear));
+        assert!(!truncation.applies_to(&imu));
+        assert_eq!(truncation.transform(&front, 7, &[0; 16]).unwrap(), None);
+        assert_eq!(
+            truncation.transform(&front, 7, &[0; 8]).unwrap(),
+            Some((7, vec![0; 8]))
+        );
+    }
+}
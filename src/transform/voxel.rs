@@ -0,0 +1,294 @@
+//! Voxel grid downsampling of point cloud topics for archival
+//!
+//! [`VoxelDownsample`] thins `sensor_msgs/msg/PointCloud2` messages to one point per
+//! cube of a configurable leaf size, like PCL's `VoxelGrid` filter. Lidar topics are
+//! usually the bulk of a bag, and a leaf size of a few centimeters keeps the
+//! geometry while dropping most of the points.
+
+use super::MessageTransform;
+use crate::cdr::CdrDeserializer;
+use crate::error::{BagError, Result};
+use crate::export::pointcloud::POINT_CLOUD2_TYPE;
+use crate::messages::{FromCdr, PointCloud2, PointField, ToCdr};
+use crate::types::Connection;
+use std::collections::HashMap;
+
+/// Downsample point cloud topics on a voxel grid
+///
+/// The points of every occupied voxel are replaced by one point at their centroid.
+/// It keeps the layout of the cloud and the other fields (intensity, color, ring,
+/// timestamps) of the first point in the voxel, so the output decodes like the
+/// input. Points with non-finite coordinates are dropped, and downsampled clouds are
+/// unorganized (`height` 1) and dense, with `width` and `row_step` of the remaining
+/// points.
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::transform::{self, VoxelDownsample};
+/// use rosbags_rs::{Reader, Writer};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = Reader::builder("path/to/bag").open()?;
+/// let mut writer = Writer::builder("path/to/archive").open()?;
+/// // One point per 5 cm cube
+/// let voxel = VoxelDownsample::new(0.05).topics(&["/lidar/points"]);
+/// transform::rewrite(&reader, &mut writer, &voxel)?;
+/// writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VoxelDownsample {
+    leaf_size: [f64; 3],
+    topics: Option<Vec<String>>,
+}
+
+impl VoxelDownsample {
+    /// Downsample every point cloud topic with cubic voxels of `leaf_size` meters
+    pub fn new(leaf_size: f64) -> Self {
+        Self::with_leaf_sizes([leaf_size; 3])
+    }
+
+    /// Downsample with voxels of the given size along `x`, `y` and `z`
+    ///
+    /// Axes with a size that is not positive are not divided, so `[0.1, 0.1, 0.0]`
+    /// keeps one point per 10 cm column.
+    pub fn with_leaf_sizes(leaf_size: [f64; 3]) -> Self {
+        Self {
+            leaf_size,
+            topics: None,
+        }
+    }
+
+    /// Only downsample the given topics; other point cloud topics are passed through
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.topics = Some(topics.iter().map(|t| t.to_string()).collect());
+        self
+    }
+
+    /// Voxel size along `x`, `y` and `z` in meters
+    pub fn leaf_size(&self) -> [f64; 3] {
+        self.leaf_size
+    }
+
+    /// Downsample a point cloud
+    pub fn downsample(&self, cloud: &PointCloud2) -> Result<PointCloud2> {
+        let coordinate = |name: &str| {
+            cloud.fields.iter().find(|f| f.name == name).ok_or_else(|| {
+                BagError::invalid_message_data(format!("Point cloud has no '{name}' field"))
+            })
+        };
+        let axes = [coordinate("x")?, coordinate("y")?, coordinate("z")?];
+        let point_step = cloud.point_step as usize;
+        for field in axes {
+            let fits = field
+                .datatype_size()
+                .is_some_and(|size| field.offset as usize + size <= point_step);
+            if !fits {
+                return Err(BagError::invalid_message_data(format!(
+                    "Field '{}' does not fit point step {point_step}",
+                    field.name
+                )));
+            }
+        }
+
+        let row_step = cloud.row_step as usize;
+        let width = cloud.width as usize;
+        let height = cloud.height as usize;
+        if point_step == 0 || width * point_step > row_step {
+            return Err(BagError::invalid_message_data(format!(
+                "Inconsistent layout: width {width} x point step {point_step} exceeds row step {row_step}"
+            )));
+        }
+        if cloud.data.len() < row_step * height {
+            return Err(BagError::invalid_message_data(format!(
+                "Point data truncated: expected {} bytes, got {}",
+                row_step * height,
+                cloud.data.len()
+            )));
+        }
+
+        // Voxels in the order of their first point, with the sum of their coordinates
+        let mut voxels: HashMap<[i64; 3], usize> = HashMap::new();
+        let mut occupied: Vec<(&[u8], [f64; 3], u32)> = Vec::new();
+        let rows = cloud.data.chunks(row_step).take(height);
+        for point in rows.flat_map(|row| row[..width * point_step].chunks_exact(point_step)) {
+            let read = |field: &PointField| {
+                field
+                    .read_value(point, 0, cloud.is_bigendian)
+                    .unwrap_or(f64::NAN)
+            };
+            let position = [read(axes[0]), read(axes[1]), read(axes[2])];
+            if !position.iter().all(|v| v.is_finite()) {
+                continue;
+            }
+            let mut key = [0; 3];
+            for ((key, value), leaf) in key.iter_mut().zip(position).zip(self.leaf_size) {
+                if leaf > 0.0 {
+                    *key = (value / leaf).floor() as i64;
+                }
+            }
+            let index = *voxels.entry(key).or_insert_with(|| {
+                occupied.push((point, [0.0; 3], 0));
+                occupied.len() - 1
+            });
+            let (_, sum, count) = &mut occupied[index];
+            for (sum, value) in sum.iter_mut().zip(position) {
+                *sum += value;
+            }
+            *count += 1;
+        }
+
+        let mut data = Vec::with_capacity(occupied.len() * point_step);
+        for (first, sum, count) in &occupied {
+            let start = data.len();
+            data.extend_from_slice(first);
+            let point = &mut data[start..];
+            for (field, sum) in axes.iter().zip(sum) {
+                field.write_value(point, 0, sum / f64::from(*count), cloud.is_bigendian);
+            }
+        }
+
+        let width = occupied.len() as u32;
+        Ok(PointCloud2 {
+            header: cloud.header.clone(),
+            height: 1,
+            width,
+            fields: cloud.fields.clone(),
+            is_bigendian: cloud.is_bigendian,
+            point_step: cloud.point_step,
+            row_step: width * cloud.point_step,
+            data,
+            is_dense: true,
+        })
+    }
+}
+
+impl MessageTransform for VoxelDownsample {
+    fn applies_to(&self, connection: &Connection) -> bool {
+        connection.message_type == POINT_CLOUD2_TYPE
+            && self
+                .topics
+                .as_ref()
+                .map_or(true, |topics| topics.contains(&connection.topic))
+    }
+
+    fn transform(
+        &self,
+        _connection: &Connection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>> {
+        let cloud = PointCloud2::from_cdr(&mut CdrDeserializer::new(data)?)?;
+        Ok(Some((timestamp, self.downsample(&cloud)?.to_cdr_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::pointcloud::decode_points;
+    use crate::messages::{Header, Time};
+
+    fn field(name: &str, offset: u32, datatype: u8) -> PointField {
+        PointField {
+            name: name.to_string(),
+            offset,
+            datatype,
+            count: 1,
+        }
+    }
+
+    /// Cloud of `x`, `y`, `z` float32, `intensity` uint8 and 3 padding bytes per point,
+    /// in rows of two points with 4 padding bytes per row
+    fn cloud(points: &[([f32; 3], u8)]) -> PointCloud2 {
+        let mut data = Vec::new();
+        for row in points.chunks(2) {
+            for (position, intensity) in row {
+                for value in position {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                data.extend_from_slice(&[*intensity, 0, 0, 0]);
+            }
+            data.resize(data.len() + 36 - row.len() * 16, 0);
+        }
+        PointCloud2 {
+            header: Header {
+                stamp: Time { sec: 1, nanosec: 2 },
+                frame_id: "lidar".to_string(),
+            },
+            height: (points.len() / 2) as u32,
+            width: 2,
+            fields: vec![
+                field("x", 0, PointField::FLOAT32),
+                field("y", 4, PointField::FLOAT32),
+                field("z", 8, PointField::FLOAT32),
+                field("intensity", 12, PointField::UINT8),
+            ],
+            is_bigendian: false,
+            point_step: 16,
+            row_step: 36,
+            data,
+            is_dense: false,
+        }
+    }
+
+    #[test]
+    fn test_points_merge_into_voxel_centroids() {
+        let input = cloud(&[
+            ([0.01, 0.01, 0.01], 10),
+            ([0.03, 0.05, 0.07], 20),
+            ([1.0, 0.0, 0.0], 30),
+            ([f32::NAN, 0.0, 0.0], 40),
+            ([-0.02, 0.0, 0.0], 50),
+            ([1.05, 0.01, 0.02], 60),
+        ]);
+        let output = VoxelDownsample::new(0.1).downsample(&input).unwrap();
+        assert_eq!((output.width, output.height), (3, 1));
+        assert_eq!(output.row_step, 48);
+        assert_eq!(output.data.len(), 48);
+        assert!(output.is_dense);
+        assert_eq!(output.fields, input.fields);
+        assert_eq!(output.header, input.header);
+
+        let decoded = decode_points(&output).unwrap();
+        let rounded = |p: &crate::export::pointcloud::CloudPoint| {
+            [p.x, p.y, p.z, f64::from(p.intensity)].map(|v| (v * 1000.0).round() / 1000.0)
+        };
+        let points: Vec<_> = decoded.points.iter().map(rounded).collect();
+        assert_eq!(
+            points,
+            [
+                [0.02, 0.03, 0.04, 10.0],
+                [1.025, 0.005, 0.01, 30.0],
+                [-0.02, 0.0, 0.0, 50.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transform_reserializes_clouds() {
+        let connection = Connection::new("/points", POINT_CLOUD2_TYPE).unwrap();
+        let voxel = VoxelDownsample::with_leaf_sizes([1.0, 1.0, 0.0]).topics(&["/points"]);
+        assert!(voxel.applies_to(&connection));
+        assert!(!voxel.applies_to(&Connection::new("/other", POINT_CLOUD2_TYPE).unwrap()));
+
+        // Points above each other share a column without a z leaf size
+        let input = cloud(&[([0.5, 0.5, 0.0], 1), ([0.5, 0.5, 30.0], 2)]);
+        let (timestamp, data) = voxel
+            .transform(&connection, 7, &input.to_cdr_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(timestamp, 7);
+        let output = PointCloud2::from_cdr(&mut CdrDeserializer::new(&data).unwrap()).unwrap();
+        assert_eq!(output.width, 1);
+        let z = output.fields[2].read_value(&output.data, 0, false).unwrap();
+        assert_eq!(z, 15.0);
+
+        let flat = PointCloud2 {
+            fields: input.fields[..2].to_vec(),
+            ..input
+        };
+        assert!(voxel.downsample(&flat).is_err());
+    }
+}
@@ -1,6 +1,15 @@
 //! Core data types for ROS2 bag files
 
+use crate::cdr::CdrDeserializer;
+use crate::definitions::DefinitionResolver;
+use crate::dynamic::{connection_schemas, decode_message, DynamicMessage};
+use crate::error::{BagError, ErrorContext};
+use crate::messages::{FromCdr, MessageType};
+use crate::schema::MessageSchemas;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 /// Represents a connection to a topic in the bag file
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,10 +30,21 @@ pub struct Connection {
     pub serialization_format: String,
     /// QoS profiles offered for this topic
     pub offered_qos_profiles: Vec<QosProfile>,
+    /// Key-value metadata of the connection in the storage, kept as read
+    ///
+    /// Holds the metadata map of MCAP channels (e.g. [`OFFERED_QOS_PROFILES_KEY`] and
+    /// keys added by the recorder such as the RMW implementation) and, for SQLite3
+    /// storage, the offered QoS profiles string of the `topics` table. Writers store
+    /// it unchanged, so copied bags keep the exact strings of the original.
+    pub metadata: BTreeMap<String, String>,
 }
 
+/// Key of the offered QoS profiles, serialized as YAML, in [`Connection::metadata`]
+/// and the metadata of MCAP channels written by rosbag2
+pub const OFFERED_QOS_PROFILES_KEY: &str = "offered_qos_profiles";
+
 /// Message definition format and content
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MessageDefinition {
     /// Format of the definition (MSG, IDL, or None)
     pub format: MessageDefinitionFormat,
@@ -33,7 +53,7 @@ pub struct MessageDefinition {
 }
 
 /// Format of message definitions
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MessageDefinitionFormat {
     /// No definition available
     None,
@@ -134,6 +154,66 @@ pub struct TopicInfo {
     pub connections: Vec<Connection>,
 }
 
+/// A storage file of a bag and the time span of its messages
+///
+/// Split bags (`ros2 bag record --max-bag-size/--max-bag-duration`) consist of several
+/// files; returned by `Reader::files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagFile {
+    /// Position of the file in the bag, the `file_index` of its messages
+    pub index: usize,
+    /// Path of the file
+    pub path: PathBuf,
+    /// Timestamp of the first message in nanoseconds (0 without messages)
+    pub start_time: u64,
+    /// Timestamp of the last message in nanoseconds (0 without messages)
+    pub end_time: u64,
+    /// Number of messages in the file
+    pub message_count: u64,
+}
+
+impl BagFile {
+    /// Whether the file holds messages in the time range from `start` (inclusive)
+    /// to `stop` (exclusive)
+    pub fn overlaps(&self, start: u64, stop: u64) -> bool {
+        self.message_count > 0 && self.start_time < stop && start <= self.end_time
+    }
+}
+
+/// A file attached to an MCAP storage file, e.g. a calibration file or URDF
+///
+/// Returned by `Reader::attachments` and written with `Writer::add_attachment`.
+/// SQLite3 storage has no attachments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Name of the attachment, e.g. a file name
+    pub name: String,
+    /// Media type of the data, e.g. `application/xml`
+    pub media_type: String,
+    /// Time the attachment was recorded in nanoseconds since epoch
+    pub log_time: u64,
+    /// Time the attachment was created in nanoseconds since epoch
+    pub create_time: u64,
+    /// Contents of the attachment
+    pub data: Vec<u8>,
+    /// Index of the storage file holding the attachment (see `Reader::files`)
+    pub file_index: usize,
+}
+
+/// A named metadata record of an MCAP storage file
+///
+/// Returned by `Reader::mcap_metadata` and written with `Writer::add_mcap_metadata`.
+/// The `rosbag2` record holding the bag metadata is not listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McapMetadata {
+    /// Name of the record
+    pub name: String,
+    /// Key-value pairs of the record
+    pub metadata: BTreeMap<String, String>,
+    /// Index of the storage file holding the record (see `Reader::files`)
+    pub file_index: usize,
+}
+
 /// A message from the bag file
 #[derive(Debug, Clone)]
 pub struct Message {
@@ -141,10 +221,77 @@ pub struct Message {
     pub connection: Connection,
     /// Topic name (convenience field)
     pub topic: String,
-    /// Timestamp in nanoseconds since epoch
+    /// Log (receive) time in nanoseconds since epoch, which orders messages in the bag
     pub timestamp: u64,
+    /// Publish time in nanoseconds since epoch
+    ///
+    /// Recorded by MCAP bags; SQLite3 bags only store the log time, so it equals
+    /// `timestamp` there.
+    pub publish_time: u64,
+    /// Sequence number of the message on its channel, recorded by MCAP bags (0 otherwise)
+    pub sequence: u32,
     /// Raw message data (serialized)
     pub data: Vec<u8>,
+    /// Index of the storage file holding the message (see `Reader::files`)
+    pub file_index: usize,
+}
+
+impl Message {
+    /// Decode the payload as `T`, checking that the connection carries `T`
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::messages::Imu;
+    /// use rosbags_rs::Reader;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let reader = Reader::builder("path/to/bag").open()?;
+    /// for message in reader.messages()? {
+    ///     let message = message?;
+    ///     if message.topic == "/imu" {
+    ///         let imu: Imu = message.decode()?;
+    ///         println!("{:?}", imu.linear_acceleration);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode<T: FromCdr + MessageType>(&self) -> crate::Result<T> {
+        if !T::accepts(&self.connection.message_type) {
+            return Err(BagError::message_type_mismatch(
+                &self.topic,
+                T::MESSAGE_TYPE,
+                &self.connection.message_type,
+            ));
+        }
+        self.decode_unchecked()
+    }
+
+    /// Decode the payload as `T` without checking the connection's message type
+    ///
+    /// For structs that do not implement [`MessageType`], e.g. hand-written ones.
+    pub fn decode_unchecked<T: FromCdr>(&self) -> crate::Result<T> {
+        CdrDeserializer::new(&self.data)
+            .and_then(|mut deserializer| T::from_cdr(&mut deserializer))
+            .map_err(|e| e.with_context(self.error_context()))
+    }
+
+    /// Decode the payload from the connection's message definition
+    ///
+    /// The definition is parsed once per connection type and cached.
+    pub fn decode_dynamic(&self) -> crate::Result<DynamicMessage> {
+        let schemas = connection_schemas(&self.connection)?;
+        CdrDeserializer::new(&self.data)
+            .and_then(|mut deserializer| {
+                decode_message(&mut deserializer, &schemas, &schemas.root().name)
+            })
+            .map_err(|e| e.with_context(self.error_context()))
+    }
+
+    /// Topic and timestamp of the message, attached to its decoding errors
+    pub fn error_context(&self) -> ErrorContext {
+        ErrorContext::message(&self.topic, self.timestamp)
+    }
 }
 
 /// A raw message from the bag file with minimal processing overhead
@@ -153,10 +300,70 @@ pub struct Message {
 pub struct RawMessage {
     /// Connection this message belongs to
     pub connection: Connection,
-    /// Timestamp in nanoseconds since epoch
+    /// Log (receive) time in nanoseconds since epoch
     pub timestamp: u64,
+    /// Publish time in nanoseconds since epoch, `timestamp` if the storage lacks it
+    pub publish_time: u64,
+    /// Sequence number of the message on its channel, recorded by MCAP bags (0 otherwise)
+    pub sequence: u32,
     /// Raw serialized message data (CDR format)
     pub raw_data: Vec<u8>,
+    /// Index of the storage file holding the message (see `Reader::files`)
+    pub file_index: usize,
+}
+
+/// A raw message whose data is borrowed from the storage buffer where possible
+///
+/// Returned by `Reader::raw_messages_borrowed`. Payloads of uncompressed MCAP chunks
+/// point into the loaded file, or the memory-mapped file with the `unsafe-opt`
+/// feature, so large messages such as images are not copied. Payloads of compressed
+/// chunks and SQLite3 rows are owned.
+#[derive(Debug, Clone)]
+pub struct RawMessageRef<'a> {
+    /// Connection this message belongs to
+    pub connection: Cow<'a, Connection>,
+    /// Log (receive) time in nanoseconds since epoch
+    pub timestamp: u64,
+    /// Publish time in nanoseconds since epoch, `timestamp` if the storage lacks it
+    pub publish_time: u64,
+    /// Sequence number of the message on its channel, recorded by MCAP bags (0 otherwise)
+    pub sequence: u32,
+    /// Raw serialized message data (CDR format)
+    pub data: Cow<'a, [u8]>,
+    /// Index of the storage file holding the message (see `Reader::files`)
+    pub file_index: usize,
+}
+
+impl RawMessageRef<'_> {
+    /// Whether the data points into the storage buffer instead of an owned copy
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
+    /// Copy the message into an owned [`RawMessage`]
+    pub fn into_owned(self) -> RawMessage {
+        RawMessage {
+            connection: self.connection.into_owned(),
+            timestamp: self.timestamp,
+            publish_time: self.publish_time,
+            sequence: self.sequence,
+            raw_data: self.data.into_owned(),
+            file_index: self.file_index,
+        }
+    }
+}
+
+impl From<RawMessage> for RawMessageRef<'_> {
+    fn from(message: RawMessage) -> Self {
+        Self {
+            connection: Cow::Owned(message.connection),
+            timestamp: message.timestamp,
+            publish_time: message.publish_time,
+            sequence: message.sequence,
+            data: Cow::Owned(message.raw_data),
+            file_index: message.file_index,
+        }
+    }
 }
 
 /// Time duration in nanoseconds
@@ -200,6 +407,8 @@ pub enum StoragePlugin {
     Sqlite3,
     /// MCAP storage
     Mcap,
+    /// In-memory storage for tests (see [`crate::storage::memory`])
+    Memory,
 }
 
 impl Default for MessageDefinition {
@@ -227,16 +436,449 @@ impl Default for QosProfile {
     }
 }
 
+/// Serialization formats accepted for connections
+pub const SERIALIZATION_FORMATS: &[&str] = &["cdr"];
+
+/// Interface kinds accepted in message types (`pkg/<kind>/Type`)
+const MESSAGE_TYPE_KINDS: &[&str] = &["msg", "srv", "action"];
+
+/// rosbag2-specific fields of a [`Connection`], returned by [`Connection::ext`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionExt<'a> {
+    /// Serialization format (typically "cdr")
+    pub serialization_format: &'a str,
+    /// QoS profiles offered for the topic
+    pub offered_qos_profiles: &'a [QosProfile],
+    /// Key-value metadata of the connection in the storage
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+/// Builder for a validated [`Connection`]
+///
+/// Created by [`Connection::builder`]; optional fields default to no message
+/// definition, no type hash, `cdr` serialization and no QoS profiles.
+#[derive(Debug, Clone)]
+pub struct ConnectionBuilder {
+    connection: Connection,
+}
+
+impl ConnectionBuilder {
+    /// Set the connection ID (default: 1)
+    pub fn id(mut self, id: u32) -> Self {
+        self.connection.id = id;
+        self
+    }
+
+    /// Set the message definition
+    pub fn message_definition(mut self, message_definition: MessageDefinition) -> Self {
+        self.connection.message_definition = message_definition;
+        self
+    }
+
+    /// Set the type description hash
+    pub fn type_description_hash(mut self, type_description_hash: impl Into<String>) -> Self {
+        self.connection.type_description_hash = type_description_hash.into();
+        self
+    }
+
+    /// Set the number of messages on the connection
+    pub fn message_count(mut self, message_count: u64) -> Self {
+        self.connection.message_count = message_count;
+        self
+    }
+
+    /// Set the serialization format (default: `cdr`)
+    pub fn serialization_format(mut self, serialization_format: impl Into<String>) -> Self {
+        self.connection.serialization_format = serialization_format.into();
+        self
+    }
+
+    /// Set the offered QoS profiles
+    pub fn offered_qos_profiles(mut self, offered_qos_profiles: Vec<QosProfile>) -> Self {
+        self.connection.offered_qos_profiles = offered_qos_profiles;
+        self
+    }
+
+    /// Set the key-value metadata stored with the connection
+    pub fn metadata(mut self, metadata: BTreeMap<String, String>) -> Self {
+        self.connection.metadata = metadata;
+        self
+    }
+
+    /// Validate and build the connection
+    pub fn build(self) -> crate::Result<Connection> {
+        self.connection.validate()?;
+        Ok(self.connection)
+    }
+}
+
 impl Connection {
+    /// Create a validated connection with default optional fields
+    ///
+    /// Shorthand for `Connection::builder(topic, message_type).build()`.
+    pub fn new(
+        topic: impl Into<String>,
+        message_type: impl Into<String>,
+    ) -> crate::Result<Connection> {
+        Self::builder(topic, message_type).build()
+    }
+
+    /// Start building a connection; [`ConnectionBuilder::build`] validates it
+    ///
+    /// # Example
+    /// ```
+    /// use rosbags_rs::Connection;
+    ///
+    /// let connection = Connection::builder("/imu/data", "sensor_msgs/msg/Imu")
+    ///     .id(3)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(connection.serialization_format, "cdr");
+    ///
+    /// assert!(Connection::new("imu data", "sensor_msgs/msg/Imu").is_err());
+    /// assert!(Connection::new("/imu/data", "sensor_msgs/Imu").is_err());
+    /// ```
+    pub fn builder(topic: impl Into<String>, message_type: impl Into<String>) -> ConnectionBuilder {
+        ConnectionBuilder {
+            connection: Connection {
+                id: 1,
+                topic: topic.into(),
+                message_type: message_type.into(),
+                message_definition: MessageDefinition::default(),
+                type_description_hash: String::new(),
+                message_count: 0,
+                serialization_format: "cdr".to_string(),
+                offered_qos_profiles: Vec::new(),
+                metadata: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Check the topic name, message type and serialization format
+    ///
+    /// Topic names must be fully qualified ROS2 names (`/ns/name`: tokens of
+    /// letters, digits and underscores not starting with a digit, no empty tokens).
+    /// Message types must be `pkg/msg/Type`, `pkg/srv/Type` or `pkg/action/Type`.
+    /// The serialization format must be one of [`SERIALIZATION_FORMATS`].
+    pub fn validate(&self) -> crate::Result<()> {
+        let invalid = |reason: String| BagError::invalid_connection(&self.topic, reason);
+
+        let tokens = self
+            .topic
+            .strip_prefix('/')
+            .ok_or_else(|| invalid("topic name must start with '/'".to_string()))?;
+        for token in tokens.split('/') {
+            if !is_name_token(token) {
+                return Err(invalid(format!(
+                    "topic name token '{token}' must be non-empty, contain only letters, digits and '_', and not start with a digit"
+                )));
+            }
+        }
+
+        let parts: Vec<&str> = self.message_type.split('/').collect();
+        let valid_type = matches!(
+            parts.as_slice(),
+            [package, kind, name]
+                if is_name_token(package)
+                    && MESSAGE_TYPE_KINDS.contains(kind)
+                    && is_name_token(name)
+        );
+        if !valid_type {
+            return Err(invalid(format!(
+                "message type '{}' is not of the form 'pkg/msg/Type'",
+                self.message_type
+            )));
+        }
+
+        if !SERIALIZATION_FORMATS.contains(&self.serialization_format.as_str()) {
+            return Err(BagError::UnsupportedSerializationFormat {
+                format: self.serialization_format.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the connection ID
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Get the topic name
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
     /// Get the message type (compatibility alias for message_type)
     pub fn msgtype(&self) -> &str {
         &self.message_type
     }
 
+    /// Get the message definition (compatibility alias for message_definition)
+    pub fn msgdef(&self) -> &MessageDefinition {
+        &self.message_definition
+    }
+
+    /// Get the type description hash (compatibility alias for type_description_hash)
+    ///
+    /// Empty for bags recorded before ROS2 Iron.
+    pub fn digest(&self) -> &str {
+        &self.type_description_hash
+    }
+
     /// Get the message count (compatibility alias for message_count)
     pub fn msgcount(&self) -> u64 {
         self.message_count
     }
+
+    /// Get the serialization format (e.g. `cdr`)
+    pub fn serialization_format(&self) -> &str {
+        &self.serialization_format
+    }
+
+    /// Get the QoS profiles offered for the topic
+    pub fn offered_qos_profiles(&self) -> &[QosProfile] {
+        &self.offered_qos_profiles
+    }
+
+    /// Get the rosbag2-specific connection fields
+    ///
+    /// Mirrors `Connection.ext` (`ConnectionExtRosbag2`) of Python rosbags.
+    ///
+    /// # Example
+    /// ```
+    /// use rosbags_rs::Connection;
+    ///
+    /// let connection = Connection::new("/imu/data", "sensor_msgs/msg/Imu").unwrap();
+    /// assert_eq!(connection.msgtype(), "sensor_msgs/msg/Imu");
+    /// assert_eq!(connection.ext().serialization_format, "cdr");
+    /// assert!(connection.ext().offered_qos_profiles.is_empty());
+    /// ```
+    pub fn ext(&self) -> ConnectionExt<'_> {
+        ConnectionExt {
+            serialization_format: &self.serialization_format,
+            offered_qos_profiles: &self.offered_qos_profiles,
+            metadata: &self.metadata,
+        }
+    }
+
+    /// Parse the stored message definition into runtime schemas
+    ///
+    /// Gives access to field layouts and constants (e.g. `STATUS_FIX`) of the
+    /// message type and every type it depends on. Connections of bags without stored
+    /// definitions, or with empty ones, fall back to the
+    /// [bundled definitions](crate::definitions).
+    pub fn message_schemas(&self) -> crate::Result<MessageSchemas> {
+        if self.message_definition.format == MessageDefinitionFormat::None
+            || self.message_definition.data.trim().is_empty()
+        {
+            if let Ok(definition) = DefinitionResolver::new().resolve(&self.message_type) {
+                return MessageSchemas::parse(&self.message_type, &definition);
+            }
+        }
+        MessageSchemas::parse(&self.message_type, &self.message_definition)
+    }
+
+    /// Get what the topic carries: messages, service events or action traffic
+    pub fn kind(&self) -> TopicKind {
+        TopicKind::of(&self.topic, &self.message_type)
+    }
+
+    /// Get the service of a service event topic (e.g. `/add_two_ints`)
+    ///
+    /// Service events of actions (e.g. `/fibonacci/_action/send_goal/_service_event`)
+    /// name the action's internal service.
+    pub fn service_name(&self) -> Option<&str> {
+        service_name(&self.topic, &self.message_type)
+    }
+
+    /// Get the service type of a service event topic (e.g. `example_interfaces/srv/AddTwoInts`)
+    pub fn service_type(&self) -> Option<&str> {
+        service_type(&self.topic, &self.message_type)
+    }
+
+    /// Get the action of an action topic (e.g. `/fibonacci`)
+    pub fn action_name(&self) -> Option<&str> {
+        self.topic
+            .find(ACTION_TOPIC_INFIX)
+            .map(|index| &self.topic[..index])
+    }
+}
+
+/// Whether `token` is a valid ROS2 name token: `[A-Za-z_][A-Za-z0-9_]*`
+fn is_name_token(token: &str) -> bool {
+    let mut chars = token.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Service of a service event topic
+pub(crate) fn service_name<'a>(topic: &'a str, message_type: &str) -> Option<&'a str> {
+    if TopicKind::of(topic, message_type) == TopicKind::Message {
+        return None;
+    }
+    topic.strip_suffix(SERVICE_EVENT_TOPIC_SUFFIX)
+}
+
+/// Service type of a service event topic
+pub(crate) fn service_type<'a>(topic: &str, message_type: &'a str) -> Option<&'a str> {
+    service_name(topic, message_type)?;
+    message_type.strip_suffix(SERVICE_EVENT_TYPE_SUFFIX)
+}
+
+/// Whether `text` matches the glob `pattern` (see `Reader::topics_matching`)
+pub(crate) fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        [b'*', rest @ ..] => {
+            let segment = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment).any(|i| glob_match(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != b'/') && glob_match(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Pattern selecting topics by name
+///
+/// Both kinds match whole topic names. Globs follow [`Reader::topics_matching`]
+/// (`*` within a name segment, `**` across segments, `?` a single character other
+/// than `/`); regular expressions use the syntax of the `regex` crate and, like
+/// `ros2 bag play --regex`, must match the entire name.
+///
+/// [`Reader::topics_matching`]: crate::Reader::topics_matching
+#[derive(Debug, Clone)]
+pub enum TopicPattern {
+    /// Glob pattern
+    Glob(String),
+    /// Regular expression, anchored to the whole topic name
+    Regex(regex::Regex),
+}
+
+impl TopicPattern {
+    /// Create a glob pattern
+    pub fn glob(pattern: impl Into<String>) -> Self {
+        Self::Glob(pattern.into())
+    }
+
+    /// Create a regular expression pattern matching entire topic names
+    pub fn regex(pattern: &str) -> Result<Self, BagError> {
+        regex::Regex::new(&format!("^(?:{pattern})$"))
+            .map(Self::Regex)
+            .map_err(|e| BagError::generic(format!("Invalid topic regex '{pattern}': {e}")))
+    }
+
+    /// Whether `topic` matches the pattern
+    pub fn matches(&self, topic: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => glob_match(pattern.as_bytes(), topic.as_bytes()),
+            Self::Regex(regex) => regex.is_match(topic),
+        }
+    }
+
+    /// The pattern as an anchored regular expression
+    #[cfg(not(feature = "write-only"))]
+    fn to_regex(&self) -> String {
+        match self {
+            Self::Glob(pattern) => {
+                let mut regex = String::from("^");
+                let mut rest = pattern.as_str();
+                while let Some(c) = rest.chars().next() {
+                    if let Some(after) = rest.strip_prefix("**") {
+                        regex.push_str(".*");
+                        rest = after;
+                        continue;
+                    }
+                    match c {
+                        '*' => regex.push_str("[^/]*"),
+                        '?' => regex.push_str("[^/]"),
+                        c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                    }
+                    rest = &rest[c.len_utf8()..];
+                }
+                regex.push('$');
+                regex
+            }
+            Self::Regex(regex) => regex.as_str().to_string(),
+        }
+    }
+}
+
+impl PartialEq for TopicPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Glob(a), Self::Glob(b)) => a == b,
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TopicPattern {}
+
+impl From<&str> for TopicPattern {
+    fn from(pattern: &str) -> Self {
+        Self::glob(pattern)
+    }
+}
+
+impl From<String> for TopicPattern {
+    fn from(pattern: String) -> Self {
+        Self::glob(pattern)
+    }
+}
+
+/// One regular expression matching the topics matched by any of `patterns`
+///
+/// Fails only if the union exceeds the size limit of the `regex` crate.
+#[cfg(not(feature = "write-only"))]
+pub(crate) fn topics_regex(patterns: &[TopicPattern]) -> Result<regex::Regex, BagError> {
+    let alternatives: Vec<String> = patterns.iter().map(TopicPattern::to_regex).collect();
+    regex::Regex::new(&alternatives.join("|"))
+        .map_err(|e| BagError::generic(format!("Invalid topic patterns: {e}")))
+}
+
+/// Suffix of topics on which service events are recorded
+pub const SERVICE_EVENT_TOPIC_SUFFIX: &str = "/_service_event";
+
+/// Suffix of service event message types (e.g. `example_interfaces/srv/AddTwoInts_Event`)
+pub const SERVICE_EVENT_TYPE_SUFFIX: &str = "_Event";
+
+/// Infix of the feedback, status and service topics of an action
+pub const ACTION_TOPIC_INFIX: &str = "/_action/";
+
+/// What a recorded topic carries
+///
+/// rosbag2 records service introspection events and action traffic on hidden topics
+/// next to regular ones. They are stored like any other topic, so readers and writers
+/// handle them unchanged; this tells them apart for display and filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TopicKind {
+    /// Regular topic
+    Message,
+    /// Service introspection events (`<service>/_service_event`)
+    ServiceEvent,
+    /// Feedback, status or service events of an action (`<action>/_action/...`)
+    Action,
+}
+
+impl TopicKind {
+    /// Classify a topic by its name and message type
+    pub fn of(topic: &str, message_type: &str) -> Self {
+        if topic.contains(ACTION_TOPIC_INFIX) {
+            Self::Action
+        } else if topic.ends_with(SERVICE_EVENT_TOPIC_SUFFIX)
+            && message_type.ends_with(SERVICE_EVENT_TYPE_SUFFIX)
+        {
+            Self::ServiceEvent
+        } else {
+            Self::Message
+        }
+    }
 }
 
 impl CompressionMode {
@@ -259,6 +901,17 @@ impl CompressionFormat {
             CompressionFormat::Zstd => "zstd",
         }
     }
+
+    /// Detect the compression of a message payload from its leading magic bytes
+    ///
+    /// CDR payloads start with their encapsulation header, never with the zstd magic.
+    pub(crate) fn of_payload(data: &[u8]) -> Self {
+        if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            CompressionFormat::Zstd
+        } else {
+            CompressionFormat::None
+        }
+    }
 }
 
 impl StoragePlugin {
@@ -267,6 +920,7 @@ impl StoragePlugin {
         match self {
             StoragePlugin::Sqlite3 => "sqlite3",
             StoragePlugin::Mcap => "mcap",
+            StoragePlugin::Memory => "memory",
         }
     }
 }
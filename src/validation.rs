@@ -0,0 +1,208 @@
+//! Validation of serialized message payloads
+//!
+//! Checks payloads handed to the [`Writer`](crate::writer::Writer) before they are
+//! stored, so that corrupt producers are caught at write time instead of when the bag
+//! is read months later.
+
+use crate::cdr::CdrDeserializer;
+use crate::dynamic;
+use crate::error::{BagError, Result};
+use crate::messages;
+use crate::schema::MessageSchemas;
+use crate::types::{Connection, MessageDefinitionFormat};
+use std::collections::HashMap;
+
+/// Largest number of bytes accepted after the last field (alignment padding)
+const MAX_TRAILING_BYTES: usize = 7;
+
+/// How thoroughly payloads are checked before they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationLevel {
+    /// No checks
+    #[default]
+    None,
+    /// Check the CDR encapsulation header
+    Header,
+    /// Check the header and that the payload decodes completely as its message type
+    ///
+    /// Uses the connection's message definition, falling back to the compiled
+    /// message types. Payloads of types without either only get the header check.
+    Strict,
+}
+
+/// Validates payloads at a [`ValidationLevel`], caching parsed definitions per type
+#[derive(Debug, Default)]
+pub struct PayloadValidator {
+    level: ValidationLevel,
+    schemas: HashMap<String, Option<MessageSchemas>>,
+}
+
+impl PayloadValidator {
+    /// Create a validator for `level`
+    pub fn new(level: ValidationLevel) -> Self {
+        Self {
+            level,
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Get the validation level
+    pub fn level(&self) -> ValidationLevel {
+        self.level
+    }
+
+    /// Check a serialized payload of `connection`
+    pub fn validate(&mut self, connection: &Connection, data: &[u8]) -> Result<()> {
+        if self.level == ValidationLevel::None {
+            return Ok(());
+        }
+        let invalid = |reason: String| {
+            BagError::invalid_message_data(format!(
+                "{} ({}): {reason}",
+                connection.topic, connection.message_type
+            ))
+        };
+
+        validate_header(data).map_err(|e| invalid(e.to_string()))?;
+        if self.level == ValidationLevel::Header {
+            return Ok(());
+        }
+
+        let schemas = self
+            .schemas
+            .entry(connection.message_type.clone())
+            .or_insert_with(|| {
+                (connection.message_definition.format == MessageDefinitionFormat::Msg)
+                    .then(|| {
+                        MessageSchemas::parse(
+                            &connection.message_type,
+                            &connection.message_definition,
+                        )
+                        .ok()
+                    })
+                    .flatten()
+            });
+
+        match schemas {
+            Some(schemas) => validate_with_schemas(data, schemas, &connection.message_type),
+            None => match messages::deserialize_message(data, &connection.message_type) {
+                Ok(_) | Err(BagError::MessageTypeNotFound { .. }) => Ok(()),
+                Err(e) => Err(e),
+            },
+        }
+        .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+/// Check that a payload starts with a plain CDR encapsulation header
+///
+/// ROS2 serializes messages as CDR with a 4-byte header: a zero byte, the
+/// endianness (0 = big endian, 1 = little endian) and two option bytes.
+pub fn validate_header(data: &[u8]) -> Result<()> {
+    if data.len() < 4 {
+        return Err(BagError::invalid_message_data(format!(
+            "payload of {} bytes is too short for a CDR header",
+            data.len()
+        )));
+    }
+    if data[0] != 0 || data[1] > 1 {
+        return Err(BagError::invalid_message_data(format!(
+            "unsupported CDR encapsulation {:#04x}{:02x}",
+            data[0], data[1]
+        )));
+    }
+    Ok(())
+}
+
+/// Check that a payload decodes completely as `message_type`
+fn validate_with_schemas(data: &[u8], schemas: &MessageSchemas, message_type: &str) -> Result<()> {
+    let mut deserializer = CdrDeserializer::new(data)?;
+    dynamic::skip_message(&mut deserializer, schemas, message_type)?;
+    let trailing = data.len() - deserializer.position();
+    if trailing > MAX_TRAILING_BYTES {
+        return Err(BagError::invalid_message_data(format!(
+            "{trailing} unexpected bytes after the last field at offset {}",
+            deserializer.position()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageDefinition;
+
+    fn connection(message_type: &str, definition: Option<&str>) -> Connection {
+        Connection {
+            id: 1,
+            topic: "/test".to_string(),
+            message_type: message_type.to_string(),
+            message_definition: definition.map_or_else(MessageDefinition::default, |data| {
+                MessageDefinition {
+                    format: MessageDefinitionFormat::Msg,
+                    data: data.to_string(),
+                }
+            }),
+            type_description_hash: String::new(),
+            message_count: 0,
+            serialization_format: "cdr".to_string(),
+            offered_qos_profiles: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let point = connection("pkg/msg/Point", Some("float64 x\nfloat64 y\n"));
+        let mut valid = vec![0, 1, 0, 0];
+        valid.extend_from_slice(&1.0f64.to_le_bytes());
+        valid.extend_from_slice(&2.0f64.to_le_bytes());
+
+        let mut none = PayloadValidator::new(ValidationLevel::None);
+        assert!(none.validate(&point, b"garbage").is_ok());
+
+        let mut header = PayloadValidator::new(ValidationLevel::Header);
+        assert!(header.validate(&point, &valid[..12]).is_ok());
+        assert!(header.validate(&point, &[0, 1]).is_err());
+        assert!(header.validate(&point, b"{\"x\": 1}").is_err());
+
+        let mut strict = PayloadValidator::new(ValidationLevel::Strict);
+        assert!(strict.validate(&point, &valid).is_ok());
+        let truncated = strict.validate(&point, &valid[..12]).unwrap_err();
+        assert!(truncated.to_string().contains("/test (pkg/msg/Point)"));
+        let mut padded = valid.clone();
+        padded.extend_from_slice(&[0; 16]);
+        assert!(strict.validate(&point, &padded).is_err());
+
+        // Compiled types are checked without a stored definition
+        let clock = connection("rosgraph_msgs/msg/Clock", None);
+        assert!(strict.validate(&clock, &valid[..12]).is_ok());
+        assert!(strict.validate(&clock, &valid[..6]).is_err());
+        // Unknown types without a definition only get the header check
+        let unknown = connection("pkg/msg/Unknown", None);
+        assert!(strict.validate(&unknown, &valid[..4]).is_ok());
+        assert!(strict.validate(&unknown, &[1, 1, 0, 0]).is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap", not(feature = "write-only")))]
+    #[test]
+    fn test_recorded_messages_pass_strict_validation() {
+        use crate::Reader;
+
+        for bag in [
+            "tests/test_bags/test_bag_sqlite3",
+            "tests/test_bags/test_bag_mcap",
+        ] {
+            let mut reader = Reader::new(bag).unwrap();
+            reader.open().unwrap();
+            let mut validator = PayloadValidator::new(ValidationLevel::Strict);
+            for message in reader.raw_messages().unwrap() {
+                let message = message.unwrap();
+                validator
+                    .validate(&message.connection, &message.raw_data)
+                    .unwrap();
+            }
+        }
+    }
+}
@@ -1,12 +1,29 @@
 //! Main writer implementation for ROS2 bag files
 
+use crate::definitions::DefinitionResolver;
+use crate::dynamic::{connection_schemas, DynamicMessage};
 use crate::error::{BagError, Result};
-use crate::metadata::{BagFileInformation, BagMetadata};
+use crate::metadata::{
+    BagFileInformation, BagMetadata, FileInformation, QosProfilesField, CHECKSUM_KEY_PREFIX,
+};
+use crate::registry::TypeRegistry;
+use crate::storage::background::BackgroundStorage;
+#[cfg(feature = "mcap")]
+use crate::storage::mcap::{McapWriteOptions, McapWriter};
+use crate::storage::memory::MemoryWriter;
+#[cfg(feature = "sqlite")]
+use crate::storage::sqlite::{
+    SqliteJournalMode, SqliteSynchronous, SqliteWriteOptions, SqliteWriter,
+    SQLITE_SCHEMA_VERSION_LATEST,
+};
 use crate::storage::{create_storage_writer, StorageWriter};
 use crate::types::{
-    CompressionFormat, CompressionMode, Connection, MessageDefinition, QosProfile, StoragePlugin,
+    glob_match, Attachment, CompressionFormat, CompressionMode, Connection, McapMetadata,
+    MessageDefinition, MessageDefinitionFormat, QosProfile, StoragePlugin,
+    OFFERED_QOS_PROFILES_KEY,
 };
-use std::collections::HashMap;
+use crate::validation::{PayloadValidator, ValidationLevel};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 
 /// Buffered message for batch writing
@@ -17,14 +34,557 @@ struct BufferedMessage {
     data: Vec<u8>,
 }
 
+/// When a [`Writer`] continues in a new storage file
+///
+/// Like `ros2 bag record --max-bag-size/--max-bag-duration`, a split bag is stored in
+/// files named `<bag name>_<index>.<ext>`, all listed in `metadata.yaml`. A new file
+/// is started before the first message that would exceed a limit, so every file
+/// holds at least one message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SplitPolicy {
+    /// Maximum number of payload bytes per file
+    pub max_bagfile_size: Option<u64>,
+    /// Maximum time span of the messages in a file
+    pub max_bagfile_duration: Option<std::time::Duration>,
+}
+
+impl SplitPolicy {
+    /// Whether the bag is split at all
+    pub fn is_enabled(&self) -> bool {
+        self.max_bagfile_size.is_some() || self.max_bagfile_duration.is_some()
+    }
+}
+
+/// How much of a split bag a [`Writer`] keeps, for always-on recording
+///
+/// When a storage file is finished, the oldest files are deleted while all their
+/// messages are older than `max_duration` before the newest message, or while the
+/// finished files take more than `max_size` bytes on disk. The file being written is
+/// never deleted, so the bag holds up to one split file more than the limits.
+/// Retention needs a [`SplitPolicy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Time span of messages to keep
+    pub max_duration: Option<std::time::Duration>,
+    /// Bytes of finished storage files to keep
+    pub max_size: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Whether old files are deleted at all
+    pub fn is_enabled(&self) -> bool {
+        self.max_duration.is_some() || self.max_size.is_some()
+    }
+}
+
+/// What a [`Writer`] does with a message older than a message written before it
+///
+/// Readers return messages in timestamp order whatever order they were written in,
+/// and the metadata time range, split files and MCAP chunk indices cover out-of-order
+/// messages, so every policy yields a valid bag. Storing messages in order helps
+/// tools that stream storage files sequentially.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutOfOrderPolicy {
+    /// Write messages in the order they are written (default)
+    #[default]
+    Accept,
+    /// Write messages in the order they are written, printing a warning for each
+    /// out-of-order message
+    Warn,
+    /// Fail to write out-of-order messages
+    Reject,
+    /// Hold messages until they are the given time span behind the newest message
+    /// and write them in timestamp order
+    ///
+    /// Messages arriving after younger messages have already been written are
+    /// written at once with a warning. Held messages are written by
+    /// [`Writer::snapshot`] and [`Writer::close`].
+    Reorder(std::time::Duration),
+}
+
+/// Limits of the in-memory buffer used in snapshot mode
+///
+/// Messages older than `max_duration` relative to the newest message, and the oldest
+/// messages beyond `max_bytes`, are dropped. At least one limit should be set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotOptions {
+    /// Maximum time span of buffered messages
+    pub max_duration: Option<std::time::Duration>,
+    /// Maximum total size of buffered message data
+    pub max_bytes: Option<usize>,
+}
+
+/// Ring buffer of the most recent messages, within the limits of [`SnapshotOptions`]
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotBuffer {
+    limits: SnapshotOptions,
+    messages: VecDeque<(Connection, u64, Vec<u8>)>,
+    bytes: usize,
+}
+
+impl SnapshotBuffer {
+    pub(crate) fn new(limits: SnapshotOptions) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Add a message, returning the number of old messages dropped to make room
+    pub(crate) fn push(&mut self, message: (Connection, u64, Vec<u8>)) -> u64 {
+        let newest = message.1;
+        self.bytes += message.2.len();
+        self.messages.push_back(message);
+
+        let mut dropped = 0;
+        while let Some((_, oldest, data)) = self.messages.front() {
+            let too_old = self
+                .limits
+                .max_duration
+                .is_some_and(|span| newest.saturating_sub(*oldest) > span.as_nanos() as u64);
+            let too_big = self.limits.max_bytes.is_some_and(|max| self.bytes > max);
+            if !too_old && !too_big {
+                break;
+            }
+            self.bytes -= data.len();
+            self.messages.pop_front();
+            dropped += 1;
+        }
+        dropped
+    }
+
+    /// Number of messages held
+    pub(crate) fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Remove and return the held messages, oldest first
+    pub(crate) fn take(&mut self) -> Vec<(Connection, u64, Vec<u8>)> {
+        self.bytes = 0;
+        self.messages.drain(..).collect()
+    }
+}
+
+/// Statistics of the storage file being written
+#[derive(Debug, Clone)]
+struct FileStats {
+    min_timestamp: u64,
+    max_timestamp: u64,
+    message_count: u64,
+    size: u64,
+    /// Message counts per connection id
+    message_counts: HashMap<u32, u64>,
+}
+
+impl Default for FileStats {
+    fn default() -> Self {
+        Self {
+            min_timestamp: u64::MAX,
+            max_timestamp: 0,
+            message_count: 0,
+            size: 0,
+            message_counts: HashMap::new(),
+        }
+    }
+}
+
+impl FileStats {
+    /// Account for a message of connection `connection_id` written to the file
+    fn record(&mut self, connection_id: u32, timestamp: u64, size: usize) {
+        self.min_timestamp = self.min_timestamp.min(timestamp);
+        self.max_timestamp = self.max_timestamp.max(timestamp);
+        self.message_count += 1;
+        self.size += size as u64;
+        *self.message_counts.entry(connection_id).or_insert(0) += 1;
+    }
+
+    /// Get the metadata entry of the file stored at `path`
+    fn info(&self, path: String) -> FileInformation {
+        // Without messages the file starts at 0 with zero duration rather than u64::MAX
+        let starting_time = if self.min_timestamp > self.max_timestamp {
+            0
+        } else {
+            self.min_timestamp
+        };
+        FileInformation {
+            path,
+            starting_time: crate::types::StartingTime {
+                nanoseconds_since_epoch: starting_time,
+            },
+            duration: crate::types::Duration {
+                nanoseconds: self.max_timestamp.saturating_sub(starting_time),
+            },
+            message_count: self.message_count,
+        }
+    }
+}
+
+/// A finished storage file of a split bag
+#[derive(Debug, Clone)]
+struct FinishedFile {
+    /// Metadata entry of the file
+    info: FileInformation,
+    /// Statistics of the file, to forget its messages when it is deleted
+    stats: FileStats,
+    /// Size of the file on disk in bytes
+    disk_size: u64,
+}
+
+/// Builder for a [`Writer`] with all settings applied before opening
+///
+/// Created by [`Writer::builder`].
+///
+/// # Example
+/// ```no_run
+/// use rosbags_rs::storage::sqlite::{SqliteJournalMode, SqliteSynchronous};
+/// use rosbags_rs::Writer;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut writer = Writer::builder("imu_bag")
+///     .sqlite_journal_mode(SqliteJournalMode::Wal)
+///     .sqlite_synchronous(SqliteSynchronous::Off)
+///     .sqlite_page_size(65536)
+///     .buffer(20, 500)
+///     .max_bagfile_duration(Duration::from_secs(60))
+///     .open()?;
+/// # writer.close()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WriterBuilder {
+    bag_path: PathBuf,
+    version: Option<u32>,
+    ros_distro: Option<String>,
+    storage_plugin: Option<StoragePlugin>,
+    compression: Option<(CompressionMode, CompressionFormat)>,
+    compression_level: i32,
+    topic_compression: Vec<(String, CompressionFormat, i32)>,
+    buffer: Option<(usize, usize)>,
+    background_flush: Option<usize>,
+    out_of_order: OutOfOrderPolicy,
+    validation: ValidationLevel,
+    custom_data: Vec<(String, String)>,
+    #[cfg(feature = "sqlite")]
+    sqlite_options: SqliteWriteOptions,
+    #[cfg(feature = "mcap")]
+    mcap_options: McapWriteOptions,
+    split_policy: SplitPolicy,
+    retention: RetentionPolicy,
+    type_registry: Option<TypeRegistry>,
+    snapshot: Option<SnapshotOptions>,
+    checksums: bool,
+    complete_schemas: bool,
+    deterministic: bool,
+}
+
+impl WriterBuilder {
+    /// Set the bag format version (default: [`Writer::VERSION_LATEST`])
+    ///
+    /// See [`Writer::new`] for the supported versions.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Set the ROS distribution recorded in the bag (default: `rosbags`)
+    pub fn ros_distro(mut self, ros_distro: impl Into<String>) -> Self {
+        self.ros_distro = Some(ros_distro.into());
+        self
+    }
+
+    /// Set the storage plugin (default: SQLite3)
+    pub fn storage(mut self, storage_plugin: StoragePlugin) -> Self {
+        self.storage_plugin = Some(storage_plugin);
+        self
+    }
+
+    /// Compress the bag (see [`Writer::set_compression`])
+    pub fn compression(mut self, mode: CompressionMode, format: CompressionFormat) -> Self {
+        self.compression = Some((mode, format));
+        self
+    }
+
+    /// Set the zstd level of message and file compression (default: 0, zstd's default)
+    ///
+    /// MCAP chunk compression always uses the `mcap` crate's default level.
+    pub fn compression_level(mut self, level: i32) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Compress the messages of topics matching `pattern` on their own
+    /// (see [`Writer::set_topic_compression`])
+    pub fn topic_compression(
+        mut self,
+        pattern: impl Into<String>,
+        format: CompressionFormat,
+        level: i32,
+    ) -> Self {
+        self.topic_compression.push((pattern.into(), format, level));
+        self
+    }
+
+    /// Set the message buffer limits (see [`Writer::configure_buffer`])
+    pub fn buffer(mut self, buffer_size_mb: usize, batch_threshold: usize) -> Self {
+        self.buffer = Some((buffer_size_mb, batch_threshold));
+        self
+    }
+
+    /// Write flushed batches on a background thread, queueing up to
+    /// `max_queued_batches` of them before [`Writer::write`] waits
+    ///
+    /// The writer keeps buffering the next batch while the previous ones are stored,
+    /// so writing only stalls on storage I/O once the queue is full. With the default
+    /// buffer limits a batch holds up to 100 messages or 10 MB. Errors of the
+    /// background thread are returned by the next write or by [`Writer::close`].
+    pub fn background_flush(mut self, max_queued_batches: usize) -> Self {
+        self.background_flush = Some(max_queued_batches);
+        self
+    }
+
+    /// Set how thoroughly payloads are checked (see [`Writer::set_validation`])
+    pub fn validation(mut self, level: ValidationLevel) -> Self {
+        self.validation = level;
+        self
+    }
+
+    /// Set what happens to messages older than previously written ones
+    /// (default: [`OutOfOrderPolicy::Accept`])
+    pub fn out_of_order(mut self, policy: OutOfOrderPolicy) -> Self {
+        self.out_of_order = policy;
+        self
+    }
+
+    /// Add a custom metadata entry (see [`Writer::set_custom_value`])
+    pub fn custom_data(mut self, key: impl Into<String>, value: impl std::fmt::Display) -> Self {
+        self.custom_data.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Replace all SQLite3 tuning (see [`Writer::set_sqlite_options`])
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_options(mut self, options: SqliteWriteOptions) -> Self {
+        self.sqlite_options = options;
+        self
+    }
+
+    /// Set the SQLite3 journal mode (`PRAGMA journal_mode`)
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_journal_mode(mut self, journal_mode: SqliteJournalMode) -> Self {
+        self.sqlite_options.journal_mode = Some(journal_mode);
+        self
+    }
+
+    /// Set the SQLite3 sync mode (`PRAGMA synchronous`)
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_synchronous(mut self, synchronous: SqliteSynchronous) -> Self {
+        self.sqlite_options.synchronous = Some(synchronous);
+        self
+    }
+
+    /// Set the SQLite3 page size in bytes (`PRAGMA page_size`)
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_page_size(mut self, page_size: u32) -> Self {
+        self.sqlite_options.page_size = Some(page_size);
+        self
+    }
+
+    /// Set the minimum number of messages per SQLite3 transaction
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_messages_per_transaction(mut self, messages: usize) -> Self {
+        self.sqlite_options.messages_per_transaction = messages;
+        self
+    }
+
+    /// Write the SQLite3 table layout of an older schema version, from 2 to 4
+    ///
+    /// 3 is read by Humble and 2 by Foxy and Galactic (see
+    /// [`SqliteWriteOptions::schema_version`]). Unless [`WriterBuilder::version`] is
+    /// set, older schemas are written with bag format version 5, the version those
+    /// releases read.
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_schema_version(mut self, schema_version: u32) -> Self {
+        self.sqlite_options.schema_version = schema_version;
+        self
+    }
+
+    /// Replace all MCAP tuning
+    #[cfg(feature = "mcap")]
+    pub fn mcap_options(mut self, options: McapWriteOptions) -> Self {
+        self.mcap_options = options;
+        self
+    }
+
+    /// Set the target uncompressed size of MCAP chunks in bytes
+    #[cfg(feature = "mcap")]
+    pub fn mcap_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.mcap_options.chunk_size = chunk_size;
+        self
+    }
+
+    /// Replace the split policy
+    pub fn split(mut self, split_policy: SplitPolicy) -> Self {
+        self.split_policy = split_policy;
+        self
+    }
+
+    /// Continue in a new file once a file holds `bytes` of payload
+    pub fn max_bagfile_size(mut self, bytes: u64) -> Self {
+        self.split_policy.max_bagfile_size = Some(bytes);
+        self
+    }
+
+    /// Continue in a new file once the messages of a file span `duration`
+    pub fn max_bagfile_duration(mut self, duration: std::time::Duration) -> Self {
+        self.split_policy.max_bagfile_duration = Some(duration);
+        self
+    }
+
+    /// Delete the oldest files of the split bag beyond `retention`
+    pub fn retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Store the definitions of registered types for connections added without one
+    /// (see [`Writer::set_type_registry`])
+    pub fn type_registry(mut self, registry: TypeRegistry) -> Self {
+        self.type_registry = Some(registry);
+        self
+    }
+
+    /// Hold messages in memory and only write them on [`Writer::snapshot`]
+    pub fn snapshot(mut self, limits: SnapshotOptions) -> Self {
+        self.snapshot = Some(limits);
+        self
+    }
+
+    /// Record the SHA-256 checksum of every storage file in the metadata on close
+    ///
+    /// The checksums are stored as custom data under [`CHECKSUM_KEY_PREFIX`] keys, so
+    /// they need bag format version 6 or later. [`Reader::verify_checksums`] checks
+    /// the storage files against them.
+    ///
+    /// [`CHECKSUM_KEY_PREFIX`]: crate::metadata::CHECKSUM_KEY_PREFIX
+    /// [`Reader::verify_checksums`]: crate::Reader::verify_checksums
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Reject connections whose message definition is incomplete
+    ///
+    /// [`Writer::add_connection`] then fails for a type without a stored, registered
+    /// or bundled definition, or whose definition lacks a type it references (see
+    /// [`DefinitionResolver::complete_definition`]). Bags written this way can be
+    /// decoded from their definitions alone, e.g. MCAP bags by Foxglove Studio.
+    ///
+    /// [`DefinitionResolver::complete_definition`]: crate::definitions::DefinitionResolver::complete_definition
+    pub fn complete_schemas(mut self, complete_schemas: bool) -> Self {
+        self.complete_schemas = complete_schemas;
+        self
+    }
+
+    /// Write bit-identical files for identical input (see [`Writer::set_deterministic`])
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Create the writer without opening it
+    pub fn build(self) -> Result<Writer> {
+        check_compression_level(self.compression_level)?;
+        if self.split_policy.max_bagfile_size == Some(0)
+            || self.split_policy.max_bagfile_duration == Some(std::time::Duration::ZERO)
+        {
+            return Err(BagError::writer("Split limits must be larger than zero"));
+        }
+        if self.background_flush == Some(0) {
+            return Err(BagError::writer(
+                "Background flushing needs room for at least one queued batch",
+            ));
+        }
+        if self.retention.is_enabled() && !self.split_policy.is_enabled() {
+            return Err(BagError::writer(
+                "A retention policy needs a split policy to delete old files",
+            ));
+        }
+
+        #[cfg(feature = "sqlite")]
+        let version = self.version.or_else(|| {
+            (self.sqlite_options.schema_version < SQLITE_SCHEMA_VERSION_LATEST)
+                .then_some(LEGACY_SQLITE_VERSION)
+        });
+        #[cfg(not(feature = "sqlite"))]
+        let version = self.version;
+        let mut writer = Writer::new(self.bag_path, version, self.storage_plugin)?;
+        if let Some(ros_distro) = self.ros_distro {
+            writer.set_ros_distro(ros_distro)?;
+        }
+        if let Some((mode, format)) = self.compression {
+            writer.set_compression(mode, format)?;
+        }
+        for (pattern, format, level) in self.topic_compression {
+            writer.set_topic_compression(pattern, format, level)?;
+        }
+        if let Some((buffer_size_mb, batch_threshold)) = self.buffer {
+            writer.configure_buffer(buffer_size_mb, batch_threshold)?;
+        }
+        writer.set_validation(self.validation);
+        for (key, value) in self.custom_data {
+            writer.set_custom_data(key, value)?;
+        }
+        writer.compression_level = self.compression_level;
+        #[cfg(feature = "sqlite")]
+        {
+            writer.sqlite_options = self.sqlite_options;
+        }
+        #[cfg(feature = "mcap")]
+        {
+            writer.mcap_options = self.mcap_options;
+        }
+        writer.split_policy = self.split_policy;
+        writer.retention = self.retention;
+        writer.type_registry = self.type_registry;
+        writer.snapshot = self.snapshot.map(SnapshotBuffer::new);
+        writer.background_flush = self.background_flush;
+        writer.out_of_order = self.out_of_order;
+        if self.checksums && writer.version < 6 {
+            return Err(BagError::writer(format!(
+                "Checksums need bag format version 6 or later, not {}",
+                writer.version
+            )));
+        }
+        writer.checksums = self.checksums;
+        writer.complete_schemas = self.complete_schemas;
+        writer.deterministic = self.deterministic;
+        Ok(writer)
+    }
+
+    /// Create and open the writer
+    pub fn open(self) -> Result<Writer> {
+        let mut writer = self.build()?;
+        writer.open()?;
+        Ok(writer)
+    }
+}
+
+/// Bag format version read by the releases writing SQLite3 schema versions before 4
+#[cfg(feature = "sqlite")]
+const LEGACY_SQLITE_VERSION: u32 = 5;
+
 /// Main writer for ROS2 bag files
 pub struct Writer {
     /// Path to the bag directory
     bag_path: PathBuf,
     /// Metadata file path
     metadata_path: PathBuf,
-    /// Bag format version (8 or 9)
+    /// Bag format version (5 to 9)
     version: u32,
+    /// ROS distribution recorded in the metadata and SQLite schema table
+    ros_distro: String,
     /// Storage plugin to use
     storage_plugin: StoragePlugin,
     /// Compression mode
@@ -38,7 +598,7 @@ pub struct Writer {
     /// Message counts per connection
     message_counts: HashMap<u32, u64>,
     /// Custom metadata
-    custom_data: HashMap<String, String>,
+    custom_data: BTreeMap<String, String>,
     /// Added message types (to avoid duplicates)
     added_types: std::collections::HashSet<String>,
     /// Minimum timestamp seen
@@ -55,14 +615,62 @@ pub struct Writer {
     current_buffer_size: usize,
     /// Batch write size threshold (number of messages to trigger flush)
     batch_threshold: usize,
+    /// Maximum number of batches queued for the background thread, if flushing there
+    background_flush: Option<usize>,
+    /// What happens to messages older than previously written ones
+    out_of_order: OutOfOrderPolicy,
+    /// Newest timestamp handed to the ordering policy
+    newest_timestamp: u64,
+    /// Messages held for reordering, by timestamp and arrival
+    held_messages: BTreeMap<(u64, u64), (Connection, Vec<u8>)>,
+    /// Number of messages held for reordering so far, to keep arrival order on ties
+    held_count: u64,
+    /// Validation of written payloads
+    validator: PayloadValidator,
+    /// Commit and page tuning of the SQLite3 storage
+    #[cfg(feature = "sqlite")]
+    sqlite_options: SqliteWriteOptions,
+    /// Chunk layout tuning of the MCAP storage
+    #[cfg(feature = "mcap")]
+    mcap_options: McapWriteOptions,
+    /// zstd level of message and file compression (0 for zstd's default)
+    compression_level: i32,
+    /// Message compression and zstd level of the topics matching a glob pattern
+    topic_compression: Vec<(String, CompressionFormat, i32)>,
+    /// Message compression and zstd level of each connection written so far
+    connection_compression: HashMap<u32, (CompressionFormat, i32)>,
+    /// When to continue in a new storage file
+    split_policy: SplitPolicy,
+    /// Which old storage files to delete
+    retention: RetentionPolicy,
+    /// Index of the oldest storage file not deleted under the retention policy
+    first_file_index: usize,
+    /// Finished storage files of a split bag
+    finished_files: Vec<FinishedFile>,
+    /// Statistics of the storage file being written
+    file_stats: FileStats,
+    /// Definitions of custom types for connections added without one
+    type_registry: Option<TypeRegistry>,
+    /// Whether written messages are discarded
+    paused: bool,
+    /// Messages held until [`Writer::snapshot`], in snapshot mode
+    snapshot: Option<SnapshotBuffer>,
+    /// Whether the checksums of the storage files are recorded on close
+    checksums: bool,
+    /// Whether connections with incomplete message definitions are rejected
+    complete_schemas: bool,
+    /// Whether identical input gives bit-identical files
+    deterministic: bool,
 }
 
 impl std::fmt::Debug for Writer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Writer")
+        let mut debug = f.debug_struct("Writer");
+        debug
             .field("bag_path", &self.bag_path)
             .field("metadata_path", &self.metadata_path)
             .field("version", &self.version)
+            .field("ros_distro", &self.ros_distro)
             .field("storage_plugin", &self.storage_plugin)
             .field("compression_mode", &self.compression_mode)
             .field("compression_format", &self.compression_format)
@@ -78,7 +686,32 @@ impl std::fmt::Debug for Writer {
             .field("buffer_size_limit", &self.buffer_size_limit)
             .field("current_buffer_size", &self.current_buffer_size)
             .field("batch_threshold", &self.batch_threshold)
-            .finish()
+            .field("background_flush", &self.background_flush)
+            .field("out_of_order", &self.out_of_order)
+            .field("newest_timestamp", &self.newest_timestamp)
+            .field("held_messages", &self.held_messages.len())
+            .field("validation", &self.validator.level());
+        #[cfg(feature = "sqlite")]
+        debug.field("sqlite_options", &self.sqlite_options);
+        #[cfg(feature = "mcap")]
+        debug.field("mcap_options", &self.mcap_options);
+        debug
+            .field("compression_level", &self.compression_level)
+            .field("topic_compression", &self.topic_compression)
+            .field("split_policy", &self.split_policy)
+            .field("retention", &self.retention)
+            .field("first_file_index", &self.first_file_index)
+            .field("finished_files", &self.finished_files.len())
+            .field("type_registry", &self.type_registry)
+            .field("paused", &self.paused)
+            .field(
+                "snapshot",
+                &self.snapshot.as_ref().map(|buffer| buffer.limits),
+            )
+            .field("checksums", &self.checksums)
+            .field("complete_schemas", &self.complete_schemas)
+            .field("deterministic", &self.deterministic);
+        debug.finish()
     }
 }
 
@@ -86,7 +719,47 @@ impl Writer {
     /// Latest supported bag format version
     pub const VERSION_LATEST: u32 = 9;
 
+    /// Oldest supported bag format version
+    pub const VERSION_OLDEST: u32 = 5;
+
+    /// Start configuring a writer for the given bag path
+    ///
+    /// Storage tuning, compression level and split policies are only available
+    /// through the builder.
+    pub fn builder<P: AsRef<Path>>(bag_path: P) -> WriterBuilder {
+        WriterBuilder {
+            bag_path: bag_path.as_ref().to_path_buf(),
+            version: None,
+            ros_distro: None,
+            storage_plugin: None,
+            compression: None,
+            compression_level: 0,
+            topic_compression: Vec::new(),
+            buffer: None,
+            background_flush: None,
+            out_of_order: OutOfOrderPolicy::default(),
+            validation: ValidationLevel::default(),
+            custom_data: Vec::new(),
+            #[cfg(feature = "sqlite")]
+            sqlite_options: SqliteWriteOptions::default(),
+            #[cfg(feature = "mcap")]
+            mcap_options: McapWriteOptions::default(),
+            split_policy: SplitPolicy::default(),
+            retention: RetentionPolicy::default(),
+            type_registry: None,
+            snapshot: None,
+            checksums: false,
+            complete_schemas: false,
+            deterministic: false,
+        }
+    }
+
     /// Create a new writer for the given bag path
+    ///
+    /// `version` selects the metadata layout, from [`Writer::VERSION_OLDEST`] to
+    /// [`Writer::VERSION_LATEST`] (the default). `ros2 bag` reads bags up to its own
+    /// version: 5 for Humble, 7 for Iron, 8 for Jazzy and 9 for Kilted and Rolling.
+    /// Fields a version does not know are left out of its metadata.
     pub fn new<P: AsRef<Path>>(
         bag_path: P,
         version: Option<u32>,
@@ -94,12 +767,15 @@ impl Writer {
     ) -> Result<Self> {
         let bag_path = bag_path.as_ref().to_path_buf();
 
-        // Check if the bag directory already exists
-        if bag_path.exists() {
+        // Check if the bag directory already exists, on disk or in memory
+        if bag_path.exists() || crate::storage::memory::contains(&bag_path) {
             return Err(BagError::BagAlreadyExists { path: bag_path });
         }
 
         let version = version.unwrap_or(Self::VERSION_LATEST);
+        if !(Self::VERSION_OLDEST..=Self::VERSION_LATEST).contains(&version) {
+            return Err(BagError::UnsupportedVersion { version });
+        }
         let storage_plugin = storage_plugin.unwrap_or(StoragePlugin::Sqlite3);
 
         let metadata_path = bag_path.join("metadata.yaml");
@@ -108,13 +784,14 @@ impl Writer {
             bag_path,
             metadata_path,
             version,
+            ros_distro: "rosbags".to_string(),
             storage_plugin,
             compression_mode: CompressionMode::None,
             compression_format: CompressionFormat::None,
             storage: None,
             connections: Vec::new(),
             message_counts: HashMap::new(),
-            custom_data: HashMap::new(),
+            custom_data: BTreeMap::new(),
             added_types: std::collections::HashSet::new(),
             min_timestamp: u64::MAX,
             max_timestamp: 0,
@@ -123,6 +800,30 @@ impl Writer {
             buffer_size_limit: 10 * 1024 * 1024, // 10MB
             current_buffer_size: 0,
             batch_threshold: 100, // 100 messages
+            background_flush: None,
+            out_of_order: OutOfOrderPolicy::default(),
+            newest_timestamp: 0,
+            held_messages: BTreeMap::new(),
+            held_count: 0,
+            validator: PayloadValidator::default(),
+            #[cfg(feature = "sqlite")]
+            sqlite_options: SqliteWriteOptions::default(),
+            #[cfg(feature = "mcap")]
+            mcap_options: McapWriteOptions::default(),
+            compression_level: 0,
+            topic_compression: Vec::new(),
+            connection_compression: HashMap::new(),
+            split_policy: SplitPolicy::default(),
+            retention: RetentionPolicy::default(),
+            first_file_index: 0,
+            finished_files: Vec::new(),
+            file_stats: FileStats::default(),
+            type_registry: None,
+            paused: false,
+            snapshot: None,
+            checksums: false,
+            complete_schemas: false,
+            deterministic: false,
         })
     }
 
@@ -135,6 +836,12 @@ impl Writer {
         if self.is_open {
             return Err(BagError::BagAlreadyOpen);
         }
+        if !self.topic_compression.is_empty() && !compresses_per_message(mode) {
+            return Err(BagError::writer(format!(
+                "Per-topic compression cannot be combined with {} compression",
+                mode.as_str()
+            )));
+        }
 
         self.compression_mode = mode;
         self.compression_format = format;
@@ -152,12 +859,138 @@ impl Writer {
         Ok(())
     }
 
+    /// Compress the messages of topics matching the glob `pattern` with `format` at
+    /// zstd `level` (0 for zstd's default), instead of following the bag compression
+    ///
+    /// `*` matches within a topic name segment, `**` across segments and `?` a single
+    /// character, so `/camera/*` compresses every topic directly below `/camera`.
+    /// [`CompressionFormat::None`] stores matching topics uncompressed, e.g. small
+    /// high-rate topics of a bag with [`CompressionMode::Message`]. The first matching
+    /// pattern applies, other topics follow the bag compression.
+    ///
+    /// Topics are compressed per message, so this cannot be combined with file or
+    /// storage compression. The metadata then declares message compression; this
+    /// crate's [`Reader`](crate::Reader) only decompresses compressed payloads, but
+    /// `ros2 bag` expects every message of such a bag to be compressed.
+    pub fn set_topic_compression(
+        &mut self,
+        pattern: impl Into<String>,
+        format: CompressionFormat,
+        level: i32,
+    ) -> Result<()> {
+        if self.is_open {
+            return Err(BagError::BagAlreadyOpen);
+        }
+        if !compresses_per_message(self.compression_mode) {
+            return Err(BagError::writer(format!(
+                "Per-topic compression cannot be combined with {} compression",
+                self.compression_mode.as_str()
+            )));
+        }
+        #[cfg(not(feature = "compression"))]
+        if format == CompressionFormat::Zstd {
+            return Err(BagError::UnsupportedCompressionFormat {
+                format: "zstd (feature not enabled)".to_string(),
+            });
+        }
+        check_compression_level(level)?;
+
+        self.topic_compression.push((pattern.into(), format, level));
+        Ok(())
+    }
+
     /// Set custom metadata
+    ///
+    /// Custom data needs bag format version 6 or later. Keys starting with
+    /// [`CHECKSUM_KEY_PREFIX`] are reserved for the checksums of the storage files.
     pub fn set_custom_data(&mut self, key: String, value: String) -> Result<()> {
+        if self.version < 6 {
+            return Err(BagError::writer(format!(
+                "Custom data needs bag format version 6 or later, not {}",
+                self.version
+            )));
+        }
+        if key.starts_with(CHECKSUM_KEY_PREFIX) {
+            return Err(BagError::writer(format!(
+                "Custom data key '{key}' is reserved for checksums"
+            )));
+        }
         self.custom_data.insert(key, value);
         Ok(())
     }
 
+    /// Set custom metadata from any value that formats as text, e.g. a number or
+    /// version
+    ///
+    /// Entries are written in key order and read back with
+    /// [`Reader::custom_data`](crate::Reader::custom_data) or parsed with
+    /// [`Reader::parse_custom_value`](crate::Reader::parse_custom_value).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Writer;
+    /// # let mut writer = Writer::new("test", None, None).unwrap();
+    /// writer.set_custom_value("dataset_id", 4217)?;
+    /// writer.set_custom_value("vin", "1HGCM82633A004352")?;
+    /// # Ok::<(), rosbags_rs::BagError>(())
+    /// ```
+    pub fn set_custom_value(
+        &mut self,
+        key: impl Into<String>,
+        value: impl std::fmt::Display,
+    ) -> Result<()> {
+        self.set_custom_data(key.into(), value.to_string())
+    }
+
+    /// Get the custom metadata set so far, in key order
+    pub fn custom_data(&self) -> &BTreeMap<String, String> {
+        &self.custom_data
+    }
+
+    /// Set the ROS distribution recorded in the bag (default: `rosbags`)
+    ///
+    /// The metadata only lists it from bag format version 8, SQLite storage files
+    /// always record it.
+    pub fn set_ros_distro(&mut self, ros_distro: impl Into<String>) -> Result<()> {
+        if self.is_open {
+            return Err(BagError::BagAlreadyOpen);
+        }
+        self.ros_distro = ros_distro.into();
+        Ok(())
+    }
+
+    /// Set how thoroughly payloads are checked before they are written
+    ///
+    /// Invalid payloads are rejected with [`BagError::InvalidMessageData`]. Raw
+    /// writes are not checked in message compression mode, where their payloads
+    /// are already compressed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Writer;
+    /// use rosbags_rs::validation::ValidationLevel;
+    ///
+    /// # let mut writer = Writer::new("test", None, None).unwrap();
+    /// writer.set_validation(ValidationLevel::Strict);
+    /// ```
+    pub fn set_validation(&mut self, level: ValidationLevel) {
+        self.validator = PayloadValidator::new(level);
+    }
+
+    /// Get the payload validation level
+    pub fn validation(&self) -> ValidationLevel {
+        self.validator.level()
+    }
+
+    /// Use `registry` for the definitions of custom message types
+    ///
+    /// Connections of a registered type added without a message definition get the
+    /// registered one, dependencies included. It is stored in the bag and used by
+    /// payload validation, so readers can decode the custom type.
+    pub fn set_type_registry(&mut self, registry: TypeRegistry) {
+        self.type_registry = Some(registry);
+    }
+
     /// Configure message buffer settings for performance optimization
     ///
     /// # Arguments
@@ -185,30 +1018,69 @@ impl Writer {
         Ok(())
     }
 
+    /// Set commit and page tuning for SQLite3 storage
+    ///
+    /// Has no effect on other storage plugins. Must be called before [`Writer::open`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rosbags_rs::Writer;
+    /// use rosbags_rs::storage::sqlite::SqliteWriteOptions;
+    ///
+    /// # let mut writer = Writer::new("test", None, None).unwrap();
+    /// // Sustain kHz rates of small messages by committing every 1000 messages
+    /// writer.set_sqlite_options(SqliteWriteOptions::high_rate()).unwrap();
+    /// ```
+    #[cfg(feature = "sqlite")]
+    pub fn set_sqlite_options(&mut self, options: SqliteWriteOptions) -> Result<()> {
+        if self.is_open {
+            return Err(BagError::BagAlreadyOpen);
+        }
+
+        self.sqlite_options = options;
+        Ok(())
+    }
+
+    /// Write bit-identical files for identical input, e.g. for content-hash caching
+    ///
+    /// Writing the same connections, messages and options to a bag of the same name
+    /// then gives the same storage files and `metadata.yaml`: SQLite3 databases are
+    /// rebuilt with `VACUUM` on close (see [`SqliteWriteOptions::vacuum`]) and
+    /// [`Writer::add_attachment`] records time 0 instead of the current time. MCAP
+    /// chunks already close at fixed sizes and custom data is always written in key
+    /// order. Must be called before [`Writer::open`].
+    pub fn set_deterministic(&mut self, deterministic: bool) -> Result<()> {
+        if self.is_open {
+            return Err(BagError::BagAlreadyOpen);
+        }
+
+        self.deterministic = deterministic;
+        Ok(())
+    }
+
     /// Flush the message buffer to storage
     ///
     /// This method writes all buffered messages to storage in a batch operation.
     /// It's automatically called when the buffer reaches its limits, but can also
-    /// be called manually for explicit control.
+    /// be called manually for explicit control. With
+    /// [`WriterBuilder::background_flush`] it only queues the batch for writing.
     pub fn flush_buffer(&mut self) -> Result<()> {
         if self.message_buffer.is_empty() {
             return Ok(());
         }
 
         // Convert buffer to format expected by write_batch
-        let batch_messages: Vec<(Connection, u64, Vec<u8>)> = self
-            .message_buffer
-            .iter()
-            .map(|msg| (msg.connection.clone(), msg.timestamp, msg.data.clone()))
-            .collect();
+        let batch_messages: Vec<(Connection, u64, Vec<u8>)> =
+            std::mem::take(&mut self.message_buffer)
+                .into_iter()
+                .map(|msg| (msg.connection, msg.timestamp, msg.data))
+                .collect();
 
         let storage = self.storage.as_mut().unwrap();
 
         // Use batch write for better performance
-        storage.write_batch(&batch_messages)?;
+        storage.write_owned_batch(batch_messages)?;
 
-        // Clear the buffer
-        self.message_buffer.clear();
         self.current_buffer_size = 0;
 
         Ok(())
@@ -226,14 +1098,33 @@ impl Writer {
             return Ok(());
         }
 
-        // Create bag directory
-        std::fs::create_dir_all(&self.bag_path)?;
-
-        // Create storage writer
-        let mut storage =
-            create_storage_writer(self.storage_plugin, &self.bag_path, self.compression_mode)?;
+        #[cfg(feature = "sqlite")]
+        if self.storage_plugin == StoragePlugin::Sqlite3
+            && self.sqlite_options.schema_version < SQLITE_SCHEMA_VERSION_LATEST
+            && self.version > LEGACY_SQLITE_VERSION
+        {
+            return Err(BagError::writer(format!(
+                "SQLite3 schema version {} is read by releases that need bag format version {}, not {}",
+                self.sqlite_options.schema_version,
+                LEGACY_SQLITE_VERSION,
+                self.version
+            )));
+        }
+        if self.storage_plugin == StoragePlugin::Memory {
+            // In-memory bags are written without files to compress or hash
+            if self.compression_mode == CompressionMode::File || self.checksums {
+                return Err(BagError::UnsupportedStorageFormat {
+                    format: "memory (file compression and checksums need files on disk)"
+                        .to_string(),
+                });
+            }
+        } else {
+            // Create bag directory
+            std::fs::create_dir_all(&self.bag_path)?;
+        }
 
-        // Open storage
+        // Create and open storage writer
+        let mut storage = self.create_storage(self.current_file_index())?;
         storage.open()?;
 
         self.storage = Some(storage);
@@ -242,6 +1133,217 @@ impl Writer {
         Ok(())
     }
 
+    /// Create the storage writer of storage file `index`
+    fn create_storage(&self, index: usize) -> Result<Box<dyn StorageWriter>> {
+        let file_name = self.storage_file_name(index);
+        let storage: Box<dyn StorageWriter> = match self.storage_plugin {
+            #[cfg(feature = "sqlite")]
+            StoragePlugin::Sqlite3 => Box::new(
+                SqliteWriter::with_options(
+                    &self.bag_path,
+                    self.compression_mode,
+                    SqliteWriteOptions {
+                        vacuum: self.sqlite_options.vacuum || self.deterministic,
+                        ..self.sqlite_options.clone()
+                    },
+                )?
+                .with_file_name(&file_name)
+                .with_ros_distro(&self.ros_distro),
+            ),
+            #[cfg(feature = "mcap")]
+            StoragePlugin::Mcap => Box::new(
+                McapWriter::with_options(
+                    &self.bag_path,
+                    self.compression_mode,
+                    self.mcap_options.clone(),
+                )?
+                .with_file_name(&file_name),
+            ),
+            StoragePlugin::Memory => {
+                Box::new(MemoryWriter::new(&self.bag_path)?.with_file_name(&file_name))
+            }
+            #[allow(unreachable_patterns)]
+            _ => create_storage_writer(self.storage_plugin, &self.bag_path, self.compression_mode)?,
+        };
+        match self.background_flush {
+            Some(max_queued_batches) => Ok(Box::new(BackgroundStorage::spawn(
+                storage,
+                max_queued_batches,
+            )?)),
+            None => Ok(storage),
+        }
+    }
+
+    /// Get the name of storage file `index`, before file compression
+    fn storage_file_name(&self, index: usize) -> String {
+        let stem = self.bag_path.file_name().unwrap().to_string_lossy();
+        let extension = match self.storage_plugin {
+            StoragePlugin::Sqlite3 => "db3",
+            StoragePlugin::Mcap => "mcap",
+            StoragePlugin::Memory => crate::storage::memory::EXTENSION,
+        };
+        if self.split_policy.is_enabled() {
+            format!("{stem}_{index}.{extension}")
+        } else {
+            format!("{stem}.{extension}")
+        }
+    }
+
+    /// Get the name of storage file `index` as listed in the metadata
+    fn stored_file_name(&self, index: usize) -> String {
+        let file_name = self.storage_file_name(index);
+        if self.compression_mode == CompressionMode::File {
+            format!("{}.{}", file_name, self.compression_format.as_str())
+        } else {
+            file_name
+        }
+    }
+
+    /// Whether a message must go to a new storage file under the split policy
+    fn should_split(&self, timestamp: u64) -> bool {
+        let file = &self.file_stats;
+        if file.message_count == 0 {
+            return false;
+        }
+        let policy = &self.split_policy;
+        policy
+            .max_bagfile_size
+            .is_some_and(|max_size| file.size >= max_size)
+            || policy.max_bagfile_duration.is_some_and(|max_duration| {
+                let span = timestamp.saturating_sub(file.min_timestamp);
+                u128::from(span) >= max_duration.as_nanos()
+            })
+    }
+
+    /// Finish the current storage file and continue in the next one
+    fn split_file(&mut self) -> Result<()> {
+        self.flush_buffer()?;
+
+        let index = self.current_file_index();
+        let metadata_yaml = BagMetadata {
+            rosbag2_bagfile_information: self.generate_metadata()?,
+        }
+        .to_yaml()?;
+        if let Some(mut storage) = self.storage.take() {
+            storage.close(self.version, &metadata_yaml)?;
+        }
+        if self.compression_mode == CompressionMode::File {
+            self.compress_storage_file(index)?;
+        }
+        let path = self.stored_file_name(index);
+        let disk_size = if self.storage_plugin == StoragePlugin::Memory {
+            crate::storage::memory::file_size(&self.bag_path.join(&path))?
+        } else {
+            std::fs::metadata(self.bag_path.join(&path))?.len()
+        };
+        self.finished_files.push(FinishedFile {
+            info: self.file_stats.info(path),
+            stats: std::mem::take(&mut self.file_stats),
+            disk_size,
+        });
+        self.apply_retention()?;
+
+        // Every file is self-contained, so it gets all types and connections again
+        let mut storage = self.create_storage(index + 1)?;
+        storage.open()?;
+        let mut added_types = std::collections::HashSet::new();
+        for connection in &self.connections {
+            if added_types.insert(connection.message_type.as_str()) {
+                storage.add_msgtype(connection)?;
+            }
+            let qos_yaml = self.connection_qos_profiles(connection)?;
+            storage.add_connection(connection, &qos_yaml)?;
+        }
+        self.storage = Some(storage);
+        Ok(())
+    }
+
+    /// Get the index of the storage file being written
+    fn current_file_index(&self) -> usize {
+        self.first_file_index + self.finished_files.len()
+    }
+
+    /// Delete the oldest finished storage files beyond the retention policy
+    fn apply_retention(&mut self) -> Result<()> {
+        let policy = self.retention;
+        if !policy.is_enabled() {
+            return Ok(());
+        }
+
+        while let Some(oldest) = self.finished_files.first() {
+            let too_old = policy.max_duration.is_some_and(|max_duration| {
+                let age = self
+                    .max_timestamp
+                    .saturating_sub(oldest.stats.max_timestamp);
+                u128::from(age) > max_duration.as_nanos()
+            });
+            let too_big = policy.max_size.is_some_and(|max_size| {
+                let size: u64 = self.finished_files.iter().map(|file| file.disk_size).sum();
+                size > max_size
+            });
+            if !too_old && !too_big {
+                break;
+            }
+
+            let file = self.finished_files.remove(0);
+            if self.storage_plugin == StoragePlugin::Memory {
+                crate::storage::memory::remove_file(&self.bag_path.join(&file.info.path))?;
+            } else {
+                std::fs::remove_file(self.bag_path.join(&file.info.path))?;
+            }
+            for (id, count) in &file.stats.message_counts {
+                if let Some(total) = self.message_counts.get_mut(id) {
+                    *total -= count;
+                }
+            }
+            self.first_file_index += 1;
+        }
+
+        // The bag now starts at the first message of the oldest kept file
+        self.min_timestamp = self
+            .finished_files
+            .iter()
+            .map(|file| file.stats.min_timestamp)
+            .chain([self.file_stats.min_timestamp])
+            .min()
+            .unwrap_or(u64::MAX);
+        Ok(())
+    }
+
+    /// Account for a message and buffer it, first continuing in a new storage file
+    /// if the split policy requires it
+    fn buffer_message(
+        &mut self,
+        connection: &Connection,
+        timestamp: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        if self.should_split(timestamp) {
+            self.split_file()?;
+        }
+
+        // Update statistics
+        *self.message_counts.entry(connection.id).or_insert(0) += 1;
+        self.min_timestamp = self.min_timestamp.min(timestamp);
+        self.max_timestamp = self.max_timestamp.max(timestamp);
+        self.file_stats.record(connection.id, timestamp, data.len());
+
+        // Add message to buffer
+        self.current_buffer_size += data.len();
+        self.message_buffer.push(BufferedMessage {
+            connection: connection.clone(),
+            timestamp,
+            data,
+        });
+
+        // Flush buffer if it's full
+        if self.should_flush_buffer() {
+            self.flush_buffer()?;
+        }
+
+        Ok(())
+    }
+
     /// Add a connection (topic) to the bag
     pub fn add_connection(
         &mut self,
@@ -256,24 +1358,53 @@ impl Writer {
             return Err(BagError::BagNotOpen);
         }
 
-        let connection_id = (self.connections.len() + 1) as u32;
+        let connection = Connection::builder(topic, message_type)
+            .message_definition(message_definition.unwrap_or_default())
+            .type_description_hash(type_description_hash.unwrap_or_default())
+            .serialization_format(serialization_format.unwrap_or_else(|| "cdr".to_string()))
+            .offered_qos_profiles(offered_qos_profiles.unwrap_or_default())
+            .build()?;
+        self.insert_connection(connection)
+    }
 
-        // Use defaults if not provided
-        let message_definition = message_definition.unwrap_or_default();
-        let type_description_hash = type_description_hash.unwrap_or_default();
-        let serialization_format = serialization_format.unwrap_or_else(|| "cdr".to_string());
-        let offered_qos_profiles = offered_qos_profiles.unwrap_or_default();
+    /// Add a connection with the same topic, type, message definition, type hash,
+    /// serialization format, QoS profiles and storage metadata as `connection`
+    ///
+    /// Use this when copying connections from a [`Reader`](crate::Reader) so that the
+    /// output keeps the original message definitions. The QoS profiles are stored as
+    /// the string of the original storage while they still match it, and the other
+    /// metadata keys of MCAP channels are written to the new channel.
+    pub fn add_connection_from(&mut self, connection: &Connection) -> Result<Connection> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
 
-        let connection = Connection {
-            id: connection_id,
-            topic: topic.clone(),
-            message_type: message_type.clone(),
-            message_definition: message_definition.clone(),
-            type_description_hash: type_description_hash.clone(),
-            message_count: 0,
-            serialization_format,
-            offered_qos_profiles: offered_qos_profiles.clone(),
-        };
+        let connection = Connection::builder(&connection.topic, &connection.message_type)
+            .message_definition(connection.message_definition.clone())
+            .type_description_hash(connection.type_description_hash.clone())
+            .serialization_format(connection.serialization_format.clone())
+            .offered_qos_profiles(connection.offered_qos_profiles.clone())
+            .metadata(connection.metadata.clone())
+            .build()?;
+        self.insert_connection(connection)
+    }
+
+    /// Assign an id to a new connection and add it to the bag and the storage
+    fn insert_connection(&mut self, mut connection: Connection) -> Result<Connection> {
+        connection.id = (self.connections.len() + 1) as u32;
+
+        // Use the registered definition if none is provided
+        if connection.message_definition.format == MessageDefinitionFormat::None {
+            if let Some(registry) = &self.type_registry {
+                if registry.contains(&connection.message_type) {
+                    connection.message_definition =
+                        registry.definition(&connection.message_type)?;
+                }
+            }
+        }
+        if self.complete_schemas {
+            DefinitionResolver::new().complete_definition(&connection)?;
+        }
 
         // Check for duplicate connections
         for existing_conn in &self.connections {
@@ -287,32 +1418,93 @@ impl Writer {
         }
 
         // Serialize QoS profiles
-        let qos_yaml = self.serialize_qos_profiles(&offered_qos_profiles)?;
+        let qos_yaml = self.connection_qos_profiles(&connection)?;
 
         let storage = self.storage.as_mut().unwrap();
 
         // Add message type definition if not already added
-        if !self.added_types.contains(&message_type) {
+        if !self.added_types.contains(&connection.message_type) {
             storage.add_msgtype(&connection)?;
-            self.added_types.insert(message_type);
+            self.added_types.insert(connection.message_type.clone());
         }
 
         // Add connection to storage
         storage.add_connection(&connection, &qos_yaml)?;
 
         // Initialize message count
-        self.message_counts.insert(connection_id, 0);
+        self.message_counts.insert(connection.id, 0);
 
         self.connections.push(connection.clone());
 
         Ok(connection)
     }
 
+    /// Attach a file, e.g. a calibration file or URDF, to the storage file being written
+    ///
+    /// Only MCAP storage holds attachments. The attachment is logged and created at
+    /// the current time, or at time 0 by deterministic writers; use
+    /// [`Writer::add_attachment_with`] to set the times.
+    pub fn add_attachment(
+        &mut self,
+        name: impl Into<String>,
+        media_type: impl Into<String>,
+        data: &[u8],
+    ) -> Result<()> {
+        let now = if self.deterministic {
+            0
+        } else {
+            crate::time::from_system_time(std::time::SystemTime::now()).unwrap_or(0)
+        };
+        self.add_attachment_with(&Attachment {
+            name: name.into(),
+            media_type: media_type.into(),
+            log_time: now,
+            create_time: now,
+            data: data.to_vec(),
+            file_index: 0,
+        })
+    }
+
+    /// Attach a file with the times of `attachment` to the storage file being written
+    ///
+    /// The `file_index` of `attachment` is ignored.
+    pub fn add_attachment_with(&mut self, attachment: &Attachment) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        self.storage.as_mut().unwrap().add_attachment(attachment)
+    }
+
+    /// Write a named metadata record to the storage file being written
+    ///
+    /// Only MCAP storage holds metadata records. The name `rosbag2` is reserved for
+    /// the bag metadata written on close.
+    pub fn add_mcap_metadata(
+        &mut self,
+        name: impl Into<String>,
+        metadata: BTreeMap<String, String>,
+    ) -> Result<()> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        self.storage
+            .as_mut()
+            .unwrap()
+            .add_mcap_metadata(&McapMetadata {
+                name: name.into(),
+                metadata,
+                file_index: 0,
+            })
+    }
+
     /// Write a message to the bag
     pub fn write(&mut self, connection: &Connection, timestamp: u64, data: &[u8]) -> Result<()> {
         if !self.is_open {
             return Err(BagError::BagNotOpen);
         }
+        if self.paused {
+            return Ok(());
+        }
 
         // Check if connection exists
         if !self.connections.iter().any(|c| c.id == connection.id) {
@@ -321,46 +1513,90 @@ impl Writer {
             });
         }
 
+        self.validator.validate(connection, data)?;
+
         // Apply compression if needed
-        let final_data = match self.compression_mode {
-            CompressionMode::Message => {
-                #[cfg(feature = "compression")]
-                {
-                    if self.compression_format == CompressionFormat::Zstd {
-                        zstd::encode_all(data, 0)?
-                    } else {
-                        data.to_vec()
-                    }
-                }
-                #[cfg(not(feature = "compression"))]
-                {
-                    data.to_vec()
-                }
-            }
+        let final_data = match self.message_compression(connection) {
+            #[cfg(feature = "compression")]
+            (CompressionFormat::Zstd, level) => zstd::bulk::compress(data, level)?,
             _ => data.to_vec(),
         };
 
-        // Add message to buffer
-        let buffered_message = BufferedMessage {
-            connection: connection.clone(),
-            timestamp,
-            data: final_data.clone(),
-        };
-
-        self.current_buffer_size += final_data.len();
-        self.message_buffer.push(buffered_message);
-
-        // Update statistics
-        *self.message_counts.entry(connection.id).or_insert(0) += 1;
-        self.min_timestamp = self.min_timestamp.min(timestamp);
-        self.max_timestamp = self.max_timestamp.max(timestamp);
+        self.accept_message(connection, timestamp, final_data)
+    }
 
-        // Flush buffer if it's full
-        if self.should_flush_buffer() {
-            self.flush_buffer()?;
+    /// Serialize a [`DynamicMessage`] with the definition of `connection` and write it
+    ///
+    /// The message must be of the connection's type. Connections added without a
+    /// definition are serialized with the registered or bundled one.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rosbags_rs::dynamic::DynamicMessage;
+    /// use rosbags_rs::Writer;
+    ///
+    /// let mut writer = Writer::builder("markers").open()?;
+    /// let connection = writer.add_connection(
+    ///     "/status".to_string(),
+    ///     "std_msgs/msg/String".to_string(),
+    ///     None,
+    ///     None,
+    ///     None,
+    ///     None,
+    /// )?;
+    /// let mut message = DynamicMessage::new(&connection.message_schemas()?)?;
+    /// message.set_field("data", "ready")?;
+    /// writer.write_dynamic(&connection, 1_000_000_000, &message)?;
+    /// writer.close()?;
+    /// # Ok::<(), rosbags_rs::BagError>(())
+    /// ```
+    pub fn write_dynamic(
+        &mut self,
+        connection: &Connection,
+        timestamp: u64,
+        message: &DynamicMessage,
+    ) -> Result<()> {
+        let stored = self
+            .connections
+            .iter()
+            .find(|c| c.id == connection.id)
+            .ok_or_else(|| BagError::ConnectionNotFound {
+                topic: connection.topic.clone(),
+            })?;
+        let schemas = connection_schemas(stored)?;
+        if schemas
+            .get(&message.message_type)
+            .map(|schema| &schema.name)
+            != Some(&schemas.root().name)
+        {
+            return Err(BagError::schema_validation(format!(
+                "Cannot write a {} message to {} of type {}",
+                message.message_type, connection.topic, connection.message_type
+            )));
         }
+        let data = message.to_cdr_bytes(&schemas)?;
+        self.write(connection, timestamp, &data)
+    }
 
-        Ok(())
+    /// Get the compression and zstd level of the messages of `connection`
+    fn message_compression(&mut self, connection: &Connection) -> (CompressionFormat, i32) {
+        let topic_compression = &self.topic_compression;
+        let bag_compression = if self.compression_mode == CompressionMode::Message {
+            (self.compression_format, self.compression_level)
+        } else {
+            (CompressionFormat::None, 0)
+        };
+        *self
+            .connection_compression
+            .entry(connection.id)
+            .or_insert_with(|| {
+                topic_compression
+                    .iter()
+                    .find(|(pattern, ..)| {
+                        glob_match(pattern.as_bytes(), connection.topic.as_bytes())
+                    })
+                    .map_or(bag_compression, |(_, format, level)| (*format, *level))
+            })
     }
 
     /// Close the bag and write metadata
@@ -369,7 +1605,8 @@ impl Writer {
             return Ok(());
         }
 
-        // Flush any remaining buffered messages
+        // Flush any remaining held and buffered messages
+        self.release_held_messages(u64::MAX)?;
         self.flush_buffer()?;
 
         // Generate metadata
@@ -377,19 +1614,30 @@ impl Writer {
         let metadata = BagMetadata {
             rosbag2_bagfile_information: bag_info,
         };
-        let metadata_yaml = serde_yml::to_string(&metadata)?;
+        let metadata_yaml = metadata.to_yaml()?;
 
         // Close storage
         if let Some(mut storage) = self.storage.take() {
             storage.close(self.version, &metadata_yaml)?;
         }
 
-        // Write metadata.yaml
-        std::fs::write(&self.metadata_path, &metadata_yaml)?;
-
         // Handle file compression if needed
         if self.compression_mode == CompressionMode::File {
-            self.compress_storage_file()?;
+            self.compress_storage_file(self.current_file_index())?;
+        }
+
+        // Checksums cover the storage files as stored, so only metadata.yaml has them
+        let metadata_yaml = if self.checksums {
+            self.record_checksums(metadata)?.to_yaml()?
+        } else {
+            metadata_yaml
+        };
+
+        // Write metadata.yaml
+        if self.storage_plugin == StoragePlugin::Memory {
+            crate::storage::memory::set_metadata(&self.bag_path, &metadata_yaml);
+        } else {
+            std::fs::write(&self.metadata_path, &metadata_yaml)?;
         }
 
         self.is_open = false;
@@ -406,29 +1654,177 @@ impl Writer {
         self.is_open
     }
 
+    /// Discard written messages until [`Writer::resume`], like pausing `ros2 bag record`
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Write messages again after [`Writer::pause`]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Check if written messages are discarded
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Check if messages are held for [`Writer::snapshot`]
+    pub fn is_snapshot_mode(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// Write the messages held in snapshot mode to the bag and start holding anew,
+    /// returning the number of messages written
+    ///
+    /// Like `ros2 bag record --snapshot-mode`, a snapshot mode writer keeps the most
+    /// recent messages within its [`SnapshotOptions`] in memory; only snapshots reach
+    /// the bag. Messages still held when the writer is closed are discarded.
+    pub fn snapshot(&mut self) -> Result<usize> {
+        if !self.is_open {
+            return Err(BagError::BagNotOpen);
+        }
+        let Some(snapshot) = &mut self.snapshot else {
+            return Err(BagError::writer("Writer is not in snapshot mode"));
+        };
+        let messages = snapshot.take();
+        let count = messages.len();
+        for (connection, timestamp, data) in messages {
+            self.order_message(&connection, timestamp, data)?;
+        }
+        self.release_held_messages(u64::MAX)?;
+        self.flush_buffer()?;
+        Ok(count)
+    }
+
+    /// Number of messages held for the next [`Writer::snapshot`]
+    pub fn snapshot_len(&self) -> usize {
+        self.snapshot.as_ref().map_or(0, SnapshotBuffer::len)
+    }
+
+    /// Hold a validated (and compressed) message for the next snapshot in snapshot
+    /// mode, or buffer it for writing
+    fn accept_message(
+        &mut self,
+        connection: &Connection,
+        timestamp: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        match &mut self.snapshot {
+            Some(snapshot) => {
+                snapshot.push((connection.clone(), timestamp, data));
+                Ok(())
+            }
+            None => self.order_message(connection, timestamp, data),
+        }
+    }
+
+    /// Apply the out-of-order policy to a message, then buffer it or hold it for
+    /// reordering
+    fn order_message(
+        &mut self,
+        connection: &Connection,
+        timestamp: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let newest = self.newest_timestamp;
+        let late = timestamp < newest;
+        match self.out_of_order {
+            OutOfOrderPolicy::Accept => {}
+            OutOfOrderPolicy::Warn => {
+                if late {
+                    eprintln!(
+                        "Warning: Message on {} at {timestamp} ns is older than a previous message at {newest} ns",
+                        connection.topic
+                    );
+                }
+            }
+            OutOfOrderPolicy::Reject => {
+                if late {
+                    return Err(BagError::writer(format!(
+                        "Message on {} at {timestamp} ns is older than a previous message at {newest} ns",
+                        connection.topic
+                    )));
+                }
+            }
+            OutOfOrderPolicy::Reorder(window) => {
+                self.newest_timestamp = newest.max(timestamp);
+                // Younger messages are already written, so it cannot be put in order
+                if timestamp < self.max_timestamp {
+                    eprintln!(
+                        "Warning: Message on {} at {timestamp} ns arrived after the reorder window and is written out of order",
+                        connection.topic
+                    );
+                    return self.buffer_message(connection, timestamp, data);
+                }
+                self.held_messages
+                    .insert((timestamp, self.held_count), (connection.clone(), data));
+                self.held_count += 1;
+                let window = u64::try_from(window.as_nanos()).unwrap_or(u64::MAX);
+                return self.release_held_messages(self.newest_timestamp.saturating_sub(window));
+            }
+        }
+        self.newest_timestamp = newest.max(timestamp);
+        self.buffer_message(connection, timestamp, data)
+    }
+
+    /// Buffer the messages held for reordering up to timestamp `until`, in order
+    fn release_held_messages(&mut self, until: u64) -> Result<()> {
+        while let Some(entry) = self.held_messages.first_entry() {
+            if entry.key().0 > until {
+                break;
+            }
+            let ((timestamp, _), (connection, data)) = entry.remove_entry();
+            self.buffer_message(&connection, timestamp, data)?;
+        }
+        Ok(())
+    }
+
+    /// Add the checksums of the closed storage files to the custom data of `metadata`
+    fn record_checksums(&self, mut metadata: BagMetadata) -> Result<BagMetadata> {
+        let info = &mut metadata.rosbag2_bagfile_information;
+        let custom_data = info.custom_data.get_or_insert_with(BTreeMap::new);
+        for path in &info.relative_file_paths {
+            let (_, sha256) = crate::metadata::sha256_file(self.bag_path.join(path))?;
+            custom_data.insert(format!("{CHECKSUM_KEY_PREFIX}{path}"), sha256);
+        }
+        Ok(metadata)
+    }
+
     /// Generate bag metadata
     fn generate_metadata(&self) -> Result<BagFileInformation> {
-        let storage_file_name = match self.storage_plugin {
-            StoragePlugin::Sqlite3 => format!(
-                "{}.db3",
-                self.bag_path.file_name().unwrap().to_string_lossy()
-            ),
-            StoragePlugin::Mcap => format!(
-                "{}.mcap",
-                self.bag_path.file_name().unwrap().to_string_lossy()
-            ),
-        };
+        let mut files: Vec<FileInformation> = self
+            .finished_files
+            .iter()
+            .map(|file| file.info.clone())
+            .collect();
+        files.push(
+            self.file_stats
+                .info(self.stored_file_name(self.current_file_index())),
+        );
 
-        let final_file_name = if self.compression_mode == CompressionMode::File {
-            format!("{}.{}", storage_file_name, self.compression_format.as_str())
+        // Without messages the bag starts at 0 with zero duration rather than u64::MAX
+        let starting_time = if self.min_timestamp > self.max_timestamp {
+            0
         } else {
-            storage_file_name
+            self.min_timestamp
         };
-
-        let duration = self.max_timestamp.saturating_sub(self.min_timestamp);
+        let duration = self.max_timestamp.saturating_sub(starting_time);
 
         let total_message_count: u64 = self.message_counts.values().sum();
 
+        // Bags with compressed topics declare message compression for every reader
+        let (compression_mode, compression_format) = match self
+            .topic_compression
+            .iter()
+            .find(|(_, format, _)| *format != CompressionFormat::None)
+        {
+            Some((_, format, _)) if self.compression_mode == CompressionMode::None => {
+                (CompressionMode::Message, *format)
+            }
+            _ => (self.compression_mode, self.compression_format),
+        };
+
         let topics_with_message_count = self
             .connections
             .iter()
@@ -449,44 +1845,47 @@ impl Writer {
         Ok(BagFileInformation {
             version: self.version,
             storage_identifier: self.storage_plugin.as_str().to_string(),
-            relative_file_paths: vec![final_file_name.clone()],
+            relative_file_paths: files.iter().map(|file| file.path.clone()).collect(),
             duration: crate::types::Duration {
                 nanoseconds: duration,
             },
             starting_time: crate::types::StartingTime {
-                nanoseconds_since_epoch: self.min_timestamp,
+                nanoseconds_since_epoch: starting_time,
             },
             message_count: total_message_count,
-            compression_format: if self.compression_mode == CompressionMode::None {
+            compression_format: if compression_mode == CompressionMode::None {
                 String::new()
             } else {
-                self.compression_format.as_str().to_string()
+                compression_format.as_str().to_string()
             },
-            compression_mode: if self.compression_mode == CompressionMode::None {
+            compression_mode: if compression_mode == CompressionMode::None {
                 String::new()
             } else {
-                self.compression_mode.as_str().to_string()
+                compression_mode.as_str().to_string()
             },
             topics_with_message_count,
-            files: vec![crate::metadata::FileInformation {
-                path: final_file_name,
-                starting_time: crate::types::StartingTime {
-                    nanoseconds_since_epoch: self.min_timestamp,
-                },
-                duration: crate::types::Duration {
-                    nanoseconds: duration,
-                },
-                message_count: total_message_count,
-            }],
+            files,
             custom_data: if self.custom_data.is_empty() {
                 None
             } else {
                 Some(self.custom_data.clone())
             },
-            ros_distro: Some("rosbags".to_string()),
+            ros_distro: Some(self.ros_distro.clone()),
         })
     }
 
+    /// QoS profiles of a connection as stored, keeping the string read from the
+    /// original storage unless the profiles were changed since
+    fn connection_qos_profiles(&self, connection: &Connection) -> Result<String> {
+        if let Some(qos) = connection.metadata.get(OFFERED_QOS_PROFILES_KEY) {
+            let profiles = QosProfilesField::String(qos.clone()).profiles();
+            if profiles == connection.offered_qos_profiles {
+                return Ok(qos.clone());
+            }
+        }
+        self.serialize_qos_profiles(&connection.offered_qos_profiles)
+    }
+
     /// Serialize QoS profiles to YAML
     fn serialize_qos_profiles(&self, profiles: &[QosProfile]) -> Result<String> {
         if profiles.is_empty() {
@@ -498,29 +1897,15 @@ impl Writer {
         Ok(yaml.trim().to_string())
     }
 
-    /// Compress storage file (for file-level compression)
-    fn compress_storage_file(&self) -> Result<()> {
+    /// Compress storage file `index` (for file-level compression)
+    fn compress_storage_file(&self, index: usize) -> Result<()> {
         #[cfg(feature = "compression")]
         {
-            let storage_file = match self.storage_plugin {
-                StoragePlugin::Sqlite3 => self.bag_path.join(format!(
-                    "{}.db3",
-                    self.bag_path.file_name().unwrap().to_string_lossy()
-                )),
-                StoragePlugin::Mcap => self.bag_path.join(format!(
-                    "{}.mcap",
-                    self.bag_path.file_name().unwrap().to_string_lossy()
-                )),
-            };
-
-            let compressed_file = storage_file.with_extension(format!(
-                "{}.{}",
-                storage_file.extension().unwrap().to_string_lossy(),
-                self.compression_format.as_str()
-            ));
+            let storage_file = self.bag_path.join(self.storage_file_name(index));
+            let compressed_file = self.bag_path.join(self.stored_file_name(index));
 
             let input_data = std::fs::read(&storage_file)?;
-            let compressed_data = zstd::encode_all(input_data.as_slice(), 0)?;
+            let compressed_data = zstd::encode_all(input_data.as_slice(), self.compression_level)?;
             std::fs::write(&compressed_file, compressed_data)?;
             std::fs::remove_file(&storage_file)?;
             Ok(())
@@ -528,6 +1913,7 @@ impl Writer {
 
         #[cfg(not(feature = "compression"))]
         {
+            let _ = index;
             return Err(BagError::UnsupportedCompressionFormat {
                 format: "zstd (feature not enabled)".to_string(),
             });
@@ -548,34 +1934,15 @@ impl Writer {
         if !self.is_open {
             return Err(BagError::BagNotOpen);
         }
-
-        // Update min/max timestamps
-        if timestamp < self.min_timestamp {
-            self.min_timestamp = timestamp;
-        }
-        if timestamp > self.max_timestamp {
-            self.max_timestamp = timestamp;
+        if self.paused {
+            return Ok(());
         }
 
-        // Update message count
-        *self.message_counts.entry(connection.id).or_insert(0) += 1;
-
-        // Add to buffer for batch writing
-        let buffered_msg = BufferedMessage {
-            connection: connection.clone(),
-            timestamp,
-            data: raw_data.to_vec(),
-        };
-
-        self.current_buffer_size += raw_data.len();
-        self.message_buffer.push(buffered_msg);
-
-        // Flush if buffer is full
-        if self.should_flush_buffer() {
-            self.flush_buffer()?;
+        if !is_compressed(self.compression_mode, raw_data) {
+            self.validator.validate(connection, raw_data)?;
         }
 
-        Ok(())
+        self.accept_message(connection, timestamp, raw_data.to_vec())
     }
 
     /// Write multiple raw messages in a batch for maximum performance.
@@ -588,15 +1955,38 @@ impl Writer {
             return Err(BagError::BagNotOpen);
         }
 
-        if messages.is_empty() {
+        if messages.is_empty() || self.paused {
             return Ok(());
         }
 
+        // Validate the whole batch before anything is written
+        for (connection, _timestamp, data) in messages {
+            if !is_compressed(self.compression_mode, data) {
+                self.validator.validate(connection, data)?;
+            }
+        }
+
+        if let Some(snapshot) = &mut self.snapshot {
+            for message in messages {
+                snapshot.push(message.clone());
+            }
+            return Ok(());
+        }
+
+        // Split bags go through the buffer, which starts new files as needed, and
+        // ordering policies through their checks
+        if self.split_policy.is_enabled() || self.out_of_order != OutOfOrderPolicy::Accept {
+            for (connection, timestamp, data) in messages {
+                self.order_message(connection, *timestamp, data.clone())?;
+            }
+            return self.flush_buffer();
+        }
+
         // Flush existing buffer first
         self.flush_buffer()?;
 
         // Update statistics
-        for (connection, timestamp, _data) in messages {
+        for (connection, timestamp, data) in messages {
             if *timestamp < self.min_timestamp {
                 self.min_timestamp = *timestamp;
             }
@@ -604,6 +1994,8 @@ impl Writer {
                 self.max_timestamp = *timestamp;
             }
             *self.message_counts.entry(connection.id).or_insert(0) += 1;
+            self.file_stats
+                .record(connection.id, *timestamp, data.len());
         }
 
         // Use storage's direct batch write if available
@@ -632,6 +2024,32 @@ impl Drop for Writer {
     }
 }
 
+/// Check a zstd compression level (0 for zstd's default)
+fn check_compression_level(level: i32) -> Result<()> {
+    #[cfg(feature = "compression")]
+    if level != 0 && !zstd::compression_level_range().contains(&level) {
+        return Err(BagError::writer(format!(
+            "zstd compression level {level} is out of range {:?}",
+            zstd::compression_level_range()
+        )));
+    }
+    #[cfg(not(feature = "compression"))]
+    let _ = level;
+    Ok(())
+}
+
+/// Whether payloads of a bag with compression `mode` are stored one by one, so
+/// topics can be compressed on their own
+fn compresses_per_message(mode: CompressionMode) -> bool {
+    matches!(mode, CompressionMode::None | CompressionMode::Message)
+}
+
+/// Whether a raw payload is already compressed and cannot be validated
+fn is_compressed(mode: CompressionMode, data: &[u8]) -> bool {
+    mode == CompressionMode::Message
+        || CompressionFormat::of_payload(data) == CompressionFormat::Zstd
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -767,6 +2185,77 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_invalid_connection_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("test_bag");
+
+        let mut writer = Writer::new(&bag_path, None, None).unwrap();
+        writer.open().unwrap();
+
+        for (topic, message_type) in [
+            ("test_topic", "std_msgs/msg/String"),
+            ("/test_topic/", "std_msgs/msg/String"),
+            ("/test//topic", "std_msgs/msg/String"),
+            ("/test topic", "std_msgs/msg/String"),
+            ("/1test", "std_msgs/msg/String"),
+            ("/test_topic", "std_msgs/String"),
+            ("/test_topic", "std_msgs/foo/String"),
+            ("/test_topic", "std_msgs/msg/"),
+        ] {
+            let result = writer.add_connection(
+                topic.to_string(),
+                message_type.to_string(),
+                None,
+                None,
+                None,
+                None,
+            );
+            assert!(
+                matches!(result, Err(BagError::InvalidConnection { .. })),
+                "{topic} {message_type}: {result:?}"
+            );
+        }
+
+        let result = writer.add_connection(
+            "/test_topic".to_string(),
+            "std_msgs/msg/String".to_string(),
+            None,
+            None,
+            Some("json".to_string()),
+            None,
+        );
+        assert!(matches!(
+            result,
+            Err(BagError::UnsupportedSerializationFormat { .. })
+        ));
+
+        // Service events, actions and hidden topics are valid
+        for (topic, message_type) in [
+            (
+                "/add/_service_event",
+                "example_interfaces/srv/AddTwoInts_Event",
+            ),
+            (
+                "/fibonacci/_action/feedback",
+                "example_interfaces/action/Fibonacci_FeedbackMessage",
+            ),
+            ("/_hidden", "std_msgs/msg/String"),
+        ] {
+            writer
+                .add_connection(
+                    topic.to_string(),
+                    message_type.to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+        }
+        assert_eq!(writer.connections().len(), 3);
+    }
+
     #[test]
     fn test_write_message() {
         let temp_dir = TempDir::new().unwrap();
@@ -796,6 +2285,833 @@ mod tests {
         assert_eq!(*writer.message_counts.get(&connection.id).unwrap(), 1);
     }
 
+    #[test]
+    fn test_validation_rejects_corrupt_payloads() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("test_bag");
+
+        let mut writer = Writer::new(&bag_path, None, None).unwrap();
+        writer.set_validation(ValidationLevel::Strict);
+        writer.open().unwrap();
+        let connection = writer
+            .add_connection(
+                "/point".to_string(),
+                "geometry_msgs/msg/Point".to_string(),
+                Some(MessageDefinition {
+                    format: crate::types::MessageDefinitionFormat::Msg,
+                    data: "float64 x\nfloat64 y\nfloat64 z\n".to_string(),
+                }),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let mut point = vec![0x00, 0x01, 0x00, 0x00];
+        point.extend_from_slice(&[0; 24]);
+        writer.write(&connection, 1, &point).unwrap();
+
+        let result = writer.write(&connection, 2, &point[..20]);
+        assert!(matches!(result, Err(BagError::InvalidMessageData { .. })));
+        let result = writer.write_raw_message(&connection, 3, b"not cdr");
+        assert!(matches!(result, Err(BagError::InvalidMessageData { .. })));
+        let batch = vec![
+            (connection.clone(), 4, point.clone()),
+            (connection.clone(), 5, point[..4].to_vec()),
+        ];
+        assert!(writer.write_raw_messages_batch(&batch).is_err());
+
+        // Rejected messages are not counted
+        assert_eq!(*writer.message_counts.get(&connection.id).unwrap(), 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_metadata_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(matches!(
+            Writer::new(temp_dir.path().join("v4"), Some(4), None),
+            Err(BagError::UnsupportedVersion { version: 4 })
+        ));
+        assert!(Writer::new(temp_dir.path().join("v10"), Some(10), None).is_err());
+
+        let write = |version: u32| {
+            let bag_path = temp_dir.path().join(format!("v{version}"));
+            let mut writer = Writer::builder(&bag_path)
+                .version(version)
+                .ros_distro("humble")
+                .open()
+                .unwrap();
+            writer
+                .add_connection(
+                    "/chatter".to_string(),
+                    "std_msgs/msg/String".to_string(),
+                    None,
+                    Some("RIHS01_abc".to_string()),
+                    None,
+                    Some(vec![QosProfile::default()]),
+                )
+                .unwrap();
+            writer.close().unwrap();
+            let metadata = std::fs::read_to_string(bag_path.join("metadata.yaml")).unwrap();
+            let value: serde_yml::Value = serde_yml::from_str(&metadata).unwrap();
+            (bag_path, value["rosbag2_bagfile_information"].clone())
+        };
+
+        let (_, v5) = write(5);
+        assert_eq!(v5["version"].as_u64(), Some(5));
+        assert!(v5.get("ros_distro").is_none());
+        assert!(v5.get("custom_data").is_none());
+        assert!(v5.get("files").is_some());
+        let topic = &v5["topics_with_message_count"][0]["topic_metadata"];
+        assert!(topic.get("type_description_hash").is_none());
+        assert!(topic["offered_qos_profiles"].is_string());
+
+        let (bag_path, v8) = write(8);
+        assert_eq!(v8["ros_distro"].as_str(), Some("humble"));
+        let topic = &v8["topics_with_message_count"][0]["topic_metadata"];
+        assert_eq!(topic["type_description_hash"].as_str(), Some("RIHS01_abc"));
+        assert!(topic["offered_qos_profiles"].is_string());
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        let qos = &metadata.info().topics_with_message_count[0]
+            .topic_metadata
+            .offered_qos_profiles;
+        assert_eq!(qos.profiles().len(), 1);
+
+        let db = rusqlite::Connection::open(bag_path.join("v8.db3")).unwrap();
+        let ros_distro: String = db
+            .query_row("SELECT ros_distro FROM schema", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(ros_distro, "humble");
+
+        let (_, v9) = write(9);
+        assert!(
+            v9["topics_with_message_count"][0]["topic_metadata"]["offered_qos_profiles"]
+                .is_sequence()
+        );
+
+        let mut writer = Writer::new(temp_dir.path().join("custom"), Some(5), None).unwrap();
+        assert!(writer
+            .set_custom_data("robot".to_string(), "rover".to_string())
+            .is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_pause_and_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("snapshot");
+        let mut writer = Writer::builder(&bag_path)
+            .snapshot(SnapshotOptions {
+                max_duration: Some(std::time::Duration::from_nanos(2_000)),
+                max_bytes: None,
+            })
+            .open()
+            .unwrap();
+        let connection = writer
+            .add_connection(
+                "/data".to_string(),
+                "std_msgs/msg/Int32".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let message = |value: i32| {
+            let mut data = vec![0x00, 0x01, 0x00, 0x00];
+            data.extend_from_slice(&value.to_le_bytes());
+            data
+        };
+
+        // Only the last 2 µs are held: 1000 to 3000
+        for i in 0..4 {
+            writer
+                .write(&connection, i * 1_000, &message(i as i32))
+                .unwrap();
+        }
+        assert_eq!(writer.snapshot_len(), 3);
+        assert_eq!(writer.snapshot().unwrap(), 3);
+        assert_eq!(writer.snapshot_len(), 0);
+
+        // Paused messages are discarded, not held
+        writer.pause();
+        writer.write(&connection, 4_000, &message(4)).unwrap();
+        writer
+            .write_raw_messages_batch(&[(connection.clone(), 5_000, message(5))])
+            .unwrap();
+        assert_eq!(writer.snapshot_len(), 0);
+        writer.resume();
+        writer
+            .write_raw_message(&connection, 6_000, &message(6))
+            .unwrap();
+        writer
+            .write_raw_messages_batch(&[(connection.clone(), 7_000, message(7))])
+            .unwrap();
+        assert_eq!(writer.snapshot().unwrap(), 2);
+
+        // Held messages without a snapshot are discarded on close
+        writer.write(&connection, 8_000, &message(8)).unwrap();
+        writer.close().unwrap();
+
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        assert_eq!(metadata.message_count(), 5);
+        assert_eq!(metadata.start_time(), 1_000);
+        assert_eq!(metadata.end_time(), 7_000);
+
+        let mut writer = Writer::new(temp_dir.path().join("plain"), None, None).unwrap();
+        writer.open().unwrap();
+        assert!(!writer.is_snapshot_mode());
+        assert!(writer.snapshot().is_err());
+    }
+
+    #[test]
+    fn test_builder_applies_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("test_bag");
+
+        let mut writer = Writer::builder(&bag_path)
+            .sqlite_journal_mode(SqliteJournalMode::Wal)
+            .sqlite_synchronous(SqliteSynchronous::Off)
+            .sqlite_page_size(16384)
+            .sqlite_messages_per_transaction(50)
+            .buffer(1, 10)
+            .validation(ValidationLevel::Header)
+            .custom_data("robot", "rover")
+            .open()
+            .unwrap();
+        assert_eq!(writer.buffer_size_limit, 1024 * 1024);
+        assert_eq!(writer.batch_threshold, 10);
+        assert_eq!(writer.validation(), ValidationLevel::Header);
+        assert_eq!(writer.sqlite_options.messages_per_transaction, 50);
+        writer.close().unwrap();
+
+        let db = rusqlite::Connection::open(bag_path.join("test_bag.db3")).unwrap();
+        let page_size: u32 = db
+            .query_row("PRAGMA page_size", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(page_size, 16384);
+        let journal_mode: String = db
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let metadata = std::fs::read_to_string(bag_path.join("metadata.yaml")).unwrap();
+        assert!(metadata.contains("robot: rover"));
+
+        assert!(Writer::builder(temp_dir.path().join("bad_level"))
+            .compression_level(100)
+            .build()
+            .is_err());
+        assert!(Writer::builder(temp_dir.path().join("bad_split"))
+            .max_bagfile_size(0)
+            .build()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_retention_deletes_oldest_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, retention: RetentionPolicy| {
+            let bag_path = temp_dir.path().join(name);
+            let mut writer = Writer::builder(&bag_path)
+                .max_bagfile_duration(std::time::Duration::from_nanos(1_000))
+                .retention(retention)
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/count".to_string(),
+                    "std_msgs/msg/UInt32".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            // Two messages per file, files 0 to 9
+            for i in 0..20u32 {
+                let mut data = vec![0x00, 0x01, 0x00, 0x00];
+                data.extend_from_slice(&i.to_le_bytes());
+                writer
+                    .write(&connection, u64::from(i) * 500, &data)
+                    .unwrap();
+            }
+            writer.close().unwrap();
+            bag_path
+        };
+
+        // Files whose last message is more than 2 µs older than the newest are deleted
+        let bag_path = write(
+            "by_duration",
+            RetentionPolicy {
+                max_duration: Some(std::time::Duration::from_nanos(2_000)),
+                max_size: None,
+            },
+        );
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        let expected: Vec<String> = (6..10).map(|i| format!("by_duration_{i}.db3")).collect();
+        assert_eq!(metadata.info().relative_file_paths, expected);
+        assert!(!bag_path.join("by_duration_5.db3").exists());
+        assert_eq!(metadata.message_count(), 8);
+        assert_eq!(metadata.start_time(), 6_000);
+        assert_eq!(metadata.end_time(), 9_500);
+
+        let mut reader = crate::Reader::new(&bag_path).unwrap();
+        reader.open().unwrap();
+        assert_eq!(reader.connections()[0].message_count, 8);
+        let timestamps: Vec<u64> = reader
+            .raw_messages()
+            .unwrap()
+            .map(|m| m.unwrap().timestamp)
+            .collect();
+        assert_eq!(timestamps, (12..20).map(|i| i * 500).collect::<Vec<_>>());
+
+        // Finished files are kept within the size of two of them
+        let file_size = std::fs::metadata(bag_path.join("by_duration_6.db3"))
+            .unwrap()
+            .len();
+        let bag_path = write(
+            "by_size",
+            RetentionPolicy {
+                max_duration: None,
+                max_size: Some(2 * file_size),
+            },
+        );
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        let expected: Vec<String> = (7..10).map(|i| format!("by_size_{i}.db3")).collect();
+        assert_eq!(metadata.info().relative_file_paths, expected);
+
+        assert!(Writer::builder(temp_dir.path().join("unsplit"))
+            .retention(RetentionPolicy {
+                max_duration: Some(std::time::Duration::from_secs(60)),
+                max_size: None,
+            })
+            .build()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_background_flush_writes_all_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        for storage in [StoragePlugin::Sqlite3, StoragePlugin::Mcap] {
+            let bag_path = temp_dir.path().join(storage.as_str());
+            // Batches of 8 messages through a queue of one, in files of 100 messages
+            let mut writer = Writer::builder(&bag_path)
+                .storage(storage)
+                .buffer(1, 8)
+                .background_flush(1)
+                .max_bagfile_duration(std::time::Duration::from_nanos(100))
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/count".to_string(),
+                    "std_msgs/msg/UInt32".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            for i in 0..1000u32 {
+                let mut data = vec![0x00, 0x01, 0x00, 0x00];
+                data.extend_from_slice(&i.to_le_bytes());
+                writer.write(&connection, u64::from(i), &data).unwrap();
+            }
+            writer.close().unwrap();
+
+            let mut reader = crate::Reader::new(&bag_path).unwrap();
+            reader.open().unwrap();
+            assert_eq!(
+                reader.metadata().unwrap().info().relative_file_paths.len(),
+                10
+            );
+            let values: Vec<u32> = reader
+                .raw_messages()
+                .unwrap()
+                .map(|m| u32::from_le_bytes(m.unwrap().raw_data[4..8].try_into().unwrap()))
+                .collect();
+            assert_eq!(values, (0..1000).collect::<Vec<_>>());
+        }
+
+        assert!(Writer::builder(temp_dir.path().join("no_queue"))
+            .background_flush(0)
+            .build()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_checksums_recorded_and_verified() {
+        use crate::metadata::ChecksumMismatch;
+
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("checksummed");
+        let mut writer = Writer::builder(&bag_path)
+            .storage(StoragePlugin::Mcap)
+            .max_bagfile_duration(std::time::Duration::from_nanos(1_000))
+            .checksums(true)
+            .open()
+            .unwrap();
+        let connection = writer
+            .add_connection(
+                "/count".to_string(),
+                "std_msgs/msg/UInt32".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        for i in 0..4u32 {
+            let mut data = vec![0x00, 0x01, 0x00, 0x00];
+            data.extend_from_slice(&i.to_le_bytes());
+            writer
+                .write(&connection, u64::from(i) * 500, &data)
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        let files = metadata.info().relative_file_paths.clone();
+        assert_eq!(files, ["checksummed_0.mcap", "checksummed_1.mcap"]);
+        let (_, sha256) =
+            crate::metadata::sha256_file(bag_path.join("checksummed_1.mcap")).unwrap();
+        assert_eq!(
+            metadata.checksum("checksummed_1.mcap"),
+            Some(sha256.as_str())
+        );
+
+        let reader = crate::Reader::new(&bag_path).unwrap();
+        assert!(reader.verify_checksums().unwrap().is_empty());
+
+        // A changed file is reported with both checksums, a deleted one as missing
+        let mut data = std::fs::read(bag_path.join("checksummed_1.mcap")).unwrap();
+        data.push(0);
+        std::fs::write(bag_path.join("checksummed_1.mcap"), data).unwrap();
+        std::fs::remove_file(bag_path.join("checksummed_0.mcap")).unwrap();
+        let mismatches = reader.verify_checksums().unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[0],
+            ChecksumMismatch::Missing {
+                path: "checksummed_0.mcap".to_string()
+            }
+        );
+        assert!(matches!(
+            &mismatches[1],
+            ChecksumMismatch::Checksum { expected, actual, .. }
+                if *expected == sha256 && *actual != sha256
+        ));
+
+        // Bags without checksums cannot be verified, old versions cannot record them
+        let plain_path = temp_dir.path().join("plain");
+        let mut writer = Writer::builder(&plain_path).open().unwrap();
+        writer.close().unwrap();
+        assert!(crate::Reader::new(&plain_path)
+            .unwrap()
+            .verify_checksums()
+            .is_err());
+        assert!(Writer::builder(temp_dir.path().join("v5"))
+            .version(5)
+            .checksums(true)
+            .build()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_split_policy_rotates_storage_files() {
+        let temp_dir = TempDir::new().unwrap();
+        for (storage, extension) in [
+            (StoragePlugin::Sqlite3, "db3"),
+            (StoragePlugin::Mcap, "mcap"),
+        ] {
+            for (name, builder) in [
+                (
+                    "by_duration",
+                    Writer::builder(temp_dir.path().join(format!("{extension}_by_duration")))
+                        .max_bagfile_duration(std::time::Duration::from_secs(3)),
+                ),
+                (
+                    "by_size",
+                    Writer::builder(temp_dir.path().join(format!("{extension}_by_size")))
+                        .max_bagfile_size(24),
+                ),
+            ] {
+                let bag_path = temp_dir.path().join(format!("{extension}_{name}"));
+                let mut writer = builder.storage(storage).buffer(10, 4).open().unwrap();
+                let connection = writer
+                    .add_connection(
+                        "/count".to_string(),
+                        "std_msgs/msg/UInt32".to_string(),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .unwrap();
+                for i in 0..10u32 {
+                    let mut data = vec![0x00, 0x01, 0x00, 0x00];
+                    data.extend_from_slice(&i.to_le_bytes());
+                    writer
+                        .write(&connection, u64::from(i) * 1_000_000_000, &data)
+                        .unwrap();
+                }
+                writer.close().unwrap();
+
+                // 3 messages of 8 bytes or 3 s per file
+                let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+                let info = metadata.info();
+                let stem = bag_path.file_name().unwrap().to_string_lossy();
+                let expected: Vec<String> = (0..4)
+                    .map(|index| format!("{stem}_{index}.{extension}"))
+                    .collect();
+                assert_eq!(info.relative_file_paths, expected, "{extension} {name}");
+                let counts: Vec<u64> = info.files.iter().map(|f| f.message_count).collect();
+                assert_eq!(counts, [3, 3, 3, 1], "{extension} {name}");
+                assert_eq!(
+                    info.files[1].starting_time.nanoseconds_since_epoch,
+                    3_000_000_000
+                );
+                assert_eq!(info.files[1].duration.nanoseconds, 2_000_000_000);
+                assert_eq!(info.message_count, 10);
+                assert_eq!(metadata.duration(), 9_000_000_000);
+
+                let mut reader = crate::Reader::new(&bag_path).unwrap();
+                reader.open().unwrap();
+                assert_eq!(reader.connections().len(), 1, "{extension} {name}");
+                assert_eq!(reader.connections()[0].message_count, 10);
+                let values: Vec<u32> = reader
+                    .raw_messages()
+                    .unwrap()
+                    .map(|m| u32::from_le_bytes(m.unwrap().raw_data[4..].try_into().unwrap()))
+                    .collect();
+                assert_eq!(values, (0..10).collect::<Vec<_>>(), "{extension} {name}");
+            }
+        }
+    }
+
+    #[cfg(feature = "mcap")]
+    #[test]
+    fn test_mcap_chunk_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut chunks = Vec::new();
+        for chunk_size in [McapWriteOptions::DEFAULT_CHUNK_SIZE, 1024] {
+            let bag_path = temp_dir.path().join(format!("chunks_{chunk_size}"));
+            let mut writer = Writer::builder(&bag_path)
+                .storage(StoragePlugin::Mcap)
+                .mcap_chunk_size(chunk_size)
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/data".to_string(),
+                    "std_msgs/msg/UInt8MultiArray".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            for i in 0..64u64 {
+                writer.write(&connection, i, &[0; 256]).unwrap();
+            }
+            writer.close().unwrap();
+
+            let file = std::fs::read(bag_path.join(format!("chunks_{chunk_size}.mcap"))).unwrap();
+            let summary = mcap::Summary::read(&file).unwrap().unwrap();
+            chunks.push(summary.chunk_indexes.len());
+        }
+        assert_eq!(chunks[0], 1);
+        assert!(chunks[1] >= 16, "{chunks:?}");
+
+        assert!(Writer::builder(temp_dir.path().join("zero_chunks"))
+            .storage(StoragePlugin::Mcap)
+            .mcap_chunk_size(0)
+            .open()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "sqlite", feature = "mcap"))]
+    #[test]
+    fn test_out_of_order_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, storage, policy, timestamps: &[u64]| {
+            let bag_path = temp_dir.path().join(name);
+            let mut writer = Writer::builder(&bag_path)
+                .storage(storage)
+                .out_of_order(policy)
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/count".to_string(),
+                    "std_msgs/msg/UInt32".to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap();
+            let results: Vec<bool> = timestamps
+                .iter()
+                .map(|&timestamp| {
+                    let mut data = vec![0x00, 0x01, 0x00, 0x00];
+                    data.extend_from_slice(&(timestamp as u32).to_le_bytes());
+                    writer.write(&connection, timestamp, &data).is_ok()
+                })
+                .collect();
+            writer.close().unwrap();
+            (bag_path, results)
+        };
+
+        // Accepted messages keep their order, the time range and chunk index cover them
+        let (bag_path, _) = write(
+            "accept",
+            StoragePlugin::Mcap,
+            OutOfOrderPolicy::Accept,
+            &[50, 30, 80, 10],
+        );
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        assert_eq!((metadata.start_time(), metadata.end_time()), (10, 80));
+        let file = std::fs::read(bag_path.join("accept.mcap")).unwrap();
+        let summary = mcap::Summary::read(&file).unwrap().unwrap();
+        let chunk_times: Vec<(u64, u64)> = summary
+            .chunk_indexes
+            .iter()
+            .map(|chunk| (chunk.message_start_time, chunk.message_end_time))
+            .collect();
+        assert_eq!(chunk_times, [(10, 80)]);
+        let mut reader = crate::Reader::new(&bag_path).unwrap();
+        reader.open().unwrap();
+        let read = |reader: &crate::Reader, start, stop| -> Vec<u64> {
+            reader
+                .raw_messages_filtered(None, start, stop)
+                .unwrap()
+                .map(|m| m.unwrap().timestamp)
+                .collect()
+        };
+        assert_eq!(read(&reader, None, None), [10, 30, 50, 80]);
+        assert_eq!(read(&reader, Some(5), Some(40)), [10, 30]);
+
+        let stored = |bag_path: &Path| -> Vec<u64> {
+            let name = bag_path.file_name().unwrap().to_string_lossy();
+            let db = rusqlite::Connection::open(bag_path.join(format!("{name}.db3"))).unwrap();
+            let mut stmt = db
+                .prepare("SELECT timestamp FROM messages ORDER BY id")
+                .unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.map(|row| row.unwrap()).collect()
+        };
+
+        let (bag_path, results) = write(
+            "reject",
+            StoragePlugin::Sqlite3,
+            OutOfOrderPolicy::Reject,
+            &[50, 30, 50, 80],
+        );
+        assert_eq!(results, [true, false, true, true]);
+        assert_eq!(stored(&bag_path), [50, 50, 80]);
+
+        // 95 waits for 100, 80 comes after 105 was written and cannot be put in order
+        let (bag_path, results) = write(
+            "reorder",
+            StoragePlugin::Sqlite3,
+            OutOfOrderPolicy::Reorder(std::time::Duration::from_nanos(10)),
+            &[100, 95, 105, 120, 80, 118],
+        );
+        assert!(results.iter().all(|&ok| ok));
+        assert_eq!(stored(&bag_path), [95, 100, 105, 80, 118, 120]);
+        let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+        assert_eq!((metadata.start_time(), metadata.end_time()), (80, 120));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_resilient_options_write_ahead() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("test_bag");
+
+        let mut writer = Writer::builder(&bag_path)
+            .sqlite_options(SqliteWriteOptions::resilient())
+            .open()
+            .unwrap();
+        let connection = writer
+            .add_connection(
+                "/imu".to_string(),
+                "std_msgs/msg/UInt32".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        // One full transaction and a partial one committed on close
+        for i in 0..1500u32 {
+            let mut data = vec![0x00, 0x01, 0x00, 0x00];
+            data.extend_from_slice(&i.to_le_bytes());
+            writer.write(&connection, u64::from(i), &data).unwrap();
+        }
+        writer.close().unwrap();
+
+        // Closing checkpoints the log into the database
+        assert!(!bag_path.join("test_bag.db3-wal").exists());
+        let db = rusqlite::Connection::open(bag_path.join("test_bag.db3")).unwrap();
+        let journal_mode: String = db
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let count: u32 = db
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1500);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_schema_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let tables = |db: &rusqlite::Connection| -> Vec<String> {
+            let mut statement = db
+                .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+                .unwrap();
+            let names = statement.query_map([], |row| row.get(0)).unwrap();
+            names.collect::<rusqlite::Result<_>>().unwrap()
+        };
+
+        for schema_version in [2, 3] {
+            let name = format!("schema_v{schema_version}");
+            let bag_path = temp_dir.path().join(&name);
+            let mut writer = Writer::builder(&bag_path)
+                .sqlite_schema_version(schema_version)
+                .open()
+                .unwrap();
+            let connection = writer
+                .add_connection(
+                    "/count".to_string(),
+                    "std_msgs/msg/UInt32".to_string(),
+                    None,
+                    Some("RIHS01_abc".to_string()),
+                    None,
+                    None,
+                )
+                .unwrap();
+            writer
+                .write(&connection, 1, &[0, 1, 0, 0, 7, 0, 0, 0])
+                .unwrap();
+            writer.close().unwrap();
+
+            // Older schemas are written with the metadata version their releases read
+            let metadata = BagMetadata::from_file(bag_path.join("metadata.yaml")).unwrap();
+            assert_eq!(metadata.info().version, 5);
+
+            let db = rusqlite::Connection::open(bag_path.join(format!("{name}.db3"))).unwrap();
+            let mut statement = db.prepare("PRAGMA table_info(topics)").unwrap();
+            let columns: Vec<String> = statement
+                .query_map([], |row| row.get(1))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            assert_eq!(
+                columns,
+                [
+                    "id",
+                    "name",
+                    "type",
+                    "serialization_format",
+                    "offered_qos_profiles"
+                ]
+            );
+            if schema_version == 3 {
+                assert_eq!(tables(&db), ["messages", "metadata", "schema", "topics"]);
+                let stored: u32 = db
+                    .query_row("SELECT schema_version FROM schema", [], |row| row.get(0))
+                    .unwrap();
+                assert_eq!(stored, 3);
+            } else {
+                assert_eq!(tables(&db), ["messages", "topics"]);
+            }
+        }
+
+        // The bag format version has to be one the older releases read
+        let mut writer = Writer::builder(temp_dir.path().join("mismatch"))
+            .sqlite_schema_version(3)
+            .version(9)
+            .build()
+            .unwrap();
+        assert!(matches!(writer.open(), Err(BagError::Writer { .. })));
+        let mut writer = Writer::builder(temp_dir.path().join("v1"))
+            .sqlite_schema_version(1)
+            .build()
+            .unwrap();
+        assert!(matches!(writer.open(), Err(BagError::Writer { .. })));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_options_coalesce_transactions() {
+        let temp_dir = TempDir::new().unwrap();
+        let bag_path = temp_dir.path().join("test_bag");
+
+        let mut writer = Writer::new(&bag_path, None, None).unwrap();
+        writer
+            .set_sqlite_options(SqliteWriteOptions {
+                messages_per_transaction: 250,
+                begin_concurrent: true,
+                ..SqliteWriteOptions::high_rate()
+            })
+            .unwrap();
+        writer.configure_buffer(10, 64).unwrap();
+        writer.open().unwrap();
+        assert!(matches!(
+            writer.set_sqlite_options(SqliteWriteOptions::default()),
+            Err(BagError::BagAlreadyOpen)
+        ));
+        let connection = writer
+            .add_connection(
+                "/imu".to_string(),
+                "std_msgs/msg/UInt32".to_string(),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        for i in 0..1000u32 {
+            let mut data = vec![0x00, 0x01, 0x00, 0x00];
+            data.extend_from_slice(&i.to_le_bytes());
+            writer.write(&connection, u64::from(i), &data).unwrap();
+        }
+        writer.close().unwrap();
+
+        let db = rusqlite::Connection::open(bag_path.join("test_bag.db3")).unwrap();
+        let page_size: u32 = db
+            .query_row("PRAGMA page_size", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(page_size, 65536);
+        let (count, last): (u32, Vec<u8>) = db
+            .query_row(
+                "SELECT COUNT(*), (SELECT data FROM messages ORDER BY id DESC LIMIT 1) FROM messages",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(count, 1000);
+        assert_eq!(last[4..], 999u32.to_le_bytes());
+
+        let mut writer = Writer::new(temp_dir.path().join("bad_page_size"), None, None).unwrap();
+        writer
+            .set_sqlite_options(SqliteWriteOptions {
+                page_size: Some(1000),
+                ..SqliteWriteOptions::default()
+            })
+            .unwrap();
+        assert!(matches!(writer.open(), Err(BagError::Writer { .. })));
+    }
+
     /// Test writing all supported message types to a bag file
     #[test]
     fn test_write_all_supported_topics() {
@@ -0,0 +1,953 @@
+// Generated by rosbags-rs 0.3.4 from recorded message definitions. Do not edit.
+
+#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]
+pub mod builtin_interfaces {
+    /// `builtin_interfaces/msg/Time`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Time {
+        pub sec: i32,
+        pub nanosec: u32,
+    }
+
+    impl Time {
+        pub const MESSAGE_TYPE: &'static str = "builtin_interfaces/msg/Time";
+    }
+
+    impl rosbags_rs::messages::MessageType for Time {
+        const MESSAGE_TYPE: &'static str = "builtin_interfaces/msg/Time";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Time {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                sec: d.read_i32()?,
+                nanosec: d.read_u32()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Time {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_i32(self.sec);
+            s.write_u32(self.nanosec);
+        }
+    }
+}
+
+#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]
+pub mod geometry_msgs {
+    /// `geometry_msgs/msg/Point`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+    }
+
+    impl Point {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Point";
+    }
+
+    impl rosbags_rs::messages::MessageType for Point {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Point";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Point {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                x: d.read_f64()?,
+                y: d.read_f64()?,
+                z: d.read_f64()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Point {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_f64(self.x);
+            s.write_f64(self.y);
+            s.write_f64(self.z);
+        }
+    }
+
+    /// `geometry_msgs/msg/Pose`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Pose {
+        pub position: super::geometry_msgs::Point,
+        pub orientation: super::geometry_msgs::Quaternion,
+    }
+
+    impl Pose {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Pose";
+    }
+
+    impl rosbags_rs::messages::MessageType for Pose {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Pose";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Pose {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                position: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                orientation: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Pose {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.position, s);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.orientation, s);
+        }
+    }
+
+    /// `geometry_msgs/msg/PoseWithCovariance`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PoseWithCovariance {
+        pub pose: super::geometry_msgs::Pose,
+        pub covariance: [f64; 36],
+    }
+
+    impl PoseWithCovariance {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/PoseWithCovariance";
+    }
+
+    impl rosbags_rs::messages::MessageType for PoseWithCovariance {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/PoseWithCovariance";
+    }
+
+    impl rosbags_rs::messages::FromCdr for PoseWithCovariance {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                pose: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                covariance: d.read_array(|d| d.read_f64())?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for PoseWithCovariance {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.pose, s);
+            for item in &self.covariance {
+                s.write_f64(*item);
+            }
+        }
+    }
+
+    /// `geometry_msgs/msg/PoseWithCovarianceStamped`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PoseWithCovarianceStamped {
+        pub header: super::std_msgs::Header,
+        pub pose: super::geometry_msgs::PoseWithCovariance,
+    }
+
+    impl PoseWithCovarianceStamped {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/PoseWithCovarianceStamped";
+    }
+
+    impl rosbags_rs::messages::MessageType for PoseWithCovarianceStamped {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/PoseWithCovarianceStamped";
+    }
+
+    impl rosbags_rs::messages::FromCdr for PoseWithCovarianceStamped {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                header: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                pose: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for PoseWithCovarianceStamped {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.header, s);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.pose, s);
+        }
+    }
+
+    /// `geometry_msgs/msg/Quaternion`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Quaternion {
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+        pub w: f64,
+    }
+
+    impl Quaternion {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Quaternion";
+    }
+
+    impl rosbags_rs::messages::MessageType for Quaternion {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Quaternion";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Quaternion {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                x: d.read_f64()?,
+                y: d.read_f64()?,
+                z: d.read_f64()?,
+                w: d.read_f64()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Quaternion {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_f64(self.x);
+            s.write_f64(self.y);
+            s.write_f64(self.z);
+            s.write_f64(self.w);
+        }
+    }
+
+    /// `geometry_msgs/msg/Transform`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Transform {
+        pub translation: super::geometry_msgs::Vector3,
+        pub rotation: super::geometry_msgs::Quaternion,
+    }
+
+    impl Transform {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Transform";
+    }
+
+    impl rosbags_rs::messages::MessageType for Transform {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Transform";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Transform {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                translation: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                rotation: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Transform {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.translation, s);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.rotation, s);
+        }
+    }
+
+    /// `geometry_msgs/msg/TransformStamped`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TransformStamped {
+        pub header: super::std_msgs::Header,
+        pub child_frame_id: ::std::string::String,
+        pub transform: super::geometry_msgs::Transform,
+    }
+
+    impl TransformStamped {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/TransformStamped";
+    }
+
+    impl rosbags_rs::messages::MessageType for TransformStamped {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/TransformStamped";
+    }
+
+    impl rosbags_rs::messages::FromCdr for TransformStamped {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                header: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                child_frame_id: d.read_string()?,
+                transform: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for TransformStamped {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.header, s);
+            s.write_string(&self.child_frame_id);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.transform, s);
+        }
+    }
+
+    /// `geometry_msgs/msg/Vector3`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Vector3 {
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+    }
+
+    impl Vector3 {
+        pub const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Vector3";
+    }
+
+    impl rosbags_rs::messages::MessageType for Vector3 {
+        const MESSAGE_TYPE: &'static str = "geometry_msgs/msg/Vector3";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Vector3 {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                x: d.read_f64()?,
+                y: d.read_f64()?,
+                z: d.read_f64()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Vector3 {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_f64(self.x);
+            s.write_f64(self.y);
+            s.write_f64(self.z);
+        }
+    }
+}
+
+#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]
+pub mod sensor_msgs {
+    /// `sensor_msgs/msg/CameraInfo`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct CameraInfo {
+        pub header: super::std_msgs::Header,
+        pub height: u32,
+        pub width: u32,
+        pub distortion_model: ::std::string::String,
+        pub d: ::std::vec::Vec<f64>,
+        pub k: [f64; 9],
+        pub r: [f64; 9],
+        pub p: [f64; 12],
+        pub binning_x: u32,
+        pub binning_y: u32,
+        pub roi: super::sensor_msgs::RegionOfInterest,
+    }
+
+    impl CameraInfo {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/CameraInfo";
+    }
+
+    impl rosbags_rs::messages::MessageType for CameraInfo {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/CameraInfo";
+    }
+
+    impl rosbags_rs::messages::FromCdr for CameraInfo {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                header: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                height: d.read_u32()?,
+                width: d.read_u32()?,
+                distortion_model: d.read_string()?,
+                d: d.read_sequence(|d| d.read_f64())?,
+                k: d.read_array(|d| d.read_f64())?,
+                r: d.read_array(|d| d.read_f64())?,
+                p: d.read_array(|d| d.read_f64())?,
+                binning_x: d.read_u32()?,
+                binning_y: d.read_u32()?,
+                roi: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for CameraInfo {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.header, s);
+            s.write_u32(self.height);
+            s.write_u32(self.width);
+            s.write_string(&self.distortion_model);
+            s.write_sequence_length(self.d.len());
+            for item in &self.d {
+                s.write_f64(*item);
+            }
+            for item in &self.k {
+                s.write_f64(*item);
+            }
+            for item in &self.r {
+                s.write_f64(*item);
+            }
+            for item in &self.p {
+                s.write_f64(*item);
+            }
+            s.write_u32(self.binning_x);
+            s.write_u32(self.binning_y);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.roi, s);
+        }
+    }
+
+    /// `sensor_msgs/msg/JoyFeedback`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct JoyFeedback {
+        pub r#type: u8,
+        pub id: u8,
+        pub intensity: f32,
+    }
+
+    impl JoyFeedback {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/JoyFeedback";
+        pub const TYPE_LED: u8 = 0;
+        pub const TYPE_RUMBLE: u8 = 1;
+        pub const TYPE_BUZZER: u8 = 2;
+    }
+
+    impl rosbags_rs::messages::MessageType for JoyFeedback {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/JoyFeedback";
+    }
+
+    impl rosbags_rs::messages::FromCdr for JoyFeedback {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                r#type: d.read_u8()?,
+                id: d.read_u8()?,
+                intensity: d.read_f32()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for JoyFeedback {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_u8(self.r#type);
+            s.write_u8(self.id);
+            s.write_f32(self.intensity);
+        }
+    }
+
+    /// `sensor_msgs/msg/JoyFeedbackArray`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct JoyFeedbackArray {
+        pub array: ::std::vec::Vec<super::sensor_msgs::JoyFeedback>,
+    }
+
+    impl JoyFeedbackArray {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/JoyFeedbackArray";
+    }
+
+    impl rosbags_rs::messages::MessageType for JoyFeedbackArray {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/JoyFeedbackArray";
+    }
+
+    impl rosbags_rs::messages::FromCdr for JoyFeedbackArray {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                array: d.read_sequence(|d| rosbags_rs::messages::FromCdr::from_cdr(d))?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for JoyFeedbackArray {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_sequence_length(self.array.len());
+            for item in &self.array {
+                rosbags_rs::messages::ToCdr::to_cdr(item, s);
+            }
+        }
+    }
+
+    /// `sensor_msgs/msg/NavSatFix`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct NavSatFix {
+        pub header: super::std_msgs::Header,
+        pub status: super::sensor_msgs::NavSatStatus,
+        pub latitude: f64,
+        pub longitude: f64,
+        pub altitude: f64,
+        pub position_covariance: [f64; 9],
+        pub position_covariance_type: u8,
+    }
+
+    impl NavSatFix {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/NavSatFix";
+        pub const COVARIANCE_TYPE_UNKNOWN: u8 = 0;
+        pub const COVARIANCE_TYPE_APPROXIMATED: u8 = 1;
+        pub const COVARIANCE_TYPE_DIAGONAL_KNOWN: u8 = 2;
+        pub const COVARIANCE_TYPE_KNOWN: u8 = 3;
+    }
+
+    impl rosbags_rs::messages::MessageType for NavSatFix {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/NavSatFix";
+    }
+
+    impl rosbags_rs::messages::FromCdr for NavSatFix {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                header: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                status: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                latitude: d.read_f64()?,
+                longitude: d.read_f64()?,
+                altitude: d.read_f64()?,
+                position_covariance: d.read_array(|d| d.read_f64())?,
+                position_covariance_type: d.read_u8()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for NavSatFix {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.header, s);
+            rosbags_rs::messages::ToCdr::to_cdr(&self.status, s);
+            s.write_f64(self.latitude);
+            s.write_f64(self.longitude);
+            s.write_f64(self.altitude);
+            for item in &self.position_covariance {
+                s.write_f64(*item);
+            }
+            s.write_u8(self.position_covariance_type);
+        }
+    }
+
+    /// `sensor_msgs/msg/NavSatStatus`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct NavSatStatus {
+        pub status: i8,
+        pub service: u16,
+    }
+
+    impl NavSatStatus {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/NavSatStatus";
+        pub const STATUS_NO_FIX: i8 = -1;
+        pub const STATUS_FIX: i8 = 0;
+        pub const STATUS_SBAS_FIX: i8 = 1;
+        pub const STATUS_GBAS_FIX: i8 = 2;
+        pub const SERVICE_GPS: u16 = 1;
+        pub const SERVICE_GLONASS: u16 = 2;
+        pub const SERVICE_COMPASS: u16 = 4;
+        pub const SERVICE_GALILEO: u16 = 8;
+    }
+
+    impl rosbags_rs::messages::MessageType for NavSatStatus {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/NavSatStatus";
+    }
+
+    impl rosbags_rs::messages::FromCdr for NavSatStatus {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                status: d.read_i8()?,
+                service: d.read_u16()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for NavSatStatus {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_i8(self.status);
+            s.write_u16(self.service);
+        }
+    }
+
+    /// `sensor_msgs/msg/PointCloud2`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PointCloud2 {
+        pub header: super::std_msgs::Header,
+        pub height: u32,
+        pub width: u32,
+        pub fields: ::std::vec::Vec<super::sensor_msgs::PointField>,
+        pub is_bigendian: bool,
+        pub point_step: u32,
+        pub row_step: u32,
+        pub data: ::std::vec::Vec<u8>,
+        pub is_dense: bool,
+    }
+
+    impl PointCloud2 {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/PointCloud2";
+    }
+
+    impl rosbags_rs::messages::MessageType for PointCloud2 {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/PointCloud2";
+    }
+
+    impl rosbags_rs::messages::FromCdr for PointCloud2 {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                header: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                height: d.read_u32()?,
+                width: d.read_u32()?,
+                fields: d.read_sequence(|d| rosbags_rs::messages::FromCdr::from_cdr(d))?,
+                is_bigendian: d.read_bool()?,
+                point_step: d.read_u32()?,
+                row_step: d.read_u32()?,
+                data: d.read_byte_sequence()?,
+                is_dense: d.read_bool()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for PointCloud2 {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.header, s);
+            s.write_u32(self.height);
+            s.write_u32(self.width);
+            s.write_sequence_length(self.fields.len());
+            for item in &self.fields {
+                rosbags_rs::messages::ToCdr::to_cdr(item, s);
+            }
+            s.write_bool(self.is_bigendian);
+            s.write_u32(self.point_step);
+            s.write_u32(self.row_step);
+            s.write_byte_sequence(&self.data);
+            s.write_bool(self.is_dense);
+        }
+    }
+
+    /// `sensor_msgs/msg/PointField`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PointField {
+        pub name: ::std::string::String,
+        pub offset: u32,
+        pub datatype: u8,
+        pub count: u32,
+    }
+
+    impl PointField {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/PointField";
+        pub const INT8: u8 = 1;
+        pub const UINT8: u8 = 2;
+        pub const INT16: u8 = 3;
+        pub const UINT16: u8 = 4;
+        pub const INT32: u8 = 5;
+        pub const UINT32: u8 = 6;
+        pub const FLOAT32: u8 = 7;
+        pub const FLOAT64: u8 = 8;
+    }
+
+    impl rosbags_rs::messages::MessageType for PointField {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/PointField";
+    }
+
+    impl rosbags_rs::messages::FromCdr for PointField {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                name: d.read_string()?,
+                offset: d.read_u32()?,
+                datatype: d.read_u8()?,
+                count: d.read_u32()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for PointField {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_string(&self.name);
+            s.write_u32(self.offset);
+            s.write_u8(self.datatype);
+            s.write_u32(self.count);
+        }
+    }
+
+    /// `sensor_msgs/msg/RegionOfInterest`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RegionOfInterest {
+        pub x_offset: u32,
+        pub y_offset: u32,
+        pub height: u32,
+        pub width: u32,
+        pub do_rectify: bool,
+    }
+
+    impl RegionOfInterest {
+        pub const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/RegionOfInterest";
+    }
+
+    impl rosbags_rs::messages::MessageType for RegionOfInterest {
+        const MESSAGE_TYPE: &'static str = "sensor_msgs/msg/RegionOfInterest";
+    }
+
+    impl rosbags_rs::messages::FromCdr for RegionOfInterest {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                x_offset: d.read_u32()?,
+                y_offset: d.read_u32()?,
+                height: d.read_u32()?,
+                width: d.read_u32()?,
+                do_rectify: d.read_bool()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for RegionOfInterest {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_u32(self.x_offset);
+            s.write_u32(self.y_offset);
+            s.write_u32(self.height);
+            s.write_u32(self.width);
+            s.write_bool(self.do_rectify);
+        }
+    }
+}
+
+#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]
+pub mod std_msgs {
+    /// `std_msgs/msg/Bool`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Bool {
+        pub data: bool,
+    }
+
+    impl Bool {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/Bool";
+    }
+
+    impl rosbags_rs::messages::MessageType for Bool {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/Bool";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Bool {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                data: d.read_bool()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Bool {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_bool(self.data);
+        }
+    }
+
+    /// `std_msgs/msg/Char`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Char {
+        pub data: u8,
+    }
+
+    impl Char {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/Char";
+    }
+
+    impl rosbags_rs::messages::MessageType for Char {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/Char";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Char {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                data: d.read_u8()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Char {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_u8(self.data);
+        }
+    }
+
+    /// `std_msgs/msg/Empty`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Empty {
+    }
+
+    impl Empty {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/Empty";
+    }
+
+    impl rosbags_rs::messages::MessageType for Empty {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/Empty";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Empty {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            d.read_u8()?;
+            Ok(Self {
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Empty {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_u8(0);
+        }
+    }
+
+    /// `std_msgs/msg/Float64MultiArray`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Float64MultiArray {
+        pub layout: super::std_msgs::MultiArrayLayout,
+        pub data: ::std::vec::Vec<f64>,
+    }
+
+    impl Float64MultiArray {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/Float64MultiArray";
+    }
+
+    impl rosbags_rs::messages::MessageType for Float64MultiArray {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/Float64MultiArray";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Float64MultiArray {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                layout: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                data: d.read_sequence(|d| d.read_f64())?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Float64MultiArray {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.layout, s);
+            s.write_sequence_length(self.data.len());
+            for item in &self.data {
+                s.write_f64(*item);
+            }
+        }
+    }
+
+    /// `std_msgs/msg/Header`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Header {
+        pub stamp: super::builtin_interfaces::Time,
+        pub frame_id: ::std::string::String,
+    }
+
+    impl Header {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/Header";
+    }
+
+    impl rosbags_rs::messages::MessageType for Header {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/Header";
+    }
+
+    impl rosbags_rs::messages::FromCdr for Header {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                stamp: rosbags_rs::messages::FromCdr::from_cdr(d)?,
+                frame_id: d.read_string()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for Header {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            rosbags_rs::messages::ToCdr::to_cdr(&self.stamp, s);
+            s.write_string(&self.frame_id);
+        }
+    }
+
+    /// `std_msgs/msg/MultiArrayDimension`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MultiArrayDimension {
+        pub label: ::std::string::String,
+        pub size: u32,
+        pub stride: u32,
+    }
+
+    impl MultiArrayDimension {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/MultiArrayDimension";
+    }
+
+    impl rosbags_rs::messages::MessageType for MultiArrayDimension {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/MultiArrayDimension";
+    }
+
+    impl rosbags_rs::messages::FromCdr for MultiArrayDimension {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                label: d.read_string()?,
+                size: d.read_u32()?,
+                stride: d.read_u32()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for MultiArrayDimension {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_string(&self.label);
+            s.write_u32(self.size);
+            s.write_u32(self.stride);
+        }
+    }
+
+    /// `std_msgs/msg/MultiArrayLayout`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct MultiArrayLayout {
+        pub dim: ::std::vec::Vec<super::std_msgs::MultiArrayDimension>,
+        pub data_offset: u32,
+    }
+
+    impl MultiArrayLayout {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/MultiArrayLayout";
+    }
+
+    impl rosbags_rs::messages::MessageType for MultiArrayLayout {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/MultiArrayLayout";
+    }
+
+    impl rosbags_rs::messages::FromCdr for MultiArrayLayout {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                dim: d.read_sequence(|d| rosbags_rs::messages::FromCdr::from_cdr(d))?,
+                data_offset: d.read_u32()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for MultiArrayLayout {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_sequence_length(self.dim.len());
+            for item in &self.dim {
+                rosbags_rs::messages::ToCdr::to_cdr(item, s);
+            }
+            s.write_u32(self.data_offset);
+        }
+    }
+
+    /// `std_msgs/msg/String`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct String {
+        pub data: ::std::string::String,
+    }
+
+    impl String {
+        pub const MESSAGE_TYPE: &'static str = "std_msgs/msg/String";
+    }
+
+    impl rosbags_rs::messages::MessageType for String {
+        const MESSAGE_TYPE: &'static str = "std_msgs/msg/String";
+    }
+
+    impl rosbags_rs::messages::FromCdr for String {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                data: d.read_string()?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for String {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_string(&self.data);
+        }
+    }
+}
+
+#[allow(clippy::all, dead_code, non_camel_case_types, non_snake_case)]
+pub mod tf2_msgs {
+    /// `tf2_msgs/msg/TFMessage`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TFMessage {
+        pub transforms: ::std::vec::Vec<super::geometry_msgs::TransformStamped>,
+    }
+
+    impl TFMessage {
+        pub const MESSAGE_TYPE: &'static str = "tf2_msgs/msg/TFMessage";
+    }
+
+    impl rosbags_rs::messages::MessageType for TFMessage {
+        const MESSAGE_TYPE: &'static str = "tf2_msgs/msg/TFMessage";
+    }
+
+    impl rosbags_rs::messages::FromCdr for TFMessage {
+        fn from_cdr(d: &mut rosbags_rs::cdr::CdrDeserializer) -> rosbags_rs::Result<Self> {
+            Ok(Self {
+                transforms: d.read_sequence(|d| rosbags_rs::messages::FromCdr::from_cdr(d))?,
+            })
+        }
+    }
+
+    impl rosbags_rs::messages::ToCdr for TFMessage {
+        fn to_cdr(&self, s: &mut rosbags_rs::cdr::CdrSerializer) {
+            s.write_sequence_length(self.transforms.len());
+            for item in &self.transforms {
+                rosbags_rs::messages::ToCdr::to_cdr(item, s);
+            }
+        }
+    }
+}